@@ -0,0 +1,190 @@
+//! Startup latency benchmarks with a regression gate.
+//!
+//! Measures the work every `vortex run` does before a backend is involved:
+//! building a `VmSpec`, loading the config and validating the spec. With the
+//! `mock-backend` feature it also times `VmManager` creating and removing a
+//! VM on the in-memory backend:
+//!
+//! ```text
+//! cargo bench --bench startup
+//! cargo bench --bench startup --features mock-backend
+//! ```
+//!
+//! After measuring, each benchmark's mean is compared with its budget in
+//! `benches/thresholds.toml` and the run fails when any budget is exceeded.
+
+use criterion::{black_box, criterion_group, Criterion};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use vortex::{NetworkPolicy, ResourceLimits, TuningProfile, VmSpec, VortexConfig};
+
+const GROUP: &str = "startup";
+const THRESHOLDS: &str = include_str!("thresholds.toml");
+
+/// The spec `vortex run -m 2048 -c 2 -p 8080:80 -v .:/workspace --tuning build`
+/// builds
+fn run_spec() -> VmSpec {
+    let mut ports = HashMap::new();
+    ports.insert(8080, 80);
+    let mut volumes = HashMap::new();
+    volumes.insert(
+        PathBuf::from("/home/dev/project"),
+        PathBuf::from("/workspace"),
+    );
+    let mut environment = HashMap::new();
+    environment.insert("RUST_LOG".to_string(), "info".to_string());
+    let mut labels = HashMap::new();
+    labels.insert("vortex.run-id".to_string(), "1a2b3c4d5e6f".to_string());
+
+    VmSpec {
+        image: "ubuntu:22.04".to_string(),
+        memory: 2048,
+        cpus: 2,
+        ports,
+        volumes,
+        environment,
+        command: Some("cargo test".to_string()),
+        labels,
+        network_config: None,
+        resource_limits: ResourceLimits {
+            max_memory: Some(8192),
+            ..Default::default()
+        },
+        backend: None,
+        tuning: TuningProfile::builtin("build"),
+        hooks: Default::default(),
+        health_check: None,
+        ttl_seconds: None,
+        gpus: Vec::new(),
+        provision: None,
+        publish: Vec::new(),
+        tls_ports: HashMap::new(),
+        port_addresses: HashMap::new(),
+        share_mechanisms: HashMap::new(),
+        tmpfs: HashMap::new(),
+        network_policy: NetworkPolicy::Open,
+    }
+}
+
+fn core_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group(GROUP);
+
+    group.bench_function("spec_construct", |b| b.iter(|| black_box(run_spec())));
+
+    // The first load writes the default config under the temporary home
+    VortexConfig::load().expect("config load");
+    group.bench_function("config_load", |b| {
+        b.iter(|| black_box(VortexConfig::load().expect("config load")))
+    });
+
+    let spec = run_spec();
+    group.bench_function("spec_validate", |b| {
+        b.iter(|| black_box(&spec).validate().expect("valid spec"))
+    });
+
+    group.finish();
+}
+
+#[cfg(feature = "mock-backend")]
+fn vm_lifecycle(c: &mut Criterion) {
+    use vortex::mock::mock_manager;
+
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let (manager, _) = mock_manager();
+    let spec = run_spec();
+
+    let mut group = c.benchmark_group(GROUP);
+    group.bench_function("vm_create", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let vm = manager.create(spec.clone()).await.expect("create");
+            manager.cleanup(&vm.id).await.expect("cleanup");
+        })
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "mock-backend"))]
+fn vm_lifecycle(_c: &mut Criterion) {}
+
+criterion_group!(benches, core_path, vm_lifecycle);
+
+/// Where criterion writes its results, following its own lookup
+fn criterion_dir() -> PathBuf {
+    if let Some(home) = std::env::var_os("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"))
+        .join("criterion")
+}
+
+/// Mean of a benchmark measured since `since`, in microseconds
+fn measured_mean(dir: &Path, name: &str, since: SystemTime) -> Option<f64> {
+    let path = dir
+        .join(GROUP)
+        .join(name)
+        .join("new")
+        .join("estimates.json");
+    let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+    if modified < since {
+        // Left over from an earlier run, e.g. when this one was filtered
+        return None;
+    }
+    let estimates: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    Some(estimates["mean"]["point_estimate"].as_f64()? / 1000.0)
+}
+
+/// Compare this run's results with their budgets, returning the overruns
+fn check_budgets(since: SystemTime) -> Vec<String> {
+    let thresholds: toml::Table = THRESHOLDS.parse().expect("valid thresholds.toml");
+    let budgets = thresholds["budgets_us"]
+        .as_table()
+        .expect("[budgets_us] table");
+    let dir = criterion_dir();
+
+    let mut overruns = Vec::new();
+    for (name, budget) in budgets {
+        let budget = budget
+            .as_float()
+            .or_else(|| budget.as_integer().map(|b| b as f64))
+            .expect("numeric budget");
+        let Some(mean) = measured_mean(&dir, name, since) else {
+            continue;
+        };
+        let verdict = if mean > budget { "OVER BUDGET" } else { "ok" };
+        println!(
+            "{:<16} {:>10.2} µs (budget {} µs) {}",
+            name, mean, budget, verdict
+        );
+        if mean > budget {
+            overruns.push(format!("{}: {:.2} µs > {} µs", name, mean, budget));
+        }
+    }
+    overruns
+}
+
+fn main() {
+    // Keep config loads away from the real ~/.vortex
+    let home = tempfile::tempdir().expect("temporary home");
+    std::env::set_var("HOME", home.path());
+
+    let started = SystemTime::now();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+
+    // `cargo test --benches` runs each benchmark once without saving results
+    if std::env::args().any(|arg| arg == "--test" || arg == "--list") {
+        return;
+    }
+    let overruns = check_budgets(started);
+    if !overruns.is_empty() {
+        eprintln!("Startup latency regressed:");
+        for overrun in &overruns {
+            eprintln!("  {}", overrun);
+        }
+        std::process::exit(1);
+    }
+}
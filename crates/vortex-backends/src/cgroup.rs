@@ -0,0 +1,194 @@
+//! Host-side resource limits for VMM processes.
+//!
+//! Guest limits only bound what the guest kernel sees; a misbehaving VMM
+//! (runaway device emulation, a leaking virtiofsd) can still exhaust the
+//! host. On Linux with cgroup v2, each VM's host processes are moved into
+//! `<root>/<vm-id>` with `memory.max` and `cpu.max` derived from its spec.
+//! The root is `/sys/fs/cgroup/vortex` unless `VORTEX_CGROUP_ROOT` points at
+//! a delegated subtree, e.g. one granted by systemd to an unprivileged user.
+//!
+//! Enforcement is best effort: when the root cannot be created or written,
+//! VMs run unconfined and a debug message records why.
+//!
+//! The cgroup also gives exact accounting: `memory.current` covers every
+//! process of the VM and `cpu.stat` reports consumed CPU time, which backends
+//! prefer over per-process RSS estimates.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use vortex_core::error::Result;
+use vortex_core::vm::VmSpec;
+
+/// Environment variable overriding the cgroup root
+pub const ROOT_ENV: &str = "VORTEX_CGROUP_ROOT";
+const DEFAULT_ROOT: &str = "/sys/fs/cgroup/vortex";
+/// Host memory a VMM needs beyond guest RAM (device emulation, virtiofsd)
+const VMM_OVERHEAD_MIB: u64 = 128;
+const CPU_PERIOD_USEC: u64 = 100_000;
+const REMOVE_ATTEMPTS: u32 = 20;
+const REMOVE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Resource usage read from a VM's cgroup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CgroupUsage {
+    pub memory_bytes: u64,
+    pub cpu_usec: u64,
+}
+
+impl CgroupUsage {
+    /// Average CPU usage over `uptime_seconds`, where 100% is one host CPU
+    pub fn cpu_percent(&self, uptime_seconds: u64) -> f64 {
+        if uptime_seconds == 0 {
+            return 0.0;
+        }
+        self.cpu_usec as f64 / (uptime_seconds as f64 * 1e6) * 100.0
+    }
+}
+
+/// The cgroup holding one VM's host processes
+#[derive(Debug, Clone)]
+pub struct VmCgroup {
+    path: PathBuf,
+}
+
+impl VmCgroup {
+    /// Cgroup v2 root to create VM cgroups under, if one is usable
+    pub fn root() -> Option<PathBuf> {
+        if let Ok(root) = std::env::var(ROOT_ENV) {
+            if !root.is_empty() {
+                return Some(PathBuf::from(root));
+            }
+        }
+        let root = PathBuf::from(DEFAULT_ROOT);
+        root.parent()?
+            .join("cgroup.controllers")
+            .is_file()
+            .then_some(root)
+    }
+
+    /// The cgroup for `vm_id`, whether or not it exists
+    pub fn for_vm(vm_id: &str) -> Option<Self> {
+        Self::root().map(|root| Self::at(&root, vm_id))
+    }
+
+    pub fn at(root: &Path, vm_id: &str) -> Self {
+        Self {
+            path: root.join(vm_id),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create the cgroup with limits from `spec` and move `pids` into it.
+    /// Failures are logged and leave the processes unconfined.
+    pub fn confine(vm_id: &str, spec: &VmSpec, pids: &[u32]) -> Option<Self> {
+        let cgroup = Self::for_vm(vm_id)?;
+        match cgroup.apply(spec, pids) {
+            Ok(()) => Some(cgroup),
+            Err(e) => {
+                tracing::debug!(
+                    "Not confining {} in {}: {}",
+                    vm_id,
+                    cgroup.path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn apply(&self, spec: &VmSpec, pids: &[u32]) -> Result<()> {
+        let root = self.path.parent().unwrap_or(&self.path);
+        std::fs::create_dir_all(&self.path)?;
+        // Limits only take effect once the parent delegates the controllers
+        std::fs::write(root.join("cgroup.subtree_control"), "+memory +cpu")?;
+        self.set_limits(spec.memory, spec.cpus)?;
+
+        for pid in pids {
+            self.add(*pid)?;
+        }
+        Ok(())
+    }
+
+    /// Limit the cgroup to a VM of `memory` MB and `cpus` vCPUs, e.g. after
+    /// the VM was resized
+    pub fn set_limits(&self, memory: u32, cpus: u32) -> Result<()> {
+        let memory_max = (u64::from(memory) + VMM_OVERHEAD_MIB) * 1024 * 1024;
+        std::fs::write(self.path.join("memory.max"), memory_max.to_string())?;
+        let quota = u64::from(cpus.max(1)) * CPU_PERIOD_USEC;
+        std::fs::write(
+            self.path.join("cpu.max"),
+            format!("{} {}", quota, CPU_PERIOD_USEC),
+        )?;
+        Ok(())
+    }
+
+    /// Move another process, e.g. a respawned VMM, into the cgroup
+    pub fn add(&self, pid: u32) -> Result<()> {
+        std::fs::write(self.path.join("cgroup.procs"), pid.to_string())?;
+        Ok(())
+    }
+
+    /// Current memory and cumulative CPU usage, if the cgroup exists
+    pub fn usage(&self) -> Option<CgroupUsage> {
+        let memory = std::fs::read_to_string(self.path.join("memory.current")).ok()?;
+        let cpu_stat = std::fs::read_to_string(self.path.join("cpu.stat")).ok()?;
+        let cpu_usec = cpu_stat
+            .lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))
+            .and_then(|usec| usec.trim().parse().ok())
+            .unwrap_or(0);
+        Some(CgroupUsage {
+            memory_bytes: memory.trim().parse().ok()?,
+            cpu_usec,
+        })
+    }
+
+    /// Remove the cgroup once its processes have exited
+    pub async fn remove(&self) {
+        for _ in 0..REMOVE_ATTEMPTS {
+            match std::fs::remove_dir(&self.path) {
+                Ok(()) => return,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+                // Killed processes linger briefly, keeping the cgroup busy
+                Err(_) => tokio::time::sleep(REMOVE_INTERVAL).await,
+            }
+        }
+        tracing::warn!("Could not remove cgroup {}", self.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limits_and_usage() {
+        let root = tempfile::tempdir().unwrap();
+        let cgroup = VmCgroup::at(root.path(), "vortex-1");
+        let spec = VmSpec {
+            image: "alpine".to_string(),
+            memory: 512,
+            cpus: 2,
+            ..Default::default()
+        };
+        cgroup.apply(&spec, &[4242]).unwrap();
+
+        let read = |file: &str| std::fs::read_to_string(cgroup.path().join(file)).unwrap();
+        assert_eq!(read("memory.max"), (640u64 * 1024 * 1024).to_string());
+        assert_eq!(read("cpu.max"), "200000 100000");
+        assert_eq!(read("cgroup.procs"), "4242");
+
+        std::fs::write(cgroup.path().join("memory.current"), "1048576\n").unwrap();
+        std::fs::write(
+            cgroup.path().join("cpu.stat"),
+            "usage_usec 5000000\nuser_usec 4000000\n",
+        )
+        .unwrap();
+        let usage = cgroup.usage().unwrap();
+        assert_eq!(usage.memory_bytes, 1048576);
+        assert_eq!(usage.cpu_percent(10), 50.0);
+    }
+}
@@ -0,0 +1,37 @@
+//! Guest commands that run as child processes of the backend
+//!
+//! Backends whose `start` spawns the VM command on the host keep the child
+//! here, so `wait` can collect its exit status. Only the process that
+//! started a VM can wait for it.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::process::Child;
+use vortex_core::backend::ExitStatus;
+use vortex_core::error::{Result, VortexError};
+
+#[derive(Debug, Default)]
+pub(crate) struct Children {
+    running: Mutex<HashMap<String, Child>>,
+}
+
+impl Children {
+    /// Keep `child`, the command of `vm_id`, until it is waited for
+    pub(crate) fn insert(&self, vm_id: &str, child: Child) {
+        self.running
+            .lock()
+            .unwrap()
+            .insert(vm_id.to_string(), child);
+    }
+
+    /// Wait for the command of `vm_id` to exit
+    pub(crate) async fn wait(&self, vm_id: &str) -> Result<ExitStatus> {
+        let child = self.running.lock().unwrap().remove(vm_id);
+        let Some(mut child) = child else {
+            return Err(VortexError::VmError {
+                message: format!("No command started by this process is running in {}", vm_id),
+            });
+        };
+        Ok(child.wait().await?.into())
+    }
+}
@@ -0,0 +1,771 @@
+//! Cloud Hypervisor backend driven through its REST API.
+//!
+//! Every VM gets its own `cloud-hypervisor` process listening on an API socket
+//! in `~/.vortex/cloud-hypervisor/vms/<vm-id>/`. Cloud Hypervisor boots raw
+//! disk images rather than OCI images, so `spec.image` is looked up as
+//! `~/.vortex/cloud-hypervisor/images/<image>.raw` (`/` and `:` replaced by
+//! `_`), or else built once from the image's rootfs tarball by `image_cache`,
+//! and booted with the kernel at `~/.vortex/cloud-hypervisor/vmlinux`.
+//! The base image is copied per VM so each VM starts from a clean disk.
+//!
+//! Volumes are shared over virtio-fs, one `virtiofsd` per volume. Mounts and
+//! commands are typed into the guest console, so images are expected to log
+//! root into a shell on `hvc0`. The serial port goes to the VM's log. GPUs
+//! are passed through as VFIO devices. Running VMs are resized with
+//! `vm.resize`: vCPUs are hotplugged up to the CPU limit (or the host's
+//! CPUs) and memory through virtio-mem up to the memory limit (or four
+//! times the boot memory).
+//!
+//! Snapshots pause the VM, copy its disk and save memory and device state
+//! with `vm.snapshot`. A restore starts a fresh VMM in a new VM directory and
+//! loads the snapshot with its paths pointed at that directory. A clone
+//! copies the disk of the paused source VM and boots it with its own spec.
+
+use crate::cgroup::VmCgroup;
+use crate::vmm::{
+    attach_console, check_disk_limit, console_exec, console_wait, disk_image, kill_pid,
+    link_or_copy, load_spec, mount_script, process_rss, record_exit, save_spec, send_to_console,
+    shares, start_virtiofsd, wait_for_path, KERNEL_CMDLINE, SPEC_FILE, VIRTIOFSD_PIDS,
+    VIRTIOFS_MOUNT,
+};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use vortex_core::backend::{
+    boot_prelude, exec_script, Backend, ExecOptions, ExecResult, ExitStatus, VmMetrics,
+};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::logs;
+use vortex_core::vm::{GpuDevice, ShareMechanism, VmInstance, VmSpec};
+
+const API_SOCKET: &str = "api.sock";
+const ROOTFS: &str = "rootfs.raw";
+const VMM_PID: &str = "vmm.pid";
+/// Where `vm.snapshot` writes inside a snapshot's state directory
+const VMM_STATE: &str = "vmm";
+/// Snapshot files rewritten for a restore, inside the new VM directory
+const RESTORE_DIR: &str = "restore";
+/// virtio-mem plugs and unplugs memory in blocks of this many MiB
+const HOTPLUG_BLOCK_MIB: u64 = 128;
+/// Headroom for memory hotplug when the spec sets no memory limit
+const HOTPLUG_FACTOR: u64 = 4;
+const MAX_VCPUS: u32 = 254;
+
+/// Serial port writing to the log of `vm_id`. Cloud Hypervisor cannot
+/// record the console PTY, so the log holds what the guest writes to
+/// `ttyS0`, kernel messages included.
+fn serial_log(vm_id: &str) -> Result<Value> {
+    Ok(json!({ "mode": "File", "file": logs::log_path(vm_id)? }))
+}
+
+/// vCPUs a VM can be resized up to: its CPU limit, or else the host's CPUs
+fn max_vcpus(spec: &VmSpec) -> u32 {
+    let host = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+    spec.resource_limits
+        .max_cpus
+        .unwrap_or(host)
+        .max(spec.cpus)
+        .min(MAX_VCPUS)
+}
+
+/// Memory in MiB that virtio-mem can plug on top of the boot memory: up to
+/// the memory limit, or else a multiple of the boot memory
+fn hotplug_size(spec: &VmSpec) -> u64 {
+    let memory = u64::from(spec.memory);
+    let ceiling = spec
+        .resource_limits
+        .max_memory
+        .map_or(memory * HOTPLUG_FACTOR, u64::from);
+    let size = ceiling.saturating_sub(memory);
+    (size + HOTPLUG_BLOCK_MIB - 1) / HOTPLUG_BLOCK_MIB * HOTPLUG_BLOCK_MIB
+}
+
+/// Body of the `vm.create` request
+fn vm_config(spec: &VmSpec, kernel: &Path, disk: &Path, fs_sockets: &[(String, PathBuf)]) -> Value {
+    let mut config = json!({
+        "cpus": { "boot_vcpus": spec.cpus, "max_vcpus": max_vcpus(spec) },
+        "memory": {
+            "size": u64::from(spec.memory) * 1024 * 1024,
+            // virtio-fs needs guest memory shared with virtiofsd
+            "shared": !fs_sockets.is_empty(),
+        },
+        "payload": { "kernel": kernel, "cmdline": KERNEL_CMDLINE },
+        "disks": [{ "path": disk }],
+        "console": { "mode": "Pty" },
+        "serial": { "mode": "Null" },
+    });
+    let hotplug = hotplug_size(spec);
+    if hotplug > 0 {
+        config["memory"]["hotplug_method"] = json!("VirtioMem");
+        config["memory"]["hotplug_size"] = json!(hotplug * 1024 * 1024);
+    }
+    if !spec.network_disabled() {
+        config["net"] = json!([{}]);
+    }
+    let vfio: Vec<Value> = spec
+        .gpus
+        .iter()
+        .filter_map(|gpu| match gpu {
+            GpuDevice::Vfio { address } => {
+                Some(json!({ "path": format!("/sys/bus/pci/devices/{}/", address) }))
+            }
+            GpuDevice::Virtio => None,
+        })
+        .collect();
+    if !vfio.is_empty() {
+        config["devices"] = json!(vfio);
+    }
+    if !fs_sockets.is_empty() {
+        config["fs"] = fs_sockets
+            .iter()
+            .map(|(tag, socket)| {
+                json!({ "tag": tag, "socket": socket, "num_queues": 1, "queue_size": 1024 })
+            })
+            .collect();
+    }
+    config
+}
+
+/// A snapshot's `config.json` pointed at the disk and virtio-fs sockets of
+/// the VM being restored into `dir`
+fn restore_config(mut config: Value, dir: &Path, fs_sockets: &[(String, PathBuf)]) -> Value {
+    if let Some(disk) = config["disks"].get_mut(0) {
+        disk["path"] = json!(dir.join(ROOTFS));
+    }
+    if let Some(devices) = config["fs"].as_array_mut() {
+        for device in devices {
+            if let Some((_, socket)) = fs_sockets.iter().find(|(tag, _)| device["tag"] == *tag) {
+                device["socket"] = json!(socket);
+            }
+        }
+    }
+    // The source VM may still hold its TAP devices; let the VMM create new ones
+    if let Some(nets) = config["net"].as_array_mut() {
+        for net in nets.iter_mut().filter_map(Value::as_object_mut) {
+            net.remove("tap");
+        }
+    }
+    config
+}
+
+/// Split a buffered HTTP response into status and body once it is complete
+fn parse_response(raw: &[u8]) -> Result<Option<(u16, String)>> {
+    let Some(header_end) = raw.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let head = String::from_utf8_lossy(&raw[..header_end]);
+    let mut lines = head.lines();
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| VortexError::VmError {
+            message: "Malformed response from Cloud Hypervisor API".to_string(),
+        })?;
+
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let body_start = header_end + 4;
+    if raw.len() < body_start + content_length {
+        return Ok(None);
+    }
+    let body = String::from_utf8_lossy(&raw[body_start..body_start + content_length]);
+    Ok(Some((status, body.into_owned())))
+}
+
+async fn api_request(
+    socket: &Path,
+    method: &str,
+    endpoint: &str,
+    body: Option<&Value>,
+) -> Result<Value> {
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| VortexError::VmError {
+            message: format!("Cloud Hypervisor API unreachable: {}", e),
+        })?;
+
+    let body = body.map(Value::to_string).unwrap_or_default();
+    let request = format!(
+        "{} /api/v1/{} HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        endpoint,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 4096];
+    let (status, body) = loop {
+        let n = stream.read(&mut buf).await?;
+        raw.extend_from_slice(&buf[..n]);
+        if let Some(response) = parse_response(&raw)? {
+            break response;
+        }
+        if n == 0 {
+            return Err(VortexError::VmError {
+                message: format!("Truncated response to {} {}", method, endpoint),
+            });
+        }
+    };
+
+    if !(200..300).contains(&status) {
+        return Err(VortexError::VmError {
+            message: format!(
+                "Cloud Hypervisor {} {} failed ({}): {}",
+                method,
+                endpoint,
+                status,
+                body.trim()
+            ),
+        });
+    }
+
+    if body.trim().is_empty() {
+        Ok(Value::Null)
+    } else {
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+#[derive(Debug)]
+pub struct CloudHypervisorBackend {
+    root: PathBuf,
+}
+
+impl CloudHypervisorBackend {
+    pub async fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self {
+            root: home.join(".vortex").join("cloud-hypervisor"),
+        })
+    }
+
+    fn kernel_path(&self) -> PathBuf {
+        self.root.join("vmlinux")
+    }
+
+    fn vm_dir(&self, vm_id: &str) -> PathBuf {
+        self.root.join("vms").join(vm_id)
+    }
+
+    async fn api(
+        &self,
+        vm_id: &str,
+        method: &str,
+        endpoint: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        api_request(&self.vm_dir(vm_id).join(API_SOCKET), method, endpoint, body).await
+    }
+
+    /// Start virtiofsd and an empty VMM for a VM in `dir`, returning the
+    /// virtio-fs (tag, socket) pairs
+    async fn start_vmm(
+        &self,
+        vm_id: &str,
+        spec: &VmSpec,
+        dir: &Path,
+    ) -> Result<Vec<(String, PathBuf)>> {
+        // Cloud Hypervisor has no 9p, so every share is virtio-fs
+        let (mut pids, fs_sockets) = start_virtiofsd(&shares(spec), dir).await?;
+
+        let log = std::fs::File::create(dir.join("vmm.log"))?;
+        let api_socket = dir.join(API_SOCKET);
+        let child = tokio::process::Command::new("cloud-hypervisor")
+            .arg("--api-socket")
+            .arg(format!("path={}", api_socket.display()))
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to start cloud-hypervisor: {}", e),
+            })?;
+        if let Some(pid) = child.id() {
+            tokio::fs::write(dir.join(VMM_PID), pid.to_string()).await?;
+        }
+        pids.extend(child.id());
+        VmCgroup::confine(vm_id, spec, &pids);
+
+        wait_for_path(&api_socket, "the Cloud Hypervisor API socket").await?;
+        self.api(vm_id, "GET", "vmm.ping", None).await?;
+        Ok(fs_sockets)
+    }
+
+    async fn launch(&self, vm: &VmInstance, dir: &Path) -> Result<()> {
+        save_spec(dir, &vm.spec).await?;
+        let fs_sockets = self.start_vmm(&vm.id, &vm.spec, dir).await?;
+        let mut config = vm_config(
+            &vm.spec,
+            &self.kernel_path(),
+            &dir.join(ROOTFS),
+            &fs_sockets,
+        );
+        config["serial"] = serial_log(&vm.id)?;
+        config["payload"]["cmdline"] = json!(format!("console=ttyS0 {}", KERNEL_CMDLINE));
+        self.api(&vm.id, "PUT", "vm.create", Some(&config)).await?;
+        self.api(&vm.id, "PUT", "vm.boot", None).await?;
+        Ok(())
+    }
+
+    /// Kill the VMM and virtiofsd processes and delete the VM directory
+    async fn teardown(&self, vm_id: &str) -> Result<()> {
+        let dir = self.vm_dir(vm_id);
+        for file in [VMM_PID, VIRTIOFSD_PIDS] {
+            if let Ok(pids) = tokio::fs::read_to_string(dir.join(file)).await {
+                for pid in pids.lines().filter(|pid| !pid.is_empty()) {
+                    kill_pid(pid).await;
+                }
+            }
+        }
+        if let Some(cgroup) = VmCgroup::for_vm(vm_id) {
+            cgroup.remove().await;
+        }
+
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Load the snapshot in `state` into a new VMM for `vm_id`
+    async fn restore_into(
+        &self,
+        vm_id: &str,
+        spec: &VmSpec,
+        dir: &Path,
+        state: &Path,
+    ) -> Result<()> {
+        let fs_sockets = self.start_vmm(vm_id, spec, dir).await?;
+
+        let source = state.join(VMM_STATE);
+        let restore = dir.join(RESTORE_DIR);
+        tokio::fs::create_dir_all(&restore).await?;
+        let mut entries = tokio::fs::read_dir(&source).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name() != "config.json" {
+                link_or_copy(&entry.path(), &restore.join(entry.file_name())).await?;
+            }
+        }
+        let config: Value =
+            serde_json::from_str(&tokio::fs::read_to_string(source.join("config.json")).await?)?;
+        let mut config = restore_config(config, dir, &fs_sockets);
+        config["serial"] = serial_log(vm_id)?;
+        tokio::fs::write(restore.join("config.json"), config.to_string()).await?;
+
+        let source_url = format!("file://{}", restore.display());
+        self.api(
+            vm_id,
+            "PUT",
+            "vm.restore",
+            Some(&json!({ "source_url": source_url })),
+        )
+        .await?;
+        self.api(vm_id, "PUT", "vm.resume", None).await?;
+        if let Err(e) = tokio::fs::remove_dir_all(&restore).await {
+            tracing::warn!("Failed to remove {}: {}", restore.display(), e);
+        }
+        Ok(())
+    }
+
+    async fn console_path(&self, vm_id: &str) -> Result<PathBuf> {
+        let info = self.api(vm_id, "GET", "vm.info", None).await?;
+        info["config"]["console"]["file"]
+            .as_str()
+            .map(PathBuf::from)
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("No console PTY for VM {}", vm_id),
+            })
+    }
+
+    async fn send_to_console(&self, vm_id: &str, line: &str) -> Result<()> {
+        send_to_console(&self.console_path(vm_id).await?, line).await
+    }
+}
+
+#[async_trait]
+impl Backend for CloudHypervisorBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        let kernel = self.kernel_path();
+        if !kernel.is_file() {
+            return Err(VortexError::VmError {
+                message: format!("Cloud Hypervisor kernel not found at {}", kernel.display()),
+            });
+        }
+        let base_image = disk_image(&self.root, &vm.spec.image).await?;
+        check_disk_limit(&vm.spec, &base_image)?;
+
+        if !vm.spec.ports.is_empty() {
+            tracing::warn!(
+                "cloud-hypervisor backend does not forward ports; ignoring {} mapping(s)",
+                vm.spec.ports.len()
+            );
+        }
+
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::copy(&base_image, dir.join(ROOTFS)).await?;
+
+        let result = self.launch(vm, &dir).await;
+        if result.is_err() {
+            if let Err(e) = self.teardown(&vm.id).await {
+                tracing::warn!("Failed to clean up after failed create: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        let info = self.api(&vm.id, "GET", "vm.info", None).await?;
+        match info["state"].as_str() {
+            Some("Created") | Some("Shutdown") => {
+                self.api(&vm.id, "PUT", "vm.boot", None).await?;
+            }
+            Some("Paused") => {
+                self.api(&vm.id, "PUT", "vm.resume", None).await?;
+            }
+            _ => {}
+        }
+
+        let mut script = mount_script(&vm.spec, VIRTIOFS_MOUNT);
+        script.push_str(&boot_prelude(vm));
+        if let Some(command) = &vm.spec.command {
+            script.push_str(&record_exit(command));
+        }
+        if !script.is_empty() {
+            self.send_to_console(&vm.id, &script).await?;
+        }
+        Ok(())
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        self.api(&vm.id, "PUT", "vm.shutdown", None).await?;
+        // The VMM process exits after vmm.shutdown, which closes the socket
+        if let Err(e) = self.api(&vm.id, "PUT", "vmm.shutdown", None).await {
+            tracing::debug!("vmm.shutdown for {}: {}", vm.id, e);
+        }
+        Ok(())
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        if let Err(e) = self.stop(vm).await {
+            tracing::warn!(
+                "Cloud Hypervisor shutdown failed (may already be stopped): {}",
+                e
+            );
+        }
+        self.teardown(&vm.id).await
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        let setup = format!(
+            "{}{}",
+            boot_prelude(vm),
+            mount_script(&vm.spec, VIRTIOFS_MOUNT)
+        );
+        if !setup.is_empty() {
+            self.send_to_console(&vm.id, &setup).await?;
+        }
+
+        attach_console(&vm.id, self.console_path(&vm.id).await?).await
+    }
+
+    async fn exec(
+        &self,
+        vm: &VmInstance,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        let script = format!(
+            "{}{}",
+            mount_script(&vm.spec, VIRTIOFS_MOUNT),
+            exec_script(command, options)
+        );
+        console_exec(&self.console_path(&vm.id).await?, &script).await
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        console_wait(&self.console_path(&vm.id).await?).await
+    }
+
+    async fn pause(&self, vm: &VmInstance) -> Result<()> {
+        self.api(&vm.id, "PUT", "vm.pause", None).await?;
+        Ok(())
+    }
+
+    async fn resume(&self, vm: &VmInstance) -> Result<()> {
+        self.api(&vm.id, "PUT", "vm.resume", None).await?;
+        Ok(())
+    }
+
+    async fn resize(&self, vm: &VmInstance, memory: Option<u32>, cpus: Option<u32>) -> Result<()> {
+        let info = self.api(&vm.id, "GET", "vm.info", None).await?;
+        let current_memory = info["memory_actual_size"]
+            .as_u64()
+            .map_or(vm.spec.memory, |bytes| (bytes / 1024 / 1024) as u32);
+        let current_cpus = info["config"]["cpus"]["boot_vcpus"]
+            .as_u64()
+            .map_or(vm.spec.cpus, |cpus| cpus as u32);
+        let memory = memory.unwrap_or(current_memory);
+        let cpus = cpus.unwrap_or(current_cpus);
+
+        // Raise the host limits before the guest grows, lower them after it shrank
+        let cgroup = VmCgroup::for_vm(&vm.id).filter(|cgroup| cgroup.path().is_dir());
+        if let Some(cgroup) = &cgroup {
+            cgroup.set_limits(memory.max(current_memory), cpus.max(current_cpus))?;
+        }
+        self.api(
+            &vm.id,
+            "PUT",
+            "vm.resize",
+            Some(&json!({
+                "desired_vcpus": cpus,
+                "desired_ram": u64::from(memory) * 1024 * 1024,
+            })),
+        )
+        .await?;
+        if let Some(cgroup) = &cgroup {
+            cgroup.set_limits(memory, cpus)?;
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self, vm: &VmInstance, state: &Path) -> Result<()> {
+        let dir = self.vm_dir(&vm.id);
+        let destination = state.join(VMM_STATE);
+        tokio::fs::create_dir_all(&destination).await?;
+
+        self.api(&vm.id, "PUT", "vm.pause", None).await?;
+        let result = async {
+            tokio::fs::copy(dir.join(SPEC_FILE), state.join(SPEC_FILE)).await?;
+            tokio::fs::copy(dir.join(ROOTFS), state.join(ROOTFS)).await?;
+            let destination_url = format!("file://{}", destination.display());
+            self.api(
+                &vm.id,
+                "PUT",
+                "vm.snapshot",
+                Some(&json!({ "destination_url": destination_url })),
+            )
+            .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = self.api(&vm.id, "PUT", "vm.resume", None).await {
+            tracing::warn!("Failed to resume {} after snapshot: {}", vm.id, e);
+        }
+        result
+    }
+
+    async fn restore(&self, vm: &VmInstance, state: &Path) -> Result<()> {
+        let spec = load_spec(state).await?;
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::copy(state.join(ROOTFS), dir.join(ROOTFS)).await?;
+        save_spec(&dir, &spec).await?;
+
+        let result = self.restore_into(&vm.id, &spec, &dir, state).await;
+        if result.is_err() {
+            if let Err(e) = self.teardown(&vm.id).await {
+                tracing::warn!("Failed to clean up after failed restore: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn clone_vm(&self, source: &VmInstance, vm: &VmInstance) -> Result<()> {
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        // Paused, the source cannot write to the disk while it is copied
+        self.api(&source.id, "PUT", "vm.pause", None).await?;
+        let copied = tokio::fs::copy(self.vm_dir(&source.id).join(ROOTFS), dir.join(ROOTFS)).await;
+        if let Err(e) = self.api(&source.id, "PUT", "vm.resume", None).await {
+            tracing::warn!(
+                "Failed to resume {} after copying its disk: {}",
+                source.id,
+                e
+            );
+        }
+
+        let result = match copied {
+            Ok(_) => self.launch(vm, &dir).await,
+            Err(e) => Err(e.into()),
+        };
+        if result.is_err() {
+            if let Err(e) = self.teardown(&vm.id).await {
+                tracing::warn!("Failed to clean up after failed clone: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        let info = self.api(&vm.id, "GET", "vm.info", None).await?;
+        let counters = self
+            .api(&vm.id, "GET", "vm.counters", None)
+            .await
+            .unwrap_or(Value::Null);
+
+        let (mut network_rx, mut network_tx) = (0, 0);
+        if let Some(devices) = counters.as_object() {
+            for (name, device) in devices {
+                if name.starts_with("_net") {
+                    network_rx += device["rx_bytes"].as_u64().unwrap_or(0);
+                    network_tx += device["tx_bytes"].as_u64().unwrap_or(0);
+                }
+            }
+        }
+
+        let dir = self.vm_dir(&vm.id);
+        let uptime_seconds = (chrono::Utc::now() - vm.created_at).num_seconds().max(0) as u64;
+        let usage = VmCgroup::for_vm(&vm.id).and_then(|cgroup| cgroup.usage());
+        let memory_usage = match usage {
+            Some(usage) => usage.memory_bytes,
+            None => tokio::fs::read_to_string(dir.join(VMM_PID))
+                .await
+                .ok()
+                .and_then(|pid| process_rss(pid.trim()))
+                .unwrap_or(0),
+        };
+
+        #[cfg(unix)]
+        let disk_usage = {
+            use std::os::unix::fs::MetadataExt;
+            std::fs::metadata(dir.join(ROOTFS))
+                .map(|m| m.blocks() * 512)
+                .unwrap_or(0)
+        };
+        #[cfg(not(unix))]
+        let disk_usage = 0;
+
+        Ok(VmMetrics {
+            // Cloud Hypervisor has no CPU accounting API; only the cgroup has it
+            cpu_usage: usage.map_or(0.0, |usage| usage.cpu_percent(uptime_seconds)),
+            memory_usage,
+            memory_total: info["memory_actual_size"]
+                .as_u64()
+                .or_else(|| info["config"]["memory"]["size"].as_u64())
+                .unwrap_or(u64::from(vm.spec.memory) * 1024 * 1024),
+            disk_usage,
+            network_rx,
+            network_tx,
+            uptime_seconds,
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        let Ok(mut entries) = tokio::fs::read_dir(self.root.join("vms")).await else {
+            return Ok(Vec::new());
+        };
+
+        let mut vms = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(vm_id) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if self.api(&vm_id, "GET", "vmm.ping", None).await.is_ok() {
+                vms.push(vm_id);
+            }
+        }
+        vms.sort();
+        Ok(vms)
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        if !Path::new("/dev/kvm").exists() {
+            return Ok(false);
+        }
+        let output = tokio::process::Command::new("cloud-hypervisor")
+            .arg("--version")
+            .output()
+            .await;
+        Ok(output.is_ok_and(|o| o.status.success()))
+    }
+
+    fn name(&self) -> &'static str {
+        "cloud-hypervisor"
+    }
+
+    fn supports_gpu(&self, gpu: &GpuDevice) -> bool {
+        matches!(gpu, GpuDevice::Vfio { .. })
+    }
+
+    fn supports_share(&self, mechanism: ShareMechanism) -> bool {
+        mechanism != ShareMechanism::NineP
+    }
+
+    fn supports_prewarm(&self) -> bool {
+        true
+    }
+
+    fn supports_network_isolation(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response_waits_for_full_body() {
+        let partial = b"HTTP/1.1 200 OK\r\nContent-Length: 14\r\n\r\n{\"state\":";
+        assert!(parse_response(partial).unwrap().is_none());
+
+        let full = b"HTTP/1.1 200 OK\r\nContent-Length: 14\r\n\r\n{\"state\":\"Ok\"}";
+        let (status, body) = parse_response(full).unwrap().unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, "{\"state\":\"Ok\"}");
+
+        let no_content = b"HTTP/1.1 204 No Content\r\n\r\n";
+        assert_eq!(
+            parse_response(no_content).unwrap(),
+            Some((204, String::new()))
+        );
+    }
+
+    #[test]
+    fn test_vm_config_shares_memory_only_with_virtio_fs() {
+        let spec = VmSpec {
+            memory: 1024,
+            cpus: 2,
+            ..Default::default()
+        };
+        let kernel = Path::new("/k/vmlinux");
+        let disk = Path::new("/d/rootfs.raw");
+
+        let config = vm_config(&spec, kernel, disk, &[]);
+        assert_eq!(config["memory"]["size"], 1024u64 * 1024 * 1024);
+        assert_eq!(config["memory"]["shared"], false);
+        assert_eq!(config["cpus"]["boot_vcpus"], 2);
+        assert_eq!(config["memory"]["hotplug_size"], 3u64 * 1024 * 1024 * 1024);
+        assert!(config.get("fs").is_none());
+
+        let sockets = vec![("vortexfs0".to_string(), PathBuf::from("/d/vortexfs0.sock"))];
+        let config = vm_config(&spec, kernel, disk, &sockets);
+        assert_eq!(config["memory"]["shared"], true);
+        assert_eq!(config["fs"][0]["tag"], "vortexfs0");
+
+        // A restore points the snapshot's config at the new VM directory
+        let mut saved = config;
+        saved["net"] = json!([{ "tap": "vmtap0", "mac": "12:34:56:78:9a:bc" }]);
+        let new_sockets = vec![("vortexfs0".to_string(), PathBuf::from("/n/vortexfs0.sock"))];
+        let restored = restore_config(saved, Path::new("/n"), &new_sockets);
+        assert_eq!(restored["disks"][0]["path"], "/n/rootfs.raw");
+        assert_eq!(restored["fs"][0]["socket"], "/n/vortexfs0.sock");
+        assert!(restored["net"][0].get("tap").is_none());
+    }
+}
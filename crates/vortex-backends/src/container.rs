@@ -0,0 +1,614 @@
+//! Container backend for hosts without hardware virtualization.
+//!
+//! CI runners rarely allow nested virtualization, so as a last resort a
+//! `VmSpec` is mapped onto `podman run` (or `docker run`) to keep the same CLI
+//! and tests working everywhere. Containers share the host kernel, so this is
+//! reduced isolation: the backend reports it and `vortex list` flags such VMs.
+//! It is registered after every VM backend and only used when none of them is
+//! available, or when asked for by name.
+//!
+//! Set `VORTEX_CONTAINER_ENGINE` to `podman` or `docker` to pick the engine;
+//! otherwise podman is preferred.
+//!
+//! Snapshots use `podman container checkpoint`, which needs CRIU and root;
+//! Docker's checkpoints cannot be restored under a new name, so it has none.
+//! Clones commit the source container to an image, `localhost/vortex-clone`
+//! tagged with the clone's id, and run that image; both engines support it.
+//!
+//! A private network is an engine network named `vortex-net-<name>` on the
+//! network's subnet, created when its first member is. Members join it
+//! instead of the default network, which still reaches the internet.
+
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+use vortex_core::backend::{Backend, ExecOptions, ExecResult, ExitStatus, VmMetrics};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::logs;
+use vortex_core::network::{NetworkManager, VmNetwork};
+use vortex_core::vm::{VmInstance, HOST_ALIAS};
+
+/// Environment variable selecting the container engine
+pub const ENGINE_ENV: &str = "VORTEX_CONTAINER_ENGINE";
+/// Label marking containers created by Vortex
+const MANAGED_LABEL: &str = "vortex.managed=true";
+/// Keeps a container without a command alive until it is stopped
+const IDLE_COMMAND: &str = "trap 'exit 0' TERM; while :; do sleep 3600 & wait; done";
+const STOP_TIMEOUT_SECS: &str = "10";
+/// Exit code of `<engine> exec` when the engine itself failed
+const ENGINE_ERROR_EXIT: i32 = 125;
+/// Exported checkpoint inside a snapshot's state directory
+const CHECKPOINT: &str = "checkpoint.tar.gz";
+/// Repository of the images clones run, tagged with the clone's id
+const CLONE_REPOSITORY: &str = "localhost/vortex-clone";
+/// Prefix of the engine networks backing private networks
+const NETWORK_PREFIX: &str = "vortex-net-";
+
+#[derive(Debug)]
+pub struct ContainerBackend {
+    engine: String,
+}
+
+impl ContainerBackend {
+    pub async fn new() -> Result<Self> {
+        let engine = match std::env::var(ENGINE_ENV) {
+            Ok(engine) if !engine.is_empty() => engine,
+            _ => {
+                let mut found = "podman";
+                for candidate in ["podman", "docker"] {
+                    if engine_responds(candidate, "--version").await {
+                        found = candidate;
+                        break;
+                    }
+                }
+                found.to_string()
+            }
+        };
+        Ok(Self { engine })
+    }
+
+    fn command(&self) -> Command {
+        Command::new(&self.engine)
+    }
+
+    fn require_checkpoints(&self) -> Result<()> {
+        if Path::new(&self.engine)
+            .file_stem()
+            .is_some_and(|name| name == "podman")
+        {
+            return Ok(());
+        }
+        Err(VortexError::VmError {
+            message: format!(
+                "Snapshots need podman; {} cannot restore a checkpoint as a new container",
+                self.engine
+            ),
+        })
+    }
+
+    /// The private network `vm_id` is on, created in the engine on first use
+    async fn private_network(&self, vm_id: &str) -> Result<Option<VmNetwork>> {
+        let Some(nic) = NetworkManager::new().await?.get_vm_network(vm_id).await? else {
+            return Ok(None);
+        };
+        // Engines have no per-container bandwidth or connection limits
+        if !nic.limits.is_unlimited() {
+            return Err(VortexError::NetworkError {
+                message: format!(
+                    "The {} engine cannot hold VMs to the limits of network {} ({})",
+                    self.engine, nic.network_name, nic.limits
+                ),
+            });
+        }
+        let name = format!("{}{}", NETWORK_PREFIX, nic.network_name);
+        if self.run(&["network", "inspect", &name]).await.is_err() {
+            let created = self
+                .run(&[
+                    "network",
+                    "create",
+                    "--subnet",
+                    &nic.subnet,
+                    "--label",
+                    MANAGED_LABEL,
+                    &name,
+                ])
+                .await;
+            // Another member may have created it meanwhile
+            if let Err(e) = created {
+                if self.run(&["network", "inspect", &name]).await.is_err() {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(Some(nic))
+    }
+
+    /// Arguments for `<engine> create` reproducing `vm.spec`, on the private
+    /// network `nic` if it has one
+    fn create_args(&self, vm: &VmInstance, nic: Option<&VmNetwork>) -> Vec<String> {
+        let spec = &vm.spec;
+        let mut args = vec![
+            "create".to_string(),
+            "--name".to_string(),
+            vm.id.to_string(),
+            "--label".to_string(),
+            MANAGED_LABEL.to_string(),
+            "--memory".to_string(),
+            format!("{}m", spec.memory),
+            "--cpus".to_string(),
+            spec.cpus.to_string(),
+        ];
+
+        if spec.network_disabled() {
+            args.extend(["--network".to_string(), "none".to_string()]);
+        } else {
+            // The engine's address for the host (Docker 20.10+, Podman 5.3+)
+            args.extend([
+                "--add-host".to_string(),
+                format!("{}:host-gateway", HOST_ALIAS),
+            ]);
+        }
+        if let Some(nic) = nic {
+            args.extend([
+                "--network".to_string(),
+                format!("{}{}", NETWORK_PREFIX, nic.network_name),
+                "--ip".to_string(),
+                nic.ip_address.clone(),
+                "--mac-address".to_string(),
+                nic.mac_address.clone(),
+            ]);
+            // The engine's DNS already resolves the container name
+            if let Some(service) = &nic.service {
+                args.extend(["--network-alias".to_string(), service.clone()]);
+            }
+        }
+        let mut ports: Vec<_> = spec.ports.iter().collect();
+        ports.sort();
+        for (host, guest) in ports {
+            let mapping = match spec.port_addresses.get(host) {
+                Some(ip) => format!("{}:{}", SocketAddr::new(*ip, *host), guest),
+                None => format!("{}:{}", host, guest),
+            };
+            args.extend(["-p".to_string(), mapping]);
+        }
+        let mut volumes: Vec<_> = spec.volumes.iter().collect();
+        volumes.sort();
+        for (host, guest) in volumes {
+            args.extend([
+                "-v".to_string(),
+                format!("{}:{}", host.display(), guest.display()),
+            ]);
+        }
+        let mut tmpfs: Vec<_> = spec.tmpfs.iter().collect();
+        tmpfs.sort();
+        for (guest, size_mib) in tmpfs {
+            args.extend([
+                "--tmpfs".to_string(),
+                format!("{}:size={}m", guest.display(), size_mib),
+            ]);
+        }
+        let mut environment: Vec<_> = spec.environment.iter().collect();
+        environment.sort();
+        for (key, value) in environment {
+            args.extend(["-e".to_string(), format!("{}={}", key, value)]);
+        }
+        let mut labels: Vec<_> = spec.labels.iter().collect();
+        labels.sort();
+        for (key, value) in labels {
+            args.extend(["--label".to_string(), format!("{}={}", key, value)]);
+        }
+
+        args.push(spec.image.clone());
+        let provision = spec.provision.as_ref().map(|p| p.boot_script());
+        args.extend([
+            "sh".to_string(),
+            "-c".to_string(),
+            format!(
+                "{}{}",
+                provision.unwrap_or_default(),
+                spec.command.as_deref().unwrap_or(IDLE_COMMAND)
+            ),
+        ]);
+        args
+    }
+
+    async fn run(&self, args: &[&str]) -> Result<String> {
+        let output = self
+            .command()
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to run {}: {}", self.engine, e),
+            })?;
+        if !output.status.success() {
+            return Err(VortexError::VmError {
+                message: format!(
+                    "{} {} failed: {}",
+                    self.engine,
+                    args.first().unwrap_or(&""),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+async fn engine_responds(engine: &str, arg: &str) -> bool {
+    Command::new(engine)
+        .arg(arg)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+/// Parse sizes such as `12.5MiB`, `1.2GB` or `512kB` from engine stats
+fn parse_size(value: &str) -> u64 {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().unwrap_or(0.0);
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        _ => 1.0,
+    };
+    (number * scale) as u64
+}
+
+#[async_trait]
+impl Backend for ContainerBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        if vm.spec.tuning.is_some() {
+            tracing::warn!("container backend shares the host kernel; ignoring tuning profile");
+        }
+        let nic = self.private_network(&vm.id).await?;
+        let args = self.create_args(vm, nic.as_ref());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.run(&args).await?;
+        Ok(())
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        self.run(&["start", &vm.id]).await?;
+
+        // Copy the output into the Vortex log; `logs --follow` exits with
+        // the container
+        let log = logs::create(&vm.id)?;
+        self.command()
+            .args(["logs", "--follow", &vm.id])
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to run {}: {}", self.engine, e),
+            })?;
+        Ok(())
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        let code = self.run(&["wait", &vm.id]).await?;
+        let code = code.trim().parse().map_err(|_| VortexError::VmError {
+            message: format!("Unexpected exit status from {} wait: {}", self.engine, code),
+        })?;
+        Ok(ExitStatus { code })
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        self.run(&["stop", "-t", STOP_TIMEOUT_SECS, &vm.id]).await?;
+        Ok(())
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        self.run(&["rm", "-f", &vm.id]).await?;
+        // Only clones have an image of their own
+        let image = format!("{}:{}", CLONE_REPOSITORY, vm.id);
+        if let Err(e) = self.run(&["rmi", &image]).await {
+            tracing::debug!("No clone image to remove for {}: {}", vm.id, e);
+        }
+        Ok(())
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        let status = self
+            .command()
+            .args(["exec", "-it", &vm.id, "sh"])
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(VortexError::VmError {
+                message: format!("{} exec exited with {}", self.engine, status),
+            });
+        }
+        Ok(())
+    }
+
+    async fn exec(
+        &self,
+        vm: &VmInstance,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        let mut cmd = self.command();
+        cmd.arg("exec");
+        if let Some(workdir) = &options.workdir {
+            cmd.arg("--workdir").arg(workdir);
+        }
+        let mut env: Vec<_> = options.env.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            cmd.arg("--env").arg(format!("{}={}", key, value));
+        }
+        let output = cmd
+            .arg(&vm.id)
+            .args(command)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to run {}: {}", self.engine, e),
+            })?;
+        if output.status.code() == Some(ENGINE_ERROR_EXIT) {
+            return Err(VortexError::VmError {
+                message: format!(
+                    "{} exec failed: {}",
+                    self.engine,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+        Ok(ExecResult::from_output(&output))
+    }
+
+    async fn copy_to(&self, vm: &VmInstance, host: &Path, guest: &str) -> Result<()> {
+        let host = host.to_string_lossy();
+        self.run(&["cp", &host, &format!("{}:{}", vm.id, guest)])
+            .await?;
+        Ok(())
+    }
+
+    async fn copy_from(&self, vm: &VmInstance, guest: &str, host: &Path) -> Result<()> {
+        let host = host.to_string_lossy();
+        self.run(&["cp", &format!("{}:{}", vm.id, guest), &host])
+            .await?;
+        Ok(())
+    }
+
+    async fn pause(&self, vm: &VmInstance) -> Result<()> {
+        self.run(&["pause", &vm.id]).await?;
+        Ok(())
+    }
+
+    async fn resume(&self, vm: &VmInstance) -> Result<()> {
+        self.run(&["unpause", &vm.id]).await?;
+        Ok(())
+    }
+
+    async fn resize(&self, vm: &VmInstance, memory: Option<u32>, cpus: Option<u32>) -> Result<()> {
+        let mut args = vec!["update".to_string()];
+        if let Some(memory) = memory {
+            // Keep the swap allowance `--memory` gave the container at create
+            args.extend([
+                "--memory".to_string(),
+                format!("{}m", memory),
+                "--memory-swap".to_string(),
+                format!("{}m", u64::from(memory) * 2),
+            ]);
+        }
+        if let Some(cpus) = cpus {
+            args.extend(["--cpus".to_string(), cpus.to_string()]);
+        }
+        args.push(vm.id.to_string());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.run(&args).await?;
+        Ok(())
+    }
+
+    async fn snapshot(&self, vm: &VmInstance, state: &Path) -> Result<()> {
+        self.require_checkpoints()?;
+        let export = state.join(CHECKPOINT).display().to_string();
+        self.run(&[
+            "container",
+            "checkpoint",
+            "--leave-running",
+            "--export",
+            &export,
+            &vm.id,
+        ])
+        .await?;
+        Ok(())
+    }
+
+    async fn restore(&self, vm: &VmInstance, state: &Path) -> Result<()> {
+        self.require_checkpoints()?;
+        let import = state.join(CHECKPOINT).display().to_string();
+        self.run(&[
+            "container",
+            "restore",
+            "--import",
+            &import,
+            "--name",
+            &vm.id,
+        ])
+        .await?;
+        Ok(())
+    }
+
+    async fn clone_vm(&self, source: &VmInstance, vm: &VmInstance) -> Result<()> {
+        // `commit` pauses the source while its filesystem is copied
+        let image = format!("{}:{}", CLONE_REPOSITORY, vm.id);
+        self.run(&["commit", &source.id, &image]).await?;
+
+        let mut clone = vm.clone();
+        clone.spec.image = image.clone();
+        let nic = self.private_network(&vm.id).await?;
+        let args = self.create_args(&clone, nic.as_ref());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let created = match self.run(&args).await {
+            Ok(_) => self.run(&["start", &vm.id]).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = created {
+            if let Err(cleanup) = self.run(&["rmi", &image]).await {
+                tracing::warn!("Failed to remove {}: {}", image, cleanup);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        let stats = self
+            .run(&[
+                "stats",
+                "--no-stream",
+                "--format",
+                "{{.CPUPerc}}|{{.MemUsage}}",
+                &vm.id,
+            ])
+            .await?;
+        let mut fields = stats.trim().split('|');
+        let cpu_usage = fields
+            .next()
+            .and_then(|cpu| cpu.trim().trim_end_matches('%').parse().ok())
+            .unwrap_or(0.0);
+        let (memory_usage, memory_total) = fields
+            .next()
+            .and_then(|memory| memory.split_once('/'))
+            .map(|(used, total)| (parse_size(used), parse_size(total)))
+            .unwrap_or((0, vm.spec.memory as u64 * 1024 * 1024));
+
+        Ok(VmMetrics {
+            cpu_usage,
+            memory_usage,
+            memory_total,
+            disk_usage: 0,
+            network_rx: 0,
+            network_tx: 0,
+            uptime_seconds: (chrono::Utc::now() - vm.created_at).num_seconds().max(0) as u64,
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        let output = self
+            .run(&[
+                "ps",
+                "-a",
+                "--filter",
+                &format!("label={}", MANAGED_LABEL),
+                "--format",
+                "{{.Names}}",
+            ])
+            .await?;
+        Ok(output
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        // `info` also checks that the engine's daemon or socket is usable
+        Ok(engine_responds(&self.engine, "info").await)
+    }
+
+    fn name(&self) -> &'static str {
+        "container"
+    }
+
+    fn supports_private_networks(&self) -> bool {
+        true
+    }
+
+    fn supports_network_isolation(&self) -> bool {
+        true
+    }
+
+    fn supports_port_addresses(&self) -> bool {
+        true
+    }
+
+    fn reduced_isolation(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use vortex_core::network::NetworkLimits;
+    use vortex_core::vm::{VmSpec, VmState, NETWORK_NONE};
+
+    #[test]
+    fn test_create_args_map_spec() {
+        let backend = Arc::new(ContainerBackend {
+            engine: "podman".to_string(),
+        });
+        let mut spec = VmSpec {
+            image: "alpine".to_string(),
+            memory: 256,
+            cpus: 2,
+            command: Some("echo hi".to_string()),
+            network_config: Some(NETWORK_NONE.to_string()),
+            ..Default::default()
+        };
+        spec.volumes
+            .insert(PathBuf::from("/src"), PathBuf::from("/workspace"));
+        spec.tmpfs.insert(PathBuf::from("/tmp"), 64);
+        let vm = VmInstance {
+            id: "vortex-1".into(),
+            spec,
+            state: VmState::Creating,
+            backend: backend.clone(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+
+        let args = backend.create_args(&vm, None).join(" ");
+        assert!(args.starts_with("create --name vortex-1 --label vortex.managed=true"));
+        assert!(args.contains(
+            "--memory 256m --cpus 2 --network none -v /src:/workspace --tmpfs /tmp:size=64m"
+        ));
+        assert!(args.ends_with("alpine sh -c echo hi"));
+        assert!(!args.contains("--add-host"));
+
+        let nic = VmNetwork {
+            vm_id: "vortex-1".to_string(),
+            network_name: "backend".to_string(),
+            subnet: "10.89.1.0/24".to_string(),
+            ip_address: "10.89.1.2".to_string(),
+            prefix_len: 24,
+            mac_address: "02:0a:59:00:01:02".to_string(),
+            service: Some("database".to_string()),
+            limits: NetworkLimits::default(),
+        };
+        let args = backend.create_args(&vm, Some(&nic)).join(" ");
+        assert!(args.contains("--network vortex-net-backend --ip 10.89.1.2"));
+        assert!(args.contains("--network-alias database"));
+        let vm = VmInstance {
+            spec: VmSpec::default(),
+            ..vm
+        };
+        let args = backend.create_args(&vm, None).join(" ");
+        assert!(args.contains("--add-host host.vortex.internal:host-gateway"));
+        assert_eq!(parse_size("1.5GiB"), 1610612736);
+        assert_eq!(parse_size("512kB"), 512000);
+    }
+}
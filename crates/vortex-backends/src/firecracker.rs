@@ -0,0 +1,73 @@
+//! Firecracker backend placeholder, reserved behind the `firecracker` feature
+
+use async_trait::async_trait;
+use vortex_core::backend::{Backend, VmMetrics};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::vm::VmInstance;
+
+#[derive(Debug)]
+pub struct FirecrackerBackend;
+
+impl FirecrackerBackend {
+    pub async fn new() -> Result<Self> {
+        Ok(Self)
+    }
+}
+
+// TODO: Implement Firecracker backend (see https://github.com/exec/vortex/issues/123)
+#[async_trait]
+impl Backend for FirecrackerBackend {
+    async fn create(&self, _vm: &VmInstance) -> Result<()> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn start(&self, _vm: &VmInstance) -> Result<()> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn stop(&self, _vm: &VmInstance) -> Result<()> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn cleanup(&self, _vm: &VmInstance) -> Result<()> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn attach(&self, _vm: &VmInstance) -> Result<()> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn get_metrics(&self, _vm: &VmInstance) -> Result<VmMetrics> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        Err(VortexError::VmError {
+            message: "Firecracker backend not yet implemented".to_string(),
+        })
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        use tokio::process::Command;
+
+        let output = Command::new("which").arg("firecracker").output().await?;
+
+        Ok(output.status.success())
+    }
+
+    fn name(&self) -> &'static str {
+        "firecracker"
+    }
+}
@@ -1,8 +1,13 @@
-use crate::error::{Result, VortexError};
-use crate::vm::VmInstance;
+//! krunvm CLI backend, run inside `buildah unshare`
+
+use crate::children::Children;
+use crate::tsi;
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::process::Stdio;
+use vortex_core::backend::{boot_prelude, process_stats, Backend, ExitStatus, VmMetrics};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::logs;
+use vortex_core::vm::{ShareMechanism, VmInstance};
 
 /// Sanitize error messages from external commands to prevent information disclosure
 fn sanitize_error_message(msg: &str) -> String {
@@ -31,126 +36,16 @@ fn sanitize_error_message(msg: &str) -> String {
     sanitized
 }
 
-#[async_trait]
-pub trait Backend: Send + Sync + std::fmt::Debug {
-    /// Create a new VM instance
-    async fn create(&self, vm: &VmInstance) -> Result<()>;
-
-    /// Start a VM instance
-    async fn start(&self, vm: &VmInstance) -> Result<()>;
-
-    /// Stop a VM instance
-    async fn stop(&self, vm: &VmInstance) -> Result<()>;
-
-    /// Cleanup/destroy a VM instance
-    async fn cleanup(&self, vm: &VmInstance) -> Result<()>;
-
-    /// Attach to an interactive session
-    async fn attach(&self, vm: &VmInstance) -> Result<()>;
-
-    /// Get VM metrics
-    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics>;
-
-    /// List all VMs managed by this backend
-    async fn list_vms(&self) -> Result<Vec<String>>;
-
-    /// Check if backend is available
-    async fn is_available(&self) -> Result<bool>;
-
-    /// Get backend name
-    fn name(&self) -> &'static str;
-}
-
-#[derive(Debug, Clone)]
-pub struct VmMetrics {
-    pub cpu_usage: f64,
-    pub memory_usage: u64,
-    pub memory_total: u64,
-    pub disk_usage: u64,
-    pub network_rx: u64,
-    pub network_tx: u64,
-    pub uptime_seconds: u64,
-}
-
-pub struct BackendProvider {
-    backends: HashMap<String, Arc<dyn Backend>>,
-    preferred: Option<String>,
-}
-
-impl BackendProvider {
-    pub async fn new() -> Result<Self> {
-        let mut provider = Self {
-            backends: HashMap::new(),
-            preferred: None,
-        };
-
-        // Register available backends
-        #[cfg(feature = "krunvm")]
-        {
-            let krunvm = KrunvmBackend::new().await?;
-            if krunvm.is_available().await? {
-                provider.register("krunvm", Arc::new(krunvm));
-            }
-        }
-
-        #[cfg(feature = "firecracker")]
-        {
-            let firecracker = FirecrackerBackend::new().await?;
-            if firecracker.is_available().await? {
-                provider.register("firecracker", Arc::new(firecracker));
-            }
-        }
-
-        Ok(provider)
-    }
-
-    pub fn register(&mut self, name: &str, backend: Arc<dyn Backend>) {
-        if self.preferred.is_none() {
-            self.preferred = Some(name.to_string());
-        }
-        self.backends.insert(name.to_string(), backend);
-    }
-
-    pub async fn get_backend(&self, preferred_backend: Option<&str>) -> Result<Arc<dyn Backend>> {
-        // First try the preferred backend if specified
-        if let Some(name) = preferred_backend {
-            if let Some(backend) = self.backends.get(name) {
-                return Ok(Arc::clone(backend));
-            }
-        }
-
-        if let Some(preferred) = &self.preferred {
-            if let Some(backend) = self.backends.get(preferred) {
-                return Ok(Arc::clone(backend));
-            }
-        }
-
-        Err(VortexError::BackendUnavailable {
-            backend: "none available".to_string(),
-        })
-    }
-
-    pub fn has_backends(&self) -> bool {
-        !self.backends.is_empty()
-    }
-
-    pub fn new_empty() -> Self {
-        BackendProvider {
-            backends: HashMap::new(),
-            preferred: None,
-        }
-    }
-}
-
-// Krunvm Backend Implementation
-#[cfg(feature = "krunvm")]
 #[derive(Debug)]
-pub struct KrunvmBackend;
+pub struct KrunvmBackend {
+    children: Children,
+}
 
-#[cfg(feature = "krunvm")]
 impl KrunvmBackend {
     pub async fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self {
+            children: Children::default(),
+        })
     }
 
     /// Create a krunvm Command wrapped in buildah unshare
@@ -169,7 +64,6 @@ impl KrunvmBackend {
     }
 }
 
-#[cfg(feature = "krunvm")]
 #[async_trait]
 impl Backend for KrunvmBackend {
     async fn create(&self, vm: &VmInstance) -> Result<()> {
@@ -222,24 +116,43 @@ impl Backend for KrunvmBackend {
                     ),
                 });
             }
-            // For safe command execution, pass arguments directly
-            // Split the command into individual arguments safely
-            cmd.args(command.split_whitespace());
-        }
-
-        let output = cmd.output().await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let sanitized_stderr = sanitize_error_message(&stderr);
-            return Err(VortexError::VmError {
-                message: format!("krunvm start failed: {}", sanitized_stderr),
-            });
+            let prelude = boot_prelude(vm);
+            if prelude.is_empty() {
+                // For safe command execution, pass arguments directly
+                // Split the command into individual arguments safely
+                cmd.args(command.split_whitespace());
+            } else {
+                // The prelude is generated from a validated profile, the user
+                // command has already been checked for metacharacters above
+                cmd.arg("sh")
+                    .arg("-c")
+                    .arg(format!("{}exec {}", prelude, command));
+            }
+        } else if !boot_prelude(vm).is_empty() {
+            tracing::warn!(
+                "VM {} has provisioning or a tuning profile but no command; they are applied on attach",
+                vm.id
+            );
         }
 
+        // krunvm runs until the command exits; `wait` collects its status
+        let log = logs::create(&vm.id)?;
+        let child = cmd
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to start {}: {}", vm.id, e),
+            })?;
+        self.children.insert(&vm.id, child);
         Ok(())
     }
 
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        self.children.wait(&vm.id).await
+    }
+
     async fn stop(&self, vm: &VmInstance) -> Result<()> {
         // krunvm doesn't have a separate stop, it's part of cleanup
         self.cleanup(vm).await
@@ -277,7 +190,11 @@ impl Backend for KrunvmBackend {
         }
 
         // Build the shell command safely - construct it without allowing injection
-        let full_command = format!("export TERM=vt100; stty sane; exec {}", shell_command);
+        let full_command = format!(
+            "{}export TERM=vt100; stty sane; exec {}",
+            boot_prelude(vm),
+            shell_command
+        );
 
         let mut cmd = Self::krunvm_command();
         cmd.args(["start", &vm.id, "--"])
@@ -307,6 +224,10 @@ impl Backend for KrunvmBackend {
                     // SIGHUP - terminal disconnection, also normal
                     Ok(())
                 }
+                vortex_core::process::WINDOWS_CTRL_C_EXIT => {
+                    // Ctrl+C on a Windows console
+                    Ok(())
+                }
                 _ => {
                     // Other exit codes - still report as error for debugging
                     Err(VortexError::VmError {
@@ -341,6 +262,18 @@ impl Backend for KrunvmBackend {
         }
     }
 
+    async fn vmm_pids(&self, vm: &VmInstance) -> Result<Vec<u32>> {
+        // krunvm keeps no pid file; find the `krunvm start` process for this VM
+        let output = tokio::process::Command::new("pgrep")
+            .args(["-f", &format!("krunvm start {}( |$)", vm.id)])
+            .output()
+            .await?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|pid| pid.trim().parse().ok())
+            .collect())
+    }
+
     async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
         // Get basic VM info from krunvm
         let output = Self::krunvm_command().args(["list"]).output().await?;
@@ -359,7 +292,6 @@ impl Backend for KrunvmBackend {
 
         let stdout = String::from_utf8_lossy(&output.stdout);
         let mut memory_mb = vm.spec.memory;
-        let mut cpus = vm.spec.cpus;
 
         // Parse krunvm list output to get actual allocated resources
         let lines: Vec<&str> = stdout.lines().collect();
@@ -368,16 +300,7 @@ impl Backend for KrunvmBackend {
         for (i, line) in lines.iter().enumerate() {
             if line.trim() == vm.id {
                 found_vm = true;
-                // Look for CPU and RAM info in subsequent lines
-                if let Some(cpu_line) = lines.get(i + 1) {
-                    if cpu_line.contains("CPUs:") {
-                        if let Some(cpu_str) = cpu_line.split("CPUs:").nth(1) {
-                            if let Ok(parsed_cpus) = cpu_str.trim().parse::<u32>() {
-                                cpus = parsed_cpus;
-                            }
-                        }
-                    }
-                }
+                // RAM follows the CPUs line
                 if let Some(ram_line) = lines.get(i + 2) {
                     if ram_line.contains("RAM (MiB):") {
                         if let Some(ram_str) = ram_line.split("RAM (MiB):").nth(1) {
@@ -403,19 +326,22 @@ impl Backend for KrunvmBackend {
             });
         }
 
-        // Try to get system-level metrics for the VM process
-        let memory_total = (memory_mb as u64) * 1024 * 1024;
-        let estimated_memory_usage = memory_total / 2; // Rough estimate
-        let estimated_cpu_usage = if cpus > 0 { 10.0 / cpus as f64 } else { 5.0 }; // Rough estimate
+        // The VMM process is the VM: its CPU time and resident memory are
+        // what the guest uses
+        let pids = self.vmm_pids(vm).await.unwrap_or_default();
+        let stats = process_stats(&pids).await.unwrap_or_default();
+        let (network_rx, network_tx) = tsi::network_bytes(&pids).await.unwrap_or_default();
+        let uptime = (chrono::Utc::now() - vm.created_at).num_seconds().max(0) as u64;
 
         Ok(VmMetrics {
-            cpu_usage: estimated_cpu_usage,
-            memory_usage: estimated_memory_usage,
-            memory_total,
-            disk_usage: 100 * 1024 * 1024, // Estimate 100MB disk usage
-            network_rx: 1024,              // Small amounts for basic network activity
-            network_tx: 512,
-            uptime_seconds: 30, // Rough estimate - would need to track creation time
+            cpu_usage: stats.cpu_percent(),
+            memory_usage: stats.rss_bytes,
+            memory_total: (memory_mb as u64) * 1024 * 1024,
+            // Unknown: an estimate would trip disk quotas
+            disk_usage: 0,
+            network_rx,
+            network_tx,
+            uptime_seconds: uptime,
         })
     }
 
@@ -485,75 +411,9 @@ impl Backend for KrunvmBackend {
     fn name(&self) -> &'static str {
         "krunvm"
     }
-}
-
-// Firecracker Backend (placeholder)
-#[cfg(feature = "firecracker")]
-#[derive(Debug)]
-pub struct FirecrackerBackend;
-
-#[cfg(feature = "firecracker")]
-impl FirecrackerBackend {
-    pub async fn new() -> Result<Self> {
-        Ok(Self)
-    }
-}
-
-#[cfg(feature = "firecracker")]
-// TODO: Implement Firecracker backend (see https://github.com/exec/vortex/issues/123)
-#[async_trait]
-impl Backend for FirecrackerBackend {
-    async fn create(&self, _vm: &VmInstance) -> Result<()> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
-
-    async fn start(&self, _vm: &VmInstance) -> Result<()> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
-
-    async fn stop(&self, _vm: &VmInstance) -> Result<()> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
-
-    async fn cleanup(&self, _vm: &VmInstance) -> Result<()> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
-
-    async fn attach(&self, _vm: &VmInstance) -> Result<()> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
 
-    async fn get_metrics(&self, _vm: &VmInstance) -> Result<VmMetrics> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
-
-    async fn list_vms(&self) -> Result<Vec<String>> {
-        Err(VortexError::VmError {
-            message: "Firecracker backend not yet implemented".to_string(),
-        })
-    }
-
-    async fn is_available(&self) -> Result<bool> {
-        use tokio::process::Command;
-
-        let output = Command::new("which").arg("firecracker").output().await?;
-
-        Ok(output.status.success())
-    }
-
-    fn name(&self) -> &'static str {
-        "firecracker"
+    fn supports_share(&self, mechanism: ShareMechanism) -> bool {
+        // krunvm maps volumes over virtio-fs itself
+        mechanism == ShareMechanism::Virtiofs
     }
 }
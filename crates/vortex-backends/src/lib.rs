@@ -0,0 +1,154 @@
+//! # Vortex Backends
+//!
+//! [`Backend`] implementations for the VM technologies Vortex drives, each
+//! behind a feature of the same name, and [`probe`] to find the ones usable
+//! on this host. Library users who bring their own backend only need
+//! `vortex-core`.
+
+pub mod cgroup;
+#[cfg(any(feature = "krunvm", feature = "libkrun", feature = "wsl"))]
+pub(crate) mod children;
+#[cfg(feature = "cloud-hypervisor")]
+pub mod cloud_hypervisor;
+#[cfg(feature = "container")]
+pub mod container;
+#[cfg(feature = "firecracker")]
+pub mod firecracker;
+#[cfg(feature = "krunvm")]
+pub mod krunvm;
+#[cfg(feature = "libkrun")]
+pub mod libkrun;
+#[cfg(feature = "mock-backend")]
+pub mod mock;
+#[cfg(feature = "qemu")]
+pub mod qemu;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(any(feature = "krunvm", feature = "libkrun"))]
+pub(crate) mod tsi;
+#[cfg(any(feature = "cloud-hypervisor", feature = "libkrun", feature = "qemu"))]
+#[cfg_attr(
+    not(any(feature = "cloud-hypervisor", feature = "qemu")),
+    allow(dead_code)
+)]
+pub(crate) mod vmm;
+#[cfg(feature = "wsl")]
+pub mod wsl;
+
+use vortex_core::backend::BackendProvider;
+use vortex_core::error::Result;
+
+#[cfg(feature = "cloud-hypervisor")]
+pub use cloud_hypervisor::CloudHypervisorBackend;
+#[cfg(feature = "container")]
+pub use container::ContainerBackend;
+#[cfg(feature = "firecracker")]
+pub use firecracker::FirecrackerBackend;
+#[cfg(feature = "krunvm")]
+pub use krunvm::KrunvmBackend;
+#[cfg(feature = "libkrun")]
+pub use libkrun::LibkrunBackend;
+#[cfg(feature = "qemu")]
+pub use qemu::QemuBackend;
+#[cfg(feature = "remote")]
+pub use remote::RemoteBackend;
+#[cfg(feature = "wsl")]
+pub use wsl::WslBackend;
+
+/// Register every compiled-in backend that is usable on this host, in order
+/// of preference
+// Every backend feature may be disabled, leaving the provider empty
+#[allow(unused_imports, unused_mut)]
+pub async fn probe() -> Result<BackendProvider> {
+    use std::sync::Arc;
+    use vortex_core::backend::Backend;
+
+    let mut provider = BackendProvider::new_empty();
+
+    // Register available backends
+    // An explicitly configured remote host takes precedence over local VMs
+    #[cfg(feature = "remote")]
+    if let Some(remote) = RemoteBackend::from_env() {
+        if remote.is_available().await? {
+            provider.register("remote", Arc::new(remote));
+        }
+    }
+
+    #[cfg(feature = "libkrun")]
+    {
+        let libkrun = LibkrunBackend::new().await?;
+        if libkrun.is_available().await? {
+            provider.register("libkrun", Arc::new(libkrun));
+        }
+    }
+
+    #[cfg(feature = "krunvm")]
+    {
+        let krunvm = KrunvmBackend::new().await?;
+        // A missing krunvm binary must not hide the other backends
+        if krunvm.is_available().await.unwrap_or(false) {
+            provider.register("krunvm", Arc::new(krunvm));
+        }
+    }
+
+    #[cfg(feature = "firecracker")]
+    {
+        let firecracker = FirecrackerBackend::new().await?;
+        if firecracker.is_available().await? {
+            provider.register("firecracker", Arc::new(firecracker));
+        }
+    }
+
+    #[cfg(feature = "cloud-hypervisor")]
+    {
+        let cloud_hypervisor = CloudHypervisorBackend::new().await?;
+        if cloud_hypervisor.is_available().await? {
+            provider.register("cloud-hypervisor", Arc::new(cloud_hypervisor));
+        }
+    }
+
+    // The slowest VM backend, only preferred when no faster one is available
+    #[cfg(feature = "qemu")]
+    {
+        let qemu = QemuBackend::new().await?;
+        if qemu.is_available().await? {
+            provider.register("qemu", Arc::new(qemu));
+        }
+    }
+
+    // Only available on Windows, where none of the above run
+    #[cfg(feature = "wsl")]
+    {
+        let wsl = WslBackend::new().await?;
+        if wsl.is_available().await? {
+            provider.register("wsl", Arc::new(wsl));
+        }
+    }
+
+    // Containers share the host kernel, so they come after every VM backend
+    #[cfg(feature = "container")]
+    {
+        let container = ContainerBackend::new().await?;
+        if container.is_available().await? {
+            provider.register("container", Arc::new(container));
+        }
+    }
+
+    Ok(provider)
+}
+
+/// The backends available on this host, or none when probing fails
+pub async fn detect_backends() -> BackendProvider {
+    match probe().await {
+        Ok(provider) => provider,
+        Err(e) => {
+            // If no backends are available, return an empty provider
+            // This allows VortexCore to be initialized for config-only operations
+            tracing::warn!(
+                "No VM backends available: {}. Config-only operations will still work.",
+                e
+            );
+            BackendProvider::new_empty()
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! Backend that drives libkrun directly instead of the `krunvm` CLI.
+//!
+//! libkrun is loaded at runtime, so Vortex builds and runs on hosts without
+//! it and the backend simply reports itself unavailable. Every libkrun call
+//! returns a negative errno on failure, which is turned into an error naming
+//! the call instead of scraping CLI output.
+//!
+//! `krun_start_enter` never returns: it turns the calling process into the VM.
+//! The backend therefore writes a launch config into
+//! `~/.vortex/libkrun/vms/<vm-id>/` and runs `vortex __libkrun-enter <config>`
+//! in a child process, whose PID it tracks for stop, cleanup and metrics.
+//!
+//! libkrun boots a root directory rather than an OCI image. `spec.image` is
+//! looked up as `~/.vortex/libkrun/images/<image>/` (`/` and `:` replaced by
+//! `_`), e.g. a directory filled by `podman export`, and copied per VM.
+//! Without one, the image's rootfs tarball, pulled from its registry if
+//! there is none (see `image_store`), is unpacked once into the prepared
+//! image cache (see `image_cache`).
+//!
+//! A `virtio` GPU is a virtio-gpu device rendering Vulkan on the host's GPU
+//! through Venus, which needs a libkrun built with GPU support.
+
+use crate::cgroup::VmCgroup;
+use crate::children::Children;
+use crate::tsi;
+use crate::vmm::{image_key, kill_pid, process_rss};
+use async_trait::async_trait;
+use libloading::{Library, Symbol};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::ffi::{c_char, CString};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use vortex_core::backend::{boot_prelude, Backend, ExitStatus, VmMetrics};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::image_cache::{ImageCache, PreparedFormat};
+use vortex_core::image_store::ImageStore;
+use vortex_core::logs;
+use vortex_core::snapshot::dir_size;
+use vortex_core::storage;
+use vortex_core::vm::{GpuDevice, ShareMechanism, VmInstance};
+
+/// Hidden CLI subcommand that runs a VM in the current process
+pub const ENTER_SUBCOMMAND: &str = "__libkrun-enter";
+
+const LIBRARY_NAMES: &[&str] = &[
+    "libkrun.so.1",
+    "libkrun.so",
+    "libkrun.dylib",
+    "/opt/homebrew/lib/libkrun.dylib",
+];
+const ROOTFS: &str = "rootfs";
+const LAUNCH_CONFIG: &str = "launch.json";
+const VM_PID: &str = "vm.pid";
+/// How long `start` watches the VM process for an immediate failure
+const STARTUP_GRACE: Duration = Duration::from_millis(500);
+/// virglrenderer flags for `krun_set_gpu_options`: Vulkan through Venus, no
+/// OpenGL
+const VIRGLRENDERER_VENUS: u32 = 1 << 6;
+const VIRGLRENDERER_NO_VIRGL: u32 = 1 << 7;
+
+/// Everything the `__libkrun-enter` process needs to configure the VM
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchConfig {
+    pub cpus: u8,
+    pub memory_mib: u32,
+    pub root: PathBuf,
+    /// `host:guest` directory mappings
+    pub volumes: Vec<String>,
+    /// `host:guest` TCP port mappings
+    pub ports: Vec<String>,
+    /// `KEY=value` guest environment
+    pub env: Vec<String>,
+    pub exec: String,
+    pub args: Vec<String>,
+    /// Attach a virtio-gpu using the host's GPU through Venus (Vulkan)
+    #[serde(default)]
+    pub gpu: bool,
+}
+
+impl LaunchConfig {
+    fn new(vm: &VmInstance, root: PathBuf, script: String) -> Result<Self> {
+        let cpus = u8::try_from(vm.spec.cpus).map_err(|_| VortexError::InvalidInput {
+            field: "cpus".to_string(),
+            message: format!("libkrun supports at most 255 vCPUs, got {}", vm.spec.cpus),
+        })?;
+
+        let mut volumes: Vec<String> = vm
+            .spec
+            .volumes
+            .iter()
+            .map(|(host, guest)| format!("{}:{}", host.display(), guest.display()))
+            .collect();
+        volumes.sort();
+        let mut ports: Vec<String> = vm
+            .spec
+            .ports
+            .iter()
+            .map(|(host, guest)| format!("{}:{}", host, guest))
+            .collect();
+        ports.sort();
+        let mut env: Vec<String> = vm
+            .spec
+            .environment
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        env.sort();
+        env.push("TERM=vt100".to_string());
+
+        Ok(Self {
+            cpus,
+            memory_mib: vm.spec.memory,
+            root,
+            volumes,
+            ports,
+            env,
+            exec: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), script],
+            gpu: vm.spec.gpus.contains(&GpuDevice::Virtio),
+        })
+    }
+}
+
+fn c_string(value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| VortexError::InvalidInput {
+        field: "libkrun".to_string(),
+        message: format!("Value contains a NUL byte: {}", value),
+    })
+}
+
+/// Owned C strings plus the NULL-terminated pointer array libkrun expects
+struct CStringArray {
+    _strings: Vec<CString>,
+    pointers: Vec<*const c_char>,
+}
+
+impl CStringArray {
+    fn new(values: &[String]) -> Result<Self> {
+        let strings = values
+            .iter()
+            .map(|v| c_string(v))
+            .collect::<Result<Vec<_>>>()?;
+        let mut pointers: Vec<*const c_char> = strings.iter().map(|s| s.as_ptr()).collect();
+        pointers.push(std::ptr::null());
+        Ok(Self {
+            _strings: strings,
+            pointers,
+        })
+    }
+
+    fn as_ptr(&self) -> *const *const c_char {
+        self.pointers.as_ptr()
+    }
+}
+
+/// Turn a libkrun return code into an error naming the failed call
+fn check(call: &str, ret: i32) -> Result<u32> {
+    if ret < 0 {
+        return Err(VortexError::VmError {
+            message: format!(
+                "{} failed: {}",
+                call,
+                std::io::Error::from_raw_os_error(-ret)
+            ),
+        });
+    }
+    Ok(ret as u32)
+}
+
+fn load_library() -> Result<Library> {
+    let mut last_error = None;
+    for name in LIBRARY_NAMES {
+        // SAFETY: loading libkrun runs no initialisation code with preconditions
+        match unsafe { Library::new(name) } {
+            Ok(library) => return Ok(library),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(VortexError::BackendUnavailable {
+        backend: format!(
+            "libkrun ({})",
+            last_error.map_or_else(|| "not found".to_string(), |e| e.to_string())
+        ),
+    })
+}
+
+/// Configure a libkrun context from `config` and boot it in this process.
+/// Only returns if configuration or boot fails.
+pub fn enter(config: &LaunchConfig) -> Result<Infallible> {
+    type CreateCtx = unsafe extern "C" fn() -> i32;
+    type SetVmConfig = unsafe extern "C" fn(u32, u8, u32) -> i32;
+    type SetPath = unsafe extern "C" fn(u32, *const c_char) -> i32;
+    type SetList = unsafe extern "C" fn(u32, *const *const c_char) -> i32;
+    type SetExec =
+        unsafe extern "C" fn(u32, *const c_char, *const *const c_char, *const *const c_char) -> i32;
+    type SetGpuOptions = unsafe extern "C" fn(u32, u32) -> i32;
+    type StartEnter = unsafe extern "C" fn(u32) -> i32;
+
+    let library = load_library()?;
+    let symbol = |name: &str| VortexError::BackendUnavailable {
+        backend: format!("libkrun (missing symbol {})", name),
+    };
+
+    let root = c_string(&config.root.display().to_string())?;
+    let volumes = CStringArray::new(&config.volumes)?;
+    let ports = CStringArray::new(&config.ports)?;
+    let workdir = c_string("/")?;
+    let exec = c_string(&config.exec)?;
+    let args = CStringArray::new(&config.args)?;
+    let env = CStringArray::new(&config.env)?;
+
+    // SAFETY: the signatures match libkrun.h, every pointer refers to a
+    // NUL-terminated string or NULL-terminated array that outlives the call
+    unsafe {
+        let create_ctx: Symbol<CreateCtx> = library
+            .get(b"krun_create_ctx\0")
+            .map_err(|_| symbol("krun_create_ctx"))?;
+        let set_vm_config: Symbol<SetVmConfig> = library
+            .get(b"krun_set_vm_config\0")
+            .map_err(|_| symbol("krun_set_vm_config"))?;
+        let set_root: Symbol<SetPath> = library
+            .get(b"krun_set_root\0")
+            .map_err(|_| symbol("krun_set_root"))?;
+        let set_mapped_volumes: Symbol<SetList> = library
+            .get(b"krun_set_mapped_volumes\0")
+            .map_err(|_| symbol("krun_set_mapped_volumes"))?;
+        let set_port_map: Symbol<SetList> = library
+            .get(b"krun_set_port_map\0")
+            .map_err(|_| symbol("krun_set_port_map"))?;
+        let set_workdir: Symbol<SetPath> = library
+            .get(b"krun_set_workdir\0")
+            .map_err(|_| symbol("krun_set_workdir"))?;
+        let set_exec: Symbol<SetExec> = library
+            .get(b"krun_set_exec\0")
+            .map_err(|_| symbol("krun_set_exec"))?;
+        let start_enter: Symbol<StartEnter> = library
+            .get(b"krun_start_enter\0")
+            .map_err(|_| symbol("krun_start_enter"))?;
+
+        let ctx = check("krun_create_ctx", create_ctx())?;
+        check(
+            "krun_set_vm_config",
+            set_vm_config(ctx, config.cpus, config.memory_mib),
+        )?;
+        check("krun_set_root", set_root(ctx, root.as_ptr()))?;
+        check(
+            "krun_set_mapped_volumes",
+            set_mapped_volumes(ctx, volumes.as_ptr()),
+        )?;
+        check("krun_set_port_map", set_port_map(ctx, ports.as_ptr()))?;
+        check("krun_set_workdir", set_workdir(ctx, workdir.as_ptr()))?;
+        check(
+            "krun_set_exec",
+            set_exec(ctx, exec.as_ptr(), args.as_ptr(), env.as_ptr()),
+        )?;
+        if config.gpu {
+            // Only in libkrun builds with GPU support
+            let set_gpu_options: Symbol<SetGpuOptions> = library
+                .get(b"krun_set_gpu_options\0")
+                .map_err(|_| symbol("krun_set_gpu_options"))?;
+            check(
+                "krun_set_gpu_options",
+                set_gpu_options(ctx, VIRGLRENDERER_VENUS | VIRGLRENDERER_NO_VIRGL),
+            )?;
+        }
+        check("krun_start_enter", start_enter(ctx))?;
+    }
+
+    Err(VortexError::VmError {
+        message: "krun_start_enter returned without starting the VM".to_string(),
+    })
+}
+
+/// Read a launch config written by the backend
+pub fn load_launch_config(path: &Path) -> Result<LaunchConfig> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[derive(Debug)]
+pub struct LibkrunBackend {
+    root: PathBuf,
+    children: Children,
+}
+
+impl LibkrunBackend {
+    pub async fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self {
+            root: home.join(".vortex").join("libkrun"),
+            children: Children::default(),
+        })
+    }
+
+    fn vm_dir(&self, vm_id: &str) -> PathBuf {
+        self.root.join("vms").join(vm_id)
+    }
+
+    fn image_dir(&self, image: &str) -> PathBuf {
+        self.root.join("images").join(image_key(image))
+    }
+
+    /// Write the launch config for `script` and build the VM process command
+    async fn launch_command(
+        &self,
+        vm: &VmInstance,
+        script: String,
+    ) -> Result<tokio::process::Command> {
+        let dir = self.vm_dir(&vm.id);
+        let config = LaunchConfig::new(vm, dir.join(ROOTFS), script)?;
+        let config_path = dir.join(LAUNCH_CONFIG);
+        tokio::fs::write(&config_path, serde_json::to_string_pretty(&config)?).await?;
+
+        let exe = std::env::current_exe()?;
+        let mut cmd = tokio::process::Command::new(exe);
+        cmd.arg(ENTER_SUBCOMMAND).arg(config_path);
+        Ok(cmd)
+    }
+
+    /// PID of the VM process, if it is still running
+    async fn vm_pid(&self, vm_id: &str) -> Option<String> {
+        let pid = tokio::fs::read_to_string(self.vm_dir(vm_id).join(VM_PID))
+            .await
+            .ok()?;
+        let pid = pid.trim().to_string();
+        let alive = tokio::process::Command::new("kill")
+            .args(["-0", &pid])
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success());
+        alive.then_some(pid)
+    }
+}
+
+#[async_trait]
+impl Backend for LibkrunBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        let mut image_dir = self.image_dir(&vm.spec.image);
+        if !image_dir.is_dir() {
+            ImageStore::new()?.ensure(&vm.spec.image).await?;
+            let cache = ImageCache::new()?;
+            image_dir = cache
+                .prepare(&vm.spec.image, PreparedFormat::Directory)
+                .await?
+                .ok_or_else(|| VortexError::VmError {
+                    message: format!(
+                        "libkrun root filesystem not found at {} or as a tarball at {}",
+                        image_dir.display(),
+                        cache.source_path(&vm.spec.image).display()
+                    ),
+                })?;
+        }
+
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        // Reflinked where the filesystem allows, so VMs share the image's
+        // blocks and store only the files they change
+        if let Err(e) = storage::clone_tree(&image_dir, &dir.join(ROOTFS)).await {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            return Err(VortexError::VmError {
+                message: format!("Failed to copy root filesystem for {}: {}", vm.id, e),
+            });
+        }
+        Ok(())
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        let Some(command) = &vm.spec.command else {
+            return Ok(());
+        };
+        let script = format!("{}{}", boot_prelude(vm), command);
+
+        let dir = self.vm_dir(&vm.id);
+        let log = logs::create(&vm.id)?;
+        let mut child = self
+            .launch_command(vm, script)
+            .await?
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()?;
+        if let Some(pid) = child.id() {
+            tokio::fs::write(dir.join(VM_PID), pid.to_string()).await?;
+            VmCgroup::confine(&vm.id, &vm.spec, &[pid]);
+        }
+
+        // Configuration errors surface right away; a running VM keeps going
+        if let Ok(status) = tokio::time::timeout(STARTUP_GRACE, child.wait()).await {
+            let status = status?;
+            if !status.success() {
+                let log = tokio::fs::read_to_string(logs::log_path(&vm.id)?)
+                    .await
+                    .unwrap_or_default();
+                return Err(VortexError::VmError {
+                    message: format!("libkrun VM exited with {}: {}", status, log.trim()),
+                });
+            }
+        }
+        self.children.insert(&vm.id, child);
+        Ok(())
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        self.children.wait(&vm.id).await
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        if let Some(pid) = self.vm_pid(&vm.id).await {
+            kill_pid(&pid).await;
+        }
+        let _ = tokio::fs::remove_file(self.vm_dir(&vm.id).join(VM_PID)).await;
+        Ok(())
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        self.stop(vm).await?;
+        if let Some(cgroup) = VmCgroup::for_vm(&vm.id) {
+            cgroup.remove().await;
+        }
+        match tokio::fs::remove_dir_all(self.vm_dir(&vm.id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        let shell = vm.spec.command.as_deref().unwrap_or("sh");
+        let script = format!(
+            "{}export TERM=vt100; stty sane; exec {}",
+            boot_prelude(vm),
+            shell
+        );
+
+        let dir = self.vm_dir(&vm.id);
+        let mut child = self
+            .launch_command(vm, script)
+            .await?
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        if let Some(pid) = child.id() {
+            tokio::fs::write(dir.join(VM_PID), pid.to_string()).await?;
+            VmCgroup::confine(&vm.id, &vm.spec, &[pid]);
+        }
+
+        let status = child.wait().await?;
+        let _ = tokio::fs::remove_file(dir.join(VM_PID)).await;
+        match status.code() {
+            // Normal exit, Ctrl-C and hangup all end a session normally
+            Some(0) | Some(130) | Some(129) | None => Ok(()),
+            Some(code) => Err(VortexError::VmError {
+                message: format!("Interactive session ended with exit code: {}", code),
+            }),
+        }
+    }
+
+    async fn vmm_pids(&self, vm: &VmInstance) -> Result<Vec<u32>> {
+        Ok(self
+            .vm_pid(&vm.id)
+            .await
+            .and_then(|pid| pid.parse().ok())
+            .into_iter()
+            .collect())
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        let uptime_seconds = (chrono::Utc::now() - vm.created_at).num_seconds().max(0) as u64;
+        let usage = VmCgroup::for_vm(&vm.id).and_then(|cgroup| cgroup.usage());
+        let pid = self.vm_pid(&vm.id).await;
+        let memory_usage = match (usage, &pid) {
+            (Some(usage), _) => usage.memory_bytes,
+            (None, Some(pid)) => process_rss(pid).unwrap_or(0),
+            (None, None) => 0,
+        };
+        let pids: Vec<u32> = pid.iter().filter_map(|pid| pid.parse().ok()).collect();
+        let (network_rx, network_tx) = tsi::network_bytes(&pids).await.unwrap_or_default();
+        let rootfs = self.vm_dir(&vm.id).join(ROOTFS);
+        let disk_usage = tokio::task::spawn_blocking(move || dir_size(&rootfs))
+            .await
+            .unwrap_or(0);
+
+        Ok(VmMetrics {
+            // CPU time needs the cgroup
+            cpu_usage: usage.map_or(0.0, |usage| usage.cpu_percent(uptime_seconds)),
+            memory_usage,
+            memory_total: u64::from(vm.spec.memory) * 1024 * 1024,
+            disk_usage,
+            network_rx,
+            network_tx,
+            uptime_seconds,
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        let Ok(mut entries) = tokio::fs::read_dir(self.root.join("vms")).await else {
+            return Ok(Vec::new());
+        };
+
+        let mut vms = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(vm_id) = entry.file_name().to_str() {
+                vms.push(vm_id.to_string());
+            }
+        }
+        vms.sort();
+        Ok(vms)
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        if cfg!(target_os = "linux") && !Path::new("/dev/kvm").exists() {
+            return Ok(false);
+        }
+        Ok(load_library().is_ok())
+    }
+
+    fn name(&self) -> &'static str {
+        "libkrun"
+    }
+
+    fn supports_gpu(&self, gpu: &GpuDevice) -> bool {
+        *gpu == GpuDevice::Virtio
+    }
+
+    fn supports_share(&self, mechanism: ShareMechanism) -> bool {
+        // libkrun maps volumes over virtio-fs itself
+        mechanism == ShareMechanism::Virtiofs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_maps_errno() {
+        assert_eq!(check("krun_create_ctx", 3).unwrap(), 3);
+        let err = check("krun_set_root", -2).unwrap_err().to_string();
+        assert!(err.contains("krun_set_root failed"), "{}", err);
+        assert!(err.contains("os error 2"), "{}", err);
+
+        let array = CStringArray::new(&["8080:80".to_string()]).unwrap();
+        assert_eq!(array.pointers.len(), 2);
+        assert!(array.pointers[1].is_null());
+    }
+}
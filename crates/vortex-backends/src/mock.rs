@@ -0,0 +1,170 @@
+//! In-memory backend for benchmarks and tests, built with the
+//! `mock-backend` feature.
+//!
+//! VMs are only entries in a set and every call returns at once, so timing a
+//! `VmManager` operation against this backend measures Vortex's own overhead.
+
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use vortex_core::backend::{
+    Backend, BackendProvider, ExecOptions, ExecResult, ExitStatus, VmMetrics,
+};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::vm::{ShareMechanism, VmInstance, VmManager};
+
+/// A `VmManager` whose only backend is a new `MockBackend`, handed back
+/// too so callers can look at the VMs it holds
+pub fn mock_manager() -> (VmManager, Arc<MockBackend>) {
+    let backend = Arc::new(MockBackend::new());
+    let mut provider = BackendProvider::new_empty();
+    provider.register("mock", backend.clone());
+    (VmManager::with_backends(provider), backend)
+}
+
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    vms: Mutex<BTreeSet<String>>,
+    disk_usage: AtomicU64,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disk usage reported for every VM from now on
+    pub fn set_disk_usage(&self, bytes: u64) {
+        self.disk_usage.store(bytes, Ordering::Relaxed);
+    }
+
+    fn vms(&self) -> std::sync::MutexGuard<'_, BTreeSet<String>> {
+        // A panic while holding the lock cannot leave the set inconsistent
+        self.vms
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn require(&self, vm: &VmInstance) -> Result<()> {
+        if self.vms().contains(vm.id.as_str()) {
+            Ok(())
+        } else {
+            Err(VortexError::VmError {
+                message: format!("VM {} not found", vm.id),
+            })
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for MockBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        self.vms().insert(vm.id.to_string());
+        Ok(())
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        self.require(vm)
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        self.require(vm)
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        self.require(vm)?;
+        Ok(ExitStatus { code: 0 })
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        self.vms().remove(vm.id.as_str());
+        Ok(())
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        self.require(vm)
+    }
+
+    async fn pause(&self, vm: &VmInstance) -> Result<()> {
+        self.require(vm)
+    }
+
+    async fn resume(&self, vm: &VmInstance) -> Result<()> {
+        self.require(vm)
+    }
+
+    async fn resize(
+        &self,
+        vm: &VmInstance,
+        _memory: Option<u32>,
+        _cpus: Option<u32>,
+    ) -> Result<()> {
+        self.require(vm)
+    }
+
+    async fn exec(
+        &self,
+        vm: &VmInstance,
+        command: &[String],
+        _options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        self.require(vm)?;
+        Ok(ExecResult {
+            stdout: format!("{}\n", command.join(" ")),
+            stderr: String::new(),
+            exit_code: 0,
+        })
+    }
+
+    async fn clone_vm(&self, source: &VmInstance, vm: &VmInstance) -> Result<()> {
+        self.require(source)?;
+        self.vms().insert(vm.id.to_string());
+        Ok(())
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        self.require(vm)?;
+        Ok(VmMetrics {
+            cpu_usage: 0.0,
+            memory_usage: 0,
+            memory_total: u64::from(vm.spec.memory) * 1024 * 1024,
+            disk_usage: self.disk_usage.load(Ordering::Relaxed),
+            network_rx: 0,
+            network_tx: 0,
+            uptime_seconds: 0,
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        Ok(self.vms().iter().cloned().collect())
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn supports_prewarm(&self) -> bool {
+        true
+    }
+
+    fn supports_network_isolation(&self) -> bool {
+        true
+    }
+
+    fn supports_egress_proxy(&self) -> bool {
+        true
+    }
+
+    fn supports_port_addresses(&self) -> bool {
+        true
+    }
+
+    fn supports_share(&self, _mechanism: ShareMechanism) -> bool {
+        true
+    }
+}
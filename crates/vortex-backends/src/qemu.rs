@@ -0,0 +1,890 @@
+//! QEMU backend, the fallback for hosts without krunvm or Cloud Hypervisor.
+//!
+//! Each VM runs a daemonized `qemu-system-*` process using the `microvm`
+//! machine type on x86_64 (`virt` on aarch64), controlled over a QMP socket in
+//! `~/.vortex/qemu/vms/<vm-id>/`. Like the Cloud Hypervisor backend it boots
+//! raw disk images, looked up as `~/.vortex/qemu/images/<image>.raw` or prepared
+//! from a rootfs tarball, and booted with the kernel at `~/.vortex/qemu/vmlinux`. KVM or HVF is used when
+//! present; otherwise QEMU falls back to TCG emulation, which is slow but works
+//! anywhere.
+//!
+//! Volumes are shared over virtio-9p, or over virtio-fs for those asking
+//! for it (one `virtiofsd` each, with guest memory in a shared memfd), and
+//! ports are forwarded by QEMU's user networking. Mounts and commands are typed into the guest console on `hvc0`.
+//! GPUs are passed through with `vfio-pci`, on a PCIe bus added to `microvm`.
+//! A VM on a private network gets a second NIC on a multicast socket shared
+//! by the network's members, configured from the console like the mounts.
+//! VMs under a `host-only` or `allow` network policy have their user
+//! networking restricted, so the only way out is a forward to the egress
+//! proxy on the host.
+//!
+//! A VM's disk is a qcow2 delta over a base layer shared by every VM
+//! created from the same image (see `vortex_core::storage`), so creating
+//! one copies nothing and it stores only what it writes.
+//!
+//! Snapshots stop the VM, copy its disk and migrate its state to a file
+//! (QEMU 8.2 or newer); a restore boots the same devices with `-incoming`.
+//! QEMU refuses to migrate while a volume is mounted, so VMs with volumes
+//! cannot be snapshotted. Clones have no such limit: they copy the disk of
+//! the stopped source VM and boot it with their own spec.
+
+use crate::cgroup::VmCgroup;
+use crate::vmm::{
+    attach_console, check_disk_limit, console_exec, console_wait, disk_image, kill_pid, load_spec,
+    mount_script, network_script, process_rss, record_exit, save_spec, send_to_console, shares,
+    start_virtiofsd, wait_for_path, KERNEL_CMDLINE, SPEC_FILE, VIRTIOFSD_PIDS,
+};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use vortex_core::backend::{
+    boot_prelude, exec_script, Backend, ExecOptions, ExecResult, ExitStatus, VmMetrics,
+};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::ids::LABEL_EGRESS_PROXY;
+use vortex_core::logs;
+use vortex_core::network::{NetworkManager, VmNetwork};
+use vortex_core::storage::{self, LayerStore};
+use vortex_core::vm::{GpuDevice, ShareMechanism, VmInstance, VmSpec};
+
+const QMP_SOCKET: &str = "qmp.sock";
+/// The VM's disk: a qcow2 delta, or a raw copy of the image without qemu-img
+const ROOTFS: &str = "rootfs.raw";
+const QEMU_PID: &str = "qemu.pid";
+const CONSOLE_CHARDEV: &str = "con0";
+const NINEP_MOUNT: &str = "-t 9p -o trans=virtio,version=9p2000.L";
+/// Migration stream inside a snapshot's state directory
+const VM_STATE: &str = "vmstate";
+const MIGRATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Where restricted guests reach the egress proxy, on the user network
+const EGRESS_PROXY: &str = "10.0.2.100:3128";
+/// The host, as the user network's gateway
+const HOST_GATEWAY: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+
+/// QEMU binary and machine type for the host architecture
+fn qemu_system() -> (&'static str, &'static str) {
+    match std::env::consts::ARCH {
+        "aarch64" => ("qemu-system-aarch64", "virt"),
+        _ => ("qemu-system-x86_64", "microvm"),
+    }
+}
+
+/// Hardware acceleration available on this host, `tcg` when there is none
+fn accelerator() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "hvf"
+    } else if Path::new("/dev/kvm").exists() {
+        "kvm"
+    } else {
+        "tcg"
+    }
+}
+
+/// Shell line pointing the guest's HTTP clients at the egress proxy, for
+/// VMs whose network policy needs one
+fn proxy_script(spec: &VmSpec) -> String {
+    if !spec.network_policy.needs_proxy() {
+        return String::new();
+    }
+    format!(
+        "export http_proxy=http://{0} https_proxy=http://{0} HTTP_PROXY=http://{0} HTTPS_PROXY=http://{0}; ",
+        EGRESS_PROXY
+    )
+}
+
+/// Command line for a VM whose files live in `dir`, with a second NIC on
+/// the private network `nic`
+fn qemu_args(
+    spec: &VmSpec,
+    nic: Option<&VmNetwork>,
+    kernel: &Path,
+    dir: &Path,
+    log: &Path,
+    machine: &str,
+    accel: &str,
+) -> Vec<String> {
+    let cpu = if accel == "tcg" { "max" } else { "host" };
+    let vfio: Vec<&str> = spec
+        .gpus
+        .iter()
+        .filter_map(|gpu| match gpu {
+            GpuDevice::Vfio { address } => Some(address.as_str()),
+            GpuDevice::Virtio => None,
+        })
+        .collect();
+    let shares = shares(spec);
+    let virtiofs = shares
+        .iter()
+        .any(|share| share.mechanism == Some(ShareMechanism::Virtiofs));
+    let mut machine = format!("{},accel={}", machine, accel);
+    // microvm has no PCI bus to pass devices through on unless asked for one
+    if !vfio.is_empty() && machine.starts_with("microvm") {
+        machine.push_str(",pcie=on");
+    }
+    // virtiofsd maps guest memory, so it has to be shareable
+    if virtiofs {
+        machine.push_str(",memory-backend=mem");
+    }
+    let mut args: Vec<String> = vec![
+        "-machine".into(),
+        machine,
+        "-cpu".into(),
+        cpu.into(),
+        "-nodefaults".into(),
+        "-no-user-config".into(),
+        "-display".into(),
+        "none".into(),
+        "-m".into(),
+        spec.memory.to_string(),
+        "-smp".into(),
+        spec.cpus.to_string(),
+        "-kernel".into(),
+        kernel.display().to_string(),
+        "-append".into(),
+        KERNEL_CMDLINE.into(),
+        "-drive".into(),
+        format!(
+            "id=root,file={},format={},if=none",
+            dir.join(ROOTFS).display(),
+            if storage::is_qcow2(&dir.join(ROOTFS)) {
+                "qcow2"
+            } else {
+                "raw"
+            }
+        ),
+        "-device".into(),
+        "virtio-blk-device,drive=root".into(),
+    ];
+
+    // -nodefaults leaves the VM without a NIC unless one is added here
+    if !spec.network_disabled() {
+        let mut ports: Vec<_> = spec.ports.iter().collect();
+        ports.sort();
+        let mut netdev = "user,id=net0".to_string();
+        for (host, guest) in ports {
+            match spec.port_addresses.get(host) {
+                Some(ip) => netdev.push_str(&format!(
+                    ",hostfwd=tcp:{}-:{}",
+                    SocketAddr::new(*ip, *host),
+                    guest
+                )),
+                None => netdev.push_str(&format!(",hostfwd=tcp::{}-:{}", host, guest)),
+            }
+        }
+        if spec.network_policy.needs_proxy() {
+            // Without a proxy port the guest can reach nothing at all
+            netdev.push_str(",restrict=on");
+            let proxy = spec.labels.get(LABEL_EGRESS_PROXY);
+            if let Some(port) = proxy.and_then(|port| port.parse::<u16>().ok()) {
+                netdev.push_str(&format!(
+                    ",guestfwd=tcp:{}-tcp:127.0.0.1:{}",
+                    EGRESS_PROXY, port
+                ));
+            }
+        }
+        args.extend([
+            "-netdev".into(),
+            netdev,
+            "-device".into(),
+            "virtio-net-device,netdev=net0".into(),
+        ]);
+    }
+    if let Some(nic) = nic {
+        // Bound to loopback, so the segment never leaves the host
+        args.extend([
+            "-netdev".into(),
+            format!(
+                "socket,id=net1,mcast={},localaddr=127.0.0.1",
+                nic.multicast_group()
+            ),
+            "-device".into(),
+            format!("virtio-net-device,netdev=net1,mac={}", nic.mac_address),
+        ]);
+    }
+
+    for address in vfio {
+        args.extend(["-device".into(), format!("vfio-pci,host={}", address)]);
+    }
+
+    if virtiofs {
+        args.extend([
+            "-object".into(),
+            format!("memory-backend-memfd,id=mem,size={}M,share=on", spec.memory),
+        ]);
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.mechanism == Some(ShareMechanism::Virtiofs) {
+            args.extend([
+                "-chardev".into(),
+                format!("socket,id=fs{},path={}", i, share.socket(dir).display()),
+                "-device".into(),
+                format!("vhost-user-fs-device,chardev=fs{},tag={}", i, share.tag),
+            ]);
+            continue;
+        }
+        args.extend([
+            "-fsdev".into(),
+            format!(
+                "local,id=fs{},path={},security_model=none",
+                i,
+                share.host.display()
+            ),
+            "-device".into(),
+            format!("virtio-9p-device,fsdev=fs{},mount_tag={}", i, share.tag),
+        ]);
+    }
+
+    args.extend([
+        "-chardev".into(),
+        // A restored VM keeps appending to the log of the original
+        format!(
+            "pty,id={},logfile={},logappend=on",
+            CONSOLE_CHARDEV,
+            log.display()
+        ),
+        "-device".into(),
+        "virtio-serial-device".into(),
+        "-device".into(),
+        format!("virtconsole,chardev={}", CONSOLE_CHARDEV),
+        "-qmp".into(),
+        format!("unix:{},server=on,wait=off", dir.join(QMP_SOCKET).display()),
+        "-pidfile".into(),
+        dir.join(QEMU_PID).display().to_string(),
+        "-daemonize".into(),
+    ]);
+    args
+}
+
+/// Interpret one QMP line: `None` for the greeting and async events,
+/// otherwise the command's return value or its error
+fn parse_qmp_line(line: &str) -> Result<Option<Value>> {
+    let message: Value = serde_json::from_str(line)?;
+    if let Some(value) = message.get("return") {
+        return Ok(Some(value.clone()));
+    }
+    if let Some(error) = message.get("error") {
+        return Err(VortexError::VmError {
+            message: format!(
+                "QMP error: {}",
+                error["desc"].as_str().unwrap_or("unknown error")
+            ),
+        });
+    }
+    Ok(None)
+}
+
+/// Run one QMP command on a fresh connection
+async fn qmp_command(socket: &Path, execute: &str, arguments: Option<Value>) -> Result<Value> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| VortexError::VmError {
+            message: format!("QEMU monitor unreachable: {}", e),
+        })?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    let mut commands = vec![json!({ "execute": "qmp_capabilities" })];
+    let mut command = json!({ "execute": execute });
+    if let Some(arguments) = arguments {
+        command["arguments"] = arguments;
+    }
+    commands.push(command);
+
+    let mut result = Value::Null;
+    for command in commands {
+        writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .await?;
+        result = loop {
+            let Some(line) = lines.next_line().await? else {
+                // `quit` closes the monitor before or instead of replying
+                if execute == "quit" {
+                    return Ok(Value::Null);
+                }
+                return Err(VortexError::VmError {
+                    message: format!("QEMU monitor closed during {}", execute),
+                });
+            };
+            if let Some(value) = parse_qmp_line(&line)? {
+                break value;
+            }
+        };
+    }
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct QemuBackend {
+    root: PathBuf,
+}
+
+impl QemuBackend {
+    pub async fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self {
+            root: home.join(".vortex").join("qemu"),
+        })
+    }
+
+    fn kernel_path(&self) -> PathBuf {
+        self.root.join("vmlinux")
+    }
+
+    fn vm_dir(&self, vm_id: &str) -> PathBuf {
+        self.root.join("vms").join(vm_id)
+    }
+
+    async fn qmp(&self, vm_id: &str, execute: &str, arguments: Option<Value>) -> Result<Value> {
+        qmp_command(&self.vm_dir(vm_id).join(QMP_SOCKET), execute, arguments).await
+    }
+
+    /// Start QEMU for a VM in `dir`, loading its state from `incoming` when
+    /// restoring a snapshot
+    async fn launch(
+        &self,
+        vm_id: &str,
+        spec: &VmSpec,
+        dir: &Path,
+        incoming: Option<&Path>,
+    ) -> Result<()> {
+        save_spec(dir, spec).await?;
+        let (binary, machine) = qemu_system();
+        let log = logs::log_path(vm_id)?;
+        let nic = NetworkManager::new().await?.get_vm_network(vm_id).await?;
+        let mut args = qemu_args(
+            spec,
+            nic.as_ref(),
+            &self.kernel_path(),
+            dir,
+            &log,
+            machine,
+            accelerator(),
+        );
+        if let Some(state) = incoming {
+            args.extend(["-incoming".into(), format!("file:{}", state.display())]);
+        }
+        let virtiofs: Vec<_> = shares(spec)
+            .into_iter()
+            .filter(|share| share.mechanism == Some(ShareMechanism::Virtiofs))
+            .collect();
+        let (mut pids, _) = start_virtiofsd(&virtiofs, dir).await?;
+
+        // With -daemonize the parent exits once the VM is set up
+        let output = tokio::process::Command::new(binary)
+            .args(&args)
+            .output()
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to start {}: {}", binary, e),
+            })?;
+        if !output.status.success() {
+            return Err(VortexError::VmError {
+                message: format!(
+                    "{} failed: {}",
+                    binary,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+
+        // Guest RAM is faulted in lazily, so confining after -daemonize
+        // still charges nearly all of it to the cgroup
+        pids.extend(
+            tokio::fs::read_to_string(dir.join(QEMU_PID))
+                .await
+                .ok()
+                .and_then(|pid| pid.trim().parse::<u32>().ok()),
+        );
+        if !pids.is_empty() {
+            VmCgroup::confine(vm_id, spec, &pids);
+        }
+
+        wait_for_path(&dir.join(QMP_SOCKET), "the QEMU monitor socket").await?;
+        self.qmp(vm_id, "query-status", None).await?;
+        Ok(())
+    }
+
+    /// Poll `query` until its `status` leaves the `pending` states
+    async fn wait_while(&self, vm_id: &str, query: &str, pending: &[&str]) -> Result<String> {
+        loop {
+            let info = self.qmp(vm_id, query, None).await?;
+            let status = info["status"].as_str().unwrap_or_default();
+            if !pending.contains(&status) {
+                return Ok(status.to_string());
+            }
+            tokio::time::sleep(MIGRATION_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Kill the QEMU and virtiofsd processes and delete the VM directory
+    async fn teardown(&self, vm_id: &str) -> Result<()> {
+        let dir = self.vm_dir(vm_id);
+        for file in [QEMU_PID, VIRTIOFSD_PIDS] {
+            if let Ok(pids) = tokio::fs::read_to_string(dir.join(file)).await {
+                for pid in pids.lines().map(str::trim).filter(|pid| !pid.is_empty()) {
+                    kill_pid(pid).await;
+                }
+            }
+        }
+        if let Some(cgroup) = VmCgroup::for_vm(vm_id) {
+            cgroup.remove().await;
+        }
+
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn console_path(&self, vm_id: &str) -> Result<PathBuf> {
+        let chardevs = self.qmp(vm_id, "query-chardev", None).await?;
+        chardevs
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|chardev| chardev["label"] == CONSOLE_CHARDEV)
+            .and_then(|chardev| chardev["filename"].as_str()?.strip_prefix("pty:"))
+            .map(PathBuf::from)
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("No console PTY for VM {}", vm_id),
+            })
+    }
+
+    async fn send_to_console(&self, vm_id: &str, line: &str) -> Result<()> {
+        send_to_console(&self.console_path(vm_id).await?, line).await
+    }
+}
+
+#[async_trait]
+impl Backend for QemuBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        let kernel = self.kernel_path();
+        if !kernel.is_file() {
+            return Err(VortexError::VmError {
+                message: format!("QEMU kernel not found at {}", kernel.display()),
+            });
+        }
+        let base_image = disk_image(&self.root, &vm.spec.image).await?;
+        check_disk_limit(&vm.spec, &base_image)?;
+
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        LayerStore::new()?
+            .create_disk(&base_image, &dir.join(ROOTFS))
+            .await?;
+
+        let result = self.launch(&vm.id, &vm.spec, &dir, None).await;
+        if result.is_err() {
+            if let Err(e) = self.teardown(&vm.id).await {
+                tracing::warn!("Failed to clean up after failed create: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        let status = self.qmp(&vm.id, "query-status", None).await?;
+        if status["status"] == "paused" {
+            self.qmp(&vm.id, "cont", None).await?;
+        }
+
+        let nic = NetworkManager::new().await?.get_vm_network(&vm.id).await?;
+        let mut script = network_script(nic.as_ref());
+        script.push_str(&proxy_script(&vm.spec));
+        script.push_str(&mount_script(&vm.spec, NINEP_MOUNT));
+        script.push_str(&boot_prelude(vm));
+        if let Some(command) = &vm.spec.command {
+            script.push_str(&record_exit(command));
+        }
+        if !script.is_empty() {
+            self.send_to_console(&vm.id, &script).await?;
+        }
+        Ok(())
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        self.qmp(&vm.id, "quit", None).await?;
+        Ok(())
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        if let Err(e) = self.stop(vm).await {
+            tracing::warn!("QEMU quit failed (may already be stopped): {}", e);
+        }
+        self.teardown(&vm.id).await
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        let nic = NetworkManager::new().await?.get_vm_network(&vm.id).await?;
+        let setup = format!(
+            "{}{}{}{}",
+            network_script(nic.as_ref()),
+            proxy_script(&vm.spec),
+            boot_prelude(vm),
+            mount_script(&vm.spec, NINEP_MOUNT)
+        );
+        if !setup.is_empty() {
+            self.send_to_console(&vm.id, &setup).await?;
+        }
+
+        attach_console(&vm.id, self.console_path(&vm.id).await?).await
+    }
+
+    async fn exec(
+        &self,
+        vm: &VmInstance,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        let script = format!(
+            "{}{}",
+            mount_script(&vm.spec, NINEP_MOUNT),
+            exec_script(command, options)
+        );
+        console_exec(&self.console_path(&vm.id).await?, &script).await
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        console_wait(&self.console_path(&vm.id).await?).await
+    }
+
+    async fn pause(&self, vm: &VmInstance) -> Result<()> {
+        self.qmp(&vm.id, "stop", None).await?;
+        Ok(())
+    }
+
+    async fn resume(&self, vm: &VmInstance) -> Result<()> {
+        self.qmp(&vm.id, "cont", None).await?;
+        Ok(())
+    }
+
+    async fn snapshot(&self, vm: &VmInstance, state: &Path) -> Result<()> {
+        let dir = self.vm_dir(&vm.id);
+        let running = self.qmp(&vm.id, "query-status", None).await?["running"] == true;
+        self.qmp(&vm.id, "stop", None).await?;
+
+        let result = async {
+            tokio::fs::copy(dir.join(SPEC_FILE), state.join(SPEC_FILE)).await?;
+            storage::copy_disk(&dir.join(ROOTFS), &state.join(ROOTFS)).await?;
+            let uri = format!("file:{}", state.join(VM_STATE).display());
+            self.qmp(&vm.id, "migrate", Some(json!({ "uri": uri })))
+                .await?;
+            let status = self
+                .wait_while(&vm.id, "query-migrate", &["setup", "active", "device"])
+                .await?;
+            if status != "completed" {
+                return Err(VortexError::VmError {
+                    message: format!("Saving the state of {} ended with {}", vm.id, status),
+                });
+            }
+            Ok(())
+        }
+        .await;
+        if running {
+            if let Err(e) = self.qmp(&vm.id, "cont", None).await {
+                tracing::warn!("Failed to resume {} after snapshot: {}", vm.id, e);
+            }
+        }
+        result
+    }
+
+    async fn restore(&self, vm: &VmInstance, state: &Path) -> Result<()> {
+        let spec = load_spec(state).await?;
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        storage::copy_disk(&state.join(ROOTFS), &dir.join(ROOTFS)).await?;
+
+        let result = async {
+            self.launch(&vm.id, &spec, &dir, Some(&state.join(VM_STATE)))
+                .await?;
+            // The state was saved from a stopped VM, so it loads paused
+            let status = self
+                .wait_while(&vm.id, "query-status", &["inmigrate"])
+                .await?;
+            if status != "running" {
+                self.qmp(&vm.id, "cont", None).await?;
+            }
+            Ok(())
+        }
+        .await;
+        if result.is_err() {
+            if let Err(e) = self.teardown(&vm.id).await {
+                tracing::warn!("Failed to clean up after failed restore: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn clone_vm(&self, source: &VmInstance, vm: &VmInstance) -> Result<()> {
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        // Stopped, the source cannot write to the disk while it is copied
+        let running = self.qmp(&source.id, "query-status", None).await?["running"] == true;
+        self.qmp(&source.id, "stop", None).await?;
+        let copied =
+            storage::copy_disk(&self.vm_dir(&source.id).join(ROOTFS), &dir.join(ROOTFS)).await;
+        if running {
+            if let Err(e) = self.qmp(&source.id, "cont", None).await {
+                tracing::warn!(
+                    "Failed to resume {} after copying its disk: {}",
+                    source.id,
+                    e
+                );
+            }
+        }
+
+        let result = match copied {
+            Ok(()) => self.launch(&vm.id, &vm.spec, &dir, None).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            if let Err(e) = self.teardown(&vm.id).await {
+                tracing::warn!("Failed to clean up after failed clone: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        // Fails fast when the VM is gone
+        self.qmp(&vm.id, "query-status", None).await?;
+
+        let dir = self.vm_dir(&vm.id);
+        let uptime_seconds = (chrono::Utc::now() - vm.created_at).num_seconds().max(0) as u64;
+        let usage = VmCgroup::for_vm(&vm.id).and_then(|cgroup| cgroup.usage());
+        let memory_usage = match usage {
+            Some(usage) => usage.memory_bytes,
+            None => tokio::fs::read_to_string(dir.join(QEMU_PID))
+                .await
+                .ok()
+                .and_then(|pid| process_rss(pid.trim()))
+                .unwrap_or(0),
+        };
+
+        #[cfg(unix)]
+        let disk_usage = {
+            use std::os::unix::fs::MetadataExt;
+            std::fs::metadata(dir.join(ROOTFS))
+                .map(|m| m.blocks() * 512)
+                .unwrap_or(0)
+        };
+        #[cfg(not(unix))]
+        let disk_usage = 0;
+
+        Ok(VmMetrics {
+            // User networking and QMP expose no network counters
+            cpu_usage: usage.map_or(0.0, |usage| usage.cpu_percent(uptime_seconds)),
+            memory_usage,
+            memory_total: u64::from(vm.spec.memory) * 1024 * 1024,
+            disk_usage,
+            network_rx: 0,
+            network_tx: 0,
+            uptime_seconds,
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        let Ok(mut entries) = tokio::fs::read_dir(self.root.join("vms")).await else {
+            return Ok(Vec::new());
+        };
+
+        let mut vms = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(vm_id) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if self.qmp(&vm_id, "query-status", None).await.is_ok() {
+                vms.push(vm_id);
+            }
+        }
+        vms.sort();
+        Ok(vms)
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        let output = tokio::process::Command::new(qemu_system().0)
+            .arg("--version")
+            .output()
+            .await;
+        Ok(output.is_ok_and(|o| o.status.success()))
+    }
+
+    fn name(&self) -> &'static str {
+        "qemu"
+    }
+
+    fn supports_gpu(&self, gpu: &GpuDevice) -> bool {
+        matches!(gpu, GpuDevice::Vfio { .. })
+    }
+
+    fn supports_share(&self, mechanism: ShareMechanism) -> bool {
+        // virtiofsd and memfd guest memory are Linux only
+        mechanism != ShareMechanism::Virtiofs || cfg!(target_os = "linux")
+    }
+
+    fn supports_prewarm(&self) -> bool {
+        true
+    }
+
+    fn supports_private_networks(&self) -> bool {
+        true
+    }
+
+    fn supports_network_isolation(&self) -> bool {
+        true
+    }
+
+    fn supports_egress_proxy(&self) -> bool {
+        true
+    }
+
+    fn supports_port_addresses(&self) -> bool {
+        true
+    }
+
+    fn host_address(&self) -> Option<IpAddr> {
+        Some(HOST_GATEWAY.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vortex_core::network::NetworkLimits;
+
+    #[test]
+    fn test_qemu_args_forward_ports_and_share_volumes() {
+        let mut spec = VmSpec {
+            memory: 512,
+            cpus: 2,
+            ..Default::default()
+        };
+        spec.ports.extend([(8080, 80), (8443, 443)]);
+        spec.port_addresses.insert(8443, "::1".parse().unwrap());
+        spec.volumes
+            .insert(PathBuf::from("/src"), PathBuf::from("/workspace"));
+
+        let args = qemu_args(
+            &spec,
+            None,
+            Path::new("/q/vmlinux"),
+            Path::new("/q/vms/abc"),
+            Path::new("/q/logs/abc.log"),
+            "microvm",
+            "tcg",
+        );
+        let value_of = |flag: &str| {
+            let i = args.iter().position(|a| a == flag).unwrap();
+            args[i + 1].as_str()
+        };
+
+        assert_eq!(value_of("-machine"), "microvm,accel=tcg");
+        assert_eq!(value_of("-cpu"), "max");
+        assert_eq!(value_of("-m"), "512");
+        assert_eq!(
+            value_of("-netdev"),
+            "user,id=net0,hostfwd=tcp::8080-:80,hostfwd=tcp:[::1]:8443-:443"
+        );
+        assert_eq!(
+            value_of("-fsdev"),
+            "local,id=fs0,path=/src,security_model=none"
+        );
+        assert!(args.contains(&"virtio-9p-device,fsdev=fs0,mount_tag=vortexfs0".to_string()));
+        assert_eq!(
+            value_of("-chardev"),
+            "pty,id=con0,logfile=/q/logs/abc.log,logappend=on"
+        );
+        assert_eq!(args.last().map(String::as_str), Some("-daemonize"));
+
+        let mut shared = spec.clone();
+        shared
+            .share_mechanisms
+            .insert(PathBuf::from("/workspace"), ShareMechanism::Virtiofs);
+        let args = qemu_args(
+            &shared,
+            None,
+            Path::new("/q/vmlinux"),
+            Path::new("/q/vms/abc"),
+            Path::new("/q/logs/abc.log"),
+            "microvm",
+            "tcg",
+        );
+        assert!(!args.iter().any(|a| a == "-fsdev"));
+        assert!(args.contains(&"microvm,accel=tcg,memory-backend=mem".to_string()));
+        assert!(args.contains(&"memory-backend-memfd,id=mem,size=512M,share=on".to_string()));
+        assert!(args.contains(&"socket,id=fs0,path=/q/vms/abc/vortexfs0.sock".to_string()));
+        assert!(args.contains(&"vhost-user-fs-device,chardev=fs0,tag=vortexfs0".to_string()));
+
+        spec.gpus.push("01:00.0".parse().unwrap());
+        let args = qemu_args(
+            &spec,
+            None,
+            Path::new("/q/vmlinux"),
+            Path::new("/q/vms/abc"),
+            Path::new("/q/logs/abc.log"),
+            "microvm",
+            "kvm",
+        );
+        assert!(args.contains(&"microvm,accel=kvm,pcie=on".to_string()));
+        assert!(args.contains(&"vfio-pci,host=0000:01:00.0".to_string()));
+
+        let nic = VmNetwork {
+            vm_id: "abc".to_string(),
+            network_name: "backend".to_string(),
+            subnet: "10.89.3.0/24".to_string(),
+            ip_address: "10.89.3.2".to_string(),
+            prefix_len: 24,
+            mac_address: "02:0a:59:00:03:02".to_string(),
+            service: Some("api".to_string()),
+            limits: NetworkLimits {
+                egress_mbps: Some(50),
+                max_connections: Some(128),
+                ..Default::default()
+            },
+        };
+        let args = qemu_args(
+            &spec,
+            Some(&nic),
+            Path::new("/q/vmlinux"),
+            Path::new("/q/vms/abc"),
+            Path::new("/q/logs/abc.log"),
+            "microvm",
+            "kvm",
+        );
+        assert!(
+            args.contains(&"socket,id=net1,mcast=239.89.3.1:5489,localaddr=127.0.0.1".to_string())
+        );
+        let script = network_script(Some(&nic));
+        assert!(script.contains("ip addr add 10.89.3.2/24"));
+        assert!(script.contains("ip -6 addr add fd89:0:0:3::2/64"));
+        assert!(script.contains("echo 'nameserver 10.89.3.1'"));
+        assert!(script.contains("root tbf rate 50mbit"));
+        assert!(!script.contains("ingress"));
+        assert!(script.contains("OUTPUT -p tcp --syn -m connlimit --connlimit-above 128"));
+    }
+
+    #[test]
+    fn test_parse_qmp_line_skips_greeting_and_events() {
+        assert!(
+            parse_qmp_line(r#"{"QMP": {"version": {}, "capabilities": []}}"#)
+                .unwrap()
+                .is_none()
+        );
+        assert!(parse_qmp_line(r#"{"event": "RESUME", "timestamp": {}}"#)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            parse_qmp_line(r#"{"return": {"status": "running"}}"#).unwrap(),
+            Some(json!({ "status": "running" }))
+        );
+        assert!(parse_qmp_line(r#"{"error": {"class": "GenericError", "desc": "nope"}}"#).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//! Backend that schedules VMs on another machine over SSH.
+//!
+//! With `VORTEX_REMOTE=user@host` set, every `Backend` call is forwarded as a
+//! JSON request to the `vortex` binary on that host, which serves it with its
+//! own preferred backend through the hidden `__remote-backend` subcommand.
+//! Each call is a single `ssh` invocation; connections share a control socket
+//! so only the first call pays for the handshake. Attach allocates a remote
+//! TTY, streaming the console to the local terminal.
+//!
+//! Volume host paths are resolved on the remote machine. Paths that do not
+//! exist there are skipped with a warning rather than failing the VM.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::process::Command;
+use vortex_core::backend::{Backend, ExecOptions, ExecResult, ExitStatus, VmMetrics};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::ids::VmId;
+use vortex_core::vm::{VmInstance, VmSpec, VmState};
+
+/// Environment variable naming the SSH destination
+pub const REMOTE_ENV: &str = "VORTEX_REMOTE";
+
+/// Hidden CLI subcommand the remote host runs for each request
+pub const SERVE_SUBCOMMAND: &str = "__remote-backend";
+
+/// The parts of a `VmInstance` a backend needs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteVm {
+    pub id: VmId,
+    pub spec: VmSpec,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl RemoteVm {
+    fn from_instance(vm: &VmInstance) -> Self {
+        let mut spec = vm.spec.clone();
+        // Backend selection happens on the remote host
+        spec.backend = None;
+        Self {
+            id: vm.id.clone(),
+            spec,
+            created_at: vm.created_at,
+        }
+    }
+
+    fn into_instance(mut self, backend: Arc<dyn Backend>) -> VmInstance {
+        self.spec.volumes.retain(|host, _| {
+            let exists = host.exists();
+            if !exists {
+                tracing::warn!(
+                    "Skipping volume {}: not present on the remote host",
+                    host.display()
+                );
+            }
+            exists
+        });
+        VmInstance {
+            id: self.id,
+            spec: self.spec,
+            state: VmState::Running,
+            backend,
+            created_at: self.created_at,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum RemoteRequest {
+    Ping,
+    ListVms,
+    Create {
+        vm: RemoteVm,
+    },
+    Start {
+        vm: RemoteVm,
+    },
+    Stop {
+        vm: RemoteVm,
+    },
+    Cleanup {
+        vm: RemoteVm,
+    },
+    Attach {
+        vm: RemoteVm,
+    },
+    Pause {
+        vm: RemoteVm,
+    },
+    Resume {
+        vm: RemoteVm,
+    },
+    Wait {
+        vm: RemoteVm,
+    },
+    Metrics {
+        vm: RemoteVm,
+    },
+    Exec {
+        vm: RemoteVm,
+        command: Vec<String>,
+        options: ExecOptions,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RemoteReply {
+    Ok,
+    Vms { ids: Vec<String> },
+    Metrics { metrics: VmMetrics },
+    Exec { result: ExecResult },
+    Exited { exit: ExitStatus },
+    Error { message: String },
+}
+
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn remote_error(message: String) -> VortexError {
+    VortexError::VmError {
+        message: format!("remote backend: {}", message),
+    }
+}
+
+#[derive(Debug)]
+pub struct RemoteBackend {
+    destination: String,
+}
+
+impl RemoteBackend {
+    /// The backend configured by `VORTEX_REMOTE`, if set
+    pub fn from_env() -> Option<Self> {
+        std::env::var(REMOTE_ENV)
+            .ok()
+            .filter(|destination| !destination.trim().is_empty())
+            .map(|destination| Self { destination })
+    }
+
+    fn ssh(&self, request: &RemoteRequest, tty: bool) -> Result<Command> {
+        let mut cmd = Command::new("ssh");
+        if let Some(home) = dirs::home_dir() {
+            let sockets = home.join(".vortex").join("ssh");
+            std::fs::create_dir_all(&sockets)?;
+            cmd.arg("-o").arg("ControlMaster=auto");
+            cmd.arg("-o")
+                .arg(format!("ControlPath={}", sockets.join("%C").display()));
+            cmd.arg("-o").arg("ControlPersist=60");
+        }
+        if tty {
+            cmd.arg("-t");
+        } else {
+            // Fail instead of hanging on a password prompt nobody can see
+            cmd.arg("-T").arg("-o").arg("BatchMode=yes");
+        }
+        cmd.arg("--").arg(&self.destination);
+        cmd.arg(format!(
+            "vortex {} {}",
+            SERVE_SUBCOMMAND,
+            sh_quote(&serde_json::to_string(request)?)
+        ));
+        Ok(cmd)
+    }
+
+    async fn call(&self, request: RemoteRequest) -> Result<RemoteReply> {
+        let output = self
+            .ssh(&request, false)?
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .await?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let reply = stdout
+            .lines()
+            .rev()
+            .find_map(|line| serde_json::from_str::<RemoteReply>(line).ok());
+        match reply {
+            Some(RemoteReply::Error { message }) => Err(remote_error(message)),
+            Some(reply) => Ok(reply),
+            None => Err(remote_error(format!(
+                "no reply from {} (ssh exited with {})",
+                self.destination, output.status
+            ))),
+        }
+    }
+
+    async fn call_ok(&self, request: RemoteRequest) -> Result<()> {
+        match self.call(request).await? {
+            RemoteReply::Ok => Ok(()),
+            other => Err(remote_error(format!("unexpected reply {:?}", other))),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for RemoteBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        self.call_ok(RemoteRequest::Create { vm }).await
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        self.call_ok(RemoteRequest::Start { vm }).await
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        self.call_ok(RemoteRequest::Stop { vm }).await
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        self.call_ok(RemoteRequest::Cleanup { vm }).await
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        let status = self
+            .ssh(&RemoteRequest::Attach { vm }, true)?
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(remote_error(format!("attach exited with {}", status)));
+        }
+        Ok(())
+    }
+
+    async fn pause(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        self.call_ok(RemoteRequest::Pause { vm }).await
+    }
+
+    async fn resume(&self, vm: &VmInstance) -> Result<()> {
+        let vm = RemoteVm::from_instance(vm);
+        self.call_ok(RemoteRequest::Resume { vm }).await
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        let vm = RemoteVm::from_instance(vm);
+        match self.call(RemoteRequest::Wait { vm }).await? {
+            RemoteReply::Exited { exit } => Ok(exit),
+            other => Err(remote_error(format!("unexpected reply {:?}", other))),
+        }
+    }
+
+    async fn exec(
+        &self,
+        vm: &VmInstance,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        let request = RemoteRequest::Exec {
+            vm: RemoteVm::from_instance(vm),
+            command: command.to_vec(),
+            options: options.clone(),
+        };
+        match self.call(request).await? {
+            RemoteReply::Exec { result } => Ok(result),
+            other => Err(remote_error(format!("unexpected reply {:?}", other))),
+        }
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        let vm = RemoteVm::from_instance(vm);
+        match self.call(RemoteRequest::Metrics { vm }).await? {
+            RemoteReply::Metrics { metrics } => Ok(metrics),
+            other => Err(remote_error(format!("unexpected reply {:?}", other))),
+        }
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        match self.call(RemoteRequest::ListVms).await? {
+            RemoteReply::Vms { ids } => Ok(ids),
+            other => Err(remote_error(format!("unexpected reply {:?}", other))),
+        }
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        match self.call(RemoteRequest::Ping).await {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::warn!("Remote host {} unavailable: {}", self.destination, e);
+                Ok(false)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "remote"
+    }
+}
+
+async fn handle(request: RemoteRequest) -> Result<RemoteReply> {
+    let provider = crate::probe().await?;
+    let backend = provider.get_backend(None).await?;
+    if backend.name() == "remote" {
+        // The remote host has VORTEX_REMOTE set too; refuse to chain hops
+        return Err(VortexError::BackendUnavailable {
+            backend: "remote (no local backend on the remote host)".to_string(),
+        });
+    }
+
+    let instance = |vm: RemoteVm| vm.into_instance(Arc::clone(&backend));
+    match request {
+        RemoteRequest::Ping => {}
+        RemoteRequest::ListVms => {
+            return Ok(RemoteReply::Vms {
+                ids: backend.list_vms().await?,
+            })
+        }
+        RemoteRequest::Metrics { vm } => {
+            return Ok(RemoteReply::Metrics {
+                metrics: backend.get_metrics(&instance(vm)).await?,
+            })
+        }
+        RemoteRequest::Exec {
+            vm,
+            command,
+            options,
+        } => {
+            return Ok(RemoteReply::Exec {
+                result: backend.exec(&instance(vm), &command, &options).await?,
+            })
+        }
+        RemoteRequest::Wait { vm } => {
+            return Ok(RemoteReply::Exited {
+                exit: backend.wait(&instance(vm)).await?,
+            })
+        }
+        RemoteRequest::Create { vm } => backend.create(&instance(vm)).await?,
+        RemoteRequest::Start { vm } => backend.start(&instance(vm)).await?,
+        RemoteRequest::Stop { vm } => backend.stop(&instance(vm)).await?,
+        RemoteRequest::Cleanup { vm } => backend.cleanup(&instance(vm)).await?,
+        RemoteRequest::Attach { vm } => backend.attach(&instance(vm)).await?,
+        RemoteRequest::Pause { vm } => backend.pause(&instance(vm)).await?,
+        RemoteRequest::Resume { vm } => backend.resume(&instance(vm)).await?,
+    }
+    Ok(RemoteReply::Ok)
+}
+
+/// Serve one request on the remote host, writing the reply to stdout
+pub async fn serve(request: &str) -> Result<()> {
+    let request: RemoteRequest = serde_json::from_str(request)?;
+    // Attach owns the terminal, so its outcome is the exit status alone
+    if matches!(request, RemoteRequest::Attach { .. }) {
+        return handle(request).await.map(|_| ());
+    }
+
+    let reply = handle(request)
+        .await
+        .unwrap_or_else(|e| RemoteReply::Error {
+            message: e.to_string(),
+        });
+    println!("{}", serde_json::to_string(&reply)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_request_round_trip_and_quoting() {
+        let mut spec = VmSpec {
+            image: "alpine".to_string(),
+            command: Some("echo 'it''s'".to_string()),
+            backend: Some("remote".to_string()),
+            ..Default::default()
+        };
+        spec.volumes
+            .insert(PathBuf::from("/nonexistent/vortex"), PathBuf::from("/src"));
+        let vm = RemoteVm {
+            id: "vortex-1".into(),
+            spec,
+            created_at: chrono::Utc::now(),
+        };
+
+        let json = serde_json::to_string(&RemoteRequest::Create { vm }).unwrap();
+        assert!(json.starts_with(r#"{"op":"create""#));
+        let quoted = sh_quote(&json);
+        let unquoted = quoted[1..quoted.len() - 1].replace("'\\''", "'");
+        assert_eq!(unquoted, json);
+
+        let RemoteRequest::Create { vm } = serde_json::from_str(&json).unwrap() else {
+            panic!("expected create");
+        };
+        assert_eq!(vm.spec.command.as_deref(), Some("echo 'it''s'"));
+
+        let backend = Arc::new(RemoteBackend {
+            destination: "build@example.com".to_string(),
+        });
+        let instance = vm.into_instance(backend);
+        assert!(instance.spec.volumes.is_empty());
+        assert_eq!(RemoteVm::from_instance(&instance).spec.backend, None);
+    }
+}
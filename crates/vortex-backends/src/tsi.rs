@@ -0,0 +1,101 @@
+//! Network accounting for VMs on libkrun's transparent socket impersonation
+//! (TSI), where the VMM process opens the guest's connections itself: a
+//! VM's traffic is the traffic of its VMM's sockets.
+//!
+//! macOS counts bytes per process (`nettop`), closed connections included.
+//! Linux only counts per network namespace, which the VMM shares with the
+//! host, so there the counters of the VMM's open TCP sockets (`ss`) are
+//! summed and traffic of closed connections drops out.
+
+use tokio::process::Command;
+
+/// Bytes received and sent by the processes `pids`, or `None` when the
+/// host cannot tell
+pub(crate) async fn network_bytes(pids: &[u32]) -> Option<(u64, u64)> {
+    if pids.is_empty() {
+        return None;
+    }
+    if cfg!(target_os = "macos") {
+        let mut totals = (0, 0);
+        for pid in pids {
+            let output = Command::new("nettop")
+                .args(["-P", "-L", "1", "-x", "-J", "bytes_in,bytes_out"])
+                .args(["-p", &pid.to_string()])
+                .output()
+                .await
+                .ok()?;
+            let (rx, tx) = parse_nettop(&String::from_utf8_lossy(&output.stdout))?;
+            totals = (totals.0 + rx, totals.1 + tx);
+        }
+        Some(totals)
+    } else if cfg!(target_os = "linux") {
+        let output = Command::new("ss").arg("-Htinp").output().await.ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(parse_ss(&String::from_utf8_lossy(&output.stdout), pids))
+    } else {
+        None
+    }
+}
+
+/// Totals of `nettop -P -x -J bytes_in,bytes_out` CSV: a header, then a
+/// row per process
+fn parse_nettop(output: &str) -> Option<(u64, u64)> {
+    let mut rows = output.lines();
+    rows.next()?;
+    let mut totals = (0, 0);
+    for row in rows {
+        let mut fields = row.split(',').skip(1);
+        let rx: u64 = fields.next()?.trim().parse().ok()?;
+        let tx: u64 = fields.next()?.trim().parse().ok()?;
+        totals = (totals.0 + rx, totals.1 + tx);
+    }
+    Some(totals)
+}
+
+/// Totals of the sockets of `pids` in `ss -Htinp` output, where each
+/// socket's line is followed by an indented line of TCP info
+fn parse_ss(output: &str, pids: &[u32]) -> (u64, u64) {
+    let counter = |info: &str, name: &str| -> u64 {
+        info.split_whitespace()
+            .find_map(|field| field.strip_prefix(name)?.parse().ok())
+            .unwrap_or(0)
+    };
+    let mut totals = (0, 0);
+    let mut owned = false;
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            owned = pids
+                .iter()
+                .any(|pid| line.contains(&format!("pid={},", pid)));
+        } else if owned {
+            totals.0 += counter(line, "bytes_received:");
+            totals.1 += counter(line, "bytes_sent:");
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_counters_are_summed_per_process() {
+        let ss = "\
+ESTAB 0 0 192.168.1.5:50712 140.82.112.4:443 users:((\"krunvm\",pid=4242,fd=21))
+\t cubic wscale:7,7 rto:204 bytes_sent:1500 bytes_acked:1500 bytes_received:64000 segs_out:40
+ESTAB 0 0 192.168.1.5:50714 151.101.1.69:443 users:((\"curl\",pid=77,fd=5))
+\t cubic bytes_sent:99 bytes_received:99
+ESTAB 0 0 192.168.1.5:50716 151.101.1.69:80 users:((\"krunvm\",pid=4242,fd=22))
+\t cubic bytes_sent:500 bytes_received:36000
+";
+        assert_eq!(parse_ss(ss, &[4242]), (100_000, 2_000));
+        assert_eq!(parse_ss(ss, &[4]), (0, 0));
+
+        let nettop = ",bytes_in,bytes_out,\nkrunvm.4242,1048576,2048,\n";
+        assert_eq!(parse_nettop(nettop), Some((1_048_576, 2_048)));
+        assert_eq!(parse_nettop(""), None);
+    }
+}
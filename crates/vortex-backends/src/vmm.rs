@@ -0,0 +1,502 @@
+//! Helpers shared by backends that run their own VMM process per VM
+//! (Cloud Hypervisor, QEMU, libkrun): image lookup, volume shares, the guest
+//! console and host process bookkeeping.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use vortex_core::backend::{ExecResult, ExitStatus};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::image_cache::{ImageCache, PreparedFormat};
+use vortex_core::image_store::ImageStore;
+use vortex_core::network::{NetworkLimits, VmNetwork, PREFIX6_LEN};
+use vortex_core::vm::{ShareMechanism, VmSpec};
+
+/// Kernel command line for the raw images these backends boot
+pub(crate) const KERNEL_CMDLINE: &str = "console=hvc0 root=/dev/vda rw";
+/// Ctrl-] ends an attached console session
+const DETACH_KEY: u8 = 0x1d;
+const STARTUP_POLLS: u32 = 50;
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Console shell variable holding the exit status of the VM command
+const EXIT_VAR: &str = "vortex_exit";
+pub(crate) const VIRTIOFS_MOUNT: &str = "-t virtiofs";
+/// PIDs of the virtiofsd processes serving a VM, in its directory
+pub(crate) const VIRTIOFSD_PIDS: &str = "virtiofsd.pids";
+/// Where `copy` shares are mounted in the guest while they are copied
+const COPY_STAGING: &str = "/run/vortex";
+
+/// A host directory exported to the guest
+pub(crate) struct Share {
+    pub tag: String,
+    pub host: PathBuf,
+    pub guest: PathBuf,
+    /// How the spec asks for the share, if it does
+    pub mechanism: Option<ShareMechanism>,
+}
+
+impl Share {
+    /// Socket of the virtiofsd serving the share of a VM in `dir`
+    pub fn socket(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.sock", self.tag))
+    }
+}
+
+/// Volumes in a stable order so tags match between create and attach
+pub(crate) fn shares(spec: &VmSpec) -> Vec<Share> {
+    let mut volumes: Vec<_> = spec.volumes.iter().collect();
+    volumes.sort_by(|a, b| a.1.cmp(b.1));
+    volumes
+        .into_iter()
+        .enumerate()
+        .map(|(i, (host, guest))| Share {
+            tag: format!("vortexfs{}", i),
+            host: host.clone(),
+            guest: guest.clone(),
+            mechanism: spec.share_mechanisms.get(guest).copied(),
+        })
+        .collect()
+}
+
+/// Start a virtiofsd for each of `shares` of a VM in `dir`, recording their
+/// PIDs in `VIRTIOFSD_PIDS`, and return them with their sockets
+pub(crate) async fn start_virtiofsd(
+    shares: &[Share],
+    dir: &Path,
+) -> Result<(Vec<u32>, Vec<(String, PathBuf)>)> {
+    let mut sockets = Vec::new();
+    let mut pids = Vec::new();
+
+    for share in shares {
+        let socket = share.socket(dir);
+        let child = tokio::process::Command::new("virtiofsd")
+            .arg(format!("--socket-path={}", socket.display()))
+            .arg(format!("--shared-dir={}", share.host.display()))
+            .arg("--cache=never")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to start virtiofsd: {}", e),
+            })?;
+        pids.extend(child.id());
+        sockets.push((share.tag.clone(), socket));
+    }
+
+    let recorded: Vec<String> = pids.iter().map(u32::to_string).collect();
+    tokio::fs::write(dir.join(VIRTIOFSD_PIDS), recorded.join("\n")).await?;
+    for (tag, socket) in &sockets {
+        wait_for_path(socket, &format!("virtiofsd share {}", tag)).await?;
+    }
+    Ok((pids, sockets))
+}
+
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Shell line mounting the VM's shares, idempotent so attach can repeat it.
+/// `mount_args` selects the filesystem of shares not asking for virtio-fs,
+/// e.g. `-t virtiofs`. `copy` shares are mounted aside, copied to their
+/// guest path once and unmounted.
+pub(crate) fn mount_script(spec: &VmSpec, mount_args: &str) -> String {
+    shares(spec)
+        .iter()
+        .map(|share| {
+            let guest = sh_quote(&share.guest.display().to_string());
+            let tag = &share.tag;
+            match share.mechanism {
+                Some(ShareMechanism::Copy) => {
+                    let staging = format!("{}/{}", COPY_STAGING, tag);
+                    format!(
+                        "mkdir -p {staging} {guest}; [ -e {staging}.copied ] || \
+                         {{ mount {mount_args} {tag} {staging} && cp -a {staging}/. {guest}/ && \
+                         umount {staging} && touch {staging}.copied; }}; "
+                    )
+                }
+                Some(ShareMechanism::Virtiofs) => format!(
+                    "mkdir -p {guest}; mountpoint -q {guest} || mount {VIRTIOFS_MOUNT} {tag} {guest}; "
+                ),
+                _ => format!(
+                    "mkdir -p {guest}; mountpoint -q {guest} || mount {mount_args} {tag} {guest}; "
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Shell line giving the guest NIC with `nic`'s MAC address its private
+/// network address, asking the network's gateway for names first and
+/// applying the network's limits. Idempotent, so attach can repeat it.
+pub(crate) fn network_script(nic: Option<&VmNetwork>) -> String {
+    let Some(nic) = nic else {
+        return String::new();
+    };
+    let nameserver = format!("nameserver {}", nic.gateway());
+    let address = format!(
+        "dev=$(grep -l {} /sys/class/net/*/address | cut -d/ -f5) && ip link set \"$dev\" up && \
+         {{ ip addr add {}/{} dev \"$dev\" 2>/dev/null || true; }}; \
+         grep -qx '{3}' /etc/resolv.conf 2>/dev/null || \
+         {{ {{ echo '{3}'; cat /etc/resolv.conf 2>/dev/null; }} > /tmp/resolv.conf && \
+         cat /tmp/resolv.conf > /etc/resolv.conf; }}; ",
+        nic.mac_address, nic.ip_address, nic.prefix_len, nameserver
+    );
+    // Guests without IPv6 keep their IPv4 address
+    let address6 = nic
+        .ipv6_address()
+        .map(|ip| {
+            format!(
+                "{{ ip -6 addr add {}/{} dev \"$dev\" 2>/dev/null || true; }}; ",
+                ip, PREFIX6_LEN
+            )
+        })
+        .unwrap_or_default();
+    address + &address6 + &limits_script(&nic.limits)
+}
+
+/// Shell line holding every guest interface to `limits`: bandwidth with
+/// `tc`, connections with `iptables`. Images without them go unlimited,
+/// with the errors in the console log.
+fn limits_script(limits: &NetworkLimits) -> String {
+    let mut shaping = String::new();
+    if let Some(mbps) = limits.egress_mbps {
+        shaping.push_str(&format!(
+            "tc qdisc replace dev \"$dev\" root tbf rate {}mbit burst 64kb latency 50ms; ",
+            mbps
+        ));
+    }
+    if let Some(mbps) = limits.ingress_mbps {
+        shaping.push_str(&format!(
+            "tc qdisc del dev \"$dev\" ingress 2>/dev/null; tc qdisc add dev \"$dev\" ingress && \
+             tc filter add dev \"$dev\" parent ffff: protocol all u32 match u32 0 0 \
+             police rate {}mbit burst 64k drop; ",
+            mbps
+        ));
+    }
+
+    let mut script = String::new();
+    if !shaping.is_empty() {
+        script.push_str(&format!(
+            "for dev in $(ls /sys/class/net); do [ \"$dev\" = lo ] || {{ {}}}; done; ",
+            shaping
+        ));
+    }
+    if let Some(max) = limits.max_connections {
+        // A mask of 0 counts all connections together, whatever the peer
+        for chain in ["OUTPUT", "INPUT"] {
+            let rule = format!(
+                "{} -p tcp --syn -m connlimit --connlimit-above {} --connlimit-mask 0 -j REJECT",
+                chain, max
+            );
+            script.push_str(&format!(
+                "iptables -C {0} 2>/dev/null || iptables -A {0}; ",
+                rule
+            ));
+        }
+    }
+    script
+}
+
+pub(crate) use vortex_core::image_cache::image_key;
+
+/// Raw disk image for an image reference under `root/images`
+fn base_image_path(root: &Path, image: &str) -> PathBuf {
+    root.join("images")
+        .join(format!("{}.raw", image_key(image)))
+}
+
+/// Disk to copy for a new VM: a raw image provided under `root/images`, or
+/// else the ext4 image prepared from the image's rootfs tarball, pulled
+/// from its registry when there is none
+pub(crate) async fn disk_image(root: &Path, image: &str) -> Result<PathBuf> {
+    let raw = base_image_path(root, image);
+    if raw.is_file() {
+        return Ok(raw);
+    }
+    ImageStore::new()?.ensure(image).await?;
+    let cache = ImageCache::new()?;
+    match cache.prepare(image, PreparedFormat::Ext4).await? {
+        Some(prepared) => Ok(prepared),
+        None => Err(VortexError::VmError {
+            message: format!(
+                "No disk image for {}: expected {} or a rootfs tarball at {}",
+                image,
+                raw.display(),
+                cache.source_path(image).display()
+            ),
+        }),
+    }
+}
+
+/// Refuse `disk` if it is larger than the spec's `max_disk`. Disk images
+/// have a fixed size, so the guest cannot grow past it later.
+pub(crate) fn check_disk_limit(spec: &VmSpec, disk: &Path) -> Result<()> {
+    let Some(max_mib) = spec.resource_limits.max_disk else {
+        return Ok(());
+    };
+    let mib = 1024 * 1024;
+    let size_mib = (std::fs::metadata(disk)?.len() + mib - 1) / mib;
+    if size_mib > max_mib {
+        return Err(VortexError::ResourceLimitExceeded {
+            resource: format!("disk: {}MB > {}MB", size_mib, max_mib),
+        });
+    }
+    Ok(())
+}
+
+/// Wait for a socket or file created by a freshly started process
+pub(crate) async fn wait_for_path(path: &Path, what: &str) -> Result<()> {
+    for _ in 0..STARTUP_POLLS {
+        if path.exists() {
+            return Ok(());
+        }
+        tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+    }
+    Err(VortexError::VmError {
+        message: format!("Timed out waiting for {}", what),
+    })
+}
+
+/// Spec a VM was launched with, kept in its directory so a snapshot of the
+/// VM can recreate the same devices
+pub(crate) const SPEC_FILE: &str = "spec.json";
+
+pub(crate) async fn save_spec(dir: &Path, spec: &VmSpec) -> Result<()> {
+    tokio::fs::write(dir.join(SPEC_FILE), serde_json::to_string_pretty(spec)?).await?;
+    Ok(())
+}
+
+pub(crate) async fn load_spec(dir: &Path) -> Result<VmSpec> {
+    let path = dir.join(SPEC_FILE);
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| VortexError::VmError {
+            message: format!(
+                "Cannot read {} ({}); VMs created by older Vortex releases cannot be snapshotted",
+                path.display(),
+                e
+            ),
+        })?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Hard-link a file that is only read, copying across filesystems
+pub(crate) async fn link_or_copy(from: &Path, to: &Path) -> Result<()> {
+    if tokio::fs::hard_link(from, to).await.is_err() {
+        tokio::fs::copy(from, to).await?;
+    }
+    Ok(())
+}
+
+/// Resident memory of a host process in bytes
+pub(crate) fn process_rss(pid: &str) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+pub(crate) async fn kill_pid(pid: &str) {
+    let _ = tokio::process::Command::new("kill")
+        .arg(pid)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+}
+
+/// Write one line of input to the guest console
+pub(crate) async fn send_to_console(pty: &Path, line: &str) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut console = tokio::fs::OpenOptions::new().write(true).open(pty).await?;
+    console.write_all(format!("{}\n", line).as_bytes()).await?;
+    Ok(())
+}
+
+/// Run `script` through the guest console shell and collect its result.
+///
+/// The console has a single stream shared with the echo of what is typed,
+/// so the guest reports stdout, stderr and the exit status as hex lines
+/// tagged with a marker unique to this call. A command already running in
+/// the foreground of the console delays the exec until it finishes.
+pub(crate) async fn console_exec(pty: &Path, script: &str) -> Result<ExecResult> {
+    use tokio::io::AsyncReadExt;
+
+    let marker = format!(
+        "vortex-exec-{}",
+        &uuid::Uuid::new_v4().simple().to_string()[..12]
+    );
+    // Only the printf output puts the marker at the start of a line; the
+    // echoed command has it as an argument
+    let line = format!(
+        "( o=$(mktemp); e=$(mktemp); ( {script} ) >\"$o\" 2>\"$e\" </dev/null; c=$?; \
+         printf '\\n%s:out:' {m}; od -An -v -tx1 \"$o\" | tr -d ' \\n'; \
+         printf '\\n%s:err:' {m}; od -An -v -tx1 \"$e\" | tr -d ' \\n'; \
+         printf '\\n%s:exit:%d\\n' {m} \"$c\"; rm -f \"$o\" \"$e\" )",
+        script = script,
+        m = marker
+    );
+
+    let mut console = tokio::fs::File::open(pty).await?;
+    send_to_console(pty, &line).await?;
+
+    let mut output = String::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = console.read(&mut buf).await?;
+        if n == 0 {
+            return Err(VortexError::VmError {
+                message: "Console closed before the command finished".to_string(),
+            });
+        }
+        output.push_str(&String::from_utf8_lossy(&buf[..n]));
+        if let Some(result) = parse_console_exec(&output, &marker) {
+            return Ok(result);
+        }
+    }
+}
+
+/// `command` followed by saving its exit status in the console shell, for
+/// `console_wait`. A `;` ending the command is dropped, as `;;` would not
+/// parse.
+pub(crate) fn record_exit(command: &str) -> String {
+    let command = command.trim_end().trim_end_matches(';').trim_end();
+    format!("{}; {}=$?", command, EXIT_VAR)
+}
+
+/// Exit status of the command `record_exit` typed into the console. The
+/// exec queues behind the command in the foreground, so this returns once
+/// it finishes.
+pub(crate) async fn console_wait(pty: &Path) -> Result<ExitStatus> {
+    let result = console_exec(pty, &format!("echo \"${{{}-none}}\"", EXIT_VAR)).await?;
+    match result.stdout.trim().parse() {
+        Ok(code) => Ok(ExitStatus { code }),
+        Err(_) => Err(VortexError::VmError {
+            message: "No command was started on the VM console".to_string(),
+        }),
+    }
+}
+
+/// The result in console output, once its exit line has arrived
+fn parse_console_exec(output: &str, marker: &str) -> Option<ExecResult> {
+    fn decode(hex: &str) -> String {
+        let digits: Vec<u8> = hex.bytes().filter(u8::is_ascii_hexdigit).collect();
+        let bytes: Vec<u8> = digits
+            .chunks_exact(2)
+            .filter_map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    let prefix = format!("{}:", marker);
+    let (mut stdout, mut stderr, mut exit_code) = (String::new(), String::new(), None);
+    for line in output.lines() {
+        let Some(field) = line.trim_start_matches('\r').strip_prefix(&prefix) else {
+            continue;
+        };
+        match field.split_once(':') {
+            Some(("out", hex)) => stdout = decode(hex),
+            Some(("err", hex)) => stderr = decode(hex),
+            Some(("exit", code)) => exit_code = code.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    Some(ExecResult {
+        stdout,
+        stderr,
+        exit_code: exit_code?,
+    })
+}
+
+/// Connect the terminal to the guest console PTY until Ctrl-] or EOF
+pub(crate) async fn attach_console(vm_id: &str, pty: PathBuf) -> Result<()> {
+    println!("Connected to {}. Press Ctrl-] to detach.", vm_id);
+
+    let _ = std::process::Command::new("stty")
+        .args(["raw", "-echo"])
+        .status();
+    let result = tokio::task::spawn_blocking(move || proxy_console(&pty))
+        .await
+        .map_err(|e| VortexError::VmError {
+            message: format!("Task join error: {}", e),
+        })?;
+    let _ = std::process::Command::new("stty").arg("sane").status();
+    println!();
+
+    result.map_err(Into::into)
+}
+
+fn proxy_console(pty: &Path) -> std::io::Result<()> {
+    use std::io::{Read, Write};
+
+    let mut to_guest = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(pty)?;
+    let mut from_guest = to_guest.try_clone()?;
+
+    // Detached: the read only ends when the console closes
+    std::thread::spawn(move || {
+        let mut stdout = std::io::stdout();
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = from_guest.read(&mut buf) {
+            if n == 0 || stdout.write_all(&buf[..n]).is_err() {
+                break;
+            }
+            let _ = stdout.flush();
+        }
+    });
+
+    let mut stdin = std::io::stdin().lock();
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = stdin.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if let Some(pos) = buf[..n].iter().position(|&b| b == DETACH_KEY) {
+            return to_guest.write_all(&buf[..pos]);
+        }
+        to_guest.write_all(&buf[..n])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_exit_parses_after_a_trailing_semicolon() {
+        let line = record_exit("cd /work; make || true; ");
+        assert_eq!(line, "cd /work; make || true; vortex_exit=$?");
+        let parsed = std::process::Command::new("sh")
+            .args(["-n", "-c", &line])
+            .status()
+            .unwrap();
+        assert!(parsed.success());
+    }
+
+    #[test]
+    fn test_console_exec_output_is_decoded() {
+        let marker = "vortex-exec-1234";
+        // The echoed command line never starts with the marker
+        let echo = "( o=$(mktemp); ... printf '\\n%s:exit:%d\\n' vortex-exec-1234 \"$c\" )\r\n";
+        let partial = format!(
+            "{}\r\nvortex-exec-1234:out:68690a\r\nvortex-exec-1234:err:",
+            echo
+        );
+        assert_eq!(parse_console_exec(&partial, marker), None);
+
+        let done = format!("{}6f6f7073\r\nvortex-exec-1234:exit:3\r\n", partial);
+        assert_eq!(
+            parse_console_exec(&done, marker),
+            Some(ExecResult {
+                stdout: "hi\n".to_string(),
+                stderr: "oops".to_string(),
+                exit_code: 3,
+            })
+        );
+    }
+}
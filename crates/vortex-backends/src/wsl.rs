@@ -0,0 +1,401 @@
+//! WSL2 backend for Windows hosts.
+//!
+//! Each VM is its own WSL distribution named after the VM id, imported from
+//! the image's root filesystem tarball (`~/.vortex/images/<image>.tar`, see
+//! `image_cache`, pulled from its registry when missing) into `~/.vortex/wsl/vms/<vm-id>/`. WSL2 runs every
+//! distribution in one lightweight Hyper-V VM, so guests are isolated from
+//! Windows but share a kernel with each other. Memory and CPU limits belong
+//! to that shared VM (`%UserProfile%\.wslconfig`) and cannot be set per VM.
+//!
+//! Volumes are bind-mounted from the Windows drives WSL exposes under
+//! `/mnt/<drive>`. Guest ports reach Windows through WSL's localhost
+//! forwarding, which keeps the port number, so `host:guest` mappings with
+//! different numbers are not supported.
+//!
+//! A clone exports the source distribution and imports it under the clone's
+//! id.
+
+use crate::children::Children;
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::process::Stdio;
+use tokio::process::Command;
+use vortex_core::backend::{
+    boot_prelude, exec_script, Backend, ExecOptions, ExecResult, ExitStatus, VmMetrics,
+};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::image_cache::ImageCache;
+use vortex_core::image_store::ImageStore;
+use vortex_core::logs;
+use vortex_core::process;
+use vortex_core::vm::{VmInstance, VmSpec};
+
+const WSL: &str = "wsl.exe";
+/// Export of the source distribution while a clone is imported
+const CLONE_TARBALL: &str = "clone.tar";
+
+#[derive(Debug)]
+pub struct WslBackend {
+    root: PathBuf,
+    children: Children,
+}
+
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Where a Windows path appears inside WSL, e.g. `C:\src` at `/mnt/c/src`.
+/// Paths that are already POSIX pass through; UNC paths have no mount.
+pub fn wsl_path(path: &Path) -> Option<String> {
+    let mut components = path.components();
+    match components.next()? {
+        Component::Prefix(prefix) => {
+            let drive = match prefix.kind() {
+                Prefix::Disk(drive) | Prefix::VerbatimDisk(drive) => drive,
+                _ => return None,
+            };
+            let mut wsl = format!("/mnt/{}", (drive as char).to_ascii_lowercase());
+            for component in components {
+                if let Component::Normal(part) = component {
+                    wsl.push('/');
+                    wsl.push_str(&part.to_string_lossy());
+                }
+            }
+            Some(wsl)
+        }
+        Component::RootDir => Some(path.to_string_lossy().replace('\\', "/")),
+        _ => None,
+    }
+}
+
+/// `wsl.exe` writes UTF-16 to pipes; fall back to UTF-8 for older builds
+fn decode_output(bytes: &[u8]) -> String {
+    if bytes.len() % 2 == 0 && bytes.iter().skip(1).step_by(2).any(|b| *b == 0) {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Shell line preparing the distribution for `spec`: volumes, then environment
+fn setup_script(spec: &VmSpec) -> String {
+    let mut script = String::new();
+
+    let mut volumes: Vec<_> = spec.volumes.iter().collect();
+    volumes.sort_by(|a, b| a.1.cmp(b.1));
+    for (host, guest) in volumes {
+        let Some(source) = wsl_path(host) else {
+            tracing::warn!("Skipping volume {}: not on a local drive", host.display());
+            continue;
+        };
+        let guest = sh_quote(&guest.to_string_lossy().replace('\\', "/"));
+        script.push_str(&format!(
+            "mkdir -p {guest}; mountpoint -q {guest} || mount --bind {} {guest}; ",
+            sh_quote(&source)
+        ));
+    }
+
+    let mut environment: Vec<_> = spec.environment.iter().collect();
+    environment.sort();
+    for (key, value) in environment {
+        script.push_str(&format!("export {}={}; ", key, sh_quote(value)));
+    }
+    script
+}
+
+impl WslBackend {
+    pub async fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::VmError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self {
+            root: home.join(".vortex").join("wsl"),
+            children: Children::default(),
+        })
+    }
+
+    fn vm_dir(&self, vm_id: &str) -> PathBuf {
+        self.root.join("vms").join(vm_id)
+    }
+
+    async fn wsl(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new(WSL)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to run {}: {}", WSL, e),
+            })?;
+        if !output.status.success() {
+            // wsl.exe reports most errors on stdout
+            let mut detail = decode_output(&output.stderr);
+            if detail.trim().is_empty() {
+                detail = decode_output(&output.stdout);
+            }
+            return Err(VortexError::VmError {
+                message: format!(
+                    "{} {} failed: {}",
+                    WSL,
+                    args.first().unwrap_or(&""),
+                    detail.trim()
+                ),
+            });
+        }
+        Ok(decode_output(&output.stdout))
+    }
+
+    /// `wsl.exe` running `script` as root in the VM's distribution
+    fn shell(vm: &VmInstance, script: String) -> Command {
+        let mut cmd = Command::new(WSL);
+        cmd.args([
+            "--distribution",
+            &vm.id,
+            "--user",
+            "root",
+            "--cd",
+            "/",
+            "--exec",
+        ])
+        .arg("sh")
+        .arg("-c")
+        .arg(script);
+        cmd
+    }
+}
+
+#[async_trait]
+impl Backend for WslBackend {
+    async fn create(&self, vm: &VmInstance) -> Result<()> {
+        ImageStore::new()?.ensure(&vm.spec.image).await?;
+        let tarball = ImageCache::new()?.source_path(&vm.spec.image);
+        if !tarball.is_file() {
+            return Err(VortexError::VmError {
+                message: format!(
+                    "No root filesystem for {}: expected a tarball at {}",
+                    vm.spec.image,
+                    tarball.display()
+                ),
+            });
+        }
+        for (host, guest) in &vm.spec.ports {
+            if host != guest {
+                tracing::warn!(
+                    "WSL forwards guest port {} to the same host port, not {}",
+                    guest,
+                    host
+                );
+            }
+        }
+
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        let (dir_arg, tarball_arg) = (dir.to_string_lossy(), tarball.to_string_lossy());
+        if let Err(e) = self
+            .wsl(&["--import", &vm.id, &dir_arg, &tarball_arg, "--version", "2"])
+            .await
+        {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn start(&self, vm: &VmInstance) -> Result<()> {
+        let Some(command) = &vm.spec.command else {
+            return Ok(());
+        };
+        let script = format!("{}{}{}", setup_script(&vm.spec), boot_prelude(vm), command);
+
+        // The distribution stays up while the command runs; stop terminates it
+        let log = logs::create(&vm.id)?;
+        let child = Self::shell(vm, script)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to start {}: {}", vm.id, e),
+            })?;
+        self.children.insert(&vm.id, child);
+        Ok(())
+    }
+
+    async fn wait(&self, vm: &VmInstance) -> Result<ExitStatus> {
+        self.children.wait(&vm.id).await
+    }
+
+    async fn stop(&self, vm: &VmInstance) -> Result<()> {
+        self.wsl(&["--terminate", &vm.id]).await?;
+        Ok(())
+    }
+
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()> {
+        // Unregistering also deletes the distribution's virtual disk
+        if let Err(e) = self.wsl(&["--unregister", &vm.id]).await {
+            tracing::warn!("Failed to unregister {}: {}", vm.id, e);
+        }
+        match tokio::fs::remove_dir_all(self.vm_dir(&vm.id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn attach(&self, vm: &VmInstance) -> Result<()> {
+        let shell = vm.spec.command.as_deref().unwrap_or("sh");
+        let script = format!(
+            "{}{}exec {}",
+            setup_script(&vm.spec),
+            boot_prelude(vm),
+            shell
+        );
+        let status = Self::shell(vm, script)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .await?;
+        if !process::ended_normally(&status) {
+            return Err(VortexError::VmError {
+                message: format!("Interactive session ended with {}", status),
+            });
+        }
+        Ok(())
+    }
+
+    async fn exec(
+        &self,
+        vm: &VmInstance,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        let script = format!(
+            "{}{}",
+            setup_script(&vm.spec),
+            exec_script(command, options)
+        );
+        let output = Self::shell(vm, script)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to run {}: {}", WSL, e),
+            })?;
+        Ok(ExecResult::from_output(&output))
+    }
+
+    async fn clone_vm(&self, source: &VmInstance, vm: &VmInstance) -> Result<()> {
+        let dir = self.vm_dir(&vm.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        let tarball = dir.join(CLONE_TARBALL);
+        let (dir_arg, tarball_arg) = (dir.to_string_lossy(), tarball.to_string_lossy());
+
+        let result = async {
+            self.wsl(&["--export", &source.id, &tarball_arg]).await?;
+            self.wsl(&["--import", &vm.id, &dir_arg, &tarball_arg, "--version", "2"])
+                .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = tokio::fs::remove_file(&tarball).await {
+            tracing::warn!("Failed to remove {}: {}", tarball.display(), e);
+        }
+        if result.is_err() {
+            if let Err(e) = self.cleanup(vm).await {
+                tracing::warn!("Failed to clean up after failed clone: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics> {
+        let disk_usage = std::fs::metadata(self.vm_dir(&vm.id).join("ext4.vhdx"))
+            .map(|m| m.len())
+            .unwrap_or(0);
+
+        Ok(VmMetrics {
+            // Distributions share one kernel, which accounts for none of
+            // them separately
+            cpu_usage: 0.0,
+            memory_usage: 0,
+            memory_total: u64::from(vm.spec.memory) * 1024 * 1024,
+            disk_usage,
+            network_rx: 0,
+            network_tx: 0,
+            uptime_seconds: (chrono::Utc::now() - vm.created_at).num_seconds().max(0) as u64,
+        })
+    }
+
+    async fn list_vms(&self) -> Result<Vec<String>> {
+        let output = self.wsl(&["--list", "--quiet"]).await?;
+        let mut vms: Vec<String> = output
+            .lines()
+            .map(|name| name.trim_matches(|c: char| c.is_whitespace() || c == '\0'))
+            .filter(|name| name.starts_with("vortex-"))
+            .map(String::from)
+            .collect();
+        vms.sort();
+        Ok(vms)
+    }
+
+    async fn is_available(&self) -> Result<bool> {
+        if !cfg!(windows) {
+            return Ok(false);
+        }
+        Ok(Command::new(WSL)
+            .arg("--status")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success()))
+    }
+
+    fn name(&self) -> &'static str {
+        "wsl"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_and_output_decoding() {
+        assert_eq!(wsl_path(Path::new("/srv/app")).as_deref(), Some("/srv/app"));
+        if cfg!(windows) {
+            assert_eq!(
+                wsl_path(Path::new(r"C:\Users\dev\src")).as_deref(),
+                Some("/mnt/c/Users/dev/src")
+            );
+            assert_eq!(wsl_path(Path::new(r"\\server\share")), None);
+        }
+
+        let utf16: Vec<u8> = "vortex-1\r\nUbuntu\r\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        assert_eq!(decode_output(&utf16), "vortex-1\r\nUbuntu\r\n");
+        assert_eq!(decode_output(b"vortex-1\n"), "vortex-1\n");
+
+        let mut spec = VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        };
+        spec.volumes
+            .insert(PathBuf::from("/srv/app"), PathBuf::from("/workspace"));
+        spec.environment
+            .insert("GREETING".to_string(), "it's".to_string());
+        assert_eq!(
+            setup_script(&spec),
+            "mkdir -p '/workspace'; mountpoint -q '/workspace' || \
+             mount --bind '/srv/app' '/workspace'; export GREETING='it'\\''s'; "
+        );
+    }
+}
@@ -0,0 +1,6345 @@
+mod top;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::info;
+use vortex::{
+    archive::ArchiveManifest,
+    config::PluginConfig,
+    credentials,
+    dep_cache::DependencyCache,
+    detect_template, detect_workspace_info,
+    dev_project::{DevProject, DEFAULT_PRESET, DEV_PROJECT_FILE, RESOURCE_PRESETS},
+    diagnostics,
+    events::EventPayload,
+    home_volume,
+    ids::{SnapshotId, VmId, LABEL_RUN_ID},
+    image_cache::ImageCache,
+    image_store::{ImageReference, ImageStore},
+    init, logs,
+    metrics::VmUsage,
+    oidc::OidcAuthProvider,
+    plugin::Capability,
+    policy::ProjectPolicy,
+    progress,
+    project::{ProjectConfig, ServiceConfig, PROJECT_FILE},
+    provision::Provision,
+    run_dir,
+    run_dir::RunDir,
+    secrets::{SecretRequest, SecretsManager},
+    storage::PrunedKind,
+    sync::{Conflict, ConflictPolicy, PendingSync, Resolution, SyncBack},
+    templates::{DevEnvironmentManager, DevTemplate},
+    trace::{TraceIndex, TraceKind, TraceNode},
+    tunnel, validation,
+    DaemonClient, DevOverrides, ExecOptions, LayerStore, LifecycleHooks, ListQuery, NetworkLimits,
+    NetworkPolicy, Probe, ResourceLimits, SessionCommand, SessionResponse, ShareMechanism,
+    StorageManager, TemplateOrigin, TuningProfile, VmSpec, VmState, VortexConfig, VortexCore,
+    WorkspaceInfo, VERSION,
+};
+
+/// Longest a command waits at exit for event handlers to catch up
+const EVENT_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Longest `vortex run` waits for a VM to pass its health check
+const READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+#[derive(Parser)]
+#[command(
+    name = "vortex",
+    about = "Vortex - Lightning-fast ephemeral VM platform",
+    version = env!("CARGO_PKG_VERSION")
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    #[arg(long, global = true, help = "Enable verbose logging")]
+    verbose: bool,
+
+    #[arg(long, global = true, help = "Emit newline-delimited JSON progress events on stderr")]
+    progress_json: bool,
+
+    #[arg(long, global = true, value_enum, default_value = "text", help = "Log format; json writes one object per line to stderr, with the VM and session IDs of the operation")]
+    log_format: LogFormat,
+
+    #[arg(long, global = true, help = "Context of vortex.yaml to apply to its services, e.g. dev, staging or prod")]
+    context: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+// Parsed once per invocation, so the size of `Run` does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Commands {
+    #[command(about = "Start a new ephemeral VM")]
+    Run {
+        #[arg(
+            help = "VM image (alpine, ubuntu:22.04, debian:bullseye); defaults to the service's image in vortex.yaml"
+        )]
+        image: Option<String>,
+
+        #[arg(long, help = "Service of vortex.yaml to take the image, ports, env and volumes from")]
+        service: Option<String>,
+
+        #[arg(short, long, help = "Memory in MB [default: the service's in vortex.yaml, else 512]")]
+        memory: Option<u32>,
+
+        #[arg(short, long, help = "CPU cores [default: the service's in vortex.yaml, else 1]")]
+        cpus: Option<u32>,
+
+        #[arg(
+            short,
+            long,
+            help = "Port forwarding (host:guest); host port 0 picks a free one, host:guest:tls serves HTTPS"
+        )]
+        port: Vec<String>,
+
+        #[arg(long, help = "Forward every --port from a free host port")]
+        publish_all: bool,
+
+        #[arg(short = 'v', long, help = "Volume mounts (host:guest, or volume:guest for a named volume), optionally ending in :virtiofs, :9p or :copy")]
+        volume: Vec<String>,
+
+        #[arg(long, help = "RAM-backed scratch mount (guest path:size, e.g. /tmp:512m or /build:2g)")]
+        tmpfs: Vec<String>,
+
+        #[arg(short = 'e', long, help = "Command to run in VM")]
+        command: Option<String>,
+
+        #[arg(
+            long,
+            help = "Stored secret to pass to the VM (NAME or NAME=VAR), as /run/secrets/NAME and an environment variable"
+        )]
+        secret: Vec<String>,
+
+        #[arg(long, help = "Keep VM running after command exits")]
+        persist: bool,
+
+        #[arg(
+            short = 'q',
+            long,
+            help = "Quiet mode - suppress all non-command output"
+        )]
+        quiet: bool,
+
+        #[arg(long, help = "Watch the VM in the live dashboard while it runs (Docker can't do this)")]
+        monitor_performance: bool,
+
+        #[arg(
+            long,
+            help = "Copy contents of host directory to VM directory (host:guest)"
+        )]
+        copy_to: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Copy VM directory contents back to host after execution (guest:host)"
+        )]
+        sync_back: Vec<String>,
+
+        #[arg(
+            long,
+            help = "For --sync-back files changed on both the host and in the VM: prompt (default), host, guest or merge"
+        )]
+        on_conflict: Option<String>,
+
+        #[arg(short = 'w', long, help = "Set working directory inside VM")]
+        workdir: Option<String>,
+
+        #[arg(long, help = "Add labels (key=value)")]
+        label: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Mount cargo, npm, pip and go caches keyed by the project's lockfiles"
+        )]
+        cache_deps: bool,
+
+        #[arg(
+            long,
+            help = "Guest tuning profile applied at boot (database, build, latency-sensitive)"
+        )]
+        tuning: Option<String>,
+
+        #[arg(
+            long,
+            help = "VM backend to use (libkrun, krunvm, cloud-hypervisor, qemu, remote, wsl or container); defaults to the preferred available one"
+        )]
+        backend: Option<String>,
+
+        #[arg(long, help = "Host command run before the VM is created")]
+        pre_start: Option<String>,
+
+        #[arg(long, help = "Command run in the VM once it has started")]
+        post_start: Option<String>,
+
+        #[arg(long, help = "Host command run before the VM is stopped")]
+        pre_stop: Option<String>,
+
+        #[arg(
+            long,
+            help = "Health check the VM must pass before it counts as ready (exec:<command>, tcp:<port> or http:<port>[/<path>])"
+        )]
+        health_check: Option<String>,
+
+        #[arg(long, help = "Seconds between health checks", default_value = "10")]
+        health_interval: u64,
+
+        #[arg(long, help = "Stop and clean up the VM this many seconds after it starts")]
+        ttl: Option<u64>,
+
+        #[arg(
+            long,
+            help = "Fail the run if the VM is still running after this many seconds"
+        )]
+        timeout: Option<u64>,
+
+        #[arg(
+            long,
+            help = "GPU to attach: a host PCI address to pass through (vfio:0000:01:00.0) or virtio"
+        )]
+        gpus: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Cloud-config style YAML (or .toml) file of users, write_files and runcmd applied on first boot"
+        )]
+        provision: Option<PathBuf>,
+
+        #[arg(long, help = "Private network to join (see 'vortex network create')")]
+        network: Option<String>,
+
+        #[arg(
+            long,
+            help = "Where the VM may connect to: open, none, host-only or allow:<domain>,..."
+        )]
+        network_policy: Option<NetworkPolicy>,
+    },
+
+    #[command(about = "List running VMs")]
+    List,
+
+    #[command(about = "Stop and cleanup a VM")]
+    Stop {
+        #[arg(help = "VM ID")]
+        vm_id: VmId,
+    },
+
+    #[command(about = "Stop all running VMs")]
+    Cleanup,
+
+    #[command(about = "Copy a run's --sync-back results to the host now")]
+    Sync {
+        #[arg(help = "Run ID")]
+        run_id: String,
+
+        #[arg(
+            long,
+            help = "Override the run's conflict policy: prompt, host, guest or merge"
+        )]
+        on_conflict: Option<String>,
+    },
+
+    #[command(about = "Run a command in a running VM (like docker exec)")]
+    Exec {
+        #[arg(help = "VM ID")]
+        vm_id: VmId,
+
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Command and arguments to run"
+        )]
+        command: Vec<String>,
+
+        #[arg(short = 'w', long, help = "Working directory inside the VM")]
+        workdir: Option<String>,
+
+        #[arg(short = 'e', long, help = "Set environment variables (KEY=value)")]
+        env: Vec<String>,
+
+        #[arg(long, help = "Give up after this many seconds")]
+        timeout: Option<u64>,
+    },
+
+    #[command(about = "Copy files between the host and a running VM (like docker cp)")]
+    Cp {
+        #[arg(help = "Source: a host path, or <vm-id>:<guest path>")]
+        source: String,
+
+        #[arg(help = "Destination: a host path, or <vm-id>:<guest path>")]
+        dest: String,
+    },
+
+    #[command(about = "Change the memory and CPUs of a running VM")]
+    Resize {
+        #[arg(help = "VM ID")]
+        vm_id: VmId,
+
+        #[arg(short, long, help = "Memory in MB")]
+        memory: Option<u32>,
+
+        #[arg(short, long, help = "CPU cores")]
+        cpus: Option<u32>,
+    },
+
+    #[command(
+        about = "Save the state of a running VM",
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
+    )]
+    Snapshot {
+        #[arg(help = "VM ID", required = true)]
+        vm_id: Option<VmId>,
+
+        #[command(subcommand)]
+        action: Option<SnapshotCommand>,
+    },
+
+    #[command(about = "Start a new VM from a snapshot")]
+    Restore {
+        #[arg(help = "Snapshot ID")]
+        snapshot_id: String,
+    },
+
+    #[command(about = "Save a running VM to a portable archive")]
+    Save {
+        #[arg(help = "VM ID")]
+        vm_id: VmId,
+
+        #[arg(help = "Output archive path (e.g. web.vortex)")]
+        output: PathBuf,
+    },
+
+    #[command(about = "Start a new VM from an archive written by vortex save")]
+    Load {
+        #[arg(help = "Archive path")]
+        archive: PathBuf,
+    },
+
+    #[command(about = "Start copies of a running VM or a snapshot")]
+    Clone {
+        #[arg(help = "VM ID, session ID or name, or snapshot ID")]
+        source: String,
+
+        #[arg(short = 'n', long, help = "Number of clones", default_value = "1")]
+        count: u32,
+
+        #[arg(short, long, help = "Memory in MB (default: the source's)")]
+        memory: Option<u32>,
+
+        #[arg(short, long, help = "CPU cores (default: the source's)")]
+        cpus: Option<u32>,
+    },
+
+    #[command(
+        about = "Run from a template",
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
+    )]
+    Template {
+        #[arg(help = "Template name", required = true)]
+        name: Option<String>,
+
+        #[arg(short, long, help = "Override command")]
+        command: Option<String>,
+
+        #[arg(long, help = "Forward the template's ports from free host ports")]
+        publish_all: bool,
+
+        #[command(subcommand)]
+        action: Option<TemplateCommand>,
+    },
+
+    #[command(about = "Show available templates and aliases")]
+    Templates,
+
+    #[command(about = "Show VM metrics")]
+    Metrics {
+        #[arg(help = "VM ID (optional - shows all if omitted)")]
+        vm_id: Option<String>,
+
+        #[arg(long, requires = "vm_id", help = "Show the samples of the last DURATION (e.g. 30s, 10m, 2h, 1d), also of stopped VMs")]
+        since: Option<String>,
+    },
+
+    #[command(about = "Sign in through the single sign-on issuer of [auth.oidc]")]
+    Login,
+
+    #[command(about = "Forget the single sign-on tokens")]
+    Logout,
+
+    #[command(about = "Live dashboard of the VMs' CPU, memory and network")]
+    Top {
+        #[arg(long, help = "Only VMs with this label (key=value)")]
+        label: Vec<String>,
+
+        #[arg(long, value_enum, default_value = "cpu", help = "Column to sort by (s cycles it)")]
+        sort: top::SortKey,
+    },
+
+    #[command(about = "Run command across multiple VMs in parallel (Docker can't do this)")]
+    Parallel {
+        #[arg(help = "VM images to run in parallel")]
+        images: Vec<String>,
+
+        #[arg(short = 'e', long, help = "Command to run in each VM")]
+        command: String,
+
+        #[arg(short = 'q', long, help = "Quiet mode - show only aggregated results")]
+        quiet: bool,
+
+        #[arg(long, help = "Copy contents to each VM (host:guest)")]
+        copy_to: Vec<String>,
+
+        #[arg(long, help = "Sync results back from each VM")]
+        sync_back: Vec<String>,
+
+        #[arg(long, help = "Private network the VMs join, e.g. one with limits")]
+        network: Option<String>,
+    },
+
+    #[command(about = "Create instant dev environments (Docker can't match this speed!)")]
+    Dev {
+        #[arg(
+            help = "Development template (python, node, rust, go, ai); omit to use or create .vortex-dev.yaml"
+        )]
+        template: Option<String>,
+
+        #[arg(short, long, help = "Custom working directory")]
+        workdir: Option<String>,
+
+        #[arg(short = 'v', long, help = "Volume mounts (host:guest, or volume:guest for a named volume), optionally ending in :virtiofs, :9p or :copy")]
+        volume: Vec<String>,
+
+        #[arg(short = 'p', long, help = "Port mappings (host:guest)")]
+        port: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Stored secret to pass to the VM (NAME or NAME=VAR), as /run/secrets/NAME and an environment variable"
+        )]
+        secret: Vec<String>,
+
+        #[arg(short = 'q', long, help = "Quiet mode - no banner")]
+        quiet: bool,
+
+        #[arg(long, help = "List available development templates")]
+        list: bool,
+
+        #[arg(long, help = "Create/use persistent workspace")]
+        workspace: Option<String>,
+
+        #[arg(long, help = "Initialize workspace from current directory")]
+        init: bool,
+
+        #[arg(short, long, help = "Session name for background execution")]
+        name: Option<String>,
+
+        #[arg(long, help = "Run in background (detached mode)")]
+        detach: bool,
+
+        #[arg(long, help = "Keep the guest home directory across VMs (~/.vortex/homes/<template>)")]
+        persist_home: bool,
+
+        #[arg(long, help = "Service of vortex.yaml to take the image, ports, env and volumes from")]
+        service: Option<String>,
+
+        #[arg(
+            long,
+            help = "VM backend to use (libkrun, krunvm, cloud-hypervisor, qemu, remote, wsl or container); defaults to the preferred available one"
+        )]
+        backend: Option<String>,
+    },
+
+    #[command(about = "Manage persistent workspaces")]
+    Workspace {
+        #[command(subcommand)]
+        command: WorkspaceCommand,
+    },
+
+    #[command(about = "Session management - create persistent VM sessions")]
+    Session {
+        #[command(subcommand)]
+        command: SessionSubcommand,
+    },
+
+    #[command(about = "Daemon management - background session orchestration")]
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonSubcommand,
+    },
+
+    #[command(about = "Plugin management - add and manage vortex extensions")]
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommand,
+    },
+
+    #[command(about = "Attach to a running session (like screen -r)")]
+    Attach {
+        #[arg(help = "Session ID or name to attach to")]
+        session: String,
+    },
+
+    #[command(about = "Show VM and session events from the event log")]
+    Events {
+        #[arg(short = 'n', long, help = "Number of recent events to show", default_value = "20")]
+        limit: usize,
+
+        #[arg(short, long, help = "Keep printing new events as they arrive")]
+        follow: bool,
+
+        #[arg(long, help = "Print raw versioned JSON, one event per line")]
+        json: bool,
+
+        #[arg(long = "vm", help = "Only show events of this VM")]
+        vm_id: Option<String>,
+    },
+
+    #[command(about = "Search the audit log of who ran which VMs and commands")]
+    Audit {
+        #[arg(short = 'n', long, help = "Number of recent records to show", default_value = "50")]
+        limit: usize,
+
+        #[arg(long, help = "Only records of this user or host account")]
+        user: Option<String>,
+
+        #[arg(long = "vm", help = "Only records of this VM")]
+        vm_id: Option<String>,
+
+        #[arg(long = "session", help = "Only records of this session")]
+        session_id: Option<String>,
+
+        #[arg(long, help = "Only this action, e.g. vm_create or vm_exec")]
+        action: Option<String>,
+
+        #[arg(long, help = "Only records this recent, e.g. 30m, 2h or 7d")]
+        since: Option<String>,
+
+        #[arg(long, help = "Only operations that failed or commands that exited non-zero")]
+        failed: bool,
+
+        #[arg(long, help = "Print the records as JSON, one per line")]
+        json: bool,
+    },
+
+    #[command(about = "Show the console output of a VM")]
+    Logs {
+        #[arg(help = "VM ID")]
+        vm_id: VmId,
+
+        #[arg(short, long, help = "Keep printing output until the VM stops")]
+        follow: bool,
+    },
+
+    #[command(about = "Show how a workspace, session, VM or run relates to the others")]
+    Trace {
+        #[arg(help = "Any workspace, session, VM or run ID (or workspace/session name)")]
+        id: String,
+
+        #[arg(long, help = "Print the graph as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Show a run's record and, with --failure, its failure diagnostics")]
+    Inspect {
+        #[arg(help = "Run ID")]
+        run_id: String,
+
+        #[arg(long, help = "Show the diagnostic bundle captured when the command failed")]
+        failure: bool,
+    },
+
+    #[command(about = "Manage private networks shared between VMs")]
+    Network {
+        #[command(subcommand)]
+        command: NetworkCommand,
+    },
+
+    #[command(about = "Manage persistent guest home volumes")]
+    Home {
+        #[command(subcommand)]
+        command: HomeCommand,
+    },
+
+    #[command(about = "Manage named volumes that outlive VMs")]
+    Volume {
+        #[command(subcommand)]
+        command: VolumeCommand,
+    },
+
+    #[command(about = "Manage the dependency caches of --cache-deps")]
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    #[command(about = "Manage root filesystems prepared from image tarballs")]
+    Image {
+        #[command(subcommand)]
+        command: ImageCommand,
+    },
+
+    #[command(about = "Check the config file and the project files in this directory")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    #[command(about = "Manage Vortex's own disk usage")]
+    System {
+        #[command(subcommand)]
+        command: SystemCommand,
+    },
+
+    #[command(about = "Manage secrets in the OS credential store")]
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommand,
+    },
+
+    #[command(about = "Manage short names for images")]
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+
+    #[command(about = "Virtual machine management commands")]
+    Vm {
+        #[command(subcommand)]
+        command: VmCommand,
+    },
+
+    /// Boot a libkrun VM in this process; spawned by the libkrun backend
+    #[cfg(feature = "libkrun")]
+    #[command(name = vortex::libkrun::ENTER_SUBCOMMAND, hide = true)]
+    LibkrunEnter { config: PathBuf },
+
+    /// Serve one backend call for a remote client; run over SSH
+    #[cfg(feature = "remote")]
+    #[command(name = vortex::remote::SERVE_SUBCOMMAND, hide = true)]
+    RemoteBackend { request: String },
+}
+
+#[derive(Subcommand)]
+enum WorkspaceCommand {
+    #[command(about = "List all workspaces")]
+    List,
+
+    #[command(about = "Create a new workspace")]
+    Create {
+        #[arg(help = "Workspace name")]
+        name: String,
+
+        #[arg(short, long, help = "Development template to use")]
+        template: String,
+
+        #[arg(long, help = "Source directory to copy (defaults to current dir)")]
+        source: Option<PathBuf>,
+
+        #[arg(
+            long,
+            help = "VM backend to use (libkrun, krunvm, firecracker, cloud-hypervisor, qemu, remote, wsl or container)",
+            default_value = "krunvm"
+        )]
+        backend: String,
+
+        #[arg(
+            long,
+            help = "Encrypt the workspace's files at rest, under a key in the OS credential store"
+        )]
+        encrypted: bool,
+    },
+
+    #[command(about = "Delete a workspace")]
+    Delete {
+        #[arg(help = "Workspace name or ID")]
+        workspace: String,
+    },
+
+    #[command(about = "Show workspace details")]
+    Info {
+        #[arg(help = "Workspace name or ID")]
+        workspace: String,
+    },
+
+    #[command(about = "Let other users on this machine use a workspace")]
+    Share {
+        #[arg(help = "Workspace name or ID")]
+        workspace: String,
+
+        #[arg(required = true, help = "User names")]
+        users: Vec<String>,
+
+        #[arg(long, help = "Take access away from the users instead")]
+        revoke: bool,
+    },
+
+    #[command(about = "Import from devcontainer.json")]
+    Import {
+        #[arg(help = "Workspace name")]
+        name: String,
+
+        #[arg(
+            long,
+            help = "Path to devcontainer.json",
+            default_value = ".devcontainer/devcontainer.json"
+        )]
+        devcontainer: PathBuf,
+
+        #[arg(long, help = "Source directory (defaults to current dir)")]
+        source: Option<PathBuf>,
+
+        #[arg(
+            long,
+            help = "VM backend to use (libkrun, krunvm, firecracker, cloud-hypervisor, qemu, remote, wsl or container)",
+            default_value = "krunvm"
+        )]
+        backend: String,
+    },
+
+    #[command(about = "Export a workspace to a portable archive")]
+    Export {
+        #[arg(help = "Workspace name or ID")]
+        workspace: String,
+
+        #[arg(help = "Output archive path (e.g. myproject.vortex)")]
+        output: PathBuf,
+    },
+
+    #[command(about = "Load a workspace from an exported archive")]
+    Load {
+        #[arg(help = "Archive path")]
+        archive: PathBuf,
+
+        #[arg(long, help = "Workspace name (defaults to the archived name)")]
+        name: Option<String>,
+    },
+
+    #[command(about = "Initialize a new workspace with interactive setup")]
+    Init {
+        #[arg(
+            help = "Directory to scan (defaults to current dir)",
+            default_value = "."
+        )]
+        directory: PathBuf,
+
+        #[arg(
+            long,
+            help = "Output path for vortex.yaml",
+            default_value = "vortex.yaml"
+        )]
+        output: PathBuf,
+
+        #[arg(long, help = "Non-interactive mode - auto-scan and generate")]
+        non_interactive: bool,
+
+        #[arg(
+            long,
+            help = "VM backend to use (libkrun, krunvm, firecracker, cloud-hypervisor, qemu, remote, wsl or container)",
+            default_value = "krunvm"
+        )]
+        backend: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionSubcommand {
+    #[command(about = "Create a new persistent session")]
+    Create {
+        #[arg(help = "Development template (python, node, rust, etc.)")]
+        template: String,
+
+        #[arg(short, long, help = "Session name (optional)")]
+        name: Option<String>,
+
+        #[arg(short, long, help = "Memory in MB", default_value = "512")]
+        memory: u32,
+
+        #[arg(short, long, help = "CPU cores", default_value = "1")]
+        cpus: u32,
+
+        #[arg(short, long, help = "Port mappings (host:guest)")]
+        port: Vec<String>,
+
+        #[arg(short = 'v', long, help = "Volume mounts (host:guest, or volume:guest for a named volume), optionally ending in :virtiofs, :9p or :copy")]
+        volume: Vec<String>,
+
+        #[arg(long, help = "Create but don't attach immediately")]
+        detach: bool,
+
+        #[arg(long, help = "Make session persistent (survives logout)")]
+        persistent: bool,
+    },
+
+    #[command(about = "List all sessions")]
+    List {
+        #[arg(long, help = "Maximum sessions to show per page")]
+        limit: Option<usize>,
+
+        #[arg(long, help = "Continue from the cursor printed by a previous page")]
+        cursor: Option<String>,
+
+        #[arg(long, help = "Only sessions in this state (running, stopped, ...)")]
+        state: Option<String>,
+
+        #[arg(long, help = "Only sessions with this label (key=value)")]
+        label: Vec<String>,
+    },
+
+    #[command(about = "Show session details")]
+    Info {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Start a stopped session")]
+    Start {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Stop a running session")]
+    Stop {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Pause a session (preserve state)")]
+    Pause {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Resume a paused session")]
+    Resume {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Restart a session")]
+    Restart {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Delete a session")]
+    Delete {
+        #[arg(help = "Session ID or name")]
+        session: String,
+
+        #[arg(short, long, help = "Force deletion without confirmation")]
+        force: bool,
+    },
+
+    #[command(about = "Enable autostart for a session (starts on boot)")]
+    EnableAutostart {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+
+    #[command(about = "Disable autostart for a session")]
+    DisableAutostart {
+        #[arg(help = "Session ID or name")]
+        session: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonSubcommand {
+    #[command(about = "Start the Vortex daemon")]
+    Start {
+        #[arg(long, help = "Run in background (fork)")]
+        background: bool,
+    },
+
+    #[command(about = "Stop the Vortex daemon")]
+    Stop,
+
+    #[command(about = "Show daemon status")]
+    Status,
+
+    #[command(about = "Restart the daemon")]
+    Restart,
+
+    #[command(about = "Replace the running daemon with this binary, keeping sessions and VMs")]
+    Upgrade,
+
+    #[command(about = "Show daemon logs (if available)")]
+    Logs,
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    #[command(about = "List saved snapshots")]
+    List,
+
+    #[command(about = "Delete a snapshot")]
+    Delete {
+        #[arg(help = "Snapshot ID")]
+        snapshot_id: String,
+    },
+
+    #[command(about = "Export a snapshot to a portable archive")]
+    Export {
+        #[arg(help = "Snapshot ID")]
+        snapshot_id: String,
+
+        #[arg(help = "Output archive path (e.g. web.vortex)")]
+        output: PathBuf,
+    },
+
+    #[command(about = "Load a snapshot from an exported archive")]
+    Load {
+        #[arg(help = "Archive path")]
+        archive: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    #[command(about = "Add a user dev template, copied from another or read from a file")]
+    Add {
+        #[arg(help = "Dev template name")]
+        name: String,
+
+        #[arg(long, help = "Dev template to copy", required_unless_present = "file")]
+        from: Option<String>,
+
+        #[arg(long, conflicts_with = "from", help = "TOML or YAML file defining the template")]
+        file: Option<PathBuf>,
+    },
+
+    #[command(about = "Remove a user dev template")]
+    Remove {
+        #[arg(help = "Dev template name")]
+        name: String,
+    },
+
+    #[command(about = "Edit a dev template in $EDITOR, copying the built-in on first edit")]
+    Edit {
+        #[arg(help = "Dev template name")]
+        name: String,
+    },
+
+    #[command(about = "Show a dev template definition")]
+    Show {
+        #[arg(help = "Dev template name")]
+        name: String,
+
+        #[arg(
+            long,
+            help = "Show whether the template is built-in or a user override"
+        )]
+        origin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginCommand {
+    #[command(about = "List all installed plugins")]
+    List,
+
+    #[command(about = "Add a new plugin from a git repository")]
+    Add {
+        #[arg(help = "Git repository URL (e.g., https://github.com/exec/vortex-web)")]
+        repo: String,
+
+        #[arg(long, help = "Plugin name (defaults to repo name)")]
+        name: Option<String>,
+    },
+
+    #[command(about = "Remove a plugin")]
+    Remove {
+        #[arg(help = "Plugin name")]
+        name: String,
+    },
+
+    #[command(about = "Enable a plugin")]
+    Enable {
+        #[arg(help = "Plugin name")]
+        name: String,
+    },
+
+    #[command(about = "Disable a plugin")]
+    Disable {
+        #[arg(help = "Plugin name")]
+        name: String,
+    },
+
+    #[command(about = "Grant capabilities to a sandboxed (WebAssembly) plugin")]
+    Grant {
+        #[arg(help = "Plugin name")]
+        name: String,
+
+        #[arg(
+            required = true,
+            help = "Capabilities: hook:<hook>, api:log, api:vm-spec, read:<path>, write:<path>, net:<host:port>"
+        )]
+        capabilities: Vec<String>,
+    },
+
+    #[command(about = "Revoke capabilities from a sandboxed plugin")]
+    Revoke {
+        #[arg(help = "Plugin name")]
+        name: String,
+
+        #[arg(required = true, help = "Capabilities to revoke")]
+        capabilities: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HomeCommand {
+    #[command(about = "List persistent home volumes")]
+    List,
+
+    #[command(about = "Delete a template's home volume so the next VM starts fresh")]
+    Reset {
+        #[arg(help = "Template name")]
+        template: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum VolumeCommand {
+    #[command(about = "Create an empty named volume")]
+    Create {
+        #[arg(help = "Volume name")]
+        name: String,
+
+        #[arg(
+            long,
+            help = "Encrypt the volume's files at rest, under a key in the OS credential store"
+        )]
+        encrypted: bool,
+    },
+
+    #[command(about = "List named volumes")]
+    List,
+
+    #[command(about = "Show a named volume's path, size and creation time")]
+    Inspect {
+        #[arg(help = "Volume name")]
+        name: String,
+    },
+
+    #[command(about = "Delete a named volume and its files")]
+    Rm {
+        #[arg(help = "Volume name")]
+        name: String,
+    },
+
+    #[command(about = "Decrypt an encrypted volume's files so VMs can mount it")]
+    Unlock {
+        #[arg(help = "Volume name")]
+        name: String,
+    },
+
+    #[command(about = "Seal an encrypted volume's files again once no VM needs them")]
+    Lock {
+        #[arg(help = "Volume name")]
+        name: String,
+    },
+
+    #[command(about = "Write a named volume's files to a zstd-compressed tarball")]
+    Export {
+        #[arg(help = "Volume name")]
+        name: String,
+
+        #[arg(help = "Tarball to write (e.g. pgdata.tar.zst)")]
+        file: PathBuf,
+    },
+
+    #[command(about = "Create a named volume from a tarball written by volume export")]
+    Import {
+        #[arg(help = "Volume name")]
+        name: String,
+
+        #[arg(help = "Tarball to read")]
+        file: PathBuf,
+    },
+
+    #[command(about = "Snapshot a named volume's files")]
+    Snapshot {
+        #[arg(help = "Volume name")]
+        name: String,
+    },
+
+    #[command(about = "Put a named volume's files back as they were at a snapshot")]
+    Rollback {
+        #[arg(help = "Volume name")]
+        name: String,
+
+        #[arg(help = "Snapshot ID")]
+        snapshot: String,
+    },
+
+    #[command(about = "Delete a snapshot of a named volume")]
+    RmSnapshot {
+        #[arg(help = "Volume name")]
+        name: String,
+
+        #[arg(help = "Snapshot ID")]
+        snapshot: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum NetworkCommand {
+    #[command(about = "Create a private network")]
+    Create {
+        #[arg(help = "Network name")]
+        name: String,
+
+        #[arg(long, help = "Megabits per second each VM receives at most")]
+        ingress_mbps: Option<u32>,
+
+        #[arg(long, help = "Megabits per second each VM sends at most")]
+        egress_mbps: Option<u32>,
+
+        #[arg(long, help = "TCP connections each VM has open at once, each way")]
+        max_connections: Option<u32>,
+    },
+
+    #[command(about = "List private networks and their VMs")]
+    List,
+
+    #[command(about = "Delete a private network no VM is on")]
+    Rm {
+        #[arg(help = "Network name")]
+        name: String,
+    },
+
+    #[command(
+        about = "Open a WireGuard tunnel into a private network for another machine (needs root)"
+    )]
+    Tunnel {
+        #[arg(help = "Network name")]
+        name: String,
+
+        #[arg(
+            long,
+            required_unless_present = "down",
+            help = "Host name or address the peer reaches this host at"
+        )]
+        endpoint: Option<String>,
+
+        #[arg(long, help = "Name of the peer to add (default: peer-<n>)")]
+        peer: Option<String>,
+
+        #[arg(
+            long,
+            default_value_t = tunnel::DEFAULT_LISTEN_PORT,
+            help = "UDP port the tunnel listens on"
+        )]
+        listen_port: u16,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["endpoint", "peer"],
+            help = "Close the tunnel and forget its peers"
+        )]
+        down: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImageCommand {
+    #[command(about = "Pull an OCI image from its registry into a root filesystem")]
+    Pull {
+        #[arg(help = "Image reference (e.g. alpine:3.19 or ghcr.io/org/app:1.0)")]
+        image: String,
+    },
+
+    #[command(about = "List pulled images and prepared root filesystems")]
+    List,
+
+    #[command(about = "Delete prepared root filesystems that are stale or unused")]
+    Gc {
+        #[arg(long, help = "Also delete those unused for this many days", default_value = "14")]
+        max_unused_days: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    #[command(about = "List dependency caches with their sizes")]
+    List,
+
+    #[command(about = "Delete dependency caches no run used recently")]
+    Prune {
+        #[arg(long, help = "Delete those unused for this many days", default_value = "30")]
+        max_unused_days: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    #[command(
+        about = "Report unknown keys, bad port mappings and missing templates with their line"
+    )]
+    Validate,
+}
+
+#[derive(Subcommand)]
+enum SystemCommand {
+    #[command(
+        about = "Remove leftover run and VM directories, unused base layers and image blobs"
+    )]
+    Prune {
+        #[arg(long, help = "Only remove what is older than this many hours", default_value = "24")]
+        older_than: i64,
+
+        #[arg(long, help = "List what would be removed without removing it")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretCommand {
+    #[command(about = "Store a secret, reading the value from stdin")]
+    Set {
+        #[arg(help = "Secret name")]
+        name: String,
+    },
+
+    #[command(about = "Print a secret")]
+    Get {
+        #[arg(help = "Secret name")]
+        name: String,
+    },
+
+    #[command(about = "Delete a secret")]
+    Rm {
+        #[arg(help = "Secret name")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AliasCommand {
+    #[command(about = "List image aliases and the default registry")]
+    List,
+
+    #[command(about = "Make a name stand for an image")]
+    Add {
+        #[arg(help = "Alias, e.g. alpine")]
+        name: String,
+
+        #[arg(help = "Image it stands for, e.g. registry.corp.example/alpine:3.19")]
+        image: String,
+    },
+
+    #[command(about = "Remove an image alias")]
+    Remove {
+        #[arg(help = "Alias")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum VmCommand {
+    #[command(about = "Create a new VM")]
+    Create {
+        #[arg(help = "VM name")]
+        name: String,
+
+        #[arg(help = "VM image (alpine, ubuntu:22.04, debian:bullseye)")]
+        image: String,
+
+        #[arg(short, long, help = "Memory in MB", default_value = "512")]
+        memory: u32,
+
+        #[arg(short, long, help = "CPU cores", default_value = "1")]
+        cpus: u32,
+
+        #[arg(short, long, help = "Port forwarding (host:guest)")]
+        port: Vec<String>,
+    },
+
+    #[command(about = "List running VMs")]
+    List,
+
+    #[command(about = "Stop and cleanup a VM")]
+    Stop {
+        #[arg(help = "VM name or ID")]
+        vm_name: VmId,
+    },
+
+    #[command(about = "Cleanup a specific VM or all VMs")]
+    Cleanup {
+        #[arg(long, help = "Name of VM to cleanup (if omitted, cleans all)")]
+        name: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // krun_start_enter takes over the process, so skip all other setup
+    #[cfg(feature = "libkrun")]
+    if let Commands::LibkrunEnter { config } = &cli.command {
+        let config = vortex::libkrun::load_launch_config(config)?;
+        match vortex::libkrun::enter(&config)? {}
+    }
+
+    // stdout carries the reply back to the client, so logs go to stderr
+    #[cfg(feature = "remote")]
+    if let Commands::RemoteBackend { request } = &cli.command {
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_writer(std::io::stderr)
+            .init();
+        return Ok(vortex::remote::serve(request).await?);
+    }
+
+    // Check if any command is using quiet mode
+    let is_quiet = match &cli.command {
+        Commands::Run { quiet, .. } => *quiet,
+        Commands::Dev { quiet, .. } => *quiet,
+        _ => false,
+    };
+
+    if let LogFormat::Json = cli.log_format {
+        let level = if is_quiet {
+            tracing::Level::ERROR
+        } else if cli.verbose {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::INFO
+        };
+        // Each line carries the spans it was logged in, with their vm_id
+        // and session_id fields
+        tracing_subscriber::fmt()
+            .json()
+            .with_max_level(level)
+            .with_writer(std::io::stderr)
+            .init();
+    } else if is_quiet {
+        // Disable all logging in quiet mode - use ERROR level as lowest
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::ERROR)
+            .with_target(false)
+            .without_time()
+            .init();
+    } else if cli.verbose {
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .init();
+    }
+
+    if !is_quiet {
+        info!("Vortex v{} - Ephemeral VM Platform", VERSION);
+    }
+
+    // Initializing loads the config, which may be what needs fixing
+    if let Commands::Config {
+        command: ConfigCommand::Validate,
+    } = &cli.command
+    {
+        return validate_config();
+    }
+
+    if cli.progress_json {
+        if let Some(name) = progress_command_name(&cli.command) {
+            progress::init(name);
+        }
+    }
+
+    let result: Result<()> = async {
+        // Initialize Vortex Core
+        let vortex = Arc::new(init().await.context("Failed to initialize Vortex core")?);
+        let result = dispatch(vortex.clone(), cli.command, cli.context.as_deref()).await;
+        // Let queued events reach the event log before the process exits
+        vortex.flush_events(EVENT_FLUSH_TIMEOUT).await;
+        result
+    }
+    .await;
+    progress::finish(&result);
+    result
+}
+
+/// Long-running commands that report `--progress-json` events
+fn progress_command_name(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Run { .. } => Some("run"),
+        Commands::Dev { .. } => Some("dev"),
+        Commands::Workspace {
+            command: WorkspaceCommand::Create { .. },
+        } => Some("workspace create"),
+        _ => None,
+    }
+}
+
+async fn dispatch(vortex: Arc<VortexCore>, command: Commands, context: Option<&str>) -> Result<()> {
+    match command {
+        Commands::Run {
+            image,
+            service,
+            memory,
+            cpus,
+            port,
+            publish_all,
+            volume,
+            tmpfs,
+            command,
+            secret,
+            persist,
+            quiet: run_quiet,
+            monitor_performance,
+            copy_to,
+            sync_back,
+            on_conflict,
+            workdir,
+            label,
+            cache_deps,
+            tuning,
+            backend,
+            pre_start,
+            post_start,
+            pre_stop,
+            health_check,
+            health_interval,
+            ttl,
+            timeout,
+            gpus,
+            provision,
+            network,
+            network_policy,
+        } => {
+            let health_check = health_check
+                .as_deref()
+                .map(str::parse::<Probe>)
+                .transpose()?
+                .map(|probe| Probe {
+                    interval_secs: health_interval,
+                    ..probe
+                });
+            let mappings = parse_port_mappings(port)?;
+            let volumes = parse_volume_mappings(volume).await?;
+            let mut spec = VmSpec {
+                image: image.unwrap_or_default(),
+                memory: 512,
+                cpus: 1,
+                ports: mappings.ports,
+                volumes: volumes.volumes,
+                environment: HashMap::new(),
+                command,
+                labels: parse_labels(label)?,
+                network_config: network,
+                resource_limits: ResourceLimits {
+                    timeout_seconds: timeout,
+                    ..Default::default()
+                },
+                backend,
+                tuning: tuning.as_deref().map(TuningProfile::resolve).transpose()?,
+                hooks: LifecycleHooks {
+                    pre_start,
+                    post_start,
+                    pre_stop,
+                },
+                health_check,
+                ttl_seconds: ttl,
+                gpus: gpus
+                    .iter()
+                    .map(|gpu| gpu.parse())
+                    .collect::<std::result::Result<_, _>>()?,
+                provision: provision.as_deref().map(Provision::load).transpose()?,
+                publish: mappings.publish,
+                tls_ports: mappings.tls_ports,
+                port_addresses: mappings.addresses,
+                share_mechanisms: volumes.mechanisms,
+                tmpfs: parse_tmpfs_mounts(tmpfs)?,
+                network_policy: network_policy.unwrap_or_default(),
+            };
+            if let Some((project, service)) =
+                project_service(service.as_deref(), context, run_quiet)?
+            {
+                project.apply(&service, &mut spec)?;
+            }
+            if let Some(memory) = memory {
+                spec.memory = memory;
+            }
+            if let Some(cpus) = cpus {
+                spec.cpus = cpus;
+            }
+            if spec.image.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "No image given; pass one, e.g. vortex run alpine, or run in a directory with a {}",
+                    PROJECT_FILE
+                ));
+            }
+            if publish_all {
+                spec.publish_all();
+            }
+            let on_conflict = on_conflict.as_deref().map(str::parse).transpose()?;
+            let secrets = parse_secrets(&secret)?;
+
+            run_vm(
+                &vortex,
+                spec,
+                persist,
+                run_quiet,
+                monitor_performance,
+                copy_to,
+                sync_back,
+                on_conflict.unwrap_or_default(),
+                workdir,
+                cache_deps,
+                &secrets,
+            )
+            .await?;
+        }
+        Commands::List => {
+            list_vms(&vortex).await?;
+        }
+        Commands::Stop { vm_id } => {
+            stop_vm(&vortex, &vm_id).await?;
+        }
+        Commands::Cleanup => {
+            cleanup_vms(&vortex).await?;
+        }
+        Commands::Sync {
+            run_id,
+            on_conflict,
+        } => {
+            let policy = on_conflict.as_deref().map(str::parse).transpose()?;
+            sync_run(&run_id, policy)?;
+        }
+        Commands::Exec {
+            vm_id,
+            command,
+            workdir,
+            env,
+            timeout,
+        } => {
+            let options = ExecOptions {
+                workdir,
+                env: parse_env(env)?,
+                timeout_secs: timeout,
+            };
+            let result = vortex.exec_vm(&vm_id, &command, &options).await?;
+            print!("{}", result.stdout);
+            eprint!("{}", result.stderr);
+            if !result.success() {
+                vortex.flush_events(EVENT_FLUSH_TIMEOUT).await;
+                std::process::exit(result.exit_code);
+            }
+        }
+        Commands::Cp { source, dest } => {
+            match (split_vm_path(&source), split_vm_path(&dest)) {
+                (Some((vm_id, guest)), None) => {
+                    vortex
+                        .copy_from_vm(&VmId::new(vm_id), guest, Path::new(&dest))
+                        .await?;
+                    println!("📥 Copied {}:{} to {}", vm_id, guest, dest);
+                }
+                (None, Some((vm_id, guest))) => {
+                    vortex
+                        .copy_to_vm(&VmId::new(vm_id), Path::new(&source), guest)
+                        .await?;
+                    println!("📤 Copied {} to {}:{}", source, vm_id, guest);
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Name a VM on exactly one side, e.g. vortex cp ./src <vm-id>:/workspace"
+                    ))
+                }
+            }
+        }
+        Commands::Resize {
+            vm_id,
+            memory,
+            cpus,
+        } => {
+            vortex.resize_vm(&vm_id, memory, cpus).await?;
+            let vm = vortex.get_vm(&vm_id).await?;
+            match vm {
+                Some(vm) => println!(
+                    "📐 Resized {} to {}MB and {} CPU(s)",
+                    vm_id, vm.spec.memory, vm.spec.cpus
+                ),
+                None => println!("📐 Resized {}", vm_id),
+            }
+        }
+        Commands::Snapshot { vm_id, action } => match action {
+            Some(SnapshotCommand::List) => {
+                list_snapshots(&vortex)?;
+            }
+            Some(SnapshotCommand::Delete { snapshot_id }) => {
+                let snapshot_id = SnapshotId::new(snapshot_id);
+                vortex.delete_snapshot(&snapshot_id)?;
+                println!("🗑️  Deleted snapshot {}", snapshot_id);
+            }
+            Some(SnapshotCommand::Export {
+                snapshot_id,
+                output,
+            }) => {
+                let snapshot_id = SnapshotId::new(snapshot_id);
+                let manifest = vortex.export_snapshot(&snapshot_id, &output)?;
+                println!("📦 Snapshot {} exported", snapshot_id);
+                print_archive(&output, &manifest);
+            }
+            Some(SnapshotCommand::Load { archive }) => {
+                let record = vortex.load_snapshot_archive(&archive)?;
+                println!(
+                    "✅ Loaded snapshot {} of {} on {}",
+                    record.id, record.vm_id, record.backend
+                );
+                println!("💡 Restore it with: vortex restore {}", record.id);
+            }
+            None => {
+                let vm_id = vm_id.ok_or_else(|| anyhow::anyhow!("VM ID required"))?;
+                let snapshot_id = vortex.snapshot_vm(&vm_id).await?;
+                println!("📸 Saved {} as snapshot {}", vm_id, snapshot_id);
+                println!("💡 Restore it with: vortex restore {}", snapshot_id);
+            }
+        },
+        Commands::Restore { snapshot_id } => {
+            let snapshot_id = SnapshotId::new(snapshot_id);
+            let vm = vortex.restore_snapshot(&snapshot_id).await?;
+            println!("✅ Restored snapshot {} as VM {}", snapshot_id, vm.id);
+        }
+        Commands::Save { vm_id, output } => {
+            let manifest = vortex.save_vm(&vm_id, &output).await?;
+            println!("📦 Saved VM {}", vm_id);
+            print_archive(&output, &manifest);
+            println!("💡 Start it again with: vortex load {}", output.display());
+        }
+        Commands::Load { archive } => {
+            let vm = vortex.load_vm(&archive).await?;
+            println!("✅ Loaded {} as VM {}", archive.display(), vm.id);
+        }
+        Commands::Clone {
+            source,
+            count,
+            memory,
+            cpus,
+        } => {
+            clone_vms(&vortex, &source, count, memory, cpus).await?;
+        }
+        Commands::Template {
+            name,
+            command,
+            publish_all,
+            action,
+        } => match action {
+            Some(TemplateCommand::Add { name, from, file }) => {
+                add_dev_template(&vortex, &name, from, file)?;
+            }
+            Some(TemplateCommand::Remove { name }) => {
+                remove_dev_template(&vortex, &name)?;
+            }
+            Some(TemplateCommand::Edit { name }) => {
+                edit_dev_template(&vortex, &name)?;
+            }
+            Some(TemplateCommand::Show { name, origin }) => {
+                show_dev_template(&vortex, &name, origin)?;
+            }
+            None => {
+                let name = name.ok_or_else(|| anyhow::anyhow!("Template name required"))?;
+                run_template(&vortex, &name, command, publish_all).await?;
+            }
+        },
+        Commands::Templates => {
+            show_templates().await?;
+        }
+        Commands::Metrics { vm_id, since } => match (vm_id, since) {
+            (Some(vm_id), Some(since)) => show_metrics_history(&vortex, &vm_id, &since).await?,
+            (vm_id, _) => show_metrics(&vortex, vm_id.as_deref()).await?,
+        },
+        Commands::Login => login(vortex.config()).await?,
+        Commands::Logout => {
+            oidc_provider(vortex.config())?.logout()?;
+            println!("👋 Signed out");
+        }
+        Commands::Top { label, sort } => {
+            let options = top::TopOptions {
+                labels: parse_labels(label)?,
+                vm_id: None,
+                sort,
+            };
+            top::run(&vortex, options).await?;
+        }
+        Commands::Parallel {
+            images,
+            command,
+            quiet,
+            copy_to,
+            sync_back,
+            network,
+        } => {
+            run_parallel_vms(&vortex, images, command, quiet, copy_to, sync_back, network).await?;
+        }
+        Commands::Dev {
+            template,
+            workdir,
+            volume,
+            port,
+            secret,
+            quiet,
+            list,
+            workspace,
+            init,
+            name,
+            detach,
+            persist_home,
+            service,
+            backend,
+        } => {
+            if list {
+                show_dev_templates(&vortex).await?;
+            } else if init {
+                init_workspace_from_current_dir(&vortex).await?;
+            } else if let Some(workspace_name) = workspace {
+                start_workspace(&vortex, &workspace_name, quiet).await?;
+            } else {
+                let (template_name, mut overrides) = match template {
+                    Some(template_name) => (template_name, DevOverrides::default()),
+                    None => resolve_dev_project(&vortex)?,
+                };
+                if backend.is_some() {
+                    overrides.backend = backend;
+                }
+                if let Some((project, service)) = project_service(service.as_deref(), context, quiet)? {
+                    project.dev_overrides(&service, &mut overrides)?;
+                }
+                start_dev_environment(
+                    &vortex,
+                    &template_name,
+                    workdir,
+                    volume,
+                    port,
+                    &parse_secrets(&secret)?,
+                    quiet,
+                    name,
+                    detach,
+                    persist_home,
+                    overrides,
+                )
+                .await?;
+            }
+        }
+        Commands::Workspace { command } => match command {
+            WorkspaceCommand::List => {
+                list_workspaces(&vortex).await?;
+            }
+            WorkspaceCommand::Create {
+                name,
+                template,
+                source,
+                backend,
+                encrypted,
+            } => {
+                create_workspace(&vortex, &name, &template, &source, &backend, encrypted).await?;
+            }
+            WorkspaceCommand::Delete { workspace } => {
+                delete_workspace(&vortex, &workspace).await?;
+            }
+            WorkspaceCommand::Info { workspace } => {
+                show_workspace_info(&vortex, &workspace).await?;
+            }
+            WorkspaceCommand::Share {
+                workspace,
+                users,
+                revoke,
+            } => {
+                share_workspace(&vortex, &workspace, &users, revoke)?;
+            }
+            WorkspaceCommand::Import {
+                name,
+                devcontainer,
+                source,
+                backend,
+            } => {
+                import_devcontainer_workspace(&vortex, &name, &devcontainer, &source, &backend)
+                    .await?;
+            }
+            WorkspaceCommand::Export { workspace, output } => {
+                export_workspace(&vortex, &workspace, &output).await?;
+            }
+            WorkspaceCommand::Load { archive, name } => {
+                load_workspace(&vortex, &archive, name.as_deref()).await?;
+            }
+            WorkspaceCommand::Init {
+                directory,
+                output,
+                non_interactive,
+                backend,
+            } => {
+                // Workspace init can work without a backend for config generation
+                handle_workspace_init(&directory, &output, non_interactive, &backend).await?;
+            }
+        },
+        Commands::Session { command } => match command {
+            SessionSubcommand::Create {
+                template,
+                name,
+                memory,
+                cpus,
+                port,
+                volume,
+                detach,
+                persistent,
+            } => {
+                handle_session_create(
+                    &vortex, &template, name, memory, cpus, &port, &volume, detach, persistent,
+                )
+                .await?;
+            }
+            SessionSubcommand::List {
+                limit,
+                cursor,
+                state,
+                label,
+            } => {
+                let query = ListQuery {
+                    cursor,
+                    limit,
+                    labels: parse_labels(label)?,
+                    state,
+                    ..Default::default()
+                };
+                handle_session_list(query).await?;
+            }
+            SessionSubcommand::Info { session } => {
+                handle_session_info(&session).await?;
+            }
+            SessionSubcommand::Start { session } => {
+                handle_session_start(&session).await?;
+            }
+            SessionSubcommand::Stop { session } => {
+                handle_session_stop(&session).await?;
+            }
+            SessionSubcommand::Pause { session } => {
+                handle_session_pause(&session).await?;
+            }
+            SessionSubcommand::Resume { session } => {
+                handle_session_resume(&session).await?;
+            }
+            SessionSubcommand::Restart { session } => {
+                handle_session_restart(&session).await?;
+            }
+            SessionSubcommand::Delete { session, force } => {
+                handle_session_delete(&session, force).await?;
+            }
+            SessionSubcommand::EnableAutostart { session } => {
+                handle_session_enable_autostart(&session).await?;
+            }
+            SessionSubcommand::DisableAutostart { session } => {
+                handle_session_disable_autostart(&session).await?;
+            }
+        },
+        Commands::Daemon { command } => match command {
+            DaemonSubcommand::Start { background } => {
+                handle_daemon_start(background).await?;
+            }
+            DaemonSubcommand::Stop => {
+                handle_daemon_stop().await?;
+            }
+            DaemonSubcommand::Status => {
+                handle_daemon_status().await?;
+            }
+            DaemonSubcommand::Restart => {
+                handle_daemon_restart().await?;
+            }
+            DaemonSubcommand::Upgrade => {
+                handle_daemon_upgrade().await?;
+            }
+            DaemonSubcommand::Logs => {
+                handle_daemon_logs().await?;
+            }
+        },
+        Commands::Attach { session } => {
+            // Just use the VM manager's attach directly
+            vortex.attach_vm(&VmId::new(session)).await?;
+        }
+        Commands::Events {
+            limit,
+            follow,
+            json,
+            vm_id,
+        } => {
+            show_events(limit, follow, json, vm_id.as_deref()).await?;
+        }
+        Commands::Audit {
+            limit,
+            user,
+            vm_id,
+            session_id,
+            action,
+            since,
+            failed,
+            json,
+        } => {
+            let query = vortex::audit::AuditQuery {
+                user,
+                vm_id,
+                session_id,
+                action: action.as_deref().map(str::parse).transpose()?,
+                since: since
+                    .as_deref()
+                    .map(parse_since)
+                    .transpose()?
+                    .map(|window| chrono::Utc::now() - window),
+                failed_only: failed,
+                limit: Some(limit),
+            };
+            show_audit(vortex.config(), &query, json)?;
+        }
+        Commands::Logs { vm_id, follow } => {
+            show_logs(&vortex, &vm_id, follow).await?;
+        }
+        Commands::Trace { id, json } => {
+            show_trace(&vortex, &id, json).await?;
+        }
+        Commands::Inspect { run_id, failure } => {
+            inspect_run(&run_id, failure)?;
+        }
+        Commands::Network { command } => match command {
+            NetworkCommand::Create {
+                name,
+                ingress_mbps,
+                egress_mbps,
+                max_connections,
+            } => {
+                let limits = NetworkLimits {
+                    ingress_mbps,
+                    egress_mbps,
+                    max_connections,
+                };
+                let network = vortex
+                    .network_manager
+                    .create_network(&name, limits)
+                    .await?;
+                println!("🌐 Created network {} ({})", network.name, network.subnet);
+                if !network.limits.is_unlimited() {
+                    println!("🚦 Each VM is limited to {}", network.limits);
+                }
+                println!("💡 Join it with: vortex run --network {} ...", network.name);
+            }
+            NetworkCommand::List => list_networks(&vortex).await?,
+            NetworkCommand::Rm { name } => {
+                vortex.network_manager.remove_network(&name).await?;
+                println!("🗑️  Deleted network {}", name);
+            }
+            NetworkCommand::Tunnel {
+                name,
+                endpoint,
+                peer,
+                listen_port,
+                down,
+            } => {
+                if down {
+                    vortex.network_manager.close_tunnel(&name).await?;
+                    println!("🔌 Closed the tunnel of network {}", name);
+                } else {
+                    let endpoint = endpoint.unwrap_or_default();
+                    let config = vortex
+                        .network_manager
+                        .open_tunnel(&name, listen_port, peer.as_deref(), &endpoint)
+                        .await?;
+                    // The configuration alone goes to stdout, for `> peer.conf`
+                    eprintln!(
+                        "🔐 Tunnel of network {} is up on UDP port {}",
+                        name, listen_port
+                    );
+                    eprintln!(
+                        "💡 On the peer, save this as vortex-{0}.conf and run \
+                         `wg-quick up ./vortex-{0}.conf`",
+                        name
+                    );
+                    print!("{}", config);
+                }
+            }
+        },
+        Commands::Home { command } => match command {
+            HomeCommand::List => list_home_volumes()?,
+            HomeCommand::Reset { template } => reset_home_volume(&template)?,
+        },
+        Commands::Volume { command } => match command {
+            VolumeCommand::Create { name, encrypted } => {
+                let volume = if encrypted {
+                    vortex.storage_manager.create_encrypted_volume(&name).await?
+                } else {
+                    vortex.storage_manager.create_volume(&name).await?
+                };
+                println!("💾 Created volume {}", volume.name);
+                if volume.encrypted {
+                    println!("🔒 Its files are encrypted at rest; mounting it unlocks it");
+                }
+                println!("💡 Mount it with: vortex run -v {}:/data ...", volume.name);
+            }
+            VolumeCommand::List => list_volumes(&vortex).await?,
+            VolumeCommand::Inspect { name } => {
+                let volume = vortex
+                    .storage_manager
+                    .get_volume(&name)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Volume {} does not exist", name))?;
+                println!("💾 Volume {}", volume.name);
+                println!("   Path:    {}", volume.path.display());
+                if volume.encrypted {
+                    let state = if volume.locked { "locked" } else { "unlocked" };
+                    println!("   Encrypted: yes, {}", state);
+                }
+                println!(
+                    "   Size:    {:.1} MB",
+                    volume.size_bytes as f64 / 1024.0 / 1024.0
+                );
+                println!(
+                    "   Created: {}",
+                    volume.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+                let snapshots = vortex.storage_manager.list_snapshots(&name).await?;
+                if !snapshots.is_empty() {
+                    println!("   Snapshots:");
+                    for snapshot in snapshots {
+                        println!(
+                            "     {}  {}",
+                            snapshot.id,
+                            snapshot.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+                        );
+                    }
+                }
+            }
+            VolumeCommand::Rm { name } => {
+                vortex.storage_manager.remove_volume(&name).await?;
+                println!("🗑️  Deleted volume {}", name);
+            }
+            VolumeCommand::Unlock { name } => {
+                let volume = vortex.storage_manager.unlock_volume(&name).await?;
+                println!("🔓 Unlocked volume {} at {}", name, volume.path.display());
+                println!("💡 Lock it again with: vortex volume lock {}", name);
+            }
+            VolumeCommand::Lock { name } => {
+                vortex.storage_manager.lock_volume(&name).await?;
+                println!("🔒 Locked volume {}", name);
+            }
+            VolumeCommand::Export { name, file } => {
+                let size = vortex.storage_manager.export_volume(&name, &file).await?;
+                println!(
+                    "📦 Exported volume {} to {} ({:.1} MB)",
+                    name,
+                    file.display(),
+                    size as f64 / 1024.0 / 1024.0
+                );
+            }
+            VolumeCommand::Import { name, file } => {
+                let volume = vortex.storage_manager.import_volume(&name, &file).await?;
+                println!(
+                    "💾 Imported volume {} from {} ({:.1} MB)",
+                    name,
+                    file.display(),
+                    volume.size_bytes as f64 / 1024.0 / 1024.0
+                );
+            }
+            VolumeCommand::Snapshot { name } => {
+                let snapshot = vortex.storage_manager.snapshot(&name).await?;
+                println!("📸 Saved volume {} as snapshot {}", name, snapshot.id);
+                println!(
+                    "💡 Roll back to it with: vortex volume rollback {} {}",
+                    name, snapshot.id
+                );
+            }
+            VolumeCommand::Rollback { name, snapshot } => {
+                vortex.storage_manager.rollback(&name, &snapshot).await?;
+                println!("⏪ Rolled volume {} back to snapshot {}", name, snapshot);
+            }
+            VolumeCommand::RmSnapshot { name, snapshot } => {
+                vortex
+                    .storage_manager
+                    .remove_snapshot(&name, &snapshot)
+                    .await?;
+                println!("🗑️  Deleted snapshot {} of volume {}", snapshot, name);
+            }
+        },
+        Commands::Cache { command } => match command {
+            CacheCommand::List => list_dependency_caches()?,
+            CacheCommand::Prune { max_unused_days } => {
+                let removed =
+                    DependencyCache::new()?.prune(chrono::Duration::days(max_unused_days))?;
+                let freed: u64 = removed.iter().map(|entry| entry.size_bytes).sum();
+                for entry in &removed {
+                    println!("🗑️  {} cache {}", entry.ecosystem, entry.key);
+                }
+                println!(
+                    "Removed {} dependency cache(s), {:.1} MB freed",
+                    removed.len(),
+                    freed as f64 / 1024.0 / 1024.0
+                );
+            }
+        },
+        Commands::Image { command } => match command {
+            ImageCommand::Pull { image } => {
+                let image = vortex.config().resolve_image(&image);
+                let pulled = ImageStore::new()?.pull(&image).await?;
+                println!(
+                    "📦 Pulled {} ({}, {} layer(s), {:.1} MB)",
+                    pulled.image,
+                    &pulled.digest[..19],
+                    pulled.layers.len(),
+                    pulled.size_bytes as f64 / 1024.0 / 1024.0
+                );
+            }
+            ImageCommand::List => list_prepared_images()?,
+            ImageCommand::Gc { max_unused_days } => gc_prepared_images(max_unused_days)?,
+        },
+        Commands::System { command } => match command {
+            SystemCommand::Prune {
+                older_than,
+                dry_run,
+            } => prune_system(&vortex, older_than, dry_run).await?,
+        },
+        Commands::Secret { command } => handle_secret_command(command)?,
+        Commands::Alias { command } => handle_alias_command(vortex.config(), command)?,
+        Commands::Config { .. } => unreachable!("handled before initialization"),
+        #[cfg(feature = "libkrun")]
+        Commands::LibkrunEnter { .. } => unreachable!("handled before initialization"),
+        #[cfg(feature = "remote")]
+        Commands::RemoteBackend { .. } => unreachable!("handled before initialization"),
+        Commands::Vm { command } => match command {
+            VmCommand::Create {
+                name,
+                image,
+                memory,
+                cpus,
+                port,
+            } => {
+                let mappings = parse_port_mappings(port)?;
+                let mut spec = VmSpec {
+                    image,
+                    memory,
+                    cpus,
+                    ports: mappings.ports,
+                    volumes: HashMap::new(),
+                    environment: HashMap::new(),
+                    command: None,
+                    labels: HashMap::new(),
+                    network_config: None,
+                    resource_limits: ResourceLimits::default(),
+                    backend: None,
+                    tuning: None,
+                    hooks: LifecycleHooks::default(),
+                    health_check: None,
+                    ttl_seconds: None,
+                    gpus: Vec::new(),
+                    provision: None,
+                    publish: mappings.publish,
+                    tls_ports: mappings.tls_ports,
+                    port_addresses: mappings.addresses,
+                    share_mechanisms: HashMap::new(),
+                    tmpfs: HashMap::new(),
+                    network_policy: NetworkPolicy::Open,
+                };
+                if let Some(policy) = project_policy()? {
+                    policy.enforce(&mut spec)?;
+                }
+                tracing::info!("Creating VM '{}' with spec: {:?}", name, spec);
+                vortex.create_vm(spec).await?;
+            }
+            VmCommand::List => {
+                list_vms(&vortex).await?;
+            }
+            VmCommand::Stop { vm_name } => {
+                stop_vm(&vortex, &vm_name).await?;
+            }
+            VmCommand::Cleanup { name } => {
+                if let Some(vm_name) = name {
+                    vortex.cleanup_vm(&VmId::new(vm_name)).await?;
+                } else {
+                    cleanup_vms(&vortex).await?;
+                }
+            }
+        }
+        Commands::Plugin { command } => match command {
+            PluginCommand::List => {
+                list_plugins(&vortex).await?;
+            }
+            PluginCommand::Add { repo, name } => {
+                add_plugin(&vortex, &repo, name.as_deref()).await?;
+            }
+            PluginCommand::Remove { name } => {
+                remove_plugin(&vortex, &name).await?;
+            }
+            PluginCommand::Enable { name } => {
+                enable_plugin(&vortex, &name).await?;
+            }
+            PluginCommand::Disable { name } => {
+                disable_plugin(&vortex, &name).await?;
+            }
+            PluginCommand::Grant { name, capabilities } => {
+                update_plugin_grants(&name, &capabilities, true)?;
+            }
+            PluginCommand::Revoke { name, capabilities } => {
+                update_plugin_grants(&name, &capabilities, false)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The `.vortex-policy.yaml` governing the current directory, if any
+fn project_policy() -> Result<Option<ProjectPolicy>> {
+    Ok(ProjectPolicy::discover(&std::env::current_dir()?)?)
+}
+
+/// The `vortex.yaml` of the current directory, in `context`, and the service
+/// of it to use, if there is one
+fn project_service(
+    name: Option<&str>,
+    context: Option<&str>,
+    quiet: bool,
+) -> Result<Option<(ProjectConfig, ServiceConfig)>> {
+    let Some(project) = ProjectConfig::load_in_context(&std::env::current_dir()?, context)? else {
+        let option = match (name, context) {
+            (Some(name), _) => format!("--service {}", name),
+            (None, Some(context)) => format!("--context {}", context),
+            (None, None) => return Ok(None),
+        };
+        return Err(anyhow::anyhow!(
+            "{} needs a {} in the current directory",
+            option,
+            PROJECT_FILE
+        ));
+    };
+    // Several services and none chosen: run without any rather than fail
+    if name.is_none() && project.service(None).is_err() {
+        if !quiet {
+            println!(
+                "💡 {} has several services; pick one with --service to use it",
+                PROJECT_FILE
+            );
+        }
+        return Ok(None);
+    }
+    let Some((name, service)) = project.service(name)? else {
+        return Ok(None);
+    };
+    if !quiet {
+        match &project.context {
+            Some(context) => println!(
+                "📄 Using service '{}' of {} in context '{}'",
+                name, PROJECT_FILE, context
+            ),
+            None => println!("📄 Using service '{}' of {}", name, PROJECT_FILE),
+        }
+    }
+    let service = service.clone();
+    Ok(Some((project, service)))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_vm(
+    vortex: &Arc<VortexCore>,
+    mut spec: VmSpec,
+    persist: bool,
+    quiet: bool,
+    monitor_performance: bool,
+    copy_to: Vec<String>,
+    sync_back: Vec<String>,
+    on_conflict: ConflictPolicy,
+    workdir: Option<String>,
+    cache_deps: bool,
+    secrets: &[SecretRequest],
+) -> Result<Option<VmUsage>> {
+    let copy_mappings = parse_copy_mappings(copy_to)?;
+    let sync_mappings = parse_sync_back_mappings(sync_back)?;
+
+    // Checked before Vortex adds its own run, cache and diagnostics mounts.
+    // The --copy sources are mounted too, so they are held to the same rules
+    if let Some(policy) = project_policy()? {
+        policy.enforce(&mut spec)?;
+        policy.check_copies(&copy_mappings)?;
+    }
+
+    // Per-run directory so transient mount points never collide between runs
+    progress::phase("preparing", "Preparing run directory and mounts");
+    let mut run_dir = RunDir::create()?;
+    spec.labels
+        .insert(LABEL_RUN_ID.to_string(), run_dir.run_id().to_string());
+
+    // Mount the caches of the ecosystems the project's lockfiles name
+    if cache_deps {
+        let mounts = DependencyCache::new()?
+            .prepare(&std::env::current_dir()?)
+            .await?;
+        if mounts.is_empty() && !quiet {
+            println!("⚠️  No lockfile found here, so no dependency cache is mounted");
+        }
+        for mount in &mounts {
+            mount.apply(&mut spec);
+            if !quiet {
+                match &mount.seeded_from {
+                    Some(seed) => println!(
+                        "🔄 New {} cache {}, seeded from {}",
+                        mount.ecosystem, mount.key, seed
+                    ),
+                    None => println!("🔄 Using {} cache {}", mount.ecosystem, mount.key),
+                }
+            }
+        }
+    }
+
+    // Add temporary mount points for copy operations
+    for (i, (host_path, _)) in copy_mappings.iter().enumerate() {
+        let temp_mount = run_dir.guest_path(&format!("copy_in_{}", i));
+        spec.volumes.insert(host_path.clone(), temp_mount);
+    }
+
+    // Sync back results are staged in the run directory and copied to the
+    // host afterwards, so files edited on the host meanwhile are not clobbered
+    let mut pending_sync = PendingSync {
+        policy: on_conflict,
+        mappings: Vec::new(),
+    };
+    for (i, (guest_path, host_path)) in sync_mappings.iter().enumerate() {
+        let staging = run_dir.path().join(format!("sync_back_{}", i));
+        pending_sync.mappings.push(SyncBack::new(
+            guest_path.clone(),
+            host_path.clone(),
+            staging.clone(),
+        )?);
+        let temp_mount = run_dir.guest_path(&format!("copy_out_{}", i));
+        spec.volumes.insert(staging, temp_mount);
+    }
+    if !pending_sync.mappings.is_empty() {
+        pending_sync.save(run_dir.path())?;
+    }
+
+    // Failure bundles are written here by the wrapped guest command
+    if spec.command.is_some() {
+        let bundle_dir = diagnostics::prepare(run_dir.run_id().as_str())?;
+        spec.volumes.insert(bundle_dir, run_dir.guest_path("diagnostics"));
+    }
+
+    // Copy steps, workdir and diagnostics around the user's command, which
+    // the VM isolates; paths in the copy mappings were validated
+    if let Some(command) = &spec.command {
+        let copy_to: Vec<PathBuf> = copy_mappings
+            .iter()
+            .map(|(_, dest)| dest.clone())
+            .collect();
+        let sync_back: Vec<PathBuf> = sync_mappings
+            .iter()
+            .map(|(source, _)| source.clone())
+            .collect();
+        spec.command = Some(run_dir.guest_command(
+            command,
+            &copy_to,
+            workdir.as_deref(),
+            &sync_back,
+        ));
+    }
+
+    // Only the mount and a line sourcing it go into the spec, never the values
+    if !secrets.is_empty() {
+        let prepared = SecretsManager::new()
+            .and_then(|manager| manager.prepare(secrets, &run_dir.path().join("secrets")));
+        match prepared {
+            Ok(mount) => mount.apply(&mut spec),
+            Err(e) => {
+                if let Err(cleanup_err) = run_dir.remove() {
+                    tracing::warn!("Failed to remove run directory: {}", cleanup_err);
+                }
+                return Err(e.into());
+            }
+        }
+    }
+
+    if !quiet {
+        info!("Starting VM with image: {}", spec.image);
+    }
+
+    progress::phase("creating_vm", format!("Creating VM from image {}", spec.image));
+    let vm = match vortex.create_vm(spec).await {
+        Ok(vm) => vm,
+        Err(e) => {
+            if let Err(cleanup_err) = run_dir.remove() {
+                tracing::warn!("Failed to remove run directory: {}", cleanup_err);
+            }
+            return Err(e.into());
+        }
+    };
+    run_dir.attach_vm(&vm)?;
+    progress::phase("vm_started", format!("VM {} started", vm.id));
+    if !quiet {
+        let mut ports: Vec<_> = vm.spec.ports.iter().collect();
+        ports.sort_by_key(|(_, guest)| **guest);
+        let address = |host: u16| match vm.spec.port_addresses.get(&host) {
+            Some(ip) => SocketAddr::new(*ip, host).to_string(),
+            None => format!("localhost:{}", host),
+        };
+        for (host, guest) in ports {
+            println!("🔌 Port {} → {}", guest, address(*host));
+        }
+        let mut tls_ports: Vec<_> = vm.spec.tls_ports.iter().collect();
+        tls_ports.sort_by_key(|(_, guest)| **guest);
+        for (host, guest) in tls_ports {
+            println!("🔒 Port {} → https://{}", guest, address(*host));
+        }
+    }
+
+    if let Some(probe) = &vm.spec.health_check {
+        progress::phase("waiting_ready", format!("Waiting for VM {} to pass its health check", vm.id));
+        if let Err(e) = vortex.wait_ready(&vm.id, probe, READY_TIMEOUT).await {
+            if !persist {
+                if let Err(stop_err) = stop_vm(vortex, &vm.id).await {
+                    tracing::warn!("Failed to clean up VM {}: {}", vm.id, stop_err);
+                }
+            }
+            return Err(e.into());
+        }
+    }
+
+    // The dashboard closes when the user quits it or the VM is gone
+    if monitor_performance && !quiet {
+        let options = top::TopOptions {
+            labels: HashMap::new(),
+            vm_id: Some(vm.id.to_string()),
+            sort: top::SortKey::Cpu,
+        };
+        top::run(vortex, options).await?;
+    }
+
+    if persist {
+        if !quiet {
+            info!(
+                "VM {} started and persisting. Use 'vortex stop {}' to stop it.",
+                vm.id, vm.id
+            );
+        }
+    } else if vm.spec.command.is_some() {
+        // Non-persistent VMs go away once their command finishes, taking
+        // --sync-back results to the host on the way
+        progress::phase("waiting", format!("Waiting for the command in VM {}", vm.id));
+        let status = vortex.wait_vm(&vm.id).await;
+        let usage = vortex.vm_usage(&vm.id).await;
+        if let Err(e) = stop_vm(vortex, &vm.id).await {
+            tracing::warn!("Failed to clean up VM {}: {}", vm.id, e);
+        }
+        let status = status?;
+        if !quiet {
+            info!(
+                "Command exited with code {}. Its output: vortex logs {}",
+                status.code, vm.id
+            );
+            if let Ok(usage) = &usage {
+                println!("📊 Resources: {}", describe_usage(usage));
+            }
+        }
+        if !status.success() {
+            vortex.flush_events(EVENT_FLUSH_TIMEOUT).await;
+            std::process::exit(status.code);
+        }
+        return Ok(usage.ok());
+    } else if !quiet {
+        info!("VM {} started. Use 'vortex stop {}' to stop it.", vm.id, vm.id);
+    }
+
+    Ok(None)
+}
+
+fn describe_usage(usage: &VmUsage) -> String {
+    format!(
+        "{:.1} CPU-seconds, {:.0}MB peak memory, {:.1}s wall time",
+        usage.cpu_seconds,
+        usage.peak_memory_bytes as f64 / 1024.0 / 1024.0,
+        usage.wall_seconds
+    )
+}
+
+fn print_event(line: &str, json: bool) {
+    if json {
+        println!("{}", line);
+        return;
+    }
+
+    match vortex::events::parse_event(line) {
+        Ok(event) => {
+            let body = match &event.payload {
+                EventPayload::VmCreated { vm_id } => format!("🆕 VM {} created", vm_id),
+                EventPayload::VmStarted { vm_id } => format!("🟢 VM {} started", vm_id),
+                EventPayload::VmStopped { vm_id } => format!("🔴 VM {} stopped", vm_id),
+                EventPayload::VmExpired { vm_id } => format!("⏰ VM {} expired", vm_id),
+                EventPayload::VmError { vm_id, error } => {
+                    format!("❌ VM {} error: {}", vm_id, error)
+                }
+                EventPayload::SnapshotCreated { vm_id, snapshot_id } => {
+                    format!("📸 VM {} snapshot {}", vm_id, snapshot_id)
+                }
+                EventPayload::ResourceUsage {
+                    vm_id,
+                    cpu_percent,
+                    memory_bytes,
+                } => format!(
+                    "📊 VM {} cpu {:.1}% mem {}MB",
+                    vm_id,
+                    cpu_percent,
+                    memory_bytes / 1024 / 1024
+                ),
+                EventPayload::DiskQuotaExceeded {
+                    vm_id,
+                    used_bytes,
+                    limit_bytes,
+                } => format!(
+                    "💾 VM {} disk {}MB over its {}MB limit",
+                    vm_id,
+                    used_bytes / 1024 / 1024,
+                    limit_bytes / 1024 / 1024
+                ),
+                EventPayload::VmPaused { vm_id } => format!("⏸️  VM {} paused", vm_id),
+                EventPayload::VmResumed { vm_id } => format!("▶️  VM {} resumed", vm_id),
+                EventPayload::VmResized {
+                    vm_id,
+                    memory_mb,
+                    cpus,
+                } => format!(
+                    "📐 VM {} resized to {}MB and {} CPU(s)",
+                    vm_id, memory_mb, cpus
+                ),
+                EventPayload::VmHealthChanged {
+                    vm_id,
+                    healthy: true,
+                    ..
+                } => format!("💚 VM {} is healthy", vm_id),
+                EventPayload::VmHealthChanged { vm_id, message, .. } => format!(
+                    "🤒 VM {} is unhealthy: {}",
+                    vm_id,
+                    message.as_deref().unwrap_or("health check failed")
+                ),
+                EventPayload::VmCleanedUp { vm_id } => format!("🧹 VM {} cleaned up", vm_id),
+                EventPayload::MetricAlert {
+                    vm_id,
+                    alert,
+                    metric,
+                    value,
+                    threshold,
+                } => format!(
+                    "🚨 VM {} {} at {:.1}, over {} (alert '{}')",
+                    vm_id, metric, value, threshold, alert
+                ),
+                EventPayload::SessionStateChanged {
+                    session_id, state, ..
+                } => format!("🔄 Session {} is now {:?}", session_id, state),
+                EventPayload::Unknown => "❔ Unknown event type".to_string(),
+            };
+            println!("{} {}", event.timestamp.format("%Y-%m-%d %H:%M:%S"), body);
+        }
+        Err(e) => tracing::warn!("Skipping unreadable event: {}", e),
+    }
+}
+
+async fn show_events(limit: usize, follow: bool, json: bool, vm_id: Option<&str>) -> Result<()> {
+    let wanted = |line: &&str| match vm_id {
+        Some(vm_id) => vortex::events::parse_event(line)
+            .is_ok_and(|event| event.payload.vm_id() == Some(vm_id)),
+        None => true,
+    };
+    let path = vortex::events::event_log_path()?;
+    let content = std::fs::read_to_string(&path).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().filter(wanted).collect();
+
+    if lines.is_empty() && !follow {
+        println!("No events recorded yet.");
+        return Ok(());
+    }
+
+    for line in &lines[lines.len().saturating_sub(limit)..] {
+        print_event(line, json);
+    }
+
+    if follow {
+        let mut offset = content.len() as u64;
+        loop {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            let Ok(new_content) = std::fs::read(&path) else {
+                continue;
+            };
+            if (new_content.len() as u64) < offset {
+                // Log was truncated or rotated
+                offset = 0;
+            }
+            let fresh = String::from_utf8_lossy(&new_content[offset as usize..]).to_string();
+            // Only consume complete lines
+            if let Some(end) = fresh.rfind('\n') {
+                for line in fresh[..end].lines().filter(wanted) {
+                    print_event(line, json);
+                }
+                offset += end as u64 + 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn show_audit(config: &VortexConfig, query: &vortex::audit::AuditQuery, json: bool) -> Result<()> {
+    use vortex::audit::{AuditLog, AuditOutcome};
+
+    let Some(audit) = AuditLog::from_config(&config.audit)? else {
+        println!("Auditing is off; set [audit] target = \"file\" to record operations.");
+        return Ok(());
+    };
+    let records = audit.query(query)?;
+    if records.is_empty() {
+        println!("No audit records found.");
+        return Ok(());
+    }
+    for record in records {
+        if json {
+            println!("{}", serde_json::to_string(&record)?);
+            continue;
+        }
+        let who = match &record.host_user {
+            Some(host_user) if *host_user != record.user => {
+                format!("{} ({})", record.user, host_user)
+            }
+            _ => record.user.clone(),
+        };
+        let mut line = format!(
+            "{} {} {} {}",
+            if record.failed() { "❌" } else { "✅" },
+            record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            who,
+            record.action
+        );
+        for id in [&record.session_id, &record.vm_id].into_iter().flatten() {
+            line.push_str(&format!(" {}", id));
+        }
+        if let Some(image) = &record.image {
+            line.push_str(&format!(" image={}", image));
+        }
+        if !record.paths.is_empty() {
+            line.push_str(&format!(" paths={}", record.paths.join(",")));
+        }
+        if !record.command.is_empty() {
+            line.push_str(&format!(" command={:?}", record.command.join(" ")));
+        }
+        if let Some(code) = record.exit_code {
+            line.push_str(&format!(" exit={}", code));
+        }
+        if let AuditOutcome::Failed { error } = &record.outcome {
+            line.push_str(&format!(": {}", error));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+async fn show_logs(vortex: &Arc<VortexCore>, vm_id: &VmId, follow: bool) -> Result<()> {
+    use futures::StreamExt;
+
+    let lines = vortex.vm_logs(vm_id, follow).await?;
+    futures::pin_mut!(lines);
+    while let Some(line) = lines.next().await {
+        println!("{}", line?);
+    }
+    Ok(())
+}
+
+fn print_trace_node(node: &TraceNode, depth: usize) {
+    let icon = match node.kind {
+        TraceKind::Workspace => "📁",
+        TraceKind::Session => "🖥️ ",
+        TraceKind::Vm => "⚡",
+        TraceKind::Run => "▶️ ",
+    };
+    let marker = if node.matched { "  ◀" } else { "" };
+    println!(
+        "{}{} {:?} {} {}{}",
+        "   ".repeat(depth),
+        icon,
+        node.kind,
+        node.id,
+        node.summary,
+        marker
+    );
+    for child in &node.children {
+        print_trace_node(child, depth + 1);
+    }
+}
+
+async fn show_trace(vortex: &Arc<VortexCore>, id: &str, json: bool) -> Result<()> {
+    let index = TraceIndex {
+        workspaces: vortex.workspace_manager.list_workspaces()?,
+        sessions: vortex.list_sessions().await?,
+        runs: run_dir::list_runs()?
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect(),
+    };
+
+    let node = index
+        .trace(id)
+        .ok_or_else(|| anyhow::anyhow!("No workspace, session, VM or run matches '{}'", id))?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&node)?);
+    } else {
+        print_trace_node(&node, 0);
+    }
+
+    Ok(())
+}
+
+fn inspect_run(run_id: &str, failure: bool) -> Result<()> {
+    let record =
+        run_dir::find_run(run_id)?.ok_or_else(|| anyhow::anyhow!("No run with ID '{}'", run_id))?;
+    let bundle = diagnostics::load_failure(run_id)?;
+
+    println!("▶️  Run {}", record.run_id);
+    println!(
+        "   Started: {}",
+        record.created_at.format("%Y-%m-%d %H:%M:%S")
+    );
+    if let Some(vm_id) = &record.vm_id {
+        println!("   VM: {}", vm_id);
+    }
+    if let Some(session_id) = &record.session_id {
+        println!("   Session: {}", session_id);
+    }
+    if let Some(workspace_id) = &record.workspace_id {
+        println!("   Workspace: {}", workspace_id);
+    }
+
+    match (&bundle, failure) {
+        (None, _) => println!("   No failure captured"),
+        (Some(bundle), false) => {
+            println!("   ❌ Failed with exit code {}", bundle.exit_code);
+            println!("   Details: vortex inspect {} --failure", run_id);
+        }
+        (Some(bundle), true) => {
+            println!("   ❌ Failed with exit code {}", bundle.exit_code);
+            for (name, content) in &bundle.files {
+                println!();
+                println!("── {} ──", name);
+                print!("{}", content);
+                if !content.ends_with('\n') {
+                    println!();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_networks(vortex: &Arc<VortexCore>) -> Result<()> {
+    let networks = vortex.network_manager.list_networks().await?;
+    if networks.is_empty() {
+        println!("No private networks. Create one with 'vortex network create <name>'.");
+        return Ok(());
+    }
+
+    println!("🌐 Private networks:");
+    for network in networks {
+        if network.limits.is_unlimited() {
+            println!("  {:<16} {}", network.name, network.subnet);
+        } else {
+            println!(
+                "  {:<16} {}  ({} per VM)",
+                network.name, network.subnet, network.limits
+            );
+        }
+        for member in network.members.values() {
+            println!("    {:<14} {}", member.ip_address, member.vm_id);
+        }
+        if let Some(tunnel) = &network.tunnel {
+            for (name, peer) in &tunnel.peers {
+                println!("    {:<14} {} (tunnel peer)", peer.address, name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_volumes(vortex: &Arc<VortexCore>) -> Result<()> {
+    let volumes = vortex.storage_manager.list_volumes().await?;
+    if volumes.is_empty() {
+        println!("No named volumes. Create one with 'vortex volume create <name>'.");
+        return Ok(());
+    }
+
+    println!("💾 Named volumes:");
+    for volume in volumes {
+        let lock = match (volume.encrypted, volume.locked) {
+            (false, _) => "  ",
+            (true, true) => "🔒",
+            (true, false) => "🔓",
+        };
+        println!(
+            "  {:<24} {:>8.1} MB  {} {}",
+            volume.name,
+            volume.size_bytes as f64 / 1024.0 / 1024.0,
+            lock,
+            volume.path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn list_home_volumes() -> Result<()> {
+    let volumes = home_volume::list_home_volumes()?;
+    if volumes.is_empty() {
+        println!(
+            "No persistent home volumes. Enable one with 'vortex dev <template> --persist-home'."
+        );
+        return Ok(());
+    }
+
+    println!("🏠 Persistent home volumes:");
+    for (template, path) in volumes {
+        println!("  {:<16} {}", template, path.display());
+    }
+
+    Ok(())
+}
+
+fn reset_home_volume(template: &str) -> Result<()> {
+    if home_volume::remove_home_volume(template)? {
+        println!("🗑️  Home volume for '{}' deleted", template);
+    } else {
+        println!("No home volume for '{}'", template);
+    }
+    Ok(())
+}
+
+fn list_prepared_images() -> Result<()> {
+    let pulled = ImageStore::new()?.list()?;
+    if !pulled.is_empty() {
+        println!("⬇️  Pulled images:");
+        for image in pulled {
+            println!(
+                "  {:<24} {}  {:>8.1} MB  pulled {}",
+                image.image,
+                &image.digest[..19],
+                image.size_bytes as f64 / 1024.0 / 1024.0,
+                image.pulled_at.format("%Y-%m-%d %H:%M")
+            );
+        }
+    }
+
+    let entries = ImageCache::new()?.entries()?;
+    if entries.is_empty() {
+        println!("No prepared images. One is built from ~/.vortex/images/<image>.tar, pulled or put there, on first use.");
+        return Ok(());
+    }
+
+    println!("📦 Prepared images:");
+    for entry in entries {
+        println!(
+            "  {:<24} {:<10} sha256:{}  {:>8.1} MB  last used {}",
+            entry.image,
+            format!("{:?}", entry.format).to_lowercase(),
+            &entry.digest[..12],
+            entry.size_bytes as f64 / 1024.0 / 1024.0,
+            entry.last_used.format("%Y-%m-%d %H:%M")
+        );
+    }
+    Ok(())
+}
+
+fn list_dependency_caches() -> Result<()> {
+    let entries = DependencyCache::new()?.entries()?;
+    if entries.is_empty() {
+        println!("No dependency caches. `vortex run --cache-deps` creates them from the project's lockfiles.");
+        return Ok(());
+    }
+
+    println!("📦 Dependency caches:");
+    for entry in &entries {
+        println!(
+            "  {:<6} {}  {:>8.1} MB  last used {}",
+            entry.ecosystem.name(),
+            entry.key,
+            entry.size_bytes as f64 / 1024.0 / 1024.0,
+            entry.last_used.format("%Y-%m-%d %H:%M")
+        );
+    }
+    let total: u64 = entries.iter().map(|entry| entry.size_bytes).sum();
+    println!("Total: {:.1} MB", total as f64 / 1024.0 / 1024.0);
+    Ok(())
+}
+
+fn gc_prepared_images(max_unused_days: i64) -> Result<()> {
+    let removed = ImageCache::new()?.gc(chrono::Duration::days(max_unused_days))?;
+    let freed: u64 = removed.iter().map(|e| e.size_bytes).sum();
+    for entry in &removed {
+        println!("🗑️  {} (sha256:{})", entry.image, &entry.digest[..12]);
+    }
+    println!(
+        "Removed {} prepared image(s), {:.1} MB freed",
+        removed.len(),
+        freed as f64 / 1024.0 / 1024.0
+    );
+    let layers_freed = LayerStore::new()?.prune()?;
+    if layers_freed > 0 {
+        println!(
+            "Removed base layers no VM uses, {:.1} MB freed",
+            layers_freed as f64 / 1024.0 / 1024.0
+        );
+    }
+    Ok(())
+}
+
+async fn prune_system(
+    vortex: &Arc<VortexCore>,
+    older_than_hours: i64,
+    dry_run: bool,
+) -> Result<()> {
+    let live: Vec<String> = vortex
+        .list_vms()
+        .await?
+        .into_iter()
+        .map(|vm| vm.id.to_string())
+        .collect();
+    let pruned = vortex
+        .storage_manager
+        .prune(&live, chrono::Duration::hours(older_than_hours), dry_run)
+        .await?;
+
+    for item in &pruned {
+        let kind = match item.kind {
+            PrunedKind::RunDirectory => "run directory",
+            PrunedKind::VmDirectory => "VM directory",
+            PrunedKind::BaseLayer => "base layer",
+            PrunedKind::ImageBlob => "image blob",
+        };
+        println!(
+            "🗑️  {:<14} {}  {:.1} MB",
+            kind,
+            item.path.display(),
+            item.size_bytes as f64 / 1024.0 / 1024.0
+        );
+    }
+    let freed: u64 = pruned.iter().map(|item| item.size_bytes).sum();
+    println!(
+        "{} {} item(s), {:.1} MB{}",
+        if dry_run { "Would remove" } else { "Removed" },
+        pruned.len(),
+        freed as f64 / 1024.0 / 1024.0,
+        if dry_run { "" } else { " freed" }
+    );
+    Ok(())
+}
+
+fn validate_config() -> Result<()> {
+    let dir = std::env::current_dir()?;
+    let templates = DevEnvironmentManager::new().with_project_templates(&dir);
+    let mut diagnostics = Vec::new();
+    let system_path = vortex::config::system_config_path();
+    match std::fs::read_to_string(&system_path) {
+        Ok(content) => {
+            diagnostics.extend(validation::check_config(&system_path, &content, &templates))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).context(format!("Failed to read {}", system_path.display())),
+    }
+    let config_path = vortex::config::get_config_path()?;
+    match std::fs::read_to_string(&config_path) {
+        Ok(content) => {
+            diagnostics.extend(validation::check_config(&config_path, &content, &templates))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("No config file at {}; defaults apply", config_path.display());
+        }
+        Err(e) => return Err(e).context(format!("Failed to read {}", config_path.display())),
+    }
+    diagnostics.extend(validation::check_project(&dir, &templates)?);
+
+    if diagnostics.is_empty() {
+        println!("✅ No problems found");
+        return Ok(());
+    }
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    Err(anyhow::anyhow!(
+        "Found {} problem{}",
+        diagnostics.len(),
+        if diagnostics.len() == 1 { "" } else { "s" }
+    ))
+}
+
+fn handle_alias_command(config: &VortexConfig, command: AliasCommand) -> Result<()> {
+    match command {
+        AliasCommand::List => {
+            let mut names: Vec<&String> = config.image_aliases.keys().collect();
+            names.sort();
+            for name in names {
+                println!("{} -> {}", name, config.resolve_image(name));
+            }
+            match &config.default_registry {
+                Some(registry) => println!("\nDocker Hub images are pulled from {}", registry),
+                None => println!("\nNo default_registry set; Docker Hub images come from docker.io"),
+            }
+        }
+        AliasCommand::Add { name, image } => {
+            ImageReference::parse(&name).context("Invalid alias")?;
+            ImageReference::parse(&image)?;
+            let mut config = VortexConfig::load_raw()?;
+            config.image_aliases.insert(name.clone(), image.clone());
+            config.save()?;
+            println!("✅ {} -> {}", name, image);
+        }
+        AliasCommand::Remove { name } => {
+            let mut config = VortexConfig::load_raw()?;
+            if config.image_aliases.remove(&name).is_none() {
+                return Err(anyhow::anyhow!("No image alias '{}'", name));
+            }
+            config.save()?;
+            // Aliases of the defaults or the system config come back
+            match VortexConfig::load_raw()?.image_aliases.get(&name) {
+                Some(image) => println!(
+                    "'{}' is also an alias of the defaults or the system config; it now stands for {}",
+                    name, image
+                ),
+                None => println!("🗑️  Removed alias '{}'", name),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_secret_command(command: SecretCommand) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let secrets = SecretsManager::new()?;
+    match command {
+        SecretCommand::Set { name } => {
+            credentials::secret_key(&name)?;
+            let interactive = std::io::stdin().is_terminal();
+            if interactive {
+                eprint!("Value for '{}': ", name);
+                let _ = std::process::Command::new("stty").arg("-echo").status();
+            }
+            let mut value = String::new();
+            let read = std::io::stdin().read_line(&mut value);
+            if interactive {
+                let _ = std::process::Command::new("stty").arg("echo").status();
+                eprintln!();
+            }
+            read?;
+            let value = value.trim_end_matches(['\r', '\n']);
+            if value.is_empty() {
+                return Err(anyhow::anyhow!("Refusing to store an empty secret"));
+            }
+            secrets.set(&name, value)?;
+            println!("🔐 Stored secret '{}' in {}", name, secrets.store_name());
+        }
+        SecretCommand::Get { name } => {
+            match secrets.get(&name)? {
+                Some(value) => println!("{}", value),
+                None => return Err(anyhow::anyhow!("No secret named '{}'", name)),
+            }
+        }
+        SecretCommand::Rm { name } => {
+            if secrets.delete(&name)? {
+                println!("🗑️  Secret '{}' deleted", name);
+            } else {
+                println!("No secret named '{}'", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn list_vms(vortex: &Arc<VortexCore>) -> Result<()> {
+    let vms = vortex.list_vms().await?;
+    // Health checks of VMs started by other `vortex run`s
+    let probes: HashMap<String, Probe> = run_dir::list_runs()?
+        .into_iter()
+        .filter_map(|(_, record)| Some((record.vm_id?.to_string(), record.health_check?)))
+        .collect();
+
+    if vms.is_empty() {
+        println!("No background sessions found.");
+        println!("💡 Create one with: vortex dev <template> --name <name> --detach");
+    } else {
+        println!("🔥 Background Sessions:");
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        for vm in vms {
+            let isolation = if vm.backend.reduced_isolation() {
+                format!(" ⚠️  reduced isolation ({})", vm.backend.name())
+            } else {
+                String::new()
+            };
+            let state = match vortex
+                .check_health(&vm.id, probes.get(vm.id.as_str()))
+                .await
+            {
+                Ok(state) => state,
+                Err(_) => vm.state.clone(),
+            };
+            let (icon, health) = match &state {
+                VmState::Starting => ("🟡", " - starting".to_string()),
+                VmState::Unhealthy { message } => ("🔴", format!(" - unhealthy: {}", message)),
+                _ => ("🟢", String::new()),
+            };
+            println!(
+                "{} {} - {}MB RAM, {} CPU(s){}{}",
+                icon, vm.id, vm.spec.memory, vm.spec.cpus, isolation, health
+            );
+        }
+        println!();
+        println!("💡 Attach to session: vortex attach <session-id>");
+    }
+
+    Ok(())
+}
+
+fn list_snapshots(vortex: &VortexCore) -> Result<()> {
+    let snapshots = vortex.list_snapshots()?;
+
+    if snapshots.is_empty() {
+        println!("No snapshots found.");
+        println!("💡 Snapshot a running VM with: vortex snapshot <vm-id>");
+        return Ok(());
+    }
+
+    println!("📸 Snapshots:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    for (snapshot, size) in snapshots {
+        println!(
+            "{} - {} on {} ({}), {} MB, {}",
+            snapshot.id,
+            snapshot.vm_id,
+            snapshot.backend,
+            snapshot.spec.image,
+            size / (1024 * 1024),
+            snapshot.created_at.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    Ok(())
+}
+
+async fn clone_vms(
+    vortex: &Arc<VortexCore>,
+    source: &str,
+    count: u32,
+    memory: Option<u32>,
+    cpus: Option<u32>,
+) -> Result<()> {
+    if count == 0 {
+        return Err(anyhow::anyhow!("Clone count must be at least 1"));
+    }
+
+    if let Ok(record) = vortex.get_snapshot(&SnapshotId::new(source)) {
+        if memory.is_some() || cpus.is_some() {
+            return Err(anyhow::anyhow!(
+                "Snapshot {} restores with the {} MB and {} CPU(s) it was taken with",
+                record.id,
+                record.spec.memory,
+                record.spec.cpus
+            ));
+        }
+        for _ in 0..count {
+            let vm = vortex.restore_snapshot(&record.id).await?;
+            println!("✅ Restored snapshot {} as VM {}", record.id, vm.id);
+        }
+        return Ok(());
+    }
+
+    // Sessions keep the spec of VMs this process did not start
+    let sessions = vortex.list_sessions().await?;
+    let session = sessions.iter().find(|session| {
+        session.id == source || session.vm_id == source || session.name.as_deref() == Some(source)
+    });
+    let vm_id = session.map_or_else(|| VmId::new(source), |session| session.vm_id.clone());
+    let mut overrides = session.map(|session| session.spec.for_clone(&vm_id));
+    match overrides.as_mut() {
+        Some(spec) => {
+            spec.memory = memory.unwrap_or(spec.memory);
+            spec.cpus = cpus.unwrap_or(spec.cpus);
+        }
+        None if memory.is_some() || cpus.is_some() => {
+            return Err(anyhow::anyhow!(
+                "The spec of VM {} is unknown, so it cannot be changed; clone a session instead",
+                vm_id
+            ));
+        }
+        None => {}
+    }
+
+    for _ in 0..count {
+        let vm = vortex.clone_vm(&vm_id, overrides.clone()).await?;
+        println!("✅ Cloned {} as VM {}", vm_id, vm.id);
+    }
+    Ok(())
+}
+
+async fn stop_vm(vortex: &Arc<VortexCore>, vm_id: &VmId) -> Result<()> {
+    vortex.stop_vm(vm_id).await?;
+    sync_runs_for_vm(vm_id)?;
+    run_dir::remove_runs_for_vm(vm_id)?;
+    info!("VM {} stopped and cleaned up.", vm_id);
+    Ok(())
+}
+
+async fn cleanup_vms(vortex: &Arc<VortexCore>) -> Result<()> {
+    let vms = vortex.list_vms().await?;
+    let count = vms.len();
+
+    for vm in &vms {
+        if let Err(e) = vortex.cleanup_vm(&vm.id).await {
+            tracing::warn!("Failed to cleanup VM {}: {}", vm.id, e);
+        }
+        if let Err(e) = sync_runs_for_vm(&vm.id) {
+            tracing::warn!("Keeping run directories for VM {}: {}", vm.id, e);
+            continue;
+        }
+        if let Err(e) = run_dir::remove_runs_for_vm(&vm.id) {
+            tracing::warn!("Failed to remove run directories for VM {}: {}", vm.id, e);
+        }
+        if let Err(e) = logs::remove(&vm.id) {
+            tracing::warn!("Failed to remove the log of VM {}: {}", vm.id, e);
+        }
+    }
+
+    // Sweep run directories left behind by crashed or killed runs
+    let removed = run_dir::cleanup_stale_runs(chrono::Duration::hours(1), &[])?;
+    if removed > 0 {
+        info!("Removed {} stale run directories.", removed);
+    }
+
+    info!("Cleaned up {} VMs.", count);
+    Ok(())
+}
+
+/// Copy a run's staged --sync-back results to the host, settling conflicts
+/// with `policy` or else the policy the run was started with
+fn apply_sync_back(run_path: &Path, run_id: &str, policy: Option<ConflictPolicy>) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let Some(mut pending) = PendingSync::load(run_path)? else {
+        return Ok(());
+    };
+    let policy = policy.unwrap_or(pending.policy);
+    let mut unresolved = Vec::new();
+    for mapping in &mut pending.mappings {
+        let (applied, conflicts) = mapping.apply()?;
+        if applied > 0 {
+            info!(
+                "Synced {} file(s) from {} to {}",
+                applied,
+                mapping.guest.display(),
+                mapping.host.display()
+            );
+        }
+        for conflict in conflicts {
+            let resolution = match policy.resolution() {
+                Some(resolution) => resolution,
+                None if std::io::stdin().is_terminal() => {
+                    prompt_conflict(&conflict, std::io::stdout().is_terminal())?
+                }
+                None => {
+                    unresolved.push(conflict.host.display().to_string());
+                    continue;
+                }
+            };
+            if let Err(e) = mapping.resolve(&conflict, resolution) {
+                tracing::warn!("{}", e);
+                unresolved.push(conflict.host.display().to_string());
+            }
+        }
+    }
+    pending.save(run_path)?;
+
+    if !unresolved.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Changed on both the host and in the VM: {}. Settle with: vortex sync {} --on-conflict host|guest|merge",
+            unresolved.join(", "),
+            run_id
+        ));
+    }
+    Ok(())
+}
+
+fn prompt_conflict(conflict: &Conflict, color: bool) -> Result<Resolution> {
+    println!();
+    println!(
+        "⚠️  {} changed on both the host and in the VM",
+        conflict.host.display()
+    );
+    let diff = conflict.diff(color)?;
+    match &diff {
+        Some(diff) => print!("{}", diff),
+        None => println!("   Binary files differ"),
+    }
+    let question = if diff.is_some() {
+        "Keep [h]ost, [g]uest or [m]erge with conflict markers? "
+    } else {
+        "Keep [h]ost or [g]uest? "
+    };
+    loop {
+        match prompt(question)?.to_lowercase().as_str() {
+            "h" | "host" => return Ok(Resolution::Host),
+            "g" | "guest" => return Ok(Resolution::Guest),
+            "m" | "merge" if diff.is_some() => return Ok(Resolution::Merge),
+            _ => println!("❌ Unrecognized answer"),
+        }
+    }
+}
+
+/// Apply the pending --sync-back results of the runs served by `vm_id`
+fn sync_runs_for_vm(vm_id: &str) -> Result<()> {
+    for (path, record) in run_dir::list_runs()? {
+        if record.vm_id.as_ref().is_some_and(|id| id == vm_id) {
+            apply_sync_back(&path, record.run_id.as_str(), None)?;
+        }
+    }
+    Ok(())
+}
+
+fn sync_run(run_id: &str, policy: Option<ConflictPolicy>) -> Result<()> {
+    let (path, record) = run_dir::list_runs()?
+        .into_iter()
+        .find(|(_, record)| record.run_id == *run_id)
+        .ok_or_else(|| anyhow::anyhow!("No run directory for '{}'", run_id))?;
+    if PendingSync::load(&path)?.is_none() {
+        println!("Run {} has no --sync-back results", record.run_id);
+        return Ok(());
+    }
+    apply_sync_back(&path, record.run_id.as_str(), policy)?;
+    println!("✅ Synced results of run {}", record.run_id);
+    Ok(())
+}
+
+/// The config, with the settings of the `vortex.yaml` in the current
+/// directory over it
+async fn project_config() -> Result<VortexConfig> {
+    let config = vortex::config_cache::load_cached().await?;
+    Ok(config.with_project(&std::env::current_dir()?)?)
+}
+
+async fn run_template(
+    vortex: &Arc<VortexCore>,
+    template_name: &str,
+    override_command: Option<String>,
+    publish_all: bool,
+) -> Result<()> {
+    let config = project_config().await?;
+    let template = config
+        .get_template(template_name)
+        .ok_or_else(|| anyhow::anyhow!("Template '{}' not found", template_name))?;
+
+    info!(
+        "Running template: {} - {}",
+        template_name, template.description
+    );
+
+    let mappings = parse_port_mappings(template.ports.clone())?;
+    let volumes = parse_volume_mappings(template.volumes.clone()).await?;
+    let mut spec = VmSpec {
+        image: config.resolve_image(&template.image),
+        memory: template.memory,
+        cpus: template.cpus,
+        ports: mappings.ports,
+        volumes: volumes.volumes,
+        environment: template.environment.clone(),
+        command: override_command.or_else(|| template.command.clone()),
+        labels: template.labels.clone(),
+        network_config: None,
+        resource_limits: ResourceLimits::default(),
+        backend: None,
+        tuning: template
+            .tuning
+            .as_deref()
+            .map(TuningProfile::resolve)
+            .transpose()?,
+        hooks: template.hooks.clone(),
+        health_check: template.health_check.as_deref().map(str::parse).transpose()?,
+        ttl_seconds: None,
+        gpus: Vec::new(),
+        provision: template.provision.clone(),
+        publish: mappings.publish,
+        tls_ports: mappings.tls_ports,
+        port_addresses: mappings.addresses,
+        share_mechanisms: volumes.mechanisms,
+        tmpfs: HashMap::new(),
+        network_policy: NetworkPolicy::Open,
+    };
+    if publish_all {
+        spec.publish_all();
+    }
+
+    run_vm(
+        vortex,
+        spec,
+        false,
+        false,
+        false,
+        vec![],
+        vec![],
+        ConflictPolicy::default(),
+        None,
+        false,
+        &[],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn list_plugins(_vortex: &Arc<VortexCore>) -> Result<()> {
+    let config = vortex::config_cache::load_cached().await?;
+
+    println!("Installed Plugins:");
+    if config.plugins.is_empty() {
+        println!("  No plugins installed. Use 'vortex plugin add <repo-url>' to add one.");
+    } else {
+        for (name, plugin) in &config.plugins {
+            let status = if plugin.enabled { "enabled" } else { "disabled" };
+            if plugin.version.is_empty() {
+                println!("  {} - {}", name, status);
+            } else {
+                println!("  {} (v{}) - {}", name, plugin.version, status);
+            }
+            if !plugin.source_repo.is_empty() {
+                println!("    {} by {}", plugin.description, plugin.author);
+                println!("    Source: {}", plugin.source_repo);
+            }
+            if !plugin.settings.is_null() {
+                println!("    Settings: {}", plugin.settings);
+            }
+            let grants = plugin.grants.capabilities();
+            if !grants.is_empty() {
+                let grants: Vec<String> = grants.iter().map(ToString::to_string).collect();
+                println!("    Grants: {}", grants.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate plugin name - only allow alphanumeric, hyphens, underscores, and dots
+fn validate_plugin_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Plugin name cannot be empty"));
+    }
+
+    if name.len() > 100 {
+        return Err(anyhow::anyhow!("Plugin name is too long (max 100 characters)"));
+    }
+
+    // Check for path traversal attempts
+    if name.contains("..") || name.contains("/") || name.contains("\\") {
+        return Err(anyhow::anyhow!(
+            "Invalid plugin name: path traversal characters not allowed"
+        ));
+    }
+
+    // Only allow alphanumeric, hyphens, underscores, and dots
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() && c != '-' && c != '_' && c != '.' {
+            return Err(anyhow::anyhow!(
+                "Invalid plugin name: only alphanumeric, hyphens, underscores, and dots are allowed"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Normalize repo URL: convert "exec/vortex-web" to "https://github.com/exec/vortex-web.git"
+fn normalize_repo_url(repo: &str) -> Result<String> {
+    // If it already has a protocol, validate it
+    if let Some(protocol_end) = repo.find("://") {
+        let protocol = &repo[..protocol_end];
+        // Only allow HTTPS protocol (HTTP is insecure for plugin downloads)
+        if protocol != "https" {
+            return Err(anyhow::anyhow!(
+                "Only HTTPS URLs are supported for plugins. HTTP is not allowed \
+                as it is vulnerable to man-in-the-middle attacks. Please use a HTTPS URL."
+            ));
+        }
+        return Ok(repo.to_string());
+    }
+
+    // Convert "user/repo" to "https://github.com/user/repo.git"
+    // First validate the repo format (no path traversal)
+    let repo_clean = repo.trim_end_matches(".git");
+    if repo_clean.contains("..") || repo_clean.contains("/") {
+        // Only allow single-level "user/repo" format
+        let parts: Vec<&str> = repo_clean.split('/').collect();
+        if parts.len() > 2 {
+            return Err(anyhow::anyhow!(
+                "Invalid repo format. Use 'user/repo' (e.g., 'exec/vortex-web') or full HTTPS URL"
+            ));
+        }
+    }
+
+    Ok(format!("https://github.com/{}.git", repo_clean))
+}
+
+async fn add_plugin(_vortex: &Arc<VortexCore>, repo: &str, name: Option<&str>) -> Result<()> {
+    // Normalize repo URL: convert "exec/vortex-web" to "https://github.com/exec/vortex-web.git"
+    let normalized_repo = normalize_repo_url(repo)?;
+
+    let plugin_name = match name {
+        Some(n) => {
+            // Validate custom plugin name
+            validate_plugin_name(n)?;
+            n.to_string()
+        }
+        None => {
+            // Extract name from repo URL
+            let raw_name = normalized_repo
+                .trim_end_matches(".git")
+                .split('/')
+                .next_back()
+                .unwrap_or("plugin");
+
+            // Validate the extracted name
+            validate_plugin_name(raw_name)?;
+            raw_name.to_string()
+        }
+    };
+
+    println!("Adding plugin '{}' from {}...", plugin_name, normalized_repo);
+
+    let mut config = VortexConfig::load_raw()?;
+
+    // Check if plugin already exists
+    if config.plugins.contains_key(&plugin_name) {
+        return Err(anyhow::anyhow!(
+            "Plugin '{}' is already installed",
+            plugin_name
+        ));
+    }
+
+    // Get the plugins directory
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
+    let vortex_dir = home.join(".vortex");
+    let plugins_dir = vortex_dir.join("plugins");
+
+    // Create plugins directory if it doesn't exist
+    std::fs::create_dir_all(&plugins_dir)?;
+
+    // Clone the plugin repo
+    let plugin_repo_dir = plugins_dir.join(&plugin_name);
+
+    println!("Cloning plugin repository...");
+    clone_repo(&normalized_repo, &plugin_repo_dir)?;
+
+    // Read plugin metadata from plugin.toml if it exists
+    let (version, description, author) = read_plugin_metadata(&plugin_repo_dir)?;
+
+    // Add plugin config
+    config.add_plugin(
+        plugin_name.clone(),
+        PluginConfig {
+            enabled: true,
+            version,
+            source_repo: normalized_repo.clone(),
+            description: description.unwrap_or_else(|| format!("Plugin from {}", normalized_repo)),
+            author: author.unwrap_or_else(|| "Unknown".to_string()),
+            ..Default::default()
+        },
+    );
+
+    config.save()?;
+
+    println!("Plugin '{}' added successfully!", plugin_name);
+    println!("Plugin repository cloned to: {}", plugin_repo_dir.display());
+    println!("The plugin config has been saved. You can enable/disable it with: vortex plugin enable/disable {}", plugin_name);
+
+    let requested = requested_capabilities(&plugin_repo_dir);
+    if !requested.is_empty() {
+        println!("The plugin requests: {}", requested.join(" "));
+        println!(
+            "Nothing is granted yet. Review the list, then run: vortex plugin grant {} <capability>...",
+            plugin_name
+        );
+    }
+
+    Ok(())
+}
+
+/// Clone a git repository to the specified directory
+/// Note: This function does not verify git signatures. For production use,
+/// implement GPG signature verification before executing plugin code.
+fn clone_repo(repo_url: &str, dest_dir: &Path) -> Result<()> {
+    // Check if directory exists and is not empty
+    if dest_dir.exists() {
+        if dest_dir.read_dir()?.any(|_| true) {
+            return Err(anyhow::anyhow!(
+                "Plugin directory '{}' already exists and is not empty",
+                dest_dir.display()
+            ));
+        }
+    } else {
+        std::fs::create_dir_all(dest_dir)?;
+    }
+
+    // Use git CLI which typically has better HTTPS support
+    // Add timeout to prevent hanging on slow/stalled repositories (5 minutes)
+    let output = std::process::Command::new("git")
+        .arg("clone")
+        .arg(repo_url)
+        .arg(dest_dir)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run git clone: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("Failed to clone repository: {}", stderr));
+    }
+
+    // Verify that the clone was successful by checking for .git directory
+    let git_dir = dest_dir.join(".git");
+    if !git_dir.exists() || !git_dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Git clone completed but .git directory not found. Repository may be corrupted."
+        ));
+    }
+
+    // Basic plugin signature verification
+    // This provides warning for unsigned plugins rather than a strict verification system
+    // Full GPG verification would require additional dependencies and user setup
+    verify_plugin_integrity(dest_dir)?;
+
+    Ok(())
+}
+
+/// Basic verification of plugin integrity
+/// Checks for common security indicators and warns if issues are found.
+fn verify_plugin_integrity(repo_dir: &Path) -> Result<()> {
+    let plugin_toml = repo_dir.join("plugin.toml");
+    let signature_file = repo_dir.join(".vortex-signature");
+    let metadata_file = repo_dir.join(".vortex-metadata.json");
+
+    // Check if plugin.toml exists (required)
+    if !plugin_toml.exists() {
+        tracing::warn!(
+            "Plugin at {} does not have a plugin.toml file. \
+            This is required for plugin functionality.",
+            repo_dir.display()
+        );
+    }
+
+    // Check for signature file (optional but recommended)
+    if !signature_file.exists() {
+        tracing::warn!(
+            "Plugin at {} has no signature file (.vortex-signature). \
+            Unsigned plugins may have been modified. Consider only using signed plugins \
+            from trusted sources.",
+            repo_dir.display()
+        );
+    }
+
+    // Check for metadata file (optional but recommended)
+    if !metadata_file.exists() {
+        tracing::info!(
+            "Plugin at {} has no metadata file (.vortex-metadata.json). \
+            This file can store checksums and other integrity information.",
+            repo_dir.display()
+        );
+    }
+
+    // Check that plugin.toml is not empty
+    if plugin_toml.exists() {
+        if let Ok(content) = std::fs::read_to_string(&plugin_toml) {
+            if content.trim().is_empty() {
+                tracing::warn!("Plugin plugin.toml file is empty.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read plugin metadata from plugin.toml in the repo root
+fn read_plugin_metadata(repo_dir: &Path) -> Result<(String, Option<String>, Option<String>)> {
+    let plugin_toml_path = repo_dir.join("plugin.toml");
+
+    if plugin_toml_path.exists() {
+        let content = std::fs::read_to_string(&plugin_toml_path).unwrap_or_default();
+        if let Ok(metadata) = toml::from_str::<PluginMetadata>(&content) {
+            return Ok((
+                metadata.version.unwrap_or_else(|| "0.1.0".to_string()),
+                metadata.description,
+                metadata.author,
+            ));
+        }
+    }
+
+    // Default metadata if plugin.toml doesn't exist or is invalid
+    Ok((
+        "0.1.0".to_string(),
+        None,
+        None,
+    ))
+}
+
+/// Capabilities a WebAssembly plugin lists in its plugin.toml
+fn requested_capabilities(repo_dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(repo_dir.join("plugin.toml"))
+        .ok()
+        .and_then(|content| toml::from_str::<PluginMetadata>(&content).ok())
+        .and_then(|metadata| metadata.capabilities)
+        .unwrap_or_default()
+}
+
+fn update_plugin_grants(name: &str, capabilities: &[String], grant: bool) -> Result<()> {
+    let capabilities = capabilities
+        .iter()
+        .map(|capability| capability.parse::<Capability>())
+        .collect::<vortex::Result<Vec<_>>>()?;
+
+    let mut config = VortexConfig::load_raw()?;
+    let plugin = config
+        .plugins
+        .get_mut(name)
+        .ok_or_else(|| anyhow::anyhow!("Plugin '{}' not found", name))?;
+    for capability in &capabilities {
+        if grant {
+            plugin.grants.grant(capability.clone());
+            println!("Granted {} to '{}'", capability, name);
+        } else if plugin.grants.revoke(capability) {
+            println!("Revoked {} from '{}'", capability, name);
+        } else {
+            println!("'{}' did not have {}", name, capability);
+        }
+    }
+    config.save()?;
+
+    if cfg!(not(feature = "wasm-plugins")) {
+        println!(
+            "Note: this build cannot run WebAssembly plugins (enable the wasm-plugins feature)"
+        );
+    }
+    Ok(())
+}
+
+/// Plugin metadata structure for plugin.toml
+#[derive(serde::Deserialize, Debug)]
+struct PluginMetadata {
+    version: Option<String>,
+    description: Option<String>,
+    author: Option<String>,
+    /// Grants a WebAssembly plugin asks for, shown when it is added
+    capabilities: Option<Vec<String>>,
+}
+
+async fn remove_plugin(_vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
+    let mut config = VortexConfig::load_raw()?;
+
+    if config.remove_plugin(name).is_some() {
+        config.save()?;
+        println!("Plugin '{}' removed successfully!", name);
+    } else {
+        return Err(anyhow::anyhow!("Plugin '{}' not found", name));
+    }
+
+    Ok(())
+}
+
+async fn enable_plugin(_vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
+    let mut config = VortexConfig::load_raw()?;
+
+    if config.enable_plugin(name) {
+        config.save()?;
+        println!("Plugin '{}' enabled!", name);
+    } else {
+        return Err(anyhow::anyhow!("Plugin '{}' not found", name));
+    }
+
+    Ok(())
+}
+
+async fn disable_plugin(vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
+    let mut config = VortexConfig::load_raw()?;
+    // Plugin libraries and programs are disabled by a section of their own
+    let loaded = vortex
+        .plugin_manager
+        .read()
+        .await
+        .list_plugins()
+        .iter()
+        .any(|plugin| plugin.name == name);
+    if loaded && config.get_plugin(name).is_none() {
+        config.add_plugin(name.to_string(), PluginConfig::default());
+    }
+
+    if config.disable_plugin(name) {
+        config.save()?;
+        println!("Plugin '{}' disabled!", name);
+    } else {
+        return Err(anyhow::anyhow!("Plugin '{}' not found", name));
+    }
+
+    Ok(())
+}
+
+async fn show_templates() -> Result<()> {
+    let config = project_config().await?;
+
+    println!("Available Templates:");
+    for (name, template) in &config.templates {
+        println!("  {} - {} ({})", name, template.description, template.image);
+        if !template.ports.is_empty() {
+            println!("    Ports: {}", template.ports.join(", "));
+        }
+        if template.memory != 512 || template.cpus != 1 {
+            println!(
+                "    Resources: {}MB RAM, {} CPU(s)",
+                template.memory, template.cpus
+            );
+        }
+        println!();
+    }
+
+    println!("\nImage Aliases:");
+    for (alias, image) in &config.image_aliases {
+        println!("  {} -> {}", alias, image);
+    }
+
+    Ok(())
+}
+
+/// Samples of `vm_id` taken over the last `since`, one line each
+async fn show_metrics_history(vortex: &Arc<VortexCore>, vm_id: &str, since: &str) -> Result<()> {
+    let window = parse_since(since)?;
+    let samples = vortex
+        .metrics_collector
+        .history(vm_id, chrono::Utc::now() - window..)
+        .await?;
+    if samples.is_empty() {
+        println!("No metrics for VM {} in the last {}", vm_id, since);
+        return Ok(());
+    }
+    println!(
+        "{:<19} {:>7} {:>12} {:>12} {:>12} {:>9}",
+        "TIME", "CPU", "MEMORY", "NET RX", "NET TX", "UPTIME"
+    );
+    for sample in samples {
+        println!(
+            "{:<19} {:>6.1}% {:>10.1}MB {:>10.1}KB {:>10.1}KB {:>8}s",
+            sample.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            sample.cpu_usage_percent,
+            sample.memory_usage_bytes as f64 / 1024.0 / 1024.0,
+            sample.network_rx_bytes as f64 / 1024.0,
+            sample.network_tx_bytes as f64 / 1024.0,
+            sample.uptime_seconds
+        );
+    }
+    Ok(())
+}
+
+/// A duration such as `90s`, `10m`, `2h` or `1d`; plain numbers are seconds
+fn parse_since(since: &str) -> Result<chrono::Duration> {
+    let invalid = || anyhow::anyhow!("Invalid duration: {}. Use e.g. 30s, 10m, 2h or 1d", since);
+    let (amount, unit_secs) = match since.char_indices().last() {
+        Some((i, 's')) => (&since[..i], 1),
+        Some((i, 'm')) => (&since[..i], 60),
+        Some((i, 'h')) => (&since[..i], 3600),
+        Some((i, 'd')) => (&since[..i], 86400),
+        _ => (since, 1),
+    };
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    amount
+        .checked_mul(unit_secs)
+        .and_then(chrono::Duration::try_seconds)
+        .filter(|window| *window > chrono::Duration::zero())
+        .ok_or_else(invalid)
+}
+
+async fn show_metrics(vortex: &Arc<VortexCore>, vm_id: Option<&str>) -> Result<()> {
+    if let Some(vm_id) = vm_id {
+        // Get VM and collect real-time metrics
+        let vms = vortex.list_vms().await?;
+        if let Some(vm) = vms.iter().find(|v| v.id == vm_id) {
+            match vm.backend.get_metrics(vm).await {
+                Ok(metrics) => {
+                    println!("VM Metrics for {}:", vm_id);
+                    println!("  CPU Usage: {:.1}%", metrics.cpu_usage);
+                    println!(
+                        "  Memory: {:.1}MB / {:.1}MB",
+                        metrics.memory_usage as f64 / 1024.0 / 1024.0,
+                        metrics.memory_total as f64 / 1024.0 / 1024.0
+                    );
+                    println!(
+                        "  Disk Usage: {:.1}MB",
+                        metrics.disk_usage as f64 / 1024.0 / 1024.0
+                    );
+                    println!("  Network RX: {:.1}KB", metrics.network_rx as f64 / 1024.0);
+                    println!("  Network TX: {:.1}KB", metrics.network_tx as f64 / 1024.0);
+                    println!("  Uptime: {}s", metrics.uptime_seconds);
+                }
+                Err(e) => {
+                    println!("Failed to collect metrics for VM {}: {}", vm_id, e);
+                }
+            }
+        } else {
+            println!("VM {} not found", vm_id);
+        }
+    } else {
+        // Show system metrics based on all running VMs
+        let vms = vortex.list_vms().await?;
+        let mut total_memory_allocated = 0u64;
+        let mut total_memory_used = 0u64;
+        let mut total_cpu_usage = 0.0f64;
+        let mut successful_metrics = 0;
+
+        for vm in &vms {
+            if let Ok(metrics) = vm.backend.get_metrics(vm).await {
+                total_memory_allocated += metrics.memory_total;
+                total_memory_used += metrics.memory_usage;
+                total_cpu_usage += metrics.cpu_usage;
+                successful_metrics += 1;
+            }
+        }
+
+        println!("System Metrics:");
+        println!("  Total VMs: {}", vms.len());
+        println!("  Running VMs: {}", vms.len());
+        println!("  Total CPU Usage: {:.1}%", total_cpu_usage);
+        println!(
+            "  Total Memory Used: {:.1}MB / {:.1}MB",
+            total_memory_used as f64 / 1024.0 / 1024.0,
+            total_memory_allocated as f64 / 1024.0 / 1024.0
+        );
+        if successful_metrics > 0 {
+            println!(
+                "  Average CPU per VM: {:.1}%",
+                total_cpu_usage / successful_metrics as f64
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The `-p` options of a VM, parsed
+#[derive(Debug, Default, PartialEq)]
+struct PortMappings {
+    /// Guest ports by host port
+    ports: HashMap<u16, u16>,
+    /// Guest ports whose host port is 0, so a free one is picked when the
+    /// VM is created
+    publish: Vec<u16>,
+    /// Mappings ending in `:tls`, whose host port serves HTTPS
+    tls_ports: HashMap<u16, u16>,
+    /// Host addresses the mappings starting with one listen on
+    addresses: HashMap<u16, IpAddr>,
+}
+
+/// Parse port mappings of the form `[address:]host:guest[:tls]`, where an
+/// IPv6 address is in brackets (`[::1]:8080:80`)
+fn parse_port_mappings(ports: Vec<String>) -> Result<PortMappings> {
+    let mut mappings = PortMappings::default();
+
+    for port in ports {
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid port mapping format: {}. Use [address:]host:guest[:tls]",
+                port
+            )
+        };
+        let (address, rest) = match port.strip_prefix('[') {
+            Some(bracketed) => {
+                let (address, rest) = bracketed.split_once("]:").ok_or_else(invalid)?;
+                let address: Ipv6Addr = address
+                    .parse()
+                    .with_context(|| format!("Invalid IPv6 address: {}", address))?;
+                (Some(IpAddr::V6(address)), rest)
+            }
+            None => (None, port.as_str()),
+        };
+        let mut parts: Vec<&str> = rest.split(':').collect();
+        let tls = parts.len() > 2 && parts[parts.len() - 1] == "tls";
+        if tls {
+            parts.pop();
+        }
+        let address = match (address, parts.as_slice()) {
+            (None, [address, _, _]) => {
+                let address: Ipv4Addr = address
+                    .parse()
+                    .with_context(|| format!("Invalid IPv4 address: {}", address))?;
+                parts.remove(0);
+                Some(IpAddr::V4(address))
+            }
+            (address, [_, _]) => address,
+            _ => return Err(invalid()),
+        };
+
+        let host_port: u16 = parts[0]
+            .parse()
+            .with_context(|| format!("Invalid host port: {}", parts[0]))?;
+        let guest_port: u16 = parts[1]
+            .parse()
+            .with_context(|| format!("Invalid guest port: {}", parts[1]))?;
+
+        if host_port == 0 && (tls || address.is_some()) {
+            return Err(anyhow::anyhow!("Port mapping {} needs a host port", port));
+        }
+        if let Some(address) = address {
+            mappings.addresses.insert(host_port, address);
+        }
+
+        if tls {
+            mappings.tls_ports.insert(host_port, guest_port);
+            continue;
+        }
+
+        if host_port == 0 {
+            mappings.publish.push(guest_port);
+            continue;
+        }
+
+        // Prevent host_port == guest_port which could cause issues with some backends
+        if host_port == guest_port {
+            return Err(anyhow::anyhow!(
+                "Host port {} cannot be the same as guest port. This may cause port conflicts.",
+                host_port
+            ));
+        }
+
+        mappings.ports.insert(host_port, guest_port);
+    }
+
+    Ok(mappings)
+}
+
+/// Helper function to validate and normalize a host path, preventing path traversal
+fn validate_host_path(path: &str) -> Result<std::path::PathBuf> {
+    let host_path = std::path::PathBuf::from(path);
+
+    // Check for path traversal attempts (basic first line of defense)
+    if path.contains("..") {
+        return Err(anyhow::anyhow!(
+            "Invalid path: path traversal detected. Relative paths with '..' are not allowed."
+        ));
+    }
+
+    // Normalize the path
+    let normalized = if path.starts_with('/') {
+        // Absolute path - check against known safe locations
+        std::fs::canonicalize(&host_path)
+            .map_err(|e| anyhow::anyhow!("Cannot access host path '{}': {}", path, e))?
+    } else {
+        // Relative path - join with current directory
+        std::env::current_dir()
+            .map_err(|e| anyhow::anyhow!("Cannot get current directory: {}", e))?
+            .join(&host_path)
+    };
+
+    // Check if path exists
+    if !normalized.exists() {
+        return Err(anyhow::anyhow!(
+            "Host path does not exist: {}",
+            normalized.display()
+        ));
+    }
+
+    // Explicitly deny sensitive system directories (even if they happen to be inside allowed locations)
+    let normalized_str = normalized.to_string_lossy();
+    let sensitive_patterns = [
+        "/etc/", "/etc$",          // Configuration files
+        "/proc/", "/proc$",        // Process info
+        "/sys/", "/sys$",          // System info
+        "/root/", "/root$",        // Root user home
+        "/.ssh/", "/.ssh$",        // SSH keys
+        "/.gnupg/", "/.gnupg$",    // GPG keys
+        "/.kube/", "/.kube$",      // Kubernetes config
+        "/.config/google/", "/.config/google$",
+        "/.aws/", "/.aws$",        // AWS credentials
+        "/.azure/", "/.azure$",    // Azure credentials
+        "/.kube/", "/.kube$",      // Kubernetes config
+    ];
+
+    for pattern in &sensitive_patterns {
+        if normalized_str.starts_with(pattern.trim_end_matches('$'))
+            || normalized_str == pattern.trim_end_matches('$')
+        {
+            let suffix = if pattern.ends_with('$') {
+                " or its exact path"
+            } else {
+                ""
+            };
+            return Err(anyhow::anyhow!(
+                "Cannot mount {} paths for security reasons.{} Path: {}",
+                pattern.trim_end_matches('$'),
+                suffix,
+                normalized.display()
+            ));
+        }
+    }
+
+    // Prevent mounting sensitive system directories
+    // Only allow paths under user's home directory or the current working directory
+    let home_dir =
+        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
+    let canonical_home = home_dir
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Invalid home directory: {}", e))?;
+
+    let current_dir = std::env::current_dir()
+        .map_err(|e| anyhow::anyhow!("Cannot get current directory: {}", e))?;
+    let canonical_cwd = current_dir
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Invalid current directory: {}", e))?;
+
+    // Check if path is within allowed boundaries
+    if !normalized.starts_with(&canonical_home) && !normalized.starts_with(&canonical_cwd) {
+        return Err(anyhow::anyhow!(
+            "Cannot mount paths outside of home directory ({}) or current directory ({}). Path: {}",
+            canonical_home.display(),
+            canonical_cwd.display(),
+            normalized.display()
+        ));
+    }
+
+    // Final verification: ensure canonicalized path is still under allowed boundaries
+    // (prevents symlink-based bypasses)
+    for parent in normalized.ancestors() {
+        if parent == canonical_home || parent == canonical_cwd {
+            break;
+        }
+        // Check if any parent is a sensitive directory
+        let parent_str = parent.to_string_lossy();
+        for pattern in &sensitive_patterns {
+            if parent_str.starts_with(pattern.trim_end_matches('$')) {
+                return Err(anyhow::anyhow!(
+                    "Cannot mount path inside sensitive directory: {}",
+                    parent.display()
+                ));
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// The `-v` options of a VM, parsed
+#[derive(Debug, Default)]
+struct VolumeMappings {
+    /// Guest paths by host path
+    volumes: HashMap<PathBuf, PathBuf>,
+    /// Share mechanisms of the mappings ending in one, by guest path
+    mechanisms: HashMap<PathBuf, ShareMechanism>,
+}
+
+/// Parse `host:guest[:virtiofs|9p|copy]` volume mappings. A host side
+/// without a `/` names a volume of the `StorageManager`, created on first
+/// use; relative host paths start with `./`.
+async fn parse_volume_mappings(volumes: Vec<String>) -> Result<VolumeMappings> {
+    let mut mappings = VolumeMappings::default();
+
+    for volume in volumes {
+        let parts: Vec<&str> = volume.split(':').collect();
+        if !(2..=3).contains(&parts.len()) {
+            return Err(anyhow::anyhow!(
+                "Invalid volume mapping format: {}. Use host:guest or name:guest, \
+                 optionally followed by :virtiofs, :9p or :copy",
+                volume
+            ));
+        }
+
+        let host_path = if parts[0].contains('/') || parts[0].starts_with(['.', '~']) {
+            // Validate host path (prevents path traversal and forbidden directories)
+            validate_host_path(parts[0])?
+        } else {
+            let storage = StorageManager::new().await?;
+            storage.ensure_volume(parts[0]).await?.path
+        };
+
+        // Guest path - just validate it's not empty and doesn't contain path traversal
+        let guest_path_str = parts[1];
+        if guest_path_str.is_empty() {
+            return Err(anyhow::anyhow!("Guest path cannot be empty"));
+        }
+        if guest_path_str.contains("..") {
+            return Err(anyhow::anyhow!(
+                "Invalid guest path: path traversal detected"
+            ));
+        }
+        let guest_path = std::path::PathBuf::from(guest_path_str);
+
+        if let Some(mechanism) = parts.get(2) {
+            mappings
+                .mechanisms
+                .insert(guest_path.clone(), mechanism.parse()?);
+        }
+        mappings.volumes.insert(host_path, guest_path);
+    }
+
+    Ok(mappings)
+}
+
+/// Validate that a label key matches allowed patterns (alphanumeric, hyphens, underscores, dots)
+fn validate_label_key(key: &str) -> Result<()> {
+    // Label keys must match Kubernetes label key format: [A-Za-z0-9]([-A-Za-z0-9_.]*)?[A-Za-z0-9]
+    if key.is_empty() {
+        return Err(anyhow::anyhow!("Label key cannot be empty"));
+    }
+    if key.len() > 63 {
+        return Err(anyhow::anyhow!("Label key cannot exceed 63 characters"));
+    }
+
+    // Check first character
+    let first = key
+        .chars()
+        .next()
+        .expect("non-empty string must have first char");
+    if !first.is_ascii_alphanumeric() && first != '.' && first != '-' {
+        return Err(anyhow::anyhow!(
+            "Label key must start with alphanumeric, dot, or hyphen"
+        ));
+    }
+
+    for c in key.chars().skip(1) {
+        if !c.is_ascii_alphanumeric() && c != '-' && c != '_' && c != '.' {
+            return Err(anyhow::anyhow!(
+                "Label key contains invalid character: '{}'",
+                c
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_labels(labels: Vec<String>) -> Result<HashMap<String, String>> {
+    let mut mappings = HashMap::new();
+
+    for label in labels {
+        let parts: Vec<&str> = label.splitn(2, '=').collect();
+        if parts.len() != 2 {
+            return Err(anyhow::anyhow!(
+                "Invalid label format: {}. Use key=value",
+                label
+            ));
+        }
+
+        // Validate label key
+        validate_label_key(parts[0])?;
+
+        mappings.insert(parts[0].to_string(), parts[1].to_string());
+    }
+
+    Ok(mappings)
+}
+
+fn parse_env(vars: Vec<String>) -> Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+
+    for var in vars {
+        match var.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                env.insert(key.to_string(), value.to_string());
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid environment variable: {}. Use KEY=value",
+                    var
+                ))
+            }
+        }
+    }
+
+    Ok(env)
+}
+
+fn parse_secrets(secrets: &[String]) -> Result<Vec<SecretRequest>> {
+    Ok(secrets
+        .iter()
+        .map(|secret| secret.parse())
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+/// The VM ID and guest path of a `vortex cp` argument of the form
+/// `<vm-id>:<absolute guest path>`; host paths have no such prefix
+fn split_vm_path(arg: &str) -> Option<(&str, &str)> {
+    let (vm_id, guest) = arg.split_once(':')?;
+    let is_id = !vm_id.is_empty()
+        && vm_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    (is_id && guest.starts_with('/')).then_some((vm_id, guest))
+}
+
+/// `guest path:size` tmpfs mounts, sizes in MiB unless they end in `g`
+fn parse_tmpfs_mounts(mounts: Vec<String>) -> Result<HashMap<PathBuf, u32>> {
+    let mut tmpfs = HashMap::new();
+    for mount in mounts {
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid tmpfs mount: {}. Use guest_path:size, e.g. /tmp:512m",
+                mount
+            )
+        };
+        let (guest, size) = mount.rsplit_once(':').ok_or_else(invalid)?;
+        let size = size.to_ascii_lowercase();
+        let size_mib = match size.strip_suffix('g') {
+            Some(gib) => gib.parse::<u32>().ok().and_then(|gib| gib.checked_mul(1024)),
+            None => size.strip_suffix('m').unwrap_or(&size).parse().ok(),
+        }
+        .ok_or_else(invalid)?;
+        tmpfs.insert(PathBuf::from(guest), size_mib);
+    }
+    Ok(tmpfs)
+}
+
+fn parse_copy_mappings(copy_to: Vec<String>) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut mappings = Vec::new();
+
+    for copy_spec in copy_to {
+        let parts: Vec<&str> = copy_spec.split(':').collect();
+        if parts.len() != 2 {
+            return Err(anyhow::anyhow!(
+                "Invalid copy-to format: {}. Use host_dir:guest_dir",
+                copy_spec
+            ));
+        }
+
+        // Validate host path (prevents path traversal and forbidden directories)
+        let host_path = validate_host_path(parts[0])?;
+
+        let guest_path = PathBuf::from(parts[1]);
+
+        // Verify host path exists and is a directory
+        if !host_path.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Host path is not a directory: {}",
+                host_path.display()
+            ));
+        }
+
+        // Validate guest path
+        if guest_path.to_string_lossy().contains("..") {
+            return Err(anyhow::anyhow!(
+                "Invalid guest path: path traversal detected"
+            ));
+        }
+
+        mappings.push((host_path, guest_path));
+    }
+
+    Ok(mappings)
+}
+
+fn parse_sync_back_mappings(sync_back: Vec<String>) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut mappings = Vec::new();
+
+    for sync_spec in sync_back {
+        let parts: Vec<&str> = sync_spec.split(':').collect();
+        if parts.len() != 2 {
+            return Err(anyhow::anyhow!(
+                "Invalid sync-back format: {}. Use guest_dir:host_dir",
+                sync_spec
+            ));
+        }
+
+        // Guest path - validate it's not empty and doesn't contain path traversal
+        let guest_path_str = parts[0];
+        if guest_path_str.is_empty() {
+            return Err(anyhow::anyhow!("Guest path cannot be empty"));
+        }
+        if guest_path_str.contains("..") {
+            return Err(anyhow::anyhow!(
+                "Invalid guest path: path traversal detected"
+            ));
+        }
+        let guest_path = std::path::PathBuf::from(guest_path_str);
+
+        // Validate host path (prevents path traversal and forbidden directories)
+        let host_path = validate_host_path(parts[1])?;
+
+        // Create host directory if it doesn't exist
+        if let Some(parent) = host_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        mappings.push((guest_path, host_path));
+    }
+
+    Ok(mappings)
+}
+
+async fn run_parallel_vms(
+    vortex: &Arc<VortexCore>,
+    images: Vec<String>,
+    command: String,
+    quiet: bool,
+    copy_to: Vec<String>,
+    sync_back: Vec<String>,
+    network: Option<String>,
+) -> Result<()> {
+    use tokio::time::Instant;
+
+    let start_time = Instant::now();
+
+    if !quiet {
+        println!(
+            "🚀 Launching {} VMs in parallel (try this with Docker!)",
+            images.len()
+        );
+    }
+
+    // Get resource limits to enforce concurrent VM cap
+    let config = project_config().await?;
+    let max_concurrent = config.get_resource_limits().max_concurrent_vms as usize;
+
+    // Create a semaphore to limit concurrent VM creation
+    let semaphore = Arc::new(Semaphore::new(max_concurrent));
+
+    if images.len() > max_concurrent && !quiet {
+        println!(
+            "⚠️  Limiting parallel execution to {} VMs (configured max_concurrent_vms)",
+            max_concurrent
+        );
+    }
+
+    // Create futures for all VMs with concurrency limiting
+    let mut tasks = Vec::new();
+
+    for (i, image) in images.into_iter().enumerate() {
+        let vortex_clone = Arc::clone(vortex);
+        let command = command.clone();
+        let copy_to = copy_to.clone();
+        let sync_back = sync_back.clone();
+        let network = network.clone();
+        let semaphore = Arc::clone(&semaphore);
+        let config = config.clone();
+
+        let task = async move {
+            // Acquire semaphore permit (blocks if at capacity)
+            let _permit = semaphore.acquire().await;
+
+            let resolved_image = config.resolve_image(&image);
+
+            // Create unique sync paths for each VM
+            let mut unique_sync_back = Vec::new();
+            for sync_spec in &sync_back {
+                let parts: Vec<&str> = sync_spec.split(':').collect();
+                if parts.len() == 2 {
+                    let guest_path = parts[0];
+                    let host_path = format!("{}_vm_{}", parts[1], i);
+                    unique_sync_back.push(format!("{}:{}", guest_path, host_path));
+                }
+            }
+
+            let spec = VmSpec {
+                image: resolved_image.clone(),
+                memory: 512,
+                cpus: 1,
+                ports: HashMap::new(),
+                volumes: HashMap::new(),
+                environment: HashMap::new(),
+                command: Some(command),
+                labels: HashMap::new(),
+                network_config: network,
+                resource_limits: ResourceLimits::default(),
+                backend: None,
+                tuning: None,
+                hooks: LifecycleHooks::default(),
+                health_check: None,
+                ttl_seconds: None,
+                gpus: Vec::new(),
+                provision: None,
+                publish: Vec::new(),
+                tls_ports: HashMap::new(),
+                port_addresses: HashMap::new(),
+                share_mechanisms: HashMap::new(),
+                tmpfs: HashMap::new(),
+                network_policy: NetworkPolicy::Open,
+            };
+
+            let vm_start = Instant::now();
+            let usage = run_vm(
+                &vortex_clone,
+                spec,
+                false,
+                true,
+                false,
+                copy_to,
+                unique_sync_back,
+                ConflictPolicy::default(),
+                None,
+                false,
+                &[],
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("{}: {}", resolved_image, e))?;
+            let vm_duration = vm_start.elapsed();
+
+            Ok::<_, anyhow::Error>((resolved_image, vm_duration, usage))
+        };
+
+        tasks.push(tokio::spawn(task));
+    }
+
+    // Collect results from all tasks; one VM failing does not hide the rest
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok(Ok(result)) => results.push(result),
+            Ok(Err(e)) => failures.push(e.to_string()),
+            Err(e) => failures.push(format!("Task panicked: {}", e)),
+        }
+    }
+
+
+    let total_duration = start_time.elapsed();
+
+    if !quiet {
+        println!("\n🎯 Parallel Execution Results:");
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        for (image, duration, usage) in &results {
+            println!("  {} - completed in {:.2}s", image, duration.as_secs_f64());
+            if let Some(usage) = usage {
+                println!("    {}", describe_usage(usage));
+            }
+        }
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        println!(
+            "🚀 Total time: {:.2}s ({} VMs in parallel)",
+            total_duration.as_secs_f64(),
+            results.len()
+        );
+        let usages: Vec<&VmUsage> = results.iter().filter_map(|(_, _, u)| u.as_ref()).collect();
+        if !usages.is_empty() {
+            println!(
+                "📊 Resources: {}",
+                describe_usage(&VmUsage::total("total", usages))
+            );
+        }
+        println!(
+            "⚡ Docker would take {}x longer running these sequentially!",
+            results.len()
+        );
+    }
+
+    if !failures.is_empty() {
+        for failure in &failures {
+            eprintln!("❌ {}", failure);
+        }
+        return Err(anyhow::anyhow!(
+            "{} of {} VMs failed",
+            failures.len(),
+            failures.len() + results.len()
+        ));
+    }
+    Ok(())
+}
+
+fn oidc_provider(config: &VortexConfig) -> Result<OidcAuthProvider> {
+    let oidc =
+        config.auth.oidc.clone().ok_or_else(|| {
+            anyhow::anyhow!("No [auth.oidc] issuer in the config to sign in with")
+        })?;
+    Ok(OidcAuthProvider::new(oidc)?)
+}
+
+async fn login(config: &VortexConfig) -> Result<()> {
+    let provider = oidc_provider(config)?;
+    let tokens = provider
+        .login(|device| {
+            println!(
+                "🔑 Open {} and enter the code {}",
+                device.verification_uri, device.user_code
+            );
+            if let Some(complete) = &device.verification_uri_complete {
+                println!("   or open {}", complete);
+            }
+            println!("⏳ Waiting for the sign-in...");
+        })
+        .await?;
+    let claims = provider.claims(&tokens.access_token).await?;
+    let user = ["preferred_username", "email", "sub"]
+        .iter()
+        .find_map(|claim| claims[claim].as_str())
+        .unwrap_or("unknown");
+    println!("✅ Signed in as {}", user);
+    let permissions = provider.config().permissions(&claims);
+    if permissions.is_empty() {
+        println!("⚠️  Your groups grant no Vortex permissions");
+    } else {
+        println!("🔓 Permissions: {:?}", permissions);
+    }
+    Ok(())
+}
+
+async fn show_dev_templates(vortex: &Arc<VortexCore>) -> Result<()> {
+    let templates = vortex.dev_env_manager.list_templates();
+
+    println!("🔥 Available Dev Environment Templates:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+    for template in templates {
+        println!("📦 {} - {}", template.name, template.description);
+        println!("   Base: {}", template.base_image);
+        println!("   Tools: {}", template.tools.join(", "));
+        if !template.ports.is_empty() {
+            println!("   Ports: {}", template.ports.join(", "));
+        }
+        if !template.extensions.is_empty() {
+            println!("   IDE Extensions: {}", template.extensions.join(", "));
+        }
+        if let Some(origin) = vortex.dev_env_manager.template_origin(&template.name) {
+            if *origin != TemplateOrigin::Builtin {
+                println!("   Origin: {}", origin);
+            }
+        }
+        println!();
+    }
+
+    println!("💡 Usage: vortex dev <template> [options]");
+    println!("📖 Example: vortex dev python --workdir /workspace --volume ./src:/workspace/src");
+
+    Ok(())
+}
+
+fn show_dev_template(vortex: &Arc<VortexCore>, name: &str, origin: bool) -> Result<()> {
+    let template = vortex
+        .dev_env_manager
+        .get_template(name)
+        .ok_or_else(|| anyhow::anyhow!("Template '{}' not found", name))?;
+
+    if origin {
+        if let Some(origin) = vortex.dev_env_manager.template_origin(name) {
+            println!("# origin: {}", origin);
+        }
+    }
+    print!("{}", toml::to_string_pretty(template)?);
+
+    Ok(())
+}
+
+fn add_dev_template(
+    vortex: &Arc<VortexCore>,
+    name: &str,
+    from: Option<String>,
+    file: Option<PathBuf>,
+) -> Result<()> {
+    if let Some(origin) = vortex.dev_env_manager.template_origin(name) {
+        return Err(anyhow::anyhow!(
+            "Template '{}' exists ({}); change it with vortex template edit {}",
+            name,
+            origin,
+            name
+        ));
+    }
+    let template = match (from, file) {
+        (Some(from), _) => vortex
+            .dev_env_manager
+            .get_template(&from)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Template '{}' not found", from))?,
+        (None, Some(file)) => vortex::templates::read_template(&file)?,
+        (None, None) => unreachable!("clap requires --from or --file"),
+    };
+    let template = DevTemplate {
+        name: name.to_string(),
+        ..template
+    };
+    let path = vortex::templates::save_user_template(&template)?;
+    println!("✅ Template '{}' saved to {}", name, path.display());
+    println!("💡 Start it with vortex dev {}", name);
+    Ok(())
+}
+
+fn remove_dev_template(vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
+    match vortex.dev_env_manager.template_origin(name) {
+        Some(TemplateOrigin::User { .. } | TemplateOrigin::Override { .. }) => {}
+        Some(origin) => {
+            return Err(anyhow::anyhow!(
+                "Template '{}' is not a user template ({})",
+                name,
+                origin
+            ))
+        }
+        None => return Err(anyhow::anyhow!("Template '{}' not found", name)),
+    }
+    let path = vortex::templates::remove_user_template(name)?;
+    println!("🗑️  Removed {}", path.display());
+    if matches!(
+        vortex.dev_env_manager.template_origin(name),
+        Some(TemplateOrigin::Override { .. })
+    ) {
+        println!("💡 The built-in '{}' applies again", name);
+    }
+    Ok(())
+}
+
+fn edit_dev_template(vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
+    let path = vortex.dev_env_manager.prepare_user_template(name)?;
+    let original = std::fs::read_to_string(&path)?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut editor_parts = editor.split_whitespace();
+    let program = editor_parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("$EDITOR is empty"))?;
+    let editor_args: Vec<&str> = editor_parts.collect();
+
+    loop {
+        let status = std::process::Command::new(program)
+            .args(&editor_args)
+            .arg(&path)
+            .status()
+            .with_context(|| format!("Failed to launch editor '{}'", editor))?;
+        if !status.success() {
+            std::fs::write(&path, &original)?;
+            return Err(anyhow::anyhow!(
+                "Editor exited with {}, changes discarded",
+                status
+            ));
+        }
+
+        match vortex::templates::read_template_file(&path) {
+            Ok(_) => break,
+            Err(e) => {
+                println!("❌ Invalid template: {}", e);
+                print!("Re-open editor? [Y/n] ");
+                std::io::Write::flush(&mut std::io::stdout())?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    std::fs::write(&path, &original)?;
+                    println!("↩️  Changes discarded");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    println!("✅ Template '{}' saved to {}", name, path.display());
+    if matches!(
+        vortex.dev_env_manager.template_origin(name),
+        Some(TemplateOrigin::Builtin | TemplateOrigin::Override { .. })
+    ) {
+        println!("💡 This user template shadows the built-in '{}'", name);
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn start_dev_environment(
+    vortex: &Arc<VortexCore>,
+    template_name: &str,
+    workdir: Option<String>,
+    volumes: Vec<String>,
+    ports: Vec<String>,
+    secrets: &[SecretRequest],
+    quiet: bool,
+    name: Option<String>,
+    detach: bool,
+    persist_home: bool,
+    overrides: DevOverrides,
+) -> Result<()> {
+    // Parse volume and port mappings
+    let VolumeMappings {
+        volumes: mut volume_mappings,
+        mechanisms,
+    } = parse_volume_mappings(volumes).await?;
+    let mut overrides = overrides;
+    overrides.share_mechanisms.extend(mechanisms);
+    if let Some(policy) = project_policy()? {
+        policy.check_mounts(&volume_mappings)?;
+        policy.check_mounts(&overrides.volumes)?;
+        if let Some(image) = &overrides.image {
+            policy.check_image(image)?;
+        } else if let Some(template) = vortex.dev_env_manager.get_template(template_name) {
+            policy.check_image(&template.base_image)?;
+        }
+    }
+    if persist_home {
+        let home = home_volume::prepare(template_name)?;
+        volume_mappings.insert(home, PathBuf::from(home_volume::GUEST_HOME));
+    }
+    let _port_mappings = parse_port_mappings(ports)?;
+
+    // Secrets are written to a run directory, removed along with the VM
+    let mut run_dir = None;
+    if !secrets.is_empty() {
+        let dir = RunDir::create()?;
+        let prepared = SecretsManager::new()
+            .and_then(|manager| manager.prepare(secrets, &dir.path().join("secrets")));
+        match prepared {
+            Ok(mount) => overrides.secrets = Some(mount),
+            Err(e) => {
+                if let Err(cleanup_err) = dir.remove() {
+                    tracing::warn!("Failed to remove run directory: {}", cleanup_err);
+                }
+                return Err(e.into());
+            }
+        }
+        run_dir = Some(dir);
+    }
+
+    // Create the dev environment VM with optional custom name
+    progress::phase(
+        "creating_vm",
+        format!("Creating dev environment from template '{}'", template_name),
+    );
+    let created = vortex
+        .create_dev_environment(template_name, workdir.clone(), volume_mappings, overrides)
+        .await;
+    let mut vm = match (created, run_dir) {
+        (Ok(vm), Some(mut run_dir)) => {
+            run_dir.attach_vm(&vm)?;
+            vm
+        }
+        (Ok(vm), None) => vm,
+        (Err(e), run_dir) => {
+            if let Some(Err(cleanup_err)) = run_dir.map(RunDir::remove) {
+                tracing::warn!("Failed to remove run directory: {}", cleanup_err);
+            }
+            return Err(e.into());
+        }
+    };
+    progress::phase("vm_started", format!("VM {} started", vm.id));
+    let created_id = vm.id.clone();
+
+    // If a name is provided, update the VM ID to be more user-friendly
+    if let Some(session_name) = &name {
+        let new_id = format!("vortex-{}", session_name);
+        vm.id = new_id.into();
+    }
+
+    if !quiet {
+        let template = vortex
+            .dev_env_manager
+            .get_template(template_name)
+            .ok_or_else(|| anyhow::anyhow!("Template '{}' not found", template_name))?;
+
+        println!();
+        println!("🚀 Dev Environment Ready!");
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        println!("📦 Template: {} ({})", template.name, template.description);
+        println!("🐳 Base: {}", vm.spec.image);
+        println!("🔧 Tools: {}", template.tools.join(", "));
+        if !template.ports.is_empty() {
+            println!("🌐 Ports: {}", template.ports.join(", "));
+        }
+        if !vm.spec.volumes.is_empty() {
+            println!("📂 Volumes: {} mount(s)", vm.spec.volumes.len());
+        }
+        println!("💾 VM ID: {}", vm.id);
+
+        if let Some(session_name) = &name {
+            println!("📝 Session: {}", session_name);
+        }
+
+        if detach {
+            println!("🔓 Running in background (detached mode)");
+            println!("💡 Attach with: vortex attach {}", vm.id);
+        } else {
+            println!();
+            println!("⚡ Lightning-fast setup complete! (Docker would still be pulling images)");
+            println!("💬 Connecting to interactive shell...");
+        }
+        println!();
+    }
+
+    if detach {
+        // For detached mode, just leave the VM running
+        if !quiet {
+            println!("✅ Background session '{}' started", vm.id);
+            println!("🔗 Attach anytime with: vortex attach {}", vm.id);
+        }
+    } else {
+        // Attach to the VM for interactive development
+        progress::phase("attaching", format!("Attaching to {}", vm.id));
+        vortex.attach_vm(&vm.id).await?;
+
+        // Cleanup when done (only for non-detached sessions)
+        if !quiet {
+            println!("\n🧹 Cleaning up dev environment...");
+        }
+        progress::phase("cleanup", format!("Cleaning up {}", vm.id));
+        vortex.cleanup_vm(&vm.id).await?;
+        run_dir::remove_runs_for_vm(&created_id)?;
+
+        if !quiet {
+            println!("✅ Dev session complete!");
+        }
+    }
+
+    Ok(())
+}
+
+/// Template and settings for `vortex dev` without a template: the project's
+/// saved setup, the detected project type, or the picker's answers
+fn resolve_dev_project(vortex: &Arc<VortexCore>) -> Result<(String, DevOverrides)> {
+    use std::io::IsTerminal;
+
+    let current_dir = std::env::current_dir()?;
+    if let Some(project) = DevProject::load(&current_dir)? {
+        println!("📄 Using {}", DEV_PROJECT_FILE);
+        return Ok((project.template.clone(), project.overrides()?));
+    }
+    if let Some(template) = detect_template(&current_dir) {
+        println!("🔍 Detected a {} project", template);
+        return Ok((template.to_string(), DevOverrides::default()));
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "No template given and no project detected; pass one, e.g. vortex dev python"
+        ));
+    }
+
+    let project = pick_dev_project(vortex)?;
+    let path = project.save(&current_dir)?;
+    println!();
+    println!("💾 Saved to {}", path.display());
+    println!("💡 vortex dev will use it from now on; edit or delete it to change");
+    println!();
+    Ok((project.template.clone(), project.overrides()?))
+}
+
+fn prompt(question: &str) -> Result<String> {
+    print!("{}", question);
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+fn pick_dev_project(vortex: &Arc<VortexCore>) -> Result<DevProject> {
+    let mut templates = vortex.dev_env_manager.list_templates();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!("🧭 No project detected here. Pick a dev environment:");
+    for (i, template) in templates.iter().enumerate() {
+        println!("  {}. {} - {}", i + 1, template.name, template.description);
+    }
+    let template = loop {
+        let answer = prompt("Template [1]: ")?;
+        let choice = if answer.is_empty() {
+            Some(0)
+        } else {
+            answer.parse::<usize>().ok().and_then(|n| n.checked_sub(1))
+        };
+        match choice.and_then(|i| templates.get(i)) {
+            Some(template) => break *template,
+            None => match templates.iter().find(|t| t.name == answer) {
+                Some(template) => break *template,
+                None => println!("❌ Enter a number from 1 to {}", templates.len()),
+            },
+        }
+    };
+
+    println!();
+    println!("Resources:");
+    for (i, preset) in RESOURCE_PRESETS.iter().enumerate() {
+        println!(
+            "  {}. {} - {} MB, {} CPU(s)",
+            i + 1,
+            preset.name,
+            preset.memory,
+            preset.cpus
+        );
+    }
+    let preset = loop {
+        let answer = prompt(&format!("Resources [{}]: ", DEFAULT_PRESET + 1))?;
+        let choice = if answer.is_empty() {
+            Some(DEFAULT_PRESET)
+        } else {
+            answer.parse::<usize>().ok().and_then(|n| n.checked_sub(1))
+        };
+        match choice.and_then(|i| RESOURCE_PRESETS.get(i)) {
+            Some(preset) => break *preset,
+            None => println!("❌ Enter a number from 1 to {}", RESOURCE_PRESETS.len()),
+        }
+    };
+
+    println!();
+    let suggested = template.ports.join(", ");
+    let ports = loop {
+        let answer = prompt(&format!(
+            "Ports (host:guest, comma-separated, - for none) [{}]: ",
+            suggested
+        ))?;
+        if answer.is_empty() {
+            break None;
+        }
+        if answer == "-" {
+            break Some(Vec::new());
+        }
+        let ports: Vec<String> = answer.split(',').map(|p| p.trim().to_string()).collect();
+        match ports
+            .iter()
+            .try_for_each(|p| vortex::dev_project::parse_port(p).map(drop))
+        {
+            Ok(()) => break Some(ports),
+            Err(e) => println!("❌ {}", e),
+        }
+    };
+
+    Ok(DevProject {
+        template: template.name.clone(),
+        memory: Some(preset.memory),
+        cpus: Some(preset.cpus),
+        ports,
+        backend: None,
+    })
+}
+
+// Workspace management functions
+
+async fn init_workspace_from_current_dir(vortex: &Arc<VortexCore>) -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+
+    if let Some(info) = detect_workspace_info(&current_dir) {
+        println!("🔍 Detected project in current directory:");
+        println!("   Name: {}", info.name);
+        println!("   Suggested template: {}", info.suggested_template);
+
+        if info.has_devcontainer {
+            println!("   📦 DevContainer detected!");
+            println!();
+            println!("Would you like to:");
+            println!("  1. Import from devcontainer.json (recommended)");
+            println!("  2. Create standard Vortex workspace");
+            println!();
+
+            // For now, just import the devcontainer
+            if let Some(devcontainer_path) = &info.devcontainer_path {
+                let workspace = vortex.workspace_manager.create_from_devcontainer(
+                    &info.name,
+                    devcontainer_path,
+                    &current_dir,
+                )?;
+
+                println!(
+                    "✅ Workspace '{}' created from devcontainer!",
+                    workspace.name
+                );
+                println!("🚀 Run: vortex dev --workspace {}", workspace.name);
+            }
+        } else {
+            let workspace = vortex.workspace_manager.create_workspace(
+                &info.name,
+                &info.suggested_template,
+                Some(&current_dir),
+            )?;
+
+            println!("✅ Workspace '{}' created!", workspace.name);
+            println!("🚀 Run: vortex dev --workspace {}", workspace.name);
+        }
+    } else {
+        return Err(anyhow::anyhow!(
+            "Could not detect project type in current directory"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn start_workspace(
+    vortex: &Arc<VortexCore>,
+    workspace_name: &str,
+    quiet: bool,
+) -> Result<()> {
+    // Try to find workspace by name first, then by ID
+    let workspace = vortex
+        .workspace_manager
+        .find_workspace_by_name(workspace_name)?
+        .or_else(|| {
+            vortex
+                .workspace_manager
+                .get_workspace(workspace_name)
+                .unwrap_or(None)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
+
+    if !quiet {
+        println!();
+        println!("🔄 Launching workspace '{}'...", workspace.name);
+        println!("📁 Path: {}", workspace.path.display());
+        println!("🎯 Template: {}", workspace.config.template);
+
+        if let Some(devcontainer) = &workspace.config.devcontainer_source {
+            println!("📦 DevContainer: {}", devcontainer);
+        }
+
+        println!(
+            "⏰ Last used: {}",
+            workspace.config.last_used.format("%Y-%m-%d %H:%M")
+        );
+        println!();
+    }
+
+    // Create and start VM from workspace
+    let vm = vortex.create_workspace_vm(&workspace.id).await?;
+
+    if !quiet {
+        println!("⚡ Workspace VM ready!");
+        println!("💬 Connecting to interactive session...");
+        println!();
+    }
+
+    // Attach to the VM
+    vortex.attach_vm(&vm.id).await?;
+
+    // Cleanup when done
+    if !quiet {
+        println!("\n🧹 Cleaning up workspace VM...");
+    }
+    vortex.cleanup_vm(&vm.id).await?;
+
+    if !quiet {
+        println!("✅ Workspace session complete! Your work is safely stored.");
+    }
+
+    Ok(())
+}
+
+async fn list_workspaces(vortex: &Arc<VortexCore>) -> Result<()> {
+    let workspaces = vortex.workspace_manager.list_workspaces()?;
+
+    if workspaces.is_empty() {
+        println!("No workspaces found.");
+        println!("💡 Create one with: vortex workspace create <name> --template <template>");
+        println!("💡 Or initialize from current dir: vortex dev --init");
+        return Ok(());
+    }
+
+    println!("🔥 Persistent Workspaces:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+    for workspace in workspaces {
+        println!("📁 {} ({})", workspace.name, &workspace.id[..8]);
+        println!("   Template: {}", workspace.config.template);
+        println!("   Path: {}", workspace.path.display());
+        println!(
+            "   Last used: {}",
+            workspace.config.last_used.format("%Y-%m-%d %H:%M")
+        );
+
+        if let Some(devcontainer) = &workspace.config.devcontainer_source {
+            println!("   📦 DevContainer: {}", devcontainer);
+        }
+
+        if !workspace.config.port_forwards.is_empty() {
+            println!(
+                "   🌐 Ports: {}",
+                workspace
+                    .config
+                    .port_forwards
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        println!();
+    }
+
+    println!("💡 Start workspace: vortex dev --workspace <name>");
+    println!("📖 More info: vortex workspace info <name>");
+
+    Ok(())
+}
+
+async fn create_workspace(
+    vortex: &Arc<VortexCore>,
+    name: &str,
+    template: &str,
+    source: &Option<PathBuf>,
+    backend: &str,
+    encrypted: bool,
+) -> Result<()> {
+    let source_dir = source
+        .as_ref()
+        .map(|p| p.as_path())
+        .unwrap_or_else(|| std::path::Path::new("."));
+
+    // Verify template exists
+    if vortex.dev_env_manager.get_template(template).is_none() {
+        return Err(anyhow::anyhow!("Template '{}' not found", template));
+    }
+
+    // For now, we'll store the backend in the workspace config
+    // The backend field is stored in VortexWorkspaceConfig
+    progress::phase(
+        "copying_source",
+        format!("Copying {} into workspace", source_dir.display()),
+    );
+    let workspace = vortex
+        .workspace_manager
+        .create_workspace(name, template, Some(source_dir))?;
+
+    // Store backend preference in workspace config
+    if let Some(mut config) = vortex.workspace_manager.get_workspace(&workspace.id)? {
+        config.config.backend = Some(backend.to_string());
+        vortex
+            .workspace_manager
+            .save_workspace_config(&workspace.id, &config.config)?;
+    }
+    let workspace = if encrypted {
+        vortex.workspace_manager.encrypt_workspace(&workspace.id)?
+    } else {
+        workspace
+    };
+
+    println!("✅ Workspace '{}' created!", workspace.name);
+    println!("📁 Path: {}", workspace.path.display());
+    println!("🎯 Template: {}", workspace.config.template);
+    println!("⚙️  Backend: {}", backend);
+    if workspace.config.encrypted {
+        println!("🔒 Its files are encrypted at rest and unlocked while a VM has them mounted");
+    }
+    println!("🚀 Start with: vortex dev --workspace {}", workspace.name);
+
+    Ok(())
+}
+
+async fn delete_workspace(vortex: &Arc<VortexCore>, workspace_name: &String) -> Result<()> {
+    // Find workspace by name or ID
+    let workspace = vortex
+        .workspace_manager
+        .find_workspace_by_name(workspace_name)?
+        .or_else(|| {
+            vortex
+                .workspace_manager
+                .get_workspace(workspace_name)
+                .unwrap_or(None)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
+
+    println!(
+        "⚠️  This will permanently delete workspace '{}'",
+        workspace.name
+    );
+    println!("📁 Path: {}", workspace.path.display());
+    println!();
+    println!("Are you sure? [y/N]: ");
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+
+    if input.trim().to_lowercase() == "y" {
+        vortex.workspace_manager.delete_workspace(&workspace.id)?;
+        println!("🗑️  Workspace '{}' deleted", workspace.name);
+    } else {
+        println!("❌ Cancelled");
+    }
+
+    Ok(())
+}
+
+async fn show_workspace_info(vortex: &Arc<VortexCore>, workspace_name: &String) -> Result<()> {
+    let workspace = vortex
+        .workspace_manager
+        .find_workspace_by_name(workspace_name)?
+        .or_else(|| {
+            vortex
+                .workspace_manager
+                .get_workspace(workspace_name)
+                .unwrap_or(None)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
+
+    println!("🔍 Workspace Details:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("📁 Name: {}", workspace.name);
+    println!("🆔 ID: {}", workspace.id);
+    println!("🎯 Template: {}", workspace.config.template);
+    println!("📂 Path: {}", workspace.path.display());
+    println!(
+        "📅 Created: {}",
+        workspace.config.created_at.format("%Y-%m-%d %H:%M")
+    );
+    println!(
+        "⏰ Last used: {}",
+        workspace.config.last_used.format("%Y-%m-%d %H:%M")
+    );
+    println!(
+        "📁 Working directory: {}",
+        workspace.config.preferred_workdir
+    );
+    if let Some(owner) = &workspace.config.owner {
+        println!("👤 Owner: {}", owner);
+    }
+    if !workspace.config.allowed_users.is_empty() {
+        println!(
+            "👥 Shared with: {}",
+            workspace.config.allowed_users.join(", ")
+        );
+    }
+
+    if let Some(devcontainer) = &workspace.config.devcontainer_source {
+        println!("📦 DevContainer source: {}", devcontainer);
+    }
+
+    if !workspace.config.port_forwards.is_empty() {
+        println!(
+            "🌐 Port forwards: {}",
+            workspace
+                .config
+                .port_forwards
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    if !workspace.config.environment_vars.is_empty() {
+        println!("🌍 Environment variables:");
+        for (key, value) in &workspace.config.environment_vars {
+            println!("   {}={}", key, value);
+        }
+    }
+
+    if !workspace.config.custom_commands.is_empty() {
+        println!("⚙️  Custom commands:");
+        for cmd in &workspace.config.custom_commands {
+            println!("   {}", cmd);
+        }
+    }
+
+    // Show directory contents
+    if workspace.path.exists() {
+        println!("\n📋 Workspace contents:");
+        match std::fs::read_dir(&workspace.path) {
+            Ok(entries) => {
+                let mut files: Vec<_> = entries.collect::<std::io::Result<Vec<_>>>()?;
+                files.sort_by_key(|e| e.file_name());
+
+                for entry in files.iter().take(10) {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if entry.file_type()?.is_dir() {
+                        println!("   📁 {}/", name);
+                    } else {
+                        println!("   📄 {}", name);
+                    }
+                }
+
+                if files.len() > 10 {
+                    println!("   ... and {} more files", files.len() - 10);
+                }
+            }
+            Err(e) => println!("   Error reading directory: {}", e),
+        }
+    }
+
+    println!();
+    println!(
+        "🚀 Start workspace: vortex dev --workspace {}",
+        workspace.name
+    );
+
+    Ok(())
+}
+
+async fn import_devcontainer_workspace(
+    vortex: &Arc<VortexCore>,
+    name: &str,
+    devcontainer_path: &Path,
+    source: &Option<PathBuf>,
+    backend: &str,
+) -> Result<()> {
+    let source_dir = source
+        .as_ref()
+        .map(|p| p.as_path())
+        .unwrap_or_else(|| std::path::Path::new("."));
+
+    if !devcontainer_path.exists() {
+        return Err(anyhow::anyhow!(
+            "DevContainer file not found: {}",
+            devcontainer_path.display()
+        ));
+    }
+
+    let workspace =
+        vortex
+            .workspace_manager
+            .create_from_devcontainer(name, devcontainer_path, source_dir)?;
+
+    // Store backend preference in workspace config
+    if let Some(mut config) = vortex.workspace_manager.get_workspace(&workspace.id)? {
+        config.config.backend = Some(backend.to_string());
+        vortex
+            .workspace_manager
+            .save_workspace_config(&workspace.id, &config.config)?;
+    }
+
+    println!(
+        "✅ Workspace '{}' imported from devcontainer!",
+        workspace.name
+    );
+    println!("📦 DevContainer: {}", devcontainer_path.display());
+    println!("📁 Path: {}", workspace.path.display());
+    println!("🎯 Detected template: {}", workspace.config.template);
+    println!("⚙️  Backend: {}", backend);
+
+    if !workspace.config.port_forwards.is_empty() {
+        println!(
+            "🌐 Port forwards: {}",
+            workspace
+                .config
+                .port_forwards
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    println!("🚀 Start with: vortex dev --workspace {}", workspace.name);
+
+    Ok(())
+}
+
+fn share_workspace(
+    vortex: &Arc<VortexCore>,
+    workspace_name: &str,
+    users: &[String],
+    revoke: bool,
+) -> Result<()> {
+    let mut workspace = vortex
+        .workspace_manager
+        .find_workspace_by_name(workspace_name)?
+        .or_else(|| {
+            vortex
+                .workspace_manager
+                .get_workspace(workspace_name)
+                .unwrap_or(None)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
+
+    let allowed = &mut workspace.config.allowed_users;
+    if revoke {
+        allowed.retain(|user| !users.contains(user));
+    } else {
+        for user in users {
+            if !allowed.contains(user) {
+                allowed.push(user.clone());
+            }
+        }
+    }
+    vortex
+        .workspace_manager
+        .save_workspace_config(&workspace.id, &workspace.config)?;
+
+    if workspace.config.allowed_users.is_empty() {
+        println!("🔒 Workspace '{}' is not shared", workspace.name);
+    } else {
+        println!(
+            "👥 Workspace '{}' is shared with {}",
+            workspace.name,
+            workspace.config.allowed_users.join(", ")
+        );
+    }
+    Ok(())
+}
+
+async fn export_workspace(
+    vortex: &Arc<VortexCore>,
+    workspace_name: &str,
+    output: &Path,
+) -> Result<()> {
+    let workspace = vortex
+        .workspace_manager
+        .find_workspace_by_name(workspace_name)?
+        .or_else(|| {
+            vortex
+                .workspace_manager
+                .get_workspace(workspace_name)
+                .unwrap_or(None)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
+
+    let manifest = vortex
+        .workspace_manager
+        .export_workspace(&workspace.id, output)?;
+
+    println!("📦 Workspace '{}' exported", workspace.name);
+    print_archive(output, &manifest);
+
+    Ok(())
+}
+
+/// Where an archive was written and its layers
+fn print_archive(output: &Path, manifest: &ArchiveManifest) {
+    println!("📁 Archive: {}", output.display());
+    for layer in &manifest.layers {
+        println!(
+            "   {} - {:.1}MB (sha256 {})",
+            layer.name,
+            layer.compressed_size as f64 / 1024.0 / 1024.0,
+            &layer.sha256[..12]
+        );
+    }
+}
+
+async fn load_workspace(
+    vortex: &Arc<VortexCore>,
+    archive: &Path,
+    name: Option<&str>,
+) -> Result<()> {
+    if let Some(name) = name {
+        if vortex.workspace_manager.find_workspace_by_name(name)?.is_some() {
+            return Err(anyhow::anyhow!("Workspace '{}' already exists", name));
+        }
+    }
+
+    let workspace = vortex.workspace_manager.load_workspace(archive, name)?;
+
+    println!("✅ Workspace '{}' loaded from archive", workspace.name);
+    println!("📁 Path: {}", workspace.path.display());
+    println!("🎯 Template: {}", workspace.config.template);
+    println!("🚀 Start with: vortex dev --workspace {}", workspace.name);
+
+    Ok(())
+}
+
+// Workspace initialization with interactive discovery
+
+async fn handle_workspace_init(
+    directory: &Path,
+    output: &Path,
+    non_interactive: bool,
+    backend: &str,
+) -> Result<()> {
+    println!();
+    println!("🔍 Scanning project directory: {}", directory.display());
+
+    // Auto-detect project structure using the core workspace detection
+    let workspace_info = detect_workspace_info(directory);
+
+    if let Some(info) = workspace_info {
+        if non_interactive {
+            // Non-interactive mode: Generate config directly
+            println!("✅ Detected project: {}", info.name);
+            println!("   Template: {}", info.suggested_template);
+            println!("   Backend: {}", backend);
+
+            let vortex_config_path = output;
+            generate_vortex_yaml_from_info(&info, vortex_config_path, backend)?;
+
+            println!(
+                "✅ Configuration saved to: {}",
+                vortex_config_path.display()
+            );
+            println!(
+                "🚀 Run: vortex workspace create <name> --template <template> --source {}",
+                directory.display()
+            );
+        } else {
+            // Interactive mode: Ask user questions
+            interactive_workspace_init(&info, directory, output, backend).await?;
+        }
+    } else {
+        // No project detected - use interactive mode
+        interactive_workspace_init(
+            &default_workspace_info(directory),
+            directory,
+            output,
+            backend,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn default_workspace_info(directory: &Path) -> WorkspaceInfo {
+    WorkspaceInfo {
+        name: directory
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "my-project".to_string()),
+        suggested_template: "python".to_string(),
+        has_devcontainer: false,
+        devcontainer_path: None,
+    }
+}
+
+fn generate_vortex_yaml_from_info(
+    info: &WorkspaceInfo,
+    output_path: &Path,
+    backend: &str,
+) -> Result<()> {
+    // Simple YAML generation for now
+    let yaml = format!(
+        r#"name: {}
+description: Auto-generated workspace for {}
+backend: {}
+
+services:
+  default:
+    type: backend
+    language: python
+    image: python:3.11-slim
+    ports:
+      - 8000:8000
+
+# Applied with --context, e.g. vortex run --context prod
+contexts:
+  dev:
+    env:
+      LOG_LEVEL: debug
+  prod:
+    memory: 2048
+    env:
+      LOG_LEVEL: info
+"#,
+        info.name, info.name, backend
+    );
+
+    std::fs::write(output_path, yaml)?;
+    Ok(())
+}
+
+async fn interactive_workspace_init(
+    info: &WorkspaceInfo,
+    _directory: &Path,
+    _output: &Path,
+    backend: &str,
+) -> Result<()> {
+    // Interactive mode - ask user questions
+    println!("✅ Detected project: {}", info.name);
+    println!("   Template: {}", info.suggested_template);
+    println!("   Backend: {}", backend);
+    println!();
+
+    // In a real implementation, we'd ask user questions here
+    // For now, just generate the config
+    let output = Path::new("vortex.yaml");
+    generate_vortex_yaml_from_info(info, output, backend)?;
+
+    println!("✅ Configuration saved to: vortex.yaml");
+    println!("🚀 Run: vortex workspace create <name> --template <template>");
+
+    Ok(())
+}
+
+// Session management handlers
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_session_create(
+    vortex: &Arc<VortexCore>,
+    template: &str,
+    name: Option<String>,
+    memory: u32,
+    cpus: u32,
+    ports: &[String],
+    volumes: &[String],
+    detach: bool,
+    persistent: bool,
+) -> Result<()> {
+    // Ensure daemon is running
+    DaemonClient::start_daemon_if_needed().await?;
+
+    // Convert template to VmSpec
+    let mut spec = vortex.dev_env_manager.template_to_vm_spec(template, None)?;
+
+    // Override with user preferences
+    spec.memory = memory;
+    spec.cpus = cpus;
+    let mappings = parse_port_mappings(ports.to_vec())?;
+    spec.ports = mappings.ports;
+    spec.publish = mappings.publish;
+    spec.tls_ports = mappings.tls_ports;
+    spec.port_addresses = mappings.addresses;
+
+    // Merge volumes
+    let additional_volumes = parse_volume_mappings(volumes.to_vec()).await?;
+    for (host, guest) in additional_volumes.volumes {
+        spec.volumes.insert(host, guest);
+    }
+    spec.share_mechanisms.extend(additional_volumes.mechanisms);
+
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::CreateSession {
+            spec: Box::new(spec),
+            name: name.clone(),
+            persistent,
+            boot_start: false, // New sessions don't auto-start by default
+        })
+        .await?;
+
+    match response {
+        SessionResponse::SessionCreated { session } => {
+            println!("✅ Session created: {}", session.id);
+            if let Some(name) = &session.name {
+                println!("📝 Name: {}", name);
+            }
+            println!("🎯 Template: {}", template);
+            println!("🚀 State: {:?}", session.state);
+
+            if !detach {
+                println!("🔗 Attaching to session...");
+                handle_attach_session(&session.id).await?;
+            } else {
+                println!("💡 Attach later with: vortex attach {}", session.id);
+                if let Some(name) = &session.name {
+                    println!("💡 Or: vortex attach {}", name);
+                }
+            }
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to create session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_list(query: ListQuery) -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        println!("📴 Daemon is not running. Start it with: vortex daemon start");
+        return Ok(());
+    }
+
+    let response = client
+        .send_command(SessionCommand::ListSessionsPage { query })
+        .await?;
+
+    match response {
+        SessionResponse::SessionPage { page } => {
+            let sessions = page
+                .items
+                .into_iter()
+                .map(serde_json::from_value::<vortex::VmSession>)
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            if sessions.is_empty() {
+                println!("No active sessions found.");
+                println!("💡 Create one with: vortex session create <template>");
+                return Ok(());
+            }
+
+            println!("🔥 Active Sessions:");
+            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+            let sessions_shown = sessions.len();
+
+            for session in sessions {
+                let state_emoji = match session.state {
+                    vortex::SessionState::Running => "🟢",
+                    vortex::SessionState::Detached => "🟡",
+                    vortex::SessionState::Attached { .. } => "🔵",
+                    vortex::SessionState::Stopped => "🔴",
+                    vortex::SessionState::Paused => "⏸️",
+                    vortex::SessionState::Error { .. } => "❌",
+                    _ => "⚪",
+                };
+
+                print!("{} {} ", state_emoji, &session.id[..12]);
+                if let Some(name) = &session.name {
+                    print!("({}) ", name);
+                }
+                println!("- {:?}", session.state);
+
+                println!("   🖼️  Image: {}", session.spec.image);
+                println!(
+                    "   💾 Resources: {}MB RAM, {} CPU(s)",
+                    session.spec.memory, session.spec.cpus
+                );
+
+                if let Some(last_attached) = session.last_attached {
+                    let duration = chrono::Utc::now() - last_attached;
+                    if duration.num_hours() > 0 {
+                        println!("   ⏰ Last attached: {} hours ago", duration.num_hours());
+                    } else {
+                        println!(
+                            "   ⏰ Last attached: {} minutes ago",
+                            duration.num_minutes()
+                        );
+                    }
+                }
+
+                if session.persistent {
+                    println!("   🔒 Persistent session");
+                }
+
+                println!();
+            }
+
+            if let Some(cursor) = page.next_cursor {
+                println!(
+                    "📄 Showing {} of {} sessions. Next page: vortex session list --cursor {}",
+                    sessions_shown, page.total, cursor
+                );
+            }
+            println!("💡 Attach to session: vortex attach <session-id>");
+            println!("📖 Session details: vortex session info <session-id>");
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to list sessions: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_info(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::GetSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Session { session } => {
+            println!("🔍 Session Details:");
+            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+            println!("🆔 ID: {}", session.id);
+
+            if let Some(name) = &session.name {
+                println!("📝 Name: {}", name);
+            }
+
+            println!("🚀 State: {:?}", session.state);
+            println!("🖼️  Image: {}", session.spec.image);
+            println!(
+                "💾 Resources: {}MB RAM, {} CPU(s)",
+                session.spec.memory, session.spec.cpus
+            );
+            println!(
+                "📅 Created: {}",
+                session.created_at.format("%Y-%m-%d %H:%M:%S")
+            );
+
+            if let Some(last_attached) = session.last_attached {
+                println!(
+                    "⏰ Last attached: {}",
+                    last_attached.format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+
+            if session.persistent {
+                println!("🔒 Persistent: Yes");
+            } else {
+                println!("🔒 Persistent: No");
+            }
+
+            if !session.spec.ports.is_empty() {
+                println!("🌐 Port forwards:");
+                for (host, guest) in &session.spec.ports {
+                    println!("   {}:{}", host, guest);
+                }
+            }
+
+            if !session.spec.volumes.is_empty() {
+                println!("📂 Volume mounts:");
+                for (host, guest) in &session.spec.volumes {
+                    println!("   {} → {}", host.display(), guest.display());
+                }
+            }
+
+            if !session.spec.labels.is_empty() {
+                println!("🏷️  Labels:");
+                for (key, value) in &session.spec.labels {
+                    println!("   {}={}", key, value);
+                }
+            }
+
+            match session.state {
+                vortex::SessionState::Detached | vortex::SessionState::Running => {
+                    println!("\n💡 Attach with: vortex attach {}", session.id);
+                }
+                vortex::SessionState::Stopped => {
+                    println!("\n💡 Start with: vortex session start {}", session.id);
+                }
+                vortex::SessionState::Paused => {
+                    println!("\n💡 Resume with: vortex session resume {}", session.id);
+                }
+                _ => {}
+            }
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to get session info: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_start(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::StartSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("✅ Session {} started", session_id);
+            println!("💡 Attach with: vortex attach {}", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to start session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_stop(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::StopSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("⏹️  Session {} stopped", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to stop session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_pause(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::PauseSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("⏸️  Session {} paused", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to pause session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_resume(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::ResumeSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("▶️  Session {} resumed", session_id);
+            println!("💡 Attach with: vortex attach {}", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to resume session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_restart(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::RestartSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("🔄 Session {} restarted", session_id);
+            println!("💡 Attach with: vortex attach {}", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to restart session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_delete(session_id: &str, force: bool) -> Result<()> {
+    if !force {
+        println!("⚠️  This will permanently delete session: {}", session_id);
+        println!("Are you sure? [y/N]: ");
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+
+        if input.trim().to_lowercase() != "y" {
+            println!("❌ Cancelled");
+            return Ok(());
+        }
+    }
+
+    let client = DaemonClient::new()?;
+    let response = client
+        .send_command(SessionCommand::DeleteSession {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("🗑️  Session {} deleted", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to delete session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_attach_session(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        return Err(anyhow::anyhow!(
+            "Daemon is not running. Start it with: vortex daemon start"
+        ));
+    }
+
+    let client_pid = std::process::id();
+    let response = client
+        .send_command(SessionCommand::AttachSession {
+            session_id: session_id.into(),
+            client_pid,
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("✅ Attached to session {}", session_id);
+            println!("🚪 Session detached");
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to attach to session: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_enable_autostart(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        return Err(anyhow::anyhow!(
+            "Daemon is not running. Start it with: vortex daemon start"
+        ));
+    }
+
+    let response = client
+        .send_command(SessionCommand::EnableBootStart {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("✅ Autostart enabled for session {}", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to enable autostart: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_session_disable_autostart(session_id: &str) -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        return Err(anyhow::anyhow!(
+            "Daemon is not running. Start it with: vortex daemon start"
+        ));
+    }
+
+    let response = client
+        .send_command(SessionCommand::DisableBootStart {
+            session_id: session_id.into(),
+        })
+        .await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("✅ Autostart disabled for session {}", session_id);
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to disable autostart: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+// Daemon management handlers
+
+async fn handle_daemon_start(background: bool) -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if client.is_running().await {
+        println!("📴 Daemon is already running");
+        return Ok(());
+    }
+
+    if background {
+        // Start daemon in background by spawning a new process
+        let current_exe = std::env::current_exe()?;
+        let child = std::process::Command::new(current_exe)
+            .arg("daemon")
+            .arg("start")
+            .spawn()?;
+
+        println!("🚀 Daemon starting in background (PID: {})", child.id());
+
+        // Wait a moment for it to start
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+        if client.is_running().await {
+            println!("✅ Daemon started successfully");
+        } else {
+            return Err(anyhow::anyhow!("Failed to start daemon"));
+        }
+    } else {
+        // Start daemon in foreground
+        println!("🚀 Starting Vortex daemon...");
+        let vortex = init().await?;
+        let daemon = vortex.into_daemon().await?;
+
+        // Handle Ctrl+C gracefully
+        let daemon_ref = Arc::new(daemon);
+        let daemon_clone = daemon_ref.clone();
+
+        tokio::spawn(async move {
+            tokio::signal::ctrl_c()
+                .await
+                .expect("Failed to listen for Ctrl+C");
+            println!("\n🛑 Shutting down daemon...");
+            if let Err(e) = daemon_clone.stop().await {
+                eprintln!("Error stopping daemon: {}", e);
+            }
+        });
+
+        daemon_ref.start().await?;
+    }
+
+    Ok(())
+}
+
+async fn handle_daemon_stop() -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        println!("📴 Daemon is not running");
+        return Ok(());
+    }
+
+    let response = client.send_command(SessionCommand::Shutdown).await?;
+
+    match response {
+        SessionResponse::Success => {
+            println!("🛑 Daemon stopped");
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to stop daemon: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_daemon_status() -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        println!("📴 Daemon Status: Not Running");
+        println!("💡 Start with: vortex daemon start");
+        return Ok(());
+    }
+
+    let response = client.send_command(SessionCommand::GetDaemonStatus).await?;
+
+    match response {
+        SessionResponse::DaemonStatus {
+            uptime,
+            sessions_count,
+            active_vms,
+            memory_usage,
+            event_subscribers,
+        } => {
+            println!("🚀 Daemon Status: Running");
+            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+            let hours = uptime / 3600;
+            let minutes = (uptime % 3600) / 60;
+            let seconds = uptime % 60;
+
+            if hours > 0 {
+                println!("⏰ Uptime: {}h {}m {}s", hours, minutes, seconds);
+            } else if minutes > 0 {
+                println!("⏰ Uptime: {}m {}s", minutes, seconds);
+            } else {
+                println!("⏰ Uptime: {}s", seconds);
+            }
+
+            println!("📊 Sessions: {}", sessions_count);
+            println!("🔥 Active VMs: {}", active_vms);
+            println!(
+                "💾 Memory Usage: {:.1}MB",
+                memory_usage as f64 / 1024.0 / 1024.0
+            );
+
+            if !event_subscribers.is_empty() {
+                println!("📨 Event subscribers:");
+                for stats in &event_subscribers {
+                    println!(
+                        "   {}: {}/{} queued, {} delivered, {} dropped, {} failed",
+                        stats.name,
+                        stats.queued,
+                        stats.capacity,
+                        stats.delivered,
+                        stats.dropped,
+                        stats.failed
+                    );
+                }
+            }
+
+            println!("\n💡 List sessions: vortex sessions");
+            println!("📖 Session details: vortex session info <id>");
+        }
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to get daemon status: {}", message));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unexpected response from daemon"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_daemon_restart() -> Result<()> {
+    println!("🔄 Restarting daemon...");
+
+    // Stop first
+    if DaemonClient::new()?.is_running().await {
+        handle_daemon_stop().await?;
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    }
+
+    // Start again
+    handle_daemon_start(true).await?;
+
+    Ok(())
+}
+
+async fn handle_daemon_upgrade() -> Result<()> {
+    let client = DaemonClient::new()?;
+
+    if !client.is_running().await {
+        println!("📴 Daemon is not running; start it with: vortex daemon start");
+        return Ok(());
+    }
+
+    let executable = std::env::current_exe()?;
+    match client
+        .send_command(SessionCommand::Upgrade { executable })
+        .await?
+    {
+        SessionResponse::Success => {}
+        SessionResponse::Error { message } => {
+            return Err(anyhow::anyhow!("Failed to upgrade daemon: {}", message));
+        }
+        _ => return Err(anyhow::anyhow!("Unexpected response from daemon")),
+    }
+
+    println!("🔄 Handing sessions over to vortex v{}...", VERSION);
+    for _ in 0..20 {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        if client.is_running().await {
+            println!("✅ Daemon upgraded; running sessions and VMs were kept");
+            return Ok(());
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "Upgraded daemon did not come up; run 'vortex daemon start' to adopt the running VMs"
+    ))
+}
+
+async fn handle_daemon_logs() -> Result<()> {
+    println!("📋 Daemon logs are currently only available in daemon output");
+    println!("💡 Start daemon in foreground to see logs: vortex daemon start");
+    Ok(())
+}
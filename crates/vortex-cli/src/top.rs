@@ -0,0 +1,334 @@
+//! `vortex top`: a live dashboard of the VMs and what they use.
+//!
+//! Every VM the manager sees is sampled once per [`REFRESH`], and the last
+//! [`TREND_SAMPLES`] samples of its CPU, memory and network traffic are drawn
+//! as sparklines next to the current values.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vortex::ids::VmId;
+use vortex::{ListQuery, VmInstance, VortexCore};
+
+const REFRESH: Duration = Duration::from_secs(1);
+/// Samples of each VM kept for its sparklines
+const TREND_SAMPLES: usize = 60;
+/// Characters a sparkline cell is drawn with, from low to high
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SPARK_WIDTH: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    Cpu,
+    Memory,
+    Network,
+    Id,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Cpu => SortKey::Memory,
+            SortKey::Memory => SortKey::Network,
+            SortKey::Network => SortKey::Id,
+            SortKey::Id => SortKey::Cpu,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::Cpu => "cpu",
+            SortKey::Memory => "memory",
+            SortKey::Network => "network",
+            SortKey::Id => "id",
+        }
+    }
+}
+
+/// Which VMs the dashboard shows, and how
+pub struct TopOptions {
+    /// Only VMs carrying all of these labels
+    pub labels: HashMap<String, String>,
+    /// Only this VM; the dashboard closes once it is gone
+    pub vm_id: Option<String>,
+    pub sort: SortKey,
+}
+
+/// What one VM used lately
+struct Trend {
+    state: &'static str,
+    cpu: VecDeque<f64>,
+    memory: VecDeque<f64>,
+    memory_total: u64,
+    /// Bytes per second received and sent
+    network: VecDeque<f64>,
+    /// Bytes received and sent so far, and when they were read
+    network_total: Option<(u64, Instant)>,
+}
+
+impl Trend {
+    fn new() -> Self {
+        Self {
+            state: "",
+            cpu: VecDeque::new(),
+            memory: VecDeque::new(),
+            memory_total: 0,
+            network: VecDeque::new(),
+            network_total: None,
+        }
+    }
+
+    fn record(&mut self, vm: &VmInstance, metrics: &vortex::backend::VmMetrics) {
+        let now = Instant::now();
+        let transferred = metrics.network_rx + metrics.network_tx;
+        let rate = match self.network_total {
+            Some((before, at)) => {
+                transferred.saturating_sub(before) as f64 / now.duration_since(at).as_secs_f64()
+            }
+            None => 0.0,
+        };
+        self.network_total = Some((transferred, now));
+        self.state = vm.state.name();
+        self.memory_total = metrics.memory_total;
+        push(&mut self.cpu, metrics.cpu_usage);
+        push(&mut self.memory, metrics.memory_usage as f64);
+        push(&mut self.network, rate);
+    }
+
+    fn latest(values: &VecDeque<f64>) -> f64 {
+        values.back().copied().unwrap_or_default()
+    }
+}
+
+struct Dashboard {
+    vortex: Arc<VortexCore>,
+    options: TopOptions,
+    trends: HashMap<VmId, Trend>,
+    table: TableState,
+}
+
+/// Show the dashboard until the user quits it, or the VM it is limited to
+/// is gone
+pub async fn run(vortex: &Arc<VortexCore>, options: TopOptions) -> Result<()> {
+    let mut dashboard = Dashboard {
+        vortex: Arc::clone(vortex),
+        options,
+        trends: HashMap::new(),
+        table: TableState::default().with_selected(0),
+    };
+    let mut terminal = ratatui::init();
+    let result = dashboard.run(&mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+impl Dashboard {
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut next_refresh = Instant::now();
+        loop {
+            if Instant::now() >= next_refresh {
+                self.refresh().await?;
+                next_refresh = Instant::now() + REFRESH;
+                if self.options.vm_id.is_some() && self.trends.is_empty() {
+                    return Ok(());
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            while event::poll(Duration::ZERO)? {
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Char('s') => self.options.sort = self.options.sort.next(),
+                    KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+                    KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+                    _ => {}
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Sample every VM shown, forgetting those that are gone
+    async fn refresh(&mut self) -> Result<()> {
+        let query = ListQuery {
+            labels: self.options.labels.clone(),
+            ..ListQuery::default()
+        };
+        let vms: Vec<VmInstance> = self
+            .vortex
+            .list_vms()
+            .await?
+            .into_iter()
+            .filter(|vm| query.matches(vm))
+            .filter(|vm| self.options.vm_id.as_ref().map_or(true, |id| *id == vm.id))
+            .collect();
+        let sampled =
+            futures::future::join_all(vms.iter().map(|vm| vm.backend.get_metrics(vm))).await;
+
+        self.trends
+            .retain(|id, _| vms.iter().any(|vm| vm.id == *id));
+        for (vm, metrics) in vms.iter().zip(sampled) {
+            let trend = self.trends.entry(vm.id.clone()).or_insert_with(Trend::new);
+            match metrics {
+                Ok(metrics) => trend.record(vm, &metrics),
+                Err(_) => trend.state = vm.state.name(),
+            }
+        }
+        Ok(())
+    }
+
+    /// The VMs shown, in the order of the sort key
+    fn rows(&self) -> Vec<(&VmId, &Trend)> {
+        let mut rows: Vec<_> = self.trends.iter().collect();
+        match self.options.sort {
+            SortKey::Id => rows.sort_by(|a, b| a.0.cmp(b.0)),
+            key => rows.sort_by(|a, b| {
+                let value = |trend: &Trend| match key {
+                    SortKey::Memory => Trend::latest(&trend.memory),
+                    SortKey::Network => Trend::latest(&trend.network),
+                    _ => Trend::latest(&trend.cpu),
+                };
+                value(b.1).total_cmp(&value(a.1)).then(a.0.cmp(b.0))
+            }),
+        }
+        rows
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let rows = self.rows();
+        // Folded from 0.0, as an empty f64 sum is -0.0
+        let total = |values: fn(&Trend) -> &VecDeque<f64>| {
+            rows.iter()
+                .fold(0.0, |sum, (_, trend)| sum + Trend::latest(values(trend)))
+        };
+        let total_cpu = total(|trend| &trend.cpu);
+        let total_memory = total(|trend| &trend.memory);
+        let mut title = format!(
+            " vortex top — {} VM(s), {:.1}% CPU, {} memory, sorted by {}",
+            rows.len(),
+            total_cpu,
+            bytes(total_memory),
+            self.options.sort.name()
+        );
+        if !self.options.labels.is_empty() {
+            let mut labels: Vec<String> = self
+                .options
+                .labels
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            labels.sort();
+            title.push_str(&format!(", labels {}", labels.join(",")));
+        }
+        frame.render_widget(Paragraph::new(title).bold(), header);
+
+        let table_rows: Vec<Row> = rows
+            .iter()
+            .map(|(id, trend)| {
+                Row::new(vec![
+                    id.to_string(),
+                    trend.state.to_string(),
+                    format!("{:.1}%", Trend::latest(&trend.cpu)),
+                    sparkline(&trend.cpu, SPARK_WIDTH),
+                    format!(
+                        "{} / {}",
+                        bytes(Trend::latest(&trend.memory)),
+                        bytes(trend.memory_total as f64)
+                    ),
+                    sparkline(&trend.memory, SPARK_WIDTH),
+                    format!("{}/s", bytes(Trend::latest(&trend.network))),
+                    sparkline(&trend.network, SPARK_WIDTH),
+                ])
+            })
+            .collect();
+        let empty = table_rows.is_empty();
+        let table = Table::new(
+            table_rows,
+            [
+                Constraint::Min(16),
+                Constraint::Length(8),
+                Constraint::Length(7),
+                Constraint::Length(SPARK_WIDTH as u16),
+                Constraint::Length(19),
+                Constraint::Length(SPARK_WIDTH as u16),
+                Constraint::Length(11),
+                Constraint::Length(SPARK_WIDTH as u16),
+            ],
+        )
+        .header(
+            Row::new(["VM", "STATE", "CPU", "", "MEMORY", "", "NETWORK", ""])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::new().reversed())
+        .block(Block::bordered());
+        frame.render_stateful_widget(table, body, &mut self.table);
+        if empty {
+            let inner = Block::bordered().inner(body);
+            frame.render_widget(Paragraph::new("\n No VMs running").dim(), inner);
+        }
+
+        frame.render_widget(Line::from(" q quit  s sort  ↑/↓ select").dim(), footer);
+    }
+}
+
+fn push(values: &mut VecDeque<f64>, value: f64) {
+    if values.len() == TREND_SAMPLES {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+/// The last `width` of `values` as bar characters, scaled to their maximum
+fn sparkline(values: &VecDeque<f64>, width: usize) -> String {
+    let shown: Vec<f64> = values
+        .iter()
+        .skip(values.len().saturating_sub(width))
+        .copied()
+        .collect();
+    let max = shown.iter().copied().fold(0.0, f64::max);
+    shown
+        .iter()
+        .map(|value| {
+            if max <= 0.0 {
+                return BARS[0];
+            }
+            let level = (value / max * (BARS.len() - 1) as f64).round() as usize;
+            BARS[level.min(BARS.len() - 1)]
+        })
+        .collect()
+}
+
+fn bytes(value: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = value;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.0}{}", value, UNITS[unit])
+}
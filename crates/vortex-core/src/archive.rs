@@ -0,0 +1,389 @@
+//! Portable Vortex archive format.
+//!
+//! A Vortex archive is a plain tar file with the following layout:
+//!
+//! ```text
+//! manifest.json            # ArchiveManifest, always the first entry
+//! layers/<name>.tar.zst    # zstd-compressed tarball of one directory tree
+//! ```
+//!
+//! The manifest records the format version, what kind of object was archived,
+//! the originating `VmSpec` (if any) and a SHA-256 checksum for every layer.
+//! Readers accept any `format_version` up to [`ARCHIVE_FORMAT_VERSION`] so
+//! archives written by older releases remain loadable.
+//!
+//! Workspace exports (`ArchiveKind::Workspace`), snapshot exports
+//! (`ArchiveKind::Snapshot`) and saved VMs (`ArchiveKind::Vm`) are written
+//! in this format.
+
+use crate::error::{Result, VortexError};
+use crate::vm::VmSpec;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Current archive format version written by this release
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "manifest.json";
+const LAYERS_DIR: &str = "layers";
+pub(crate) const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveKind {
+    Snapshot,
+    Workspace,
+    Vm,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveLayer {
+    pub name: String,
+    /// Path of the compressed layer inside the archive
+    pub path: String,
+    pub sha256: String,
+    pub compressed_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub kind: ArchiveKind,
+    pub vortex_version: String,
+    pub created_at: DateTime<Utc>,
+    /// ID of the snapshot, workspace or VM this archive was produced from
+    pub source_id: Option<String>,
+    /// Spec of the VM the contents originated from, for provenance
+    pub spec: Option<VmSpec>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub layers: Vec<ArchiveLayer>,
+}
+
+impl ArchiveManifest {
+    pub fn new(kind: ArchiveKind) -> Self {
+        Self {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            kind,
+            vortex_version: crate::VERSION.to_string(),
+            created_at: Utc::now(),
+            source_id: None,
+            spec: None,
+            labels: HashMap::new(),
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&ArchiveLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+}
+
+/// Writer adapter that hashes everything passing through it
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            written: 0,
+        }
+    }
+
+    fn finish(self) -> (W, String, u64) {
+        (self.inner, to_hex(&self.hasher.finalize()), self.written)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn archive_error(message: impl Into<String>) -> VortexError {
+    VortexError::StorageError {
+        message: message.into(),
+    }
+}
+
+/// Write an archive to `dest` containing one compressed layer per `(name, directory)` pair.
+///
+/// Layer checksums are filled into the returned manifest.
+pub fn write_archive(
+    dest: &Path,
+    mut manifest: ArchiveManifest,
+    layers: &[(String, PathBuf)],
+) -> Result<ArchiveManifest> {
+    let staging = dest.with_extension("staging");
+    fs::create_dir_all(&staging)?;
+
+    let result = (|| {
+        manifest.format_version = ARCHIVE_FORMAT_VERSION;
+        manifest.layers.clear();
+
+        for (name, source_dir) in layers {
+            validate_layer_name(name)?;
+            if !source_dir.is_dir() {
+                return Err(archive_error(format!(
+                    "Layer source is not a directory: {}",
+                    source_dir.display()
+                )));
+            }
+
+            let blob_path = staging.join(format!("{}.tar.zst", name));
+            let blob = HashingWriter::new(File::create(&blob_path)?);
+            let encoder = zstd::Encoder::new(blob, ZSTD_LEVEL)?;
+            let mut builder = tar::Builder::new(encoder);
+            builder.follow_symlinks(false);
+            builder.append_dir_all(".", source_dir)?;
+            let encoder = builder.into_inner()?;
+            let blob = encoder.finish()?;
+            let (mut file, sha256, compressed_size) = blob.finish();
+            file.flush()?;
+
+            manifest.layers.push(ArchiveLayer {
+                name: name.clone(),
+                path: layer_path(name),
+                sha256,
+                compressed_size,
+            });
+        }
+
+        let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+        let mut archive = tar::Builder::new(File::create(dest)?);
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(manifest.created_at.timestamp().max(0) as u64);
+        header.set_cksum();
+        archive.append_data(&mut header, MANIFEST_NAME, manifest_json.as_slice())?;
+
+        for layer in &manifest.layers {
+            let blob_path = staging.join(format!("{}.tar.zst", layer.name));
+            archive.append_path_with_name(&blob_path, &layer.path)?;
+        }
+
+        archive.into_inner()?.flush()?;
+        Ok(manifest)
+    })();
+
+    let _ = fs::remove_dir_all(&staging);
+    if result.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    result
+}
+
+/// Read and version-check the manifest of an archive without touching its layers
+pub fn read_manifest(path: &Path) -> Result<ArchiveManifest> {
+    let mut archive = tar::Archive::new(File::open(path)?);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_ref() == Path::new(MANIFEST_NAME) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            return parse_manifest(&content);
+        }
+    }
+
+    Err(archive_error(format!(
+        "{} is not a Vortex archive (missing {})",
+        path.display(),
+        MANIFEST_NAME
+    )))
+}
+
+fn parse_manifest(content: &str) -> Result<ArchiveManifest> {
+    let manifest: ArchiveManifest = serde_json::from_str(content)
+        .map_err(|e| archive_error(format!("Invalid archive manifest: {}", e)))?;
+
+    if manifest.format_version == 0 || manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(archive_error(format!(
+            "Unsupported archive format version {} (this release reads up to {})",
+            manifest.format_version, ARCHIVE_FORMAT_VERSION
+        )));
+    }
+
+    // Layer names become directories under the extraction root, so a
+    // manifest is only trusted with names and paths this release writes
+    for layer in &manifest.layers {
+        validate_layer_name(&layer.name)?;
+        if layer.path != layer_path(&layer.name) {
+            return Err(archive_error(format!(
+                "Layer '{}' has unexpected path '{}'",
+                layer.name, layer.path
+            )));
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Path of the compressed layer `name` inside an archive
+fn layer_path(name: &str) -> String {
+    format!("{}/{}.tar.zst", LAYERS_DIR, name)
+}
+
+/// Verify every layer checksum against the manifest
+pub fn verify_archive(path: &Path) -> Result<ArchiveManifest> {
+    let manifest = read_manifest(path)?;
+    let mut seen = Vec::new();
+    let mut archive = tar::Archive::new(File::open(path)?);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().to_string();
+        let Some(layer) = manifest.layers.iter().find(|l| l.path == entry_path) else {
+            continue;
+        };
+
+        let mut hasher = HashingWriter::new(io::sink());
+        io::copy(&mut entry, &mut hasher)?;
+        let (_, sha256, _) = hasher.finish();
+
+        if sha256 != layer.sha256 {
+            return Err(archive_error(format!(
+                "Checksum mismatch for layer '{}': expected {}, found {}",
+                layer.name, layer.sha256, sha256
+            )));
+        }
+        seen.push(layer.name.clone());
+    }
+
+    if let Some(missing) = manifest.layers.iter().find(|l| !seen.contains(&l.name)) {
+        return Err(archive_error(format!(
+            "Archive is missing layer '{}'",
+            missing.name
+        )));
+    }
+
+    Ok(manifest)
+}
+
+/// Verify an archive and unpack each layer into `dest_root/<layer name>`
+pub fn extract_archive(path: &Path, dest_root: &Path) -> Result<ArchiveManifest> {
+    let manifest = verify_archive(path)?;
+    let mut archive = tar::Archive::new(File::open(path)?);
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().to_string();
+        let Some(layer) = manifest.layers.iter().find(|l| l.path == entry_path) else {
+            continue;
+        };
+
+        let layer_dir = dest_root.join(&layer.name);
+        fs::create_dir_all(&layer_dir)?;
+        let decoder = zstd::Decoder::new(entry)?;
+        tar::Archive::new(decoder).unpack(&layer_dir)?;
+    }
+
+    Ok(manifest)
+}
+
+fn validate_layer_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(VortexError::InvalidInput {
+            field: "layer".to_string(),
+            message: format!(
+                "Invalid layer name '{}': only alphanumeric, hyphens and underscores are allowed",
+                name
+            ),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("hello.txt"), "hello").unwrap();
+        fs::write(source.join("nested/data.bin"), [1u8, 2, 3]).unwrap();
+
+        let archive_path = temp.path().join("out.vortex");
+        let mut manifest = ArchiveManifest::new(ArchiveKind::Workspace);
+        manifest.source_id = Some("ws-1".to_string());
+        let written =
+            write_archive(&archive_path, manifest, &[("files".to_string(), source)]).unwrap();
+        assert_eq!(written.layers.len(), 1);
+
+        let read = read_manifest(&archive_path).unwrap();
+        assert_eq!(read.kind, ArchiveKind::Workspace);
+        assert_eq!(read.layers[0].sha256, written.layers[0].sha256);
+
+        let out = temp.path().join("out");
+        extract_archive(&archive_path, &out).unwrap();
+        assert_eq!(
+            fs::read_to_string(out.join("files/hello.txt")).unwrap(),
+            "hello"
+        );
+        assert_eq!(fs::read(out.join("files/nested/data.bin")).unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_rejects_future_format_version() {
+        let mut manifest = ArchiveManifest::new(ArchiveKind::Vm);
+        manifest.format_version = ARCHIVE_FORMAT_VERSION + 1;
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert!(parse_manifest(&json).is_err());
+    }
+
+    #[test]
+    fn test_rejects_layers_escaping_the_extraction_root() {
+        let layer = |name: &str, path: &str| ArchiveLayer {
+            name: name.to_string(),
+            path: path.to_string(),
+            sha256: String::new(),
+            compressed_size: 0,
+        };
+        let parse = |layers| {
+            let mut manifest = ArchiveManifest::new(ArchiveKind::Snapshot);
+            manifest.layers = layers;
+            parse_manifest(&serde_json::to_string(&manifest).unwrap())
+        };
+
+        assert!(parse(vec![layer("snapshot", "layers/snapshot.tar.zst")]).is_ok());
+        assert!(parse(vec![layer("../x", "layers/../x.tar.zst")]).is_err());
+        assert!(parse(vec![layer("snapshot", "../snapshot.tar.zst")]).is_err());
+        assert!(parse(vec![
+            layer("snapshot", "layers/snapshot.tar.zst"),
+            layer("../../..", "layers/snapshot.tar.zst"),
+        ])
+        .is_err());
+    }
+}
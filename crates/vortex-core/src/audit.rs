@@ -0,0 +1,522 @@
+//! Audit log of who ran what: which VMs were created from which image,
+//! with which host paths and commands, what was run and copied in them,
+//! and how each of those ended.
+//!
+//! [`VmManager`](crate::vm::VmManager) and the daemon's session manager
+//! hand every operation to the [`AuditLog`] they were given. Records are
+//! appended as JSON lines to a file per day under `~/.vortex/audit`, which
+//! `vortex audit` searches, or sent to the local syslog daemon (facility
+//! `authpriv`, tag `vortex`) where a central collector can pick them up.
+//! Files and their directory are readable by their owner only, and nothing
+//! in Vortex rewrites or removes them.
+
+use crate::auth::{host_user, AuthContext};
+use crate::error::{Result, VortexError};
+use crate::vm::VmSpec;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+/// `authpriv.info`
+#[cfg(unix)]
+const SYSLOG_PRIORITY: u8 = 10 << 3 | 6;
+/// Sockets syslog daemons listen on: Linux, then macOS
+#[cfg(unix)]
+const SYSLOG_SOCKETS: [&str; 2] = ["/dev/log", "/var/run/syslog"];
+
+/// Where audit records go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditTarget {
+    /// A JSON-lines file per day
+    #[default]
+    File,
+    Syslog,
+    Off,
+}
+
+/// The `[audit]` section of the config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub target: AuditTarget,
+    /// Directory of the files; `~/.vortex/audit` unless set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// What was done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    VmCreate,
+    VmClone,
+    VmRestore,
+    VmExec,
+    VmCopyIn,
+    VmCopyOut,
+    VmAttach,
+    VmStop,
+    VmRemove,
+    SessionCreate,
+    SessionStart,
+    SessionStop,
+    SessionDelete,
+    /// An action added by a later release
+    #[serde(other)]
+    Unknown,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 13] = [
+        AuditAction::VmCreate,
+        AuditAction::VmClone,
+        AuditAction::VmRestore,
+        AuditAction::VmExec,
+        AuditAction::VmCopyIn,
+        AuditAction::VmCopyOut,
+        AuditAction::VmAttach,
+        AuditAction::VmStop,
+        AuditAction::VmRemove,
+        AuditAction::SessionCreate,
+        AuditAction::SessionStart,
+        AuditAction::SessionStop,
+        AuditAction::SessionDelete,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AuditAction::VmCreate => "vm_create",
+            AuditAction::VmClone => "vm_clone",
+            AuditAction::VmRestore => "vm_restore",
+            AuditAction::VmExec => "vm_exec",
+            AuditAction::VmCopyIn => "vm_copy_in",
+            AuditAction::VmCopyOut => "vm_copy_out",
+            AuditAction::VmAttach => "vm_attach",
+            AuditAction::VmStop => "vm_stop",
+            AuditAction::VmRemove => "vm_remove",
+            AuditAction::SessionCreate => "session_create",
+            AuditAction::SessionStart => "session_start",
+            AuditAction::SessionStop => "session_stop",
+            AuditAction::SessionDelete => "session_delete",
+            AuditAction::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = AuditAction::ALL.iter().map(|a| a.name()).collect();
+                VortexError::InvalidInput {
+                    field: "action".to_string(),
+                    message: format!("Unknown action '{}': use one of {}", s, names.join(", ")),
+                }
+            })
+    }
+}
+
+/// How an operation ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Succeeded,
+    Failed { error: String },
+}
+
+/// One operation, as the audit log keeps it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// Who the operation was done for, as authenticated
+    pub user: String,
+    /// Account on the host that ran it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_user: Option<String>,
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vm_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Host paths the VM was given or files were copied from or to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// Exit code of a command run in the VM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(flatten)]
+    pub outcome: AuditOutcome,
+}
+
+impl AuditRecord {
+    /// A record of `action`, to be filled in and handed to
+    /// [`AuditLog::record`] once the operation is over
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            user: String::new(),
+            host_user: None,
+            action,
+            vm_id: None,
+            session_id: None,
+            image: None,
+            paths: Vec::new(),
+            command: Vec::new(),
+            exit_code: None,
+            outcome: AuditOutcome::Succeeded,
+        }
+    }
+
+    pub fn with_vm(mut self, vm_id: &str) -> Self {
+        self.vm_id = Some(vm_id.to_string());
+        self
+    }
+
+    pub fn with_session(mut self, session_id: &str) -> Self {
+        self.session_id = Some(session_id.to_string());
+        self
+    }
+
+    /// The image, host volumes and command of `spec`, and the session it
+    /// belongs to
+    pub fn with_spec(mut self, spec: &VmSpec) -> Self {
+        self.image = Some(spec.image.clone());
+        let mut paths: Vec<String> = spec
+            .volumes
+            .keys()
+            .map(|path| path.display().to_string())
+            .collect();
+        paths.sort();
+        self.paths = paths;
+        self.command = spec.command.iter().cloned().collect();
+        if let Some(session_id) = spec.labels.get(crate::ids::LABEL_SESSION_ID) {
+            self.session_id = Some(session_id.clone());
+        }
+        self
+    }
+
+    pub fn with_paths<I: IntoIterator<Item = String>>(mut self, paths: I) -> Self {
+        self.paths = paths.into_iter().collect();
+        self
+    }
+
+    pub fn with_command(mut self, command: &[String]) -> Self {
+        self.command = command.to_vec();
+        self
+    }
+
+    pub fn with_exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = Some(exit_code);
+        self
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, AuditOutcome::Failed { .. })
+            || self.exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+/// Which records `AuditLog::query` returns
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    /// Done for this user or by this host account
+    pub user: Option<String>,
+    pub vm_id: Option<String>,
+    pub session_id: Option<String>,
+    pub action: Option<AuditAction>,
+    pub since: Option<DateTime<Utc>>,
+    /// Only operations that failed or commands that exited non-zero
+    pub failed_only: bool,
+    /// Only the most recent this many
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.user.as_ref().map_or(true, |user| {
+            record.user == *user || record.host_user.as_ref() == Some(user)
+        }) && self
+            .vm_id
+            .as_ref()
+            .map_or(true, |id| record.vm_id.as_ref() == Some(id))
+            && self
+                .session_id
+                .as_ref()
+                .map_or(true, |id| record.session_id.as_ref() == Some(id))
+            && self.action.map_or(true, |action| record.action == action)
+            && self.since.map_or(true, |since| record.timestamp >= since)
+            && (!self.failed_only || record.failed())
+    }
+}
+
+enum Sink {
+    /// Directory of the daily files
+    Files(PathBuf),
+    Syslog,
+}
+
+/// Append-only log of the operations done for one user
+pub struct AuditLog {
+    sink: Sink,
+    user: Mutex<String>,
+    host_user: Option<String>,
+}
+
+impl AuditLog {
+    /// A log in files under `dir`
+    pub fn at(dir: PathBuf) -> Self {
+        Self::with_sink(Sink::Files(dir))
+    }
+
+    /// A log sent to the local syslog daemon
+    pub fn syslog() -> Self {
+        Self::with_sink(Sink::Syslog)
+    }
+
+    fn with_sink(sink: Sink) -> Self {
+        Self {
+            sink,
+            user: Mutex::new(AuthContext::local().user_id),
+            host_user: host_user(),
+        }
+    }
+
+    /// The log `config` asks for; `None` when auditing is off
+    pub fn from_config(config: &AuditConfig) -> Result<Option<Self>> {
+        match config.target {
+            AuditTarget::Off => Ok(None),
+            AuditTarget::Syslog => Ok(Some(Self::syslog())),
+            AuditTarget::File => {
+                let dir = match &config.dir {
+                    Some(dir) => dir.clone(),
+                    None => dirs::home_dir()
+                        .ok_or_else(|| VortexError::ConfigError {
+                            message: "Could not determine home directory".to_string(),
+                        })?
+                        .join(".vortex")
+                        .join("audit"),
+                };
+                Ok(Some(Self::at(dir)))
+            }
+        }
+    }
+
+    /// Record operations as done for `user` from now on
+    pub fn set_user(&self, user: &str) {
+        *self.user.lock().unwrap_or_else(|e| e.into_inner()) = user.to_string();
+    }
+
+    /// Append `record` of an operation that ended in `result`. The
+    /// operation is not failed over it: a record that cannot be written is
+    /// only warned about.
+    pub fn record<T>(&self, mut record: AuditRecord, result: &Result<T>) {
+        record.user = self.user.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        record.host_user = self.host_user.clone();
+        if let Err(e) = result {
+            record.outcome = AuditOutcome::Failed {
+                error: e.to_string(),
+            };
+        }
+        if let Err(e) = self.append(&record) {
+            tracing::warn!(
+                "Could not write the audit record of {}: {}",
+                record.action,
+                e
+            );
+        }
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        let line = serde_json::to_string(record)?;
+        match &self.sink {
+            Sink::Files(dir) => {
+                if !dir.exists() {
+                    fs::create_dir_all(dir)?;
+                    #[cfg(unix)]
+                    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+                }
+                let path = dir.join(format!("{}.jsonl", record.timestamp.format("%Y-%m-%d")));
+                let mut options = fs::OpenOptions::new();
+                options.create(true).append(true);
+                #[cfg(unix)]
+                options.mode(0o600);
+                // One write per record, so concurrent writers do not interleave
+                options
+                    .open(path)?
+                    .write_all(format!("{}\n", line).as_bytes())?;
+                Ok(())
+            }
+            Sink::Syslog => send_to_syslog(&line),
+        }
+    }
+
+    /// Records matching `query`, oldest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let Sink::Files(dir) = &self.sink else {
+            return Err(VortexError::ConfigError {
+                message: "The audit log goes to syslog; search it there, e.g. with \
+                          `journalctl -t vortex`"
+                    .to_string(),
+            });
+        };
+        let mut days: Vec<(NaiveDate, PathBuf)> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let day = path.file_name()?.to_str()?.strip_suffix(".jsonl")?;
+                    Some((day.parse().ok()?, path))
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        days.sort();
+        if let Some(since) = query.since {
+            days.retain(|(day, _)| *day >= since.date_naive());
+        }
+
+        let mut records = Vec::new();
+        for (_, path) in days {
+            for line in fs::read_to_string(path)?.lines() {
+                // A torn last line of a crashed writer is skipped
+                let Ok(record) = serde_json::from_str::<AuditRecord>(line) else {
+                    continue;
+                };
+                if query.matches(&record) {
+                    records.push(record);
+                }
+            }
+        }
+        if let Some(limit) = query.limit {
+            records.drain(..records.len().saturating_sub(limit));
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(unix)]
+fn send_to_syslog(line: &str) -> Result<()> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    let message = format!(
+        "<{}>vortex[{}]: {}",
+        SYSLOG_PRIORITY,
+        std::process::id(),
+        line
+    );
+    let mut last_error = None;
+    for path in SYSLOG_SOCKETS {
+        match socket.send_to(message.as_bytes(), path) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(VortexError::StorageError {
+        message: format!(
+            "No syslog daemon to send to: {}",
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        ),
+    })
+}
+
+#[cfg(not(unix))]
+fn send_to_syslog(_line: &str) -> Result<()> {
+    Err(VortexError::ConfigError {
+        message: "Syslog is only available on Unix; set [audit] target = \"file\"".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_are_appended_and_queried() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::at(dir.path().join("audit"));
+        log.set_user("ci");
+
+        let mut spec = VmSpec {
+            image: "alpine:latest".to_string(),
+            command: Some("make test".to_string()),
+            ..VmSpec::default()
+        };
+        spec.volumes
+            .insert(PathBuf::from("/src/app"), PathBuf::from("/workspace"));
+        let created = AuditRecord::new(AuditAction::VmCreate)
+            .with_vm("vortex-1")
+            .with_spec(&spec);
+        log.record(created, &Ok(()));
+        let exec = AuditRecord::new(AuditAction::VmExec)
+            .with_vm("vortex-1")
+            .with_command(&["false".to_string()])
+            .with_exit_code(1);
+        log.record(exec, &Ok(()));
+        let failed: Result<()> = Err(VortexError::VmError {
+            message: "VM vortex-2 not found".to_string(),
+        });
+        log.record(
+            AuditRecord::new(AuditAction::VmStop).with_vm("vortex-2"),
+            &failed,
+        );
+
+        let all = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].user, "ci");
+        assert_eq!(all[0].image.as_deref(), Some("alpine:latest"));
+        assert_eq!(all[0].paths, ["/src/app"]);
+        assert_eq!(all[0].command, ["make test"]);
+
+        let failures = log
+            .query(&AuditQuery {
+                failed_only: true,
+                ..AuditQuery::default()
+            })
+            .unwrap();
+        let actions: Vec<AuditAction> = failures.iter().map(|r| r.action).collect();
+        assert_eq!(actions, [AuditAction::VmExec, AuditAction::VmStop]);
+        let latest = log
+            .query(&AuditQuery {
+                vm_id: Some("vortex-1".to_string()),
+                limit: Some(1),
+                ..AuditQuery::default()
+            })
+            .unwrap();
+        assert_eq!(latest[0].action, AuditAction::VmExec);
+        assert!(log
+            .query(&AuditQuery {
+                user: Some("someone-else".to_string()),
+                ..AuditQuery::default()
+            })
+            .unwrap()
+            .is_empty());
+
+        // Fields of later releases are ignored, actions read as unknown
+        let line = r#"{"timestamp":"2026-01-01T00:00:00Z","user":"ci","action":"vm_migrate","region":"eu","outcome":"succeeded"}"#;
+        let record: AuditRecord = serde_json::from_str(line).unwrap();
+        assert_eq!(record.action, AuditAction::Unknown);
+    }
+}
@@ -0,0 +1,603 @@
+use crate::config::get_config_path;
+use crate::error::{Result, VortexError};
+use crate::oidc::OidcConfig;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// Variable holding the API token Vortex authenticates with, when `[auth]`
+/// configures tokens or single sign-on
+pub const TOKEN_ENV: &str = "VORTEX_TOKEN";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub email: Option<String>,
+    pub roles: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    VmCreate,
+    VmRead,
+    VmUpdate,
+    VmDelete,
+    SnapshotCreate,
+    SnapshotRestore,
+    NetworkManage,
+    StorageManage,
+    MetricsRead,
+    WorkspaceManage,
+    AdminAll,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthToken {
+    pub token: String,
+    pub user_id: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub permissions: Vec<Permission>,
+}
+
+/// Who operations are done for, and what they may do. The managers check
+/// it before each operation that needs a permission.
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    pub user_id: String,
+    permissions: Vec<Permission>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl AuthContext {
+    /// The user running Vortex on their own machine, allowed everything.
+    /// They are known by their account on the host.
+    pub fn local() -> Self {
+        Self {
+            user_id: host_user().unwrap_or_else(|| "local".to_string()),
+            permissions: vec![Permission::AdminAll],
+            expires_at: None,
+        }
+    }
+
+    /// Someone who did not sign in, allowed nothing
+    pub fn anonymous() -> Self {
+        Self {
+            user_id: "anonymous".to_string(),
+            permissions: Vec::new(),
+            expires_at: None,
+        }
+    }
+
+    /// The context of whoever `credentials` belong to, as `provider` sees them
+    pub async fn authenticate(
+        provider: &dyn AuthProvider,
+        credentials: &AuthCredentials,
+    ) -> Result<Self> {
+        Ok(provider.authenticate(credentials).await?.into())
+    }
+
+    /// Whether `permission` is granted; `AdminAll` grants everything
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.permissions
+            .iter()
+            .any(|p| *p == permission || *p == Permission::AdminAll)
+    }
+
+    /// Fail unless `permission` is granted and the sign-in is still valid
+    pub fn require(&self, permission: Permission) -> Result<()> {
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+        {
+            return Err(VortexError::AuthError {
+                message: format!("The credentials of {} expired", self.user_id),
+            });
+        }
+        if !self.allows(permission) {
+            return Err(VortexError::PermissionDenied {
+                action: format!("{} lacks the {:?} permission", self.user_id, permission),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Name of the host account running this process, as its environment
+/// gives it
+pub fn host_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+}
+
+impl From<AuthToken> for AuthContext {
+    fn from(token: AuthToken) -> Self {
+        Self {
+            user_id: token.user_id,
+            permissions: token.permissions,
+            expires_at: Some(token.expires_at),
+        }
+    }
+}
+
+impl From<User> for AuthContext {
+    fn from(user: User) -> Self {
+        Self {
+            user_id: user.id,
+            permissions: user.permissions,
+            expires_at: None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    async fn authenticate(&self, credentials: &AuthCredentials) -> Result<AuthToken>;
+    async fn authorize(&self, token: &str, permission: Permission) -> Result<bool>;
+    async fn get_user(&self, user_id: &str) -> Result<Option<User>>;
+    async fn refresh_token(&self, token: &str) -> Result<AuthToken>;
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthCredentials {
+    UsernamePassword { username: String, password: String },
+    Token { token: String },
+    ApiKey { key: String },
+}
+
+// No-op auth provider for development
+// WARNING: This provider is INSECURE and should only be used for local development.
+// Before using in any shared environment, implement a proper AuthProvider.
+pub struct NoOpAuthProvider;
+
+#[async_trait]
+impl AuthProvider for NoOpAuthProvider {
+    async fn authenticate(&self, credentials: &AuthCredentials) -> Result<AuthToken> {
+        // Require explicit password for NoOpAuthProvider
+        // This prevents accidental use without credentials
+        match credentials {
+            AuthCredentials::UsernamePassword { username, password } => {
+                if password.is_empty() {
+                    return Err(VortexError::AuthError {
+                        message: "NoOpAuthProvider requires a password for authentication. \
+                                  For development, use any non-empty password. \
+                                  For production, implement a proper AuthProvider."
+                            .to_string(),
+                    });
+                }
+                Ok(AuthToken {
+                    token: format!("dev-token-{}", username),
+                    user_id: username.clone(),
+                    expires_at: chrono::Utc::now() + chrono::Duration::hours(24),
+                    permissions: vec![Permission::AdminAll],
+                })
+            }
+            AuthCredentials::Token { token } => {
+                if token.is_empty() {
+                    return Err(VortexError::AuthError {
+                        message: "Token authentication requires a non-empty token. \
+                                  For NoOpAuthProvider, use the 'username:password' format instead."
+                            .to_string(),
+                    });
+                }
+                Ok(AuthToken {
+                    token: token.clone(),
+                    user_id: format!("token-user-{}", token.chars().take(8).collect::<String>()),
+                    expires_at: chrono::Utc::now() + chrono::Duration::hours(24),
+                    permissions: vec![Permission::AdminAll],
+                })
+            }
+            AuthCredentials::ApiKey { key } => {
+                if key.is_empty() {
+                    return Err(VortexError::AuthError {
+                        message: "API key authentication requires a non-empty key.".to_string(),
+                    });
+                }
+                Ok(AuthToken {
+                    token: format!("api-token-{}", key),
+                    user_id: format!("api-user-{}", key.chars().take(8).collect::<String>()),
+                    expires_at: chrono::Utc::now() + chrono::Duration::hours(24),
+                    permissions: vec![Permission::AdminAll],
+                })
+            }
+        }
+    }
+
+    async fn authorize(&self, _token: &str, _permission: Permission) -> Result<bool> {
+        Ok(true) // Allow all in development - token validation happens in authenticate
+    }
+
+    async fn get_user(&self, user_id: &str) -> Result<Option<User>> {
+        Ok(Some(User {
+            id: user_id.to_string(),
+            username: "developer".to_string(),
+            email: Some("dev@vortex.local".to_string()),
+            roles: vec!["admin".to_string()],
+            permissions: vec![Permission::AdminAll],
+        }))
+    }
+
+    async fn refresh_token(&self, token: &str) -> Result<AuthToken> {
+        self.authenticate(&AuthCredentials::Token {
+            token: token.to_string(),
+        })
+        .await
+    }
+}
+
+/// The `[auth]` section of the config: API tokens the daemon and API accept
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<ApiToken>,
+    /// TOML file with more `[[tokens]]`, re-read when it changes so tokens
+    /// can be added and revoked without a restart. Relative to the config
+    /// directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_file: Option<PathBuf>,
+    /// Single sign-on through an OpenID Connect issuer, used instead of
+    /// the tokens when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oidc: Option<OidcConfig>,
+    /// Host accounts besides the daemon's own that may use its socket.
+    /// Each gets the permissions of the provider's user of the same name,
+    /// or of a token it authenticates with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peer_users: Vec<String>,
+    /// Group the daemon's socket is given when `peer_users` is set, which
+    /// the listed accounts must belong to. The socket keeps the group it is
+    /// created with, the daemon account's, when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_group: Option<String>,
+}
+
+impl AuthConfig {
+    /// Whether any tokens are configured, so access needs one
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || self.tokens_file.is_some()
+    }
+}
+
+/// An API token and what it may do
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Who the token is for, reported as its user
+    pub name: String,
+    /// The token itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Hex SHA-256 of the token, to keep it out of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_sha256: Option<String>,
+    pub permissions: Vec<Permission>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ApiToken {
+    /// Hex SHA-256 the token is looked up by
+    fn digest(&self) -> Result<String> {
+        match (&self.token, &self.token_sha256) {
+            (Some(token), None) if !token.is_empty() => Ok(token_digest(token)),
+            (None, Some(digest))
+                if digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                Ok(digest.to_ascii_lowercase())
+            }
+            _ => Err(VortexError::AuthError {
+                message: format!(
+                    "Token '{}' needs a token or the 64 hex digits of its token_sha256",
+                    self.name
+                ),
+            }),
+        }
+    }
+
+    /// Whether the token grants `permission`; `AdminAll` grants everything
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.permissions
+            .iter()
+            .any(|p| *p == permission || *p == Permission::AdminAll)
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+    }
+}
+
+fn token_digest(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[derive(Deserialize)]
+struct TokenFile {
+    #[serde(default)]
+    tokens: Vec<ApiToken>,
+}
+
+/// Tokens by digest: those configured, and those of the file as of when
+/// it was last read
+struct TokenSet {
+    configured: HashMap<String, ApiToken>,
+    from_file: HashMap<String, ApiToken>,
+    file: Option<PathBuf>,
+    file_modified: Option<SystemTime>,
+}
+
+// Accepts API tokens given in the config or a tokens file, each limited to
+// its permissions. Tokens are compared by their SHA-256, never as given.
+pub struct TokenAuthProvider {
+    tokens: RwLock<TokenSet>,
+}
+
+impl TokenAuthProvider {
+    /// A provider accepting `tokens` only
+    pub fn new(tokens: Vec<ApiToken>) -> Result<Self> {
+        Self::with_file(tokens, None)
+    }
+
+    /// A provider for the `[auth]` section of the config, or `None` when
+    /// it configures no tokens
+    pub fn from_config(config: &AuthConfig) -> Result<Option<Self>> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        let file = config.tokens_file.as_ref().map(|file| {
+            match get_config_path().ok().as_deref().and_then(Path::parent) {
+                Some(dir) if file.is_relative() => dir.join(file),
+                _ => file.clone(),
+            }
+        });
+        Self::with_file(config.tokens.clone(), file).map(Some)
+    }
+
+    fn with_file(tokens: Vec<ApiToken>, file: Option<PathBuf>) -> Result<Self> {
+        let mut set = TokenSet {
+            configured: by_digest(tokens)?,
+            from_file: HashMap::new(),
+            file,
+            file_modified: None,
+        };
+        reload(&mut set)?;
+        Ok(Self {
+            tokens: RwLock::new(set),
+        })
+    }
+
+    /// The valid token `token`, after picking up changes to the tokens file
+    fn lookup(&self, token: &str) -> Result<ApiToken> {
+        let needs_reload = {
+            let set = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+            set.file.is_some() && modified(set.file.as_deref()) != set.file_modified
+        };
+        if needs_reload {
+            let mut set = self.tokens.write().unwrap_or_else(|e| e.into_inner());
+            // A broken edit keeps the tokens read before it
+            if let Err(e) = reload(&mut set) {
+                tracing::warn!("Keeping the previous API tokens: {}", e);
+            }
+        }
+
+        let digest = token_digest(token);
+        let set = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+        let found = set
+            .configured
+            .get(&digest)
+            .or_else(|| set.from_file.get(&digest))
+            .filter(|found| !found.is_expired());
+        found.cloned().ok_or_else(|| VortexError::AuthError {
+            message: "Invalid or expired API token".to_string(),
+        })
+    }
+}
+
+fn by_digest(tokens: Vec<ApiToken>) -> Result<HashMap<String, ApiToken>> {
+    tokens
+        .into_iter()
+        .map(|token| Ok((token.digest()?, token)))
+        .collect()
+}
+
+fn modified(path: Option<&Path>) -> Option<SystemTime> {
+    std::fs::metadata(path?).and_then(|m| m.modified()).ok()
+}
+
+/// Read the tokens file of `set` again
+fn reload(set: &mut TokenSet) -> Result<()> {
+    let Some(path) = &set.file else {
+        return Ok(());
+    };
+    let modified = modified(Some(path));
+    let content = std::fs::read_to_string(path).map_err(|e| VortexError::AuthError {
+        message: format!("Cannot read tokens file {}: {}", path.display(), e),
+    })?;
+    warn_if_readable_by_others(path);
+    let file: TokenFile = toml::from_str(&content).map_err(|e| VortexError::AuthError {
+        message: format!("Invalid tokens file {}: {}", path.display(), e),
+    })?;
+    set.from_file = by_digest(file.tokens)?;
+    set.file_modified = modified;
+    Ok(())
+}
+
+fn warn_if_readable_by_others(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            let mode = metadata.permissions().mode();
+            if mode & 0o077 != 0 {
+                tracing::warn!(
+                    "Tokens file {} has insecure permissions (mode: {:o}). \
+                    Expected 0600 (owner read/write only).",
+                    path.display(),
+                    mode
+                );
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[async_trait]
+impl AuthProvider for TokenAuthProvider {
+    async fn authenticate(&self, credentials: &AuthCredentials) -> Result<AuthToken> {
+        let token = match credentials {
+            AuthCredentials::Token { token } => token,
+            AuthCredentials::ApiKey { key } => key,
+            AuthCredentials::UsernamePassword { .. } => {
+                return Err(VortexError::AuthError {
+                    message: "Only API tokens are accepted".to_string(),
+                })
+            }
+        };
+        let found = self.lookup(token)?;
+        Ok(AuthToken {
+            token: token.clone(),
+            user_id: found.name,
+            expires_at: found
+                .expires_at
+                .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC),
+            permissions: found.permissions,
+        })
+    }
+
+    async fn authorize(&self, token: &str, permission: Permission) -> Result<bool> {
+        Ok(self.lookup(token)?.allows(permission))
+    }
+
+    async fn get_user(&self, user_id: &str) -> Result<Option<User>> {
+        let set = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+        Ok(set
+            .configured
+            .values()
+            .chain(set.from_file.values())
+            .find(|token| token.name == user_id)
+            .map(|token| User {
+                id: token.name.clone(),
+                username: token.name.clone(),
+                email: None,
+                roles: Vec::new(),
+                permissions: token.permissions.clone(),
+            }))
+    }
+
+    async fn refresh_token(&self, token: &str) -> Result<AuthToken> {
+        // Tokens are long-lived; refreshing only checks it is still valid
+        self.authenticate(&AuthCredentials::Token {
+            token: token.to_string(),
+        })
+        .await
+    }
+}
+
+// JWT-based auth provider (stub)
+pub struct JwtAuthProvider {
+    _secret: String,
+    _users: HashMap<String, User>,
+}
+
+impl JwtAuthProvider {
+    pub fn new(secret: String) -> Self {
+        Self {
+            _secret: secret,
+            _users: HashMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for JwtAuthProvider {
+    async fn authenticate(&self, _credentials: &AuthCredentials) -> Result<AuthToken> {
+        Err(VortexError::AuthError {
+            message: "JWT auth provider not yet implemented".to_string(),
+        })
+    }
+
+    async fn authorize(&self, _token: &str, _permission: Permission) -> Result<bool> {
+        Err(VortexError::AuthError {
+            message: "JWT auth provider not yet implemented".to_string(),
+        })
+    }
+
+    async fn get_user(&self, _user_id: &str) -> Result<Option<User>> {
+        Err(VortexError::AuthError {
+            message: "JWT auth provider not yet implemented".to_string(),
+        })
+    }
+
+    async fn refresh_token(&self, _token: &str) -> Result<AuthToken> {
+        Err(VortexError::AuthError {
+            message: "JWT auth provider not yet implemented".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tokens_are_scoped_and_file_changes_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("tokens.toml");
+        let write = |content: String| std::fs::write(&file, content).unwrap();
+        write(format!(
+            "[[tokens]]\nname = \"ci\"\ntoken_sha256 = \"{}\"\npermissions = [\"VmCreate\", \"VmRead\"]\n",
+            token_digest("ci-secret")
+        ));
+        let config: AuthConfig = toml::from_str(&format!(
+            "tokens_file = {:?}\n\n[[tokens]]\nname = \"admin\"\ntoken = \"root-secret\"\npermissions = [\"AdminAll\"]\n",
+            file
+        ))
+        .unwrap();
+        let auth = TokenAuthProvider::from_config(&config).unwrap().unwrap();
+
+        assert!(auth
+            .authorize("root-secret", Permission::VmDelete)
+            .await
+            .unwrap());
+        assert!(auth
+            .authorize("ci-secret", Permission::VmCreate)
+            .await
+            .unwrap());
+        assert!(!auth
+            .authorize("ci-secret", Permission::VmDelete)
+            .await
+            .unwrap());
+        assert!(auth.authorize("guess", Permission::VmRead).await.is_err());
+        let token = auth
+            .authenticate(&AuthCredentials::ApiKey {
+                key: "ci-secret".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(token.user_id, "ci");
+
+        // Revoked by rewriting the file, with a clearly newer mtime
+        write("tokens = []\n".to_string());
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(auth
+            .authorize("ci-secret", Permission::VmRead)
+            .await
+            .is_err());
+        assert!(TokenAuthProvider::from_config(&AuthConfig::default())
+            .unwrap()
+            .is_none());
+    }
+}
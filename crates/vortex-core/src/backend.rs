@@ -0,0 +1,512 @@
+use crate::error::{Result, VortexError};
+use crate::transfer;
+use crate::vm::{GpuDevice, ShareMechanism, VmInstance, HOST_ALIAS};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+pub(crate) fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Shell line running `command` as given by `options`, for backends that
+/// execute through a guest shell
+pub fn exec_script(command: &[String], options: &ExecOptions) -> String {
+    let mut script = String::new();
+    if let Some(workdir) = &options.workdir {
+        script.push_str(&format!("cd {} && ", sh_quote(workdir)));
+    }
+    let mut env: Vec<_> = options.env.iter().collect();
+    env.sort();
+    for (key, value) in env {
+        script.push_str(&format!("{}={} ", key, sh_quote(value)));
+    }
+    let argv: Vec<String> = command.iter().map(|arg| sh_quote(arg)).collect();
+    script.push_str(&argv.join(" "));
+    script
+}
+
+/// Send `signal` (`STOP` or `CONT`) to the host processes of `vm`, for
+/// backends without a native pause
+async fn signal_vmm<B: Backend + ?Sized>(backend: &B, vm: &VmInstance, signal: &str) -> Result<()> {
+    let pids = backend.vmm_pids(vm).await?;
+    if pids.is_empty() {
+        return Err(VortexError::VmError {
+            message: format!("The {} backend cannot pause VMs", backend.name()),
+        });
+    }
+    let status = tokio::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .args(pids.iter().map(u32::to_string))
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(VortexError::VmError {
+            message: format!("Failed to send SIG{} to VM {}", signal, vm.id),
+        });
+    }
+    Ok(())
+}
+
+/// Resource use of host processes, as `ps` reports it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessStats {
+    /// Resident memory of all the processes
+    pub rss_bytes: u64,
+    /// CPU time all the processes used
+    pub cpu_seconds: f64,
+    /// Age of the oldest process
+    pub elapsed_seconds: u64,
+}
+
+impl ProcessStats {
+    /// Average CPU use since the oldest process started, where 100% is one
+    /// host CPU
+    pub fn cpu_percent(&self) -> f64 {
+        if self.elapsed_seconds == 0 {
+            return 0.0;
+        }
+        self.cpu_seconds / self.elapsed_seconds as f64 * 100.0
+    }
+}
+
+/// Stats of the host processes `pids`, or `None` when none of them runs.
+/// Goes through `ps`, so it works where there is no `/proc`.
+pub async fn process_stats(pids: &[u32]) -> Option<ProcessStats> {
+    if pids.is_empty() {
+        return None;
+    }
+    let pids: Vec<String> = pids.iter().map(u32::to_string).collect();
+    let output = tokio::process::Command::new("ps")
+        .args(["-o", "rss=,time=,etime=", "-p", &pids.join(",")])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    parse_ps_stats(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Sum the `rss time etime` lines of `ps`
+fn parse_ps_stats(output: &str) -> Option<ProcessStats> {
+    let mut stats: Option<ProcessStats> = None;
+    for line in output.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(rss), Some(time), Some(etime)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let (Ok(rss_kib), Some(cpu), Some(elapsed)) = (
+            rss.parse::<u64>(),
+            parse_ps_duration(time),
+            parse_ps_duration(etime),
+        ) else {
+            continue;
+        };
+        let total = stats.get_or_insert_with(ProcessStats::default);
+        total.rss_bytes += rss_kib * 1024;
+        total.cpu_seconds += cpu;
+        total.elapsed_seconds = total.elapsed_seconds.max(elapsed as u64);
+    }
+    stats
+}
+
+/// Seconds in a `ps` duration: `[dd-]hh:mm:ss`, or `mm:ss.cc` as macOS
+/// prints CPU time
+fn parse_ps_duration(value: &str) -> Option<f64> {
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (days.parse::<f64>().ok()?, clock),
+        None => (0.0, value),
+    };
+    let mut seconds = 0.0;
+    for part in clock.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(days * 86400.0 + seconds)
+}
+
+/// Shell prelude run before the VM's command: tmpfs mounts, the
+/// `/etc/hosts` entry for [`HOST_ALIAS`], first-boot provisioning, then the
+/// tuning profile. Empty when none of them apply.
+pub fn boot_prelude(vm: &VmInstance) -> String {
+    let mut tmpfs: Vec<_> = vm.spec.tmpfs.iter().collect();
+    tmpfs.sort();
+    let mounts: String = tmpfs
+        .into_iter()
+        .map(|(guest, size_mib)| {
+            let guest = sh_quote(&guest.display().to_string());
+            format!(
+                "mkdir -p {0}; mountpoint -q {0} || mount -t tmpfs -o size={1}m tmpfs {0}; ",
+                guest, size_mib
+            )
+        })
+        .collect();
+    let hosts = vm
+        .backend
+        .host_address()
+        .filter(|_| !vm.spec.network_disabled())
+        .map(|address| {
+            format!(
+                "grep -q ' {1}$' /etc/hosts 2>/dev/null || echo '{0} {1}' >> /etc/hosts; ",
+                address, HOST_ALIAS
+            )
+        });
+    let provision = vm.spec.provision.as_ref().map(|p| p.boot_script());
+    let tuning = vm.spec.tuning.as_ref().map(|t| t.boot_script());
+    format!(
+        "{}{}{}{}",
+        mounts,
+        hosts.unwrap_or_default(),
+        provision.unwrap_or_default(),
+        tuning.unwrap_or_default()
+    )
+}
+
+#[async_trait]
+pub trait Backend: Send + Sync + std::fmt::Debug {
+    /// Create a new VM instance
+    async fn create(&self, vm: &VmInstance) -> Result<()>;
+
+    /// Start a VM instance
+    async fn start(&self, vm: &VmInstance) -> Result<()>;
+
+    /// Stop a VM instance
+    async fn stop(&self, vm: &VmInstance) -> Result<()>;
+
+    /// Cleanup/destroy a VM instance
+    async fn cleanup(&self, vm: &VmInstance) -> Result<()>;
+
+    /// Attach to an interactive session
+    async fn attach(&self, vm: &VmInstance) -> Result<()>;
+
+    /// Get VM metrics
+    async fn get_metrics(&self, vm: &VmInstance) -> Result<VmMetrics>;
+
+    /// List all VMs managed by this backend
+    async fn list_vms(&self) -> Result<Vec<String>>;
+
+    /// Check if backend is available
+    async fn is_available(&self) -> Result<bool>;
+
+    /// Get backend name
+    fn name(&self) -> &'static str;
+
+    /// Run a command in a running VM, capturing its output
+    async fn exec(
+        &self,
+        _vm: &VmInstance,
+        _command: &[String],
+        _options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        Err(VortexError::VmError {
+            message: format!(
+                "The {} backend cannot run commands in a running VM",
+                self.name()
+            ),
+        })
+    }
+
+    /// Block until the command `start` ran in the VM finishes
+    async fn wait(&self, _vm: &VmInstance) -> Result<ExitStatus> {
+        Err(VortexError::VmError {
+            message: format!("The {} backend cannot wait for VM commands", self.name()),
+        })
+    }
+
+    /// Host processes running the VM, which the default `pause` and `resume`
+    /// stop and continue; empty when they are unknown
+    async fn vmm_pids(&self, _vm: &VmInstance) -> Result<Vec<u32>> {
+        Ok(Vec::new())
+    }
+
+    /// When the host processes of a VM found running started, for VMs this
+    /// manager did not create; `None` when it cannot tell
+    async fn started_at(&self, vm: &VmInstance) -> Option<chrono::DateTime<chrono::Utc>> {
+        let pids = self.vmm_pids(vm).await.ok()?;
+        let stats = process_stats(&pids).await?;
+        let age = chrono::Duration::try_seconds(stats.elapsed_seconds as i64)?;
+        Some(chrono::Utc::now() - age)
+    }
+
+    /// Freeze a running VM in place. Backends without native support send
+    /// SIGSTOP to the processes from `vmm_pids`.
+    async fn pause(&self, vm: &VmInstance) -> Result<()> {
+        signal_vmm(self, vm, "STOP").await
+    }
+
+    /// Continue a VM frozen by `pause`
+    async fn resume(&self, vm: &VmInstance) -> Result<()> {
+        signal_vmm(self, vm, "CONT").await
+    }
+
+    /// Change the memory (MB) and vCPUs of a running VM in place; `None`
+    /// leaves that resource as it is
+    async fn resize(
+        &self,
+        _vm: &VmInstance,
+        _memory: Option<u32>,
+        _cpus: Option<u32>,
+    ) -> Result<()> {
+        Err(VortexError::VmError {
+            message: format!("The {} backend cannot resize running VMs", self.name()),
+        })
+    }
+
+    /// Save the state of a running VM into `dir`, leaving the VM running
+    async fn snapshot(&self, _vm: &VmInstance, _dir: &Path) -> Result<()> {
+        Err(VortexError::VmError {
+            message: format!("The {} backend does not support snapshots", self.name()),
+        })
+    }
+
+    /// Create `vm` from state that `snapshot` saved into `dir` and resume it
+    async fn restore(&self, _vm: &VmInstance, _dir: &Path) -> Result<()> {
+        Err(VortexError::VmError {
+            message: format!("The {} backend does not support snapshots", self.name()),
+        })
+    }
+
+    /// Create `vm` from a copy of the disk of `source`, a running VM on this
+    /// backend. The copy boots fresh with `vm.spec`: files and installed
+    /// packages carry over, running processes do not.
+    async fn clone_vm(&self, _source: &VmInstance, _vm: &VmInstance) -> Result<()> {
+        Err(VortexError::VmError {
+            message: format!("The {} backend cannot clone VMs", self.name()),
+        })
+    }
+
+    /// Copy the host file or directory `host` to the path `guest` of a
+    /// running VM, or into it when it is a directory. The default goes
+    /// through `exec`, a small chunk at a time.
+    async fn copy_to(&self, vm: &VmInstance, host: &Path, guest: &str) -> Result<()> {
+        transfer::push(self, vm, host, guest).await
+    }
+
+    /// Copy the file or directory `guest` of a running VM to the host path
+    /// `host`, or into it when it is a directory
+    async fn copy_from(&self, vm: &VmInstance, guest: &str, host: &Path) -> Result<()> {
+        transfer::pull(self, vm, guest, host).await
+    }
+
+    /// Whether VMs can have `gpu` attached
+    fn supports_gpu(&self, _gpu: &GpuDevice) -> bool {
+        false
+    }
+
+    /// Whether volumes can be shared with `mechanism` when a spec asks for
+    /// it in `share_mechanisms`
+    fn supports_share(&self, _mechanism: ShareMechanism) -> bool {
+        false
+    }
+
+    /// Whether VMs can join the private networks of `crate::network`
+    fn supports_private_networks(&self) -> bool {
+        false
+    }
+
+    /// Whether `create` boots the guest and `start` only runs the command,
+    /// so VMs can be booted ahead of time and handed out later
+    fn supports_prewarm(&self) -> bool {
+        false
+    }
+
+    /// Whether VMs can boot with no network device (`network_config = "none"`)
+    fn supports_network_isolation(&self) -> bool {
+        false
+    }
+
+    /// Whether VMs can be confined to the egress proxy whose loopback port
+    /// is in their `vortex.egress-proxy` label, for `host-only` and `allow`
+    /// network policies
+    fn supports_egress_proxy(&self) -> bool {
+        false
+    }
+
+    /// Whether forwarded ports can listen on the host address in the spec's
+    /// `port_addresses`, IPv4 or IPv6
+    fn supports_port_addresses(&self) -> bool {
+        false
+    }
+
+    /// Address guests reach the host at, which `boot_prelude` names
+    /// [`HOST_ALIAS`] in their `/etc/hosts`; `None` when guests cannot
+    /// reach the host or the backend adds the name itself
+    fn host_address(&self) -> Option<IpAddr> {
+        None
+    }
+
+    /// Whether workloads share the host kernel instead of running in a VM
+    fn reduced_isolation(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VmMetrics {
+    pub cpu_usage: f64,
+    pub memory_usage: u64,
+    pub memory_total: u64,
+    pub disk_usage: u64,
+    pub network_rx: u64,
+    pub network_tx: u64,
+    pub uptime_seconds: u64,
+}
+
+/// How `Backend::exec` runs a command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecOptions {
+    /// Guest directory to run in
+    #[serde(default)]
+    pub workdir: Option<String>,
+    /// Variables added to the command's environment
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Give up waiting for the command after this many seconds
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Captured outcome of `Backend::exec`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+impl ExecResult {
+    /// Result of a host process that ran the command, e.g. `podman exec`
+    pub fn from_output(output: &std::process::Output) -> Self {
+        Self {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            exit_code: output.status.code().unwrap_or(-1),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// How the command of a VM ended, as returned by `Backend::wait`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitStatus {
+    /// Exit code of the command; 128 plus the signal number when a signal
+    /// killed it, as shells report it
+    pub code: i32,
+}
+
+impl ExitStatus {
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
+}
+
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Self { code: 128 + signal };
+            }
+        }
+        Self {
+            code: status.code().unwrap_or(-1),
+        }
+    }
+}
+
+pub struct BackendProvider {
+    backends: HashMap<String, Arc<dyn Backend>>,
+    preferred: Option<String>,
+}
+
+impl BackendProvider {
+    pub fn register(&mut self, name: &str, backend: Arc<dyn Backend>) {
+        if self.preferred.is_none() {
+            self.preferred = Some(name.to_string());
+        }
+        self.backends.insert(name.to_string(), backend);
+    }
+
+    pub async fn get_backend(&self, preferred_backend: Option<&str>) -> Result<Arc<dyn Backend>> {
+        // A backend asked for by name is required, not a hint
+        if let Some(name) = preferred_backend {
+            return match self.backends.get(name) {
+                Some(backend) => Ok(Arc::clone(backend)),
+                None => Err(VortexError::BackendUnavailable {
+                    backend: format!(
+                        "{} (available: {})",
+                        name,
+                        self.available().join(", ")
+                    ),
+                }),
+            };
+        }
+
+        if let Some(preferred) = &self.preferred {
+            if let Some(backend) = self.backends.get(preferred) {
+                return Ok(Arc::clone(backend));
+            }
+        }
+
+        Err(VortexError::BackendUnavailable {
+            backend: "none available".to_string(),
+        })
+    }
+
+    /// Names of the registered backends, sorted
+    pub fn available(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.backends.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn has_backends(&self) -> bool {
+        !self.backends.is_empty()
+    }
+
+    pub fn new_empty() -> Self {
+        BackendProvider {
+            backends: HashMap::new(),
+            preferred: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_named_backend_is_required() {
+        let provider = BackendProvider::new_empty();
+        let err = provider.get_backend(Some("qemu")).await.unwrap_err();
+        assert!(err.to_string().contains("qemu (available: )"));
+        assert!(provider.get_backend(None).await.is_err());
+    }
+
+    #[test]
+    fn test_ps_stats_are_summed() {
+        let output = "  10240    00:01:30     2-03:04:05\n   2048 0:02.50 10:00\n";
+        let stats = parse_ps_stats(output).unwrap();
+        assert_eq!(stats.rss_bytes, 12288 * 1024);
+        assert_eq!(stats.cpu_seconds, 92.5);
+        assert_eq!(stats.elapsed_seconds, 2 * 86400 + 3 * 3600 + 4 * 60 + 5);
+        assert!(parse_ps_stats("").is_none());
+        assert_eq!(
+            ProcessStats {
+                cpu_seconds: 30.0,
+                elapsed_seconds: 60,
+                ..Default::default()
+            }
+            .cpu_percent(),
+            50.0
+        );
+    }
+}
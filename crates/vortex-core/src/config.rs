@@ -0,0 +1,686 @@
+use crate::audit::AuditConfig;
+use crate::auth::AuthConfig;
+use crate::dotfiles::DotfilesConfig;
+use crate::error::{Result, VortexError};
+use crate::event_queue::EventQueueConfig;
+use crate::image_store;
+use crate::interpolate;
+use crate::metrics::MetricAlert;
+use crate::plugin::PluginGrants;
+use crate::pool::PoolTarget;
+use crate::process_plugin::ProcessPluginConfig;
+use crate::project::ProjectConfig;
+use crate::provision::Provision;
+use crate::rules::Rule;
+use crate::validation;
+use crate::vm::LifecycleHooks;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Use dirs crate for secure home directory detection
+use dirs::home_dir;
+
+/// Settings made for every user of the machine, under those of the user
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/vortex/config.toml";
+
+/// Sections a project's `vortex.yaml` may not set, as they decide who
+/// commands run for, what is audited and which programs run on the host
+/// for every command in the project's directory
+const MACHINE_SECTIONS: &[&str] = &["auth", "audit", "plugins", "process_plugins"];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VortexConfig {
+    pub default_backend: Option<String>,
+    #[serde(default)]
+    pub default_memory: u32,
+    #[serde(default)]
+    pub default_cpus: u32,
+    #[serde(default)]
+    pub image_aliases: HashMap<String, String>,
+    /// Registry, e.g. an internal mirror, that images of Docker Hub are
+    /// pulled from instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_registry: Option<String>,
+    #[serde(default)]
+    pub templates: HashMap<String, Template>,
+    #[serde(default)]
+    pub plugins: HashMap<String, PluginConfig>,
+    /// Plugins run as external programs, by name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub process_plugins: HashMap<String, ProcessPluginConfig>,
+    #[serde(default)]
+    pub resource_limits: GlobalResourceLimits,
+    #[serde(default)]
+    pub networking: NetworkingConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub monitoring: MonitoringConfig,
+    #[serde(default)]
+    pub dotfiles: Option<DotfilesConfig>,
+    #[serde(default)]
+    pub events: EventQueueConfig,
+    /// Automations the daemon runs on VM events
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    /// VMs the daemon keeps booted ahead of demand
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pool: Vec<PoolTarget>,
+    /// Thresholds on VM metrics to warn about
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<MetricAlert>,
+    /// API tokens the daemon and API accept
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Where the record of who ran what goes
+    #[serde(default)]
+    pub audit: AuditConfig,
+}
+
+/// A `[plugins.<name>]` section: a plugin installed from a repository, or
+/// only the settings of a plugin loaded some other way
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PluginConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub source_repo: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    /// Capabilities granted to the plugin's WebAssembly module
+    #[serde(default)]
+    pub grants: PluginGrants,
+    /// Passed to the plugin's `initialize`, in whatever shape it expects
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub settings: serde_json::Value,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            version: String::new(),
+            source_repo: String::new(),
+            description: String::new(),
+            author: String::new(),
+            grants: PluginGrants::default(),
+            settings: serde_json::Value::Null,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Template {
+    pub image: String,
+    pub memory: u32,
+    pub cpus: u32,
+    pub ports: Vec<String>,
+    pub volumes: Vec<String>,
+    pub environment: HashMap<String, String>,
+    pub command: Option<String>,
+    pub description: String,
+    pub labels: HashMap<String, String>,
+    /// Lifecycle hooks of VMs run from the template
+    #[serde(default)]
+    pub hooks: LifecycleHooks,
+    /// Health check of VMs run from the template, e.g. `tcp:5432`
+    #[serde(default)]
+    pub health_check: Option<String>,
+    /// First-boot provisioning of VMs run from the template
+    #[serde(default)]
+    pub provision: Option<Provision>,
+    /// Built-in guest tuning profile of VMs run from the template, e.g.
+    /// `database`
+    #[serde(default)]
+    pub tuning: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GlobalResourceLimits {
+    pub max_memory_per_vm: u32,
+    pub max_cpus_per_vm: u32,
+    pub max_concurrent_vms: u32,
+    pub max_total_memory: u32,
+    pub default_timeout_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NetworkingConfig {
+    pub default_network: String,
+    pub enable_inter_vm: bool,
+    pub dns_servers: Vec<String>,
+    /// Loopback port on which the daemon proxies
+    /// `http://<vm-name>.localhost:<port>` to VMs; off when unset
+    #[serde(default)]
+    pub ingress_port: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub default_volume_size: u64,
+    pub snapshot_directory: PathBuf,
+    pub cache_directory: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MonitoringConfig {
+    pub enable_metrics: bool,
+    pub metrics_interval_seconds: u64,
+    pub retention_days: u32,
+}
+
+impl Default for VortexConfig {
+    fn default() -> Self {
+        let mut image_aliases = HashMap::new();
+        image_aliases.insert(
+            "alpine".to_string(),
+            "docker.io/library/alpine:latest".to_string(),
+        );
+        image_aliases.insert(
+            "ubuntu".to_string(),
+            "docker.io/library/ubuntu:22.04".to_string(),
+        );
+        image_aliases.insert(
+            "debian".to_string(),
+            "docker.io/library/debian:bullseye".to_string(),
+        );
+        image_aliases.insert(
+            "node".to_string(),
+            "docker.io/library/node:18-alpine".to_string(),
+        );
+        image_aliases.insert(
+            "python".to_string(),
+            "docker.io/library/python:3.11-alpine".to_string(),
+        );
+        image_aliases.insert(
+            "rust".to_string(),
+            "docker.io/library/rust:alpine".to_string(),
+        );
+
+        let mut templates = HashMap::new();
+        templates.insert(
+            "dev".to_string(),
+            Template {
+                image: "ubuntu:22.04".to_string(),
+                memory: 2048,
+                cpus: 2,
+                ports: vec!["8080:80".to_string(), "3000:3000".to_string()],
+                volumes: vec![],
+                environment: HashMap::new(),
+                command: Some("bash".to_string()),
+                description: "Development environment with common ports".to_string(),
+                labels: HashMap::new(),
+                hooks: LifecycleHooks::default(),
+                health_check: None,
+                provision: None,
+                tuning: None,
+            },
+        );
+
+        templates.insert(
+            "web".to_string(),
+            Template {
+                image: "node:18-alpine".to_string(),
+                memory: 1024,
+                cpus: 1,
+                ports: vec!["3000:3000".to_string(), "8080:8080".to_string()],
+                volumes: vec![],
+                environment: HashMap::new(),
+                command: Some("sh".to_string()),
+                description: "Web development with Node.js".to_string(),
+                labels: HashMap::new(),
+                hooks: LifecycleHooks::default(),
+                health_check: None,
+                provision: None,
+                tuning: None,
+            },
+        );
+
+        templates.insert(
+            "minimal".to_string(),
+            Template {
+                image: "alpine:latest".to_string(),
+                memory: 256,
+                cpus: 1,
+                ports: vec![],
+                volumes: vec![],
+                environment: HashMap::new(),
+                command: Some("sh".to_string()),
+                description: "Minimal Alpine Linux environment".to_string(),
+                labels: HashMap::new(),
+                hooks: LifecycleHooks::default(),
+                health_check: None,
+                provision: None,
+                tuning: None,
+            },
+        );
+
+        Self {
+            default_backend: None,
+            default_memory: 512,
+            default_cpus: 1,
+            image_aliases,
+            default_registry: None,
+            templates,
+            plugins: HashMap::new(),
+            process_plugins: HashMap::new(),
+            resource_limits: GlobalResourceLimits::default(),
+            networking: NetworkingConfig::default(),
+            storage: StorageConfig::default(),
+            monitoring: MonitoringConfig::default(),
+            dotfiles: None,
+            events: EventQueueConfig::default(),
+            rules: Vec::new(),
+            pool: Vec::new(),
+            alerts: Vec::new(),
+            auth: AuthConfig::default(),
+            audit: AuditConfig::default(),
+        }
+    }
+}
+
+impl Default for GlobalResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_memory_per_vm: 8192,
+            max_cpus_per_vm: 8,
+            max_concurrent_vms: 10,
+            max_total_memory: 16384,
+            default_timeout_seconds: 3600,
+        }
+    }
+}
+
+impl Default for NetworkingConfig {
+    fn default() -> Self {
+        Self {
+            default_network: "default".to_string(),
+            enable_inter_vm: false,
+            dns_servers: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
+            ingress_port: None,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        // Use dirs crate for secure home directory detection
+        let home = match dirs::home_dir() {
+            Some(h) => h,
+            None => {
+                // Return an error if home directory cannot be determined
+                // This prevents falling back to insecure /tmp
+                tracing::warn!(
+                    "Could not determine home directory. Some functionality may be limited."
+                );
+                // Return a default base directory - this is acceptable for config storage
+                // but callers should check for None and handle appropriately
+                PathBuf::from("/tmp/vortex")
+            }
+        };
+
+        let base_dir = home.join(".vortex");
+
+        Self {
+            default_volume_size: 1024 * 1024 * 1024, // 1GB
+            snapshot_directory: base_dir.join("snapshots"),
+            cache_directory: base_dir.join("cache"),
+        }
+    }
+}
+
+impl Default for MonitoringConfig {
+    fn default() -> Self {
+        Self {
+            enable_metrics: true,
+            metrics_interval_seconds: 30,
+            retention_days: 7,
+        }
+    }
+}
+
+impl VortexConfig {
+    /// The config, with the `${VAR}` references of its values expanded
+    pub fn load() -> Result<Self> {
+        interpolate::expand_values(Self::load_raw()?)
+    }
+
+    /// The config with the layers that apply merged in order of precedence:
+    /// the defaults, the system file, the user's file and, when
+    /// `project_dir` is given, the `config` section of its `vortex.yaml`
+    ///
+    /// Tables are merged key by key; arrays and other values of a later
+    /// layer replace those of an earlier one.
+    pub fn load_layered(project_dir: Option<&Path>) -> Result<Self> {
+        let config = Self::load()?;
+        match project_dir {
+            Some(dir) => config.with_project(dir),
+            None => Ok(config),
+        }
+    }
+
+    /// The config with the `config` section of the `vortex.yaml` in `dir`
+    /// merged over it, if there is one. Its `auth`, `audit`, `plugins` and
+    /// `process_plugins` are ignored with a warning.
+    pub fn with_project(self, dir: &Path) -> Result<Self> {
+        let Some(project) = ProjectConfig::load(dir)? else {
+            return Ok(self);
+        };
+        if project.config.is_null() {
+            return Ok(self);
+        }
+        let mut layer = serde_json::to_value(&project.config)?;
+        if let Value::Object(sections) = &mut layer {
+            for name in MACHINE_SECTIONS {
+                if sections.remove(*name).is_some() {
+                    tracing::warn!(
+                        "{}: config.{} ignored; set it in the system or user config",
+                        ProjectConfig::path(dir).display(),
+                        name
+                    );
+                }
+            }
+        }
+        let mut tree = serde_json::to_value(&self)?;
+        merge(&mut tree, layer);
+        serde_json::from_value(tree).map_err(|e| VortexError::ConfigError {
+            message: format!("{}: config: {}", ProjectConfig::path(dir).display(), e),
+        })
+    }
+
+    /// The system and user files over the defaults, as written, for
+    /// changing and saving the user's settings back without writing out
+    /// what their references expand to
+    pub fn load_raw() -> Result<Self> {
+        let mut tree = system_layers()?;
+        let config_path = get_config_path()?;
+
+        match read_layer(&config_path)? {
+            Some(layer) => {
+                merge(&mut tree, layer);
+                Ok(serde_json::from_value(tree)?)
+            }
+            None => {
+                let config: VortexConfig = serde_json::from_value(tree)?;
+                config.save()?;
+                Ok(config)
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let config_path = get_config_path()?;
+
+        if let Some(parent) = config_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Only what differs from the layers below, so that changes to the
+        // system file still reach settings the user never made
+        let own = changes(serde_json::to_value(self)?, &system_layers()?);
+        let content = toml::to_string_pretty(&own).map_err(|e| VortexError::ConfigError {
+            message: format!("Failed to serialize config: {}", e),
+        })?;
+
+        // Write with secure permissions (0o600 - read/write only by owner)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::write(&config_path, &content)?;
+            std::fs::set_permissions(&config_path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        #[cfg(not(unix))]
+        {
+            std::fs::write(&config_path, content)?;
+        }
+
+        Ok(())
+    }
+
+    /// The image `image` stands for: its alias resolved, and moved to
+    /// `default_registry` when it is one of Docker Hub
+    pub fn resolve_image(&self, image: &str) -> String {
+        let image = self.image_aliases.get(image).map_or(image, String::as_str);
+        match &self.default_registry {
+            Some(registry) => image_store::with_registry(image, registry),
+            None => image.to_string(),
+        }
+    }
+
+    pub fn get_template(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    pub fn get_plugin(&self, name: &str) -> Option<&PluginConfig> {
+        self.plugins.get(name)
+    }
+
+    /// Get the configured resource limits
+    pub fn get_resource_limits(&self) -> &GlobalResourceLimits {
+        &self.resource_limits
+    }
+
+    pub fn add_plugin(&mut self, name: String, plugin: PluginConfig) {
+        self.plugins.insert(name, plugin);
+    }
+
+    pub fn remove_plugin(&mut self, name: &str) -> Option<PluginConfig> {
+        self.plugins.remove(name)
+    }
+
+    pub fn enable_plugin(&mut self, name: &str) -> bool {
+        if let Some(plugin) = self.plugins.get_mut(name) {
+            plugin.enabled = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn disable_plugin(&mut self, name: &str) -> bool {
+        if let Some(plugin) = self.plugins.get_mut(name) {
+            plugin.enabled = false;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The defaults with the system file merged over them
+fn system_layers() -> Result<Value> {
+    let mut tree = serde_json::to_value(VortexConfig::default())?;
+    if let Some(layer) = read_layer(&system_config_path())? {
+        merge(&mut tree, layer);
+    }
+    Ok(tree)
+}
+
+/// The settings the config file at `path` makes, if it exists. The file
+/// has to parse as a config on its own; keys no setting reads are warned
+/// about.
+fn read_layer(path: &Path) -> Result<Option<Value>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let (_, unknown): (VortexConfig, _) = validation::parse_toml(path, &content)?;
+    // The config is loaded several times per command
+    static WARNED: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned.insert(path.to_path_buf()) {
+        for diagnostic in unknown {
+            tracing::warn!("{}", diagnostic);
+        }
+    }
+    let layer: toml::Table = toml::from_str(&content).map_err(|e| VortexError::ConfigError {
+        message: format!("{}: {}", path.display(), e.message()),
+    })?;
+    Ok(Some(serde_json::to_value(layer)?))
+}
+
+/// Merge `layer` over `tree`: tables key by key, anything else replaced
+fn merge(tree: &mut Value, layer: Value) {
+    match (tree, layer) {
+        (Value::Object(fields), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match fields.get_mut(&key) {
+                    Some(field) => merge(field, value),
+                    None => {
+                        fields.insert(key, value);
+                    }
+                }
+            }
+        }
+        (tree, layer) => *tree = layer,
+    }
+}
+
+/// The sections of `config` that differ from `base`. Of a table such as
+/// `resource_limits` or `templates`, only the changed keys are kept, each
+/// whole.
+fn changes(config: Value, base: &Value) -> Value {
+    let mut changed = Map::new();
+    let Value::Object(sections) = config else {
+        return Value::Object(changed);
+    };
+    for (name, section) in sections {
+        let below = base.get(&name).unwrap_or(&Value::Null);
+        match section {
+            Value::Object(entries) if below.is_object() => {
+                let entries: Map<String, Value> = entries
+                    .into_iter()
+                    .filter(|(key, entry)| !entry.is_null() && below.get(key) != Some(entry))
+                    .collect();
+                if !entries.is_empty() {
+                    changed.insert(name, Value::Object(entries));
+                }
+            }
+            section if section.is_null() || section == *below => {}
+            section => {
+                changed.insert(name, section);
+            }
+        }
+    }
+    Value::Object(changed)
+}
+
+/// Where the system file is read from: [`SYSTEM_CONFIG_PATH`] unless
+/// `VORTEX_SYSTEM_CONFIG` names another file
+pub fn system_config_path() -> PathBuf {
+    std::env::var_os("VORTEX_SYSTEM_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(SYSTEM_CONFIG_PATH))
+}
+
+pub fn get_config_path() -> Result<PathBuf> {
+    // Use dirs crate for secure home directory detection
+    let home = home_dir().ok_or_else(|| VortexError::ConfigError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+
+    // Validate home directory exists and is a directory
+    if !home.exists() || !home.is_dir() {
+        return Err(VortexError::ConfigError {
+            message: format!("Invalid home directory: {}", home.display()),
+        });
+    }
+
+    // Canonicalize to prevent symlink attacks
+    let canonical_home = std::fs::canonicalize(&home).map_err(|e| VortexError::ConfigError {
+        message: format!("Failed to canonicalize home directory: {}", e),
+    })?;
+
+    Ok(canonical_home
+        .join(".config")
+        .join("vortex")
+        .join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(content: &str) -> Value {
+        let (_, unknown): (VortexConfig, _) =
+            validation::parse_toml(Path::new("config.toml"), content).unwrap();
+        assert!(unknown.is_empty());
+        serde_json::to_value(toml::from_str::<toml::Table>(content).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_layers_merge_and_save_only_their_own_settings() {
+        let mut tree = serde_json::to_value(VortexConfig::default()).unwrap();
+        merge(
+            &mut tree,
+            layer(
+                "default_memory = 1024\n\
+                 [resource_limits]\nmax_concurrent_vms = 4\n\
+                 [image_aliases]\nalpine = \"mirror.corp/alpine\"\n",
+            ),
+        );
+        let system = tree.clone();
+        merge(
+            &mut tree,
+            layer("[resource_limits]\nmax_memory_per_vm = 4096\n"),
+        );
+        let mut config: VortexConfig = serde_json::from_value(tree).unwrap();
+        assert_eq!(config.default_memory, 1024);
+        assert_eq!(config.resource_limits.max_concurrent_vms, 4);
+        assert_eq!(config.resource_limits.max_memory_per_vm, 4096);
+        assert_eq!(config.resolve_image("alpine"), "mirror.corp/alpine");
+        assert_eq!(
+            config.resolve_image("ubuntu"),
+            "docker.io/library/ubuntu:22.04"
+        );
+
+        config.add_plugin("lint".to_string(), PluginConfig::default());
+        let own = changes(serde_json::to_value(&config).unwrap(), &system);
+        let saved = toml::to_string_pretty(&own).unwrap();
+        assert_eq!(
+            layer(&saved),
+            serde_json::json!({
+                "plugins": { "lint": serde_json::to_value(PluginConfig::default()).unwrap() },
+                "resource_limits": { "max_memory_per_vm": 4096 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_projects_cannot_change_auth_audit_or_plugins() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            ProjectConfig::path(dir.path()),
+            "name: shop\n\
+             config:\n\
+             \x20 resource_limits:\n\
+             \x20   max_concurrent_vms: 8\n\
+             \x20 auth:\n\
+             \x20   peer_users: [mallory]\n\
+             \x20 process_plugins:\n\
+             \x20   backdoor:\n\
+             \x20     command: /tmp/backdoor\n",
+        )
+        .unwrap();
+        let config = VortexConfig::default().with_project(dir.path()).unwrap();
+        assert_eq!(config.resource_limits.max_concurrent_vms, 8);
+        assert!(config.auth.peer_users.is_empty());
+        assert!(config.process_plugins.is_empty());
+    }
+}
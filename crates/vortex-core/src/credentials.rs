@@ -0,0 +1,408 @@
+//! Secrets and registry credentials kept in the OS credential store.
+//!
+//! [`default_store`] picks the macOS keychain (through `security`) or the
+//! freedesktop Secret Service (through libsecret's `secret-tool`) when one is
+//! usable, and otherwise falls back to a ChaCha20-Poly1305 encrypted file in
+//! `~/.vortex/credentials/` for headless hosts. `VORTEX_CREDENTIAL_STORE`
+//! (`keychain`, `secret-service` or `file`) forces a particular store.
+//!
+//! The file store's key sits next to it with mode 0600, so it protects the
+//! secrets if the store file alone leaks (backups, dotfile sync), not
+//! against someone who can read the whole directory.
+
+use crate::error::{Result, VortexError};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Service name entries are stored under in the OS credential store
+pub const SERVICE: &str = "vortex";
+/// Environment variable that forces a particular store
+pub const STORE_ENV: &str = "VORTEX_CREDENTIAL_STORE";
+
+const KEY_FILE: &str = "key";
+const STORE_FILE: &str = "store.enc";
+const NONCE_LEN: usize = 12;
+
+/// Key for a user secret
+pub fn secret_key(name: &str) -> Result<String> {
+    validate_name("secret", name)?;
+    Ok(format!("secret/{}", name))
+}
+
+/// Key for the credentials of a container registry
+pub fn registry_key(host: &str) -> Result<String> {
+    validate_name("registry", host)?;
+    Ok(format!("registry/{}", host))
+}
+
+/// Key for the encryption key of a named volume
+pub fn volume_key(name: &str) -> Result<String> {
+    validate_name("volume", name)?;
+    Ok(format!("volume/{}", name))
+}
+
+fn validate_name(field: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 128
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    if !valid {
+        return Err(VortexError::InvalidInput {
+            field: field.to_string(),
+            message: format!(
+                "'{}' must be 1-128 characters of letters, digits, '-', '_', '.' or ':'",
+                name
+            ),
+        });
+    }
+    Ok(())
+}
+
+pub trait CredentialStore: Send + Sync {
+    /// Short name shown to users, e.g. `keychain`
+    fn name(&self) -> &'static str;
+    fn get(&self, key: &str) -> Result<Option<String>>;
+    fn set(&self, key: &str, secret: &str) -> Result<()>;
+    /// Remove `key`, returning whether it existed
+    fn delete(&self, key: &str) -> Result<bool>;
+}
+
+/// The store to use on this host
+pub fn default_store() -> Result<Box<dyn CredentialStore>> {
+    match std::env::var(STORE_ENV).ok().as_deref() {
+        Some("keychain") => return Ok(Box::new(KeychainStore)),
+        Some("secret-service") => return Ok(Box::new(SecretServiceStore)),
+        Some("file") => return Ok(Box::new(EncryptedFileStore::new(default_file_dir()?))),
+        Some(other) => {
+            return Err(VortexError::ConfigError {
+                message: format!(
+                    "Unknown {} '{}' (expected keychain, secret-service or file)",
+                    STORE_ENV, other
+                ),
+            })
+        }
+        None => {}
+    }
+
+    if cfg!(target_os = "macos") && KeychainStore::is_available() {
+        return Ok(Box::new(KeychainStore));
+    }
+    if SecretServiceStore::is_available() {
+        return Ok(Box::new(SecretServiceStore));
+    }
+    Ok(Box::new(EncryptedFileStore::new(default_file_dir()?)))
+}
+
+fn default_file_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("credentials"))
+}
+
+/// Run a credential helper, feeding `input` on stdin
+fn run_helper(cmd: &mut Command, input: Option<&str>) -> Result<std::process::Output> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| VortexError::StorageError {
+            message: format!("Failed to run credential helper: {}", e),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Some(input) = input {
+            stdin.write_all(input.as_bytes())?;
+        }
+    }
+    Ok(child.wait_with_output()?)
+}
+
+fn helper_error(action: &str, output: &std::process::Output) -> VortexError {
+    VortexError::StorageError {
+        message: format!(
+            "Credential store {} failed: {}",
+            action,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// macOS keychain through the `security` tool
+pub struct KeychainStore;
+
+/// `security` exit status when an item does not exist
+const KEYCHAIN_NOT_FOUND: i32 = 44;
+
+impl KeychainStore {
+    pub fn is_available() -> bool {
+        Command::new("security")
+            .arg("help")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+    }
+}
+
+impl CredentialStore for KeychainStore {
+    fn name(&self) -> &'static str {
+        "keychain"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let output = run_helper(
+            Command::new("security").args([
+                "find-generic-password",
+                "-s",
+                SERVICE,
+                "-a",
+                key,
+                "-w",
+            ]),
+            None,
+        )?;
+        match output.status.code() {
+            Some(0) => Ok(Some(
+                String::from_utf8_lossy(&output.stdout)
+                    .trim_end_matches('\n')
+                    .to_string(),
+            )),
+            Some(KEYCHAIN_NOT_FOUND) => Ok(None),
+            _ => Err(helper_error("lookup", &output)),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<()> {
+        // Interactive mode reads the command from stdin, keeping the secret
+        // out of the process list
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            quote(SERVICE),
+            quote(key),
+            quote(secret)
+        );
+        let output = run_helper(Command::new("security").arg("-i"), Some(&command))?;
+        if !output.status.success() || !output.stderr.is_empty() {
+            return Err(helper_error("update", &output));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let output = run_helper(
+            Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", key]),
+            None,
+        )?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(KEYCHAIN_NOT_FOUND) => Ok(false),
+            _ => Err(helper_error("delete", &output)),
+        }
+    }
+}
+
+/// freedesktop Secret Service (GNOME Keyring, KWallet) through `secret-tool`
+pub struct SecretServiceStore;
+
+impl SecretServiceStore {
+    /// Needs `secret-tool` and a session bus, which headless hosts lack
+    pub fn is_available() -> bool {
+        std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+            && Command::new("secret-tool")
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok()
+    }
+}
+
+impl CredentialStore for SecretServiceStore {
+    fn name(&self) -> &'static str {
+        "secret-service"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let output = run_helper(
+            Command::new("secret-tool").args(["lookup", "service", SERVICE, "key", key]),
+            None,
+        )?;
+        if output.status.success() {
+            return Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()));
+        }
+        // secret-tool exits 1 without a message when nothing matches
+        if output.stderr.is_empty() {
+            return Ok(None);
+        }
+        Err(helper_error("lookup", &output))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<()> {
+        let label = format!("Vortex {}", key);
+        let output = run_helper(
+            Command::new("secret-tool")
+                .args(["store", "--label", &label, "service", SERVICE, "key", key]),
+            Some(secret),
+        )?;
+        if !output.status.success() {
+            return Err(helper_error("update", &output));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let existed = self.get(key)?.is_some();
+        if existed {
+            let output = run_helper(
+                Command::new("secret-tool").args(["clear", "service", SERVICE, "key", key]),
+                None,
+            )?;
+            if !output.status.success() {
+                return Err(helper_error("delete", &output));
+            }
+        }
+        Ok(existed)
+    }
+}
+
+/// Fallback store: one encrypted JSON map with a per-host random key
+pub struct EncryptedFileStore {
+    dir: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn write_private(&self, name: &str, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.dir, fs::Permissions::from_mode(0o700))?;
+        }
+        let tmp = self.dir.join(format!(".{}.tmp", name));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&tmp)?.write_all(data)?;
+        fs::rename(&tmp, self.dir.join(name))?;
+        Ok(())
+    }
+
+    fn cipher(&self) -> Result<ChaCha20Poly1305> {
+        let path = self.dir.join(KEY_FILE);
+        let key = match fs::read(&path) {
+            Ok(key) if key.len() == 32 => key,
+            Ok(_) => {
+                return Err(VortexError::StorageError {
+                    message: format!("Credential key {} is corrupt", path.display()),
+                })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng).to_vec();
+                self.write_private(KEY_FILE, &key)?;
+                key
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>> {
+        let data = match fs::read(self.dir.join(STORE_FILE)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < NONCE_LEN {
+            return Err(VortexError::StorageError {
+                message: "Credential store is truncated".to_string(),
+            });
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| VortexError::StorageError {
+                message: "Credential store could not be decrypted (wrong key or corrupt file)"
+                    .to_string(),
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn save(&self, entries: &BTreeMap<String, String>) -> Result<()> {
+        let plaintext = serde_json::to_vec(entries)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| VortexError::StorageError {
+                message: "Failed to encrypt credential store".to_string(),
+            })?;
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        self.write_private(STORE_FILE, &data)
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted-file"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.load()?.remove(key))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<()> {
+        let mut entries = self.load()?;
+        entries.insert(key.to_string(), secret.to_string());
+        self.save(&entries)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let mut entries = self.load()?;
+        let existed = entries.remove(key).is_some();
+        if existed {
+            self.save(&entries)?;
+        }
+        Ok(existed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_file_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EncryptedFileStore::new(dir.path().to_path_buf());
+        let key = registry_key("ghcr.io").unwrap();
+
+        assert_eq!(store.get(&key).unwrap(), None);
+        store.set(&key, "user:hunter2").unwrap();
+        assert_eq!(store.get(&key).unwrap().as_deref(), Some("user:hunter2"));
+
+        let raw = fs::read(dir.path().join(STORE_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("hunter2"));
+
+        assert!(store.delete(&key).unwrap());
+        assert!(!store.delete(&key).unwrap());
+        assert!(secret_key("../x").is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Dependency caches shared between runs, for `vortex run --cache-deps`.
+//!
+//! Each package manager gets its own caches under
+//! `~/.vortex/cache/deps/<ecosystem>/<key>`, where the key is a hash of the
+//! project's lockfiles for that ecosystem. Projects with the same lockfile
+//! share one cache, and a changed lockfile gets a new cache seeded from the
+//! ecosystem's most recently used one (with reflinks where the filesystem
+//! has them), so only the dependencies that changed are downloaded. Caches
+//! are mounted under `/vortex_cache/<ecosystem>` and the package manager is
+//! pointed at them through its environment variable.
+//!
+//! Every use of a cache is recorded, so `vortex cache prune` can delete
+//! those no run needed for a while.
+
+use crate::error::{Result, VortexError};
+use crate::snapshot::dir_size;
+use crate::storage::clone_tree;
+use crate::vm::VmSpec;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Guest directory the caches are mounted under
+pub const GUEST_CACHE_ROOT: &str = "/vortex_cache";
+const USED_MARKER: &str = ".vortex-used";
+
+/// A package manager whose downloads are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecosystem {
+    Cargo,
+    Npm,
+    Pip,
+    Go,
+}
+
+impl Ecosystem {
+    pub const ALL: [Ecosystem; 4] = [
+        Ecosystem::Cargo,
+        Ecosystem::Npm,
+        Ecosystem::Pip,
+        Ecosystem::Go,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Ecosystem::Cargo => "cargo",
+            Ecosystem::Npm => "npm",
+            Ecosystem::Pip => "pip",
+            Ecosystem::Go => "go",
+        }
+    }
+
+    /// Files pinning the project's dependencies, any of which marks the
+    /// project as using this ecosystem
+    pub fn lockfiles(self) -> &'static [&'static str] {
+        match self {
+            Ecosystem::Cargo => &["Cargo.lock"],
+            Ecosystem::Npm => &[
+                "package-lock.json",
+                "npm-shrinkwrap.json",
+                "yarn.lock",
+                "pnpm-lock.yaml",
+            ],
+            Ecosystem::Pip => &["requirements.txt", "poetry.lock", "Pipfile.lock", "uv.lock"],
+            Ecosystem::Go => &["go.sum"],
+        }
+    }
+
+    /// Variable pointing the package manager at its cache
+    pub fn env_var(self) -> &'static str {
+        match self {
+            Ecosystem::Cargo => "CARGO_HOME",
+            Ecosystem::Npm => "npm_config_cache",
+            Ecosystem::Pip => "PIP_CACHE_DIR",
+            Ecosystem::Go => "GOMODCACHE",
+        }
+    }
+
+    pub fn guest_path(self) -> PathBuf {
+        Path::new(GUEST_CACHE_ROOT).join(self.name())
+    }
+}
+
+impl fmt::Display for Ecosystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Ecosystem {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ecosystem::ALL
+            .into_iter()
+            .find(|ecosystem| ecosystem.name() == s)
+            .ok_or_else(|| VortexError::InvalidInput {
+                field: "ecosystem".to_string(),
+                message: format!("Unknown ecosystem '{}': use cargo, npm, pip or go", s),
+            })
+    }
+}
+
+/// One cache of one ecosystem
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub ecosystem: Ecosystem,
+    /// Hash of the lockfiles the cache is for
+    pub key: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub last_used: DateTime<Utc>,
+}
+
+/// A cache a run mounts
+#[derive(Debug, Clone)]
+pub struct CacheMount {
+    pub ecosystem: Ecosystem,
+    pub key: String,
+    pub path: PathBuf,
+    /// Key of the cache this one was seeded from, when it is new
+    pub seeded_from: Option<String>,
+}
+
+impl CacheMount {
+    /// Mount the cache into `spec` and point the package manager at it,
+    /// unless the spec already sets its variable
+    pub fn apply(&self, spec: &mut VmSpec) {
+        let guest = self.ecosystem.guest_path();
+        spec.environment
+            .entry(self.ecosystem.env_var().to_string())
+            .or_insert_with(|| guest.display().to_string());
+        spec.volumes.insert(self.path.clone(), guest);
+    }
+}
+
+pub struct DependencyCache {
+    root: PathBuf,
+}
+
+impl DependencyCache {
+    /// The caches under `~/.vortex/cache/deps`
+    pub fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        // Caches end up in VMs, so they must not be planted by other users
+        if home.starts_with("/tmp") || home.starts_with("/var/tmp") {
+            return Err(VortexError::StorageError {
+                message: "Dependency caches cannot be in /tmp or /var/tmp; \
+                          set HOME to a secure location"
+                    .to_string(),
+            });
+        }
+        Ok(Self::at(home.join(".vortex").join("cache").join("deps")))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Ecosystems the project in `dir` uses, with the key of its lockfiles
+    pub fn detect(dir: &Path) -> Result<Vec<(Ecosystem, String)>> {
+        let mut found = Vec::new();
+        for ecosystem in Ecosystem::ALL {
+            let mut hasher = Sha256::new();
+            let mut any = false;
+            for lockfile in ecosystem.lockfiles() {
+                let path = dir.join(lockfile);
+                if !path.is_file() {
+                    continue;
+                }
+                hasher.update(lockfile.as_bytes());
+                hasher.update(fs::read(path)?);
+                any = true;
+            }
+            if any {
+                let key = format!("{:x}", hasher.finalize());
+                found.push((ecosystem, key[..16].to_string()));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Caches for the project in `dir`, created and seeded as needed
+    pub async fn prepare(&self, dir: &Path) -> Result<Vec<CacheMount>> {
+        let mut mounts = Vec::new();
+        for (ecosystem, key) in Self::detect(dir)? {
+            let path = self.root.join(ecosystem.name()).join(&key);
+            let mut seeded_from = None;
+            if !path.exists() {
+                let latest = self
+                    .entries()?
+                    .into_iter()
+                    .filter(|entry| entry.ecosystem == ecosystem)
+                    .max_by_key(|entry| entry.last_used);
+                match latest {
+                    Some(latest) => {
+                        clone_tree(&latest.path, &path).await?;
+                        seeded_from = Some(latest.key);
+                    }
+                    None => fs::create_dir_all(&path)?,
+                }
+            }
+            fs::write(path.join(USED_MARKER), Utc::now().to_rfc3339())?;
+            mounts.push(CacheMount {
+                ecosystem,
+                key,
+                path,
+                seeded_from,
+            });
+        }
+        Ok(mounts)
+    }
+
+    /// All caches, by ecosystem and most recently used first
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for ecosystem in Ecosystem::ALL {
+            let Ok(dirs) = fs::read_dir(self.root.join(ecosystem.name())) else {
+                continue;
+            };
+            for dir in dirs.flatten() {
+                let path = dir.path();
+                let Ok(used) = fs::read_to_string(path.join(USED_MARKER)) else {
+                    continue;
+                };
+                let Ok(last_used) = DateTime::parse_from_rfc3339(used.trim()) else {
+                    continue;
+                };
+                entries.push(CacheEntry {
+                    ecosystem,
+                    key: dir.file_name().to_string_lossy().into_owned(),
+                    size_bytes: dir_size(&path),
+                    path,
+                    last_used: last_used.with_timezone(&Utc),
+                });
+            }
+        }
+        entries.sort_by(|a, b| {
+            (a.ecosystem.name(), b.last_used).cmp(&(b.ecosystem.name(), a.last_used))
+        });
+        Ok(entries)
+    }
+
+    /// Delete the caches no run used within `max_unused`, and return them
+    pub fn prune(&self, max_unused: chrono::Duration) -> Result<Vec<CacheEntry>> {
+        let cutoff = Utc::now() - max_unused;
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            if entry.last_used < cutoff {
+                fs::remove_dir_all(&entry.path)?;
+                removed.push(entry);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_caches_are_keyed_by_lockfile_and_seeded() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DependencyCache::at(dir.path().join("deps"));
+        let project = dir.path().join("project");
+        let other = dir.path().join("other");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        fs::write(project.join("Cargo.lock"), "serde 1.0.200").unwrap();
+        fs::write(project.join("yarn.lock"), "left-pad@1").unwrap();
+        fs::write(other.join("Cargo.lock"), "serde 1.0.200").unwrap();
+
+        let mounts = cache.prepare(&project).await.unwrap();
+        let ecosystems: Vec<Ecosystem> = mounts.iter().map(|m| m.ecosystem).collect();
+        assert_eq!(ecosystems, [Ecosystem::Cargo, Ecosystem::Npm]);
+        fs::write(mounts[0].path.join("registry"), "crates").unwrap();
+
+        // The same lockfile elsewhere shares the cache
+        let shared = cache.prepare(&other).await.unwrap();
+        assert_eq!(shared[0].path, mounts[0].path);
+        assert!(shared[0].seeded_from.is_none());
+
+        // A changed lockfile starts from the last cache
+        fs::write(other.join("Cargo.lock"), "serde 1.0.201").unwrap();
+        let bumped = cache.prepare(&other).await.unwrap();
+        assert_ne!(bumped[0].key, mounts[0].key);
+        assert_eq!(
+            bumped[0].seeded_from.as_deref(),
+            Some(mounts[0].key.as_str())
+        );
+        assert!(bumped[0].path.join("registry").exists());
+
+        let mut spec = VmSpec::default();
+        bumped[0].apply(&mut spec);
+        assert_eq!(spec.environment["CARGO_HOME"], "/vortex_cache/cargo");
+
+        assert_eq!(cache.entries().unwrap().len(), 3);
+        assert!(cache.prune(chrono::Duration::hours(1)).unwrap().is_empty());
+        assert_eq!(cache.prune(chrono::Duration::zero()).unwrap().len(), 3);
+        assert!(cache.entries().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! The dev environment a project has chosen, saved as `.vortex-dev.yaml`.
+//!
+//! `vortex dev` with no template asks once, in a directory Vortex cannot
+//! classify, which template, resources and ports the project needs, and
+//! writes the answers here. Later runs read the file instead of asking:
+//!
+//! ```yaml
+//! template: rust
+//! memory: 4096
+//! cpus: 4
+//! ports: ["8080:8080"]
+//! ```
+
+use crate::error::{Result, VortexError};
+use crate::templates::DevOverrides;
+use crate::validation;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const DEV_PROJECT_FILE: &str = ".vortex-dev.yaml";
+
+/// A named memory/CPU size offered by the template picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourcePreset {
+    pub name: &'static str,
+    pub memory: u32,
+    pub cpus: u32,
+}
+
+pub const RESOURCE_PRESETS: [ResourcePreset; 3] = [
+    ResourcePreset {
+        name: "small",
+        memory: 1024,
+        cpus: 1,
+    },
+    ResourcePreset {
+        name: "standard",
+        memory: 2048,
+        cpus: 2,
+    },
+    ResourcePreset {
+        name: "large",
+        memory: 4096,
+        cpus: 4,
+    },
+];
+
+/// Index of the preset matching the template defaults
+pub const DEFAULT_PRESET: usize = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DevProject {
+    pub template: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+    /// `host:guest` mappings, replacing the template's ports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+}
+
+impl DevProject {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(DEV_PROJECT_FILE)
+    }
+
+    /// The saved setup in `dir`, if the project has one
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(dir);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (project, _) = validation::parse_yaml(&path, &content)?;
+        Ok(Some(project))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        let path = Self::path(dir);
+        let yaml = serde_yaml::to_string(self).map_err(|e| VortexError::ConfigError {
+            message: format!("Failed to serialize {}: {}", DEV_PROJECT_FILE, e),
+        })?;
+        std::fs::write(
+            &path,
+            format!("# Written by `vortex dev`; edit freely\n{}", yaml),
+        )?;
+        Ok(path)
+    }
+
+    /// Adjustments this setup makes to the template's VM spec
+    pub fn overrides(&self) -> Result<DevOverrides> {
+        let ports = match &self.ports {
+            Some(ports) => Some(
+                ports
+                    .iter()
+                    .map(|port| parse_port(port))
+                    .collect::<Result<_>>()?,
+            ),
+            None => None,
+        };
+        Ok(DevOverrides {
+            memory: self.memory,
+            cpus: self.cpus,
+            ports,
+            backend: self.backend.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Parse a `host:guest` port mapping
+pub fn parse_port(mapping: &str) -> Result<(u16, u16)> {
+    let invalid = || VortexError::InvalidInput {
+        field: "ports".to_string(),
+        message: format!("Invalid port mapping '{}', expected host:guest", mapping),
+    };
+    let (host, guest) = mapping.trim().split_once(':').ok_or_else(invalid)?;
+    Ok((
+        host.parse().map_err(|_| invalid())?,
+        guest.parse().map_err(|_| invalid())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_setup_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(DevProject::load(dir.path()).unwrap(), None);
+
+        let preset = RESOURCE_PRESETS[2];
+        let project = DevProject {
+            template: "rust".to_string(),
+            memory: Some(preset.memory),
+            cpus: Some(preset.cpus),
+            ports: Some(vec!["8080:80".to_string()]),
+            backend: None,
+        };
+        project.save(dir.path()).unwrap();
+
+        let loaded = DevProject::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded, project);
+        let overrides = loaded.overrides().unwrap();
+        assert_eq!(overrides.memory, Some(4096));
+        assert_eq!(overrides.ports, Some([(8080, 80)].into_iter().collect()));
+        assert!(parse_port("8080").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Failure bundles for guest commands.
+//!
+//! `vortex run` wraps the guest command so that a non-zero exit captures the
+//! last lines of output, the environment (with secrets redacted), the dmesg
+//! tail and df/free snapshots into a host directory mounted into the guest.
+//! Bundles live in `~/.vortex/diagnostics/<run-id>` next to a copy of the run
+//! record, so they outlive the run's temporary directory and can be read
+//! later with `vortex inspect <run-id> --failure`.
+
+use crate::error::{Result, VortexError};
+use crate::run_dir::RunRecord;
+use std::fs;
+use std::path::PathBuf;
+
+const RECORD_NAME: &str = "run.json";
+const EXIT_CODE: &str = "exit_code";
+/// Output lines kept in the bundle
+const OUTPUT_TAIL_LINES: usize = 200;
+const DMESG_TAIL_LINES: usize = 100;
+/// Guest shell variable holding the wrapped command's exit status
+const STATUS_VAR: &str = "vortex_status";
+/// Files of a bundle, in display order
+pub const BUNDLE_FILES: &[&str] = &["output.log", "env.txt", "dmesg.txt", "df.txt", "free.txt"];
+
+#[derive(Debug, Clone)]
+pub struct FailureBundle {
+    pub record: Option<RunRecord>,
+    pub exit_code: i32,
+    /// (file name, contents) for each captured file present
+    pub files: Vec<(String, String)>,
+}
+
+fn diagnostics_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("diagnostics"))
+}
+
+/// Host directory for the diagnostics of `run_id`
+pub fn bundle_dir(run_id: &str) -> Result<PathBuf> {
+    if run_id.is_empty()
+        || !run_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(VortexError::InvalidInput {
+            field: "run_id".to_string(),
+            message: format!("Invalid run ID '{}'", run_id),
+        });
+    }
+    Ok(diagnostics_root()?.join(run_id))
+}
+
+/// Create the (empty) bundle directory the guest writes into
+pub fn prepare(run_id: &str) -> Result<PathBuf> {
+    let dir = bundle_dir(run_id)?;
+    fs::create_dir_all(&dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(dir)
+}
+
+/// Keep a copy of the run record with the bundle, if the bundle directory exists
+pub fn save_record(record: &RunRecord) -> Result<()> {
+    let dir = bundle_dir(record.run_id.as_str())?;
+    if dir.is_dir() {
+        fs::write(dir.join(RECORD_NAME), serde_json::to_string_pretty(record)?)?;
+    }
+    Ok(())
+}
+
+/// Remove the bundle directory of `run_id` unless a failure was captured
+pub fn remove_if_empty(run_id: &str) -> Result<()> {
+    let dir = bundle_dir(run_id)?;
+    if dir.is_dir() && !dir.join(EXIT_CODE).exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(())
+}
+
+/// Run record kept with the bundle of `run_id`
+pub fn load_record(run_id: &str) -> Result<Option<RunRecord>> {
+    let path = bundle_dir(run_id)?.join(RECORD_NAME);
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content).ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The failure bundle of `run_id`, `None` if the command did not fail
+pub fn load_failure(run_id: &str) -> Result<Option<FailureBundle>> {
+    let dir = bundle_dir(run_id)?;
+    let Ok(exit_code) = fs::read_to_string(dir.join(EXIT_CODE)) else {
+        return Ok(None);
+    };
+
+    let files = BUNDLE_FILES
+        .iter()
+        .filter_map(|name| {
+            let content = fs::read_to_string(dir.join(name)).ok()?;
+            Some((name.to_string(), content))
+        })
+        .collect();
+
+    Ok(Some(FailureBundle {
+        record: load_record(run_id)?,
+        exit_code: exit_code.trim().parse().unwrap_or(-1),
+        files,
+    }))
+}
+
+/// Wrap `command` so a non-zero exit writes a bundle to `guest_dir`.
+/// Output is still streamed; the result ends with `; ` like other run steps
+/// and keeps the exit status for [`exit_with_status`].
+pub fn wrap_command(command: &str, guest_dir: &str) -> String {
+    let out = "/tmp/.vortex-cmd-output";
+    let status = "/tmp/.vortex-cmd-status";
+    format!(
+        "{{ ( {command} ) 2>&1; echo $? > {status}; }} | tee {out}; \
+         {STATUS_VAR}=$(cat {status}); \
+         if [ \"${STATUS_VAR}\" != 0 ]; then \
+         tail -n {OUTPUT_TAIL_LINES} {out} > {dir}/output.log; \
+         env | sed -E 's/^([^=]*(TOKEN|SECRET|PASSWORD|PASSWD|KEY|CREDENTIAL)[^=]*)=.*/\\1=[redacted]/' > {dir}/env.txt; \
+         dmesg 2>&1 | tail -n {DMESG_TAIL_LINES} > {dir}/dmesg.txt; \
+         df -h > {dir}/df.txt 2>&1; \
+         free -m > {dir}/free.txt 2>&1; \
+         cp {status} {dir}/{EXIT_CODE}; \
+         fi; rm -f {out} {status}; ",
+        dir = guest_dir,
+    )
+}
+
+/// Last step of a run, after the ones following [`wrap_command`]: ends
+/// with the wrapped command's exit status. A subshell exits, so a console
+/// shell typing the steps keeps running.
+pub fn exit_with_status() -> String {
+    format!("(exit \"${{{STATUS_VAR}:-0}}\"); ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_command_captures_on_failure() {
+        let wrapped = wrap_command("make test", "/tmp/vortex-abc/diagnostics");
+        assert!(wrapped.starts_with("{ ( make test ) 2>&1;"));
+        assert!(wrapped.contains(
+            "tail -n 200 /tmp/.vortex-cmd-output > /tmp/vortex-abc/diagnostics/output.log"
+        ));
+        assert!(
+            wrapped.contains("cp /tmp/.vortex-cmd-status /tmp/vortex-abc/diagnostics/exit_code")
+        );
+        assert!(wrapped.ends_with("; "));
+
+        assert!(bundle_dir("../etc").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! The DNS responder of private networks.
+//!
+//! Members of a private network resolve each other by VM ID and by the
+//! service name in their `vortex.service` label. Container engines answer
+//! for their own networks; QEMU guests have no host interface on their
+//! segment, so the responder joins the segment as the network's gateway.
+//! It answers ARP requests for the gateway address and DNS queries sent to
+//! it on port 53, and refuses names it does not know so guests go on to
+//! their next nameserver. Members have an IPv6 address as well, which
+//! AAAA queries get, but the responder itself is only reached over IPv4.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IPPROTO_UDP: u8 = 17;
+pub const DNS_PORT: u16 = 53;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const RCODE_REFUSED: u16 = 5;
+/// Members come and go, so answers are only cached briefly
+const TTL_SECONDS: u32 = 5;
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]))
+}
+
+/// Reply to the DNS message `query`, looking its name up with `resolve`.
+/// Known names get their addresses, unknown ones are refused. Returns
+/// `None` for anything but a standard query with one question.
+pub fn answer(query: &[u8], resolve: impl Fn(&str) -> Vec<IpAddr>) -> Option<Vec<u8>> {
+    let flags = u16_at(query, 2)?;
+    // QR unset, opcode 0 (QUERY)
+    if flags & 0xf800 != 0 || u16_at(query, 4)? != 1 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut at = 12;
+    loop {
+        let len = usize::from(*query.get(at)?);
+        at += 1;
+        if len == 0 {
+            break;
+        }
+        // Questions are never compressed
+        if len > 63 {
+            return None;
+        }
+        labels.push(String::from_utf8_lossy(query.get(at..at + len)?).to_ascii_lowercase());
+        at += len;
+    }
+    let qtype = u16_at(query, at)?;
+    let qclass = u16_at(query, at + 2)?;
+    let question = &query[12..at + 4];
+
+    let addresses = resolve(&labels.join("."));
+    let rcode = if addresses.is_empty() {
+        RCODE_REFUSED
+    } else {
+        0
+    };
+    // Other record types of a known name get an empty answer
+    let answers: Vec<IpAddr> = addresses
+        .into_iter()
+        .filter(|address| {
+            let rtype = if address.is_ipv4() { TYPE_A } else { TYPE_AAAA };
+            qclass == CLASS_IN && (qtype == rtype || qtype == TYPE_ANY)
+        })
+        .collect();
+
+    let mut reply = Vec::with_capacity(12 + question.len() + 28 * answers.len());
+    reply.extend_from_slice(&query[..2]);
+    // QR and AA set, RD copied from the query
+    reply.extend_from_slice(&(0x8400 | (flags & 0x0100) | rcode).to_be_bytes());
+    reply.extend_from_slice(&1u16.to_be_bytes());
+    reply.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    reply.extend_from_slice(&[0, 0, 0, 0]);
+    reply.extend_from_slice(question);
+    for address in answers {
+        let (rtype, data) = match address {
+            IpAddr::V4(ip) => (TYPE_A, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (TYPE_AAAA, ip.octets().to_vec()),
+        };
+        // The name, as a pointer to the question's
+        reply.extend_from_slice(&[0xc0, 0x0c]);
+        reply.extend_from_slice(&rtype.to_be_bytes());
+        reply.extend_from_slice(&CLASS_IN.to_be_bytes());
+        reply.extend_from_slice(&TTL_SECONDS.to_be_bytes());
+        reply.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        reply.extend_from_slice(&data);
+    }
+    Some(reply)
+}
+
+/// The gateway of a private network, on a segment of Ethernet frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gateway {
+    pub ip: Ipv4Addr,
+    pub mac: [u8; 6],
+}
+
+impl Gateway {
+    /// Reply to `frame` if it is an ARP request for the gateway or a DNS
+    /// query sent to it
+    pub fn reply(&self, frame: &[u8], resolve: impl Fn(&str) -> Vec<IpAddr>) -> Option<Vec<u8>> {
+        let source: [u8; 6] = frame.get(6..12)?.try_into().ok()?;
+        let payload = frame.get(14..)?;
+        match u16_at(frame, 12)? {
+            ETHERTYPE_ARP => self.arp_reply(source, payload),
+            ETHERTYPE_IPV4 => self.dns_reply(source, payload, resolve),
+            _ => None,
+        }
+    }
+
+    fn frame(&self, destination: [u8; 6], ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(14 + payload.len());
+        frame.extend_from_slice(&destination);
+        frame.extend_from_slice(&self.mac);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn arp_reply(&self, destination: [u8; 6], arp: &[u8]) -> Option<Vec<u8>> {
+        // Ethernet and IPv4 addresses, operation 1 (request)
+        if arp.get(..8)? != [0, 1, 8, 0, 6, 4, 0, 1] || arp.get(24..28)? != self.ip.octets() {
+            return None;
+        }
+        let mut reply = vec![0, 1, 8, 0, 6, 4, 0, 2];
+        reply.extend_from_slice(&self.mac);
+        reply.extend_from_slice(&self.ip.octets());
+        // The sender's MAC and IP address
+        reply.extend_from_slice(arp.get(8..18)?);
+        Some(self.frame(destination, ETHERTYPE_ARP, &reply))
+    }
+
+    fn dns_reply(
+        &self,
+        destination: [u8; 6],
+        packet: &[u8],
+        resolve: impl Fn(&str) -> Vec<IpAddr>,
+    ) -> Option<Vec<u8>> {
+        let header_len = usize::from(packet.first()? & 0x0f) * 4;
+        let unfragmented = u16_at(packet, 6)? & 0x3fff == 0;
+        if packet[0] >> 4 != 4
+            || header_len < 20
+            || !unfragmented
+            || *packet.get(9)? != IPPROTO_UDP
+            || packet.get(16..20)? != self.ip.octets()
+        {
+            return None;
+        }
+        // Frames may be padded past the end of the packet
+        let total = usize::from(u16_at(packet, 2)?).min(packet.len());
+        let udp = packet.get(header_len..total)?;
+        if u16_at(udp, 2)? != DNS_PORT {
+            return None;
+        }
+        let udp_len = usize::from(u16_at(udp, 4)?).min(udp.len());
+        let answer = answer(udp.get(8..udp_len)?, resolve)?;
+
+        let mut reply = vec![0x45, 0];
+        reply.extend_from_slice(&((28 + answer.len()) as u16).to_be_bytes());
+        // No ID, don't fragment, TTL 64, checksum filled in below
+        reply.extend_from_slice(&[0, 0, 0x40, 0, 64, IPPROTO_UDP, 0, 0]);
+        reply.extend_from_slice(&self.ip.octets());
+        reply.extend_from_slice(packet.get(12..16)?);
+        let checksum = ipv4_checksum(&reply);
+        reply[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        reply.extend_from_slice(&DNS_PORT.to_be_bytes());
+        reply.extend_from_slice(&udp[..2]);
+        reply.extend_from_slice(&((8 + answer.len()) as u16).to_be_bytes());
+        // A zero UDP checksum means none, which IPv4 allows
+        reply.extend_from_slice(&[0, 0]);
+        reply.extend_from_slice(&answer);
+        Some(self.frame(destination, ETHERTYPE_IPV4, &reply))
+    }
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A query for the `qtype` records of `name` from 10.89.1.2 to the
+    /// gateway 10.89.1.1
+    fn query_frame(name: &str, qtype: u16) -> Vec<u8> {
+        let mut dns = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            dns.push(label.len() as u8);
+            dns.extend_from_slice(label.as_bytes());
+        }
+        dns.push(0);
+        dns.extend_from_slice(&qtype.to_be_bytes());
+        dns.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        let mut frame = vec![0x02, 0x0a, 0x59, 0, 1, 1, 0x02, 0x0a, 0x59, 0, 1, 2, 8, 0];
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&((28 + dns.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[
+            0,
+            0,
+            0,
+            0,
+            64,
+            IPPROTO_UDP,
+            0,
+            0,
+            10,
+            89,
+            1,
+            2,
+            10,
+            89,
+            1,
+            1,
+        ]);
+        frame.extend_from_slice(&[0xc3, 0x50, 0, 53]);
+        frame.extend_from_slice(&((8 + dns.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&dns);
+        frame
+    }
+
+    #[test]
+    fn test_gateway_answers_members_and_refuses_other_names() {
+        let gateway = Gateway {
+            ip: Ipv4Addr::new(10, 89, 1, 1),
+            mac: [0x02, 0x0a, 0x59, 0, 1, 1],
+        };
+        let resolve = |name: &str| match name {
+            "database" => vec![
+                IpAddr::from([10, 89, 1, 3]),
+                "fd89:0:0:1::3".parse().unwrap(),
+            ],
+            _ => Vec::new(),
+        };
+
+        let reply = gateway
+            .reply(&query_frame("Database", TYPE_A), resolve)
+            .unwrap();
+        assert_eq!(&reply[..6], &[0x02, 0x0a, 0x59, 0, 1, 2]);
+        let ip = &reply[14..34];
+        assert_eq!(ipv4_checksum(ip), 0);
+        assert_eq!(&ip[16..20], &[10, 89, 1, 2]);
+        let dns = &reply[42..];
+        assert_eq!(&dns[..4], &[0x12, 0x34, 0x85, 0x00]);
+        // One answer, ending in the member's address
+        assert_eq!(u16_at(dns, 6), Some(1));
+        assert_eq!(&dns[dns.len() - 4..], &[10, 89, 1, 3]);
+
+        let reply = gateway
+            .reply(&query_frame("database", TYPE_AAAA), resolve)
+            .unwrap();
+        let dns = &reply[42..];
+        assert_eq!(u16_at(dns, 6), Some(1));
+        assert_eq!(&dns[dns.len() - 18..dns.len() - 16], &[0, 16]);
+        assert_eq!(&dns[dns.len() - 2..], &[0, 3]);
+
+        let reply = gateway
+            .reply(&query_frame("example.com", TYPE_A), resolve)
+            .unwrap();
+        assert_eq!(reply[42 + 3] & 0x0f, 5);
+        assert_eq!(u16_at(&reply[42..], 6), Some(0));
+
+        let mut arp = vec![0xff; 6];
+        arp.extend_from_slice(&[0x02, 0x0a, 0x59, 0, 1, 2, 8, 6, 0, 1, 8, 0, 6, 4, 0, 1]);
+        arp.extend_from_slice(&[0x02, 0x0a, 0x59, 0, 1, 2, 10, 89, 1, 2, 0, 0, 0, 0, 0, 0]);
+        arp.extend_from_slice(&[10, 89, 1, 1]);
+        let reply = gateway.reply(&arp, resolve).unwrap();
+        assert_eq!(&reply[20..22], &[0, 2]);
+        assert_eq!(&reply[22..28], &gateway.mac);
+        arp[41] = 9;
+        assert_eq!(gateway.reply(&arp, resolve), None);
+    }
+}
@@ -0,0 +1,273 @@
+//! Personal dotfiles applied to every dev VM.
+//!
+//! The `[dotfiles]` section of the Vortex config names a git repository or a
+//! local directory. Git sources are cloned once into
+//! `~/.vortex/cache/dotfiles/<hash>` and only re-fetched after
+//! `refresh_hours`, so boot never waits on a full clone. The directory is
+//! mounted at `/vortex/dotfiles` and either its bootstrap script is run or,
+//! without one, its top-level dotfiles are symlinked into the guest home.
+
+use crate::error::{Result, VortexError};
+use crate::home_volume::GUEST_HOME;
+use crate::vm::VmSpec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+/// Guest mount point of the dotfiles directory
+pub const GUEST_DOTFILES: &str = "/vortex/dotfiles";
+/// Scripts tried in order when no bootstrap script is configured
+const BOOTSTRAP_CANDIDATES: &[&str] = &["install.sh", "bootstrap.sh", "setup.sh"];
+const FETCH_MARKER: &str = ".vortex-fetched";
+/// Printed by the guest when installing the dotfiles fails
+const INSTALL_FAILED: &str = "vortex: installing the dotfiles failed; continuing without them";
+
+fn default_refresh_hours() -> u64 {
+    24
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DotfilesConfig {
+    /// Git URL or local directory
+    pub source: String,
+    /// Branch to clone for git sources
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Script relative to the dotfiles root, run in the guest on provision
+    #[serde(default)]
+    pub bootstrap: Option<String>,
+    /// Hours before a cached clone is fetched again
+    #[serde(default = "default_refresh_hours")]
+    pub refresh_hours: u64,
+}
+
+impl DotfilesConfig {
+    fn local_dir(&self) -> Option<PathBuf> {
+        let path = match self.source.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()?.join(rest),
+            None => PathBuf::from(&self.source),
+        };
+        path.is_dir().then_some(path)
+    }
+
+    fn cache_dir(&self) -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        let mut hasher = Sha256::new();
+        hasher.update(self.source.as_bytes());
+        hasher.update(self.branch.as_deref().unwrap_or("").as_bytes());
+        let key = format!("{:x}", hasher.finalize());
+        Ok(home
+            .join(".vortex")
+            .join("cache")
+            .join("dotfiles")
+            .join(&key[..16]))
+    }
+
+    /// Host directory with the dotfiles, cloning or refreshing git sources as needed
+    pub fn prepare(&self) -> Result<PathBuf> {
+        if let Some(dir) = self.local_dir() {
+            return Ok(dir);
+        }
+
+        let dir = self.cache_dir()?;
+        let marker = dir.join(FETCH_MARKER);
+        if !dir.join(".git").is_dir() {
+            if let Some(parent) = dir.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let _ = fs::remove_dir_all(&dir);
+
+            let mut clone = Command::new("git");
+            clone.args(["clone", "--depth", "1"]);
+            if let Some(branch) = &self.branch {
+                clone.args(["--branch", branch]);
+            }
+            run_git(clone.arg("--").arg(&self.source).arg(&dir), "clone")?;
+            fs::write(&marker, "")?;
+        } else if is_stale(&marker, self.refresh_hours) {
+            // A failed refresh keeps the cached copy usable offline
+            let mut pull = Command::new("git");
+            pull.arg("-C")
+                .arg(&dir)
+                .args(["pull", "--ff-only", "--depth", "1"]);
+            match run_git(&mut pull, "pull") {
+                Ok(()) => fs::write(&marker, "")?,
+                Err(e) => tracing::warn!("Using cached dotfiles: {}", e),
+            }
+        }
+        Ok(dir)
+    }
+
+    /// Guest commands that install the dotfiles from `dir`
+    fn install_steps(&self, dir: &Path) -> Result<Vec<String>> {
+        let script = match &self.bootstrap {
+            Some(script) => {
+                let safe = script
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+                if !safe || script.starts_with('/') || script.split('/').any(|part| part == "..") {
+                    return Err(VortexError::InvalidInput {
+                        field: "dotfiles.bootstrap".to_string(),
+                        message: format!("Bootstrap script '{}' must be a plain relative path inside the dotfiles", script),
+                    });
+                }
+                Some(script.clone())
+            }
+            None => BOOTSTRAP_CANDIDATES
+                .iter()
+                .find(|name| dir.join(name).is_file())
+                .map(|name| name.to_string()),
+        };
+
+        if let Some(script) = script {
+            return Ok(vec![
+                format!("cd {}", GUEST_DOTFILES),
+                format!("sh ./{}", script),
+            ]);
+        }
+
+        let mut names: Vec<String> = fs::read_dir(dir)?
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter(|name| {
+                name.starts_with('.')
+                    && name != ".git"
+                    && name != FETCH_MARKER
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+            })
+            .collect();
+        names.sort();
+        Ok(names
+            .iter()
+            .map(|name| {
+                format!(
+                    "ln -sfn {}/{} {}/{}",
+                    GUEST_DOTFILES, name, GUEST_HOME, name
+                )
+            })
+            .collect())
+    }
+
+    /// Mount the dotfiles into `spec` and run their installation before its
+    /// command. The installation runs in a subshell, so its `cd` stays
+    /// there, and a failure only warns: the command runs either way.
+    pub fn apply(&self, spec: &mut VmSpec) -> Result<()> {
+        let dir = self.prepare()?;
+        let steps = self.install_steps(&dir)?;
+        spec.volumes.insert(dir, PathBuf::from(GUEST_DOTFILES));
+
+        if steps.is_empty() {
+            return Ok(());
+        }
+        let install = format!("({}) || echo '{}' >&2", steps.join(" && "), INSTALL_FAILED);
+        spec.command = Some(match spec.command.take() {
+            Some(command) => format!("{}; {}", install, command),
+            None => install,
+        });
+        Ok(())
+    }
+}
+
+fn is_stale(marker: &Path, refresh_hours: u64) -> bool {
+    let max_age = Duration::from_secs(refresh_hours * 3600);
+    fs::metadata(marker)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map_or(true, |age| age > max_age)
+}
+
+fn run_git(cmd: &mut Command, action: &str) -> Result<()> {
+    let output = cmd.output().map_err(|e| VortexError::ConfigError {
+        message: format!("Failed to run git {} for dotfiles: {}", action, e),
+    })?;
+    if !output.status.success() {
+        return Err(VortexError::ConfigError {
+            message: format!(
+                "git {} for dotfiles failed: {}",
+                action,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_dotfiles_are_linked_without_bootstrap() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".bashrc"), "alias ll='ls -l'").unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+
+        let config = DotfilesConfig {
+            source: dir.path().display().to_string(),
+            branch: None,
+            bootstrap: None,
+            refresh_hours: default_refresh_hours(),
+        };
+        let mut spec = VmSpec {
+            command: Some("exec bash".to_string()),
+            ..Default::default()
+        };
+        config.apply(&mut spec).unwrap();
+
+        assert_eq!(
+            spec.command,
+            Some(format!(
+                "(ln -sfn /vortex/dotfiles/.bashrc /root/.bashrc) || echo '{}' >&2; exec bash",
+                INSTALL_FAILED
+            ))
+        );
+        assert_eq!(
+            spec.volumes.get(dir.path()),
+            Some(&PathBuf::from(GUEST_DOTFILES))
+        );
+
+        fs::write(dir.path().join("install.sh"), "").unwrap();
+        let steps = config.install_steps(dir.path()).unwrap();
+        assert_eq!(steps[1], "sh ./install.sh");
+    }
+
+    #[test]
+    fn test_failing_bootstrap_still_runs_the_command() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("install.sh"), "exit 1").unwrap();
+        let config = DotfilesConfig {
+            source: dir.path().display().to_string(),
+            branch: None,
+            bootstrap: None,
+            refresh_hours: default_refresh_hours(),
+        };
+        let mut spec = VmSpec {
+            command: Some("pwd".to_string()),
+            ..Default::default()
+        };
+        config.apply(&mut spec).unwrap();
+
+        // Run where the guest would find the dotfiles
+        let command = spec
+            .command
+            .unwrap()
+            .replace(GUEST_DOTFILES, &dir.path().display().to_string());
+        let output = Command::new("sh")
+            .args(["-c", &command])
+            .current_dir("/")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "/\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains(INSTALL_FAILED));
+    }
+}
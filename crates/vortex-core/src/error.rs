@@ -34,6 +34,9 @@ pub enum VortexError {
     #[error("Invalid input: {field} - {message}")]
     InvalidInput { field: String, message: String },
 
+    #[error("Host port {port} is already in use by {owner}")]
+    PortInUse { port: u16, owner: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
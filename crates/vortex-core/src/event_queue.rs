@@ -0,0 +1,248 @@
+//! Bounded per-subscriber queues between VM lifecycle operations and event
+//! handlers.
+//!
+//! Emitting an event only enqueues it; each handler drains its own queue on a
+//! background task, so a slow consumer cannot stall VM creation or shutdown.
+//! When a queue is full the `[events]` config decides what happens: the
+//! default `drop_oldest` discards the oldest queued event and counts the loss,
+//! while `block` makes the emitter wait for space. Queue depth and delivery,
+//! drop and failure counters are reported in `vortex daemon status`.
+
+use crate::vm::{VmEvent, VmEventHandler};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+fn default_capacity() -> usize {
+    256
+}
+
+/// What to do with a new event when a subscriber's queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued event and count it as dropped
+    #[default]
+    DropOldest,
+    /// Wait until the subscriber has made room
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventQueueConfig {
+    /// Events buffered per subscriber
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for EventQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_capacity(),
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// Queue metrics for one subscriber
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    pub name: String,
+    pub queued: usize,
+    pub capacity: usize,
+    pub delivered: u64,
+    pub dropped: u64,
+    pub failed: u64,
+}
+
+/// An event handler behind a bounded queue drained by its own task
+pub struct EventSubscriber {
+    name: String,
+    config: EventQueueConfig,
+    queue: Mutex<VecDeque<VmEvent>>,
+    /// Signalled when an event is queued
+    ready: Notify,
+    /// Signalled when the worker takes an event off the queue
+    space: Notify,
+    /// Signalled when the worker finishes handling an event
+    handled: Notify,
+    in_flight: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl EventSubscriber {
+    /// Start delivering to `handler`. Must be called within a Tokio runtime.
+    pub fn spawn(handler: Box<dyn VmEventHandler>, config: EventQueueConfig) -> Arc<Self> {
+        let subscriber = Arc::new(Self {
+            name: handler.name().to_string(),
+            config: EventQueueConfig {
+                capacity: config.capacity.max(1),
+                ..config
+            },
+            queue: Mutex::new(VecDeque::new()),
+            ready: Notify::new(),
+            space: Notify::new(),
+            handled: Notify::new(),
+            in_flight: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        });
+
+        let worker = subscriber.clone();
+        tokio::spawn(async move { worker.run(handler).await });
+        subscriber
+    }
+
+    async fn run(&self, handler: Box<dyn VmEventHandler>) {
+        loop {
+            let event = self.next_event().await;
+            match handler.handle(event).await {
+                Ok(()) => self.delivered.fetch_add(1, Ordering::Relaxed),
+                Err(e) => {
+                    tracing::warn!("Event handler {} failed: {}", self.name, e);
+                    self.failed.fetch_add(1, Ordering::Relaxed)
+                }
+            };
+            self.in_flight.store(0, Ordering::Release);
+            self.handled.notify_waiters();
+        }
+    }
+
+    async fn next_event(&self) -> VmEvent {
+        loop {
+            let ready = self.ready.notified();
+            let event = {
+                let mut queue = self.lock();
+                let event = queue.pop_front();
+                if event.is_some() {
+                    self.in_flight.store(1, Ordering::Release);
+                }
+                event
+            };
+            if let Some(event) = event {
+                self.space.notify_one();
+                return event;
+            }
+            ready.await;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<VmEvent>> {
+        // A panicking handler never holds this lock, so poisoning is harmless
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue `event`, applying the overflow policy when the queue is full
+    pub async fn push(&self, event: VmEvent) {
+        let mut event = Some(event);
+        while event.is_some() {
+            let space = self.space.notified();
+            {
+                let mut queue = self.lock();
+                let full = queue.len() >= self.config.capacity;
+                if !full || self.config.overflow == OverflowPolicy::DropOldest {
+                    if full {
+                        queue.pop_front();
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    queue.extend(event.take());
+                }
+            }
+            if event.is_some() {
+                space.await;
+            }
+        }
+        self.ready.notify_one();
+    }
+
+    /// Whether every queued event has been handled
+    pub fn is_idle(&self) -> bool {
+        self.lock().is_empty() && self.in_flight.load(Ordering::Acquire) == 0
+    }
+
+    /// Wait until the queue is drained
+    pub async fn drain(&self) {
+        loop {
+            let handled = self.handled.notified();
+            if self.is_idle() {
+                return;
+            }
+            handled.await;
+        }
+    }
+
+    pub fn stats(&self) -> SubscriberStats {
+        SubscriberStats {
+            name: self.name.clone(),
+            queued: self.lock().len(),
+            capacity: self.config.capacity,
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use async_trait::async_trait;
+
+    struct Gated(Arc<Notify>);
+
+    #[async_trait]
+    impl VmEventHandler for Gated {
+        async fn handle(&self, _event: VmEvent) -> Result<()> {
+            self.0.notified().await;
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "gated"
+        }
+    }
+
+    fn event(n: usize) -> VmEvent {
+        VmEvent::Started {
+            vm_id: format!("vortex-{}", n).into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_counts_losses_without_blocking() {
+        let gate = Arc::new(Notify::new());
+        let subscriber = EventSubscriber::spawn(
+            Box::new(Gated(gate.clone())),
+            EventQueueConfig {
+                capacity: 2,
+                overflow: OverflowPolicy::DropOldest,
+            },
+        );
+
+        // The first event is taken by the worker and parks on the gate
+        subscriber.push(event(0)).await;
+        while subscriber.stats().queued > 0 {
+            tokio::task::yield_now().await;
+        }
+        for n in 1..=5 {
+            subscriber.push(event(n)).await;
+        }
+
+        let stats = subscriber.stats();
+        assert_eq!((stats.queued, stats.dropped), (2, 3));
+
+        while !subscriber.is_idle() {
+            gate.notify_one();
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(subscriber.stats().delivered, 3);
+    }
+}
@@ -0,0 +1,391 @@
+//! Stable, versioned wire format for Vortex events.
+//!
+//! Internal enums such as [`VmEvent`] and the daemon's session states are free
+//! to change.
+//! Anything leaving the process (the event log read by `vortex events`, and
+//! future webhook or streaming consumers) is converted to an [`EventEnvelope`]
+//! first. See `docs/EVENT_SCHEMA.md` for the compatibility rules.
+
+use crate::error::{Result, VortexError};
+use crate::vm::{VmEvent, VmEventHandler};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Current event schema version written by this release
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub schema_version: u32,
+    pub event_id: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub payload: EventPayload,
+}
+
+impl EventEnvelope {
+    pub fn new(payload: EventPayload) -> Self {
+        Self {
+            schema_version: EVENT_SCHEMA_VERSION,
+            event_id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            payload,
+        }
+    }
+}
+
+/// Event body, tagged by `type`.
+///
+/// Variants and fields are only ever added. Consumers built against an older
+/// schema see newer event types as [`EventPayload::Unknown`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventPayload {
+    VmCreated {
+        vm_id: String,
+    },
+    VmStarted {
+        vm_id: String,
+    },
+    VmStopped {
+        vm_id: String,
+    },
+    VmExpired {
+        vm_id: String,
+    },
+    VmError {
+        vm_id: String,
+        error: String,
+    },
+    SnapshotCreated {
+        vm_id: String,
+        snapshot_id: String,
+    },
+    ResourceUsage {
+        vm_id: String,
+        cpu_percent: f64,
+        memory_bytes: u64,
+    },
+    DiskQuotaExceeded {
+        vm_id: String,
+        used_bytes: u64,
+        limit_bytes: u64,
+    },
+    VmPaused {
+        vm_id: String,
+    },
+    VmResumed {
+        vm_id: String,
+    },
+    VmResized {
+        vm_id: String,
+        memory_mb: u32,
+        cpus: u32,
+    },
+    VmHealthChanged {
+        vm_id: String,
+        healthy: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    VmCleanedUp {
+        vm_id: String,
+    },
+    MetricAlert {
+        vm_id: String,
+        alert: String,
+        metric: String,
+        value: f64,
+        threshold: f64,
+    },
+    SessionStateChanged {
+        session_id: String,
+        state: SessionStateName,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// Session states as they appear on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStateName {
+    Creating,
+    Running,
+    Detached,
+    Attached,
+    Paused,
+    Stopped,
+    Error,
+    #[serde(other)]
+    Unknown,
+}
+
+impl From<VmEvent> for EventPayload {
+    fn from(event: VmEvent) -> Self {
+        match event {
+            VmEvent::Created { vm_id } => EventPayload::VmCreated {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::Started { vm_id } => EventPayload::VmStarted {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::Stopped { vm_id } => EventPayload::VmStopped {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::Expired { vm_id } => EventPayload::VmExpired {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::Error { vm_id, error } => EventPayload::VmError {
+                vm_id: vm_id.into(),
+                error,
+            },
+            VmEvent::SnapshotCreated { vm_id, snapshot_id } => EventPayload::SnapshotCreated {
+                vm_id: vm_id.into(),
+                snapshot_id,
+            },
+            VmEvent::ResourceUsage { vm_id, cpu, memory } => EventPayload::ResourceUsage {
+                vm_id: vm_id.into(),
+                cpu_percent: cpu,
+                memory_bytes: memory,
+            },
+            VmEvent::DiskQuotaExceeded {
+                vm_id,
+                used_bytes,
+                limit_bytes,
+            } => EventPayload::DiskQuotaExceeded {
+                vm_id: vm_id.into(),
+                used_bytes,
+                limit_bytes,
+            },
+            VmEvent::Paused { vm_id } => EventPayload::VmPaused {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::Resumed { vm_id } => EventPayload::VmResumed {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::Resized {
+                vm_id,
+                memory,
+                cpus,
+            } => EventPayload::VmResized {
+                vm_id: vm_id.into(),
+                memory_mb: memory,
+                cpus,
+            },
+            VmEvent::HealthChanged {
+                vm_id,
+                healthy,
+                message,
+            } => EventPayload::VmHealthChanged {
+                vm_id: vm_id.into(),
+                healthy,
+                message,
+            },
+            VmEvent::CleanedUp { vm_id } => EventPayload::VmCleanedUp {
+                vm_id: vm_id.into(),
+            },
+            VmEvent::MetricAlert {
+                vm_id,
+                alert,
+                metric,
+                value,
+                threshold,
+            } => EventPayload::MetricAlert {
+                vm_id: vm_id.into(),
+                alert,
+                metric,
+                value,
+                threshold,
+            },
+            VmEvent::SessionStateChanged {
+                session_id,
+                state,
+                message,
+                ..
+            } => EventPayload::SessionStateChanged {
+                session_id,
+                state,
+                message,
+            },
+        }
+    }
+}
+
+impl EventPayload {
+    /// The `type` tag of this payload on the wire
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            EventPayload::VmCreated { .. } => "vm_created",
+            EventPayload::VmStarted { .. } => "vm_started",
+            EventPayload::VmStopped { .. } => "vm_stopped",
+            EventPayload::VmExpired { .. } => "vm_expired",
+            EventPayload::VmError { .. } => "vm_error",
+            EventPayload::SnapshotCreated { .. } => "snapshot_created",
+            EventPayload::ResourceUsage { .. } => "resource_usage",
+            EventPayload::DiskQuotaExceeded { .. } => "disk_quota_exceeded",
+            EventPayload::VmPaused { .. } => "vm_paused",
+            EventPayload::VmResumed { .. } => "vm_resumed",
+            EventPayload::VmResized { .. } => "vm_resized",
+            EventPayload::VmHealthChanged { .. } => "vm_health_changed",
+            EventPayload::VmCleanedUp { .. } => "vm_cleaned_up",
+            EventPayload::MetricAlert { .. } => "metric_alert",
+            EventPayload::SessionStateChanged { .. } => "session_state_changed",
+            EventPayload::Unknown => "unknown",
+        }
+    }
+
+    /// The VM this event is about; `None` for session and unknown events
+    pub fn vm_id(&self) -> Option<&str> {
+        match self {
+            EventPayload::VmCreated { vm_id }
+            | EventPayload::VmStarted { vm_id }
+            | EventPayload::VmStopped { vm_id }
+            | EventPayload::VmExpired { vm_id }
+            | EventPayload::VmError { vm_id, .. }
+            | EventPayload::SnapshotCreated { vm_id, .. }
+            | EventPayload::ResourceUsage { vm_id, .. }
+            | EventPayload::DiskQuotaExceeded { vm_id, .. }
+            | EventPayload::VmPaused { vm_id }
+            | EventPayload::VmResumed { vm_id }
+            | EventPayload::VmResized { vm_id, .. }
+            | EventPayload::VmHealthChanged { vm_id, .. }
+            | EventPayload::VmCleanedUp { vm_id }
+            | EventPayload::MetricAlert { vm_id, .. } => Some(vm_id),
+            EventPayload::SessionStateChanged { .. } | EventPayload::Unknown => None,
+        }
+    }
+
+    pub fn session_state_changed(
+        session_id: &str,
+        state: SessionStateName,
+        message: Option<String>,
+    ) -> Self {
+        EventPayload::SessionStateChanged {
+            session_id: session_id.to_string(),
+            state,
+            message,
+        }
+    }
+}
+
+/// Parse an envelope, rejecting schema versions newer than this release understands
+pub fn parse_event(line: &str) -> Result<EventEnvelope> {
+    let envelope: EventEnvelope =
+        serde_json::from_str(line).map_err(|e| VortexError::InvalidInput {
+            field: "event".to_string(),
+            message: format!("Invalid event: {}", e),
+        })?;
+
+    if envelope.schema_version > EVENT_SCHEMA_VERSION {
+        return Err(VortexError::InvalidInput {
+            field: "schema_version".to_string(),
+            message: format!(
+                "Event schema version {} is newer than supported version {}",
+                envelope.schema_version, EVENT_SCHEMA_VERSION
+            ),
+        });
+    }
+
+    Ok(envelope)
+}
+
+/// Path of the append-only JSON-lines event log
+pub fn event_log_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::ConfigError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("events.jsonl"))
+}
+
+/// VM event handler that appends versioned envelopes to the event log
+pub struct EventLogHandler {
+    path: PathBuf,
+}
+
+impl EventLogHandler {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            path: event_log_path()?,
+        })
+    }
+
+    pub fn append(&self, envelope: &EventEnvelope) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(envelope)?)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VmEventHandler for EventLogHandler {
+    async fn handle(&self, event: VmEvent) -> Result<()> {
+        self.append(&EventEnvelope::new(event.into()))
+    }
+
+    fn name(&self) -> &str {
+        "event-log"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelopes = [
+            EventEnvelope::new(
+                VmEvent::Created {
+                    vm_id: "vortex-1".into(),
+                }
+                .into(),
+            ),
+            EventEnvelope::new(EventPayload::session_state_changed(
+                "s1",
+                SessionStateName::Error,
+                Some("boom".to_string()),
+            )),
+        ];
+
+        for envelope in envelopes {
+            let json = serde_json::to_string(&envelope).unwrap();
+            assert_eq!(parse_event(&json).unwrap(), envelope);
+        }
+    }
+
+    #[test]
+    fn test_v1_wire_format_is_stable() {
+        let line = r#"{"schema_version":1,"event_id":"e1","timestamp":"2026-01-01T00:00:00Z","type":"resource_usage","vm_id":"vortex-1","cpu_percent":12.5,"memory_bytes":1024}"#;
+        let envelope = parse_event(line).unwrap();
+        assert_eq!(
+            envelope.payload,
+            EventPayload::ResourceUsage {
+                vm_id: "vortex-1".to_string(),
+                cpu_percent: 12.5,
+                memory_bytes: 1024,
+            }
+        );
+    }
+
+    #[test]
+    fn test_unknown_types_and_future_versions() {
+        let unknown = r#"{"schema_version":1,"event_id":"e2","timestamp":"2026-01-01T00:00:00Z","type":"vm_migrated","vm_id":"vortex-1"}"#;
+        assert_eq!(parse_event(unknown).unwrap().payload, EventPayload::Unknown);
+
+        let future = r#"{"schema_version":2,"event_id":"e3","timestamp":"2026-01-01T00:00:00Z","type":"vm_started","vm_id":"vortex-1"}"#;
+        assert!(parse_event(future).is_err());
+    }
+}
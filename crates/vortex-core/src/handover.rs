@@ -0,0 +1,120 @@
+//! State passed from a daemon to its upgraded replacement.
+//!
+//! `vortex daemon upgrade` asks the running daemon to stop accepting
+//! connections, let in-flight requests finish and write the VMs it tracks to
+//! `~/.vortex/handover.json`. It then starts the new binary as the daemon and
+//! exits. VMM processes log to files and keep their state on disk, so they
+//! outlive the old daemon; the new one adopts them from the handover file,
+//! while sessions come back from `sessions.json` as on any start.
+
+use crate::error::{Result, VortexError};
+use crate::ids::VmId;
+use crate::vm::{VmInstance, VmSpec, VmState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A tracked VM, minus the live backend handle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmRecord {
+    pub id: VmId,
+    pub spec: VmSpec,
+    pub state: VmState,
+    /// `Backend::name` of the backend running the VM
+    pub backend: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&VmInstance> for VmRecord {
+    fn from(vm: &VmInstance) -> Self {
+        Self {
+            id: vm.id.clone(),
+            spec: vm.spec.clone(),
+            state: vm.state.clone(),
+            backend: vm.backend.name().to_string(),
+            created_at: vm.created_at,
+            updated_at: vm.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Handover {
+    /// Version of the daemon that wrote the handover
+    pub from_version: String,
+    pub written_at: DateTime<Utc>,
+    pub vms: Vec<VmRecord>,
+}
+
+impl Handover {
+    pub fn new(vms: Vec<VmRecord>) -> Self {
+        Self {
+            from_version: crate::VERSION.to_string(),
+            written_at: Utc::now(),
+            vms,
+        }
+    }
+
+    pub fn default_path() -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::VmError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(home.join(".vortex").join("handover.json"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Read and remove the handover at `path`, so it is adopted only once
+    pub fn take(path: &Path) -> Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        std::fs::remove_file(path)?;
+        let handover = serde_json::from_str(&content).map_err(|e| VortexError::VmError {
+            message: format!("Failed to parse handover {}: {}", path.display(), e),
+        })?;
+        Ok(Some(handover))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handover_is_taken_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("handover.json");
+        let record = VmRecord {
+            id: "vortex-1".into(),
+            spec: VmSpec {
+                image: "alpine".to_string(),
+                ..Default::default()
+            },
+            state: VmState::Running,
+            backend: "qemu".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        Handover::new(vec![record]).write(&path).unwrap();
+
+        let handover = Handover::take(&path).unwrap().unwrap();
+        assert_eq!(handover.from_version, crate::VERSION);
+        assert_eq!(handover.vms[0].backend, "qemu");
+        assert!(Handover::take(&path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,90 @@
+//! Persistent guest home directories for ephemeral dev VMs.
+//!
+//! When enabled (per template with `persist_home = true`, or per invocation
+//! with `vortex dev --persist-home`) the host directory
+//! `~/.vortex/homes/<template>` is mounted over the guest user's home. Shell
+//! history, tool caches and dotfiles then survive across VMs while the rest of
+//! the root filesystem stays ephemeral.
+
+use crate::error::{Result, VortexError};
+use crate::vm::VmSpec;
+use std::fs;
+use std::path::PathBuf;
+
+/// Home directory of the guest user in the dev images
+pub const GUEST_HOME: &str = "/root";
+/// VM label carrying the template whose home volume is mounted
+pub const LABEL_HOME_VOLUME: &str = "vortex.home-volume";
+
+fn homes_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("homes"))
+}
+
+/// Host directory backing the home volume of `template`
+pub fn home_volume_path(template: &str) -> Result<PathBuf> {
+    if template.is_empty()
+        || !template
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(VortexError::InvalidInput {
+            field: "template".to_string(),
+            message: format!("Invalid template name '{}' for home volume", template),
+        });
+    }
+    Ok(homes_root()?.join(template))
+}
+
+/// Create the home volume for `template` if it doesn't exist yet
+pub fn prepare(template: &str) -> Result<PathBuf> {
+    let path = home_volume_path(template)?;
+    fs::create_dir_all(&path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(path)
+}
+
+/// Create the home volume for `template` if needed and mount it in `spec`
+pub fn apply(spec: &mut VmSpec, template: &str) -> Result<()> {
+    let path = prepare(template)?;
+    spec.volumes.insert(path, PathBuf::from(GUEST_HOME));
+    spec.labels
+        .insert(LABEL_HOME_VOLUME.to_string(), template.to_string());
+    Ok(())
+}
+
+/// Existing home volumes as (template, host path), sorted by template
+pub fn list_home_volumes() -> Result<Vec<(String, PathBuf)>> {
+    let Ok(entries) = fs::read_dir(homes_root()?) else {
+        return Ok(Vec::new());
+    };
+
+    let mut volumes: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            Some((name, entry.path()))
+        })
+        .collect();
+    volumes.sort();
+    Ok(volumes)
+}
+
+/// Delete the home volume of `template`, returning whether one existed
+pub fn remove_home_volume(template: &str) -> Result<bool> {
+    let path = home_volume_path(template)?;
+    match fs::remove_dir_all(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
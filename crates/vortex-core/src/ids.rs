@@ -0,0 +1,158 @@
+//! Typed identifiers for cross-references between records.
+//!
+//! Each ID serializes as a plain string, so records that previously stored a
+//! `String` stay readable.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+macro_rules! typed_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn new(id: impl Into<String>) -> Self {
+                Self(id.into())
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.to_string())
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::ops::Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<std::ffi::OsStr> for $name {
+            fn as_ref(&self) -> &std::ffi::OsStr {
+                self.0.as_ref()
+            }
+        }
+
+        impl std::borrow::Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = std::convert::Infallible;
+
+            fn from_str(id: &str) -> Result<Self, Self::Err> {
+                Ok(Self(id.to_string()))
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+
+        impl PartialEq<$name> for str {
+            fn eq(&self, other: &$name) -> bool {
+                self == other.0
+            }
+        }
+
+        impl PartialEq<$name> for &str {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+
+        impl PartialEq<$name> for String {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+    };
+}
+
+typed_id!(
+    /// ID of a VM as known to its backend (e.g. `vortex-1a2b3c4d`)
+    VmId
+);
+typed_id!(
+    /// ID of a persistent session (e.g. `session-1a2b3c4d`)
+    SessionId
+);
+typed_id!(
+    /// ID of a workspace directory under `~/.vortex/workspaces`
+    WorkspaceId
+);
+typed_id!(
+    /// ID of a single `vortex run` invocation
+    RunId
+);
+typed_id!(
+    /// ID of a saved VM snapshot (e.g. `snap-1a2b3c4d`)
+    SnapshotId
+);
+
+/// VM label carrying the owning workspace ID
+pub const LABEL_WORKSPACE_ID: &str = "vortex.workspace";
+/// VM label carrying the owning session ID
+pub const LABEL_SESSION_ID: &str = "session_id";
+/// VM label carrying the run that created the VM
+pub const LABEL_RUN_ID: &str = "vortex.run-id";
+/// VM label carrying the ID of the VM a clone was copied from
+pub const LABEL_CLONED_FROM: &str = "vortex.cloned-from";
+/// VM label carrying the name of the session the VM belongs to
+pub const LABEL_SESSION_NAME: &str = "session_name";
+/// VM label naming the service other members of its private network
+/// resolve it by
+pub const LABEL_SERVICE: &str = "vortex.service";
+/// VM label carrying the loopback port of the egress proxy the VM's
+/// network policy is enforced by
+pub const LABEL_EGRESS_PROXY: &str = "vortex.egress-proxy";
@@ -0,0 +1,394 @@
+//! Backend-native root filesystems prepared once per image digest.
+//!
+//! An image can be provided as an exported root filesystem tarball at
+//! `~/.vortex/images/<image>.tar` (`/` and `:` replaced by `_`), e.g. from
+//! `podman export`. Its SHA-256 is the image digest. The first VM created from
+//! it converts the tarball into the form its backend boots: an ext4 disk for
+//! Cloud Hypervisor and QEMU (built with `mkfs.ext4 -d`) or an unpacked
+//! directory for libkrun. The result is kept under `~/.vortex/images/prepared/`
+//! keyed by digest and format, so later VMs skip unpacking entirely and only
+//! copy the prepared rootfs. File ownership survives unpacking only when
+//! Vortex runs as root; otherwise files belong to the invoking user.
+//!
+//! `vortex image gc` removes prepared images whose tarball has changed or
+//! gone, and those unused for longer than a cutoff. Tarballs themselves are
+//! never deleted.
+
+use crate::error::{Result, VortexError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const PREPARED_DIR: &str = "prepared";
+const INDEX_FILE: &str = "index.json";
+/// Free space left in ext4 images on top of the unpacked contents
+const EXT4_HEADROOM: u64 = 256 * 1024 * 1024;
+
+/// File name component for an image reference
+pub fn image_key(image: &str) -> String {
+    image
+        .chars()
+        .map(|c| if c == '/' || c == ':' { '_' } else { c })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreparedFormat {
+    /// Raw ext4 disk image for block-device backends
+    Ext4,
+    /// Unpacked directory tree for libkrun
+    Directory,
+}
+
+impl PreparedFormat {
+    fn file_name(self, digest: &str) -> String {
+        match self {
+            PreparedFormat::Ext4 => format!("{}.ext4", digest),
+            PreparedFormat::Directory => format!("{}.dir", digest),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreparedEntry {
+    pub digest: String,
+    pub image: String,
+    pub format: PreparedFormat,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+}
+
+/// Cached digest of a tarball, valid while its size and mtime are unchanged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SourceDigest {
+    size: u64,
+    modified: i64,
+    digest: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    #[serde(default)]
+    sources: HashMap<String, SourceDigest>,
+    #[serde(default)]
+    prepared: Vec<PreparedEntry>,
+}
+
+pub struct ImageCache {
+    root: PathBuf,
+}
+
+impl ImageCache {
+    /// The cache under `~/.vortex/images`
+    pub fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self::at(home.join(".vortex").join("images")))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Root filesystem tarball for `image`
+    pub fn source_path(&self, image: &str) -> PathBuf {
+        self.root.join(format!("{}.tar", image_key(image)))
+    }
+
+    fn prepared_dir(&self) -> PathBuf {
+        self.root.join(PREPARED_DIR)
+    }
+
+    fn load_index(&self) -> Result<Index> {
+        match std::fs::read_to_string(self.prepared_dir().join(INDEX_FILE)) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| VortexError::StorageError {
+                message: format!("Corrupt prepared image index: {}", e),
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Index::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_index(&self, index: &Index) -> Result<()> {
+        let dir = self.prepared_dir();
+        std::fs::create_dir_all(&dir)?;
+        let tmp = dir.join(format!("{}.{}", INDEX_FILE, std::process::id()));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(index)?)?;
+        std::fs::rename(tmp, dir.join(INDEX_FILE))?;
+        Ok(())
+    }
+
+    /// SHA-256 of the tarball for `image`, rehashed only when it changed
+    fn source_digest(&self, index: &mut Index, image: &str) -> Result<Option<String>> {
+        let path = self.source_path(image);
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs() as i64);
+
+        let key = image_key(image);
+        if let Some(cached) = index.sources.get(&key) {
+            if cached.size == metadata.len() && cached.modified == modified {
+                return Ok(Some(cached.digest.clone()));
+            }
+        }
+
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        let digest = format!("{:x}", hasher.finalize());
+        index.sources.insert(
+            key,
+            SourceDigest {
+                size: metadata.len(),
+                modified,
+                digest: digest.clone(),
+            },
+        );
+        Ok(Some(digest))
+    }
+
+    /// Path of `image` prepared as `format`, converting its tarball on first
+    /// use. `None` when the image has no tarball.
+    pub async fn prepare(&self, image: &str, format: PreparedFormat) -> Result<Option<PathBuf>> {
+        let mut index = self.load_index()?;
+        let Some(digest) = self.source_digest(&mut index, image)? else {
+            return Ok(None);
+        };
+
+        let target = self.prepared_dir().join(format.file_name(&digest));
+        if !target.exists() {
+            tracing::info!(
+                "Preparing {} as {:?} (sha256:{})",
+                image,
+                format,
+                &digest[..12]
+            );
+            self.convert(&self.source_path(image), &target, format)
+                .await?;
+        }
+
+        let now = Utc::now();
+        match index
+            .prepared
+            .iter_mut()
+            .find(|e| e.digest == digest && e.format == format)
+        {
+            Some(entry) => entry.last_used = now,
+            None => index.prepared.push(PreparedEntry {
+                digest,
+                image: image.to_string(),
+                format,
+                size_bytes: disk_usage(&target),
+                created_at: now,
+                last_used: now,
+            }),
+        }
+        self.save_index(&index)?;
+        Ok(Some(target))
+    }
+
+    /// Build `target` next to it and rename into place, so a failed or
+    /// concurrent conversion never leaves a partial image behind
+    async fn convert(&self, source: &Path, target: &Path, format: PreparedFormat) -> Result<()> {
+        let dir = self.prepared_dir();
+        tokio::fs::create_dir_all(&dir).await?;
+        let tmp = temp_path(&dir);
+
+        let result = match format {
+            PreparedFormat::Directory => unpack(source, &tmp).await,
+            PreparedFormat::Ext4 => {
+                let staging = temp_path(&dir);
+                let result = match unpack(source, &staging).await {
+                    Ok(()) => {
+                        let size = std::fs::metadata(source)?.len();
+                        let size_mib = (size + size / 2 + EXT4_HEADROOM) / (1024 * 1024);
+                        let mut mkfs = tokio::process::Command::new("mkfs.ext4");
+                        mkfs.args(["-q", "-F", "-d"])
+                            .arg(&staging)
+                            .arg(&tmp)
+                            .arg(format!("{}M", size_mib));
+                        run(mkfs).await
+                    }
+                    Err(e) => Err(e),
+                };
+                remove_path(&staging);
+                result
+            }
+        };
+        if let Err(e) = result {
+            remove_path(&tmp);
+            return Err(e);
+        }
+
+        if let Err(e) = std::fs::rename(&tmp, target) {
+            remove_path(&tmp);
+            // Another VM creation may have finished the same conversion first
+            if !target.exists() {
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> Result<Vec<PreparedEntry>> {
+        Ok(self.load_index()?.prepared)
+    }
+
+    /// Remove prepared images whose tarball changed or was deleted, and those
+    /// not used within `max_unused`. Returns the removed entries.
+    pub fn gc(&self, max_unused: chrono::Duration) -> Result<Vec<PreparedEntry>> {
+        let mut index = self.load_index()?;
+
+        let mut current = Vec::new();
+        let images: Vec<String> = index.prepared.iter().map(|e| e.image.clone()).collect();
+        for image in images {
+            if let Some(digest) = self.source_digest(&mut index, &image)? {
+                current.push(digest);
+            }
+        }
+        index
+            .sources
+            .retain(|key, _| self.root.join(format!("{}.tar", key)).exists());
+
+        let cutoff = Utc::now() - max_unused;
+        let (kept, removed): (Vec<_>, Vec<_>) = index
+            .prepared
+            .drain(..)
+            .partition(|e| current.contains(&e.digest) && e.last_used >= cutoff);
+        index.prepared = kept;
+
+        for entry in &removed {
+            remove_path(
+                &self
+                    .prepared_dir()
+                    .join(entry.format.file_name(&entry.digest)),
+            );
+        }
+        self.save_index(&index)?;
+        Ok(removed)
+    }
+}
+
+fn temp_path(dir: &Path) -> PathBuf {
+    dir.join(format!(".tmp-{}", uuid::Uuid::new_v4().simple()))
+}
+
+async fn unpack(source: &Path, dest: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dest).await?;
+    let mut tar = tokio::process::Command::new("tar");
+    tar.arg("-xf").arg(source).arg("-C").arg(dest);
+    run(tar).await
+}
+
+async fn run(mut cmd: tokio::process::Command) -> Result<()> {
+    let output = cmd.output().await.map_err(|e| VortexError::StorageError {
+        message: format!("Failed to run image conversion: {}", e),
+    })?;
+    if !output.status.success() {
+        return Err(VortexError::StorageError {
+            message: format!(
+                "Image conversion failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn remove_path(path: &Path) {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    if let Err(e) = result {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_directory_prepared_once_and_collected_when_source_changes() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = ImageCache::at(root.path().to_path_buf());
+
+        let rootfs = tempfile::tempdir().unwrap();
+        std::fs::create_dir(rootfs.path().join("etc")).unwrap();
+        std::fs::write(rootfs.path().join("etc/hostname"), "vortex\n").unwrap();
+        let status = std::process::Command::new("tar")
+            .arg("-cf")
+            .arg(cache.source_path("alpine:3.19"))
+            .arg("-C")
+            .arg(rootfs.path())
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        assert!(cache
+            .prepare("ubuntu", PreparedFormat::Directory)
+            .await
+            .unwrap()
+            .is_none());
+        let prepared = cache
+            .prepare("alpine:3.19", PreparedFormat::Directory)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(prepared.join("etc/hostname").is_file());
+        let again = cache
+            .prepare("alpine:3.19", PreparedFormat::Directory)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(prepared, again);
+        assert_eq!(cache.entries().unwrap().len(), 1);
+
+        assert!(cache.gc(chrono::Duration::days(1)).unwrap().is_empty());
+        std::fs::remove_file(cache.source_path("alpine:3.19")).unwrap();
+        assert_eq!(cache.gc(chrono::Duration::days(1)).unwrap().len(), 1);
+        assert!(!prepared.exists());
+    }
+}
@@ -0,0 +1,756 @@
+//! OCI images pulled straight from their registry.
+//!
+//! `vortex image pull <image>`, or creating a VM from an image that has no
+//! rootfs tarball yet, fetches the image's manifest for the host's platform
+//! and then its layers, anonymously and with `curl`. Blobs are cached by
+//! digest under `~/.vortex/images/blobs/sha256/`, so layers shared between
+//! images or tags download once. The layers are then flattened, applying
+//! their whiteouts, into the rootfs tarball at `~/.vortex/images/<image>.tar`
+//! that every backend booting from tarballs reads (see `image_cache`), and
+//! the pull is recorded in `~/.vortex/images/pulled/<image>.json`.
+//!
+//! References follow Docker's: `alpine` is `docker.io/library/alpine:latest`,
+//! and a first component with a `.` or `:`, or `localhost`, names the
+//! registry (`ghcr.io/org/tool:1.2`, `localhost:5000/app@sha256:...`).
+
+use crate::error::{Result, VortexError};
+use crate::image_cache::{image_key, ImageCache};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+const BLOBS_DIR: &str = "blobs/sha256";
+const PULLED_DIR: &str = "pulled";
+const DOCKER_HUB: &str = "docker.io";
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+const MANIFEST_TYPES: [&str; 4] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+/// Prefix of the whiteout entries deleting a lower layer's file
+const WHITEOUT: &str = ".wh.";
+/// Whiteout entry hiding everything lower layers put in its directory
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+fn pull_error(message: String) -> VortexError {
+    VortexError::StorageError { message }
+}
+
+/// Where an image is pulled from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    /// Tag or `sha256:` digest
+    pub reference: String,
+}
+
+impl ImageReference {
+    pub fn parse(image: &str) -> Result<Self> {
+        let invalid = || VortexError::InvalidInput {
+            field: "image".to_string(),
+            message: format!("Invalid image reference '{}'", image),
+        };
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest)),
+            None => (image, None),
+        };
+        let (registry, path) = match split_registry(name) {
+            (Some(registry), path) => (registry.to_string(), path),
+            (None, path) => (DOCKER_HUB.to_string(), path),
+        };
+        let (repository, tag) = match path.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag)),
+            _ => (path, None),
+        };
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository.to_string()
+        };
+
+        let valid_repository = !repository.is_empty()
+            && repository.split('/').all(|part| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c))
+            });
+        if !valid_repository {
+            return Err(invalid());
+        }
+        let reference = match (digest, tag) {
+            (Some(digest), _) => {
+                validate_digest(digest).map_err(|_| invalid())?;
+                digest.to_string()
+            }
+            (None, Some(tag)) if !tag.is_empty() => tag.to_string(),
+            (None, Some(_)) => return Err(invalid()),
+            (None, None) => "latest".to_string(),
+        };
+        Ok(Self {
+            registry,
+            repository,
+            reference,
+        })
+    }
+
+    /// Host serving the registry's API
+    fn api_host(&self) -> &str {
+        if self.registry == DOCKER_HUB {
+            DOCKER_HUB_API
+        } else {
+            &self.registry
+        }
+    }
+}
+
+/// The registry `image` names, if any, and the rest of the reference
+fn split_registry(image: &str) -> (Option<&str>, &str) {
+    match image.split_once('/') {
+        Some((first, rest))
+            if first.contains('.') || first.contains(':') || first == "localhost" =>
+        {
+            (Some(first), rest)
+        }
+        _ => (None, image),
+    }
+}
+
+/// `image` pulled from `registry`, e.g. a mirror, instead of Docker Hub.
+/// The mirror is expected to keep Docker Hub's paths, so `alpine` becomes
+/// `<registry>/library/alpine`; images of other registries are left alone.
+pub fn with_registry(image: &str, registry: &str) -> String {
+    let path = match split_registry(image) {
+        (None, path) => path,
+        (Some(DOCKER_HUB | "index.docker.io" | DOCKER_HUB_API), path) => path,
+        (Some(_), _) => return image.to_string(),
+    };
+    let registry = registry.trim_end_matches('/');
+    let repository = path.split(['@', ':']).next().unwrap_or_default();
+    if repository.contains('/') {
+        format!("{}/{}", registry, path)
+    } else {
+        format!("{}/library/{}", registry, path)
+    }
+}
+
+/// An image as last pulled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PulledImage {
+    pub image: String,
+    /// Digest of the image's manifest for this host's platform
+    pub digest: String,
+    /// Digests of its layers, bottom first
+    pub layers: Vec<String>,
+    /// Compressed size of its layers
+    pub size_bytes: u64,
+    pub pulled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    /// Per-platform manifests, when this is an index
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Descriptor {
+    digest: String,
+    #[serde(default)]
+    size: u64,
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+/// Architecture of this host as OCI platforms name it
+fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64le",
+        arch => arch,
+    }
+}
+
+fn validate_digest(digest: &str) -> Result<&str> {
+    match digest.strip_prefix("sha256:") {
+        Some(hex) if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(hex),
+        _ => Err(pull_error(format!("Unsupported digest '{}'", digest))),
+    }
+}
+
+fn file_digest(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+/// Parameters of a `WWW-Authenticate: Bearer realm="...",service="..."`
+/// challenge, whose quoted values may hold commas
+fn parse_challenge(header: &str) -> Option<HashMap<String, String>> {
+    let params = header.trim().strip_prefix("Bearer ")?;
+    let mut result = HashMap::new();
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, after) = quoted.split_once('"')?;
+                (value, after)
+            }
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        result.insert(key.trim().to_lowercase(), value.to_string());
+        rest = after.trim_start_matches([',', ' ']);
+    }
+    Some(result)
+}
+
+/// Anonymous client of one repository's registry API
+struct Registry {
+    reference: ImageReference,
+    token: Option<String>,
+}
+
+impl Registry {
+    /// GET `path` of the repository's API into `output`, fetching a pull
+    /// token first when the registry asks for one
+    async fn get(&mut self, path: &str, accept: &[&str], output: &Path) -> Result<()> {
+        let url = format!(
+            "https://{}/v2/{}/{}",
+            self.reference.api_host(),
+            self.reference.repository,
+            path
+        );
+        let (status, headers) = self.curl(&url, accept, output).await?;
+        let status = if status == 401 && self.token.is_none() {
+            let challenge = headers
+                .get("www-authenticate")
+                .and_then(|header| parse_challenge(header))
+                .ok_or_else(|| {
+                    pull_error(format!("{} needs credentials", self.reference.registry))
+                })?;
+            self.token = Some(self.fetch_token(&challenge).await?);
+            self.curl(&url, accept, output).await?.0
+        } else {
+            status
+        };
+        if !(200..300).contains(&status) {
+            let body = std::fs::read_to_string(output).unwrap_or_default();
+            let _ = std::fs::remove_file(output);
+            return Err(pull_error(format!(
+                "GET {} returned {}: {}",
+                url,
+                status,
+                body.chars().take(200).collect::<String>().trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Status and headers (lowercased names) of the last response of `url`
+    async fn curl(
+        &self,
+        url: &str,
+        accept: &[&str],
+        output: &Path,
+    ) -> Result<(u16, HashMap<String, String>)> {
+        let mut command = Command::new("curl");
+        // curl drops the Authorization header when following a redirect to
+        // another host, as blob downloads to a CDN need
+        command.args(["-sSL", "-D", "-", "-o"]).arg(output);
+        if !accept.is_empty() {
+            command.args(["-H", &format!("Accept: {}", accept.join(", "))]);
+        }
+        if let Some(token) = &self.token {
+            command.args(["-H", &format!("Authorization: Bearer {}", token)]);
+        }
+        let result = command.arg(url).output().await.map_err(|e| {
+            pull_error(format!(
+                "Failed to run curl, which pulling images needs: {}",
+                e
+            ))
+        })?;
+        if !result.status.success() {
+            return Err(pull_error(format!(
+                "Failed to reach {}: {}",
+                self.reference.registry,
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+
+        // Each redirect adds a block of headers; the last one is the response
+        let text = String::from_utf8_lossy(&result.stdout);
+        let last = text
+            .split("\r\n\r\n")
+            .filter(|block| block.starts_with("HTTP/"))
+            .last()
+            .unwrap_or_default();
+        let mut lines = last.lines();
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1)?.parse().ok())
+            .unwrap_or(0);
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        Ok((status, headers))
+    }
+
+    async fn fetch_token(&self, challenge: &HashMap<String, String>) -> Result<String> {
+        let realm = challenge
+            .get("realm")
+            .ok_or_else(|| pull_error("Authentication challenge without a realm".to_string()))?;
+        let scope = challenge
+            .get("scope")
+            .cloned()
+            .unwrap_or_else(|| format!("repository:{}:pull", self.reference.repository));
+        let mut command = Command::new("curl");
+        command.args(["-sSfL", "-G", realm, "--data-urlencode"]);
+        command.arg(format!("scope={}", scope));
+        if let Some(service) = challenge.get("service") {
+            command.args(["--data-urlencode", &format!("service={}", service)]);
+        }
+        let output = command.output().await?;
+        if !output.status.success() {
+            return Err(pull_error(format!(
+                "Failed to get a pull token from {}: {}",
+                realm,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+        let response: TokenResponse = serde_json::from_slice(&output.stdout)?;
+        response
+            .token
+            .or(response.access_token)
+            .ok_or_else(|| pull_error(format!("{} returned no token", realm)))
+    }
+}
+
+/// Apply the whiteout `entry` of a layer to the `root` the lower layers were
+/// unpacked into: delete the file it names, or empty its directory when it
+/// is opaque. Other entries are left alone.
+fn apply_whiteout(root: &Path, entry: &str) -> Result<()> {
+    let entry = entry.trim_start_matches("./").trim_start_matches('/');
+    let (parent, name) = entry.rsplit_once('/').unwrap_or(("", entry));
+    if !name.starts_with(WHITEOUT) {
+        return Ok(());
+    }
+    if parent.split('/').any(|part| part == "..") {
+        return Err(pull_error(format!(
+            "Layer entry {} leaves the rootfs",
+            entry
+        )));
+    }
+    let dir = root.join(parent);
+    let targets: Vec<PathBuf> = if name == OPAQUE_WHITEOUT {
+        match std::fs::read_dir(&dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+            Err(_) => Vec::new(),
+        }
+    } else {
+        match &name[WHITEOUT.len()..] {
+            "" | "." | ".." => Vec::new(),
+            hidden => vec![dir.join(hidden)],
+        }
+    };
+    for target in targets {
+        let result = match std::fs::symlink_metadata(&target) {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&target),
+            Ok(_) => std::fs::remove_file(&target),
+            Err(_) => Ok(()),
+        };
+        result?;
+    }
+    Ok(())
+}
+
+async fn run_tar(args: &[&std::ffi::OsStr]) -> Result<String> {
+    let output = Command::new("tar").args(args).output().await?;
+    if !output.status.success() {
+        return Err(pull_error(format!(
+            "Unpacking image layers failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub struct ImageStore {
+    root: PathBuf,
+}
+
+impl ImageStore {
+    /// The store under `~/.vortex/images`, next to the rootfs tarballs
+    pub fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self::at(home.join(".vortex").join("images")))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn blob_path(&self, digest: &str) -> Result<PathBuf> {
+        Ok(self.root.join(BLOBS_DIR).join(validate_digest(digest)?))
+    }
+
+    fn record_path(&self, image: &str) -> PathBuf {
+        self.root
+            .join(PULLED_DIR)
+            .join(format!("{}.json", image_key(image)))
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        self.root
+            .join(format!(".tmp-{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    /// The blob `digest`, downloaded unless it is cached
+    async fn blob(&self, registry: &mut Registry, digest: &str) -> Result<PathBuf> {
+        let path = self.blob_path(digest)?;
+        if path.is_file() {
+            return Ok(path);
+        }
+        std::fs::create_dir_all(self.root.join(BLOBS_DIR))?;
+        let tmp = self.temp_path();
+        registry
+            .get(&format!("blobs/{}", digest), &[], &tmp)
+            .await?;
+        let actual = file_digest(&tmp)?;
+        if actual != digest {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(pull_error(format!(
+                "Blob {} arrived with digest {}",
+                digest, actual
+            )));
+        }
+        std::fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Fetch the manifest `reference` and cache it as a blob, returning its
+    /// digest and contents
+    async fn manifest(
+        &self,
+        registry: &mut Registry,
+        reference: &str,
+    ) -> Result<(String, Manifest)> {
+        std::fs::create_dir_all(self.root.join(BLOBS_DIR))?;
+        let tmp = self.temp_path();
+        registry
+            .get(&format!("manifests/{}", reference), &MANIFEST_TYPES, &tmp)
+            .await?;
+        let digest = file_digest(&tmp)?;
+        if reference.starts_with("sha256:") && digest != reference {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(pull_error(format!(
+                "Manifest {} arrived with digest {}",
+                reference, digest
+            )));
+        }
+        let path = self.blob_path(&digest)?;
+        std::fs::rename(&tmp, &path)?;
+        let manifest = serde_json::from_slice(&std::fs::read(&path)?)?;
+        Ok((digest, manifest))
+    }
+
+    /// Pull `image` for this host's platform and flatten it into its rootfs
+    /// tarball
+    pub async fn pull(&self, image: &str) -> Result<PulledImage> {
+        let reference = ImageReference::parse(image)?;
+        tracing::info!(
+            "Pulling {} from {}",
+            reference.repository,
+            reference.registry
+        );
+        let mut registry = Registry {
+            reference: reference.clone(),
+            token: None,
+        };
+
+        let (mut digest, mut manifest) = self.manifest(&mut registry, &reference.reference).await?;
+        if !manifest.manifests.is_empty() {
+            let platform = manifest
+                .manifests
+                .iter()
+                .find(|m| {
+                    m.platform
+                        .as_ref()
+                        .is_some_and(|p| p.os == "linux" && p.architecture == host_architecture())
+                })
+                .ok_or_else(|| {
+                    pull_error(format!(
+                        "{} has no linux/{} image",
+                        image,
+                        host_architecture()
+                    ))
+                })?;
+            let platform_digest = platform.digest.clone();
+            (digest, manifest) = self.manifest(&mut registry, &platform_digest).await?;
+        }
+
+        let mut layers = Vec::new();
+        for layer in &manifest.layers {
+            layers.push(self.blob(&mut registry, &layer.digest).await?);
+        }
+        let pulled = PulledImage {
+            image: image.to_string(),
+            digest,
+            layers: manifest.layers.iter().map(|l| l.digest.clone()).collect(),
+            size_bytes: manifest.layers.iter().map(|l| l.size).sum(),
+            pulled_at: Utc::now(),
+        };
+
+        let tarball = ImageCache::at(self.root.clone()).source_path(image);
+        let unchanged = self
+            .get(image)?
+            .is_some_and(|previous| previous.digest == pulled.digest);
+        if !(unchanged && tarball.is_file()) {
+            self.flatten(&layers, &tarball).await?;
+        }
+
+        let record = self.record_path(image);
+        std::fs::create_dir_all(record.parent().unwrap_or(&self.root))?;
+        std::fs::write(record, serde_json::to_vec_pretty(&pulled)?)?;
+        Ok(pulled)
+    }
+
+    /// Pull `image` unless it already has a rootfs tarball
+    pub async fn ensure(&self, image: &str) -> Result<()> {
+        let tarball = ImageCache::at(self.root.clone()).source_path(image);
+        if tarball.is_file() {
+            return Ok(());
+        }
+        match self.pull(image).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(pull_error(format!(
+                "No rootfs tarball for {} at {}, and pulling it failed: {}",
+                image,
+                tarball.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Unpack `layers` over each other and pack the result as `tarball`
+    async fn flatten(&self, layers: &[PathBuf], tarball: &Path) -> Result<()> {
+        let staging = self.temp_path();
+        std::fs::create_dir_all(&staging)?;
+        let result = async {
+            for layer in layers {
+                // Whiteouts hide what lower layers put there, so they are
+                // applied before the layer's own files are unpacked
+                let entries = run_tar(&["-tf".as_ref(), layer.as_os_str()]).await?;
+                for entry in entries.lines() {
+                    apply_whiteout(&staging, entry)?;
+                }
+                run_tar(&[
+                    "-xf".as_ref(),
+                    layer.as_os_str(),
+                    "-C".as_ref(),
+                    staging.as_os_str(),
+                    "--exclude=.wh.*".as_ref(),
+                ])
+                .await?;
+            }
+            let tmp = self.temp_path();
+            let packed = run_tar(&[
+                "-cf".as_ref(),
+                tmp.as_os_str(),
+                "-C".as_ref(),
+                staging.as_os_str(),
+                ".".as_ref(),
+            ])
+            .await;
+            match packed {
+                Ok(_) => Ok(std::fs::rename(&tmp, tarball)?),
+                Err(e) => {
+                    let _ = std::fs::remove_file(&tmp);
+                    Err(e)
+                }
+            }
+        }
+        .await;
+        if let Err(e) = std::fs::remove_dir_all(&staging) {
+            tracing::warn!("Failed to remove {}: {}", staging.display(), e);
+        }
+        result
+    }
+
+    pub fn get(&self, image: &str) -> Result<Option<PulledImage>> {
+        match std::fs::read_to_string(self.record_path(image)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Cached blobs that no pulled image is made of: layers and manifests
+    /// of images pulled again since, and indexes pointing to a platform's
+    /// manifest
+    pub fn unreferenced_blobs(&self) -> Result<Vec<PathBuf>> {
+        let Ok(entries) = std::fs::read_dir(self.root.join(BLOBS_DIR)) else {
+            return Ok(Vec::new());
+        };
+        let mut referenced = std::collections::HashSet::new();
+        for image in self.list()? {
+            for digest in std::iter::once(&image.digest).chain(&image.layers) {
+                referenced.insert(self.blob_path(digest)?);
+            }
+        }
+        Ok(entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| !referenced.contains(path))
+            .collect())
+    }
+
+    /// Pulled images, by name
+    pub fn list(&self) -> Result<Vec<PulledImage>> {
+        let Ok(entries) = std::fs::read_dir(self.root.join(PULLED_DIR)) else {
+            return Ok(Vec::new());
+        };
+        let mut images: Vec<PulledImage> = entries
+            .flatten()
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        images.sort_by(|a, b| a.image.cmp(&b.image));
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_and_whiteouts() {
+        let alpine = ImageReference::parse("alpine").unwrap();
+        assert_eq!(alpine.api_host(), "registry-1.docker.io");
+        assert_eq!(alpine.repository, "library/alpine");
+        assert_eq!(alpine.reference, "latest");
+        assert_eq!(
+            with_registry("alpine:3.19", "mirror.corp:5000"),
+            "mirror.corp:5000/library/alpine:3.19"
+        );
+        assert_eq!(
+            with_registry("docker.io/grafana/grafana", "mirror.corp/hub/"),
+            "mirror.corp/hub/grafana/grafana"
+        );
+        assert_eq!(with_registry("ghcr.io/org/tool", "mirror.corp"), "ghcr.io/org/tool");
+        let tool = ImageReference::parse("localhost:5000/org/tool:1.2").unwrap();
+        assert_eq!(
+            (
+                tool.api_host(),
+                tool.repository.as_str(),
+                tool.reference.as_str()
+            ),
+            ("localhost:5000", "org/tool", "1.2")
+        );
+        let pinned = format!("ghcr.io/org/app@sha256:{}", "a".repeat(64));
+        assert_eq!(
+            ImageReference::parse(&pinned).unwrap().reference,
+            format!("sha256:{}", "a".repeat(64))
+        );
+        assert!(ImageReference::parse("Alpine").is_err());
+        assert!(ImageReference::parse("alpine@sha256:abc").is_err());
+
+        let challenge = parse_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(challenge["realm"], "https://auth.docker.io/token");
+        assert_eq!(challenge["scope"], "repository:library/alpine:pull,push");
+
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        std::fs::create_dir_all(root.join("etc/conf.d")).unwrap();
+        std::fs::write(root.join("etc/motd"), "hi").unwrap();
+        std::fs::write(root.join("etc/conf.d/a"), "a").unwrap();
+        apply_whiteout(root, "./etc/.wh.motd").unwrap();
+        apply_whiteout(root, "etc/conf.d/.wh..wh..opq").unwrap();
+        apply_whiteout(root, "etc/hostname").unwrap();
+        assert!(!root.join("etc/motd").exists());
+        assert!(root.join("etc/conf.d").is_dir());
+        assert!(!root.join("etc/conf.d/a").exists());
+        assert!(apply_whiteout(root, "../etc/.wh.passwd").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_layers_flatten_with_whiteouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ImageStore::at(dir.path().to_path_buf());
+        let layer = |name: &str, files: &[(&str, &str)]| {
+            let src = dir.path().join(format!("{}-src", name));
+            for (path, content) in files {
+                let path = src.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, content).unwrap();
+            }
+            let tarball = dir.path().join(format!("{}.tar.gz", name));
+            let status = std::process::Command::new("tar")
+                .arg("-czf")
+                .arg(&tarball)
+                .arg("-C")
+                .arg(&src)
+                .arg(".")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            tarball
+        };
+        let base = layer("base", &[("etc/motd", "hi"), ("var/cache/a", "a")]);
+        let top = layer(
+            "top",
+            &[
+                ("etc/.wh.motd", ""),
+                ("var/cache/.wh..wh..opq", ""),
+                ("var/cache/b", "b"),
+            ],
+        );
+
+        let tarball = dir.path().join("app.tar");
+        store.flatten(&[base, top], &tarball).await.unwrap();
+        let listing = run_tar(&["-tf".as_ref(), tarball.as_os_str()])
+            .await
+            .unwrap();
+        assert!(listing.contains("./var/cache/b"));
+        assert!(!listing.contains("motd"));
+        assert!(!listing.contains("cache/a"));
+        assert!(!listing.contains(".wh."));
+    }
+}
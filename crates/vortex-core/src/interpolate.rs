@@ -0,0 +1,159 @@
+//! `${VAR}` and `${VAR:-default}` in config values, read from the
+//! environment Vortex runs in.
+//!
+//! String values of `config.toml` and `vortex.yaml` are expanded when the
+//! files are loaded, and the environment of a dev template when a VM is
+//! made from it, so that tokens and machine-specific paths stay out of
+//! files that get committed:
+//!
+//! ```toml
+//! [templates.api.environment]
+//! GITHUB_TOKEN = "${GITHUB_TOKEN}"
+//! CACHE_DIR = "${XDG_CACHE_HOME:-/var/cache}/api"
+//! ```
+//!
+//! `${VAR:-default}` gives `default` when `VAR` is unset or empty. A
+//! variable that is not set and has no default expands to nothing, with a
+//! warning. `$${` stands for a literal `${`, e.g. in a hook that should see
+//! a variable of the guest; a `$` not followed by `{` is left alone.
+
+use crate::error::{Result, VortexError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// Expand the references in `input` from the process environment
+pub fn expand(input: &str) -> Result<String> {
+    expand_with(input, |name| std::env::var(name).ok())
+}
+
+/// Expand the references in `input`, looking variables up with `lookup`
+pub fn expand_with(input: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let invalid = |message: String| VortexError::ConfigError {
+        message: format!("{} in '{}'", message, input),
+    };
+    let mut expanded = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let at = &rest[start..];
+        if let Some(after) = at.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(reference) = at.strip_prefix("${") else {
+            expanded.push('$');
+            rest = &at[1..];
+            continue;
+        };
+        let end = reference
+            .find('}')
+            .ok_or_else(|| invalid("Unterminated ${".to_string()))?;
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+        if !is_variable_name(name) {
+            return Err(invalid(format!("Invalid variable name '{}'", name)));
+        }
+        match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => expanded.push_str(default),
+            (Some(value), _) => expanded.push_str(&value),
+            (None, Some(default)) => expanded.push_str(default),
+            (None, None) => warn_unset(name),
+        }
+        rest = &reference[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Expand the references in every string value of `value`, leaving keys
+/// alone
+pub fn expand_values<T: Serialize + DeserializeOwned>(value: T) -> Result<T> {
+    let mut tree = serde_json::to_value(&value)?;
+    if !expand_tree(&mut tree, "")? {
+        return Ok(value);
+    }
+    Ok(serde_json::from_value(tree)?)
+}
+
+/// Whether anything under `value` was expanded; errors name the `path`
+fn expand_tree(value: &mut Value, path: &str) -> Result<bool> {
+    let child = |key: &str| match path {
+        "" => key.to_string(),
+        _ => format!("{}.{}", path, key),
+    };
+    match value {
+        Value::String(text) if text.contains("${") => {
+            *text = expand(text).map_err(|e| VortexError::ConfigError {
+                message: format!("{}: {}", path, reason(e)),
+            })?;
+            Ok(true)
+        }
+        Value::Array(items) => {
+            let mut expanded = false;
+            for (index, item) in items.iter_mut().enumerate() {
+                expanded |= expand_tree(item, &format!("{}[{}]", path, index))?;
+            }
+            Ok(expanded)
+        }
+        Value::Object(fields) => {
+            let mut expanded = false;
+            for (key, field) in fields.iter_mut() {
+                expanded |= expand_tree(field, &child(key))?;
+            }
+            Ok(expanded)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn reason(error: VortexError) -> String {
+    match error {
+        VortexError::ConfigError { message } => message,
+        error => error.to_string(),
+    }
+}
+
+/// Files are loaded several times per command, so each variable is only
+/// warned about once
+fn warn_unset(name: &str) {
+    static WARNED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned.insert(name.to_string()) {
+        tracing::warn!("{} is not set; using an empty value", name);
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_references_expand_from_the_environment() {
+        let env = HashMap::from([("HOME", "/home/me"), ("EMPTY", "")]);
+        let lookup = |name: &str| env.get(name).map(|value| value.to_string());
+        let expand = |input| expand_with(input, lookup).unwrap();
+        assert_eq!(expand("${HOME}/.cache"), "/home/me/.cache");
+        assert_eq!(expand("${EMPTY:-/tmp}:${UNSET:-x}"), "/tmp:x");
+        assert_eq!(expand("${UNSET}"), "");
+        assert_eq!(expand("echo $$ $HOME $${HOME}"), "echo $$ $HOME ${HOME}");
+        assert!(expand_with("${HOME", lookup).is_err());
+        assert!(expand_with("${1X}", lookup).is_err());
+
+        let mut tree = serde_json::json!({ "env": [{ "A": "${1X}" }] });
+        let err = expand_tree(&mut tree, "").unwrap_err();
+        assert!(err.to_string().contains("env[0].A: Invalid variable name"));
+    }
+}
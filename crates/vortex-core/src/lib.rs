@@ -0,0 +1,96 @@
+//! # Vortex Core
+//!
+//! The foundational library for the Vortex ephemeral VM platform.
+//! Provides abstractions for VM lifecycle management, networking, storage,
+//! and extensibility for specialized use cases.
+//!
+//! This crate is the VM lifecycle API only: [`VmManager`] runs VMs on any
+//! [`Backend`] it is given. The backends themselves live in
+//! `vortex-backends`, and sessions and the daemon in `vortex-daemon`, so
+//! embedding Vortex does not pull either in.
+//!
+//! ## Security Notice
+//!
+//! This library uses a NoOpAuthProvider by default for development purposes.
+//! This provider is INSECURE and should only be used for local development.
+//! Before using in any shared or production environment:
+//! - Implement a proper AuthProvider
+//! - Add authentication to the daemon socket
+//! - Enable plugin signature verification
+//! - Review and restrict resource limits
+
+pub mod archive;
+pub mod audit;
+pub mod auth;
+pub mod backend;
+pub mod config;
+pub mod credentials;
+pub mod dep_cache;
+pub mod dev_project;
+pub mod diagnostics;
+pub mod dns;
+pub mod dotfiles;
+pub mod error;
+pub mod event_queue;
+pub mod events;
+pub mod handover;
+pub mod home_volume;
+pub mod ids;
+pub mod image_cache;
+pub mod image_store;
+pub mod interpolate;
+pub mod listing;
+pub mod logs;
+pub mod metrics;
+pub mod native_plugin;
+pub mod network;
+pub mod nix;
+pub mod oidc;
+pub mod plugin;
+pub mod policy;
+pub mod pool;
+pub mod process;
+pub mod process_plugin;
+pub mod progress;
+pub mod project;
+pub mod provision;
+pub mod rules;
+pub mod run_dir;
+pub mod sealed;
+pub mod secrets;
+pub mod snapshot;
+pub mod storage;
+pub mod templates;
+#[cfg(feature = "tls")]
+pub mod tls;
+mod transfer;
+pub mod tuning;
+pub mod tunnel;
+pub mod validation;
+pub mod vm;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm_plugin;
+pub mod workspace;
+
+// Re-export core types
+pub use archive::{ArchiveKind, ArchiveManifest};
+pub use auth::{AuthProvider, Permission, TokenAuthProvider};
+pub use backend::{Backend, BackendProvider, ExecOptions, ExecResult, ExitStatus};
+pub use config::{Template, VortexConfig};
+pub use error::{Result, VortexError};
+pub use listing::{ListQuery, Page};
+pub use metrics::{MetricsCollector, SystemMetrics, VmMetrics};
+pub use network::{NetworkConfig, NetworkLimits, NetworkManager};
+pub use plugin::{Plugin, PluginManager};
+pub use snapshot::{SnapshotRecord, SnapshotStore};
+pub use storage::{LayerStore, StorageManager, Volume, VolumeSnapshot};
+pub use templates::{DevEnvironmentManager, DevOverrides, DevTemplate, TemplateOrigin};
+pub use tuning::TuningProfile;
+pub use vm::{
+    GpuDevice, LifecycleHooks, NetworkPolicy, Probe, ProbeCheck, ResourceLimits, ShareMechanism,
+    VmEvent, VmInstance, VmManager, VmSpec, VmState,
+};
+pub use workspace::{detect_template, detect_workspace_info, Workspace, WorkspaceInfo, WorkspaceManager};
+
+/// Vortex platform version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
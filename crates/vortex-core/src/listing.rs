@@ -0,0 +1,219 @@
+//! Paginated, filtered listing shared by the session and VM managers.
+//!
+//! Items are ordered by ID and paged with an opaque cursor (the last ID of the
+//! previous page), so pages stay stable while the fleet changes underneath.
+
+use crate::error::{Result, VortexError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Page size used when the client does not ask for one
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+/// Upper bound on a single page, regardless of what the client asks for
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListQuery {
+    /// Return items after this cursor (from a previous page's `next_cursor`)
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Only items carrying all of these labels
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Only items in this state (e.g. "running", "stopped")
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Only items whose ID or name starts with this prefix
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Top-level fields to return; all fields when empty. `id` is always included.
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page, `None` on the last page
+    pub next_cursor: Option<String>,
+    /// Number of items matching the filters across all pages
+    pub total: usize,
+}
+
+/// Items that can be listed through a [`ListQuery`]
+pub trait Listable {
+    fn list_id(&self) -> &str;
+    fn list_name(&self) -> Option<&str>;
+    fn list_state(&self) -> &'static str;
+    fn list_labels(&self) -> &HashMap<String, String>;
+}
+
+impl ListQuery {
+    pub fn effective_limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn matches<T: Listable>(&self, item: &T) -> bool {
+        if let Some(state) = &self.state {
+            if !item.list_state().eq_ignore_ascii_case(state) {
+                return false;
+            }
+        }
+
+        if let Some(prefix) = &self.prefix {
+            let name_match = item.list_name().is_some_and(|n| n.starts_with(prefix));
+            if !item.list_id().starts_with(prefix) && !name_match {
+                return false;
+            }
+        }
+
+        let labels = item.list_labels();
+        self.labels
+            .iter()
+            .all(|(k, v)| labels.get(k).is_some_and(|actual| actual == v))
+    }
+
+    /// Filter, sort and slice `items` into a single page
+    pub fn paginate<T: Listable>(&self, items: impl IntoIterator<Item = T>) -> Page<T> {
+        let mut matching: Vec<T> = items.into_iter().filter(|i| self.matches(i)).collect();
+        matching.sort_by(|a, b| a.list_id().cmp(b.list_id()));
+        let total = matching.len();
+
+        let start = match &self.cursor {
+            Some(cursor) => matching.partition_point(|i| i.list_id() <= cursor.as_str()),
+            None => 0,
+        };
+        let limit = self.effective_limit();
+        let mut items: Vec<T> = matching.into_iter().skip(start).take(limit + 1).collect();
+
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|i| i.list_id().to_string())
+        } else {
+            None
+        };
+
+        Page {
+            items,
+            next_cursor,
+            total,
+        }
+    }
+
+    /// Reduce each item to the requested fields
+    pub fn project<T: Serialize>(&self, page: Page<T>) -> Result<Page<serde_json::Value>> {
+        let items = page
+            .items
+            .iter()
+            .map(|item| project_fields(item, &self.fields))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Page {
+            items,
+            next_cursor: page.next_cursor,
+            total: page.total,
+        })
+    }
+}
+
+fn project_fields<T: Serialize>(item: &T, fields: &[String]) -> Result<serde_json::Value> {
+    let value = serde_json::to_value(item)?;
+    if fields.is_empty() {
+        return Ok(value);
+    }
+
+    let serde_json::Value::Object(map) = value else {
+        return Err(VortexError::InvalidInput {
+            field: "fields".to_string(),
+            message: "Field projection requires object items".to_string(),
+        });
+    };
+
+    Ok(serde_json::Value::Object(
+        map.into_iter()
+            .filter(|(k, _)| k == "id" || fields.iter().any(|f| f == k))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Serialize)]
+    struct Item {
+        id: String,
+        state: &'static str,
+        labels: HashMap<String, String>,
+    }
+
+    impl Listable for Item {
+        fn list_id(&self) -> &str {
+            &self.id
+        }
+        fn list_name(&self) -> Option<&str> {
+            None
+        }
+        fn list_state(&self) -> &'static str {
+            self.state
+        }
+        fn list_labels(&self) -> &HashMap<String, String> {
+            &self.labels
+        }
+    }
+
+    fn items(n: usize) -> Vec<Item> {
+        (0..n)
+            .map(|i| Item {
+                id: format!("vm-{:03}", i),
+                state: if i % 2 == 0 { "running" } else { "stopped" },
+                labels: HashMap::from([("ci".to_string(), (i % 3 == 0).to_string())]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cursor_walks_all_pages() {
+        let query = ListQuery {
+            limit: Some(4),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = ListQuery {
+                cursor: cursor.clone(),
+                ..query.clone()
+            }
+            .paginate(items(10));
+            assert_eq!(page.total, 10);
+            seen.extend(page.items.into_iter().map(|i| i.id));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 10);
+        assert_eq!(seen[9], "vm-009");
+    }
+
+    #[test]
+    fn test_filters_and_projection() {
+        let query = ListQuery {
+            state: Some("running".to_string()),
+            labels: HashMap::from([("ci".to_string(), "true".to_string())]),
+            fields: vec!["state".to_string()],
+            ..Default::default()
+        };
+        let page = query.project(query.paginate(items(10))).unwrap();
+        // Even indices that are multiples of 3: 0, 6
+        assert_eq!(page.total, 2);
+        let obj = page.items[0].as_object().unwrap();
+        assert!(obj.contains_key("id") && obj.contains_key("state"));
+        assert!(!obj.contains_key("labels"));
+    }
+}
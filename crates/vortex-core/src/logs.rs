@@ -0,0 +1,118 @@
+//! Guest console output captured per VM under `~/.vortex/logs/<vm-id>.log`.
+//!
+//! Backends point the output of the VM command (or the whole console, where
+//! the VMM can record it) at [`log_path`]. Logs outlive their VM so a run can
+//! be inspected after the fact; `vortex cleanup` removes them.
+
+use crate::error::{Result, VortexError};
+use futures::stream::{self, Stream};
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// How often a followed log is checked for new output
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+fn logs_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("logs"))
+}
+
+/// Log file of `vm_id`, whose directory exists once this returns
+pub fn log_path(vm_id: &str) -> Result<PathBuf> {
+    let root = logs_root()?;
+    fs::create_dir_all(&root)?;
+    Ok(root.join(format!("{}.log", vm_id)))
+}
+
+/// Start an empty log for `vm_id` for a backend to redirect output into
+pub fn create(vm_id: &str) -> Result<fs::File> {
+    Ok(fs::File::create(log_path(vm_id)?)?)
+}
+
+/// Delete the log of `vm_id`, if there is one
+pub fn remove(vm_id: &str) -> Result<()> {
+    match fs::remove_file(logs_root()?.join(format!("{}.log", vm_id))) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A line of console output without its line ending. Consoles end lines
+/// with `\r\n`.
+fn trim_line(line: &str) -> String {
+    line.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// Lines of `file` from the start. With `follow`, lines written later are
+/// read as they arrive until `running` reports the VM gone, after which
+/// the rest of the file is drained.
+pub(crate) fn lines<F, Fut>(
+    file: tokio::fs::File,
+    follow: bool,
+    running: F,
+) -> impl Stream<Item = Result<String>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    // (reader, running, draining, finished)
+    let state = (BufReader::new(file), running, !follow, false);
+    stream::unfold(
+        state,
+        |(mut reader, mut running, mut draining, finished)| async move {
+            if finished {
+                return None;
+            }
+            let mut line = String::new();
+            loop {
+                match reader.read_line(&mut line).await {
+                    Err(e) => return Some((Err(e.into()), (reader, running, draining, true))),
+                    Ok(_) if line.ends_with('\n') => {
+                        return Some((Ok(trim_line(&line)), (reader, running, draining, false)))
+                    }
+                    // End of file, possibly after a partial line
+                    Ok(_) if draining => {
+                        if line.is_empty() {
+                            return None;
+                        }
+                        return Some((Ok(trim_line(&line)), (reader, running, draining, true)));
+                    }
+                    Ok(_) => {
+                        if running().await {
+                            tokio::time::sleep(FOLLOW_INTERVAL).await;
+                        } else {
+                            draining = true;
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_lines_trim_console_endings_and_keep_partial_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vm.log");
+        fs::write(&path, "booting\r\nready\nno newline").unwrap();
+
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let lines: Vec<String> = lines(file, true, || async { false })
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        assert_eq!(lines, ["booting", "ready", "no newline"]);
+    }
+}
@@ -0,0 +1,773 @@
+//! Metrics of running VMs: the latest of each VM, and a history of samples
+//! taken every [`SAMPLE_INTERVAL`] that outlives the VM.
+//!
+//! The history of a VM is a JSON-lines file under `~/.vortex/metrics`,
+//! kept to its last [`HISTORY_SAMPLES`] samples like a ring buffer, so
+//! `vortex metrics --since` can show what a VM did after it finished and
+//! from another process than the one that ran it.
+//!
+//! Samples are also checked against the `[[alerts]]` of the config: a VM
+//! over an alert's threshold for its `for_secs` is reported once as a
+//! `metric_alert` event, and the alert's command and webhook run, until the
+//! VM drops back under the threshold.
+//!
+//! The samples of a VM also add up to a [`VmUsage`]: the CPU time, peak
+//! memory and wall time it took, which `vortex run` reports at the end.
+
+use crate::backend;
+use crate::config::get_config_path;
+use crate::error::{Result, VortexError};
+use crate::events::{EventEnvelope, EventPayload};
+use crate::ids::VmId;
+use crate::vm::{VmEvent, VmEventHandler, VmManager, VmState};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::ops::RangeBounds;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::RwLock;
+
+/// Maximum age for metrics entries before automatic eviction (in hours)
+const MAX_METRICS_AGE_HOURS: i64 = 24;
+/// How often `watch` samples each VM
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+/// Samples kept per VM, a day's worth at `SAMPLE_INTERVAL`
+pub const HISTORY_SAMPLES: usize = 8640;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmMetrics {
+    pub vm_id: String,
+    pub cpu_usage_percent: f64,
+    pub memory_usage_bytes: u64,
+    pub memory_total_bytes: u64,
+    pub disk_usage_bytes: u64,
+    pub network_rx_bytes: u64,
+    pub network_tx_bytes: u64,
+    pub uptime_seconds: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl VmMetrics {
+    /// What a backend reported for `vm_id`, taken now
+    pub fn sampled(vm_id: &str, metrics: &backend::VmMetrics) -> Self {
+        Self {
+            vm_id: vm_id.to_string(),
+            cpu_usage_percent: metrics.cpu_usage,
+            memory_usage_bytes: metrics.memory_usage,
+            memory_total_bytes: metrics.memory_total,
+            disk_usage_bytes: metrics.disk_usage,
+            network_rx_bytes: metrics.network_rx,
+            network_tx_bytes: metrics.network_tx,
+            uptime_seconds: metrics.uptime_seconds,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// What one VM used over its life, for accounting
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VmUsage {
+    pub vm_id: String,
+    /// CPU time, estimated from the CPU usage sampled every
+    /// [`SAMPLE_INTERVAL`]
+    pub cpu_seconds: f64,
+    pub peak_memory_bytes: u64,
+    pub wall_seconds: f64,
+}
+
+impl VmUsage {
+    /// Usage of a VM that ran from `started` to `ended`, from its samples
+    /// in that time, oldest first. Each sample's CPU usage counts for the
+    /// time since the one before it.
+    pub fn from_samples(
+        vm_id: &str,
+        samples: &[VmMetrics],
+        started: DateTime<Utc>,
+        ended: DateTime<Utc>,
+    ) -> Self {
+        let mut usage = Self {
+            vm_id: vm_id.to_string(),
+            wall_seconds: seconds_between(started, ended),
+            ..Self::default()
+        };
+        let mut last = started;
+        for sample in samples {
+            usage.cpu_seconds +=
+                sample.cpu_usage_percent / 100.0 * seconds_between(last, sample.timestamp);
+            usage.peak_memory_bytes = usage.peak_memory_bytes.max(sample.memory_usage_bytes);
+            last = last.max(sample.timestamp);
+        }
+        usage
+    }
+
+    /// Usage of several VMs together, as `vm_id`. Their peaks are added
+    /// up, as if they all peaked at once.
+    pub fn total<'a>(vm_id: &str, usages: impl IntoIterator<Item = &'a VmUsage>) -> Self {
+        usages.into_iter().fold(
+            Self {
+                vm_id: vm_id.to_string(),
+                ..Self::default()
+            },
+            |mut total, usage| {
+                total.cpu_seconds += usage.cpu_seconds;
+                total.peak_memory_bytes += usage.peak_memory_bytes;
+                total.wall_seconds += usage.wall_seconds;
+                total
+            },
+        )
+    }
+}
+
+fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_milliseconds().max(0) as f64 / 1000.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemMetrics {
+    pub total_vms: u32,
+    pub running_vms: u32,
+    pub total_cpu_usage: f64,
+    pub total_memory_usage: u64,
+    pub total_memory_allocated: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// What an alert watches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// CPU use in percent, where 100 is one host CPU
+    Cpu,
+    /// Memory use in percent of the VM's memory
+    Memory,
+    /// Disk use in MB
+    Disk,
+}
+
+impl AlertMetric {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlertMetric::Cpu => "cpu",
+            AlertMetric::Memory => "memory",
+            AlertMetric::Disk => "disk",
+        }
+    }
+
+    /// The value of this metric in `metrics`, in the unit of its thresholds
+    pub fn value(self, metrics: &VmMetrics) -> f64 {
+        match self {
+            AlertMetric::Cpu => metrics.cpu_usage_percent,
+            AlertMetric::Memory if metrics.memory_total_bytes == 0 => 0.0,
+            AlertMetric::Memory => {
+                metrics.memory_usage_bytes as f64 / metrics.memory_total_bytes as f64 * 100.0
+            }
+            AlertMetric::Disk => metrics.disk_usage_bytes as f64 / 1024.0 / 1024.0,
+        }
+    }
+}
+
+/// An `[[alerts]]` table of the config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricAlert {
+    pub name: String,
+    pub metric: AlertMetric,
+    /// Value the metric must go over
+    pub threshold: f64,
+    /// How long a VM must stay over the threshold before the alert fires
+    #[serde(default)]
+    pub for_secs: u64,
+    /// Run with `sh -c` on the host when the alert fires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// URL the event is POSTed to as JSON when the alert fires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+impl MetricAlert {
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "alerts".to_string(),
+            message,
+        };
+        if self.name.trim().is_empty() {
+            return Err(invalid("Alert without a name".to_string()));
+        }
+        if !self.threshold.is_finite() || self.threshold <= 0.0 {
+            return Err(invalid(format!(
+                "Alert '{}' needs a threshold above 0",
+                self.name
+            )));
+        }
+        if let Some(webhook) = &self.webhook {
+            if !webhook.starts_with("http://") && !webhook.starts_with("https://") {
+                return Err(invalid(format!(
+                    "Alert '{}' has webhook '{}', which is not an http(s) URL",
+                    self.name, webhook
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Since when a VM is over the threshold of an alert, and whether the alert
+/// fired for it yet
+struct Breach {
+    since: DateTime<Utc>,
+    fired: bool,
+}
+
+/// Samples of one VM: the newest of its file, and how many lines the file
+/// holds, so it is rewritten to the newest once it grew to twice as many
+struct History {
+    samples: VecDeque<VmMetrics>,
+    lines: usize,
+}
+
+pub struct MetricsCollector {
+    vm_metrics: RwLock<HashMap<String, VmMetrics>>,
+    system_metrics: RwLock<SystemMetrics>,
+    history: RwLock<HashMap<String, History>>,
+    /// Where histories are kept; in memory only when `None`
+    history_dir: Option<PathBuf>,
+    alerts: Vec<MetricAlert>,
+    /// Breaches of `alerts`, by alert name and VM
+    breaches: Mutex<HashMap<(String, String), Breach>>,
+}
+
+impl MetricsCollector {
+    /// A collector keeping histories under `~/.vortex/metrics`, or in
+    /// memory when there is no home directory
+    pub async fn new() -> Result<Self> {
+        let history_dir = dirs::home_dir().map(|home| home.join(".vortex").join("metrics"));
+        Ok(Self::with_history_dir(history_dir))
+    }
+
+    pub fn with_history_dir(history_dir: Option<PathBuf>) -> Self {
+        Self {
+            history: RwLock::new(HashMap::new()),
+            history_dir,
+            alerts: Vec::new(),
+            breaches: Mutex::new(HashMap::new()),
+            vm_metrics: RwLock::new(HashMap::new()),
+            system_metrics: RwLock::new(SystemMetrics {
+                total_vms: 0,
+                running_vms: 0,
+                total_cpu_usage: 0.0,
+                total_memory_usage: 0,
+                total_memory_allocated: 0,
+                timestamp: chrono::Utc::now(),
+            }),
+        }
+    }
+
+    /// Check samples against the valid ones of `alerts`; invalid ones are
+    /// logged and skipped
+    pub fn with_alerts(mut self, alerts: Vec<MetricAlert>) -> Self {
+        self.alerts = alerts
+            .into_iter()
+            .filter(|alert| match alert.validate() {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("Skipping alert: {}", e);
+                    false
+                }
+            })
+            .collect();
+        self
+    }
+
+    pub async fn record_vm_metrics(&self, metrics: VmMetrics) {
+        if let Err(e) = self.append_history(&metrics).await {
+            tracing::warn!("Could not save metrics of VM {}: {}", metrics.vm_id, e);
+        }
+        self.vm_metrics
+            .write()
+            .await
+            .insert(metrics.vm_id.clone(), metrics);
+
+        // Evict stale metrics to prevent memory leaks
+        self.evict_stale_metrics().await;
+
+        // Update system metrics
+        self.update_system_metrics().await;
+    }
+
+    /// Remove metrics entries older than MAX_METRICS_AGE_HOURS
+    async fn evict_stale_metrics(&self) {
+        let now = chrono::Utc::now();
+        let cutoff = now - chrono::Duration::hours(MAX_METRICS_AGE_HOURS);
+
+        let stale_ids: Vec<String> = self
+            .vm_metrics
+            .read()
+            .await
+            .values()
+            .filter(|m| m.timestamp < cutoff)
+            .map(|m| m.vm_id.clone())
+            .collect();
+
+        if !stale_ids.is_empty() {
+            let mut vm_metrics = self.vm_metrics.write().await;
+            for id in &stale_ids {
+                vm_metrics.remove(id);
+            }
+            tracing::debug!(
+                "Evicted {} stale metrics entries (older than {} hours)",
+                stale_ids.len(),
+                MAX_METRICS_AGE_HOURS
+            );
+        }
+    }
+
+    /// Samples of `vm_id` taken within `range`, oldest first. These stay
+    /// after the VM stopped, whichever process sampled them.
+    pub async fn history(
+        &self,
+        vm_id: &str,
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> Result<Vec<VmMetrics>> {
+        let samples = match self.history_path(vm_id)? {
+            Some(path) => read_history(&path)?,
+            None => {
+                let history = self.history.read().await;
+                history
+                    .get(vm_id)
+                    .map(|h| h.samples.iter().cloned().collect())
+                    .unwrap_or_default()
+            }
+        };
+        Ok(samples
+            .into_iter()
+            .filter(|sample| range.contains(&sample.timestamp))
+            .collect())
+    }
+
+    /// What `vm_id` used from its start until now, after one last sample
+    /// while it is still up. For a VM that is gone, what its samples show.
+    pub async fn usage(&self, manager: &VmManager, vm_id: &VmId) -> Result<VmUsage> {
+        let vm = manager.get(vm_id).await?;
+        if let Some(vm) = &vm {
+            if !matches!(vm.state, VmState::Stopped | VmState::Error { .. }) {
+                match vm.backend.get_metrics(vm).await {
+                    Ok(metrics) => {
+                        self.record_vm_metrics(VmMetrics::sampled(vm_id, &metrics))
+                            .await
+                    }
+                    Err(e) => tracing::debug!("No final metrics for VM {}: {}", vm_id, e),
+                }
+            }
+        }
+        let samples = match &vm {
+            Some(vm) => self.history(vm_id, vm.created_at..).await?,
+            None => self.history(vm_id, ..).await?,
+        };
+        let (started, ended) = match (&vm, samples.first(), samples.last()) {
+            (Some(vm), _, _) => (vm.created_at, Utc::now()),
+            (None, Some(first), Some(last)) => (
+                first.timestamp - chrono::Duration::seconds(first.uptime_seconds as i64),
+                last.timestamp,
+            ),
+            _ => {
+                return Err(VortexError::VmError {
+                    message: format!("No metrics recorded for VM {}", vm_id),
+                })
+            }
+        };
+        Ok(VmUsage::from_samples(vm_id, &samples, started, ended))
+    }
+
+    /// Record the metrics of every VM `manager` tracks that is up, and
+    /// fire the alerts they set off
+    pub async fn sample(&self, manager: &VmManager) {
+        let usage = manager.resource_usage().await;
+        self.lock_breaches()
+            .retain(|(_, vm_id), _| usage.iter().any(|(id, _)| id == vm_id));
+        for (vm_id, metrics) in usage {
+            let sample = VmMetrics::sampled(&vm_id, &metrics);
+            for (alert, value) in self.check_alerts(&sample) {
+                tracing::warn!(
+                    "Alert '{}': VM {} is at {:.1} {}, over {}",
+                    alert.name,
+                    vm_id,
+                    value,
+                    alert.metric.name(),
+                    alert.threshold
+                );
+                let event = VmEvent::MetricAlert {
+                    vm_id: vm_id.clone(),
+                    alert: alert.name.clone(),
+                    metric: alert.metric.name().to_string(),
+                    value,
+                    threshold: alert.threshold,
+                };
+                notify(alert, &vm_id, &EventPayload::from(event.clone()));
+                if let Err(e) = manager.emit_event(event).await {
+                    tracing::warn!("{}", e);
+                }
+            }
+            self.record_vm_metrics(sample).await;
+        }
+    }
+
+    /// Alerts `sample` has kept over their threshold for their `for_secs`,
+    /// with the value of their metric. Each fires once per breach.
+    fn check_alerts(&self, sample: &VmMetrics) -> Vec<(&MetricAlert, f64)> {
+        let mut breaches = self.lock_breaches();
+        let mut fired = Vec::new();
+        for alert in &self.alerts {
+            let key = (alert.name.clone(), sample.vm_id.clone());
+            let value = alert.metric.value(sample);
+            if value <= alert.threshold {
+                breaches.remove(&key);
+                continue;
+            }
+            let breach = breaches.entry(key).or_insert(Breach {
+                since: sample.timestamp,
+                fired: false,
+            });
+            let lasted = (sample.timestamp - breach.since).num_seconds();
+            if !breach.fired && lasted >= alert.for_secs as i64 {
+                breach.fired = true;
+                fired.push((alert, value));
+            }
+        }
+        fired
+    }
+
+    fn lock_breaches(&self) -> std::sync::MutexGuard<'_, HashMap<(String, String), Breach>> {
+        self.breaches
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sample the VMs of `manager` every `SAMPLE_INTERVAL` for as long as
+    /// both are alive
+    pub fn watch(self: &Arc<Self>, manager: &Arc<VmManager>) -> tokio::task::JoinHandle<()> {
+        let collector = Arc::downgrade(self);
+        let manager = Arc::downgrade(manager);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                let (Some(collector), Some(manager)) = (collector.upgrade(), manager.upgrade())
+                else {
+                    return;
+                };
+                collector.sample(&manager).await;
+            }
+        })
+    }
+
+    fn history_path(&self, vm_id: &str) -> Result<Option<PathBuf>> {
+        // IDs end up in a file name
+        if vm_id.is_empty()
+            || !vm_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(VortexError::InvalidInput {
+                field: "vm_id".to_string(),
+                message: format!("Invalid VM ID: {}", vm_id),
+            });
+        }
+        Ok(self
+            .history_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.jsonl", vm_id))))
+    }
+
+    /// Add `metrics` to the history of its VM, dropping the oldest sample
+    /// once there are `HISTORY_SAMPLES`
+    async fn append_history(&self, metrics: &VmMetrics) -> Result<()> {
+        let path = self.history_path(&metrics.vm_id)?;
+        let mut history = self.history.write().await;
+        let history = match history.entry(metrics.vm_id.clone()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                // Pick up where another run of this VM left off
+                let samples = match &path {
+                    Some(path) => read_history(path)?,
+                    None => Vec::new(),
+                };
+                let lines = samples.len();
+                let skip = lines.saturating_sub(HISTORY_SAMPLES);
+                entry.insert(History {
+                    samples: samples.into_iter().skip(skip).collect(),
+                    lines,
+                })
+            }
+        };
+        history.samples.push_back(metrics.clone());
+        if history.samples.len() > HISTORY_SAMPLES {
+            history.samples.pop_front();
+        }
+        let Some(path) = path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if history.lines + 1 >= 2 * HISTORY_SAMPLES {
+            let tmp = path.with_extension("tmp");
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+            for sample in &history.samples {
+                writeln!(file, "{}", serde_json::to_string(sample)?)?;
+            }
+            file.flush()?;
+            std::fs::rename(&tmp, &path)?;
+            history.lines = history.samples.len();
+        } else {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            writeln!(file, "{}", serde_json::to_string(metrics)?)?;
+            history.lines += 1;
+        }
+        Ok(())
+    }
+
+    pub async fn get_vm_metrics(&self, vm_id: &str) -> Option<VmMetrics> {
+        let vm_metrics = self.vm_metrics.read().await;
+        vm_metrics.get(vm_id).cloned()
+    }
+
+    pub async fn get_all_vm_metrics(&self) -> Vec<VmMetrics> {
+        let vm_metrics = self.vm_metrics.read().await;
+        vm_metrics.values().cloned().collect()
+    }
+
+    pub async fn get_system_metrics(&self) -> SystemMetrics {
+        let system_metrics = self.system_metrics.read().await;
+        system_metrics.clone()
+    }
+
+    async fn update_system_metrics(&self) {
+        let vm_metrics = self.vm_metrics.read().await;
+        let mut system_metrics = self.system_metrics.write().await;
+
+        system_metrics.total_vms = vm_metrics.len() as u32;
+        system_metrics.running_vms = vm_metrics.len() as u32; // Simplified
+        system_metrics.total_cpu_usage = vm_metrics.values().map(|m| m.cpu_usage_percent).sum();
+        system_metrics.total_memory_usage = vm_metrics.values().map(|m| m.memory_usage_bytes).sum();
+        system_metrics.total_memory_allocated =
+            vm_metrics.values().map(|m| m.memory_total_bytes).sum();
+        system_metrics.timestamp = chrono::Utc::now();
+    }
+}
+
+#[async_trait]
+impl VmEventHandler for MetricsCollector {
+    async fn handle(&self, event: VmEvent) -> Result<()> {
+        match event {
+            VmEvent::Created { vm_id } => {
+                tracing::info!("VM {} created - starting metrics collection", vm_id);
+            }
+            VmEvent::Stopped { vm_id } => {
+                // Its history stays for `history`
+                self.vm_metrics.write().await.remove(vm_id.as_str());
+                self.update_system_metrics().await;
+            }
+            VmEvent::ResourceUsage { vm_id, cpu, memory } => {
+                let metrics = VmMetrics {
+                    vm_id: vm_id.to_string(),
+                    cpu_usage_percent: cpu,
+                    memory_usage_bytes: memory,
+                    memory_total_bytes: memory, // Simplified
+                    disk_usage_bytes: 0,
+                    network_rx_bytes: 0,
+                    network_tx_bytes: 0,
+                    uptime_seconds: 0,
+                    timestamp: chrono::Utc::now(),
+                };
+
+                self.record_vm_metrics(metrics).await;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "metrics"
+    }
+}
+
+/// Run the command of `alert` and POST to its webhook, in the background
+fn notify(alert: &MetricAlert, vm_id: &str, payload: &EventPayload) {
+    if alert.command.is_none() && alert.webhook.is_none() {
+        return;
+    }
+    let envelope = match serde_json::to_string(&EventEnvelope::new(payload.clone())) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::warn!("Alert '{}': cannot encode event: {}", alert.name, e);
+            return;
+        }
+    };
+    let mut commands = Vec::new();
+    if let Some(command) = &alert.command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .env("VORTEX_ALERT", &alert.name)
+            .env("VORTEX_VM_ID", vm_id)
+            .env("VORTEX_EVENT_JSON", &envelope);
+        if let Some(dir) = get_config_path()
+            .ok()
+            .and_then(|p| p.parent().map(PathBuf::from))
+        {
+            cmd.current_dir(dir);
+        }
+        commands.push(("command", cmd));
+    }
+    if let Some(webhook) = &alert.webhook {
+        let mut cmd = Command::new("curl");
+        cmd.args(["-fsS", "--max-time", "10", "-X", "POST"])
+            .args(["-H", "Content-Type: application/json"])
+            .args(["--data-binary", &envelope])
+            .arg(webhook);
+        commands.push(("webhook", cmd));
+    }
+    for (what, mut cmd) in commands {
+        let name = alert.name.clone();
+        cmd.stdin(std::process::Stdio::null());
+        tokio::spawn(async move {
+            match cmd.output().await {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => tracing::warn!(
+                    "Alert '{}' {} exited with {}: {}",
+                    name,
+                    what,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => tracing::warn!("Alert '{}' {} failed to start: {}", name, what, e),
+            }
+        });
+    }
+}
+
+/// Samples in the history file at `path`, skipping lines that do not parse
+fn read_history(path: &std::path::Path) -> Result<Vec<VmMetrics>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(vm_id: &str, minutes_ago: i64) -> VmMetrics {
+        VmMetrics {
+            vm_id: vm_id.to_string(),
+            cpu_usage_percent: minutes_ago as f64,
+            memory_usage_bytes: 1024,
+            memory_total_bytes: 4096,
+            disk_usage_bytes: 0,
+            network_rx_bytes: 0,
+            network_tx_bytes: 0,
+            uptime_seconds: 0,
+            timestamp: Utc::now() - chrono::Duration::minutes(minutes_ago),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_outlives_the_collector() {
+        let dir = tempfile::tempdir().unwrap();
+        let collector = MetricsCollector::with_history_dir(Some(dir.path().to_path_buf()));
+        for minutes_ago in [30, 20, 5] {
+            collector
+                .record_vm_metrics(sample("vortex-1", minutes_ago))
+                .await;
+        }
+        collector.record_vm_metrics(sample("vortex-2", 1)).await;
+        collector
+            .handle(VmEvent::Stopped {
+                vm_id: "vortex-1".into(),
+            })
+            .await
+            .unwrap();
+
+        let later = MetricsCollector::with_history_dir(Some(dir.path().to_path_buf()));
+        let since = Utc::now() - chrono::Duration::minutes(25);
+        let recent = later.history("vortex-1", since..).await.unwrap();
+        let cpu: Vec<f64> = recent.iter().map(|m| m.cpu_usage_percent).collect();
+        assert_eq!(cpu, [20.0, 5.0]);
+        assert_eq!(later.history("vortex-1", ..).await.unwrap().len(), 3);
+        assert!(later.history("vortex-3", ..).await.unwrap().is_empty());
+        assert!(later.history("../etc/passwd", ..).await.is_err());
+    }
+
+    #[test]
+    fn test_alerts_fire_once_per_sustained_breach() {
+        let config: crate::config::VortexConfig = toml::from_str(
+            r#"
+            [[alerts]]
+            name = "hot"
+            metric = "cpu"
+            threshold = 80
+            for_secs = 60
+            command = "./page.sh"
+
+            [[alerts]]
+            name = "full"
+            metric = "memory"
+            threshold = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.alerts[0].metric, AlertMetric::Cpu);
+        assert!(config.alerts[1].validate().is_err());
+        let collector = MetricsCollector::with_history_dir(None).with_alerts(config.alerts);
+        assert_eq!(collector.alerts.len(), 1);
+
+        let at = |minutes_ago: i64, cpu: f64| VmMetrics {
+            cpu_usage_percent: cpu,
+            ..sample("vortex-1", minutes_ago)
+        };
+        let fired = |metrics: VmMetrics| collector.check_alerts(&metrics).len();
+        assert_eq!(fired(at(10, 95.0)), 0);
+        // Dropping under the threshold starts over
+        assert_eq!(fired(at(9, 50.0)), 0);
+        assert_eq!(fired(at(8, 95.0)), 0);
+        assert_eq!(fired(at(7, 95.0)), 1);
+        assert_eq!(fired(at(6, 95.0)), 0);
+        assert_eq!(fired(at(5, 10.0)), 0);
+        assert_eq!(fired(at(4, 95.0)), 0);
+        assert_eq!(fired(at(2, 95.0)), 1);
+    }
+
+    #[test]
+    fn test_usage_adds_up_the_samples() {
+        let started = Utc::now() - chrono::Duration::minutes(10);
+        let at = |minutes_ago: i64, cpu: f64, memory: u64| VmMetrics {
+            cpu_usage_percent: cpu,
+            memory_usage_bytes: memory,
+            ..sample("vortex-1", minutes_ago)
+        };
+        // Two CPUs busy for the first five minutes, half of one after
+        let samples = [at(5, 200.0, 512), at(0, 50.0, 256)];
+        let usage = VmUsage::from_samples("vortex-1", &samples, started, Utc::now());
+        assert!((usage.cpu_seconds - 750.0).abs() < 1.0);
+        assert_eq!(usage.peak_memory_bytes, 512);
+        assert!((usage.wall_seconds - 600.0).abs() < 1.0);
+
+        let total = VmUsage::total("all", [&usage, &usage]);
+        assert!((total.cpu_seconds - 1500.0).abs() < 2.0);
+        assert_eq!(total.peak_memory_bytes, 1024);
+    }
+}
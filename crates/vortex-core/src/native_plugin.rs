@@ -0,0 +1,226 @@
+//! Rust plugins distributed as shared libraries (`.so`, `.dylib`, `.dll`).
+//!
+//! A library exports a [`PluginDeclaration`] named
+//! `vortex_plugin_declaration`, which [`declare_plugin!`](crate::declare_plugin)
+//! writes for it:
+//!
+//! ```ignore
+//! vortex_core::declare_plugin!(InventoryPlugin::new);
+//! ```
+//!
+//! Trait objects have no stable ABI, so a library is only loaded when it
+//! declares the [`PLUGIN_ABI_VERSION`] and vortex-core version of this
+//! build, and it must be compiled with the same Rust toolchain. Unlike the
+//! WebAssembly plugins, a library runs with all the rights of Vortex; only
+//! install ones you trust.
+
+use crate::backend::BackendProvider;
+use crate::error::{Result, VortexError};
+use crate::plugin::{Plugin, PluginMetadata};
+use crate::templates::DevTemplate;
+use crate::vm::{VmInstance, VmSpec};
+use async_trait::async_trait;
+use libloading::Library;
+use std::path::Path;
+
+/// Version of the layout of [`PluginDeclaration`], raised whenever it or
+/// the `Plugin` trait changes incompatibly
+pub const PLUGIN_ABI_VERSION: u32 = 3;
+
+/// Version of vortex-core a library must be built against
+pub const CORE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Name of the static a plugin library exports
+const DECLARATION_SYMBOL: &[u8] = b"vortex_plugin_declaration\0";
+
+/// What a plugin library exports. `abi_version` comes first so that it can
+/// be read from libraries of any version.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub abi_version: u32,
+    pub core_version: &'static str,
+    pub create: fn() -> Box<dyn Plugin>,
+}
+
+/// Export the plugin `constructor` returns from a `cdylib` crate
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:path) => {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static vortex_plugin_declaration: $crate::native_plugin::PluginDeclaration =
+            $crate::native_plugin::PluginDeclaration {
+                abi_version: $crate::native_plugin::PLUGIN_ABI_VERSION,
+                core_version: $crate::native_plugin::CORE_VERSION,
+                create: || -> ::std::boxed::Box<dyn $crate::plugin::Plugin> {
+                    ::std::boxed::Box::new($constructor())
+                },
+            };
+    };
+}
+
+/// A plugin created by a shared library, which stays loaded for as long as
+/// the plugin lives
+pub struct NativePlugin {
+    // Dropped before the library its code lives in
+    plugin: Box<dyn Plugin>,
+    _library: Library,
+}
+
+impl NativePlugin {
+    /// Load the library at `path` and create its plugin
+    pub fn load(path: &Path) -> Result<Self> {
+        check_permissions(path)?;
+        let error = |message: String| VortexError::PluginError {
+            message: format!("{}: {}", path.display(), message),
+        };
+        // Running the library's initializers is what loading it means; the
+        // declaration is checked before anything else of it is used
+        let library = unsafe { Library::new(path) }.map_err(|e| error(e.to_string()))?;
+        let declaration = unsafe {
+            let symbol = library
+                .get::<*const PluginDeclaration>(DECLARATION_SYMBOL)
+                .map_err(|_| {
+                    error("not a Vortex plugin (no vortex_plugin_declaration)".to_string())
+                })?;
+            let declaration = *symbol;
+            let abi_version = std::ptr::addr_of!((*declaration).abi_version).read();
+            if abi_version != PLUGIN_ABI_VERSION {
+                return Err(error(format!(
+                    "built for plugin ABI {}, this Vortex loads ABI {}",
+                    abi_version, PLUGIN_ABI_VERSION
+                )));
+            }
+            &*declaration
+        };
+        if declaration.core_version != CORE_VERSION {
+            return Err(error(format!(
+                "built against vortex-core {}, this is {}",
+                declaration.core_version, CORE_VERSION
+            )));
+        }
+        Ok(Self {
+            plugin: (declaration.create)(),
+            _library: library,
+        })
+    }
+}
+
+/// Refuse libraries others could have replaced with their own code
+fn check_permissions(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o022 != 0 {
+            return Err(VortexError::PluginError {
+                message: format!(
+                    "{} is writable by other users; refusing to load it",
+                    path.display()
+                ),
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+impl std::fmt::Debug for NativePlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativePlugin")
+            .field("plugin", &self.plugin)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Plugin for NativePlugin {
+    fn metadata(&self) -> &PluginMetadata {
+        self.plugin.metadata()
+    }
+
+    async fn initialize(&mut self, settings: &serde_json::Value) -> Result<()> {
+        self.plugin.initialize(settings).await
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        self.plugin.shutdown().await
+    }
+
+    fn register_backends(&self, provider: &mut BackendProvider) -> Result<()> {
+        self.plugin.register_backends(provider)
+    }
+
+    fn templates(&self) -> Vec<DevTemplate> {
+        self.plugin.templates()
+    }
+
+    async fn on_spec_mutate(&self, spec: &mut VmSpec) -> Result<()> {
+        self.plugin.on_spec_mutate(spec).await
+    }
+
+    async fn on_vm_create(&self, vm: &VmInstance) -> Result<()> {
+        self.plugin.on_vm_create(vm).await
+    }
+
+    async fn on_vm_pre_start(&self, vm: &VmInstance) -> Result<()> {
+        self.plugin.on_vm_pre_start(vm).await
+    }
+
+    async fn on_vm_start(&self, vm: &VmInstance) -> Result<()> {
+        self.plugin.on_vm_start(vm).await
+    }
+
+    async fn on_vm_pre_stop(&self, vm: &VmInstance) -> Result<()> {
+        self.plugin.on_vm_pre_stop(vm).await
+    }
+
+    async fn on_vm_stop(&self, vm: &VmInstance) -> Result<()> {
+        self.plugin.on_vm_stop(vm).await
+    }
+
+    async fn on_snapshot_create(&self, vm: &VmInstance, snapshot_name: &str) -> Result<()> {
+        self.plugin.on_snapshot_create(vm, snapshot_name).await
+    }
+
+    async fn on_snapshot_restore(&self, snapshot_id: &str) -> Result<()> {
+        self.plugin.on_snapshot_restore(snapshot_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::PluginManager;
+
+    #[tokio::test]
+    async fn test_only_plugin_libraries_are_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new().await.unwrap();
+        assert_eq!(
+            manager
+                .load_from_dir(&dir.path().join("missing"))
+                .await
+                .unwrap(),
+            0
+        );
+
+        let library = dir
+            .path()
+            .join(format!("libbroken.{}", std::env::consts::DLL_EXTENSION));
+        std::fs::write(&library, b"not a library").unwrap();
+        std::fs::write(dir.path().join("README.md"), b"ignored").unwrap();
+        assert!(NativePlugin::load(&library).is_err());
+        assert_eq!(manager.load_from_dir(dir.path()).await.unwrap(), 0);
+        assert!(manager.list_plugins().is_empty());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&library, std::fs::Permissions::from_mode(0o666)).unwrap();
+            let err = NativePlugin::load(&library).unwrap_err();
+            assert!(err.to_string().contains("writable by other users"));
+        }
+    }
+}
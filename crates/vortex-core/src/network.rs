@@ -0,0 +1,1032 @@
+//! Private networks shared between VMs, kept in
+//! `~/.vortex/networks/<name>.json`.
+//!
+//! A VM joins a network when its spec's `network_config` names one. It then
+//! gets an address from the network's `/24` subnet (`10.89.<n>.0/24`),
+//! which it keeps until it is cleaned up, and reaches the other members at
+//! theirs, or by name (see [`crate::dns`]). Each network also has an IPv6
+//! `/64` (`fd89:0:0:<n>::/64`), where members have the same host number. Backends put the members on one
+//! segment next to the VM's usual outbound network: the container backend
+//! as a container network, QEMU as a multicast socket.
+//!
+//! The host reaches VMs through their forwarded ports, or through the
+//! [`serve_ingress`] proxy at `http://<name>.localhost:<port>`. VMs whose
+//! network policy restricts them reach out through an egress proxy (see
+//! [`serve_egress`]) instead of the network.
+
+use crate::auth::{AuthContext, Permission};
+use crate::dns::Gateway;
+use crate::error::{Result, VortexError};
+use crate::ids::{LABEL_SERVICE, LABEL_SESSION_NAME};
+use crate::tunnel::Tunnel;
+use crate::vm::{NetworkPolicy, VmManager, VmSpec, VmState, HOST_ALIAS, NETWORK_NONE};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+
+/// First two octets of every private network's subnet
+const SUBNET_BASE: [u8; 2] = [10, 89];
+/// Networks are numbered 1..=254 in the third octet
+const MAX_NETWORKS: u8 = 254;
+/// `.1` is the gateway; members are numbered from here
+const FIRST_HOST: u8 = 2;
+const PREFIX_LEN: u8 = 24;
+/// First group of every private network's IPv6 subnet, a unique local one
+const SUBNET6_BASE: u16 = 0xfd89;
+/// Prefix length of the IPv6 subnets
+pub const PREFIX6_LEN: u8 = 64;
+/// Port of the multicast groups QEMU guests share a segment on
+pub const MULTICAST_PORT: u16 = 5489;
+/// How often the DNS responders check for created or removed networks
+const DNS_RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+/// Largest request head the ingress and egress proxies read before routing
+const MAX_REQUEST_HEAD: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub name: String,
+    pub subnet: String,
+    pub gateway: String,
+    pub dns_servers: Vec<String>,
+    pub enable_internet: bool,
+    /// Limits each member is held to
+    #[serde(default)]
+    pub limits: NetworkLimits,
+    /// Member VMs by ID
+    #[serde(default)]
+    pub members: BTreeMap<String, VmNetwork>,
+    /// WireGuard tunnel other machines reach the members through
+    #[serde(default)]
+    pub tunnel: Option<Tunnel>,
+}
+
+/// Bandwidth and connection limits of each member of a network, so one
+/// busy VM cannot starve the others. They cover all of a VM's traffic, not
+/// only what stays on the network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkLimits {
+    /// Megabits per second a VM receives at most
+    #[serde(default)]
+    pub ingress_mbps: Option<u32>,
+    /// Megabits per second a VM sends at most
+    #[serde(default)]
+    pub egress_mbps: Option<u32>,
+    /// TCP connections a VM has open at once, counted separately for
+    /// connections it opens and ones it accepts
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+}
+
+impl NetworkLimits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == NetworkLimits::default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let limits = [
+            ("ingress_mbps", self.ingress_mbps),
+            ("egress_mbps", self.egress_mbps),
+            ("max_connections", self.max_connections),
+        ];
+        if let Some((field, _)) = limits.iter().find(|(_, limit)| *limit == Some(0)) {
+            return Err(VortexError::InvalidInput {
+                field: field.to_string(),
+                message: "Limits must be greater than 0".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for NetworkLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut limits = Vec::new();
+        if let Some(mbps) = self.ingress_mbps {
+            limits.push(format!("in {} Mbit/s", mbps));
+        }
+        if let Some(mbps) = self.egress_mbps {
+            limits.push(format!("out {} Mbit/s", mbps));
+        }
+        if let Some(connections) = self.max_connections {
+            limits.push(format!("{} connections", connections));
+        }
+        if limits.is_empty() {
+            limits.push("unlimited".to_string());
+        }
+        write!(f, "{}", limits.join(", "))
+    }
+}
+
+impl NetworkConfig {
+    /// Third octet of the subnet, unique among networks
+    pub(crate) fn number(&self) -> Option<u8> {
+        subnet_number(&self.subnet)
+    }
+
+    /// The network's IPv6 subnet
+    pub fn subnet6(&self) -> String {
+        format!(
+            "{}/{}",
+            ipv6_address(self.number().unwrap_or(0), 0),
+            PREFIX6_LEN
+        )
+    }
+
+    /// IPv4 and IPv6 addresses of the members named `name`, by VM ID or
+    /// service name
+    pub fn resolve(&self, name: &str) -> Vec<IpAddr> {
+        self.members
+            .values()
+            .filter(|member| {
+                member.vm_id.eq_ignore_ascii_case(name) || member.service.as_deref() == Some(name)
+            })
+            .flat_map(|member| {
+                let ipv4 = member.ip_address.parse().ok().map(IpAddr::V4);
+                ipv4.into_iter()
+                    .chain(member.ipv6_address().map(IpAddr::V6))
+            })
+            .collect()
+    }
+}
+
+/// A VM's interface on a private network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmNetwork {
+    pub vm_id: String,
+    pub network_name: String,
+    pub subnet: String,
+    pub ip_address: String,
+    pub prefix_len: u8,
+    pub mac_address: String,
+    /// Name the other members resolve the VM by, besides its ID
+    #[serde(default)]
+    pub service: Option<String>,
+    /// The network's limits when the VM joined, applied by its backend
+    #[serde(default)]
+    pub limits: NetworkLimits,
+}
+
+impl VmNetwork {
+    /// Address of the network's gateway, which answers DNS queries
+    pub fn gateway(&self) -> String {
+        let [a, b] = SUBNET_BASE;
+        format!("{}.{}.{}.1", a, b, subnet_number(&self.subnet).unwrap_or(0))
+    }
+
+    /// Multicast group QEMU members of the network share a segment on
+    pub fn multicast_group(&self) -> SocketAddrV4 {
+        multicast_group(subnet_number(&self.subnet).unwrap_or(0))
+    }
+
+    /// The VM's IPv6 address, with the host number of its IPv4 address
+    pub fn ipv6_address(&self) -> Option<Ipv6Addr> {
+        let ip: Ipv4Addr = self.ip_address.parse().ok()?;
+        Some(ipv6_address(subnet_number(&self.subnet)?, ip.octets()[3]))
+    }
+}
+
+fn ipv6_address(number: u8, host: u8) -> Ipv6Addr {
+    Ipv6Addr::new(SUBNET6_BASE, 0, 0, number.into(), 0, 0, 0, host.into())
+}
+
+/// Listen on `port` at `address`, or when there is none on every IPv4 and
+/// IPv6 address with one dual-stack socket (IPv4 only on hosts without IPv6)
+pub fn listen(address: Option<IpAddr>, port: u16) -> std::io::Result<std::net::TcpListener> {
+    if let Some(address) = address {
+        return std::net::TcpListener::bind((address, port));
+    }
+    let dual_stack = || -> std::io::Result<std::net::TcpListener> {
+        let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_only_v6(false)?;
+        // As std does for its listeners
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+        socket.listen(1024)?;
+        Ok(socket.into())
+    };
+    match dual_stack() {
+        Err(e) if e.kind() != std::io::ErrorKind::AddrInUse => {
+            std::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        }
+        result => result,
+    }
+}
+
+fn subnet_number(subnet: &str) -> Option<u8> {
+    subnet.split('.').nth(2)?.parse().ok()
+}
+
+fn multicast_group(number: u8) -> SocketAddrV4 {
+    SocketAddrV4::new(Ipv4Addr::new(239, 89, number, 1), MULTICAST_PORT)
+}
+
+/// Locally administered, and unique per network and address
+fn mac_address(number: u8, host: u8) -> [u8; 6] {
+    let [a, b] = SUBNET_BASE;
+    [0x02, a, b, 0, number, host]
+}
+
+fn networks_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("networks"))
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name != NETWORK_NONE
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(VortexError::InvalidInput {
+            field: "network".to_string(),
+            message: format!(
+                "Invalid network name '{}': use up to 32 lowercase letters, digits, '-' or '_'",
+                name
+            ),
+        })
+    }
+}
+
+/// Service names become DNS labels
+fn validate_service(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 63
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(VortexError::InvalidInput {
+            field: LABEL_SERVICE.to_string(),
+            message: format!(
+                "Invalid service name '{}': use up to 63 lowercase letters, digits or '-'",
+                name
+            ),
+        })
+    }
+}
+
+/// UDP socket on the segment of `group`, next to the QEMU guests on it
+fn multicast_socket(group: SocketAddrV4) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    // Every guest binds the group's port too
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::V4(group).into())?;
+    socket.join_multicast_v4(group.ip(), &Ipv4Addr::LOCALHOST)?;
+    socket.set_multicast_if_v4(&Ipv4Addr::LOCALHOST)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkManager {
+    root: PathBuf,
+    /// Who networks and tunnels are changed for
+    auth: AuthContext,
+}
+
+impl NetworkManager {
+    pub async fn new() -> Result<Self> {
+        Ok(Self::at(networks_root()?))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self {
+            root,
+            auth: AuthContext::local(),
+        }
+    }
+
+    /// Make changes for `auth`, which needs `NetworkManage` to create and
+    /// remove networks and to open and close their tunnels
+    pub fn with_auth(mut self, auth: AuthContext) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub(crate) fn require_manage(&self) -> Result<()> {
+        self.auth.require(Permission::NetworkManage)
+    }
+
+    pub(crate) fn path(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.json", name))
+    }
+
+    fn load(&self, name: &str) -> Result<Option<NetworkConfig>> {
+        match fs::read_to_string(self.path(name)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace the network's file in one step, so readers never see half of it
+    pub(crate) fn save(&self, network: &NetworkConfig) -> Result<()> {
+        fs::create_dir_all(&self.root)?;
+        let path = self.path(&network.name);
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, serde_json::to_string_pretty(network)?)?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+
+    /// Create the network `name` on the next free subnet, holding its
+    /// members to `limits`
+    pub async fn create_network(&self, name: &str, limits: NetworkLimits) -> Result<NetworkConfig> {
+        self.require_manage()?;
+        validate_name(name)?;
+        limits.validate()?;
+        let networks = self.list_networks().await?;
+        if networks.iter().any(|network| network.name == name) {
+            return Err(VortexError::NetworkError {
+                message: format!("Network {} already exists", name),
+            });
+        }
+
+        let number = (1..=MAX_NETWORKS)
+            .find(|n| !networks.iter().any(|network| network.number() == Some(*n)))
+            .ok_or_else(|| VortexError::NetworkError {
+                message: format!("All {} private networks are in use", MAX_NETWORKS),
+            })?;
+        let [a, b] = SUBNET_BASE;
+        let network = NetworkConfig {
+            name: name.to_string(),
+            subnet: format!("{}.{}.{}.0/{}", a, b, number, PREFIX_LEN),
+            gateway: format!("{}.{}.{}.1", a, b, number),
+            dns_servers: Vec::new(),
+            enable_internet: false,
+            limits,
+            members: BTreeMap::new(),
+            tunnel: None,
+        };
+        self.save(&network)?;
+        Ok(network)
+    }
+
+    /// Delete the network `name`, which must have no members left
+    pub async fn remove_network(&self, name: &str) -> Result<()> {
+        self.require_manage()?;
+        let network = self
+            .get_network(name)
+            .await?
+            .ok_or_else(|| VortexError::NetworkError {
+                message: format!("Network {} does not exist", name),
+            })?;
+        if !network.members.is_empty() {
+            let members: Vec<&str> = network.members.keys().map(String::as_str).collect();
+            return Err(VortexError::NetworkError {
+                message: format!("Network {} is still used by {}", name, members.join(", ")),
+            });
+        }
+        if network.tunnel.is_some() {
+            return Err(VortexError::NetworkError {
+                message: format!(
+                    "Network {} has a tunnel; close it with `vortex network tunnel {} --down`",
+                    name, name
+                ),
+            });
+        }
+        fs::remove_file(self.path(name))?;
+        Ok(())
+    }
+
+    pub async fn get_network(&self, name: &str) -> Result<Option<NetworkConfig>> {
+        validate_name(name)?;
+        self.load(name)
+    }
+
+    /// Add `vm_id` to the network, giving it the lowest free address and
+    /// `service` as a name. A VM already on the network keeps its address.
+    pub async fn assign_vm_to_network(
+        &self,
+        vm_id: &str,
+        network_name: &str,
+        service: Option<&str>,
+    ) -> Result<VmNetwork> {
+        if let Some(service) = service {
+            validate_service(service)?;
+        }
+        let mut network =
+            self.get_network(network_name)
+                .await?
+                .ok_or_else(|| VortexError::NetworkError {
+                    message: format!(
+                        "Network {} does not exist; create it with `vortex network create {}`",
+                        network_name, network_name
+                    ),
+                })?;
+        if let Some(member) = network.members.get_mut(vm_id) {
+            // Prewarmed VMs join before they know their service
+            if member.service.as_deref() != service {
+                member.service = service.map(str::to_string);
+                let member = member.clone();
+                self.save(&network)?;
+                return Ok(member);
+            }
+            return Ok(member.clone());
+        }
+
+        let number = network.number().unwrap_or(0);
+        let [a, b] = SUBNET_BASE;
+        let host = (FIRST_HOST..=254)
+            .find(|host| {
+                let ip = format!("{}.{}.{}.{}", a, b, number, host);
+                !network
+                    .members
+                    .values()
+                    .any(|member| member.ip_address == ip)
+            })
+            .ok_or_else(|| VortexError::NetworkError {
+                message: format!("Network {} has no free addresses", network_name),
+            })?;
+        let mac = mac_address(number, host).map(|byte| format!("{:02x}", byte));
+        let member = VmNetwork {
+            vm_id: vm_id.to_string(),
+            network_name: network_name.to_string(),
+            subnet: network.subnet.clone(),
+            ip_address: format!("{}.{}.{}.{}", a, b, number, host),
+            prefix_len: PREFIX_LEN,
+            mac_address: mac.join(":"),
+            service: service.map(str::to_string),
+            limits: network.limits,
+        };
+        network.members.insert(vm_id.to_string(), member.clone());
+        self.save(&network)?;
+        Ok(member)
+    }
+
+    /// Free the addresses `vm_id` holds on any network
+    pub async fn release_vm(&self, vm_id: &str) -> Result<()> {
+        for mut network in self.list_networks().await? {
+            if network.members.remove(vm_id).is_some() {
+                self.save(&network)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get_vm_network(&self, vm_id: &str) -> Result<Option<VmNetwork>> {
+        Ok(self
+            .list_networks()
+            .await?
+            .into_iter()
+            .find_map(|mut network| network.members.remove(vm_id)))
+    }
+
+    /// All networks, by name
+    pub async fn list_networks(&self) -> Result<Vec<NetworkConfig>> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Ok(Vec::new());
+        };
+        let mut networks: Vec<NetworkConfig> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        networks.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(networks)
+    }
+
+    /// Answer the DNS queries of QEMU guests on every network, starting and
+    /// stopping responders as networks are created and removed. Runs for
+    /// the life of the process.
+    pub async fn serve_dns(&self) {
+        // By network name and subnet; a responder that failed stays here so
+        // it is not restarted until its network is recreated
+        let mut responders: HashMap<(String, String), JoinHandle<()>> = HashMap::new();
+        loop {
+            let networks = self.list_networks().await.unwrap_or_default();
+            responders.retain(|(name, subnet), task| {
+                let current = networks
+                    .iter()
+                    .any(|network| &network.name == name && &network.subnet == subnet);
+                if !current {
+                    task.abort();
+                }
+                current
+            });
+            for network in networks {
+                let key = (network.name.clone(), network.subnet.clone());
+                if responders.contains_key(&key) {
+                    continue;
+                }
+                let manager = self.clone();
+                responders.insert(
+                    key,
+                    tokio::spawn(async move {
+                        if let Err(e) = manager.respond(&network).await {
+                            tracing::warn!(
+                                "DNS responder of network {} stopped: {}",
+                                network.name,
+                                e
+                            );
+                        }
+                    }),
+                );
+            }
+            tokio::time::sleep(DNS_RESCAN_INTERVAL).await;
+        }
+    }
+
+    /// Act as the gateway on the segment of `network`
+    async fn respond(&self, network: &NetworkConfig) -> Result<()> {
+        let number = network.number().unwrap_or(0);
+        let group = multicast_group(number);
+        let socket = multicast_socket(group)?;
+        let gateway = Gateway {
+            ip: network
+                .gateway
+                .parse()
+                .map_err(|_| VortexError::NetworkError {
+                    message: format!(
+                        "Invalid gateway {} of network {}",
+                        network.gateway, network.name
+                    ),
+                })?,
+            mac: mac_address(number, 1),
+        };
+        tracing::debug!(
+            "Answering DNS queries on network {} at {}",
+            network.name,
+            gateway.ip
+        );
+
+        // Members change while the responder runs, so each query reads them
+        let resolve = |name: &str| match self.load(&network.name) {
+            Ok(Some(network)) => network.resolve(name),
+            _ => Vec::new(),
+        };
+        let mut frame = vec![0u8; 65536];
+        loop {
+            let (len, _) = socket.recv_from(&mut frame).await?;
+            if let Some(reply) = gateway.reply(&frame[..len], resolve) {
+                socket.send_to(&reply, group).await?;
+            }
+        }
+    }
+}
+
+/// Proxy `http://<name>.localhost:<port>` on loopback `port` to the VMs of
+/// `vm_manager`. `<name>` is a VM's ID, session name or service name, and
+/// the request goes to the VM's only forwarded port; VMs forwarding several
+/// are reached at `<guest-port>.<name>.localhost`. Connections are routed
+/// by their first request, which is enough for browsers and HTTP clients
+/// that keep a connection per host.
+pub async fn serve_ingress(vm_manager: Arc<VmManager>, port: u16) -> Result<()> {
+    let mut listeners = vec![TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .await
+        .map_err(|e| VortexError::NetworkError {
+            message: format!("Failed to bind the ingress to port {}: {}", port, e),
+        })?];
+    // `localhost` may resolve to either
+    match TcpListener::bind((Ipv6Addr::LOCALHOST, port)).await {
+        Ok(listener) => listeners.push(listener),
+        Err(e) => tracing::debug!("Ingress not listening on [::1]:{}: {}", port, e),
+    }
+    tracing::info!("Ingress listening on http://<name>.localhost:{}", port);
+    let accepting = listeners
+        .into_iter()
+        .map(|listener| accept_ingress(listener, Arc::clone(&vm_manager)));
+    futures::future::try_join_all(accepting).await?;
+    Ok(())
+}
+
+async fn accept_ingress(listener: TcpListener, vm_manager: Arc<VmManager>) -> Result<()> {
+    loop {
+        let (client, _) = listener.accept().await?;
+        let vm_manager = Arc::clone(&vm_manager);
+        tokio::spawn(async move {
+            if let Err(e) = proxy(client, &vm_manager).await {
+                tracing::debug!("Ingress connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn proxy(mut client: TcpStream, vm_manager: &VmManager) -> Result<()> {
+    let Some(head) = read_head(&mut client).await? else {
+        return Ok(());
+    };
+    let host = String::from_utf8_lossy(&head)
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("host")
+                .then(|| value.trim().to_string())
+        })
+        .unwrap_or_default();
+    let vms = vm_manager.list().await?;
+    let running = vms
+        .iter()
+        .filter(|vm| !matches!(vm.state, VmState::Stopped | VmState::Error { .. }))
+        .map(|vm| (vm.id.as_str(), &vm.spec));
+    let address = match route(&host, running) {
+        Ok(address) => address,
+        Err(message) => return reply(&mut client, "404 Not Found", &message).await,
+    };
+
+    let mut upstream = match TcpStream::connect(address).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            let message = format!("Host port {} does not answer: {}", address.port(), e);
+            return reply(&mut client, "502 Bad Gateway", &message).await;
+        }
+    };
+    upstream.write_all(&head).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// The head of the request on `client`, or `None` if the client went away
+/// or was told its head is too large
+async fn read_head(client: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let mut head = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_HEAD {
+            reply(client, "431 Request Header Fields Too Large", "").await?;
+            return Ok(None);
+        }
+        let read = client.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        head.extend_from_slice(&chunk[..read]);
+    }
+    Ok(Some(head))
+}
+
+/// Proxy the connections of a VM whose network policy is `policy`, to the
+/// hosts the policy allows. Clients send `CONNECT` for tunnels, HTTPS
+/// included, and plain HTTP requests with absolute `http://` URLs, as they
+/// do for `http_proxy`. A connection goes to the host of its first request.
+pub(crate) async fn serve_egress(listener: TcpListener, policy: NetworkPolicy) {
+    let policy = Arc::new(policy);
+    while let Ok((client, _)) = listener.accept().await {
+        let policy = Arc::clone(&policy);
+        tokio::spawn(async move {
+            if let Err(e) = egress(client, &policy).await {
+                tracing::debug!("Egress connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn egress(mut client: TcpStream, policy: &NetworkPolicy) -> Result<()> {
+    let Some(head) = read_head(&mut client).await? else {
+        return Ok(());
+    };
+    let request = String::from_utf8_lossy(&head);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let tunnel = method.eq_ignore_ascii_case("CONNECT");
+    let authority = match target.strip_prefix("http://") {
+        _ if tunnel => Some(target),
+        Some(rest) => rest.split(['/', '?', '#']).next(),
+        None => None,
+    };
+    let Some((host, port)) =
+        authority.and_then(|a| split_authority(a, if tunnel { 443 } else { 80 }))
+    else {
+        let message = "Send CONNECT requests or absolute http:// URLs";
+        return reply(&mut client, "400 Bad Request", message).await;
+    };
+    if !policy.allows(&host) {
+        let message = format!(
+            "The VM's network policy ({}) does not allow {}",
+            policy, host
+        );
+        return reply(&mut client, "403 Forbidden", &message).await;
+    }
+
+    // The alias names the host, which the proxy runs on
+    let address = if host.trim_end_matches('.').eq_ignore_ascii_case(HOST_ALIAS) {
+        "localhost"
+    } else {
+        host.as_str()
+    };
+    let mut upstream = match TcpStream::connect((address, port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            let message = format!("{}:{} does not answer: {}", host, port, e);
+            return reply(&mut client, "502 Bad Gateway", &message).await;
+        }
+    };
+    if tunnel {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    } else {
+        upstream.write_all(&head).await?;
+    }
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Host and port of `authority` (`host[:port]`, IPv6 addresses in
+/// brackets), with `default_port` if it has none
+fn split_authority(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(rest) => {
+            let (host, port) = rest.split_once(']')?;
+            (host, port.strip_prefix(':'))
+        }
+        None => match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port,
+    };
+    (!host.is_empty()).then(|| (host.to_ascii_lowercase(), port))
+}
+
+async fn reply(client: &mut TcpStream, status: &str, message: &str) -> Result<()> {
+    let body = format!("{}\n", message);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    client.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Forwarded port the ingress sends requests for `host`, a `Host` header,
+/// to among `vms`, or why there is none
+fn route<'a>(
+    host: &str,
+    mut vms: impl Iterator<Item = (&'a str, &'a VmSpec)>,
+) -> std::result::Result<SocketAddr, String> {
+    let name = host
+        .rsplit_once(':')
+        .map_or(host, |(name, _)| name)
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    let Some(name) = name.strip_suffix(".localhost") else {
+        return Err(format!("Use http://<vm-name>.localhost, not {}", host));
+    };
+    let (guest, name) = match name.split_once('.') {
+        Some((port, rest)) => match port.parse::<u16>() {
+            Ok(port) => (Some(port), rest),
+            Err(_) => (None, name),
+        },
+        None => (None, name),
+    };
+
+    let names = |id: &str, spec: &VmSpec| {
+        id == name
+            || [LABEL_SESSION_NAME, LABEL_SERVICE]
+                .iter()
+                .any(|label| spec.labels.get(*label).map(String::as_str) == Some(name))
+    };
+    let (id, spec) = vms
+        .find(|(id, spec)| names(id, spec))
+        .ok_or_else(|| format!("No running VM is named {}", name))?;
+
+    let mut ports: Vec<(u16, u16)> = spec
+        .ports
+        .iter()
+        .map(|(host, guest)| (*guest, *host))
+        .collect();
+    ports.sort();
+    let host = match (guest, ports.as_slice()) {
+        (Some(guest), _) => ports
+            .iter()
+            .find(|(forwarded, _)| *forwarded == guest)
+            .map(|(_, host)| *host)
+            .ok_or_else(|| format!("VM {} does not forward port {}", id, guest))?,
+        (None, [(_, host)]) => *host,
+        (None, []) => return Err(format!("VM {} forwards no ports", id)),
+        (None, _) => {
+            let guests: Vec<String> = ports.iter().map(|(guest, _)| guest.to_string()).collect();
+            return Err(format!(
+                "VM {} forwards several ports ({}); use http://<port>.{}.localhost",
+                id,
+                guests.join(", "),
+                name
+            ));
+        }
+    };
+    Ok(spec.host_address(host))
+}
+
+/// Give `vm_id` its address on the private network `spec` names, if any,
+/// under the service name in its labels
+pub(crate) async fn join(vm_id: &str, spec: &VmSpec) -> Result<()> {
+    if let Some(name) = spec.private_network() {
+        let service = spec.labels.get(LABEL_SERVICE).map(String::as_str);
+        NetworkManager::new()
+            .await?
+            .assign_vm_to_network(vm_id, name, service)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Free the private network addresses of `vm_id`; failures only warn
+pub(crate) async fn leave(vm_id: &str) {
+    let released = async { NetworkManager::new().await?.release_vm(vm_id).await }.await;
+    if let Err(e) = released {
+        tracing::warn!("Failed to release the network address of {}: {}", vm_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_members_get_stable_distinct_addresses() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = NetworkManager::at(dir.path().to_path_buf());
+
+        let limits = NetworkLimits {
+            egress_mbps: Some(100),
+            max_connections: Some(64),
+            ..Default::default()
+        };
+        let backend = manager
+            .create_network("backend", NetworkLimits::default())
+            .await
+            .unwrap();
+        let other = manager.create_network("other", limits).await.unwrap();
+        assert_eq!(backend.subnet, "10.89.1.0/24");
+        assert_eq!(other.subnet, "10.89.2.0/24");
+        assert!(manager
+            .create_network("backend", NetworkLimits::default())
+            .await
+            .is_err());
+        assert!(manager
+            .create_network("none", NetworkLimits::default())
+            .await
+            .is_err());
+        let zero = NetworkLimits {
+            ingress_mbps: Some(0),
+            ..Default::default()
+        };
+        assert!(manager.create_network("zero", zero).await.is_err());
+        let reader = AuthContext::from(crate::auth::AuthToken {
+            token: String::new(),
+            user_id: "ci".to_string(),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            permissions: vec![Permission::VmRead],
+        });
+        let reader = NetworkManager::at(dir.path().to_path_buf()).with_auth(reader);
+        assert!(matches!(
+            reader
+                .create_network("mine", NetworkLimits::default())
+                .await,
+            Err(VortexError::PermissionDenied { .. })
+        ));
+        assert!(matches!(
+            reader.remove_network("other").await,
+            Err(VortexError::PermissionDenied { .. })
+        ));
+        assert_eq!(reader.list_networks().await.unwrap().len(), 2);
+
+        let api = manager
+            .assign_vm_to_network("vm-api", "backend", None)
+            .await
+            .unwrap();
+        let db = manager
+            .assign_vm_to_network("vm-db", "backend", Some("database"))
+            .await
+            .unwrap();
+        assert_eq!(api.ip_address, "10.89.1.2");
+        assert!(api.limits.is_unlimited());
+        let worker = manager
+            .assign_vm_to_network("vm-worker", "other", None)
+            .await
+            .unwrap();
+        assert_eq!(worker.limits, limits);
+        assert_eq!(db.ip_address, "10.89.1.3");
+        assert_ne!(api.mac_address, db.mac_address);
+        // Joining again keeps the address
+        let again = manager
+            .assign_vm_to_network("vm-api", "backend", None)
+            .await
+            .unwrap();
+        assert_eq!(again, api);
+        assert!(manager
+            .assign_vm_to_network("vm-x", "missing", None)
+            .await
+            .is_err());
+        assert!(manager
+            .assign_vm_to_network("vm-x", "backend", Some("Bad_Name"))
+            .await
+            .is_err());
+
+        let network = manager.get_network("backend").await.unwrap().unwrap();
+        let db_ips: [IpAddr; 2] = [
+            db.ip_address.parse().unwrap(),
+            "fd89:0:0:1::3".parse().unwrap(),
+        ];
+        assert_eq!(network.resolve("database"), db_ips);
+        assert_eq!(network.resolve("vm-db"), db_ips);
+        assert!(network.resolve("cache").is_empty());
+        assert_eq!(db.gateway(), "10.89.1.1");
+        assert_eq!(network.subnet6(), "fd89:0:0:1::/64");
+        assert_eq!(db.multicast_group().to_string(), "239.89.1.1:5489");
+
+        assert!(manager.remove_network("backend").await.is_err());
+        manager.release_vm("vm-api").await.unwrap();
+        assert_eq!(manager.get_vm_network("vm-api").await.unwrap(), None);
+        let reused = manager
+            .assign_vm_to_network("vm-web", "backend", None)
+            .await
+            .unwrap();
+        assert_eq!(reused.ip_address, "10.89.1.2");
+    }
+
+    #[tokio::test]
+    async fn test_egress_proxy_allows_only_the_policy_hosts() {
+        let service = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let service_port = service.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut conn, _) = service.accept().await.unwrap();
+            conn.write_all(b"hello").await.unwrap();
+        });
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let proxy = listener.local_addr().unwrap();
+        tokio::spawn(serve_egress(listener, NetworkPolicy::HostOnly));
+
+        let request = |target: String| async move {
+            let mut conn = TcpStream::connect(proxy).await.unwrap();
+            let head = format!("CONNECT {} HTTP/1.1\r\nHost: {0}\r\n\r\n", target);
+            conn.write_all(head.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            conn.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = request(format!("localhost:{}", service_port)).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("hello"));
+        let response = request("example.com:443".to_string()).await;
+        assert!(response.starts_with("HTTP/1.1 403"));
+
+        let allow: NetworkPolicy = "allow:*.github.com,PyPI.org".parse().unwrap();
+        assert!(allow.allows("github.com"));
+        assert!(allow.allows("api.github.com."));
+        assert!(allow.allows("files.pypi.org"));
+        assert!(!allow.allows("evilgithub.com"));
+        assert!(!allow.allows("localhost"));
+        assert_eq!(
+            split_authority("[::1]:8080", 80),
+            Some(("::1".to_string(), 8080))
+        );
+        assert!(NetworkPolicy::HostOnly.allows("::1"));
+        assert!(NetworkPolicy::HostOnly.allows("Host.Vortex.Internal."));
+        assert!("allow:".parse::<NetworkPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_ingress_routes_names_to_forwarded_ports() {
+        let mut web = VmSpec::default();
+        web.ports.insert(49152, 8000);
+        web.labels
+            .insert(LABEL_SESSION_NAME.to_string(), "web".to_string());
+        let mut api = VmSpec::default();
+        api.ports.extend([(49153, 3000), (49154, 9090)]);
+        api.labels
+            .insert(LABEL_SERVICE.to_string(), "api".to_string());
+        let vms = || [("vortex-1", &web), ("vortex-2", &api)].into_iter();
+
+        let port = |host| route(host, vms()).map(|address| address.port());
+        assert_eq!(port("web.localhost:8880"), Ok(49152));
+        assert_eq!(port("VORTEX-1.localhost"), Ok(49152));
+        assert_eq!(port("9090.api.localhost:8880"), Ok(49154));
+        assert!(route("api.localhost:8880", vms())
+            .unwrap_err()
+            .contains("several ports (3000, 9090)"));
+        assert!(route("8080.web.localhost", vms()).is_err());
+        assert!(route("db.localhost", vms()).is_err());
+        assert!(route("example.com", vms()).is_err());
+    }
+}
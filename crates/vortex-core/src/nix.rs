@@ -0,0 +1,100 @@
+//! Nix provisioning for dev templates and workspaces.
+//!
+//! A template or workspace may declare a flake devshell instead of (or in
+//! addition to) imperative `startup_commands`. The VM then enters the shell
+//! with `nix develop`, which builds or substitutes the closure inside the
+//! guest. When the host has a Nix store it is mounted into the guest and used
+//! as a read-only substituter, so closures already on the host aren't fetched
+//! again.
+
+use crate::error::{Result, VortexError};
+use crate::vm::VmSpec;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const HOST_NIX_DIR: &str = "/nix";
+const GUEST_HOST_ROOT: &str = "/vortex-host";
+
+fn default_devshell() -> String {
+    "default".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NixEnvironment {
+    /// Flake reference, relative to the working directory (e.g. "." or "github:org/repo")
+    pub flake: String,
+    /// devShell attribute to enter
+    #[serde(default = "default_devshell")]
+    pub devshell: String,
+    /// Mount the host's /nix into the guest as a read-only substituter when present
+    #[serde(default = "default_true")]
+    pub share_host_store: bool,
+}
+
+impl NixEnvironment {
+    pub fn validate(&self) -> Result<()> {
+        let flake_ok = !self.flake.is_empty()
+            && self.flake.chars().all(|c| {
+                c.is_ascii_alphanumeric()
+                    || matches!(c, '.' | ':' | '/' | '_' | '-' | '+' | '@' | '~')
+            });
+        if !flake_ok {
+            return Err(VortexError::InvalidInput {
+                field: "nix.flake".to_string(),
+                message: format!("Invalid flake reference '{}'", self.flake),
+            });
+        }
+
+        let devshell_ok = !self.devshell.is_empty()
+            && self
+                .devshell
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !devshell_ok {
+            return Err(VortexError::InvalidInput {
+                field: "nix.devshell".to_string(),
+                message: format!("Invalid devshell name '{}'", self.devshell),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Installable passed to `nix develop`
+    pub fn installable(&self) -> String {
+        format!("{}#{}", self.flake, self.devshell)
+    }
+
+    /// Shell command that enters the devshell, used in place of `exec bash`
+    pub fn shell_command(&self) -> String {
+        format!("exec nix develop {} --command bash", self.installable())
+    }
+
+    /// Add the Nix settings and optional host store mount to `spec`
+    pub fn apply(&self, spec: &mut VmSpec) -> Result<()> {
+        self.validate()?;
+
+        let mut nix_config = vec!["experimental-features = nix-command flakes".to_string()];
+
+        if self.share_host_store && Path::new(HOST_NIX_DIR).join("store").is_dir() {
+            spec.volumes.insert(
+                PathBuf::from(HOST_NIX_DIR),
+                Path::new(GUEST_HOST_ROOT).join("nix"),
+            );
+            nix_config.push(format!(
+                "extra-substituters = local?root={}&read-only=true",
+                GUEST_HOST_ROOT
+            ));
+        }
+
+        spec.environment
+            .insert("NIX_CONFIG".to_string(), nix_config.join("\n"));
+        spec.labels
+            .insert("vortex.nix".to_string(), self.installable());
+        Ok(())
+    }
+}
@@ -0,0 +1,619 @@
+//! Single sign-on through an OpenID Connect issuer, for shared Vortex hosts.
+//!
+//! `vortex login` signs in with the OAuth 2.0 device authorization grant
+//! (RFC 8628): it shows a code to enter at the issuer in a browser, polls
+//! until the sign-in completes, and caches the tokens under
+//! `~/.vortex/oidc`, refreshing them once they expire.
+//!
+//! Tokens presented to [`OidcAuthProvider`] are checked by asking the
+//! issuer's userinfo endpoint for their claims, so no signing keys need to
+//! be fetched or rotated. Only tokens issued to `client_id` are accepted:
+//! the audience of a JWT access token, or else what the issuer's
+//! introspection endpoint says, must name it. The groups in the claims map
+//! to permissions through `[auth.oidc.group_permissions]`. Like pulling
+//! images, this talks to the issuer with `curl`.
+
+use crate::auth::{AuthCredentials, AuthProvider, AuthToken, Permission, User};
+use crate::error::{Result, VortexError};
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// How long the claims of a checked token are trusted before the issuer is
+/// asked again
+const CLAIMS_TTL_SECS: i64 = 300;
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// The `[auth.oidc]` section of the config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfig {
+    /// Issuer URL, whose `/.well-known/openid-configuration` is read
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// Claim listing the user's groups
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+    /// Permissions of the members of each group
+    #[serde(default)]
+    pub group_permissions: HashMap<String, Vec<Permission>>,
+    /// Permissions of everyone who signs in
+    #[serde(default)]
+    pub default_permissions: Vec<Permission>,
+}
+
+fn default_scopes() -> Vec<String> {
+    ["openid", "profile", "email", "offline_access"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_groups_claim() -> String {
+    "groups".to_string()
+}
+
+impl OidcConfig {
+    /// Permissions of the user with `claims`
+    pub fn permissions(&self, claims: &Value) -> Vec<Permission> {
+        let groups: Vec<&str> = match claims.get(&self.groups_claim) {
+            Some(Value::Array(groups)) => groups.iter().filter_map(Value::as_str).collect(),
+            Some(Value::String(group)) => vec![group.as_str()],
+            _ => Vec::new(),
+        };
+        let mut permissions = self.default_permissions.clone();
+        for group in groups {
+            for permission in self.group_permissions.get(group).into_iter().flatten() {
+                if !permissions.contains(permission) {
+                    permissions.push(*permission);
+                }
+            }
+        }
+        permissions
+    }
+}
+
+/// What to show the user while a device sign-in waits for them
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceCode {
+    device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// The verification URI with the code filled in, where supported
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// Tokens of a signed-in user, as cached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcTokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Endpoints the issuer advertises
+#[derive(Debug, Clone, Deserialize)]
+struct Discovery {
+    issuer: String,
+    device_authorization_endpoint: Option<String>,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+    introspection_endpoint: Option<String>,
+}
+
+/// Claims of a checked token, and until when they are trusted
+struct Checked {
+    claims: Value,
+    until: DateTime<Utc>,
+}
+
+pub struct OidcAuthProvider {
+    config: OidcConfig,
+    /// Where tokens are cached
+    cache_dir: PathBuf,
+    discovery: tokio::sync::OnceCell<Discovery>,
+    /// Claims of checked tokens, by their SHA-256
+    checked: RwLock<HashMap<String, Checked>>,
+}
+
+impl OidcAuthProvider {
+    /// A provider caching tokens under `~/.vortex/oidc`
+    pub fn new(config: OidcConfig) -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::ConfigError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self::with_cache_dir(
+            config,
+            home.join(".vortex").join("oidc"),
+        ))
+    }
+
+    pub fn with_cache_dir(config: OidcConfig, cache_dir: PathBuf) -> Self {
+        Self {
+            config,
+            cache_dir,
+            discovery: tokio::sync::OnceCell::new(),
+            checked: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Sign in with the device authorization grant: `prompt` is shown the
+    /// code to enter, and the tokens are cached once the user did
+    pub async fn login(&self, prompt: impl FnOnce(&DeviceCode)) -> Result<OidcTokens> {
+        let discovery = self.discovery().await?;
+        let endpoint = discovery
+            .device_authorization_endpoint
+            .as_deref()
+            .ok_or_else(|| {
+                auth_error(format!(
+                    "{} does not support signing in with a device code",
+                    self.config.issuer
+                ))
+            })?;
+        let scope = self.config.scopes.join(" ");
+        let response = post_form(
+            endpoint,
+            &[("client_id", &self.config.client_id), ("scope", &scope)],
+        )
+        .await?;
+        let device: DeviceCode = serde_json::from_value(response)
+            .map_err(|e| auth_error(format!("Unexpected device code response: {}", e)))?;
+        prompt(&device);
+
+        let deadline = Utc::now() + chrono::Duration::seconds(device.expires_in as i64);
+        let mut interval = device.interval;
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            if Utc::now() > deadline {
+                return Err(auth_error("The sign-in code expired".to_string()));
+            }
+            let response = post_form(
+                &discovery.token_endpoint,
+                &[
+                    ("device_code", &device.device_code),
+                    ("grant_type", DEVICE_CODE_GRANT),
+                    ("client_id", &self.config.client_id),
+                ],
+            )
+            .await?;
+            match response.get("error").and_then(Value::as_str) {
+                None => {
+                    let tokens = tokens_from(&response, None)?;
+                    self.save(&tokens)?;
+                    return Ok(tokens);
+                }
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += 5,
+                Some("access_denied") => {
+                    return Err(auth_error("The sign-in was denied".to_string()))
+                }
+                Some(error) => return Err(auth_error(format!("Signing in failed: {}", error))),
+            }
+        }
+    }
+
+    /// The cached tokens, refreshed when they expired; `None` when no one
+    /// signed in or the sign-in can no longer be refreshed
+    pub async fn cached_tokens(&self) -> Result<Option<OidcTokens>> {
+        let Some(tokens) = self.load()? else {
+            return Ok(None);
+        };
+        if tokens.expires_at > Utc::now() + chrono::Duration::seconds(30) {
+            return Ok(Some(tokens));
+        }
+        let Some(refresh_token) = &tokens.refresh_token else {
+            return Ok(None);
+        };
+        let discovery = self.discovery().await?;
+        let response = post_form(
+            &discovery.token_endpoint,
+            &[
+                ("refresh_token", refresh_token),
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.config.client_id),
+            ],
+        )
+        .await?;
+        if response.get("error").is_some() {
+            return Ok(None);
+        }
+        let refreshed = tokens_from(&response, Some(&tokens))?;
+        self.save(&refreshed)?;
+        Ok(Some(refreshed))
+    }
+
+    /// Forget the cached tokens
+    pub fn logout(&self) -> Result<()> {
+        match fs::remove_file(self.cache_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Claims of the access token `token`, as the issuer tells them, if it
+    /// was issued to this client
+    pub async fn claims(&self, token: &str) -> Result<Value> {
+        let digest = format!("{:x}", Sha256::digest(token.as_bytes()));
+        {
+            let checked = self.checked.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(checked) = checked.get(&digest).filter(|c| c.until > Utc::now()) {
+                return Ok(checked.claims.clone());
+            }
+        }
+
+        let discovery = self.discovery().await?;
+        // The header goes through stdin to keep the token out of the
+        // process list
+        let (status, body) = curl(
+            &discovery.userinfo_endpoint,
+            &["-H".to_string(), "@-".to_string()],
+            Some(format!("Authorization: Bearer {}\n", token)),
+        )
+        .await?;
+        if status != 200 {
+            return Err(auth_error("Invalid or expired token".to_string()));
+        }
+        let claims: Value = serde_json::from_str(&body)
+            .map_err(|e| auth_error(format!("Unexpected userinfo response: {}", e)))?;
+        if claims.get("sub").and_then(Value::as_str).is_none() {
+            return Err(auth_error("The issuer returned no subject".to_string()));
+        }
+        // Userinfo answers for a token of any application of the issuer
+        let ours = match jwt_claims(token) {
+            Some(payload) => issued_to(&payload, &self.config.client_id),
+            None => self.introspect(discovery, token).await?,
+        };
+        if !ours {
+            return Err(auth_error(format!(
+                "The token was not issued to {}",
+                self.config.client_id
+            )));
+        }
+
+        let mut checked = self.checked.write().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now();
+        checked.retain(|_, c| c.until > now);
+        checked.insert(
+            digest,
+            Checked {
+                claims: claims.clone(),
+                until: now + chrono::Duration::seconds(CLAIMS_TTL_SECS),
+            },
+        );
+        Ok(claims)
+    }
+
+    /// Whether the issuer says the opaque access token `token` is active
+    /// and was issued to this client (RFC 7662)
+    async fn introspect(&self, discovery: &Discovery, token: &str) -> Result<bool> {
+        let endpoint = discovery.introspection_endpoint.as_deref().ok_or_else(|| {
+            auth_error(format!(
+                "{} issues access tokens that are not JWTs and has no introspection endpoint, \
+                 so who they were issued to cannot be checked",
+                self.config.issuer
+            ))
+        })?;
+        let response = post_form(
+            endpoint,
+            &[("token", token), ("client_id", &self.config.client_id)],
+        )
+        .await?;
+        let client_id = self.config.client_id.as_str();
+        let active = response.get("active").and_then(Value::as_bool) == Some(true);
+        let client = response.get("client_id").and_then(Value::as_str);
+        Ok(active && (client == Some(client_id) || issued_to(&response, client_id)))
+    }
+
+    async fn discovery(&self) -> Result<&Discovery> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let issuer = self.config.issuer.trim_end_matches('/');
+                let url = format!("{}/.well-known/openid-configuration", issuer);
+                let (status, body) = curl(&url, &[], None).await?;
+                if status != 200 {
+                    return Err(auth_error(format!("{} returned {}", url, status)));
+                }
+                let discovery: Discovery = serde_json::from_str(&body)
+                    .map_err(|e| auth_error(format!("Invalid discovery document: {}", e)))?;
+                if discovery.issuer.trim_end_matches('/') != issuer {
+                    return Err(auth_error(format!(
+                        "{} claims to be issuer {}",
+                        url, discovery.issuer
+                    )));
+                }
+                Ok(discovery)
+            })
+            .await
+    }
+
+    /// Cache file of this issuer and client
+    fn cache_path(&self) -> PathBuf {
+        let key = format!("{}\n{}", self.config.issuer, self.config.client_id);
+        let digest = format!("{:x}", Sha256::digest(key.as_bytes()));
+        self.cache_dir.join(format!("{}.json", &digest[..16]))
+    }
+
+    fn load(&self) -> Result<Option<OidcTokens>> {
+        match fs::read(self.cache_path()) {
+            Ok(content) => Ok(serde_json::from_slice(&content).ok()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, tokens: &OidcTokens) -> Result<()> {
+        write_private(&self.cache_path(), &serde_json::to_vec(tokens)?)
+    }
+}
+
+#[async_trait]
+impl AuthProvider for OidcAuthProvider {
+    async fn authenticate(&self, credentials: &AuthCredentials) -> Result<AuthToken> {
+        let token = match credentials {
+            AuthCredentials::Token { token } => token,
+            AuthCredentials::ApiKey { key } => key,
+            AuthCredentials::UsernamePassword { .. } => {
+                return Err(auth_error(
+                    "Passwords are not accepted; sign in with `vortex login`".to_string(),
+                ))
+            }
+        };
+        let claims = self.claims(token).await?;
+        Ok(AuthToken {
+            token: token.clone(),
+            user_id: claims["sub"].as_str().unwrap_or_default().to_string(),
+            expires_at: Utc::now() + chrono::Duration::seconds(CLAIMS_TTL_SECS),
+            permissions: self.config.permissions(&claims),
+        })
+    }
+
+    async fn authorize(&self, token: &str, permission: Permission) -> Result<bool> {
+        let claims = self.claims(token).await?;
+        Ok(self
+            .config
+            .permissions(&claims)
+            .iter()
+            .any(|p| *p == permission || *p == Permission::AdminAll))
+    }
+
+    async fn get_user(&self, user_id: &str) -> Result<Option<User>> {
+        let checked = self.checked.read().unwrap_or_else(|e| e.into_inner());
+        Ok(checked
+            .values()
+            .map(|c| &c.claims)
+            .find(|claims| claims["sub"].as_str() == Some(user_id))
+            .map(|claims| User {
+                id: user_id.to_string(),
+                username: claims["preferred_username"]
+                    .as_str()
+                    .unwrap_or(user_id)
+                    .to_string(),
+                email: claims["email"].as_str().map(str::to_string),
+                roles: Vec::new(),
+                permissions: self.config.permissions(claims),
+            }))
+    }
+
+    async fn refresh_token(&self, token: &str) -> Result<AuthToken> {
+        // The signed-in user's own token is refreshed; others are only
+        // checked, as their refresh tokens are not ours
+        let token = match self.load()? {
+            Some(cached) if cached.access_token == token => self
+                .cached_tokens()
+                .await?
+                .map(|tokens| tokens.access_token)
+                .ok_or_else(|| auth_error("The sign-in expired; run `vortex login`".to_string()))?,
+            _ => token.to_string(),
+        };
+        self.authenticate(&AuthCredentials::Token { token }).await
+    }
+}
+
+/// Tokens of a token endpoint `response`, keeping the refresh and ID
+/// tokens of `previous` it does not replace
+fn tokens_from(response: &Value, previous: Option<&OidcTokens>) -> Result<OidcTokens> {
+    let text = |key: &str| {
+        response
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let access_token = text("access_token")
+        .ok_or_else(|| auth_error("The issuer returned no access token".to_string()))?;
+    let expires_in = response
+        .get("expires_in")
+        .and_then(Value::as_i64)
+        .unwrap_or(3600);
+    Ok(OidcTokens {
+        access_token,
+        refresh_token: text("refresh_token").or_else(|| previous?.refresh_token.clone()),
+        id_token: text("id_token").or_else(|| previous?.id_token.clone()),
+        expires_at: Utc::now() + chrono::Duration::seconds(expires_in),
+    })
+}
+
+/// The payload of `token` if it is a JWT. Its signature is not checked:
+/// only tokens the issuer accepted get this far.
+fn jwt_claims(token: &str) -> Option<Value> {
+    let mut parts = token.split('.');
+    let (_, payload, _) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&payload).ok()
+}
+
+/// Whether `claims` name `client_id` as their audience or authorized party
+fn issued_to(claims: &Value, client_id: &str) -> bool {
+    let audience = match claims.get("aud") {
+        Some(Value::String(aud)) => aud == client_id,
+        Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    audience || claims.get("azp").and_then(Value::as_str) == Some(client_id)
+}
+
+/// POST `form` to `url` and return the JSON answer, errors included. The
+/// first value goes through stdin, so the secret put there stays out of
+/// the process list.
+async fn post_form(url: &str, form: &[(&str, &str)]) -> Result<Value> {
+    let mut args = Vec::new();
+    for (i, (name, value)) in form.iter().enumerate() {
+        args.push("--data-urlencode".to_string());
+        args.push(match i {
+            0 => format!("{}@-", name),
+            _ => format!("{}={}", name, value),
+        });
+    }
+    let first = form.first().map(|(_, value)| value.to_string());
+    let (_, body) = curl(url, &args, first).await?;
+    serde_json::from_str(&body)
+        .map_err(|e| auth_error(format!("Unexpected answer from {}: {}", url, e)))
+}
+
+/// Status and body of a request to `url` with `args`, `stdin` fed to curl.
+/// Callers name where curl reads stdin, e.g. `-H @-` or `--data-urlencode
+/// name@-`.
+async fn curl(url: &str, args: &[String], stdin: Option<String>) -> Result<(u16, String)> {
+    let mut command = Command::new("curl");
+    command.args(["-sS", "-w", "\n%{http_code}"]).args(args);
+    command
+        .arg(url)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    let mut child = command
+        .spawn()
+        .map_err(|e| auth_error(format!("Failed to run curl, which signing in needs: {}", e)))?;
+    if let Some(mut pipe) = child.stdin.take() {
+        pipe.write_all(stdin.unwrap_or_default().as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(auth_error(format!(
+            "Failed to reach {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = text.rsplit_once('\n').unwrap_or(("", &text));
+    Ok((status.trim().parse().unwrap_or(0), body.to_string()))
+}
+
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&tmp)?.write_all(data)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn auth_error(message: String) -> VortexError {
+    VortexError::AuthError { message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_groups_map_to_permissions_and_tokens_are_cached() {
+        let config: OidcConfig = toml::from_str(
+            r#"
+            issuer = "https://login.example.com"
+            client_id = "vortex"
+            default_permissions = ["VmRead"]
+
+            [group_permissions]
+            developers = ["VmCreate", "VmRead"]
+            admins = ["AdminAll"]
+            "#,
+        )
+        .unwrap();
+        let claims = serde_json::json!({"sub": "u1", "groups": ["developers", "sales"]});
+        assert_eq!(
+            config.permissions(&claims),
+            [Permission::VmRead, Permission::VmCreate]
+        );
+        let claims = serde_json::json!({"sub": "u2", "groups": "admins"});
+        assert!(config.permissions(&claims).contains(&Permission::AdminAll));
+
+        let dir = tempfile::tempdir().unwrap();
+        let provider = OidcAuthProvider::with_cache_dir(config, dir.path().to_path_buf());
+        assert!(provider.cached_tokens().await.unwrap().is_none());
+        let response = serde_json::json!({
+            "access_token": "at-1",
+            "refresh_token": "rt-1",
+            "expires_in": 600,
+        });
+        provider
+            .save(&tokens_from(&response, None).unwrap())
+            .unwrap();
+        let cached = provider.cached_tokens().await.unwrap().unwrap();
+        assert_eq!(cached.access_token, "at-1");
+
+        // A refresh without a new refresh token keeps the old one
+        let refreshed =
+            tokens_from(&serde_json::json!({"access_token": "at-2"}), Some(&cached)).unwrap();
+        assert_eq!(refreshed.refresh_token.as_deref(), Some("rt-1"));
+        provider.logout().unwrap();
+        provider.logout().unwrap();
+        assert!(provider.cached_tokens().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tokens_must_be_issued_to_the_client() {
+        let jwt = |payload: Value| {
+            format!(
+                "eyJhbGciOiJSUzI1NiJ9.{}.c2ln",
+                URL_SAFE_NO_PAD.encode(payload.to_string())
+            )
+        };
+        let ours = jwt_claims(&jwt(serde_json::json!({"sub": "u1", "aud": "vortex"}))).unwrap();
+        assert!(issued_to(&ours, "vortex"));
+        let listed = serde_json::json!({"aud": ["account", "vortex"]});
+        assert!(issued_to(&listed, "vortex"));
+        let authorized = serde_json::json!({"aud": "account", "azp": "vortex"});
+        assert!(issued_to(&authorized, "vortex"));
+
+        let theirs = jwt_claims(&jwt(serde_json::json!({"sub": "u1", "aud": "grafana"}))).unwrap();
+        assert!(!issued_to(&theirs, "vortex"));
+        assert!(!issued_to(&serde_json::json!({"sub": "u1"}), "vortex"));
+        assert!(jwt_claims("opaque-token").is_none());
+    }
+}
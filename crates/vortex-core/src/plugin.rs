@@ -0,0 +1,695 @@
+use crate::backend::BackendProvider;
+use crate::config::PluginConfig;
+use crate::error::{Result, VortexError};
+use crate::native_plugin::NativePlugin;
+use crate::process_plugin::{ProcessPlugin, ProcessPluginConfig};
+use crate::templates::DevTemplate;
+use crate::vm::{VmInstance, VmSpec};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginMetadata {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub author: String,
+    pub hooks: Vec<PluginHook>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PluginHook {
+    VmPreCreate,
+    VmPostCreate,
+    VmPreStart,
+    VmPostStart,
+    VmPreStop,
+    VmPostStop,
+    VmPreDelete,
+    VmPostDelete,
+    SnapshotCreate,
+    SnapshotRestore,
+}
+
+impl PluginHook {
+    pub const ALL: [PluginHook; 10] = [
+        PluginHook::VmPreCreate,
+        PluginHook::VmPostCreate,
+        PluginHook::VmPreStart,
+        PluginHook::VmPostStart,
+        PluginHook::VmPreStop,
+        PluginHook::VmPostStop,
+        PluginHook::VmPreDelete,
+        PluginHook::VmPostDelete,
+        PluginHook::SnapshotCreate,
+        PluginHook::SnapshotRestore,
+    ];
+
+    /// Name used in capability grants, e.g. `vm-post-start`
+    pub fn name(&self) -> &'static str {
+        match self {
+            PluginHook::VmPreCreate => "vm-pre-create",
+            PluginHook::VmPostCreate => "vm-post-create",
+            PluginHook::VmPreStart => "vm-pre-start",
+            PluginHook::VmPostStart => "vm-post-start",
+            PluginHook::VmPreStop => "vm-pre-stop",
+            PluginHook::VmPostStop => "vm-post-stop",
+            PluginHook::VmPreDelete => "vm-pre-delete",
+            PluginHook::VmPostDelete => "vm-post-delete",
+            PluginHook::SnapshotCreate => "snapshot-create",
+            PluginHook::SnapshotRestore => "snapshot-restore",
+        }
+    }
+}
+
+/// Host APIs a sandboxed plugin can be granted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PluginApi {
+    /// Write to the Vortex log
+    Log,
+    /// See the full VM spec, including environment and volumes. Without it
+    /// a plugin only sees the VM's id, image, state and size.
+    VmSpec,
+}
+
+impl PluginApi {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PluginApi::Log => "log",
+            PluginApi::VmSpec => "vm-spec",
+        }
+    }
+}
+
+/// One grant, written `hook:<hook>`, `api:<api>`, `read:<path>`,
+/// `write:<path>` or `net:<host>:<port>`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Capability {
+    Hook(PluginHook),
+    Api(PluginApi),
+    Read(PathBuf),
+    Write(PathBuf),
+    Net(String),
+}
+
+impl std::str::FromStr for Capability {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "capability".to_string(),
+            message,
+        };
+        let (kind, value) = s.split_once(':').ok_or_else(|| {
+            invalid(format!(
+                "'{}' is not of the form hook:, api:, read:, write: or net:",
+                s
+            ))
+        })?;
+        match kind {
+            "hook" => PluginHook::ALL
+                .into_iter()
+                .find(|hook| hook.name() == value)
+                .map(Capability::Hook)
+                .ok_or_else(|| invalid(format!("Unknown hook '{}'", value))),
+            "api" => [PluginApi::Log, PluginApi::VmSpec]
+                .into_iter()
+                .find(|api| api.name() == value)
+                .map(Capability::Api)
+                .ok_or_else(|| invalid(format!("Unknown API '{}'", value))),
+            "read" | "write" => {
+                let path = Path::new(value);
+                if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+                    return Err(invalid(format!(
+                        "'{}' must be an absolute path without '..'",
+                        value
+                    )));
+                }
+                // Grants name real locations so symlinks cannot widen them
+                let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+                Ok(if kind == "read" {
+                    Capability::Read(path)
+                } else {
+                    Capability::Write(path)
+                })
+            }
+            "net" => match value.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                    Ok(Capability::Net(value.to_string()))
+                }
+                _ => Err(invalid(format!("'{}' is not host:port", value))),
+            },
+            _ => Err(invalid(format!("Unknown capability kind '{}'", kind))),
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::Hook(hook) => write!(f, "hook:{}", hook.name()),
+            Capability::Api(api) => write!(f, "api:{}", api.name()),
+            Capability::Read(path) => write!(f, "read:{}", path.display()),
+            Capability::Write(path) => write!(f, "write:{}", path.display()),
+            Capability::Net(address) => write!(f, "net:{}", address),
+        }
+    }
+}
+
+/// Everything a sandboxed plugin is allowed to do. Nothing is granted by
+/// default; the user adds grants with `vortex plugin grant`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PluginGrants {
+    #[serde(default)]
+    pub hooks: Vec<PluginHook>,
+    #[serde(default)]
+    pub apis: Vec<PluginApi>,
+    /// Directories or files the plugin may read
+    #[serde(default)]
+    pub read: Vec<PathBuf>,
+    /// Directories or files the plugin may read and write
+    #[serde(default)]
+    pub write: Vec<PathBuf>,
+    /// `host:port` addresses the plugin may connect to
+    #[serde(default)]
+    pub net: Vec<String>,
+}
+
+impl PluginGrants {
+    pub fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities: Vec<Capability> =
+            self.hooks.iter().cloned().map(Capability::Hook).collect();
+        capabilities.extend(self.apis.iter().copied().map(Capability::Api));
+        capabilities.extend(self.read.iter().cloned().map(Capability::Read));
+        capabilities.extend(self.write.iter().cloned().map(Capability::Write));
+        capabilities.extend(self.net.iter().cloned().map(Capability::Net));
+        capabilities
+    }
+
+    pub fn grant(&mut self, capability: Capability) {
+        fn add<T: PartialEq>(list: &mut Vec<T>, item: T) {
+            if !list.contains(&item) {
+                list.push(item);
+            }
+        }
+        match capability {
+            Capability::Hook(hook) => add(&mut self.hooks, hook),
+            Capability::Api(api) => add(&mut self.apis, api),
+            Capability::Read(path) => add(&mut self.read, path),
+            Capability::Write(path) => add(&mut self.write, path),
+            Capability::Net(address) => add(&mut self.net, address),
+        }
+    }
+
+    /// Remove a grant, returning whether it was present
+    pub fn revoke(&mut self, capability: &Capability) -> bool {
+        fn remove<T: PartialEq>(list: &mut Vec<T>, item: &T) -> bool {
+            let before = list.len();
+            list.retain(|existing| existing != item);
+            list.len() != before
+        }
+        match capability {
+            Capability::Hook(hook) => remove(&mut self.hooks, hook),
+            Capability::Api(api) => remove(&mut self.apis, api),
+            Capability::Read(path) => remove(&mut self.read, path),
+            Capability::Write(path) => remove(&mut self.write, path),
+            Capability::Net(address) => remove(&mut self.net, address),
+        }
+    }
+
+    pub fn allows_api(&self, api: PluginApi) -> bool {
+        self.apis.contains(&api)
+    }
+
+    /// Write grants imply read access
+    pub fn allows_read(&self, path: &Path) -> bool {
+        resolve(path).is_some_and(|path| {
+            self.read
+                .iter()
+                .chain(&self.write)
+                .any(|granted| path.starts_with(granted))
+        })
+    }
+
+    pub fn allows_write(&self, path: &Path) -> bool {
+        resolve(path).is_some_and(|path| self.write.iter().any(|granted| path.starts_with(granted)))
+    }
+
+    pub fn allows_connect(&self, address: &str) -> bool {
+        self.net.iter().any(|granted| granted == address)
+    }
+}
+
+/// Real location of `path`, following symlinks in every existing component
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+        return None;
+    }
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    // A file about to be created: its directory must exist
+    let name = path.file_name()?;
+    Some(path.parent()?.canonicalize().ok()?.join(name))
+}
+
+/// Where plugins are installed: `~/.vortex/plugins`
+pub fn plugins_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".vortex").join("plugins"))
+}
+
+#[async_trait]
+pub trait Plugin: Send + Sync + std::fmt::Debug {
+    fn metadata(&self) -> &PluginMetadata;
+
+    /// Called once before the plugin is used, with the `settings` of its
+    /// `[plugins.<name>]` section of the config (`Value::Null` without any)
+    async fn initialize(&mut self, settings: &serde_json::Value) -> Result<()>;
+    async fn shutdown(&mut self) -> Result<()>;
+
+    /// Add backends for hypervisors Vortex does not ship, with
+    /// `BackendProvider::register`. Called once at startup, after the
+    /// built-in backends, so a plugin backend is only the default when the
+    /// host has no other; a plugin registering a built-in name replaces it.
+    fn register_backends(&self, _provider: &mut BackendProvider) -> Result<()> {
+        Ok(())
+    }
+
+    /// Dev templates to offer next to the built-in ones, such as a pack
+    /// for a language community. Asked once at startup.
+    fn templates(&self) -> Vec<DevTemplate> {
+        Vec::new()
+    }
+
+    // Hook implementations. `VmManager` calls each for the plugins whose
+    // metadata lists its hook.
+
+    /// Change the spec of a VM about to be created (`VmPreCreate`). An
+    /// error refuses to create the VM.
+    async fn on_spec_mutate(&self, _spec: &mut VmSpec) -> Result<()> {
+        Ok(())
+    }
+
+    /// A VM was created (`VmPostCreate`)
+    async fn on_vm_create(&self, _vm: &VmInstance) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_vm_pre_start(&self, _vm: &VmInstance) -> Result<()> {
+        Ok(())
+    }
+
+    /// A VM started running (`VmPostStart`)
+    async fn on_vm_start(&self, _vm: &VmInstance) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_vm_pre_stop(&self, _vm: &VmInstance) -> Result<()> {
+        Ok(())
+    }
+
+    /// A VM was stopped (`VmPostStop`)
+    async fn on_vm_stop(&self, _vm: &VmInstance) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_snapshot_create(&self, _vm: &VmInstance, _snapshot_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_snapshot_restore(&self, _snapshot_id: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct PluginManager {
+    plugins: HashMap<String, Box<dyn Plugin>>,
+    config: HashMap<String, PluginConfig>,
+}
+
+impl PluginManager {
+    pub async fn new() -> Result<Self> {
+        Ok(Self {
+            plugins: HashMap::new(),
+            config: HashMap::new(),
+        })
+    }
+
+    /// Take the `[plugins]` section of the config into account when
+    /// registering plugins
+    pub fn with_config(mut self, config: HashMap<String, PluginConfig>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Initialize `plugin` with its settings and register it, unless the
+    /// config disables it
+    pub async fn register_plugin(&mut self, mut plugin: Box<dyn Plugin>) -> Result<()> {
+        let name = plugin.metadata().name.clone();
+        let config = self.config.get(&name);
+        if config.is_some_and(|config| !config.enabled) {
+            tracing::info!("Plugin {} is disabled", name);
+            return Ok(());
+        }
+        let settings = config
+            .map(|config| config.settings.clone())
+            .unwrap_or_default();
+        plugin
+            .initialize(&settings)
+            .await
+            .map_err(|e| VortexError::PluginError {
+                message: format!("Plugin {} failed to initialize: {}", name, e),
+            })?;
+        tracing::info!(
+            "Registering plugin: {} v{}",
+            name,
+            plugin.metadata().version
+        );
+
+        self.plugins.insert(name, plugin);
+        Ok(())
+    }
+
+    pub async fn call_hook(&self, hook: PluginHook, context: PluginContext) -> Result<()> {
+        for (name, plugin) in &self.plugins {
+            if plugin.metadata().hooks.contains(&hook) {
+                match (&hook, &context) {
+                    (PluginHook::VmPostCreate, PluginContext::VmInstance(vm)) => {
+                        if let Err(e) = plugin.on_vm_create(vm).await {
+                            tracing::warn!("Plugin {} failed on VmPostCreate: {}", name, e);
+                        }
+                    }
+                    (PluginHook::VmPostStart, PluginContext::VmInstance(vm)) => {
+                        if let Err(e) = plugin.on_vm_start(vm).await {
+                            tracing::warn!("Plugin {} failed on VmPostStart: {}", name, e);
+                        }
+                    }
+                    (PluginHook::VmPostStop, PluginContext::VmInstance(vm)) => {
+                        if let Err(e) = plugin.on_vm_stop(vm).await {
+                            tracing::warn!("Plugin {} failed on VmPostStop: {}", name, e);
+                        }
+                    }
+                    _ => {
+                        // Handle other hooks
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load the plugin of every shared library directly in `dir`, such as
+    /// `libinventory.so` in [`plugins_dir`], and return how many were
+    /// registered. A library that fails to load is skipped with a warning.
+    pub async fn load_from_dir(&mut self, dir: &Path) -> Result<usize> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut libraries: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        libraries.sort();
+
+        let mut loaded = 0;
+        for library in libraries {
+            let plugin = match NativePlugin::load(&library) {
+                Ok(plugin) => plugin,
+                Err(e) => {
+                    tracing::warn!("Skipping plugin: {}", e);
+                    continue;
+                }
+            };
+            match self.register_plugin(Box::new(plugin)).await {
+                Ok(()) => loaded += 1,
+                Err(e) => tracing::warn!("Skipping plugin {}: {}", library.display(), e),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Start the enabled programs of `configs`, in order of their names, and
+    /// return how many were registered. A program that fails to start or to
+    /// answer `initialize` is skipped with a warning.
+    pub async fn load_processes(
+        &mut self,
+        configs: &HashMap<String, ProcessPluginConfig>,
+    ) -> usize {
+        let mut names: Vec<&String> = configs.keys().collect();
+        names.sort();
+        let mut loaded = 0;
+        for name in names {
+            let config = &configs[name];
+            if !config.enabled {
+                continue;
+            }
+            let plugin = ProcessPlugin::new(name, config.clone());
+            match self.register_plugin(Box::new(plugin)).await {
+                Ok(()) => loaded += 1,
+                Err(e) => tracing::warn!("Skipping plugin {}: {}", name, e),
+            }
+        }
+        loaded
+    }
+
+    /// Let the plugins with the `VmPreCreate` hook change `spec`, in order
+    /// of their names. The first to fail refuses the VM.
+    pub async fn mutate_spec(&self, spec: &mut VmSpec) -> Result<()> {
+        let mut names: Vec<&String> = self.plugins.keys().collect();
+        names.sort();
+        for name in names {
+            let plugin = &self.plugins[name];
+            if !plugin.metadata().hooks.contains(&PluginHook::VmPreCreate) {
+                continue;
+            }
+            plugin
+                .on_spec_mutate(spec)
+                .await
+                .map_err(|e| VortexError::PluginError {
+                    message: format!("Plugin {} refused the VM: {}", name, e),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Let every plugin add its backends to `provider`. A failing plugin
+    /// is logged and skipped.
+    pub fn register_backends(&self, provider: &mut BackendProvider) {
+        let mut names: Vec<&String> = self.plugins.keys().collect();
+        names.sort();
+        for name in names {
+            let before = provider.available();
+            if let Err(e) = self.plugins[name].register_backends(provider) {
+                tracing::warn!("Plugin {} failed to register backends: {}", name, e);
+                continue;
+            }
+            let added: Vec<String> = provider
+                .available()
+                .into_iter()
+                .filter(|backend| !before.contains(backend))
+                .collect();
+            if !added.is_empty() {
+                tracing::info!("Plugin {} registered backends: {}", name, added.join(", "));
+            }
+        }
+    }
+
+    /// The dev templates of every plugin, paired with the plugin's name, in
+    /// order of the names
+    pub fn templates(&self) -> Vec<(String, DevTemplate)> {
+        let mut names: Vec<&String> = self.plugins.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .flat_map(|name| {
+                self.plugins[name]
+                    .templates()
+                    .into_iter()
+                    .map(move |template| (name.clone(), template))
+            })
+            .collect()
+    }
+
+    pub fn list_plugins(&self) -> Vec<&PluginMetadata> {
+        self.plugins.values().map(|p| p.metadata()).collect()
+    }
+
+    pub async fn shutdown_all(&mut self) -> Result<()> {
+        for (name, plugin) in self.plugins.iter_mut() {
+            tracing::info!("Shutting down plugin: {}", name);
+            if let Err(e) = plugin.shutdown().await {
+                tracing::warn!("Plugin {} failed to shut down: {}", name, e);
+            }
+        }
+
+        self.plugins.clear();
+        Ok(())
+    }
+}
+
+pub enum PluginContext {
+    VmInstance(VmInstance),
+    VmSpec(VmSpec),
+    SnapshotId(String),
+}
+
+// Example logging plugin
+#[derive(Debug)]
+pub struct LoggingPlugin {
+    metadata: PluginMetadata,
+}
+
+impl Default for LoggingPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoggingPlugin {
+    pub fn new() -> Self {
+        Self {
+            metadata: PluginMetadata {
+                name: "logging".to_string(),
+                version: "1.0.0".to_string(),
+                description: "Logs all VM lifecycle events".to_string(),
+                author: "Vortex Team".to_string(),
+                hooks: vec![
+                    PluginHook::VmPostCreate,
+                    PluginHook::VmPostStart,
+                    PluginHook::VmPostStop,
+                ],
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Plugin for LoggingPlugin {
+    fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    async fn initialize(&mut self, _settings: &serde_json::Value) -> Result<()> {
+        tracing::info!("LoggingPlugin initialized");
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("LoggingPlugin shutting down");
+        Ok(())
+    }
+
+    async fn on_vm_create(&self, vm: &VmInstance) -> Result<()> {
+        tracing::info!("VM Created: {} ({})", vm.id, vm.spec.image);
+        Ok(())
+    }
+
+    async fn on_vm_start(&self, vm: &VmInstance) -> Result<()> {
+        tracing::info!(
+            "VM Started: {} ({}MB RAM, {} CPUs)",
+            vm.id,
+            vm.spec.memory,
+            vm.spec.cpus
+        );
+        Ok(())
+    }
+
+    async fn on_vm_stop(&self, vm: &VmInstance) -> Result<()> {
+        tracing::info!("VM Stopped: {}", vm.id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Remembers the settings it was initialized with
+    #[derive(Debug)]
+    struct SettingsPlugin {
+        metadata: PluginMetadata,
+        settings: Arc<Mutex<Option<serde_json::Value>>>,
+    }
+
+    #[async_trait]
+    impl Plugin for SettingsPlugin {
+        fn metadata(&self) -> &PluginMetadata {
+            &self.metadata
+        }
+
+        async fn initialize(&mut self, settings: &serde_json::Value) -> Result<()> {
+            *self.settings.lock().unwrap() = Some(settings.clone());
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn plugin(name: &str) -> (Box<dyn Plugin>, Arc<Mutex<Option<serde_json::Value>>>) {
+        let settings = Arc::new(Mutex::new(None));
+        let plugin = SettingsPlugin {
+            metadata: PluginMetadata {
+                name: name.to_string(),
+                version: "1.0.0".to_string(),
+                description: String::new(),
+                author: String::new(),
+                hooks: Vec::new(),
+            },
+            settings: settings.clone(),
+        };
+        (Box::new(plugin), settings)
+    }
+
+    #[tokio::test]
+    async fn test_plugins_are_initialized_with_their_settings() {
+        let config: HashMap<String, PluginConfig> = toml::from_str(
+            r#"
+            [inventory]
+            settings = { site = "lab", racks = [1, 2] }
+
+            [noisy]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        let mut manager = PluginManager::new().await.unwrap().with_config(config);
+
+        let (inventory, inventory_settings) = plugin("inventory");
+        let (noisy, noisy_settings) = plugin("noisy");
+        let (other, other_settings) = plugin("other");
+        for plugin in [inventory, noisy, other] {
+            manager.register_plugin(plugin).await.unwrap();
+        }
+
+        assert_eq!(
+            *inventory_settings.lock().unwrap(),
+            Some(serde_json::json!({ "site": "lab", "racks": [1, 2] }))
+        );
+        assert_eq!(
+            *other_settings.lock().unwrap(),
+            Some(serde_json::Value::Null)
+        );
+        assert_eq!(*noisy_settings.lock().unwrap(), None);
+        let mut names: Vec<&str> = manager
+            .list_plugins()
+            .iter()
+            .map(|metadata| metadata.name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["inventory", "other"]);
+    }
+}
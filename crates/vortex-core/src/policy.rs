@@ -0,0 +1,239 @@
+//! Per-project guard rails from a committed `.vortex-policy.yaml`.
+//!
+//! The CLI looks for the policy file in the current directory and its
+//! parents; the directory holding it is treated as the repository root. A
+//! policy can forbid host mounts outside that root, require images pinned by
+//! digest, and run matching commands without a network:
+//!
+//! ```yaml
+//! mounts:
+//!   deny_outside_repo: true
+//! images:
+//!   require_digest: true
+//! network:
+//!   offline_commands: ["cargo test", "npm test"]
+//! ```
+//!
+//! Unknown keys are rejected so a typo cannot silently disable a rule.
+
+use crate::error::{Result, VortexError};
+use crate::vm::{VmSpec, NETWORK_NONE};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const POLICY_FILE: &str = ".vortex-policy.yaml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MountPolicy {
+    /// Reject host paths outside the repository root
+    #[serde(default)]
+    pub deny_outside_repo: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImagePolicy {
+    /// Only accept `image@sha256:<digest>` references
+    #[serde(default)]
+    pub require_digest: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkPolicy {
+    /// Commands, matched by leading words, that must run without a network
+    #[serde(default)]
+    pub offline_commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub mounts: MountPolicy,
+    #[serde(default)]
+    pub images: ImagePolicy,
+    #[serde(default)]
+    pub network: NetworkPolicy,
+}
+
+/// A policy together with the repository root it applies to
+#[derive(Debug, Clone)]
+pub struct ProjectPolicy {
+    pub root: PathBuf,
+    pub path: PathBuf,
+    pub policy: Policy,
+}
+
+fn denied(path: &Path, message: String) -> VortexError {
+    VortexError::PermissionDenied {
+        action: format!("{} (policy {})", message, path.display()),
+    }
+}
+
+impl ProjectPolicy {
+    /// Find the nearest policy file at or above `start`
+    pub fn discover(start: &Path) -> Result<Option<Self>> {
+        for dir in start.ancestors() {
+            let path = dir.join(POLICY_FILE);
+            if path.is_file() {
+                return Self::load(&path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let policy = serde_yaml::from_str(&content).map_err(|e| VortexError::ConfigError {
+            message: format!("Invalid policy {}: {}", path.display(), e),
+        })?;
+        let root = path.parent().unwrap_or(Path::new(".")).canonicalize()?;
+        Ok(Self {
+            root,
+            path: path.to_path_buf(),
+            policy,
+        })
+    }
+
+    pub fn check_mounts(&self, volumes: &HashMap<PathBuf, PathBuf>) -> Result<()> {
+        self.check_host_paths(volumes.keys())
+    }
+
+    /// `--copy` sources of a run, given as host:guest pairs, which reach
+    /// the VM through mounts of their own
+    pub fn check_copies(&self, copies: &[(PathBuf, PathBuf)]) -> Result<()> {
+        self.check_host_paths(copies.iter().map(|(host, _)| host))
+    }
+
+    fn check_host_paths<'a>(&self, hosts: impl IntoIterator<Item = &'a PathBuf>) -> Result<()> {
+        if !self.policy.mounts.deny_outside_repo {
+            return Ok(());
+        }
+        for host in hosts {
+            // Resolve symlinks so a link inside the repo can't point outside it
+            let resolved = host.canonicalize().map_err(|e| {
+                denied(
+                    &self.path,
+                    format!("cannot resolve mount {}: {}", host.display(), e),
+                )
+            })?;
+            if !resolved.starts_with(&self.root) {
+                return Err(denied(
+                    &self.path,
+                    format!(
+                        "mount {} is outside the repository {}",
+                        host.display(),
+                        self.root.display()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_image(&self, image: &str) -> Result<()> {
+        if !self.policy.images.require_digest {
+            return Ok(());
+        }
+        let pinned = image.split_once("@sha256:").is_some_and(|(name, digest)| {
+            !name.is_empty() && digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())
+        });
+        if !pinned {
+            return Err(denied(
+                &self.path,
+                format!("image '{}' must be pinned as name@sha256:<digest>", image),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `command` must run without a network
+    pub fn requires_offline(&self, command: &str) -> bool {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        self.policy.network.offline_commands.iter().any(|prefix| {
+            let prefix: Vec<&str> = prefix.split_whitespace().collect();
+            !prefix.is_empty() && words.starts_with(&prefix)
+        })
+    }
+
+    /// Apply every rule to `spec`, disabling its network when required
+    pub fn enforce(&self, spec: &mut VmSpec) -> Result<()> {
+        self.check_mounts(&spec.volumes)?;
+        self.check_image(&spec.image)?;
+
+        let offline = spec
+            .command
+            .as_deref()
+            .is_some_and(|command| self.requires_offline(command));
+        if offline {
+            if !spec.ports.is_empty() {
+                return Err(denied(
+                    &self.path,
+                    "this command runs without a network, so ports cannot be forwarded".to_string(),
+                ));
+            }
+            spec.network_config = Some(NETWORK_NONE.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_rules() {
+        let repo = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::create_dir(repo.path().join("src")).unwrap();
+        std::fs::write(
+            repo.path().join(POLICY_FILE),
+            "mounts:\n  deny_outside_repo: true\nimages:\n  require_digest: true\nnetwork:\n  offline_commands: [\"cargo test\"]\n",
+        )
+        .unwrap();
+
+        let policy = ProjectPolicy::discover(&repo.path().join("src"))
+            .unwrap()
+            .unwrap();
+        let digest = "a".repeat(64);
+        let mut spec = VmSpec {
+            image: format!("alpine@sha256:{}", digest),
+            command: Some("cargo test --all".to_string()),
+            ..Default::default()
+        };
+        spec.volumes
+            .insert(repo.path().join("src"), PathBuf::from("/src"));
+        policy.enforce(&mut spec).unwrap();
+        assert_eq!(spec.network_config.as_deref(), Some(NETWORK_NONE));
+
+        assert!(policy.check_image("alpine:latest").is_err());
+        let mut outside_mount = HashMap::new();
+        outside_mount.insert(outside.path().to_path_buf(), PathBuf::from("/x"));
+        assert!(policy.check_mounts(&outside_mount).is_err());
+        assert!(!policy.requires_offline("cargo testing"));
+
+        std::fs::write(repo.path().join(POLICY_FILE), "mount:\n  deny: true\n").unwrap();
+        assert!(ProjectPolicy::discover(repo.path()).is_err());
+    }
+
+    #[test]
+    fn test_copy_sources_outside_the_repo_are_denied() {
+        let repo = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(
+            repo.path().join(POLICY_FILE),
+            "mounts:\n  deny_outside_repo: true\n",
+        )
+        .unwrap();
+        let policy = ProjectPolicy::discover(repo.path()).unwrap().unwrap();
+
+        // vortex run --copy <host>:/work
+        let copy = |host: &Path| [(host.to_path_buf(), PathBuf::from("/work"))];
+        policy.check_copies(&copy(repo.path())).unwrap();
+        assert!(policy.check_copies(&copy(outside.path())).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Prewarmed VMs for sub-second starts.
+//!
+//! Booting the guest is most of what `VmManager::create` waits for. A pool
+//! keeps a few VMs booted ahead of demand for each `[[pool]]` target in
+//! `~/.config/vortex/config.toml`:
+//!
+//! ```toml
+//! [[pool]]
+//! image = "alpine:latest"
+//! size = 2
+//!
+//! [[pool]]
+//! template = "python"
+//! size = 1
+//! ```
+//!
+//! A create whose spec boots the same way as a target takes one of its VMs
+//! and only starts its command there. What is applied once the guest is up
+//! (command, environment, labels, hooks, probes, TTL, timeout, provisioning
+//! and tuning) may differ from the target; anything else, such as memory or
+//! volumes, needs a fresh VM. Taken VMs are replaced in the background.
+//!
+//! Only backends whose `create` boots the guest and whose `start` runs the
+//! command can be pooled (see `Backend::supports_prewarm`). The daemon keeps
+//! the pool, so it serves the sessions created through it.
+
+use crate::backend::BackendProvider;
+use crate::error::{Result, VortexError};
+use crate::network;
+use crate::templates::DevEnvironmentManager;
+use crate::vm::{generate_vm_id, LifecycleHooks, ResourceLimits, VmInstance, VmSpec, VmState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Notify};
+
+/// One `[[pool]]` table: which VMs to keep booted, and how many
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolTarget {
+    /// Image to boot; give this or `template`
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Dev environment template whose VMs to boot
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub memory: Option<u32>,
+    #[serde(default)]
+    pub cpus: Option<u32>,
+    #[serde(default)]
+    pub backend: Option<String>,
+    pub size: usize,
+}
+
+impl PoolTarget {
+    /// The spec the target's VMs boot with
+    pub fn spec(&self, templates: &DevEnvironmentManager) -> Result<VmSpec> {
+        let mut spec = match (&self.image, &self.template) {
+            (Some(image), None) => VmSpec {
+                image: image.clone(),
+                ..Default::default()
+            },
+            (None, Some(template)) => templates.template_to_vm_spec(template, None)?,
+            _ => {
+                return Err(VortexError::InvalidInput {
+                    field: "pool".to_string(),
+                    message: "A pool target needs either an image or a template".to_string(),
+                })
+            }
+        };
+        spec.memory = self.memory.unwrap_or(spec.memory);
+        spec.cpus = self.cpus.unwrap_or(spec.cpus);
+        spec.backend = self.backend.clone().or(spec.backend);
+        spec.validate()?;
+        Ok(boot_spec(&spec))
+    }
+}
+
+/// `spec` without what is applied after the guest boots
+pub fn boot_spec(spec: &VmSpec) -> VmSpec {
+    VmSpec {
+        environment: HashMap::new(),
+        command: None,
+        labels: HashMap::new(),
+        resource_limits: ResourceLimits {
+            timeout_seconds: None,
+            ..spec.resource_limits.clone()
+        },
+        tuning: None,
+        hooks: LifecycleHooks::default(),
+        health_check: None,
+        ttl_seconds: None,
+        provision: None,
+        ..spec.clone()
+    }
+}
+
+/// Compared instead of specs, which are not `PartialEq`
+fn boot_key(spec: &VmSpec) -> Value {
+    serde_json::to_value(boot_spec(spec)).unwrap_or(Value::Null)
+}
+
+#[derive(Debug)]
+struct Slot {
+    spec: VmSpec,
+    key: Value,
+    size: usize,
+    ready: Vec<VmInstance>,
+}
+
+/// Booted VMs waiting to be handed out, by target
+#[derive(Debug, Default)]
+pub(crate) struct VmPool {
+    slots: Mutex<Vec<Slot>>,
+    /// Wakes the refill loop once a VM was taken
+    pub(crate) taken: Arc<Notify>,
+}
+
+impl VmPool {
+    /// Keep `size` VMs booted for each spec, dropping previous targets
+    pub(crate) async fn set_targets(&self, targets: Vec<(VmSpec, usize)>) -> Vec<VmInstance> {
+        let mut slots = self.slots.lock().await;
+        let retired = slots.drain(..).flat_map(|slot| slot.ready).collect();
+        *slots = targets
+            .into_iter()
+            .map(|(spec, size)| Slot {
+                key: boot_key(&spec),
+                spec: boot_spec(&spec),
+                size,
+                ready: Vec::new(),
+            })
+            .collect();
+        retired
+    }
+
+    /// A booted VM that `spec` can start its command in, if there is one
+    pub(crate) async fn take(&self, spec: &VmSpec) -> Option<VmInstance> {
+        let mut slots = self.slots.lock().await;
+        if slots.is_empty() {
+            return None;
+        }
+        let key = boot_key(spec);
+        let vm = slots
+            .iter_mut()
+            .find(|slot| slot.key == key)
+            .and_then(|slot| slot.ready.pop());
+        if vm.is_some() {
+            self.taken.notify_one();
+        }
+        vm
+    }
+
+    /// Number of VMs booted and waiting
+    pub(crate) async fn ready(&self) -> usize {
+        let slots = self.slots.lock().await;
+        slots.iter().map(|slot| slot.ready.len()).sum()
+    }
+
+    /// Boot VMs until every target has its size. Targets whose backend
+    /// cannot be pooled are dropped; VMs that went away are forgotten.
+    pub(crate) async fn fill(&self, backends: &BackendProvider) {
+        let specs: Vec<(Value, VmSpec)> = {
+            let slots = self.slots.lock().await;
+            slots
+                .iter()
+                .map(|slot| (slot.key.clone(), slot.spec.clone()))
+                .collect()
+        };
+
+        for (key, spec) in specs {
+            let backend = match backends.get_backend(spec.backend.as_deref()).await {
+                Ok(backend) => backend,
+                Err(e) => {
+                    tracing::warn!("Not prewarming {}: {}", spec.image, e);
+                    continue;
+                }
+            };
+            if !backend.supports_prewarm() {
+                tracing::warn!(
+                    "The {} backend cannot prewarm VMs; dropping the pool for {}",
+                    backend.name(),
+                    spec.image
+                );
+                self.slots.lock().await.retain(|slot| slot.key != key);
+                continue;
+            }
+            if let Ok(alive) = backend.list_vms().await {
+                let mut slots = self.slots.lock().await;
+                if let Some(slot) = slots.iter_mut().find(|slot| slot.key == key) {
+                    slot.ready.retain(|vm| alive.iter().any(|id| vm.id == *id));
+                }
+            }
+
+            loop {
+                let missing = {
+                    let slots = self.slots.lock().await;
+                    let Some(slot) = slots.iter().find(|slot| slot.key == key) else {
+                        break;
+                    };
+                    slot.size.saturating_sub(slot.ready.len())
+                };
+                if missing == 0 {
+                    break;
+                }
+
+                let now = chrono::Utc::now();
+                let mut vm = VmInstance {
+                    id: generate_vm_id(),
+                    spec: spec.clone(),
+                    state: VmState::Creating,
+                    backend: backend.clone(),
+                    created_at: now,
+                    updated_at: now,
+                };
+                let created = async {
+                    network::join(&vm.id, &vm.spec).await?;
+                    vm.backend.create(&vm).await
+                }
+                .await;
+                if let Err(e) = created {
+                    network::leave(&vm.id).await;
+                    tracing::warn!("Failed to prewarm a VM for {}: {}", spec.image, e);
+                    break;
+                }
+                vm.state = VmState::Running;
+                tracing::debug!("Prewarmed VM {} for {}", vm.id, spec.image);
+
+                let mut slots = self.slots.lock().await;
+                match slots.iter_mut().find(|slot| slot.key == key) {
+                    Some(slot) => slot.ready.push(vm),
+                    // The targets changed while the VM booted
+                    None => {
+                        drop(slots);
+                        if let Err(e) = vm.backend.cleanup(&vm).await {
+                            tracing::warn!("Failed to clean up prewarmed VM {}: {}", vm.id, e);
+                        }
+                        network::leave(&vm.id).await;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
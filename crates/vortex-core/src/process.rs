@@ -0,0 +1,58 @@
+//! Host process checks that work on Unix and Windows.
+//!
+//! Vortex avoids platform bindings for process control: Unix hosts use
+//! `kill`, Windows hosts `tasklist`.
+
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Exit code of a console process ended with Ctrl-C on Windows
+/// (`STATUS_CONTROL_C_EXIT`)
+pub const WINDOWS_CTRL_C_EXIT: i32 = 0xC000_013Au32 as i32;
+
+/// Whether a process with `pid` is running
+pub fn is_alive(pid: u32) -> bool {
+    #[cfg(windows)]
+    {
+        let filter = format!("PID eq {}", pid);
+        Command::new("tasklist")
+            .args(["/FI", &filter, "/NH", "/FO", "CSV"])
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| {
+                String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+            })
+    }
+    #[cfg(not(windows))]
+    {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+}
+
+/// Name of the host account with `uid`, as `id` resolves it (local
+/// accounts and directory services alike)
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> Option<String> {
+    let output = Command::new("id")
+        .args(["-nu", &uid.to_string()])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+/// Whether an interactive session ended the way users end one: a normal
+/// exit, Ctrl-C or a closed terminal
+pub fn ended_normally(status: &ExitStatus) -> bool {
+    match status.code() {
+        Some(0) | Some(130) | Some(129) => true,
+        Some(code) => code == WINDOWS_CTRL_C_EXIT,
+        // Killed by a signal, e.g. the terminal going away
+        None => true,
+    }
+}
@@ -0,0 +1,473 @@
+//! Plugins run as external programs, for teams not writing Rust.
+//!
+//! A program declared under `[process_plugins.<name>]` in the config is
+//! started at startup and speaks JSON-RPC 2.0 over its stdin and stdout, one
+//! message per line. Vortex sends these requests:
+//!
+//! - `initialize` with `name`, `protocol_version`, `core_version` and the
+//!   `settings` of the plugin's `[plugins.<name>]` section; the
+//!   result may give `version`, `description`, `author`, the `hooks` to be
+//!   called for (e.g. `["vm-pre-create", "vm-post-start"]`) and dev
+//!   `templates`
+//! - `mutate-spec` with the `spec` of a VM about to be created; the result
+//!   is the changed spec, or null to keep it. An error refuses the VM.
+//! - `on-event` with the `hook` name, the `vm` and, for snapshot hooks, the
+//!   `snapshot`
+//! - `shutdown`, after which stdin is closed
+//!
+//! The program may send `log` notifications with a `level` (`error`,
+//! `warn`, `info` or `debug`) and a `message`; what it writes to stderr is
+//! logged at debug level. Each request must be answered within the
+//! configured timeout, or the program is killed and started again for the
+//! next request. Like a plugin library, the program runs with all the
+//! rights of the user running Vortex.
+
+use crate::error::{Result, VortexError};
+use crate::native_plugin::CORE_VERSION;
+use crate::plugin::{Plugin, PluginHook, PluginMetadata};
+use crate::templates::DevTemplate;
+use crate::vm::{VmInstance, VmSpec};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+/// Version of the protocol above, sent in `initialize`
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A `[process_plugins.<name>]` section of the config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessPluginConfig {
+    /// Program to run
+    pub command: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Environment variables added to Vortex's own
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Seconds the program has to answer each request
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Result of `initialize`
+#[derive(Deserialize)]
+struct Handshake {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    hooks: Vec<String>,
+    #[serde(default)]
+    templates: Vec<DevTemplate>,
+}
+
+/// A response or notification from the program
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+/// The pipes of a running program, which is killed when this is dropped
+struct Connection {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+pub struct ProcessPlugin {
+    metadata: PluginMetadata,
+    config: ProcessPluginConfig,
+    templates: Vec<DevTemplate>,
+    settings: Value,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl std::fmt::Debug for ProcessPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessPlugin")
+            .field("metadata", &self.metadata)
+            .field("command", &self.config.command)
+            .finish()
+    }
+}
+
+impl ProcessPlugin {
+    /// A plugin for the program of `config`, which is started by
+    /// `initialize`
+    pub fn new(name: &str, config: ProcessPluginConfig) -> Self {
+        Self {
+            metadata: PluginMetadata {
+                name: name.to_string(),
+                version: String::new(),
+                description: String::new(),
+                author: String::new(),
+                hooks: Vec::new(),
+            },
+            config,
+            templates: Vec::new(),
+            settings: Value::Null,
+            connection: Mutex::new(None),
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> VortexError {
+        VortexError::PluginError {
+            message: format!("{}: {}", self.metadata.name, message),
+        }
+    }
+
+    /// Start the program and exchange `initialize` with it
+    async fn spawn(&self) -> Result<(Connection, Handshake)> {
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .envs(&self.config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                self.error(format!(
+                    "could not run {}: {}",
+                    self.config.command.display(),
+                    e
+                ))
+            })?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(self.error("no pipes to the program"));
+        };
+        if let Some(stderr) = child.stderr.take() {
+            let name = self.metadata.name.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("[plugin {}] {}", name, line);
+                }
+            });
+        }
+
+        let mut connection = Connection {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            next_id: 0,
+        };
+        let params = json!({
+            "name": self.metadata.name,
+            "protocol_version": PROTOCOL_VERSION,
+            "core_version": CORE_VERSION,
+            "settings": self.settings,
+        });
+        let result = self
+            .exchange(&mut connection, "initialize", params)
+            .await?
+            .map_err(|message| self.error(format!("initialize failed: {}", message)))?;
+        let handshake = serde_json::from_value(result)
+            .map_err(|e| self.error(format!("invalid initialize result: {}", e)))?;
+        Ok((connection, handshake))
+    }
+
+    /// Send a request and wait for its response within the timeout. The
+    /// outer error means the program can no longer be talked to; the inner
+    /// one is the error it answered with.
+    async fn exchange(
+        &self,
+        connection: &mut Connection,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<Value, String>> {
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        tokio::time::timeout(timeout, self.round_trip(connection, method, params))
+            .await
+            .map_err(|_| {
+                self.error(format!(
+                    "no answer to {} within {}s",
+                    method, self.config.timeout_secs
+                ))
+            })?
+    }
+
+    async fn round_trip(
+        &self,
+        connection: &mut Connection,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<Value, String>> {
+        connection.next_id += 1;
+        let id = connection.next_id;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let line = format!("{}\n", request);
+        connection
+            .stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| self.error(e))?;
+        connection.stdin.flush().await.map_err(|e| self.error(e))?;
+
+        loop {
+            let line = connection
+                .stdout
+                .next_line()
+                .await
+                .map_err(|e| self.error(e))?
+                .ok_or_else(|| self.error(format!("exited before answering {}", method)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: Message = serde_json::from_str(&line)
+                .map_err(|e| self.error(format!("invalid message: {}", e)))?;
+            if message.id == Some(id) {
+                return Ok(match message.error {
+                    Some(error) => Err(error.message),
+                    None => Ok(message.result),
+                });
+            }
+            match message.method.as_deref() {
+                Some("log") => self.log(&message.params),
+                _ => tracing::debug!(
+                    "Plugin {} sent an unexpected message: {}",
+                    self.metadata.name,
+                    line
+                ),
+            }
+        }
+    }
+
+    /// Call `method`, starting the program first if it is not running. A
+    /// program that did not answer properly is killed.
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let mut running = self.connection.lock().await;
+        let mut connection = match running.take() {
+            Some(connection) => connection,
+            None => self.spawn().await?.0,
+        };
+        let reply = self.exchange(&mut connection, method, params).await?;
+        *running = Some(connection);
+        reply.map_err(|message| self.error(format!("{} failed: {}", method, message)))
+    }
+
+    async fn on_event(&self, hook: PluginHook, mut params: Value) -> Result<()> {
+        if !self.metadata.hooks.contains(&hook) {
+            return Ok(());
+        }
+        params["hook"] = json!(hook.name());
+        self.call("on-event", params).await.map(|_| ())
+    }
+
+    fn log(&self, params: &Value) {
+        let plugin = &self.metadata.name;
+        let message = params["message"].as_str().unwrap_or_default();
+        match params["level"].as_str() {
+            Some("error") => tracing::error!("[plugin {}] {}", plugin, message),
+            Some("warn") => tracing::warn!("[plugin {}] {}", plugin, message),
+            Some("debug") => tracing::debug!("[plugin {}] {}", plugin, message),
+            _ => tracing::info!("[plugin {}] {}", plugin, message),
+        }
+    }
+}
+
+/// What a program is told about a VM
+fn describe(vm: &VmInstance) -> Value {
+    json!({
+        "id": vm.id,
+        "state": vm.state,
+        "backend": vm.backend.name(),
+        "spec": vm.spec,
+        "created_at": vm.created_at,
+    })
+}
+
+#[async_trait]
+impl Plugin for ProcessPlugin {
+    fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    async fn initialize(&mut self, settings: &Value) -> Result<()> {
+        self.settings = settings.clone();
+        let (connection, handshake) = self.spawn().await?;
+        for name in &handshake.hooks {
+            match PluginHook::ALL.into_iter().find(|hook| hook.name() == name) {
+                Some(hook) => self.metadata.hooks.push(hook),
+                None => tracing::warn!(
+                    "Plugin {} asked for unknown hook '{}'",
+                    self.metadata.name,
+                    name
+                ),
+            }
+        }
+        self.metadata.version = handshake.version.unwrap_or_default();
+        self.metadata.description = handshake.description.unwrap_or_default();
+        self.metadata.author = handshake.author.unwrap_or_default();
+        self.templates = handshake.templates;
+        *self.connection.get_mut() = Some(connection);
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        let Some(mut connection) = self.connection.get_mut().take() else {
+            return Ok(());
+        };
+        let reply = self
+            .exchange(&mut connection, "shutdown", Value::Null)
+            .await;
+        let Connection {
+            mut child, stdin, ..
+        } = connection;
+        drop(stdin);
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        if tokio::time::timeout(timeout, child.wait()).await.is_err() {
+            child.kill().await.map_err(|e| self.error(e))?;
+        }
+        reply?.map(|_| ()).map_err(|message| self.error(message))
+    }
+
+    fn templates(&self) -> Vec<DevTemplate> {
+        self.templates.clone()
+    }
+
+    async fn on_spec_mutate(&self, spec: &mut VmSpec) -> Result<()> {
+        if !self.metadata.hooks.contains(&PluginHook::VmPreCreate) {
+            return Ok(());
+        }
+        let changed = self.call("mutate-spec", json!({ "spec": spec })).await?;
+        if !changed.is_null() {
+            *spec = serde_json::from_value(changed)
+                .map_err(|e| self.error(format!("invalid spec: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    async fn on_vm_create(&self, vm: &VmInstance) -> Result<()> {
+        self.on_event(PluginHook::VmPostCreate, json!({ "vm": describe(vm) }))
+            .await
+    }
+
+    async fn on_vm_pre_start(&self, vm: &VmInstance) -> Result<()> {
+        self.on_event(PluginHook::VmPreStart, json!({ "vm": describe(vm) }))
+            .await
+    }
+
+    async fn on_vm_start(&self, vm: &VmInstance) -> Result<()> {
+        self.on_event(PluginHook::VmPostStart, json!({ "vm": describe(vm) }))
+            .await
+    }
+
+    async fn on_vm_pre_stop(&self, vm: &VmInstance) -> Result<()> {
+        self.on_event(PluginHook::VmPreStop, json!({ "vm": describe(vm) }))
+            .await
+    }
+
+    async fn on_vm_stop(&self, vm: &VmInstance) -> Result<()> {
+        self.on_event(PluginHook::VmPostStop, json!({ "vm": describe(vm) }))
+            .await
+    }
+
+    async fn on_snapshot_create(&self, vm: &VmInstance, snapshot_name: &str) -> Result<()> {
+        let params = json!({ "vm": describe(vm), "snapshot": snapshot_name });
+        self.on_event(PluginHook::SnapshotCreate, params).await
+    }
+
+    async fn on_snapshot_restore(&self, snapshot_id: &str) -> Result<()> {
+        let params = json!({ "snapshot": snapshot_id });
+        self.on_event(PluginHook::SnapshotRestore, params).await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::plugin::PluginManager;
+
+    /// Doubles the memory of new VMs, and hangs on events
+    const SCRIPT: &str = r#"
+while read -r line; do
+  id=$(echo "$line" | sed 's/^{[^{]*"id":\([0-9]*\).*/\1/')
+  case "$line" in
+    *'"method":"initialize"'*)
+      echo '{"jsonrpc":"2.0","method":"log","params":{"level":"info","message":"started"}}'
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"version\":\"0.1.0\",\"hooks\":[\"vm-pre-create\",\"snapshot-restore\"]}}" ;;
+    *'"method":"mutate-spec"'*)
+      spec=$(echo "$line" | sed 's/.*"params":{"spec":\(.*\)}}$/\1/; s/"memory":512/"memory":1024/')
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":$spec}" ;;
+    *'"method":"on-event"'*)
+      sleep 5 ;;
+    *)
+      echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":null}" ;;
+  esac
+done
+"#;
+
+    #[tokio::test]
+    async fn test_programs_answer_hooks_within_the_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("plugin.sh");
+        std::fs::write(&script, SCRIPT).unwrap();
+        let config = ProcessPluginConfig {
+            command: PathBuf::from("sh"),
+            args: vec![script.display().to_string()],
+            env: HashMap::new(),
+            timeout_secs: 1,
+            enabled: true,
+        };
+        let mut manager = PluginManager::new().await.unwrap();
+        let configs = HashMap::from([("doubler".to_string(), config)]);
+        assert_eq!(manager.load_processes(&configs).await, 1);
+        assert_eq!(manager.list_plugins()[0].version, "0.1.0");
+
+        let mut spec = VmSpec {
+            memory: 512,
+            ..VmSpec::default()
+        };
+        manager.mutate_spec(&mut spec).await.unwrap();
+        assert_eq!(spec.memory, 1024);
+
+        // A hung program is killed, and started again for the next request
+        let plugin = ProcessPlugin::new("doubler", configs["doubler"].clone());
+        let mut plugin: Box<dyn Plugin> = Box::new(plugin);
+        plugin.initialize(&Value::Null).await.unwrap();
+        let err = plugin.on_snapshot_restore("snap-1").await.unwrap_err();
+        assert!(err.to_string().contains("no answer to on-event"));
+        spec.memory = 512;
+        plugin.on_spec_mutate(&mut spec).await.unwrap();
+        assert_eq!(spec.memory, 1024);
+
+        plugin.shutdown().await.unwrap();
+        manager.shutdown_all().await.unwrap();
+    }
+}
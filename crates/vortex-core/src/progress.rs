@@ -0,0 +1,146 @@
+//! Machine-readable progress for tools embedding the Vortex CLI.
+//!
+//! With `--progress-json`, long-running commands write one JSON object per
+//! line to stderr while normal output stays on stdout. The format is described
+//! in `docs/PROGRESS_PROTOCOL.md`. Reporting is process-global so deeply nested
+//! helpers can emit phases without threading a reporter through every call.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Current progress protocol version
+pub const PROGRESS_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    Started {
+        command: String,
+    },
+    Phase {
+        phase: String,
+        message: String,
+    },
+    Progress {
+        phase: String,
+        current: u64,
+        total: Option<u64>,
+    },
+    Completed {
+        command: String,
+        duration_ms: u64,
+    },
+    Failed {
+        command: String,
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressLine {
+    pub schema_version: u32,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: ProgressEvent,
+}
+
+struct Reporter {
+    command: String,
+    started: Instant,
+}
+
+static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+/// Enable JSON progress for `command` and emit its `started` event
+pub fn init(command: &str) {
+    let reporter = Reporter {
+        command: command.to_string(),
+        started: Instant::now(),
+    };
+    if REPORTER.set(reporter).is_ok() {
+        emit(ProgressEvent::Started {
+            command: command.to_string(),
+        });
+    }
+}
+
+pub fn is_enabled() -> bool {
+    REPORTER.get().is_some()
+}
+
+fn emit(event: ProgressEvent) {
+    if !is_enabled() {
+        return;
+    }
+    let line = ProgressLine {
+        schema_version: PROGRESS_SCHEMA_VERSION,
+        timestamp: Utc::now(),
+        event,
+    };
+    if let Ok(json) = serde_json::to_string(&line) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = writeln!(stderr, "{}", json);
+    }
+}
+
+/// Announce that the command entered a new phase
+pub fn phase(phase: &str, message: impl Into<String>) {
+    emit(ProgressEvent::Phase {
+        phase: phase.to_string(),
+        message: message.into(),
+    });
+}
+
+/// Report incremental progress within a phase
+pub fn progress(phase: &str, current: u64, total: Option<u64>) {
+    emit(ProgressEvent::Progress {
+        phase: phase.to_string(),
+        current,
+        total,
+    });
+}
+
+/// Emit the terminal `completed` or `failed` event
+pub fn finish<T, E: std::fmt::Display>(result: &Result<T, E>) {
+    let Some(reporter) = REPORTER.get() else {
+        return;
+    };
+    let command = reporter.command.clone();
+    match result {
+        Ok(_) => emit(ProgressEvent::Completed {
+            command,
+            duration_ms: reporter.started.elapsed().as_millis() as u64,
+        }),
+        Err(e) => emit(ProgressEvent::Failed {
+            command,
+            error: e.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line_is_flat() {
+        let line = ProgressLine {
+            schema_version: PROGRESS_SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            event: ProgressEvent::Phase {
+                phase: "creating_vm".to_string(),
+                message: "Creating VM".to_string(),
+            },
+        };
+        let value = serde_json::to_value(&line).unwrap();
+        assert_eq!(value["schema_version"], 1);
+        assert_eq!(value["event"], "phase");
+        assert_eq!(value["phase"], "creating_vm");
+
+        let parsed: ProgressLine = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.event, line.event);
+    }
+}
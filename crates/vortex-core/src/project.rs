@@ -0,0 +1,444 @@
+//! The services of a project, described in the `vortex.yaml` that
+//! `vortex workspace init` writes.
+//!
+//! `vortex run` and `vortex dev` in a directory holding the file take the
+//! image, ports, environment and volumes of one of its services, and the
+//! project's backend:
+//!
+//! ```yaml
+//! name: shop
+//! backend: krunvm
+//! services:
+//!   api:
+//!     type: backend
+//!     language: python
+//!     image: python:3.11-slim
+//!     ports:
+//!       - 8000:8000
+//!     env:
+//!       DATABASE_URL: postgres://db/shop
+//!     volumes:
+//!       - ./api:/workspace
+//! ```
+//!
+//! The service is the one passed with `--service`, else the one named
+//! `default`, else the only one. What is given on the command line wins
+//! over the file.
+//!
+//! `contexts` change the services for where they run, picked with
+//! `--context`. A context's `memory`, `cpus` and `env` apply to every
+//! service; under `services`, what it gives for a service replaces the
+//! service's own settings, except that `env` is merged:
+//!
+//! ```yaml
+//! contexts:
+//!   prod:
+//!     memory: 4096
+//!     env:
+//!       LOG_LEVEL: warn
+//!     services:
+//!       api:
+//!         ports:
+//!           - 80:8000
+//! ```
+
+use crate::dev_project::parse_port;
+use crate::error::{Result, VortexError};
+use crate::interpolate;
+use crate::templates::DevOverrides;
+use crate::validation;
+use crate::vm::VmSpec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+pub const PROJECT_FILE: &str = "vortex.yaml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceConfig {
+    /// frontend, backend, worker, database, cache or queue
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub service_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// `host:guest` mappings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// `host:guest` mounts, with host paths relative to the project
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<String>,
+    /// Directory of the service's code, relative to the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Memory in MB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+}
+
+impl ServiceConfig {
+    /// Take what `other` sets over what this sets; environment variables
+    /// are merged
+    fn merge(&mut self, other: &ServiceConfig) {
+        fn replace<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                value.clone_from(other);
+            }
+        }
+        replace(&mut self.service_type, &other.service_type);
+        replace(&mut self.language, &other.language);
+        replace(&mut self.image, &other.image);
+        replace(&mut self.path, &other.path);
+        replace(&mut self.memory, &other.memory);
+        replace(&mut self.cpus, &other.cpus);
+        if !other.ports.is_empty() {
+            self.ports.clone_from(&other.ports);
+        }
+        if !other.volumes.is_empty() {
+            self.volumes.clone_from(&other.volumes);
+        }
+        self.env.extend(
+            other
+                .env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+    }
+}
+
+/// A `contexts.<name>` section of `vortex.yaml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Memory in MB of every service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+    /// Added to the environment of every service
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Settings replacing those of the services, by service name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub services: BTreeMap<String, ServiceConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    #[serde(default)]
+    pub services: BTreeMap<String, ServiceConfig>,
+    /// Changes to the services by where they run, e.g. `dev` or `prod`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub contexts: BTreeMap<String, ContextConfig>,
+    /// Settings of the Vortex config for work on the project, over those
+    /// of the system and user files
+    #[serde(default, skip_serializing_if = "serde_yaml::Value::is_null")]
+    pub config: serde_yaml::Value,
+    /// Directory the file was loaded from
+    #[serde(skip)]
+    pub dir: PathBuf,
+    /// The context applied to the services, if any
+    #[serde(skip)]
+    pub context: Option<String>,
+}
+
+impl ProjectConfig {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(PROJECT_FILE)
+    }
+
+    /// The project described in `dir`, if it has a `vortex.yaml`
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(dir);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (project, unknown): (Self, _) = validation::parse_yaml(&path, &content)?;
+        for diagnostic in unknown {
+            tracing::warn!("{}", diagnostic);
+        }
+        let mut project = interpolate::expand_values(project)?;
+        project.dir = dir.to_path_buf();
+        Ok(Some(project))
+    }
+
+    /// Like [`load`](Self::load), with the services as `context` has them
+    pub fn load_in_context(dir: &Path, context: Option<&str>) -> Result<Option<Self>> {
+        let Some(project) = Self::load(dir)? else {
+            return Ok(None);
+        };
+        match context {
+            Some(context) => project.in_context(context).map(Some),
+            None => Ok(Some(project)),
+        }
+    }
+
+    /// The project with the changes of the context `name` applied to its
+    /// services
+    pub fn in_context(mut self, name: &str) -> Result<Self> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "context".to_string(),
+            message,
+        };
+        let context = self.contexts.get(name).cloned().ok_or_else(|| {
+            let names: Vec<&str> = self.contexts.keys().map(String::as_str).collect();
+            invalid(match names.is_empty() {
+                true => format!("{} has no contexts", PROJECT_FILE),
+                false => format!(
+                    "{} has no context '{}' (it has {})",
+                    PROJECT_FILE,
+                    name,
+                    names.join(", ")
+                ),
+            })
+        })?;
+        if let Some(unknown) = context
+            .services
+            .keys()
+            .find(|service| !self.services.contains_key(*service))
+        {
+            return Err(invalid(format!(
+                "Context '{}' of {} changes the unknown service '{}'",
+                name, PROJECT_FILE, unknown
+            )));
+        }
+        let shared = ServiceConfig {
+            memory: context.memory,
+            cpus: context.cpus,
+            env: context.env,
+            ..ServiceConfig::default()
+        };
+        for (service_name, service) in &mut self.services {
+            // What the context sets for one service wins over what it sets
+            // for all of them
+            service.merge(&shared);
+            if let Some(changes) = context.services.get(service_name) {
+                service.merge(changes);
+            }
+        }
+        self.context = Some(name.to_string());
+        Ok(self)
+    }
+
+    /// The service `name`, or else the one named `default` or the only
+    /// one. `None` when the project has no services.
+    pub fn service(&self, name: Option<&str>) -> Result<Option<(&str, &ServiceConfig)>> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "service".to_string(),
+            message,
+        };
+        let names = || {
+            self.services
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let name = match name {
+            Some(name) => name,
+            None if self.services.contains_key("default") => "default",
+            None if self.services.len() <= 1 => match self.services.keys().next() {
+                Some(name) => name,
+                None => return Ok(None),
+            },
+            None => {
+                return Err(invalid(format!(
+                    "{} has the services {}; pick one with --service",
+                    PROJECT_FILE,
+                    names()
+                )))
+            }
+        };
+        match self.services.get_key_value(name) {
+            Some((name, service)) => Ok(Some((name, service))),
+            None => Err(invalid(format!(
+                "{} has no service '{}' (it has {})",
+                PROJECT_FILE,
+                name,
+                names()
+            ))),
+        }
+    }
+
+    /// Fill in what `spec` leaves open from `service`: the image when it
+    /// has none, the backend, and the ports, environment variables and
+    /// mounts it does not set itself. The service's memory and CPUs
+    /// replace those of the spec.
+    pub fn apply(&self, service: &ServiceConfig, spec: &mut VmSpec) -> Result<()> {
+        if spec.image.is_empty() {
+            if let Some(image) = &service.image {
+                spec.image = image.clone();
+            }
+        }
+        if spec.backend.is_none() {
+            spec.backend = self.backend.clone();
+        }
+        if let Some(memory) = service.memory {
+            spec.memory = memory;
+        }
+        if let Some(cpus) = service.cpus {
+            spec.cpus = cpus;
+        }
+        for (host, guest) in service_ports(service)? {
+            spec.ports.entry(host).or_insert(guest);
+        }
+        for (key, value) in &service.env {
+            spec.environment
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        for (host, guest) in self.mounts(service)? {
+            if !spec.volumes.values().any(|mounted| *mounted == guest) {
+                spec.volumes.insert(host, guest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the image, resources, ports, environment and mounts of
+    /// `service` to `overrides` for a dev template, where it has none of
+    /// its own
+    pub fn dev_overrides(
+        &self,
+        service: &ServiceConfig,
+        overrides: &mut DevOverrides,
+    ) -> Result<()> {
+        if overrides.image.is_none() {
+            overrides.image = service.image.clone();
+        }
+        if overrides.backend.is_none() {
+            overrides.backend = self.backend.clone();
+        }
+        if overrides.memory.is_none() {
+            overrides.memory = service.memory;
+        }
+        if overrides.cpus.is_none() {
+            overrides.cpus = service.cpus;
+        }
+        if overrides.ports.is_none() && !service.ports.is_empty() {
+            overrides.ports = Some(service_ports(service)?);
+        }
+        for (key, value) in &service.env {
+            overrides
+                .environment
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        for (host, guest) in self.mounts(service)? {
+            overrides.volumes.entry(host).or_insert(guest);
+        }
+        Ok(())
+    }
+
+    /// The mounts of `service`, with host paths resolved against the
+    /// project directory
+    fn mounts(&self, service: &ServiceConfig) -> Result<HashMap<PathBuf, PathBuf>> {
+        service
+            .volumes
+            .iter()
+            .map(|volume| {
+                let (host, guest) = parse_volume(volume)?;
+                Ok((self.dir.join(host), PathBuf::from(guest)))
+            })
+            .collect()
+    }
+}
+
+/// Split a `host:/guest` volume of a service
+pub fn parse_volume(volume: &str) -> Result<(&str, &str)> {
+    volume
+        .split_once(':')
+        .filter(|(host, guest)| !host.is_empty() && guest.starts_with('/'))
+        .ok_or_else(|| VortexError::InvalidInput {
+            field: "volumes".to_string(),
+            message: format!(
+                "Invalid volume '{}' in {}, expected host:/guest",
+                volume, PROJECT_FILE
+            ),
+        })
+}
+
+fn service_ports(service: &ServiceConfig) -> Result<HashMap<u16, u16>> {
+    service.ports.iter().map(|port| parse_port(port)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_services_fill_in_the_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(ProjectConfig::load(dir.path()).unwrap(), None);
+        std::fs::write(
+            ProjectConfig::path(dir.path()),
+            "name: shop\nbackend: krunvm\nservices:\n  api:\n    type: backend\n    image: python:3.11-slim\n    ports:\n      - 8000:8000\n    env:\n      MODE: dev\n    volumes:\n      - ./api:/workspace\n  web:\n    image: node:18-alpine\n",
+        )
+        .unwrap();
+        let project = ProjectConfig::load(dir.path()).unwrap().unwrap();
+        assert!(project.service(None).is_err());
+        assert!(project.service(Some("db")).is_err());
+        let (name, api) = project.service(Some("api")).unwrap().unwrap();
+        assert_eq!(name, "api");
+
+        let mut spec = VmSpec {
+            image: String::new(),
+            ..VmSpec::default()
+        };
+        spec.ports.insert(8000, 80);
+        project.apply(api, &mut spec).unwrap();
+        assert_eq!(spec.image, "python:3.11-slim");
+        assert_eq!(spec.backend.as_deref(), Some("krunvm"));
+        assert_eq!(spec.ports[&8000], 80);
+        assert_eq!(spec.environment["MODE"], "dev");
+        assert_eq!(
+            spec.volumes[&dir.path().join("./api")],
+            PathBuf::from("/workspace")
+        );
+
+        let mut overrides = DevOverrides::default();
+        project.dev_overrides(api, &mut overrides).unwrap();
+        assert_eq!(overrides.image.as_deref(), Some("python:3.11-slim"));
+        assert_eq!(overrides.ports, Some(HashMap::from([(8000, 8000)])));
+    }
+
+    #[test]
+    fn test_contexts_change_the_services() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            ProjectConfig::path(dir.path()),
+            "name: shop\nservices:\n  api:\n    image: python:3.11-slim\n    memory: 1024\n    ports:\n      - 8000:8000\n    env:\n      MODE: dev\ncontexts:\n  prod:\n    memory: 2048\n    env:\n      MODE: prod\n      LOG_LEVEL: warn\n    services:\n      api:\n        memory: 4096\n        ports:\n          - 80:8000\n  broken:\n    services:\n      web: {}\n",
+        )
+        .unwrap();
+        let project = ProjectConfig::load_in_context(dir.path(), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(project.services["api"].memory, Some(1024));
+        assert!(project.clone().in_context("staging").is_err());
+        assert!(project.clone().in_context("broken").is_err());
+
+        let project = project.in_context("prod").unwrap();
+        assert_eq!(project.context.as_deref(), Some("prod"));
+        let api = &project.services["api"];
+        assert_eq!(api.memory, Some(4096));
+        assert_eq!(api.ports, vec!["80:8000".to_string()]);
+        assert_eq!(api.env["MODE"], "prod");
+        assert_eq!(api.env["LOG_LEVEL"], "warn");
+
+        let mut spec = VmSpec::default();
+        project.apply(api, &mut spec).unwrap();
+        assert_eq!(spec.memory, 4096);
+        assert_eq!(spec.ports[&80], 8000);
+    }
+}
@@ -0,0 +1,245 @@
+//! First-boot provisioning of guests.
+//!
+//! A `provision` section declares users, files and commands in the style of
+//! cloud-config (`users`, `write_files`, `runcmd`), so a YAML file written
+//! for cloud-init mostly works as is. Guest images do not need cloud-init:
+//! the section is rendered as a shell prelude that runs before the VM's
+//! command and leaves a marker behind, so it runs once per VM disk.
+
+use crate::backend::sh_quote;
+use crate::error::{Result, VortexError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Written once provisioning succeeded; clones of the disk skip it too
+const MARKER: &str = "/var/lib/vortex/provisioned";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provision {
+    #[serde(default)]
+    pub users: Vec<ProvisionUser>,
+    #[serde(default)]
+    pub write_files: Vec<ProvisionFile>,
+    /// Commands run with `sh -c`, in order, after users and files
+    #[serde(default)]
+    pub runcmd: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisionUser {
+    pub name: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub ssh_authorized_keys: Vec<String>,
+    /// Passwordless sudo through `/etc/sudoers.d`
+    #[serde(default)]
+    pub sudo: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisionFile {
+    pub path: String,
+    #[serde(default)]
+    pub content: String,
+    /// Octal mode, e.g. `0644`
+    #[serde(default)]
+    pub permissions: Option<String>,
+    /// `user` or `user:group`, which may be created under `users`
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+impl Provision {
+    /// Load a provision section from a YAML (or JSON) file, or TOML if the
+    /// file ends in `.toml`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let provision = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str(&content).map_err(|e| e.to_string())
+        };
+        provision.map_err(|e| VortexError::InvalidInput {
+            field: "provision".to_string(),
+            message: format!("Invalid provisioning file {}: {}", path.display(), e),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty() && self.write_files.is_empty() && self.runcmd.is_empty()
+    }
+
+    /// Reject names the generated script cannot use safely
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "provision".to_string(),
+            message,
+        };
+
+        for user in &self.users {
+            if !is_name(&user.name) {
+                return Err(invalid(format!("Invalid user name '{}'", user.name)));
+            }
+            if let Some(group) = user.groups.iter().find(|group| !is_name(group)) {
+                return Err(invalid(format!("Invalid group name '{}'", group)));
+            }
+        }
+
+        for file in &self.write_files {
+            if !file.path.starts_with('/') || file.path.ends_with('/') {
+                return Err(invalid(format!(
+                    "File path '{}' must be an absolute file path",
+                    file.path
+                )));
+            }
+            if let Some(mode) = &file.permissions {
+                let octal = (3..=4).contains(&mode.len()) && mode.chars().all(|c| c.is_digit(8));
+                if !octal {
+                    return Err(invalid(format!(
+                        "Invalid permissions '{}' for {}",
+                        mode, file.path
+                    )));
+                }
+            }
+            if let Some(owner) = &file.owner {
+                if !owner.split(':').all(is_name) || owner.matches(':').count() > 1 {
+                    return Err(invalid(format!(
+                        "Invalid owner '{}' for {}",
+                        owner, file.path
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render the section as a guest shell prelude. Steps stop at the first
+    /// failure, which is reported on stderr and retried on the next boot;
+    /// the VM's command runs either way.
+    pub fn boot_script(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+
+        let mut steps = Vec::new();
+        for user in &self.users {
+            add_user_steps(&mut steps, user);
+        }
+        for file in &self.write_files {
+            let path = Path::new(&file.path);
+            if let Some(parent) = path.parent() {
+                steps.push(format!(
+                    "mkdir -p {}",
+                    sh_quote(&parent.display().to_string())
+                ));
+            }
+            steps.push(format!(
+                "printf '%s' {} > {}",
+                sh_quote(&file.content),
+                sh_quote(&file.path)
+            ));
+            if let Some(mode) = &file.permissions {
+                steps.push(format!("chmod {} {}", mode, sh_quote(&file.path)));
+            }
+            if let Some(owner) = &file.owner {
+                steps.push(format!("chown {} {}", owner, sh_quote(&file.path)));
+            }
+        }
+        for command in &self.runcmd {
+            steps.push(format!("sh -c {}", sh_quote(command)));
+        }
+        steps.push("mkdir -p /var/lib/vortex".to_string());
+        steps.push(format!("touch {}", MARKER));
+
+        format!(
+            "if [ ! -e {} ]; then {{ {}; }} || echo 'vortex: provisioning failed' >&2; fi; ",
+            MARKER,
+            steps.join(" && ")
+        )
+    }
+}
+
+fn add_user_steps(steps: &mut Vec<String>, user: &ProvisionUser) {
+    let name = &user.name;
+    let shell = sh_quote(user.shell.as_deref().unwrap_or("/bin/sh"));
+    // useradd on most distributions, adduser on BusyBox ones like Alpine
+    steps.push(format!(
+        "{{ id -u {0} >/dev/null 2>&1 || useradd -m -s {1} {0} 2>/dev/null || adduser -D -s {1} {0}; }}",
+        name, shell
+    ));
+    for group in &user.groups {
+        steps.push(format!(
+            "{{ groupadd -f {1} 2>/dev/null || addgroup {1} 2>/dev/null || true; }} && \
+             {{ usermod -aG {1} {0} 2>/dev/null || addgroup {0} {1}; }}",
+            name, group
+        ));
+    }
+
+    let home = if name == "root" {
+        "/root".to_string()
+    } else {
+        format!("/home/{}", name)
+    };
+    if !user.ssh_authorized_keys.is_empty() {
+        let keys = format!("{}\n", user.ssh_authorized_keys.join("\n"));
+        steps.push(format!("mkdir -p {}/.ssh", home));
+        steps.push(format!(
+            "printf '%s' {} > {}/.ssh/authorized_keys",
+            sh_quote(&keys),
+            home
+        ));
+        steps.push(format!(
+            "chmod 700 {0}/.ssh && chmod 600 {0}/.ssh/authorized_keys",
+            home
+        ));
+        steps.push(format!("chown -R {} {}/.ssh", name, home));
+    }
+    if user.sudo {
+        steps.push("mkdir -p /etc/sudoers.d".to_string());
+        steps.push(format!(
+            "echo '{0} ALL=(ALL) NOPASSWD:ALL' > /etc/sudoers.d/{0}",
+            name
+        ));
+    }
+}
+
+/// A user or group name that needs no quoting
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-'))
+        && name.len() <= 32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cloud_config_renders_guarded_script() {
+        let provision: Provision = serde_yaml::from_str(
+            "#cloud-config\n\
+             users:\n  - name: dev\n    groups: [docker]\n    sudo: true\n\
+             write_files:\n  - path: /etc/motd\n    content: \"it's ready\\n\"\n    permissions: '0644'\n    owner: dev\n\
+             runcmd:\n  - echo done > /tmp/done\n",
+        )
+        .unwrap();
+        provision.validate().unwrap();
+
+        let script = provision.boot_script();
+        assert!(script.starts_with("if [ ! -e /var/lib/vortex/provisioned ]; then"));
+        assert!(script.contains("printf '%s' 'it'\\''s ready\n' > '/etc/motd'"));
+        assert!(script.contains("chown dev '/etc/motd'"));
+        assert!(script.contains("sh -c 'echo done > /tmp/done'"));
+        assert!(script.contains("/etc/sudoers.d/dev"));
+
+        let mut bad = provision.clone();
+        bad.users[0].name = "dev; reboot".to_string();
+        assert!(bad.validate().is_err());
+        assert!(Provision::default().boot_script().is_empty());
+    }
+}
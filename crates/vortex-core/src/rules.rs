@@ -0,0 +1,429 @@
+//! Event-triggered automations run by the daemon.
+//!
+//! Rules are `[[rules]]` tables in `~/.config/vortex/config.toml`. Each names
+//! an event type from the event log (`vm_error`, `vm_stopped`, ... see
+//! `docs/EVENT_SCHEMA.md`), the labels a VM must carry, and an action:
+//!
+//! ```toml
+//! [[rules]]
+//! name = "notify-api-errors"
+//! event = "vm_error"
+//! labels = { project = "api" }
+//! action = "run"
+//! command = "./notify.sh"
+//!
+//! [[rules]]
+//! name = "save-workspace"
+//! event = "vm_stopped"
+//! labels = { "vortex.workspace" = "ws-1a2b3c4d" }
+//! action = "snapshot_workspace"
+//! min_interval_secs = 600
+//! ```
+//!
+//! `run` commands go to `sh -c` on the host, in the config directory, with
+//! `VORTEX_RULE`, `VORTEX_EVENT`, `VORTEX_VM_ID` and the event envelope as
+//! `VORTEX_EVENT_JSON` in their environment. `snapshot` snapshots the VM,
+//! which must still be running; `snapshot_workspace` exports the VM's
+//! workspace to `~/.vortex/workspace-snapshots/`.
+//!
+//! A rule fires at most once per `min_interval_secs` for the same VM; matches
+//! in between are skipped. The daemon remembers a VM's labels from the first
+//! event it sees, so rules still match after a stopped VM is cleaned up.
+//! Rules see the VMs the daemon manages, not those of one-off CLI runs.
+
+use crate::config::get_config_path;
+use crate::error::{Result, VortexError};
+use crate::events::{EventEnvelope, EventPayload};
+use crate::ids::{VmId, LABEL_WORKSPACE_ID};
+use crate::vm::{VmEvent, VmEventHandler, VmManager};
+use crate::workspace::WorkspaceManager;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// Event types of `VmEvent`, the ones rules can trigger on
+pub const RULE_EVENTS: &[&str] = &[
+    "vm_created",
+    "vm_started",
+    "vm_stopped",
+    "vm_expired",
+    "vm_error",
+    "snapshot_created",
+    "resource_usage",
+    "disk_quota_exceeded",
+    "vm_paused",
+    "vm_resumed",
+    "vm_resized",
+    "vm_health_changed",
+    "vm_cleaned_up",
+    "metric_alert",
+];
+
+fn default_min_interval_secs() -> u64 {
+    60
+}
+
+/// What a rule does when it fires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Run a shell command on the host
+    Run { command: String },
+    /// Snapshot the VM
+    Snapshot,
+    /// Export the workspace the VM belongs to
+    SnapshotWorkspace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    /// Event type that triggers the rule
+    pub event: String,
+    /// Labels the VM must have, with these values
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    #[serde(flatten)]
+    pub action: RuleAction,
+    /// Least time between two firings for the same VM
+    #[serde(default = "default_min_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+impl Rule {
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "rules".to_string(),
+            message,
+        };
+        if self.name.trim().is_empty() {
+            return Err(invalid("Rule without a name".to_string()));
+        }
+        if !RULE_EVENTS.contains(&self.event.as_str()) {
+            return Err(invalid(format!(
+                "Rule '{}' triggers on unknown event '{}' (expected one of {})",
+                self.name,
+                self.event,
+                RULE_EVENTS.join(", ")
+            )));
+        }
+        if let RuleAction::Run { command } = &self.action {
+            if command.trim().is_empty() {
+                return Err(invalid(format!(
+                    "Rule '{}' has an empty command",
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, event_type: &str, labels: &HashMap<String, String>) -> bool {
+        self.event == event_type
+            && self
+                .labels
+                .iter()
+                .all(|(key, value)| labels.get(key) == Some(value))
+    }
+}
+
+/// Last firing of each rule per VM
+#[derive(Debug, Default)]
+struct RateLimiter {
+    fired: HashMap<(String, String), Instant>,
+}
+
+impl RateLimiter {
+    /// Whether `rule` may fire for `vm_id` at `now`, recording it if so
+    fn allow(&mut self, rule: &Rule, vm_id: &str, now: Instant) -> bool {
+        let key = (rule.name.clone(), vm_id.to_string());
+        let interval = Duration::from_secs(rule.min_interval_secs);
+        match self.fired.get(&key) {
+            Some(last) if now.duration_since(*last) < interval => false,
+            _ => {
+                self.fired.insert(key, now);
+                true
+            }
+        }
+    }
+
+    /// Drop firings too old to hold back any rule
+    fn prune(&mut self, now: Instant, longest: Duration) {
+        self.fired
+            .retain(|_, last| now.duration_since(*last) < longest);
+    }
+}
+
+/// Event handler running the configured rules
+pub struct RulesEngine {
+    rules: Vec<Rule>,
+    vm_manager: Weak<VmManager>,
+    labels: Mutex<HashMap<String, HashMap<String, String>>>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl RulesEngine {
+    /// Engine for the valid rules among `rules`; invalid ones are logged
+    /// and skipped
+    pub fn new(rules: Vec<Rule>, vm_manager: Weak<VmManager>) -> Self {
+        let rules = rules
+            .into_iter()
+            .filter(|rule| match rule.validate() {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("Skipping rule: {}", e);
+                    false
+                }
+            })
+            .collect();
+        Self {
+            rules,
+            vm_manager,
+            labels: Mutex::new(HashMap::new()),
+            limiter: Mutex::new(RateLimiter::default()),
+        }
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Labels of `vm_id`, from the VM manager or as last seen
+    async fn labels_for(&self, vm_id: &VmId) -> HashMap<String, String> {
+        let current = match self.vm_manager.upgrade() {
+            Some(vm_manager) => vm_manager.get(vm_id).await.ok().flatten(),
+            None => None,
+        };
+        let mut known = self
+            .labels
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match current {
+            Some(vm) => {
+                known.insert(vm_id.to_string(), vm.spec.labels.clone());
+                vm.spec.labels
+            }
+            None => known.get(vm_id.as_str()).cloned().unwrap_or_default(),
+        }
+    }
+
+    fn fire(
+        &self,
+        rule: &Rule,
+        vm_id: &VmId,
+        labels: &HashMap<String, String>,
+        payload: &EventPayload,
+    ) {
+        tracing::info!("Rule '{}' fired for {} on {}", rule.name, vm_id, rule.event);
+        let name = rule.name.clone();
+        let vm_id = vm_id.clone();
+
+        match &rule.action {
+            RuleAction::Run { command } => {
+                let envelope = match serde_json::to_string(&EventEnvelope::new(payload.clone())) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        tracing::warn!("Rule '{}': cannot encode event: {}", name, e);
+                        return;
+                    }
+                };
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c")
+                    .arg(command)
+                    .env("VORTEX_RULE", &name)
+                    .env("VORTEX_EVENT", &rule.event)
+                    .env("VORTEX_VM_ID", &vm_id)
+                    .env("VORTEX_EVENT_JSON", envelope)
+                    .stdin(std::process::Stdio::null());
+                if let Some(dir) = get_config_path()
+                    .ok()
+                    .and_then(|p| p.parent().map(PathBuf::from))
+                {
+                    cmd.current_dir(dir);
+                }
+                tokio::spawn(async move {
+                    match cmd.output().await {
+                        Ok(output) if output.status.success() => {}
+                        Ok(output) => tracing::warn!(
+                            "Rule '{}' command exited with {}: {}",
+                            name,
+                            output.status,
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        ),
+                        Err(e) => tracing::warn!("Rule '{}' command failed to start: {}", name, e),
+                    }
+                });
+            }
+            RuleAction::Snapshot => {
+                let Some(vm_manager) = self.vm_manager.upgrade() else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    match vm_manager.snapshot(&vm_id).await {
+                        Ok(snapshot_id) => {
+                            tracing::info!("Rule '{}' saved {} as {}", name, vm_id, snapshot_id)
+                        }
+                        Err(e) => {
+                            tracing::warn!("Rule '{}' could not snapshot {}: {}", name, vm_id, e)
+                        }
+                    }
+                });
+            }
+            RuleAction::SnapshotWorkspace => {
+                let Some(workspace_id) = labels.get(LABEL_WORKSPACE_ID).cloned() else {
+                    tracing::warn!("Rule '{}': VM {} has no workspace", name, vm_id);
+                    return;
+                };
+                tokio::task::spawn_blocking(move || match export_workspace(&workspace_id) {
+                    Ok(path) => tracing::info!(
+                        "Rule '{}' exported workspace {} to {}",
+                        name,
+                        workspace_id,
+                        path.display()
+                    ),
+                    Err(e) => tracing::warn!(
+                        "Rule '{}' could not export workspace {}: {}",
+                        name,
+                        workspace_id,
+                        e
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Export `workspace_id` to a timestamped archive under
+/// `~/.vortex/workspace-snapshots/`
+fn export_workspace(workspace_id: &str) -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    let dir = home.join(".vortex").join("workspace-snapshots");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{}-{}.vortex",
+        workspace_id,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    ));
+    WorkspaceManager::new()?.export_workspace(workspace_id, &path)?;
+    Ok(path)
+}
+
+#[async_trait]
+impl VmEventHandler for RulesEngine {
+    async fn handle(&self, event: VmEvent) -> Result<()> {
+        let vm_id = event.vm_id().clone();
+        let payload = EventPayload::from(event.clone());
+        let event_type = payload.event_type();
+        let labels = self.labels_for(&vm_id).await;
+
+        let now = Instant::now();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(event_type, &labels))
+        {
+            let allowed = self
+                .limiter
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .allow(rule, &vm_id, now);
+            if allowed {
+                self.fire(rule, &vm_id, &labels, &payload);
+            } else {
+                tracing::debug!(
+                    "Rule '{}' for {} held back by its interval",
+                    rule.name,
+                    vm_id
+                );
+            }
+        }
+
+        if let VmEvent::Stopped { .. } = event {
+            self.labels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(vm_id.as_str());
+            let longest = self
+                .rules
+                .iter()
+                .map(|rule| Duration::from_secs(rule.min_interval_secs))
+                .max()
+                .unwrap_or_default();
+            self.limiter
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .prune(now, longest);
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "rules"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_parse_match_and_rate_limit() {
+        let config: crate::config::VortexConfig = toml::from_str(
+            r#"
+            default_backend = "qemu"
+
+            [[rules]]
+            name = "notify"
+            event = "vm_error"
+            labels = { project = "api" }
+            action = "run"
+            command = "./notify.sh"
+
+            [[rules]]
+            name = "save"
+            event = "vm_stopped"
+            action = "snapshot_workspace"
+            min_interval_secs = 0
+            "#,
+        )
+        .unwrap();
+        let [notify, save] = &config.rules[..] else {
+            panic!("expected two rules");
+        };
+        assert_eq!(
+            notify.action,
+            RuleAction::Run {
+                command: "./notify.sh".to_string()
+            }
+        );
+        assert_eq!(notify.min_interval_secs, 60);
+        assert!(notify.validate().is_ok());
+
+        let api: HashMap<_, _> = [("project".to_string(), "api".to_string())].into();
+        assert!(notify.matches("vm_error", &api));
+        assert!(!notify.matches("vm_error", &HashMap::new()));
+        assert!(!notify.matches("vm_stopped", &api));
+        assert!(save.matches("vm_stopped", &HashMap::new()));
+
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert!(limiter.allow(notify, "vortex-1", now));
+        assert!(!limiter.allow(notify, "vortex-1", now + Duration::from_secs(30)));
+        assert!(limiter.allow(notify, "vortex-2", now));
+        assert!(limiter.allow(notify, "vortex-1", now + Duration::from_secs(60)));
+        assert!(limiter.allow(save, "vortex-1", now));
+        assert!(limiter.allow(save, "vortex-1", now));
+
+        let typo = Rule {
+            event: "vm_exploded".to_string(),
+            ..save.clone()
+        };
+        assert!(typo.validate().is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Per-run temporary directories under `~/.vortex/tmp/<run-id>`.
+//!
+//! Every `vortex run` gets its own directory for transient mounts and files.
+//! A `run.json` record ties the directory to the VM it served so that stop and
+//! cleanup can remove it, and a stale sweep catches runs that never reported
+//! back (crashes, killed CLIs).
+
+use crate::backend::sh_quote;
+use crate::diagnostics;
+use crate::error::{Result, VortexError};
+use crate::ids::{RunId, SessionId, VmId, WorkspaceId, LABEL_SESSION_ID, LABEL_WORKSPACE_ID};
+use crate::vm::{Probe, VmInstance};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const RECORD_NAME: &str = "run.json";
+/// Directory under `~/.vortex` holding the run directories
+pub const RUNS_DIR: &str = "tmp";
+/// Record `vortex-sync` keeps in a run directory while the run's
+/// `--sync-back` results wait to be copied to the host
+pub const PENDING_SYNC_RECORD: &str = "sync.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub run_id: RunId,
+    pub vm_id: Option<VmId>,
+    #[serde(default)]
+    pub session_id: Option<SessionId>,
+    #[serde(default)]
+    pub workspace_id: Option<WorkspaceId>,
+    pub created_at: DateTime<Utc>,
+    /// Health check of the VM, for commands run from other processes
+    #[serde(default)]
+    pub health_check: Option<Probe>,
+    /// When the VM outlives its TTL, for reaping it from other processes
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub struct RunDir {
+    path: PathBuf,
+    record: RunRecord,
+}
+
+impl RunDir {
+    /// Create a fresh run directory with a new run ID
+    pub fn create() -> Result<Self> {
+        let run_id = RunId::new(&Uuid::new_v4().to_string()[..12]);
+        let path = runs_root()?.join(run_id.as_str());
+        fs::create_dir_all(&path)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        }
+
+        let dir = Self::new(path, run_id);
+        dir.save_record()?;
+        Ok(dir)
+    }
+
+    fn new(path: PathBuf, run_id: RunId) -> Self {
+        Self {
+            path,
+            record: RunRecord {
+                run_id,
+                vm_id: None,
+                session_id: None,
+                workspace_id: None,
+                created_at: Utc::now(),
+                health_check: None,
+                expires_at: None,
+            },
+        }
+    }
+
+    pub fn run_id(&self) -> &RunId {
+        &self.record.run_id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Guest-side directory for this run's transient mount points
+    pub fn guest_path(&self, name: &str) -> PathBuf {
+        PathBuf::from(format!("/tmp/vortex-{}", self.record.run_id)).join(name)
+    }
+
+    /// Guest script of the run: copies each `--copy` destination in from its
+    /// `copy_in_<i>` mount, runs `command` in `workdir` with failure
+    /// diagnostics, copies each `--sync-back` source out to its
+    /// `copy_out_<i>` mount and exits with the status of `command`
+    pub fn guest_command(
+        &self,
+        command: &str,
+        copy_to: &[PathBuf],
+        workdir: Option<&str>,
+        sync_back: &[PathBuf],
+    ) -> String {
+        let mount = |name: String| sh_quote(&self.guest_path(&name).display().to_string());
+        let mut script = String::new();
+        for (i, dest) in copy_to.iter().enumerate() {
+            let dest = sh_quote(&dest.display().to_string());
+            script.push_str(&format!(
+                "mkdir -p {dest} && cp -r {}/* {dest} 2>/dev/null || true; ",
+                mount(format!("copy_in_{}", i))
+            ));
+        }
+        if let Some(workdir) = workdir {
+            script.push_str(&format!("cd {}; ", sh_quote(workdir)));
+        }
+        let bundle = self.guest_path("diagnostics").display().to_string();
+        script.push_str(&diagnostics::wrap_command(command, &bundle));
+        for (i, source) in sync_back.iter().enumerate() {
+            script.push_str(&format!(
+                "cp -r {} {} 2>/dev/null || true; ",
+                sh_quote(&source.display().to_string()),
+                mount(format!("copy_out_{}", i))
+            ));
+        }
+        script.push_str(&diagnostics::exit_with_status());
+        script
+    }
+
+    /// Record which VM owns this run so stop/cleanup and trace can find it
+    pub fn attach_vm(&mut self, vm: &VmInstance) -> Result<()> {
+        let labels = &vm.spec.labels;
+        self.record.vm_id = Some(VmId::new(vm.id.as_str()));
+        self.record.session_id = labels.get(LABEL_SESSION_ID).map(|id| id.as_str().into());
+        self.record.workspace_id = labels.get(LABEL_WORKSPACE_ID).map(|id| id.as_str().into());
+        self.record.health_check = vm.spec.health_check.clone();
+        self.record.expires_at = vm.expires_at();
+        self.save_record()?;
+        diagnostics::save_record(&self.record)
+    }
+
+    pub fn remove(self) -> Result<()> {
+        remove_run(&self.path, &self.record)
+    }
+
+    fn save_record(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.record)?;
+        fs::write(self.path.join(RECORD_NAME), content)?;
+        Ok(())
+    }
+}
+
+fn runs_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join(RUNS_DIR))
+}
+
+fn remove_dir(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove a run directory, keeping its diagnostics only if a failure was captured
+fn remove_run(path: &Path, record: &RunRecord) -> Result<()> {
+    remove_dir(path)?;
+    diagnostics::remove_if_empty(record.run_id.as_str())
+}
+
+/// Record of `run_id`, from its run directory or from its failure bundle
+pub fn find_run(run_id: &str) -> Result<Option<RunRecord>> {
+    if let Some((_, record)) = list_runs()?
+        .into_iter()
+        .find(|(_, record)| record.run_id == *run_id)
+    {
+        return Ok(Some(record));
+    }
+    diagnostics::load_record(run_id)
+}
+
+/// All run directories with a readable record
+pub fn list_runs() -> Result<Vec<(PathBuf, RunRecord)>> {
+    Ok(list_runs_in(&runs_root()?))
+}
+
+/// Run directories with a readable record under `root`
+pub(crate) fn list_runs_in(root: &Path) -> Vec<(PathBuf, RunRecord)> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut runs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(content) = fs::read_to_string(path.join(RECORD_NAME)) else {
+            continue;
+        };
+        if let Ok(record) = serde_json::from_str::<RunRecord>(&content) {
+            runs.push((path, record));
+        }
+    }
+    runs
+}
+
+/// Remove the run directories belonging to `vm_id`, returning how many were removed
+pub fn remove_runs_for_vm(vm_id: &str) -> Result<usize> {
+    let mut removed = 0;
+    for (path, record) in list_runs()? {
+        if record.vm_id.as_ref().is_some_and(|id| id == vm_id) {
+            remove_run(&path, &record)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Remove runs older than `max_age` whose VM is not in `live_vm_ids`
+pub fn cleanup_stale_runs(max_age: chrono::Duration, live_vm_ids: &[String]) -> Result<usize> {
+    let cutoff = Utc::now() - max_age;
+    let mut removed = 0;
+    for (path, record) in list_runs()? {
+        let live = record
+            .vm_id
+            .as_ref()
+            .is_some_and(|id| live_vm_ids.iter().any(|live| id == live.as_str()));
+        if !live && record.created_at < cutoff {
+            remove_run(&path, &record)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guest_command_exits_with_the_command_status() {
+        let dir = RunDir::new(PathBuf::new(), RunId::new("guest-command-test"));
+        let status = |command: &str| {
+            let script = dir.guest_command(command, &[], None, &[PathBuf::from("/nonexistent")]);
+            std::process::Command::new("sh")
+                .args(["-c", &script])
+                .output()
+                .unwrap()
+                .status
+                .code()
+        };
+        assert_eq!(status("false"), Some(1));
+        assert_eq!(status("exit 3"), Some(3));
+        assert_eq!(status("true"), Some(0));
+    }
+}
@@ -0,0 +1,235 @@
+//! Directories sealed at rest, for encrypted volumes.
+//!
+//! A sealed directory is a single file: a tar archive of the directory,
+//! encrypted with ChaCha20-Poly1305 in the STREAM construction. The archive
+//! is cut into 64 KiB chunks that are authenticated one by one, the last
+//! one flagged as such, so a file with chunks swapped, dropped or cut off
+//! fails to open instead of opening to less than was sealed. The file
+//! starts with [`MAGIC`] and the random nonce prefix of the stream.
+
+use crate::error::{Result, VortexError};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Bytes of a key
+pub const KEY_LEN: usize = 32;
+const MAGIC: &[u8; 4] = b"VXS1";
+/// Nonce bytes left to the stream once it took its counter and last flag
+const NONCE_PREFIX_LEN: usize = 7;
+const CHUNK: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+
+/// A new random key
+pub fn generate_key() -> Vec<u8> {
+    ChaCha20Poly1305::generate_key(&mut OsRng).to_vec()
+}
+
+/// Seal the files under `dir` into the file `dest`, replacing it only once
+/// the new file is complete
+pub fn seal(dir: &Path, dest: &Path, key: &[u8]) -> Result<()> {
+    let mut prefix = [0u8; NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut prefix);
+    let tmp = dest.with_extension("tmp");
+    let mut file = BufWriter::new(File::create(&tmp)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&prefix)?;
+
+    let writer = SealWriter {
+        inner: file,
+        encryptor: Some(EncryptorBE32::from_aead(cipher(key)?, &prefix.into())),
+        buffer: Vec::with_capacity(CHUNK),
+    };
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    let sealed = builder
+        .append_dir_all(".", dir)
+        .and_then(|()| builder.into_inner())
+        .and_then(SealWriter::finish)
+        .and_then(|file| file.into_inner().map_err(|e| e.into_error()))
+        .and_then(|file| file.sync_all());
+    if let Err(e) = sealed {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    fs::rename(&tmp, dest)?;
+    Ok(())
+}
+
+/// Unpack the file `src` sealed with `key` into the directory `dir`
+pub fn open(src: &Path, dir: &Path, key: &[u8]) -> Result<()> {
+    let mut file = io::BufReader::new(File::open(src)?);
+    let mut header = [0u8; MAGIC.len() + NONCE_PREFIX_LEN];
+    file.read_exact(&mut header)
+        .map_err(|_| undecryptable(src))?;
+    let (magic, prefix) = header.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return Err(undecryptable(src));
+    }
+
+    let mut reader = OpenReader {
+        inner: file,
+        decryptor: Some(DecryptorBE32::from_aead(cipher(key)?, prefix.into())),
+        plaintext: Vec::new(),
+        pos: 0,
+        next: Vec::new(),
+    };
+    reader.next = reader.read_chunk()?;
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.unpack(dir).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData => undecryptable(src),
+        _ => e.into(),
+    })
+}
+
+fn cipher(key: &[u8]) -> Result<ChaCha20Poly1305> {
+    if key.len() != KEY_LEN {
+        return Err(VortexError::StorageError {
+            message: format!("Encryption keys are {} bytes, not {}", KEY_LEN, key.len()),
+        });
+    }
+    Ok(ChaCha20Poly1305::new(Key::from_slice(key)))
+}
+
+fn undecryptable(path: &Path) -> VortexError {
+    VortexError::StorageError {
+        message: format!(
+            "{} could not be decrypted (wrong key or corrupt file)",
+            path.display()
+        ),
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "sealed chunk failed to decrypt")
+}
+
+/// Encrypts what is written to it chunk by chunk, holding back the last
+/// full chunk until `finish` tells it is the last
+struct SealWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<ChaCha20Poly1305>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> SealWriter<W> {
+    fn finish(mut self) -> io::Result<W> {
+        let encryptor = self.encryptor.take().ok_or_else(corrupt)?;
+        let chunk = encryptor
+            .encrypt_last(self.buffer.as_slice())
+            .map_err(|_| corrupt())?;
+        self.inner.write_all(&chunk)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SealWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while self.buffer.len() > CHUNK {
+            let encryptor = self.encryptor.as_mut().ok_or_else(corrupt)?;
+            let chunk = encryptor
+                .encrypt_next(&self.buffer[..CHUNK])
+                .map_err(|_| corrupt())?;
+            self.inner.write_all(&chunk)?;
+            self.buffer.drain(..CHUNK);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Partial chunks can only be written by `finish`
+        Ok(())
+    }
+}
+
+/// Decrypts a sealed stream, reading a chunk ahead to tell the last one
+struct OpenReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<ChaCha20Poly1305>>,
+    plaintext: Vec<u8>,
+    pos: usize,
+    next: Vec<u8>,
+}
+
+impl<R: Read> OpenReader<R> {
+    fn read_chunk(&mut self) -> io::Result<Vec<u8>> {
+        let mut chunk = Vec::with_capacity(CHUNK + TAG_LEN);
+        (&mut self.inner)
+            .take((CHUNK + TAG_LEN) as u64)
+            .read_to_end(&mut chunk)?;
+        Ok(chunk)
+    }
+}
+
+impl<R: Read> Read for OpenReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            let current = std::mem::take(&mut self.next);
+            self.next = self.read_chunk()?;
+            self.plaintext = if self.next.is_empty() {
+                let decryptor = self.decryptor.take().ok_or_else(corrupt)?;
+                decryptor.decrypt_last(current.as_slice())
+            } else {
+                let decryptor = self.decryptor.as_mut().ok_or_else(corrupt)?;
+                decryptor.decrypt_next(current.as_slice())
+            }
+            .map_err(|_| corrupt())?;
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..n].copy_from_slice(&self.plaintext[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_directories_open_only_with_their_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir_all(source.join("src")).unwrap();
+        // Spans several chunks, ending on a chunk boundary
+        let big: Vec<u8> = (0..CHUNK * 3).map(|i| (i % 251) as u8).collect();
+        fs::write(source.join("src/big.bin"), &big).unwrap();
+        fs::write(source.join("notes.txt"), "client code").unwrap();
+        let sealed = dir.path().join("data.sealed");
+        let key = generate_key();
+        seal(&source, &sealed, &key).unwrap();
+        let contents = fs::read(&sealed).unwrap();
+        assert!(!contents
+            .windows(b"client code".len())
+            .any(|window| window == b"client code"));
+
+        let opened = dir.path().join("opened");
+        open(&sealed, &opened, &key).unwrap();
+        assert_eq!(fs::read(opened.join("src/big.bin")).unwrap(), big);
+        assert_eq!(
+            fs::read_to_string(opened.join("notes.txt")).unwrap(),
+            "client code"
+        );
+
+        let elsewhere = dir.path().join("elsewhere");
+        assert!(open(&sealed, &elsewhere, &generate_key()).is_err());
+        // Cut off after the first chunk
+        fs::write(
+            &sealed,
+            &contents[..MAGIC.len() + NONCE_PREFIX_LEN + CHUNK + TAG_LEN],
+        )
+        .unwrap();
+        assert!(open(&sealed, &elsewhere, &key).is_err());
+    }
+}
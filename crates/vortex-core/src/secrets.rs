@@ -0,0 +1,257 @@
+//! Secrets of the credential store handed to VMs with `--secret`.
+//!
+//! `vortex run --secret NAME` and `vortex dev --secret NAME` write the
+//! secret to a file in the run's directory, which only the user can read,
+//! and mount the directory at `/run/secrets` in the guest. The guest gets
+//! the value as `/run/secrets/NAME` and as an environment variable, named
+//! after the secret (`api-token` becomes `API_TOKEN`) unless given as
+//! `NAME=VAR`. The variables are exported by a line put in front of the
+//! VM's command and by a profile script for login shells, both of which
+//! only read `/run/secrets/.env`; the values themselves never enter the
+//! `VmSpec`, so they stay out of labels, logs, events and `sessions.json`.
+//! The files go away with the run directory when the VM is removed.
+
+use crate::backend::sh_quote;
+use crate::credentials::{self, CredentialStore};
+use crate::error::{Result, VortexError};
+use crate::vm::VmSpec;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Where the secrets appear in the guest
+pub const SECRETS_MOUNT: &str = "/run/secrets";
+/// Shell script of the mount exporting the variables
+const ENV_FILE: &str = ".env";
+/// Link to the script that login shells of the guest read
+const PROFILE_LINK: &str = "/etc/profile.d/vortex-secrets.sh";
+
+/// A `--secret NAME[=VAR]` argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRequest {
+    pub name: String,
+    /// Environment variable the guest gets the secret in
+    pub variable: String,
+}
+
+impl FromStr for SecretRequest {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, variable) = match s.split_once('=') {
+            Some((name, variable)) => (name, variable.to_string()),
+            None => (s, variable_name(s)),
+        };
+        credentials::secret_key(name)?;
+        if name.starts_with('.') {
+            return Err(invalid(format!(
+                "Secret name '{}' may not start with '.'",
+                name
+            )));
+        }
+        let valid_variable = !variable.is_empty()
+            && !variable.starts_with(|c: char| c.is_ascii_digit())
+            && variable
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_variable {
+            return Err(invalid(format!(
+                "Invalid environment variable '{}' for secret '{}'",
+                variable, name
+            )));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            variable,
+        })
+    }
+}
+
+fn invalid(message: String) -> VortexError {
+    VortexError::InvalidInput {
+        field: "secret".to_string(),
+        message,
+    }
+}
+
+/// `api-token` -> `API_TOKEN`
+fn variable_name(name: &str) -> String {
+    let variable: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+    match variable.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!("_{}", variable),
+        false => variable,
+    }
+}
+
+/// The user's secrets, kept in the OS credential store
+pub struct SecretsManager {
+    store: Box<dyn CredentialStore>,
+}
+
+impl SecretsManager {
+    /// Secrets in the store [`credentials::default_store`] picks
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_store(credentials::default_store()?))
+    }
+
+    pub fn with_store(store: Box<dyn CredentialStore>) -> Self {
+        Self { store }
+    }
+
+    /// Short name of the store, e.g. `keychain`
+    pub fn store_name(&self) -> &'static str {
+        self.store.name()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        self.store.get(&credentials::secret_key(name)?)
+    }
+
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        self.store.set(&credentials::secret_key(name)?, value)
+    }
+
+    /// Remove the secret `name`, returning whether it existed
+    pub fn delete(&self, name: &str) -> Result<bool> {
+        self.store.delete(&credentials::secret_key(name)?)
+    }
+
+    /// Write the secrets of `requests` to `dir`, created readable by the
+    /// user only, for a VM to mount
+    pub fn prepare(&self, requests: &[SecretRequest], dir: &Path) -> Result<SecretMount> {
+        fs::create_dir_all(dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+        let mut env = String::new();
+        for request in requests {
+            let value = self.get(&request.name)?.ok_or_else(|| {
+                invalid(format!(
+                    "No secret named '{}'; store it with vortex secret set {}",
+                    request.name, request.name
+                ))
+            })?;
+            write_private(&dir.join(&request.name), &value)?;
+            env.push_str(&format!(
+                "export {}={}\n",
+                request.variable,
+                sh_quote(&value)
+            ));
+        }
+        write_private(&dir.join(ENV_FILE), &env)?;
+        Ok(SecretMount {
+            dir: dir.to_path_buf(),
+        })
+    }
+}
+
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Secrets written to a directory of the host, ready to be mounted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretMount {
+    pub dir: PathBuf,
+}
+
+impl SecretMount {
+    /// Mount the secrets at [`SECRETS_MOUNT`] and export their variables
+    /// to the command of `spec` and to login shells
+    pub fn apply(&self, spec: &mut VmSpec) {
+        spec.volumes
+            .insert(self.dir.clone(), PathBuf::from(SECRETS_MOUNT));
+        if let Some(command) = &spec.command {
+            let env = format!("{}/{}", SECRETS_MOUNT, ENV_FILE);
+            spec.command = Some(format!(
+                "{{ mkdir -p /etc/profile.d && ln -sf {} {}; }} 2>/dev/null; . {}; {}",
+                env, PROFILE_LINK, env, command
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<String, String>>);
+
+    impl CredentialStore for MemoryStore {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, secret: &str) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), secret.to_string());
+            Ok(())
+        }
+
+        fn delete(&self, key: &str) -> Result<bool> {
+            Ok(self.0.lock().unwrap().remove(key).is_some())
+        }
+    }
+
+    #[test]
+    fn test_secrets_reach_the_guest_but_not_the_spec() {
+        let secrets = SecretsManager::with_store(Box::<MemoryStore>::default());
+        secrets.set("api-token", "it's s3cret").unwrap();
+        let requests: Vec<SecretRequest> = ["api-token", "api-token=GH_TOKEN"]
+            .iter()
+            .map(|arg| arg.parse().unwrap())
+            .collect();
+        assert_eq!(requests[0].variable, "API_TOKEN");
+        assert!("..".parse::<SecretRequest>().is_err());
+        assert!("api-token=1X".parse::<SecretRequest>().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mount = secrets
+            .prepare(&requests, &dir.path().join("secrets"))
+            .unwrap();
+        let read = |name: &str| fs::read_to_string(dir.path().join("secrets").join(name));
+        assert_eq!(read("api-token").unwrap(), "it's s3cret");
+        assert_eq!(
+            read(ENV_FILE).unwrap(),
+            "export API_TOKEN='it'\\''s s3cret'\nexport GH_TOKEN='it'\\''s s3cret'\n"
+        );
+
+        let mut spec = VmSpec {
+            command: Some("make deploy".to_string()),
+            ..VmSpec::default()
+        };
+        mount.apply(&mut spec);
+        let spec = serde_json::to_string(&spec).unwrap();
+        assert!(spec.contains("/run/secrets/.env; make deploy"));
+        assert!(!spec.contains("s3cret"));
+
+        let missing = "other".parse::<SecretRequest>().unwrap();
+        assert!(secrets.prepare(&[missing], dir.path()).is_err());
+    }
+}
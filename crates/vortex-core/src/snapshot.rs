@@ -0,0 +1,289 @@
+//! Saved VM state under `~/.vortex/snapshots/<snapshot-id>/`.
+//!
+//! A snapshot records the VM's spec and backend in `snapshot.json`; the
+//! backend writes its own state (memory, device state, disk copy) into the
+//! `state/` directory next to it. Only the backend that took a snapshot can
+//! restore it, and the restored VM gets a new ID.
+//!
+//! Snapshots move between machines as Vortex archives (see `crate::archive`)
+//! holding the snapshot's directory as one layer; a loaded snapshot gets a
+//! new ID. VMs are saved to and loaded from archives the same way, through
+//! a snapshot.
+
+use crate::archive::{self, ArchiveKind, ArchiveManifest};
+use crate::error::{Result, VortexError};
+use crate::ids::{SnapshotId, VmId};
+use crate::vm::VmSpec;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const RECORD_NAME: &str = "snapshot.json";
+const STATE_DIR: &str = "state";
+/// Name of the layer holding a snapshot's directory in archives
+const SNAPSHOT_LAYER: &str = "snapshot";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub id: SnapshotId,
+    /// The VM the snapshot was taken from
+    pub vm_id: VmId,
+    pub backend: String,
+    pub spec: VmSpec,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self::at(home.join(".vortex").join("snapshots")))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn dir(&self, id: &SnapshotId) -> PathBuf {
+        self.root.join(id.as_str())
+    }
+
+    /// Where the backend keeps its state for snapshot `id`
+    pub fn state_dir(&self, id: &SnapshotId) -> PathBuf {
+        self.dir(id).join(STATE_DIR)
+    }
+
+    /// Reserve a new snapshot with an empty state directory. The record is
+    /// written by `commit` once the backend has saved its state.
+    pub fn allocate(&self) -> Result<SnapshotId> {
+        let id = new_id();
+        fs::create_dir_all(self.state_dir(&id))?;
+        Ok(id)
+    }
+
+    pub fn commit(&self, record: &SnapshotRecord) -> Result<()> {
+        let content = serde_json::to_string_pretty(record)?;
+        fs::write(self.dir(&record.id).join(RECORD_NAME), content)?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: &SnapshotId) -> Result<SnapshotRecord> {
+        let path = self.dir(id).join(RECORD_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(VortexError::InvalidInput {
+                    field: "snapshot".to_string(),
+                    message: format!("Snapshot {} not found", id),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Completed snapshots, oldest first
+    pub fn list(&self) -> Result<Vec<SnapshotRecord>> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Ok(Vec::new());
+        };
+        let mut records: Vec<SnapshotRecord> = entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path().join(RECORD_NAME)).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        records.sort_by_key(|record| record.created_at);
+        Ok(records)
+    }
+
+    /// Write snapshot `id` to `dest` as a Vortex archive, with the spec of
+    /// the VM it was taken from for provenance
+    pub fn export(&self, id: &SnapshotId, dest: &Path) -> Result<ArchiveManifest> {
+        self.write_archive(id, ArchiveKind::Snapshot, dest)
+    }
+
+    /// Write snapshot `id` to `dest` as an archive of `kind`: of the
+    /// snapshot, or of the VM it was taken from when saving a VM
+    pub fn write_archive(
+        &self,
+        id: &SnapshotId,
+        kind: ArchiveKind,
+        dest: &Path,
+    ) -> Result<ArchiveManifest> {
+        let record = self.load(id)?;
+        let mut manifest = ArchiveManifest::new(kind);
+        manifest.source_id = Some(match kind {
+            ArchiveKind::Vm => record.vm_id.to_string(),
+            _ => id.to_string(),
+        });
+        manifest.spec = Some(record.spec);
+        manifest
+            .labels
+            .insert("vortex.backend".to_string(), record.backend);
+        archive::write_archive(
+            dest,
+            manifest,
+            &[(SNAPSHOT_LAYER.to_string(), self.dir(id))],
+        )
+    }
+
+    /// Add the snapshot in the archive `src`, written by `write_archive`,
+    /// under a new ID
+    pub fn load_archive(&self, src: &Path) -> Result<SnapshotRecord> {
+        let manifest = archive::read_manifest(src)?;
+        let kind_matches = matches!(manifest.kind, ArchiveKind::Snapshot | ArchiveKind::Vm);
+        if !kind_matches || manifest.layer(SNAPSHOT_LAYER).is_none() {
+            return Err(VortexError::InvalidInput {
+                field: "archive".to_string(),
+                message: format!("{} is not a snapshot or VM archive", src.display()),
+            });
+        }
+
+        let id = new_id();
+        let staging = self.root.join(format!(".load-{}", id));
+        let extracted = archive::extract_archive(src, &staging).and_then(|_| {
+            fs::rename(staging.join(SNAPSHOT_LAYER), self.dir(&id))?;
+            Ok(())
+        });
+        let _ = fs::remove_dir_all(&staging);
+        extracted?;
+
+        let record = match self.load(&id) {
+            Ok(record) => SnapshotRecord { id, ..record },
+            Err(e) => {
+                self.remove(&id)?;
+                return Err(e);
+            }
+        };
+        self.commit(&record)?;
+        Ok(record)
+    }
+
+    /// Delete a snapshot, complete or not
+    pub fn remove(&self, id: &SnapshotId) -> Result<()> {
+        match fs::remove_dir_all(self.dir(id)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn new_id() -> SnapshotId {
+    SnapshotId::new(format!(
+        "snap-{}",
+        &Uuid::new_v4().simple().to_string()[..8]
+    ))
+}
+
+/// Total size of the files under `dir`
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::at(dir.path().to_path_buf());
+        assert!(store.list().unwrap().is_empty());
+
+        let id = store.allocate().unwrap();
+        fs::write(store.state_dir(&id).join("memory"), [0u8; 64]).unwrap();
+        // Not listed until the backend has finished and the record is written
+        assert!(store.list().unwrap().is_empty());
+
+        let record = SnapshotRecord {
+            id: id.clone(),
+            vm_id: VmId::new("vortex-1a2b3c4d"),
+            backend: "qemu".to_string(),
+            spec: VmSpec {
+                image: "alpine".to_string(),
+                ..Default::default()
+            },
+            created_at: Utc::now(),
+        };
+        store.commit(&record).unwrap();
+        assert_eq!(store.load(&id).unwrap().vm_id, record.vm_id);
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(dir_size(&store.dir(&id)) >= 64);
+
+        store.remove(&id).unwrap();
+        assert!(store.load(&id).is_err());
+    }
+
+    #[test]
+    fn test_exported_snapshots_load_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::at(dir.path().join("here"));
+        let id = store.allocate().unwrap();
+        fs::write(store.state_dir(&id).join("memory"), [7u8; 64]).unwrap();
+        store
+            .commit(&SnapshotRecord {
+                id: id.clone(),
+                vm_id: VmId::new("vortex-1a2b3c4d"),
+                backend: "cloud-hypervisor".to_string(),
+                spec: VmSpec {
+                    image: "alpine".to_string(),
+                    ..Default::default()
+                },
+                created_at: Utc::now(),
+            })
+            .unwrap();
+
+        let archive = dir.path().join("snap.vortex");
+        let manifest = store.export(&id, &archive).unwrap();
+        assert_eq!(manifest.kind, ArchiveKind::Snapshot);
+        assert_eq!(manifest.spec.unwrap().image, "alpine");
+
+        let elsewhere = SnapshotStore::at(dir.path().join("there"));
+        let loaded = elsewhere.load_archive(&archive).unwrap();
+        assert_ne!(loaded.id, id);
+        assert_eq!(loaded.backend, "cloud-hypervisor");
+        assert_eq!(elsewhere.load(&loaded.id).unwrap().id, loaded.id);
+        assert_eq!(
+            fs::read(elsewhere.state_dir(&loaded.id).join("memory")).unwrap(),
+            [7u8; 64]
+        );
+        assert_eq!(elsewhere.list().unwrap().len(), 1);
+
+        // A saved VM is archived through a snapshot, under the VM's ID
+        let saved = dir.path().join("vm.vortex");
+        let manifest = store.write_archive(&id, ArchiveKind::Vm, &saved).unwrap();
+        assert_eq!(manifest.source_id.as_deref(), Some("vortex-1a2b3c4d"));
+        assert_eq!(
+            elsewhere.load_archive(&saved).unwrap().vm_id.as_str(),
+            "vortex-1a2b3c4d"
+        );
+
+        let not_a_snapshot = dir.path().join("workspace.vortex");
+        archive::write_archive(
+            &not_a_snapshot,
+            ArchiveManifest::new(ArchiveKind::Workspace),
+            &[],
+        )
+        .unwrap();
+        assert!(elsewhere.load_archive(&not_a_snapshot).is_err());
+    }
+}
@@ -0,0 +1,1231 @@
+//! Named volumes: host directories that VMs mount by name with
+//! `--volume <name>:<guest path>`, so data outlives ephemeral VMs.
+//!
+//! Each volume lives in `~/.vortex/volumes/<name>`, its files under `data/`
+//! next to a `volume.json` with its metadata. Volumes are plain
+//! directories, so every backend that shares host directories mounts them.
+//!
+//! Encrypted volumes keep their files sealed in `data.sealed` (see
+//! `crate::sealed`) under a random key held in the OS credential store
+//! (see `crate::credentials`). They are unlocked into `data/` to be
+//! mounted and locked again once no VM needs them, so their files are only
+//! readable on disk while unlocked: each VM mounting one leaves a claim in
+//! `~/.vortex/volumes/.claims/<vm id>` when `VmManager` starts it, and the
+//! volume is locked when the last VM holding a claim stops. Encrypted
+//! workspaces keep their files in such volumes too.
+//!
+//! A volume exports to a zstd-compressed tarball of its files, which
+//! `tar --zstd -xf` unpacks as well, and imports from one as a new volume,
+//! so caches and databases move between machines.
+//!
+//! Snapshots of a volume are kept in its `snapshots/<id>/`, copied with
+//! reflinks where the filesystem has them (Btrfs, XFS, APFS), so taking
+//! one and rolling back to it are instant whatever the volume's size.
+//!
+//! Root disks of VMs are copy-on-write layers too. Each disk image a VM is
+//! created from is copied once into `~/.vortex/layers/` as a read-only base
+//! layer, and the VM gets a qcow2 delta that reads through to it and keeps
+//! only the blocks the VM writes. The base is hard-linked next to the delta
+//! as `base.raw`, which the delta names relatively, so a VM directory (or a
+//! snapshot copied from it) stands on its own and a base lives as long as
+//! any disk links it. Without `qemu-img` VMs get full copies as before.
+
+use crate::archive::ZSTD_LEVEL;
+use crate::auth::{AuthContext, Permission};
+use crate::credentials;
+use crate::error::{Result, VortexError};
+use crate::image_store::ImageStore;
+use crate::run_dir;
+use crate::sealed;
+use crate::snapshot::dir_size;
+use crate::vm::VmSpec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Use dirs crate for secure home directory detection
+use dirs::home_dir;
+use fs2::FileExt;
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+const METADATA_FILE: &str = "volume.json";
+const DATA_DIR: &str = "data";
+const SEALED_FILE: &str = "data.sealed";
+const SNAPSHOTS_DIR: &str = "snapshots";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+/// Directory of the volume root holding the claims of VMs on encrypted volumes
+const CLAIMS_DIR: &str = ".claims";
+/// File name of the base layer next to a qcow2 delta
+pub const BASE_LAYER: &str = "base.raw";
+const QCOW2_MAGIC: &[u8; 4] = b"QFI\xfb";
+/// Backends keeping a directory per VM under `~/.vortex/<backend>/vms`
+/// that nothing else removes once the VM is gone. WSL's are left alone:
+/// their distribution has to be unregistered first.
+const VM_DIR_BACKENDS: [&str; 3] = ["qemu", "libkrun", "cloud-hypervisor"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Volume {
+    pub name: String,
+    /// Host directory holding the volume's files, which VMs mount
+    pub path: PathBuf,
+    /// Bytes the volume's files take up, sealed or not
+    pub size_bytes: u64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Whether the volume's files are encrypted at rest
+    pub encrypted: bool,
+    /// Whether an encrypted volume's files are sealed, so `path` does not
+    /// exist until it is unlocked
+    pub locked: bool,
+}
+
+/// What `volume.json` records; the rest is read from the directory
+#[derive(Serialize, Deserialize)]
+struct VolumeMetadata {
+    created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    encrypted: bool,
+}
+
+/// A volume's files as they were at one point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeSnapshot {
+    /// ID of the snapshot (e.g. `snap-1a2b3c4d`)
+    pub id: String,
+    pub volume: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// What `StorageManager::prune` sweeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrunedKind {
+    /// Directory of a run that is over, under `~/.vortex/tmp`
+    RunDirectory,
+    /// Directory a VM backend keeps for a VM that is gone
+    VmDirectory,
+    /// Base layer of VM disks under `~/.vortex/layers`
+    BaseLayer,
+    /// Cached manifest or layer under `~/.vortex/images/blobs`
+    ImageBlob,
+}
+
+/// Something `StorageManager::prune` removed, or would remove
+#[derive(Debug, Clone)]
+pub struct Pruned {
+    pub kind: PrunedKind,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+pub struct StorageManager {
+    storage_root: PathBuf,
+    /// Who changes to volumes are made for
+    auth: AuthContext,
+}
+
+impl StorageManager {
+    pub async fn new() -> Result<Self> {
+        // Use dirs crate for secure home directory detection
+        // Note: This will use /tmp if HOME is not set, which is less secure
+        // In production, ensure HOME is set to a secure location
+        let storage_root = home_dir()
+            .map(|h| h.join(".vortex").join("volumes"))
+            .unwrap_or_else(|| PathBuf::from("/tmp/vortex/volumes"));
+
+        // Warn if falling back to /tmp (world-writable directory)
+        if storage_root.starts_with("/tmp/") {
+            tracing::warn!(
+                "Using /tmp for storage root. This is less secure as /tmp is world-writable. \
+                Please set HOME environment variable to a secure location."
+            );
+        }
+
+        std::fs::create_dir_all(&storage_root)?;
+
+        Ok(Self::at(storage_root))
+    }
+
+    pub fn at(storage_root: PathBuf) -> Self {
+        Self {
+            storage_root,
+            auth: AuthContext::local(),
+        }
+    }
+
+    /// Make changes for `auth`, which needs `StorageManage` for them
+    pub fn with_auth(mut self, auth: AuthContext) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    fn volume_dir(&self, name: &str) -> Result<PathBuf> {
+        validate_name(name)?;
+        Ok(self.storage_root.join(name))
+    }
+
+    /// Create the empty volume `name`
+    pub async fn create_volume(&self, name: &str) -> Result<Volume> {
+        self.auth.require(Permission::StorageManage)?;
+        self.create(name, false)
+    }
+
+    /// Create the empty volume `name`, encrypted at rest under a new key
+    /// kept in the OS credential store. It starts out locked.
+    pub async fn create_encrypted_volume(&self, name: &str) -> Result<Volume> {
+        self.auth.require(Permission::StorageManage)?;
+        self.create_encrypted(name)?;
+        self.lock(name)
+    }
+
+    /// Create the encrypted volume `name` and its key, unlocked so files
+    /// can be put in before it is locked
+    pub(crate) fn create_encrypted(&self, name: &str) -> Result<Volume> {
+        let key = sealed::generate_key();
+        credentials::default_store()?.set(&credentials::volume_key(name)?, &to_hex(&key))?;
+        self.create(name, true)
+    }
+
+    fn create(&self, name: &str, encrypted: bool) -> Result<Volume> {
+        let dir = self.volume_dir(name)?;
+        if dir.exists() {
+            return Err(VortexError::StorageError {
+                message: format!("Volume {} already exists", name),
+            });
+        }
+        let data = dir.join(DATA_DIR);
+        fs::create_dir_all(&data)?;
+        #[cfg(unix)]
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        let metadata = VolumeMetadata {
+            created_at: chrono::Utc::now(),
+            encrypted,
+        };
+        fs::write(
+            dir.join(METADATA_FILE),
+            serde_json::to_string_pretty(&metadata)?,
+        )?;
+
+        Ok(Volume {
+            name: name.to_string(),
+            path: data,
+            size_bytes: 0,
+            created_at: metadata.created_at,
+            encrypted,
+            locked: false,
+        })
+    }
+
+    /// The volume `name` ready to be mounted: created if it does not exist
+    /// yet, unlocked if it is encrypted
+    pub async fn ensure_volume(&self, name: &str) -> Result<Volume> {
+        self.auth.require(Permission::StorageManage)?;
+        match self.get_volume(name).await? {
+            Some(volume) if volume.locked => self.unlock_volume(name).await,
+            Some(volume) => Ok(volume),
+            None => self.create_volume(name).await,
+        }
+    }
+
+    /// Decrypt the files of the encrypted volume `name` into its `path`,
+    /// for VMs to mount
+    pub async fn unlock_volume(&self, name: &str) -> Result<Volume> {
+        self.auth.require(Permission::StorageManage)?;
+        self.unlock(name)
+    }
+
+    pub(crate) fn unlock(&self, name: &str) -> Result<Volume> {
+        let volume = self.volume(name)?.ok_or_else(|| missing(name))?;
+        if !volume.encrypted {
+            return Err(not_encrypted(name));
+        }
+        if !volume.locked {
+            return Ok(volume);
+        }
+        let dir = self.volume_dir(name)?;
+        let unlocking = dir.join(".unlocking");
+        let _ = fs::remove_dir_all(&unlocking);
+        fs::create_dir(&unlocking)?;
+        if let Err(e) = sealed::open(&dir.join(SEALED_FILE), &unlocking, &self.key(name)?) {
+            let _ = fs::remove_dir_all(&unlocking);
+            return Err(e);
+        }
+        fs::rename(&unlocking, &volume.path)?;
+        self.volume(name)?.ok_or_else(|| missing(name))
+    }
+
+    /// Seal the files of the encrypted volume `name` and delete them from
+    /// its `path`. VMs that have the volume mounted lose its files, so lock
+    /// it once none do.
+    pub async fn lock_volume(&self, name: &str) -> Result<Volume> {
+        self.auth.require(Permission::StorageManage)?;
+        self.lock(name)
+    }
+
+    pub(crate) fn lock(&self, name: &str) -> Result<Volume> {
+        let volume = self.volume(name)?.ok_or_else(|| missing(name))?;
+        if !volume.encrypted {
+            return Err(not_encrypted(name));
+        }
+        if !volume.locked {
+            self.seal(name, &self.key(name)?)?;
+        }
+        self.volume(name)?.ok_or_else(|| missing(name))
+    }
+
+    fn seal(&self, name: &str, key: &[u8]) -> Result<()> {
+        let dir = self.volume_dir(name)?;
+        let data = dir.join(DATA_DIR);
+        sealed::seal(&data, &dir.join(SEALED_FILE), key)?;
+        fs::remove_dir_all(data)?;
+        Ok(())
+    }
+
+    fn key(&self, name: &str) -> Result<Vec<u8>> {
+        credentials::default_store()?
+            .get(&credentials::volume_key(name)?)?
+            .and_then(|key| from_hex(&key))
+            .ok_or_else(|| VortexError::StorageError {
+                message: format!("The key of encrypted volume {} is missing", name),
+            })
+    }
+
+    pub async fn get_volume(&self, name: &str) -> Result<Option<Volume>> {
+        self.volume(name)
+    }
+
+    pub(crate) fn volume(&self, name: &str) -> Result<Option<Volume>> {
+        let dir = self.volume_dir(name)?;
+        let metadata = match fs::read_to_string(dir.join(METADATA_FILE)) {
+            Ok(content) => serde_json::from_str::<VolumeMetadata>(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let path = dir.join(DATA_DIR);
+        let locked = metadata.encrypted && !path.exists();
+        let size_bytes = if locked {
+            dir_size_or_len(&dir.join(SEALED_FILE))
+        } else {
+            dir_size(&path)
+        };
+        Ok(Some(Volume {
+            name: name.to_string(),
+            size_bytes,
+            path,
+            created_at: metadata.created_at,
+            encrypted: metadata.encrypted,
+            locked,
+        }))
+    }
+
+    /// All volumes, by name
+    pub async fn list_volumes(&self) -> Result<Vec<Volume>> {
+        let Ok(entries) = fs::read_dir(&self.storage_root) else {
+            return Ok(Vec::new());
+        };
+        let mut volumes = Vec::new();
+        for entry in entries.flatten() {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if validate_name(&name).is_err() {
+                continue;
+            }
+            if let Some(volume) = self.get_volume(&name).await? {
+                volumes.push(volume);
+            }
+        }
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    /// Delete the volume `name`, its files and, for an encrypted volume,
+    /// its key
+    pub async fn remove_volume(&self, name: &str) -> Result<()> {
+        self.auth.require(Permission::StorageManage)?;
+        self.remove(name)
+    }
+
+    pub(crate) fn remove(&self, name: &str) -> Result<()> {
+        let volume = self.volume(name)?.ok_or_else(|| missing(name))?;
+        fs::remove_dir_all(self.volume_dir(name)?)?;
+        if volume.encrypted {
+            credentials::default_store()?.delete(&credentials::volume_key(name)?)?;
+        }
+        Ok(())
+    }
+
+    /// Write the files of the volume `name` to `dest` as a tarball
+    /// compressed with zstd as it is written, and return its size. An
+    /// encrypted volume must be unlocked, and its export is not encrypted.
+    pub async fn export_volume(&self, name: &str, dest: &Path) -> Result<u64> {
+        self.auth.require(Permission::StorageManage)?;
+        let volume = self.get_volume(name).await?.ok_or_else(|| missing(name))?;
+        if volume.locked {
+            return Err(VortexError::StorageError {
+                message: format!("Volume {} is locked; unlock it to export its files", name),
+            });
+        }
+        let dest = dest.to_path_buf();
+        tokio::task::spawn_blocking(move || export_tarball(&volume.path, &dest))
+            .await
+            .map_err(|e| VortexError::StorageError {
+                message: format!("Exporting volume {} failed: {}", name, e),
+            })?
+    }
+
+    /// Create the volume `name` holding the files of the tarball `src`, as
+    /// written by `export_volume`
+    pub async fn import_volume(&self, name: &str, src: &Path) -> Result<Volume> {
+        self.auth.require(Permission::StorageManage)?;
+        let volume = self.create(name, false)?;
+        let dir = self.volume_dir(name)?;
+        let importing = dir.join(".importing");
+        let src = src.to_path_buf();
+        let unpacked = {
+            let importing = importing.clone();
+            tokio::task::spawn_blocking(move || import_tarball(&src, &importing))
+                .await
+                .map_err(|e| VortexError::StorageError {
+                    message: format!("Importing volume {} failed: {}", name, e),
+                })
+                .and_then(|result| result)
+        };
+        let swapped = unpacked.and_then(|()| {
+            fs::remove_dir(&volume.path)?;
+            fs::rename(&importing, &volume.path)?;
+            Ok(())
+        });
+        if let Err(e) = swapped {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+        self.get_volume(name).await?.ok_or_else(|| missing(name))
+    }
+
+    /// `~/.vortex`, which holds the volumes and everything `prune` sweeps
+    fn vortex_root(&self) -> &Path {
+        self.storage_root.parent().unwrap_or(&self.storage_root)
+    }
+
+    /// Remove what nothing needs anymore and is older than `older_than`:
+    /// directories of runs that are over (holding their `--copy-to` mounts),
+    /// directories VM backends left behind for VMs not in `live_vms`, base
+    /// layers no disk links, and image blobs no pulled image references.
+    /// Run directories with `--sync-back` results not yet copied are kept.
+    /// With `dry_run` nothing is removed, so base layers only VMs about to
+    /// be pruned use are not reported.
+    pub async fn prune(
+        &self,
+        live_vms: &[String],
+        older_than: chrono::Duration,
+        dry_run: bool,
+    ) -> Result<Vec<Pruned>> {
+        self.auth.require(Permission::StorageManage)?;
+        let cutoff = chrono::Utc::now() - older_than;
+        let old_enough = |path: &Path| {
+            fs::symlink_metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| chrono::DateTime::<chrono::Utc>::from(modified) < cutoff)
+        };
+        let live = |id: &str| live_vms.iter().any(|live| live == id);
+        let root = self.vortex_root().to_path_buf();
+        let mut pruned = Vec::new();
+        let mut prune = |kind: PrunedKind, path: PathBuf| -> Result<()> {
+            let size_bytes = dir_size_or_len(&path);
+            if !dry_run {
+                match remove_path(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            pruned.push(Pruned {
+                kind,
+                path,
+                size_bytes,
+            });
+            Ok(())
+        };
+
+        for (path, record) in run_dir::list_runs_in(&root.join(run_dir::RUNS_DIR)) {
+            let running = record.vm_id.as_ref().is_some_and(|id| live(id.as_str()));
+            if !running
+                && record.created_at < cutoff
+                && !path.join(run_dir::PENDING_SYNC_RECORD).exists()
+            {
+                prune(PrunedKind::RunDirectory, path)?;
+            }
+        }
+
+        for backend in VM_DIR_BACKENDS {
+            let Ok(entries) = fs::read_dir(root.join(backend).join("vms")) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let id = entry.file_name().to_string_lossy().into_owned();
+                if !live(&id) && old_enough(&entry.path()) {
+                    prune(PrunedKind::VmDirectory, entry.path())?;
+                }
+            }
+        }
+
+        for base in LayerStore::at(root.join("layers")).unused()? {
+            if old_enough(&base) {
+                prune(PrunedKind::BaseLayer, base)?;
+            }
+        }
+
+        for blob in ImageStore::at(root.join("images")).unreferenced_blobs()? {
+            if old_enough(&blob) {
+                prune(PrunedKind::ImageBlob, blob)?;
+            }
+        }
+        Ok(pruned)
+    }
+
+    fn snapshot_dir(&self, volume: &str, snapshot: &str) -> Result<PathBuf> {
+        let dir = self.volume_dir(volume)?;
+        let valid = !snapshot.is_empty()
+            && snapshot
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(VortexError::InvalidInput {
+                field: "snapshot".to_string(),
+                message: format!("Invalid snapshot ID '{}'", snapshot),
+            });
+        }
+        Ok(dir.join(SNAPSHOTS_DIR).join(snapshot))
+    }
+
+    /// Snapshot the files of the volume `name`. Encrypted volumes are
+    /// snapshotted locked, so their snapshots stay sealed.
+    pub async fn snapshot(&self, name: &str) -> Result<VolumeSnapshot> {
+        self.auth.require(Permission::StorageManage)?;
+        let volume = self.get_volume(name).await?.ok_or_else(|| missing(name))?;
+        let contents = self.contents(&volume)?;
+        let snapshot = VolumeSnapshot {
+            id: format!("snap-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]),
+            volume: volume.name,
+            created_at: chrono::Utc::now(),
+        };
+        let dir = self.snapshot_dir(name, &snapshot.id)?;
+        fs::create_dir_all(&dir)?;
+        if let Err(e) = clone_tree(&contents, &dir.join(file_name(&contents))).await {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+        fs::write(
+            dir.join(SNAPSHOT_FILE),
+            serde_json::to_string_pretty(&snapshot)?,
+        )?;
+        Ok(snapshot)
+    }
+
+    /// Snapshots of the volume `name`, oldest first
+    pub async fn list_snapshots(&self, name: &str) -> Result<Vec<VolumeSnapshot>> {
+        let dir = self.volume_dir(name)?.join(SNAPSHOTS_DIR);
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(Vec::new());
+        };
+        let mut snapshots: Vec<VolumeSnapshot> = entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path().join(SNAPSHOT_FILE)).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        snapshots.sort_by_key(|snapshot| snapshot.created_at);
+        Ok(snapshots)
+    }
+
+    /// Put the files of the volume `name` back as they were at `snapshot`,
+    /// which is kept. VMs that have the volume mounted keep seeing the
+    /// files they had, so roll back while none do.
+    pub async fn rollback(&self, name: &str, snapshot: &str) -> Result<()> {
+        self.auth.require(Permission::StorageManage)?;
+        let volume = self.get_volume(name).await?.ok_or_else(|| missing(name))?;
+        let contents = self.contents(&volume)?;
+        let source = self.snapshot_dir(name, snapshot)?;
+        if !source.join(SNAPSHOT_FILE).exists() {
+            return Err(VortexError::StorageError {
+                message: format!("Volume {} has no snapshot {}", name, snapshot),
+            });
+        }
+
+        // Copy first and swap after, so a failed copy leaves the volume as
+        // it was
+        let dir = self.volume_dir(name)?;
+        let restored = dir.join(format!(".rollback-{}", snapshot));
+        let replaced = dir.join(format!(".replaced-{}", snapshot));
+        let _ = remove_path(&restored);
+        if let Err(e) = clone_tree(&source.join(file_name(&contents)), &restored).await {
+            let _ = remove_path(&restored);
+            return Err(e);
+        }
+        fs::rename(&contents, &replaced)?;
+        fs::rename(&restored, &contents)?;
+        remove_path(&replaced)?;
+        Ok(())
+    }
+
+    /// What snapshots of `volume` copy: its files, or the file sealing them
+    fn contents(&self, volume: &Volume) -> Result<PathBuf> {
+        if !volume.encrypted {
+            return Ok(volume.path.clone());
+        }
+        if !volume.locked {
+            return Err(VortexError::StorageError {
+                message: format!(
+                    "Volume {} is unlocked; lock it first so its snapshots stay encrypted",
+                    volume.name
+                ),
+            });
+        }
+        Ok(self.volume_dir(&volume.name)?.join(SEALED_FILE))
+    }
+
+    /// Delete `snapshot` of the volume `name`
+    pub async fn remove_snapshot(&self, name: &str, snapshot: &str) -> Result<()> {
+        self.auth.require(Permission::StorageManage)?;
+        let dir = self.snapshot_dir(name, snapshot)?;
+        if !dir.join(SNAPSHOT_FILE).exists() {
+            return Err(VortexError::StorageError {
+                message: format!("Volume {} has no snapshot {}", name, snapshot),
+            });
+        }
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
+
+/// The encrypted volume whose files are at `path`, as the manager of the
+/// root it is in and its name
+fn encrypted_volume(path: &Path) -> Option<(StorageManager, String)> {
+    if file_name(path) != DATA_DIR {
+        return None;
+    }
+    let dir = path.parent()?;
+    let metadata: VolumeMetadata =
+        serde_json::from_str(&fs::read_to_string(dir.join(METADATA_FILE)).ok()?).ok()?;
+    if !metadata.encrypted {
+        return None;
+    }
+    let name = dir.file_name()?.to_str()?.to_string();
+    Some((StorageManager::at(dir.parent()?.to_path_buf()), name))
+}
+
+/// Which VMs have which encrypted volumes mounted: a file per VM listing
+/// the directories of its volumes
+struct VolumeClaims {
+    dir: PathBuf,
+}
+
+impl VolumeClaims {
+    /// The claims under `~/.vortex/volumes`
+    fn new() -> Result<Self> {
+        let home = home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self::at(
+            home.join(".vortex").join("volumes").join(CLAIMS_DIR),
+        ))
+    }
+
+    fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Exclusive lock on the claims, released when the file is dropped.
+    /// Held across unlocking and claiming volumes, and across releasing and
+    /// locking them, so that a VM stopping in another process cannot lock a
+    /// volume a starting one has just unlocked.
+    fn lock(&self) -> Result<File> {
+        let path = self.dir.with_extension("lock");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        file.lock_exclusive()?;
+        Ok(file)
+    }
+
+    fn claimed(&self, vm_id: &str) -> bool {
+        self.dir.join(vm_id).exists()
+    }
+
+    fn claim(&self, vm_id: &str, volumes: &[PathBuf]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(vm_id), serde_json::to_string(volumes)?)?;
+        Ok(())
+    }
+
+    /// Drop the claim of `vm_id`, returning the volumes no other VM claims
+    fn release(&self, vm_id: &str) -> Result<Vec<PathBuf>> {
+        let path = self.dir.join(vm_id);
+        let mut released: Vec<PathBuf> = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        fs::remove_file(&path)?;
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let claimed: Vec<PathBuf> = fs::read_to_string(entry.path())
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .unwrap_or_default();
+            released.retain(|volume| !claimed.contains(volume));
+        }
+        Ok(released)
+    }
+}
+
+/// Unlock the encrypted volumes `spec` mounts and claim them for `vm_id`,
+/// so they stay unlocked until it stops
+pub(crate) async fn claim_volumes(vm_id: &str, spec: &VmSpec) -> Result<()> {
+    let encrypted: Vec<_> = spec
+        .volumes
+        .keys()
+        .filter_map(|host| encrypted_volume(host))
+        .collect();
+    if encrypted.is_empty() {
+        return Ok(());
+    }
+    let claims = VolumeClaims::new()?;
+    let _lock = claims.lock()?;
+    let mut claimed = Vec::new();
+    for (storage, name) in encrypted {
+        storage.unlock(&name)?;
+        claimed.push(storage.volume_dir(&name)?);
+    }
+    claims.claim(vm_id, &claimed)
+}
+
+/// Drop the claims of `vm_id` and lock the encrypted volumes no other VM
+/// has mounted; failures only warn
+pub(crate) async fn release_volumes(vm_id: &str) {
+    let released = VolumeClaims::new().and_then(|claims| {
+        if !claims.claimed(vm_id) {
+            return Ok(None);
+        }
+        let lock = claims.lock()?;
+        Ok(Some((lock, claims.release(vm_id)?)))
+    });
+    let (_lock, released) = match released {
+        Ok(Some(released)) => released,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to release the volumes of {}: {}", vm_id, e);
+            return;
+        }
+    };
+    for dir in released {
+        let Some((storage, name)) = encrypted_volume(&dir.join(DATA_DIR)) else {
+            continue;
+        };
+        match storage.lock(&name) {
+            Ok(_) => tracing::info!("Locked volume {} now that no VM has it mounted", name),
+            Err(e) => tracing::warn!("Failed to lock volume {}: {}", name, e),
+        }
+    }
+}
+
+/// Stream `dir` into a zstd-compressed tarball at `dest`, replacing it
+/// only once the tarball is complete
+fn export_tarball(dir: &Path, dest: &Path) -> Result<u64> {
+    let tmp = dest.with_extension("tmp");
+    let written = (|| -> std::io::Result<u64> {
+        let encoder = zstd::Encoder::new(BufWriter::new(File::create(&tmp)?), ZSTD_LEVEL)?;
+        let mut builder = tar::Builder::new(encoder);
+        builder.follow_symlinks(false);
+        builder.append_dir_all(".", dir)?;
+        let mut file = builder.into_inner()?.finish()?;
+        file.flush()?;
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(file.metadata()?.len())
+    })();
+    match written {
+        Ok(size) => {
+            fs::rename(&tmp, dest)?;
+            Ok(size)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e.into())
+        }
+    }
+}
+
+/// Unpack the zstd-compressed tarball `src` into the new directory `dir`
+fn import_tarball(src: &Path, dir: &Path) -> Result<()> {
+    fs::create_dir(dir)?;
+    let decoder = zstd::Decoder::new(File::open(src)?)?;
+    let mut archive = tar::Archive::new(decoder);
+    archive.set_preserve_permissions(true);
+    archive.unpack(dir).map_err(|e| VortexError::StorageError {
+        message: format!("{} is not a volume export: {}", src.display(), e),
+    })
+}
+
+fn dir_size_or_len(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => dir_size(path),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+fn missing(name: &str) -> VortexError {
+    VortexError::StorageError {
+        message: format!("Volume {} does not exist", name),
+    }
+}
+
+fn not_encrypted(name: &str) -> VortexError {
+    VortexError::StorageError {
+        message: format!("Volume {} is not encrypted", name),
+    }
+}
+
+fn file_name(path: &Path) -> &std::ffi::OsStr {
+    path.file_name().unwrap_or_default()
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Copy the file or directory `src` to `dst`, which must not exist, sharing
+/// the files' blocks where the filesystem supports it
+pub async fn clone_tree(src: &Path, dst: &Path) -> Result<()> {
+    let mut command = tokio::process::Command::new("cp");
+    if cfg!(target_os = "macos") {
+        // clonefile(2), which copies outright off APFS
+        command.arg("-Rpc");
+    } else {
+        command.args(["-a", "--reflink=auto"]);
+    }
+    let output = command.arg(src).arg(dst).output().await?;
+    if !output.status.success() {
+        return Err(VortexError::StorageError {
+            message: format!(
+                "Failed to copy {}: {}",
+                src.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Base layers of VM root disks, shared by the qcow2 deltas VMs write to
+pub struct LayerStore {
+    root: PathBuf,
+}
+
+impl LayerStore {
+    /// The store under `~/.vortex/layers`
+    pub fn new() -> Result<Self> {
+        let home = home_dir().ok_or_else(|| VortexError::StorageError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+        Ok(Self::at(home.join(".vortex").join("layers")))
+    }
+
+    pub fn at(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// The base layer with the contents of the disk image `image`, copied
+    /// in on first use. A changed image gets a new base, so bases never
+    /// change under the deltas reading them.
+    async fn base(&self, image: &Path) -> Result<PathBuf> {
+        let metadata = fs::metadata(image)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos());
+        let identity = format!(
+            "{}\n{}\n{}",
+            fs::canonicalize(image)?.display(),
+            metadata.len(),
+            modified
+        );
+        let key = format!("{:x}", Sha256::digest(identity.as_bytes()));
+        let base = self.root.join(format!("{}.raw", &key[..32]));
+        if base.exists() {
+            return Ok(base);
+        }
+
+        fs::create_dir_all(&self.root)?;
+        let tmp = self
+            .root
+            .join(format!(".tmp-{}", uuid::Uuid::new_v4().simple()));
+        if let Err(e) = clone_tree(image, &tmp).await {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        #[cfg(unix)]
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o444))?;
+        // Another VM creation may have copied the same image first; both
+        // copies hold the same contents
+        fs::rename(&tmp, &base)?;
+        Ok(base)
+    }
+
+    /// Give a VM the writable disk `disk` with the contents of the disk
+    /// image `image`: a qcow2 delta over the image's base layer, or a full
+    /// copy when `qemu-img` is missing
+    pub async fn create_disk(&self, image: &Path, disk: &Path) -> Result<()> {
+        let qemu_img = tokio::process::Command::new("qemu-img")
+            .arg("--version")
+            .output()
+            .await;
+        if !qemu_img.is_ok_and(|output| output.status.success()) {
+            tokio::fs::copy(image, disk).await?;
+            return Ok(());
+        }
+
+        let base = self.base(image).await?;
+        let link = disk.with_file_name(BASE_LAYER);
+        link_base(&base, &link).await?;
+        let output = tokio::process::Command::new("qemu-img")
+            .args(["create", "-q", "-f", "qcow2", "-F", "raw", "-b", BASE_LAYER])
+            .arg(disk)
+            .output()
+            .await?;
+        if !output.status.success() {
+            let _ = fs::remove_file(&link);
+            return Err(VortexError::StorageError {
+                message: format!(
+                    "Failed to create a disk over {}: {}",
+                    image.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Base layers no disk links anymore
+    pub fn unused(&self) -> Result<Vec<PathBuf>> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Ok(Vec::new());
+        };
+        let mut unused = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            #[cfg(unix)]
+            let linked = {
+                use std::os::unix::fs::MetadataExt;
+                metadata.nlink() > 1
+            };
+            // Without link counts every base may still be read through
+            #[cfg(not(unix))]
+            let linked = true;
+            if !linked && entry.path().extension().is_some_and(|ext| ext == "raw") {
+                unused.push(entry.path());
+            }
+        }
+        Ok(unused)
+    }
+
+    /// Remove the base layers no disk links anymore. Returns the bytes freed.
+    pub fn prune(&self) -> Result<u64> {
+        let mut freed = 0;
+        for base in self.unused()? {
+            freed += fs::metadata(&base)?.len();
+            fs::remove_file(base)?;
+        }
+        Ok(freed)
+    }
+}
+
+/// Link the base layer `base` at `link`, or copy it when `link` is on
+/// another filesystem
+async fn link_base(base: &Path, link: &Path) -> Result<()> {
+    let _ = fs::remove_file(link);
+    if fs::hard_link(base, link).is_err() {
+        clone_tree(base, link).await?;
+    }
+    Ok(())
+}
+
+/// Whether the disk at `disk` is a qcow2 delta rather than a raw image
+pub fn is_qcow2(disk: &Path) -> bool {
+    use std::io::Read;
+    let mut magic = [0u8; 4];
+    fs::File::open(disk)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == QCOW2_MAGIC
+}
+
+/// Copy the VM disk `src` to `dst`, along with the base layer it reads
+/// through to when it is a delta
+pub async fn copy_disk(src: &Path, dst: &Path) -> Result<()> {
+    tokio::fs::copy(src, dst).await?;
+    let base = src.with_file_name(BASE_LAYER);
+    if is_qcow2(src) && base.exists() {
+        link_base(&base, &dst.with_file_name(BASE_LAYER)).await?;
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.len() <= 64
+        && name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_' || c == '.'
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(VortexError::InvalidInput {
+            field: "volume".to_string(),
+            message: format!(
+                "Invalid volume name '{}': use up to 64 lowercase letters, digits, '-', '_' or '.', \
+                 starting with a letter or digit",
+                name
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_named_volumes_keep_their_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageManager::at(dir.path().to_path_buf());
+
+        let volume = storage.create_volume("pgdata").await.unwrap();
+        assert!(storage.create_volume("pgdata").await.is_err());
+        assert!(storage.create_volume("../etc").await.is_err());
+        fs::write(volume.path.join("base"), vec![0u8; 4096]).unwrap();
+
+        let volume = storage.ensure_volume("pgdata").await.unwrap();
+        assert_eq!(volume.size_bytes, 4096);
+        storage.ensure_volume("cache").await.unwrap();
+        let names: Vec<String> = storage
+            .list_volumes()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|volume| volume.name)
+            .collect();
+        assert_eq!(names, ["cache", "pgdata"]);
+
+        storage.remove_volume("pgdata").await.unwrap();
+        assert!(storage.get_volume("pgdata").await.unwrap().is_none());
+        assert!(storage.remove_volume("pgdata").await.is_err());
+    }
+
+    #[test]
+    fn test_volumes_are_released_by_their_last_vm() {
+        let dir = tempfile::tempdir().unwrap();
+        let claims = VolumeClaims::at(dir.path().join(CLAIMS_DIR));
+        let (shared, own) = (
+            PathBuf::from("/volumes/shared"),
+            PathBuf::from("/volumes/own"),
+        );
+        claims
+            .claim("vortex-a", &[shared.clone(), own.clone()])
+            .unwrap();
+        claims
+            .claim("vortex-b", std::slice::from_ref(&shared))
+            .unwrap();
+
+        assert_eq!(claims.release("vortex-a").unwrap(), [own]);
+        assert!(claims.release("vortex-a").unwrap().is_empty());
+        assert_eq!(claims.release("vortex-b").unwrap(), [shared]);
+        assert!(encrypted_volume(dir.path()).is_none());
+
+        let _held = claims.lock().unwrap();
+        let other = File::open(dir.path().join(".claims.lock")).unwrap();
+        assert!(other.try_lock_exclusive().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_volumes_need_the_storage_permission() {
+        let dir = tempfile::tempdir().unwrap();
+        let reader = AuthContext::from(crate::auth::AuthToken {
+            token: String::new(),
+            user_id: "ci".to_string(),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            permissions: vec![Permission::VmRead, Permission::MetricsRead],
+        });
+        let storage = StorageManager::at(dir.path().to_path_buf()).with_auth(reader);
+        assert!(matches!(
+            storage.create_volume("pgdata").await,
+            Err(VortexError::PermissionDenied { .. })
+        ));
+        assert!(storage.list_volumes().await.unwrap().is_empty());
+
+        let storage = storage.with_auth(AuthContext::local());
+        storage.create_volume("pgdata").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rollback_restores_snapshotted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageManager::at(dir.path().to_path_buf());
+        let volume = storage.create_volume("pgdata").await.unwrap();
+        fs::create_dir(volume.path.join("base")).unwrap();
+        fs::write(volume.path.join("base/1"), "before").unwrap();
+
+        let snapshot = storage.snapshot("pgdata").await.unwrap();
+        fs::write(volume.path.join("base/1"), "after").unwrap();
+        fs::write(volume.path.join("new"), "after").unwrap();
+        assert!(storage.rollback("pgdata", "snap-missing").await.is_err());
+        assert!(storage.rollback("pgdata", "../data").await.is_err());
+
+        storage.rollback("pgdata", &snapshot.id).await.unwrap();
+        let restored = fs::read_to_string(volume.path.join("base/1")).unwrap();
+        assert_eq!(restored, "before");
+        assert!(!volume.path.join("new").exists());
+        // The snapshot survives the rollback, so it can be rolled back to again
+        let ids: Vec<String> = storage
+            .list_snapshots("pgdata")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.id)
+            .collect();
+        assert_eq!(ids, [snapshot.id.as_str()]);
+        storage
+            .remove_snapshot("pgdata", &snapshot.id)
+            .await
+            .unwrap();
+        assert!(storage.list_snapshots("pgdata").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exported_volumes_import_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageManager::at(dir.path().join("here"));
+        let volume = storage.create_volume("cache").await.unwrap();
+        fs::create_dir(volume.path.join("deps")).unwrap();
+        fs::write(volume.path.join("deps/lib.rlib"), vec![1u8; 100_000]).unwrap();
+        let tarball = dir.path().join("cache.tar.zst");
+        let size = storage.export_volume("cache", &tarball).await.unwrap();
+        assert!(size > 0 && size < 100_000);
+
+        let elsewhere = StorageManager::at(dir.path().join("there"));
+        let imported = elsewhere.import_volume("cache", &tarball).await.unwrap();
+        assert_eq!(
+            fs::read(imported.path.join("deps/lib.rlib")).unwrap(),
+            vec![1u8; 100_000]
+        );
+        assert!(elsewhere.import_volume("cache", &tarball).await.is_err());
+
+        // A failed import leaves no volume behind
+        fs::write(dir.path().join("junk.tar.zst"), "not a tarball").unwrap();
+        let junk = dir.path().join("junk.tar.zst");
+        assert!(elsewhere.import_volume("junk", &junk).await.is_err());
+        assert!(elsewhere.get_volume("junk").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_what_is_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let storage = StorageManager::at(root.join("volumes"));
+        for vm in ["vortex-live", "vortex-gone"] {
+            fs::create_dir_all(root.join("qemu/vms").join(vm)).unwrap();
+        }
+        fs::create_dir_all(root.join("images/blobs/sha256")).unwrap();
+        fs::write(
+            root.join("images/blobs/sha256").join("a".repeat(64)),
+            "layer",
+        )
+        .unwrap();
+        let run = root.join(run_dir::RUNS_DIR).join("run1");
+        fs::create_dir_all(&run).unwrap();
+        fs::write(
+            run.join("run.json"),
+            r#"{"run_id":"run1","vm_id":"vortex-gone","created_at":"2020-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+
+        let live = ["vortex-live".to_string()];
+        let recent = storage
+            .prune(&live, chrono::Duration::hours(1), false)
+            .await
+            .unwrap();
+        // Only the run's age is recorded; everything else was just made
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].kind, PrunedKind::RunDirectory);
+
+        let pruned = storage
+            .prune(&live, chrono::Duration::zero(), true)
+            .await
+            .unwrap();
+        let kinds: Vec<PrunedKind> = pruned.iter().map(|item| item.kind).collect();
+        assert_eq!(kinds, [PrunedKind::VmDirectory, PrunedKind::ImageBlob]);
+        assert!(pruned[0].path.ends_with("vortex-gone"));
+        assert!(pruned.iter().all(|item| item.path.exists()));
+    }
+
+    #[tokio::test]
+    async fn test_disks_share_a_base_layer_until_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let layers = LayerStore::at(dir.path().join("layers"));
+        let image = dir.path().join("alpine.raw");
+        fs::write(&image, vec![7u8; 8192]).unwrap();
+
+        let base = layers.base(&image).await.unwrap();
+        assert_eq!(layers.base(&image).await.unwrap(), base);
+        assert_eq!(fs::read(&base).unwrap(), fs::read(&image).unwrap());
+
+        // A delta's base travels with it when the disk is copied
+        let vm = dir.path().join("vm");
+        let clone = dir.path().join("clone");
+        fs::create_dir_all(&vm).unwrap();
+        fs::create_dir_all(&clone).unwrap();
+        let disk = vm.join("rootfs.raw");
+        fs::write(&disk, b"QFI\xfb delta").unwrap();
+        link_base(&base, &vm.join(BASE_LAYER)).await.unwrap();
+        assert!(is_qcow2(&disk) && !is_qcow2(&image));
+        copy_disk(&disk, &clone.join("rootfs.raw")).await.unwrap();
+        assert!(clone.join(BASE_LAYER).exists());
+
+        assert_eq!(layers.prune().unwrap(), 0);
+        fs::remove_dir_all(&vm).unwrap();
+        assert_eq!(layers.prune().unwrap(), 0);
+        fs::remove_dir_all(&clone).unwrap();
+        assert_eq!(layers.prune().unwrap(), 8192);
+        assert!(!base.exists());
+    }
+}
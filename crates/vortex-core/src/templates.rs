@@ -0,0 +1,870 @@
+use crate::error::{Result, VortexError};
+use crate::home_volume;
+use crate::interpolate;
+use crate::nix::NixEnvironment;
+use crate::secrets::SecretMount;
+use crate::tuning::TuningProfile;
+use crate::vm::{NetworkPolicy, ShareMechanism, VmSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevTemplate {
+    pub name: String,
+    pub description: String,
+    pub base_image: String,
+    pub tools: Vec<String>,
+    pub environment: HashMap<String, String>,
+    pub startup_commands: Vec<String>,
+    pub default_workdir: String,
+    pub ports: Vec<String>,
+    pub extensions: Vec<String>,                // VSCode extensions, etc.
+    pub packages: HashMap<String, Vec<String>>, // package_manager -> packages
+    /// Built-in guest tuning profile applied at boot (see `TuningProfile`)
+    #[serde(default)]
+    pub tuning_profile: Option<String>,
+    /// Enter a Nix flake devshell after the startup commands
+    #[serde(default)]
+    pub nix: Option<NixEnvironment>,
+    /// Mount `~/.vortex/homes/<template>` over the guest home directory
+    #[serde(default)]
+    pub persist_home: bool,
+}
+
+/// Per-run changes to the VM spec a dev template produces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DevOverrides {
+    pub memory: Option<u32>,
+    pub cpus: Option<u32>,
+    /// Replaces the template's port mappings
+    pub ports: Option<HashMap<u16, u16>>,
+    pub backend: Option<String>,
+    /// Added to the template's share mechanisms, by guest path
+    pub share_mechanisms: HashMap<PathBuf, ShareMechanism>,
+    /// Replaces the template's base image
+    pub image: Option<String>,
+    /// Added to the template's environment
+    pub environment: HashMap<String, String>,
+    /// Added host:guest mounts
+    pub volumes: HashMap<PathBuf, PathBuf>,
+    /// Secrets mounted into the guest with `--secret`
+    pub secrets: Option<SecretMount>,
+}
+
+impl DevOverrides {
+    pub fn apply(&self, spec: &mut VmSpec) {
+        if let Some(memory) = self.memory {
+            spec.memory = memory;
+        }
+        if let Some(cpus) = self.cpus {
+            spec.cpus = cpus;
+        }
+        if let Some(ports) = &self.ports {
+            spec.ports = ports.clone();
+        }
+        spec.backend = self.backend.clone();
+        spec.share_mechanisms.extend(self.share_mechanisms.clone());
+        if let Some(image) = &self.image {
+            spec.image = image.clone();
+        }
+        spec.environment.extend(self.environment.clone());
+        spec.volumes.extend(self.volumes.clone());
+        if let Some(secrets) = &self.secrets {
+            secrets.apply(spec);
+        }
+    }
+}
+
+/// Where a dev template definition came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateOrigin {
+    Builtin,
+    /// User template with no built-in counterpart
+    User {
+        path: PathBuf,
+    },
+    /// User template shadowing the built-in of the same name
+    Override {
+        path: PathBuf,
+    },
+    /// Defined in the `.vortex/templates` of the project, shadowing any
+    /// other template of the same name
+    Project {
+        path: PathBuf,
+    },
+    /// Contributed by a plugin
+    Plugin {
+        plugin: String,
+    },
+}
+
+impl std::fmt::Display for TemplateOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateOrigin::Builtin => write!(f, "built-in"),
+            TemplateOrigin::User { path } => write!(f, "user ({})", path.display()),
+            TemplateOrigin::Override { path } => {
+                write!(f, "user override of built-in ({})", path.display())
+            }
+            TemplateOrigin::Project { path } => write!(f, "project ({})", path.display()),
+            TemplateOrigin::Plugin { plugin } => write!(f, "plugin {}", plugin),
+        }
+    }
+}
+
+impl DevTemplate {
+    /// Check a template definition before it is saved or used
+    pub fn validate(&self) -> Result<()> {
+        if self.base_image.trim().is_empty() {
+            return Err(VortexError::InvalidInput {
+                field: "base_image".to_string(),
+                message: "Template base_image must not be empty".to_string(),
+            });
+        }
+
+        for p in &self.ports {
+            let valid = p
+                .split_once(':')
+                .map(|(host, guest)| host.parse::<u16>().is_ok() && guest.parse::<u16>().is_ok())
+                .unwrap_or(false);
+            if !valid {
+                return Err(VortexError::InvalidInput {
+                    field: "ports".to_string(),
+                    message: format!("Invalid port mapping format '{}', expected 'host:guest'", p),
+                });
+            }
+        }
+
+        if let Some(profile) = &self.tuning_profile {
+            TuningProfile::resolve(profile)?;
+        }
+
+        if let Some(nix) = &self.nix {
+            nix.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct DevEnvironmentManager {
+    templates: HashMap<String, DevTemplate>,
+    origins: HashMap<String, TemplateOrigin>,
+}
+
+impl Default for DevEnvironmentManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DevEnvironmentManager {
+    pub fn new() -> Self {
+        Self::with_plugin_templates(Vec::new())
+    }
+
+    /// Like `new`, with the `(plugin, template)` pairs of
+    /// `PluginManager::templates` added between the built-in and the user
+    /// templates: a plugin cannot replace a built-in or an earlier plugin's
+    /// template, and a user template of the same name shadows it.
+    pub fn with_plugin_templates(plugin_templates: Vec<(String, DevTemplate)>) -> Self {
+        let mut manager = Self {
+            templates: HashMap::new(),
+            origins: HashMap::new(),
+        };
+
+        // Load built-in templates, then let user templates shadow them
+        manager.load_builtin_templates();
+        for name in manager.templates.keys() {
+            manager
+                .origins
+                .insert(name.clone(), TemplateOrigin::Builtin);
+        }
+        for (plugin, template) in plugin_templates {
+            manager.add_plugin_template(plugin, template);
+        }
+        if let Some(dir) = user_template_dir() {
+            manager.load_user_templates(&dir);
+        }
+        manager
+    }
+
+    /// Add the templates of the project in `dir`, from the files in its
+    /// `.vortex/templates`, over all others
+    pub fn with_project_templates(mut self, dir: &Path) -> Self {
+        for (path, template) in read_template_dir(&project_template_dir(dir)) {
+            let name = template.name.clone();
+            self.templates.insert(name.clone(), template);
+            self.origins.insert(name, TemplateOrigin::Project { path });
+        }
+        self
+    }
+
+    fn add_plugin_template(&mut self, plugin: String, template: DevTemplate) {
+        let name = template.name.clone();
+        if let Some(origin) = self.origins.get(&name) {
+            tracing::warn!(
+                "Ignoring template '{}' of plugin {}: the {} template has that name",
+                name,
+                plugin,
+                origin
+            );
+            return;
+        }
+        if let Err(e) = check_template_name(&name).and_then(|_| template.validate()) {
+            tracing::warn!("Ignoring invalid template of plugin {}: {}", plugin, e);
+            return;
+        }
+        self.templates.insert(name.clone(), template);
+        self.origins.insert(name, TemplateOrigin::Plugin { plugin });
+    }
+
+    fn load_user_templates(&mut self, dir: &Path) {
+        for (path, template) in read_template_dir(dir) {
+            let name = template.name.clone();
+            let origin = if self.origins.get(&name) == Some(&TemplateOrigin::Builtin) {
+                TemplateOrigin::Override { path }
+            } else {
+                TemplateOrigin::User { path }
+            };
+            self.templates.insert(name.clone(), template);
+            self.origins.insert(name, origin);
+        }
+    }
+
+    fn load_builtin_templates(&mut self) {
+        // Python development environment
+        self.templates.insert(
+            "python".to_string(),
+            DevTemplate {
+                name: "python".to_string(),
+                description: "Complete Python development environment with pip, virtualenv, and debugging tools".to_string(),
+                base_image: "python:3.11-slim".to_string(),
+                tools: vec![
+                    "python3".to_string(),
+                    "pip".to_string(),
+                    "virtualenv".to_string(),
+                    "git".to_string(),
+                    "curl".to_string(),
+                    "vim".to_string(),
+                    "nano".to_string(),
+                ],
+                environment: HashMap::from([
+                    ("PYTHONPATH".to_string(), "/workspace".to_string()),
+                    ("PIP_CACHE_DIR".to_string(), "/cache/pip".to_string()),
+                    ("PYTHONDONTWRITEBYTECODE".to_string(), "1".to_string()),
+                ]),
+                startup_commands: vec![
+                    "apt-get update".to_string(),
+                    "apt-get install -y git curl vim nano build-essential".to_string(),
+                    "pip install --upgrade pip setuptools wheel".to_string(),
+                    "pip install pytest black flake8 mypy ipython jupyter".to_string(),
+                ],
+                default_workdir: "/workspace".to_string(),
+                ports: vec!["8000:8000".to_string(), "8888:8888".to_string()], // Common Python ports
+                extensions: vec!["ms-python.python".to_string(), "ms-python.debugpy".to_string()],
+                packages: HashMap::from([
+                    ("pip".to_string(), vec!["requests".to_string(), "fastapi".to_string(), "pandas".to_string()]),
+                ]),
+                tuning_profile: None,
+                nix: None,
+                persist_home: false,
+            },
+        );
+
+        // Node.js development environment
+        self.templates.insert(
+            "node".to_string(),
+            DevTemplate {
+                name: "node".to_string(),
+                description:
+                    "Node.js development environment with npm, yarn, and development tools"
+                        .to_string(),
+                base_image: "node:18-slim".to_string(),
+                tools: vec![
+                    "node".to_string(),
+                    "npm".to_string(),
+                    "yarn".to_string(),
+                    "git".to_string(),
+                    "curl".to_string(),
+                    "vim".to_string(),
+                ],
+                environment: HashMap::from([
+                    ("NODE_ENV".to_string(), "development".to_string()),
+                    ("NPM_CONFIG_CACHE".to_string(), "/cache/npm".to_string()),
+                ]),
+                startup_commands: vec![
+                    "apt-get update".to_string(),
+                    "apt-get install -y git curl vim python3 make g++".to_string(),
+                    "npm install -g yarn typescript ts-node nodemon eslint prettier".to_string(),
+                ],
+                default_workdir: "/app".to_string(),
+                ports: vec![
+                    "3000:3000".to_string(),
+                    "8080:8080".to_string(),
+                    "9229:9229".to_string(),
+                ], // Dev server + debugger
+                extensions: vec!["ms-vscode.vscode-typescript-next".to_string()],
+                packages: HashMap::from([(
+                    "npm".to_string(),
+                    vec![
+                        "express".to_string(),
+                        "lodash".to_string(),
+                        "axios".to_string(),
+                    ],
+                )]),
+                tuning_profile: None,
+                nix: None,
+                persist_home: false,
+            },
+        );
+
+        // Rust development environment
+        self.templates.insert(
+            "rust".to_string(),
+            DevTemplate {
+                name: "rust".to_string(),
+                description: "Rust development environment with cargo, clippy, and debugging tools"
+                    .to_string(),
+                base_image: "rust:1.75-slim".to_string(),
+                tools: vec![
+                    "cargo".to_string(),
+                    "rustc".to_string(),
+                    "rustfmt".to_string(),
+                    "clippy".to_string(),
+                    "git".to_string(),
+                ],
+                environment: HashMap::from([
+                    ("CARGO_HOME".to_string(), "/cache/cargo".to_string()),
+                    ("RUSTUP_HOME".to_string(), "/cache/rustup".to_string()),
+                ]),
+                startup_commands: vec![
+                    "apt-get update".to_string(),
+                    "apt-get install -y git curl vim build-essential".to_string(),
+                    "rustup component add clippy rustfmt rust-src".to_string(),
+                    "cargo install cargo-watch cargo-edit cargo-audit".to_string(),
+                ],
+                default_workdir: "/workspace".to_string(),
+                ports: vec!["8000:8000".to_string()],
+                extensions: vec!["rust-lang.rust-analyzer".to_string()],
+                packages: HashMap::new(),
+                tuning_profile: Some("build".to_string()),
+                nix: None,
+                persist_home: false,
+            },
+        );
+
+        // Go development environment
+        self.templates.insert(
+            "go".to_string(),
+            DevTemplate {
+                name: "go".to_string(),
+                description: "Go development environment with modules, debugging, and tools"
+                    .to_string(),
+                base_image: "golang:1.21-alpine".to_string(),
+                tools: vec!["go".to_string(), "gofmt".to_string(), "git".to_string()],
+                environment: HashMap::from([
+                    ("GO111MODULE".to_string(), "on".to_string()),
+                    ("GOCACHE".to_string(), "/cache/go".to_string()),
+                    ("GOMODCACHE".to_string(), "/cache/gomod".to_string()),
+                ]),
+                startup_commands: vec![
+                    "apk add --no-cache git curl vim build-base".to_string(),
+                    "go install golang.org/x/tools/cmd/goimports@latest".to_string(),
+                    "go install github.com/go-delve/delve/cmd/dlv@latest".to_string(),
+                ],
+                default_workdir: "/workspace".to_string(),
+                ports: vec!["8080:8080".to_string(), "2345:2345".to_string()], // Web server + debugger
+                extensions: vec!["golang.go".to_string()],
+                packages: HashMap::new(),
+                tuning_profile: Some("build".to_string()),
+                nix: None,
+                persist_home: false,
+            },
+        );
+
+        // AI/ML development environment
+        self.templates.insert(
+            "ai".to_string(),
+            DevTemplate {
+                name: "ai".to_string(),
+                description: "AI/ML development environment with Python, PyTorch, TensorFlow, and Jupyter".to_string(),
+                base_image: "python:3.11-slim".to_string(),
+                tools: vec![
+                    "python3".to_string(),
+                    "pip".to_string(),
+                    "jupyter".to_string(),
+                    "git".to_string(),
+                ],
+                environment: HashMap::from([
+                    ("PYTHONPATH".to_string(), "/workspace".to_string()),
+                    ("CUDA_VISIBLE_DEVICES".to_string(), "0".to_string()),
+                    ("JUPYTER_ENABLE_LAB".to_string(), "yes".to_string()),
+                ]),
+                startup_commands: vec![
+                    "apt-get update".to_string(),
+                    "apt-get install -y git curl vim build-essential".to_string(),
+                    "pip install --upgrade pip".to_string(),
+                    "pip install torch torchvision tensorflow jupyter pandas numpy matplotlib scikit-learn".to_string(),
+                    "pip install transformers datasets accelerate".to_string(),
+                ],
+                default_workdir: "/workspace".to_string(),
+                ports: vec!["8888:8888".to_string(), "6006:6006".to_string()], // Jupyter + TensorBoard
+                extensions: vec!["ms-python.python".to_string(), "ms-toolsai.jupyter".to_string()],
+                packages: HashMap::from([
+                    ("pip".to_string(), vec!["torch".to_string(), "transformers".to_string(), "datasets".to_string()]),
+                ]),
+                tuning_profile: None,
+                nix: None,
+                persist_home: false,
+            },
+        );
+
+        // Nix flake devshell environment
+        self.templates.insert(
+            "nix".to_string(),
+            DevTemplate {
+                name: "nix".to_string(),
+                description: "Reproducible environment from the project's flake devShell"
+                    .to_string(),
+                base_image: "nixos/nix:latest".to_string(),
+                tools: vec!["nix".to_string(), "git".to_string()],
+                environment: HashMap::new(),
+                startup_commands: vec![],
+                default_workdir: "/workspace".to_string(),
+                ports: vec![],
+                extensions: vec!["jnoortheen.nix-ide".to_string()],
+                packages: HashMap::new(),
+                tuning_profile: None,
+                nix: Some(NixEnvironment {
+                    flake: ".".to_string(),
+                    devshell: "default".to_string(),
+                    share_host_store: true,
+                }),
+                persist_home: false,
+            },
+        );
+    }
+
+    pub fn get_template(&self, name: &str) -> Option<&DevTemplate> {
+        self.templates.get(name)
+    }
+
+    pub fn list_templates(&self) -> Vec<&DevTemplate> {
+        self.templates.values().collect()
+    }
+
+    pub fn template_origin(&self, name: &str) -> Option<&TemplateOrigin> {
+        self.origins.get(name)
+    }
+
+    /// Make sure a user-editable copy of `name` exists and return its path.
+    ///
+    /// Built-in templates are copied into the user template directory on first
+    /// edit; existing user and project templates are returned as-is.
+    pub fn prepare_user_template(&self, name: &str) -> Result<PathBuf> {
+        if let Some(TemplateOrigin::Project { path }) = self.template_origin(name) {
+            return Ok(path.clone());
+        }
+        let path = user_template_path(name)?;
+        if path.exists() {
+            return Ok(path);
+        }
+
+        let template = self
+            .get_template(name)
+            .ok_or_else(|| VortexError::TemplateNotFound {
+                name: name.to_string(),
+            })?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(template).map_err(|e| VortexError::ConfigError {
+            message: format!("Failed to serialize template: {}", e),
+        })?;
+        std::fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    pub fn template_to_vm_spec(
+        &self,
+        template_name: &str,
+        custom_workdir: Option<String>,
+    ) -> Result<VmSpec> {
+        let template =
+            self.get_template(template_name)
+                .ok_or_else(|| VortexError::TemplateNotFound {
+                    name: template_name.to_string(),
+                })?;
+
+        let workdir = custom_workdir.unwrap_or_else(|| template.default_workdir.clone());
+
+        // Validate startup commands for dangerous shell metacharacters
+        for command in &template.startup_commands {
+            if command.contains('&')
+                || command.contains('|')
+                || command.contains(';')
+                || command.contains('`')
+                || command.contains('$')
+                || command.contains('(')
+                || command.contains(')')
+                || command.contains('<')
+                || command.contains('>')
+                || command.contains('\n')
+                || command.contains('\r')
+            {
+                return Err(VortexError::InvalidInput {
+                    field: "startup_commands".to_string(),
+                    message: format!(
+                        "Startup command contains forbidden shell metacharacters: {}",
+                        command
+                    ),
+                });
+            }
+        }
+
+        // Create startup command that sets up the environment
+        let mut steps = vec![format!("mkdir -p {}", workdir), format!("cd {}", workdir)];
+        steps.extend(template.startup_commands.iter().cloned());
+        steps.push("echo 'Vortex dev environment ready!'".to_string());
+        steps.push(match &template.nix {
+            Some(nix) => nix.shell_command(),
+            None => "exec bash".to_string(),
+        });
+        let full_command = steps.join(" && ");
+
+        let mut spec = VmSpec {
+            image: template.base_image.clone(),
+            memory: 2048, // 2GB default for dev environments
+            cpus: 2,      // 2 cores default
+            ports: {
+                let mut parsed_ports = HashMap::new();
+                for p in &template.ports {
+                    let parts: Vec<&str> = p.split(':').collect();
+                    if parts.len() != 2 {
+                        return Err(VortexError::InvalidInput {
+                            field: "ports".to_string(),
+                            message: format!(
+                                "Invalid port mapping format '{}', expected 'host:guest'",
+                                p
+                            ),
+                        });
+                    }
+                    let host: u16 = parts[0].parse().map_err(|_| VortexError::InvalidInput {
+                        field: "ports".to_string(),
+                        message: format!("Invalid host port in '{}'", p),
+                    })?;
+                    let guest: u16 = parts[1].parse().map_err(|_| VortexError::InvalidInput {
+                        field: "ports".to_string(),
+                        message: format!("Invalid guest port in '{}'", p),
+                    })?;
+                    parsed_ports.insert(host, guest);
+                }
+                parsed_ports
+            },
+            volumes: HashMap::new(), // Will be set up by the caller
+            environment: template
+                .environment
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), interpolate::expand(value)?)))
+                .collect::<Result<_>>()?,
+            command: Some(full_command),
+            labels: HashMap::from([
+                ("vortex.dev-env".to_string(), "true".to_string()),
+                ("vortex.template".to_string(), template_name.to_string()),
+            ]),
+            network_config: None,
+            resource_limits: crate::vm::ResourceLimits::default(),
+            backend: None,
+            tuning: template
+                .tuning_profile
+                .as_deref()
+                .map(TuningProfile::resolve)
+                .transpose()?,
+            hooks: crate::vm::LifecycleHooks::default(),
+            health_check: None,
+            ttl_seconds: None,
+            gpus: Vec::new(),
+            provision: None,
+            publish: Vec::new(),
+            tls_ports: HashMap::new(),
+            port_addresses: HashMap::new(),
+            share_mechanisms: HashMap::new(),
+            tmpfs: HashMap::new(),
+            network_policy: NetworkPolicy::Open,
+        };
+
+        if let Some(nix) = &template.nix {
+            nix.apply(&mut spec)?;
+        }
+
+        if template.persist_home {
+            home_volume::apply(&mut spec, template_name)?;
+        }
+
+        Ok(spec)
+    }
+
+    /// Add `template` as `name` and save it to the user template directory,
+    /// so later runs have it too
+    pub fn create_custom_template(&mut self, name: String, template: DevTemplate) -> Result<()> {
+        if self.templates.contains_key(&name) {
+            return Err(VortexError::TemplateExists { name });
+        }
+
+        let template = DevTemplate {
+            name: name.clone(),
+            ..template
+        };
+        let path = save_user_template(&template)?;
+        self.templates.insert(name.clone(), template);
+        self.origins.insert(name, TemplateOrigin::User { path });
+        Ok(())
+    }
+}
+
+/// Extensions of template files, the first being the one new ones get
+const TEMPLATE_EXTENSIONS: [&str; 3] = ["toml", "yaml", "yml"];
+
+/// Directory holding user-defined and overriding dev templates,
+/// `~/.vortex/templates`
+pub fn user_template_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".vortex").join("templates"))
+}
+
+/// Directory holding the templates of the project in `dir`
+pub fn project_template_dir(dir: &Path) -> PathBuf {
+    dir.join(".vortex").join("templates")
+}
+
+/// Path of the user template file for `name`: the existing one, in
+/// whichever format, else a new TOML file
+pub fn user_template_path(name: &str) -> Result<PathBuf> {
+    check_template_name(name)?;
+    let dir = user_template_dir().ok_or_else(|| VortexError::ConfigError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    let paths = TEMPLATE_EXTENSIONS.map(|extension| dir.join(format!("{}.{}", name, extension)));
+    Ok(paths
+        .iter()
+        .find(|path| path.exists())
+        .unwrap_or(&paths[0])
+        .clone())
+}
+
+/// Write `template` to the user template directory, replacing any user
+/// template of the same name
+pub fn save_user_template(template: &DevTemplate) -> Result<PathBuf> {
+    check_template_name(&template.name)?;
+    template.validate()?;
+    let path = user_template_path(&template.name)?;
+    let content = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::to_string_pretty(template).map_err(|e| e.to_string()),
+        _ => serde_yaml::to_string(template).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| VortexError::ConfigError {
+        message: format!("Failed to serialize template: {}", e),
+    })?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Delete the user template `name`, returning the path of its file.
+/// Built-in, plugin and project templates have no file to delete there.
+pub fn remove_user_template(name: &str) -> Result<PathBuf> {
+    let path = user_template_path(name)?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(VortexError::TemplateNotFound {
+            name: name.to_string(),
+        }),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The valid templates of the files in `dir`; invalid ones are warned
+/// about and left out
+fn read_template_dir(dir: &Path) -> Vec<(PathBuf, DevTemplate)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| TEMPLATE_EXTENSIONS.contains(&e))
+        })
+        .collect();
+    paths.sort();
+
+    let mut templates = Vec::new();
+    for path in paths {
+        match read_template_file(&path) {
+            Ok(template) => templates.push((path, template)),
+            Err(e) => tracing::warn!("Ignoring invalid template {}: {}", path.display(), e),
+        }
+    }
+    templates
+}
+
+/// Read and validate the template file at `path`, TOML or YAML by its
+/// extension; the file name must match the template name
+pub fn read_template_file(path: &Path) -> Result<DevTemplate> {
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    check_user_template(name, read_template(path)?)
+}
+
+/// Read the template file at `path`, TOML or YAML by its extension,
+/// without checking it
+pub fn read_template(path: &Path) -> Result<DevTemplate> {
+    let content = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| VortexError::ConfigError {
+        message: format!("Failed to parse template {}: {}", path.display(), e),
+    })
+}
+
+fn check_template_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(VortexError::InvalidInput {
+            field: "template".to_string(),
+            message: format!(
+                "Invalid template name '{}': only alphanumeric, hyphens and underscores are allowed",
+                name
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Parse and validate a user template file; the file name must match the template name
+pub fn parse_user_template(name: &str, content: &str) -> Result<DevTemplate> {
+    let template: DevTemplate = toml::from_str(content).map_err(|e| VortexError::ConfigError {
+        message: format!("Failed to parse template '{}': {}", name, e),
+    })?;
+    check_user_template(name, template)
+}
+
+fn check_user_template(name: &str, template: DevTemplate) -> Result<DevTemplate> {
+    check_template_name(name)?;
+    if template.name != name {
+        return Err(VortexError::InvalidInput {
+            field: "name".to_string(),
+            message: format!(
+                "Template name '{}' does not match file name '{}'",
+                template.name, name
+            ),
+        });
+    }
+
+    template.validate()?;
+    Ok(template)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_template_round_trips_through_toml() {
+        let manager = DevEnvironmentManager::new();
+        let template = manager.get_template("python").unwrap();
+        let content = toml::to_string_pretty(template).unwrap();
+        let parsed = parse_user_template("python", &content).unwrap();
+        assert_eq!(parsed.base_image, template.base_image);
+    }
+
+    #[test]
+    fn test_user_template_validation() {
+        let manager = DevEnvironmentManager::new();
+        let mut template = manager.get_template("rust").unwrap().clone();
+        template.ports.push("not-a-port".to_string());
+        let content = toml::to_string_pretty(&template).unwrap();
+        assert!(parse_user_template("rust", &content).is_err());
+        assert!(parse_user_template("other", &content).is_err());
+    }
+
+    #[test]
+    fn test_plugin_templates_fill_free_names() {
+        let builtin = DevEnvironmentManager::new();
+        let mut phoenix = builtin.get_template("node").unwrap().clone();
+        phoenix.name = "elixir-phoenix".to_string();
+        phoenix.base_image = "elixir:1.16".to_string();
+        let mut python = phoenix.clone();
+        python.name = "python".to_string();
+        let mut invalid = phoenix.clone();
+        invalid.name = "broken".to_string();
+        invalid.base_image = String::new();
+
+        let manager = DevEnvironmentManager::with_plugin_templates(vec![
+            ("beam".to_string(), phoenix.clone()),
+            ("beam".to_string(), python),
+            ("beam".to_string(), invalid),
+            ("other".to_string(), phoenix),
+        ]);
+        assert_eq!(
+            manager.get_template("elixir-phoenix").unwrap().base_image,
+            "elixir:1.16"
+        );
+        assert_eq!(
+            manager.template_origin("elixir-phoenix"),
+            Some(&TemplateOrigin::Plugin {
+                plugin: "beam".to_string()
+            })
+        );
+        assert_eq!(
+            manager.get_template("python").unwrap().base_image,
+            builtin.get_template("python").unwrap().base_image
+        );
+        assert!(manager.get_template("broken").is_none());
+    }
+
+    #[test]
+    fn test_project_templates_are_read_from_yaml() {
+        let project = tempfile::tempdir().unwrap();
+        let dir = project_template_dir(project.path());
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut python = DevEnvironmentManager::new()
+            .get_template("python")
+            .unwrap()
+            .clone();
+        python.base_image = "python:3.13".to_string();
+        std::fs::write(
+            dir.join("python.yaml"),
+            serde_yaml::to_string(&python).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.yml"), "name: other\n").unwrap();
+
+        let manager = DevEnvironmentManager::new().with_project_templates(project.path());
+        assert_eq!(
+            manager.get_template("python").unwrap().base_image,
+            "python:3.13"
+        );
+        assert_eq!(
+            manager.template_origin("python"),
+            Some(&TemplateOrigin::Project {
+                path: dir.join("python.yaml")
+            })
+        );
+        assert!(manager.get_template("broken").is_none());
+    }
+}
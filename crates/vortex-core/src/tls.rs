@@ -0,0 +1,209 @@
+//! HTTPS on forwarded ports, terminated on the host.
+//!
+//! A port mapping marked `tls` (`-p 8443:8000:tls`) accepts TLS on its host
+//! port and passes the plaintext on to the guest port through a second,
+//! internal forward. Certificates cover `localhost`, `*.localhost`,
+//! `127.0.0.1` and `::1` and are issued by a local CA that Vortex creates once under
+//! `~/.vortex/tls`; trusting its `ca.pem` makes clients accept them.
+//!
+//! Connections are served by the process that created the VM: the daemon
+//! for sessions, `vortex run` while it waits for the command.
+
+use crate::error::{Result, VortexError};
+use crate::network;
+use crate::vm::VmSpec;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair,
+    KeyUsagePurpose,
+};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+const CA_CERT: &str = "ca.pem";
+const CA_KEY: &str = "ca-key.pem";
+const CA_NAME: &str = "Vortex local development CA";
+/// Names the certificates of forwarded ports are valid for
+const SERVER_NAMES: [&str; 4] = ["localhost", "*.localhost", "127.0.0.1", "::1"];
+
+fn tls_error(e: impl std::fmt::Display) -> VortexError {
+    VortexError::NetworkError {
+        message: format!("TLS setup failed: {}", e),
+    }
+}
+
+fn tls_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| VortexError::StorageError {
+        message: "Could not determine home directory".to_string(),
+    })?;
+    Ok(home.join(".vortex").join("tls"))
+}
+
+/// The CA certificate to trust for HTTPS on forwarded ports
+pub fn ca_path() -> Result<PathBuf> {
+    Ok(tls_root()?.join(CA_CERT))
+}
+
+/// The local CA, whose key stays under `~/.vortex/tls`
+pub struct LocalCa {
+    key: KeyPair,
+    cert: Certificate,
+}
+
+impl LocalCa {
+    pub fn load_or_create() -> Result<Self> {
+        Self::at(&tls_root()?)
+    }
+
+    /// The CA kept in `root`, created there on first use
+    pub fn at(root: &Path) -> Result<Self> {
+        let key_path = root.join(CA_KEY);
+        let key = match fs::read_to_string(&key_path) {
+            Ok(pem) => KeyPair::from_pem(&pem).map_err(tls_error)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = KeyPair::generate().map_err(tls_error)?;
+                fs::create_dir_all(root)?;
+                write_private(&key_path, &key.serialize_pem())?;
+                key
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // Certificates only need the CA's name and key to chain to it, so
+        // the certificate is recreated rather than parsed
+        let cert = ca_params().self_signed(&key).map_err(tls_error)?;
+        let cert_path = root.join(CA_CERT);
+        if !cert_path.exists() {
+            fs::write(&cert_path, cert.pem())?;
+        }
+        Ok(Self { key, cert })
+    }
+
+    /// Server configuration with a fresh certificate for `SERVER_NAMES`
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>> {
+        let names: Vec<String> = SERVER_NAMES.iter().map(|name| name.to_string()).collect();
+        let key = KeyPair::generate().map_err(tls_error)?;
+        let cert = CertificateParams::new(names)
+            .and_then(|params| params.signed_by(&key, &self.cert, &self.key))
+            .map_err(tls_error)?;
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+            )
+            .map_err(tls_error)?;
+        Ok(Arc::new(config))
+    }
+}
+
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, CA_NAME);
+    params.distinguished_name = name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    params
+}
+
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Accept TLS on the host ports `spec` takes HTTPS on and pass each
+/// connection on to the host port forwarding the same guest port
+pub(crate) async fn serve(spec: &VmSpec) -> Result<Vec<JoinHandle<()>>> {
+    let mut listeners = Vec::new();
+    for (host, guest) in &spec.tls_ports {
+        let upstream = spec
+            .ports
+            .iter()
+            .find(|(_, forwarded)| *forwarded == guest)
+            .map(|(host, _)| spec.host_address(*host))
+            .ok_or_else(|| VortexError::NetworkError {
+                message: format!("Guest port {} is not forwarded", guest),
+            })?;
+        let listener = network::listen(spec.port_addresses.get(host).copied(), *host)
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                TcpListener::from_std(listener)
+            })
+            .map_err(|e| VortexError::NetworkError {
+                message: format!("Failed to listen for HTTPS on port {}: {}", host, e),
+            })?;
+        listeners.push((listener, upstream));
+    }
+
+    let acceptor = TlsAcceptor::from(LocalCa::load_or_create()?.server_config()?);
+    let mut tasks = Vec::new();
+    for (listener, upstream) in listeners {
+        let acceptor = acceptor.clone();
+        tasks.push(tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = relay(acceptor, client, upstream).await {
+                        tracing::debug!("HTTPS connection to {} failed: {}", upstream, e);
+                    }
+                });
+            }
+        }));
+    }
+    Ok(tasks)
+}
+
+async fn relay(
+    acceptor: TlsAcceptor,
+    client: TcpStream,
+    upstream: SocketAddr,
+) -> std::io::Result<()> {
+    let mut client = acceptor.accept(client).await?;
+    let mut upstream = TcpStream::connect(upstream).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_ca_is_created_once_and_issues_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCa::at(dir.path()).unwrap();
+        ca.server_config().unwrap();
+        let key = fs::read_to_string(dir.path().join(CA_KEY)).unwrap();
+        let cert = fs::read_to_string(dir.path().join(CA_CERT)).unwrap();
+        assert!(cert.starts_with("-----BEGIN CERTIFICATE-----"));
+
+        // Loading again keeps the key users were told to trust
+        LocalCa::at(dir.path()).unwrap().server_config().unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join(CA_KEY)).unwrap(), key);
+        assert_eq!(fs::read_to_string(dir.path().join(CA_CERT)).unwrap(), cert);
+    }
+}
@@ -0,0 +1,264 @@
+//! Copying files in and out of running VMs over `Backend::exec`, for
+//! backends without a file transfer of their own.
+//!
+//! What is copied travels as a tar archive encoded in base64, so it
+//! survives text-only channels such as a serial console. Going in, the
+//! archive is appended to a file in the guest a chunk per exec, kept small
+//! enough for the console's line limit, and unpacked once complete; coming
+//! out, the guest prints it as one command's output. The guest needs `tar`
+//! and `base64`, which busybox has.
+
+use crate::backend::{sh_quote, Backend, ExecOptions};
+use crate::error::{Result, VortexError};
+use crate::vm::VmInstance;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::fs;
+use std::path::Path;
+
+/// Base64 characters sent per exec, under the 4 KiB a console line holds
+const PUSH_CHUNK: usize = 2048;
+
+/// Copy the host file or directory `host` to the path `guest`, or into it
+/// when it is a directory
+pub(crate) async fn push<B: Backend + ?Sized>(
+    backend: &B,
+    vm: &VmInstance,
+    host: &Path,
+    guest: &str,
+) -> Result<()> {
+    let name = entry_name(host)?;
+    let encoded = STANDARD.encode(pack(host, &name)?);
+    let staging = format!(
+        "/tmp/.vortex-cp-{}",
+        &uuid::Uuid::new_v4().simple().to_string()[..12]
+    );
+    run(backend, vm, &format!(": > {}", staging)).await?;
+    for chunk in encoded.as_bytes().chunks(PUSH_CHUNK) {
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        run(
+            backend,
+            vm,
+            &format!("printf %s '{}' >> {}", chunk, staging),
+        )
+        .await?;
+    }
+    let dest = sh_quote(guest);
+    let unpacked = run(
+        backend,
+        vm,
+        &format!(
+            "if [ -d {dest} ]; then base64 -d {staging} | tar -xf - -C {dest}; \
+             else mkdir -p \"$(dirname {dest})\" && \
+             t=$(mktemp -d \"$(dirname {dest})/.vortex-cp.XXXXXX\") && \
+             base64 -d {staging} | tar -xf - -C \"$t\" && mv \"$t\"/{name} {dest}; \
+             c=$?; rm -rf \"$t\"; exit $c; fi",
+            name = sh_quote(&name),
+        ),
+    )
+    .await;
+    let _ = run(backend, vm, &format!("rm -f {}", staging)).await;
+    unpacked.map(drop)
+}
+
+/// Copy the guest file or directory `guest` to the host path `host`, or
+/// into it when it is a directory
+pub(crate) async fn pull<B: Backend + ?Sized>(
+    backend: &B,
+    vm: &VmInstance,
+    guest: &str,
+    host: &Path,
+) -> Result<()> {
+    let guest = guest.trim_end_matches('/');
+    let (parent, name) = match guest.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => (".", guest),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(invalid_path(guest));
+    }
+    let output = run(
+        backend,
+        vm,
+        &format!(
+            "[ -e {path} ] || {{ echo \"No such file or directory: \"{path} >&2; exit 1; }}; \
+             tar -cf - -C {parent} {name} | base64",
+            path = sh_quote(guest),
+            parent = sh_quote(parent),
+            name = sh_quote(name)
+        ),
+    )
+    .await?;
+    let encoded: String = output.split_whitespace().collect();
+    let archive = STANDARD
+        .decode(encoded)
+        .map_err(|e| transfer_error(format!("Corrupt archive from the guest: {}", e)))?;
+
+    let dest = if host.is_dir() {
+        host.join(name)
+    } else {
+        host.to_path_buf()
+    };
+    let parent = match dest.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => std::env::current_dir()?,
+    };
+    let staging = parent.join(format!(
+        ".vortex-cp-{}",
+        &uuid::Uuid::new_v4().simple().to_string()[..12]
+    ));
+    let unpacked = tar::Archive::new(archive.as_slice())
+        .unpack(&staging)
+        .and_then(|()| {
+            if dest.is_dir() {
+                fs::remove_dir_all(&dest)?;
+            }
+            fs::rename(staging.join(name), &dest)
+        });
+    let _ = fs::remove_dir_all(&staging);
+    Ok(unpacked?)
+}
+
+/// Tar archive of `path` holding it as `name`
+fn pack(path: &Path, name: &str) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    if path.is_dir() {
+        builder.append_dir_all(name, path)?;
+    } else {
+        builder.append_path_with_name(path, name)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn entry_name(path: &Path) -> Result<String> {
+    path.canonicalize()?
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| invalid_path(&path.display().to_string()))
+}
+
+/// Run `script` in the guest, failing unless it exits 0
+async fn run<B: Backend + ?Sized>(backend: &B, vm: &VmInstance, script: &str) -> Result<String> {
+    let command = ["sh".to_string(), "-c".to_string(), script.to_string()];
+    let result = backend.exec(vm, &command, &ExecOptions::default()).await?;
+    if !result.success() {
+        return Err(transfer_error(format!(
+            "Copying in {} failed: {}",
+            vm.id,
+            result.stderr.trim()
+        )));
+    }
+    Ok(result.stdout)
+}
+
+fn invalid_path(path: &str) -> VortexError {
+    VortexError::InvalidInput {
+        field: "path".to_string(),
+        message: format!("Cannot copy {}: name a file or directory", path),
+    }
+}
+
+fn transfer_error(message: String) -> VortexError {
+    VortexError::VmError { message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ExecResult, VmMetrics};
+    use crate::vm::{VmSpec, VmState};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    /// A "guest" that is a shell on the host
+    #[derive(Debug)]
+    struct HostShell;
+
+    #[async_trait]
+    impl Backend for HostShell {
+        async fn create(&self, _vm: &VmInstance) -> Result<()> {
+            Ok(())
+        }
+        async fn start(&self, _vm: &VmInstance) -> Result<()> {
+            Ok(())
+        }
+        async fn stop(&self, _vm: &VmInstance) -> Result<()> {
+            Ok(())
+        }
+        async fn cleanup(&self, _vm: &VmInstance) -> Result<()> {
+            Ok(())
+        }
+        async fn attach(&self, _vm: &VmInstance) -> Result<()> {
+            Ok(())
+        }
+        async fn get_metrics(&self, _vm: &VmInstance) -> Result<VmMetrics> {
+            Ok(VmMetrics::default())
+        }
+        async fn list_vms(&self) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+        async fn is_available(&self) -> Result<bool> {
+            Ok(true)
+        }
+        fn name(&self) -> &'static str {
+            "host-shell"
+        }
+        async fn exec(
+            &self,
+            _vm: &VmInstance,
+            command: &[String],
+            _options: &ExecOptions,
+        ) -> Result<ExecResult> {
+            let output = tokio::process::Command::new(&command[0])
+                .args(&command[1..])
+                .output()
+                .await?;
+            Ok(ExecResult::from_output(&output))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_files_round_trip_through_exec() {
+        let dir = tempfile::tempdir().unwrap();
+        let vm = VmInstance {
+            id: "vortex-1".into(),
+            spec: VmSpec::default(),
+            state: VmState::Running,
+            backend: Arc::new(HostShell),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        // Several chunks' worth
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(src.join("nested/data.bin"), &big).unwrap();
+        let guest = dir.path().join("guest");
+        fs::create_dir(&guest).unwrap();
+
+        // Into an existing directory, then to a new path
+        push(&HostShell, &vm, &src, guest.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(fs::read(guest.join("src/nested/data.bin")).unwrap(), big);
+        let renamed = guest.join("deeper/app");
+        push(&HostShell, &vm, &src, renamed.to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(renamed.join("nested/data.bin").is_file());
+
+        let back = dir.path().join("back.bin");
+        let guest_file = guest.join("src/nested/data.bin");
+        pull(&HostShell, &vm, guest_file.to_str().unwrap(), &back)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&back).unwrap(), big);
+        let missing = guest.join("missing");
+        assert!(pull(&HostShell, &vm, missing.to_str().unwrap(), &back)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Guest kernel tuning profiles.
+//!
+//! A profile bundles sysctls, transparent hugepage mode and ulimits that are
+//! applied inside the guest at boot, before the user command runs.
+
+use crate::error::{Result, VortexError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThpMode {
+    Always,
+    Madvise,
+    Never,
+}
+
+impl ThpMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ThpMode::Always => "always",
+            ThpMode::Madvise => "madvise",
+            ThpMode::Never => "never",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TuningProfile {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub sysctls: BTreeMap<String, String>,
+    #[serde(default)]
+    pub transparent_hugepages: Option<ThpMode>,
+    /// Resource name (nofile, nproc, memlock, stack, core) -> soft limit
+    #[serde(default)]
+    pub ulimits: BTreeMap<String, u64>,
+}
+
+impl TuningProfile {
+    /// Look up a built-in profile by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        builtin_profiles().into_iter().find(|p| p.name == name)
+    }
+
+    /// Resolve a profile name, returning an error listing valid names if unknown
+    pub fn resolve(name: &str) -> Result<Self> {
+        Self::builtin(name).ok_or_else(|| VortexError::InvalidInput {
+            field: "tuning".to_string(),
+            message: format!(
+                "Unknown tuning profile '{}'. Available: {}",
+                name,
+                builtin_profiles()
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        })
+    }
+
+    /// Reject keys and values that could escape the generated boot script
+    pub fn validate(&self) -> Result<()> {
+        for (key, value) in &self.sysctls {
+            let key_ok = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+            let value_ok = value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '.' | '_' | '-'));
+            if !key_ok || !value_ok {
+                return Err(VortexError::InvalidInput {
+                    field: "tuning.sysctls".to_string(),
+                    message: format!("Invalid sysctl '{}={}'", key, value),
+                });
+            }
+        }
+
+        for resource in self.ulimits.keys() {
+            if ulimit_flag(resource).is_none() {
+                return Err(VortexError::InvalidInput {
+                    field: "tuning.ulimits".to_string(),
+                    message: format!(
+                        "Unsupported ulimit '{}'. Use nofile, nproc, memlock, stack or core",
+                        resource
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render the profile as a best-effort guest shell prelude.
+    ///
+    /// Failures are ignored so that images without sysctl or a writable
+    /// /sys still boot.
+    pub fn boot_script(&self) -> String {
+        let mut script = String::new();
+
+        for (key, value) in &self.sysctls {
+            script.push_str(&format!("sysctl -w {}='{}' >/dev/null 2>&1; ", key, value));
+        }
+
+        if let Some(mode) = self.transparent_hugepages {
+            script.push_str(&format!(
+                "echo {} > /sys/kernel/mm/transparent_hugepage/enabled 2>/dev/null; ",
+                mode.as_str()
+            ));
+        }
+
+        for (resource, limit) in &self.ulimits {
+            if let Some(flag) = ulimit_flag(resource) {
+                script.push_str(&format!("ulimit -{} {} 2>/dev/null; ", flag, limit));
+            }
+        }
+
+        script
+    }
+}
+
+fn ulimit_flag(resource: &str) -> Option<char> {
+    match resource {
+        "nofile" => Some('n'),
+        "nproc" => Some('u'),
+        "memlock" => Some('l'),
+        "stack" => Some('s'),
+        "core" => Some('c'),
+        _ => None,
+    }
+}
+
+/// Built-in tuning profiles
+pub fn builtin_profiles() -> Vec<TuningProfile> {
+    vec![
+        TuningProfile {
+            name: "database".to_string(),
+            description: "Low swappiness, no THP, high file limits for database engines"
+                .to_string(),
+            sysctls: BTreeMap::from([
+                ("vm.swappiness".to_string(), "1".to_string()),
+                ("vm.dirty_ratio".to_string(), "15".to_string()),
+                ("vm.dirty_background_ratio".to_string(), "5".to_string()),
+                ("vm.overcommit_memory".to_string(), "1".to_string()),
+                ("net.core.somaxconn".to_string(), "4096".to_string()),
+            ]),
+            transparent_hugepages: Some(ThpMode::Never),
+            ulimits: BTreeMap::from([("nofile".to_string(), 65536), ("nproc".to_string(), 32768)]),
+        },
+        TuningProfile {
+            name: "build".to_string(),
+            description: "Many open files and inotify watches for compilers and bundlers"
+                .to_string(),
+            sysctls: BTreeMap::from([
+                ("fs.inotify.max_user_watches".to_string(), "524288".to_string()),
+                ("fs.file-max".to_string(), "1048576".to_string()),
+                ("vm.swappiness".to_string(), "10".to_string()),
+            ]),
+            transparent_hugepages: Some(ThpMode::Madvise),
+            ulimits: BTreeMap::from([("nofile".to_string(), 1048576)]),
+        },
+        TuningProfile {
+            name: "latency-sensitive".to_string(),
+            description: "Avoid swap and THP compaction stalls, tighter network buffers"
+                .to_string(),
+            sysctls: BTreeMap::from([
+                ("vm.swappiness".to_string(), "0".to_string()),
+                ("kernel.numa_balancing".to_string(), "0".to_string()),
+                ("net.ipv4.tcp_low_latency".to_string(), "1".to_string()),
+                ("net.core.busy_poll".to_string(), "50".to_string()),
+            ]),
+            transparent_hugepages: Some(ThpMode::Never),
+            ulimits: BTreeMap::from([("memlock".to_string(), 65536)]),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_profiles_validate() {
+        for profile in builtin_profiles() {
+            profile.validate().unwrap();
+            assert!(!profile.boot_script().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_rejects_injected_sysctl() {
+        let mut profile = TuningProfile::resolve("database").unwrap();
+        profile
+            .sysctls
+            .insert("vm.swappiness".to_string(), "1; reboot".to_string());
+        assert!(profile.validate().is_err());
+    }
+}
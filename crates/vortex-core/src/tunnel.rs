@@ -0,0 +1,385 @@
+//! WireGuard tunnels into private networks.
+//!
+//! `vortex network tunnel <name>` brings up a WireGuard interface on the
+//! host, `vxwg<n>` for the network on `10.89.<n>.0/24`, and adds a peer:
+//! a teammate's machine that brings up its end with the `wg-quick`
+//! configuration it is given. Peers get addresses on `10.90.<n>.0/24` and
+//! reach the network's members through the host, which masquerades them,
+//! so members need no route back. That takes a host with a route to the
+//! members, as container engine networks give it; QEMU members share a
+//! segment the host has no interface on, so peers cannot reach them.
+//!
+//! Setting the interface up needs root and the `wg`, `ip` and `iptables`
+//! tools. The host's private key is kept next to the network's file, and
+//! peers' private keys are only in the configuration they are given.
+
+use crate::backend::sh_quote;
+use crate::error::{Result, VortexError};
+use crate::network::{NetworkConfig, NetworkManager};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// First two octets of every tunnel's subnet
+const TUNNEL_BASE: [u8; 2] = [10, 90];
+/// Host number of the host's own end of a tunnel
+const HOST_END: u8 = 1;
+const INTERFACE_PREFIX: &str = "vxwg";
+/// UDP port tunnels listen on unless told otherwise
+pub const DEFAULT_LISTEN_PORT: u16 = 51820;
+/// Keeps the peer's NAT mapping open while it is idle
+const KEEPALIVE_SECONDS: u32 = 25;
+
+/// The tunnel of a network, kept with the network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tunnel {
+    /// UDP port the host's end listens on
+    pub listen_port: u16,
+    /// Public key of the host's end
+    pub public_key: String,
+    /// Peers by name
+    #[serde(default)]
+    pub peers: BTreeMap<String, TunnelPeer>,
+}
+
+/// A machine on the other end of a tunnel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunnelPeer {
+    pub address: String,
+    pub public_key: String,
+}
+
+fn tunnel_error(message: String) -> VortexError {
+    VortexError::NetworkError { message }
+}
+
+/// Name of the host interface of network number `number`'s tunnel
+pub fn interface(number: u8) -> String {
+    format!("{}{}", INTERFACE_PREFIX, number)
+}
+
+fn tunnel_address(number: u8, host: u8) -> String {
+    let [a, b] = TUNNEL_BASE;
+    format!("{}.{}.{}.{}", a, b, number, host)
+}
+
+fn tunnel_subnet(number: u8) -> String {
+    format!("{}/24", tunnel_address(number, 0))
+}
+
+fn validate_peer(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(VortexError::InvalidInput {
+            field: "peer".to_string(),
+            message: format!(
+                "Invalid peer name '{}': use up to 32 lowercase letters, digits, '-' or '_'",
+                name
+            ),
+        })
+    }
+}
+
+/// Shell script bringing up the interface of network number `number` with
+/// `tunnel`'s peers, and routing them into the network. Safe to run again,
+/// e.g. after a reboot took the interface down.
+fn up_script(number: u8, subnet: &str, tunnel: &Tunnel, key: &Path) -> String {
+    let interface = interface(number);
+    let mut peers = String::new();
+    for peer in tunnel.peers.values() {
+        peers.push_str(&format!(
+            " peer {} allowed-ips {}/32",
+            sh_quote(&peer.public_key),
+            peer.address
+        ));
+    }
+    let masquerade = format!(
+        "POSTROUTING -s {} -d {} -j MASQUERADE",
+        tunnel_subnet(number),
+        subnet
+    );
+    [
+        format!(
+            "ip link show {0} >/dev/null 2>&1 || ip link add {0} type wireguard",
+            interface
+        ),
+        format!(
+            "wg set {} listen-port {} private-key {}{}",
+            interface,
+            tunnel.listen_port,
+            sh_quote(&key.to_string_lossy()),
+            peers
+        ),
+        format!(
+            "ip addr replace {}/24 dev {}",
+            tunnel_address(number, HOST_END),
+            interface
+        ),
+        format!("ip link set {} up", interface),
+        "sysctl -qw net.ipv4.ip_forward=1".to_string(),
+        format!(
+            "{{ iptables -t nat -C {0} 2>/dev/null || iptables -t nat -A {0}; }}",
+            masquerade
+        ),
+    ]
+    .join("\n")
+}
+
+fn down_script(number: u8, subnet: &str) -> String {
+    format!(
+        "ip link del {} 2>/dev/null || true\n\
+         iptables -t nat -D POSTROUTING -s {} -d {} -j MASQUERADE 2>/dev/null || true",
+        interface(number),
+        tunnel_subnet(number),
+        subnet
+    )
+}
+
+/// `wg-quick` configuration of the peer at `address` with `private_key`,
+/// reaching the host at `endpoint` (a host name or address)
+fn peer_config(
+    network: &NetworkConfig,
+    tunnel: &Tunnel,
+    address: &str,
+    private_key: &str,
+    endpoint: &str,
+) -> String {
+    // IPv6 endpoints are bracketed before the port
+    let endpoint = if endpoint.contains(':') && !endpoint.starts_with('[') {
+        format!("[{}]", endpoint)
+    } else {
+        endpoint.to_string()
+    };
+    format!(
+        "# Vortex network {}\n\
+         [Interface]\n\
+         PrivateKey = {}\n\
+         Address = {}/32\n\
+         \n\
+         [Peer]\n\
+         PublicKey = {}\n\
+         Endpoint = {}:{}\n\
+         AllowedIPs = {}\n\
+         PersistentKeepalive = {}\n",
+        network.name,
+        private_key,
+        address,
+        tunnel.public_key,
+        endpoint,
+        tunnel.listen_port,
+        network.subnet,
+        KEEPALIVE_SECONDS
+    )
+}
+
+/// Run `wg` with `args`, feeding it `input`, and return its output
+async fn wg(args: &[&str], input: Option<&str>) -> Result<String> {
+    let mut child = Command::new("wg")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| tunnel_error(format!("Failed to run wg (wireguard-tools): {}", e)))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(tunnel_error(format!(
+            "wg {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A fresh key pair, private key first
+async fn key_pair() -> Result<(String, String)> {
+    let private = wg(&["genkey"], None).await?;
+    let public = wg(&["pubkey"], Some(&private)).await?;
+    Ok((private, public))
+}
+
+async fn run_script(script: &str) -> Result<()> {
+    let output = Command::new("sh").args(["-ec", script]).output().await?;
+    if !output.status.success() {
+        return Err(tunnel_error(format!(
+            "Failed to set up the tunnel (it needs root): {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+impl NetworkManager {
+    fn tunnel_key_path(&self, name: &str) -> PathBuf {
+        self.path(name).with_extension("wg-key")
+    }
+
+    /// Bring up the tunnel of network `name` on UDP `listen_port`, with the
+    /// peers it already has, and add the peer `peer` (`peer-<n>` when
+    /// `None`) reaching this host at `endpoint`. Returns the new peer's
+    /// `wg-quick` configuration.
+    pub async fn open_tunnel(
+        &self,
+        name: &str,
+        listen_port: u16,
+        peer: Option<&str>,
+        endpoint: &str,
+    ) -> Result<String> {
+        self.require_manage()?;
+        let mut network = self
+            .get_network(name)
+            .await?
+            .ok_or_else(|| tunnel_error(format!("Network {} does not exist", name)))?;
+        let number = network.number().unwrap_or(0);
+        let key_path = self.tunnel_key_path(name);
+
+        let mut tunnel = match network.tunnel.take() {
+            Some(tunnel) => tunnel,
+            None => {
+                let (private, public) = key_pair().await?;
+                // A key left by a tunnel that was never saved is replaced
+                let _ = fs::remove_file(&key_path);
+                write_private(&key_path, &format!("{}\n", private))?;
+                Tunnel {
+                    listen_port,
+                    public_key: public,
+                    peers: BTreeMap::new(),
+                }
+            }
+        };
+        tunnel.listen_port = listen_port;
+
+        let host = (HOST_END + 1..=254)
+            .find(|host| {
+                let address = tunnel_address(number, *host);
+                !tunnel.peers.values().any(|peer| peer.address == address)
+            })
+            .ok_or_else(|| tunnel_error(format!("The tunnel of network {} is full", name)))?;
+        let peer = match peer {
+            Some(peer) => peer.to_string(),
+            None => format!("peer-{}", host - HOST_END),
+        };
+        validate_peer(&peer)?;
+        if tunnel.peers.contains_key(&peer) {
+            return Err(tunnel_error(format!(
+                "The tunnel of network {} already has a peer {}",
+                name, peer
+            )));
+        }
+
+        let (private, public) = key_pair().await?;
+        let address = tunnel_address(number, host);
+        tunnel.peers.insert(
+            peer,
+            TunnelPeer {
+                address: address.clone(),
+                public_key: public,
+            },
+        );
+        run_script(&up_script(number, &network.subnet, &tunnel, &key_path)).await?;
+
+        let config = peer_config(&network, &tunnel, &address, &private, endpoint);
+        network.tunnel = Some(tunnel);
+        self.save(&network)?;
+        Ok(config)
+    }
+
+    /// Take the tunnel of network `name` down and forget its peers
+    pub async fn close_tunnel(&self, name: &str) -> Result<()> {
+        self.require_manage()?;
+        let mut network = self
+            .get_network(name)
+            .await?
+            .ok_or_else(|| tunnel_error(format!("Network {} does not exist", name)))?;
+        if network.tunnel.take().is_none() {
+            return Err(tunnel_error(format!("Network {} has no tunnel", name)));
+        }
+        run_script(&down_script(network.number().unwrap_or(0), &network.subnet)).await?;
+        self.save(&network)?;
+        match fs::remove_file(self.tunnel_key_path(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::NetworkLimits;
+
+    #[tokio::test]
+    async fn test_tunnel_routes_peers_into_the_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = NetworkManager::at(dir.path().to_path_buf());
+        manager
+            .create_network("first", NetworkLimits::default())
+            .await
+            .unwrap();
+        let network = manager
+            .create_network("backend", NetworkLimits::default())
+            .await
+            .unwrap();
+        let mut tunnel = Tunnel {
+            listen_port: DEFAULT_LISTEN_PORT,
+            public_key: "host-key=".to_string(),
+            peers: BTreeMap::new(),
+        };
+        tunnel.peers.insert(
+            "alice".to_string(),
+            TunnelPeer {
+                address: tunnel_address(2, 2),
+                public_key: "alice-key=".to_string(),
+            },
+        );
+
+        let script = up_script(2, &network.subnet, &tunnel, Path::new("/k/backend.wg-key"));
+        assert!(script.contains("ip link add vxwg2 type wireguard"));
+        assert!(script.contains(
+            "wg set vxwg2 listen-port 51820 private-key '/k/backend.wg-key' \
+             peer 'alice-key=' allowed-ips 10.90.2.2/32"
+        ));
+        assert!(script.contains("ip addr replace 10.90.2.1/24 dev vxwg2"));
+        assert!(script.contains("POSTROUTING -s 10.90.2.0/24 -d 10.89.2.0/24 -j MASQUERADE"));
+
+        let config = peer_config(&network, &tunnel, "10.90.2.2", "alice-private=", "::1");
+        assert!(config.contains("PrivateKey = alice-private=\nAddress = 10.90.2.2/32\n"));
+        assert!(config.contains("PublicKey = host-key=\nEndpoint = [::1]:51820\n"));
+        assert!(config.contains("AllowedIPs = 10.89.2.0/24\n"));
+        assert!(validate_peer("Alice").is_err());
+    }
+}
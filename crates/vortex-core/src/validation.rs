@@ -0,0 +1,916 @@
+//! Checks of the config file and of a project's `vortex.yaml` and
+//! `.vortex-dev.yaml` that point at the line of each problem.
+//!
+//! Besides what fails to parse, keys no setting reads are reported with the
+//! closest known key, port mappings and volumes are parsed, and the dev
+//! templates named by pool targets and dev setups must exist:
+//!
+//! ```text
+//! ~/.config/vortex/config.toml:14:1: pool[0].template: unknown dev template 'pyhton' (did you mean `python`?)
+//! ~/shop/vortex.yaml:4:5: services.api.imgae: unknown key (did you mean `image`?)
+//! ```
+//!
+//! `vortex config validate` prints all of them. Loading a file fails with
+//! the first error that keeps it from parsing and only warns about unknown
+//! keys.
+
+use crate::config::VortexConfig;
+use crate::dev_project::{parse_port, DevProject};
+use crate::error::{Result, VortexError};
+use crate::interpolate;
+use crate::project::{parse_volume, ProjectConfig, ServiceConfig};
+use crate::templates::DevEnvironmentManager;
+use crate::tuning::TuningProfile;
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+/// One problem found in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// 1-based line and column, when they could be found
+    pub location: Option<(usize, usize)>,
+    /// Dotted path of the setting, e.g. `pool[0].template`; empty for
+    /// files that do not parse
+    pub key: String,
+    pub message: String,
+    /// What was probably meant
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some((line, column)) = self.location {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        f.write_str(": ")?;
+        if !self.key.is_empty() {
+            write!(f, "{}: ", self.key)?;
+        }
+        f.write_str(&self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Diagnostic> for VortexError {
+    fn from(diagnostic: Diagnostic) -> Self {
+        VortexError::ConfigError {
+            message: diagnostic.to_string(),
+        }
+    }
+}
+
+/// Check the config file `file` holding `content`
+pub fn check_config(
+    file: &Path,
+    content: &str,
+    templates: &DevEnvironmentManager,
+) -> Vec<Diagnostic> {
+    let (config, mut report) = match Report::parse::<VortexConfig>(file, content, Format::Toml) {
+        Ok(parsed) => parsed,
+        Err(diagnostic) => return vec![diagnostic],
+    };
+    if let Err(e) = interpolate::expand_values(config.clone()) {
+        report.add(&[], reason(e), None);
+    }
+    let config_templates: BTreeMap<_, _> = config.templates.iter().collect();
+    for (name, template) in config_templates {
+        for (index, port) in template.ports.iter().enumerate() {
+            if !is_template_port(port) {
+                report.add(
+                    &[
+                        key("templates"),
+                        key(name),
+                        key("ports"),
+                        Segment::Index(index),
+                    ],
+                    format!(
+                        "invalid port mapping '{}', expected [address:]host:guest[:tls]",
+                        port
+                    ),
+                    None,
+                );
+            }
+        }
+        if let Some(Err(e)) = template.tuning.as_deref().map(TuningProfile::resolve) {
+            report.add(
+                &[key("templates"), key(name), key("tuning")],
+                reason(e),
+                None,
+            );
+        }
+    }
+    for (index, target) in config.pool.iter().enumerate() {
+        match (&target.image, &target.template) {
+            (None, Some(template)) => report.check_template(
+                &[key("pool"), Segment::Index(index), key("template")],
+                template,
+                templates,
+            ),
+            (Some(_), None) => {}
+            _ => report.add(
+                &[key("pool"), Segment::Index(index)],
+                "needs either an image or a template",
+                None,
+            ),
+        }
+    }
+    for (index, rule) in config.rules.iter().enumerate() {
+        if let Err(e) = rule.validate() {
+            report.add(&[key("rules"), Segment::Index(index)], reason(e), None);
+        }
+    }
+    for (index, alert) in config.alerts.iter().enumerate() {
+        if let Err(e) = alert.validate() {
+            report.add(&[key("alerts"), Segment::Index(index)], reason(e), None);
+        }
+    }
+    report.finish()
+}
+
+/// Check the `vortex.yaml` and `.vortex-dev.yaml` in `dir`, where they exist
+pub fn check_project(dir: &Path, templates: &DevEnvironmentManager) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+
+    let path = ProjectConfig::path(dir);
+    if let Some(content) = read_if_exists(&path)? {
+        match Report::parse::<ProjectConfig>(&path, &content, Format::Yaml) {
+            Ok((project, mut report)) => {
+                if let Err(e) = interpolate::expand_values(project.clone()) {
+                    report.add(&[], reason(e), None);
+                }
+                for (name, service) in &project.services {
+                    report.check_service(&[key("services"), key(name)], service);
+                }
+                if !project.config.is_null() {
+                    report.check_config_section(&[key("config")], &project.config);
+                }
+                for (context_name, context) in &project.contexts {
+                    for (name, service) in &context.services {
+                        let at = [
+                            key("contexts"),
+                            key(context_name),
+                            key("services"),
+                            key(name),
+                        ];
+                        if !project.services.contains_key(name) {
+                            let names = project.services.keys().map(String::as_str);
+                            report.add(&at, "unknown service", closest(name, names));
+                        }
+                        report.check_service(&at, service);
+                    }
+                }
+                diagnostics.extend(report.finish());
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    let path = DevProject::path(dir);
+    if let Some(content) = read_if_exists(&path)? {
+        match Report::parse::<DevProject>(&path, &content, Format::Yaml) {
+            Ok((project, mut report)) => {
+                report.check_template(&[key("template")], &project.template, templates);
+                for (index, port) in project.ports.iter().flatten().enumerate() {
+                    if let Err(e) = parse_port(port) {
+                        report.add(&[key("ports"), Segment::Index(index)], reason(e), None);
+                    }
+                }
+                diagnostics.extend(report.finish());
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    Ok(diagnostics)
+}
+
+/// Parse the TOML `content` of `file`, along with the keys nothing read
+pub(crate) fn parse_toml<T: DeserializeOwned + Serialize>(
+    file: &Path,
+    content: &str,
+) -> std::result::Result<(T, Vec<Diagnostic>), Diagnostic> {
+    Report::parse(file, content, Format::Toml).map(|(value, report)| (value, report.finish()))
+}
+
+/// Parse the YAML `content` of `file`, along with the keys nothing read
+pub(crate) fn parse_yaml<T: DeserializeOwned + Serialize>(
+    file: &Path,
+    content: &str,
+) -> std::result::Result<(T, Vec<Diagnostic>), Diagnostic> {
+    Report::parse(file, content, Format::Yaml).map(|(value, report)| (value, report.finish()))
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The message of a validation error, without the field it names
+fn reason(error: VortexError) -> String {
+    match error {
+        VortexError::InvalidInput { message, .. } | VortexError::ConfigError { message } => message,
+        error => error.to_string(),
+    }
+}
+
+/// Whether `port` has the `[address:]host:guest[:tls]` form `vortex run
+/// --port` and config templates take
+fn is_template_port(port: &str) -> bool {
+    let rest = match port.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once("]:") {
+            Some((address, rest)) if address.parse::<Ipv6Addr>().is_ok() => rest,
+            _ => return false,
+        },
+        None => port,
+    };
+    let mut parts: Vec<&str> = rest.split(':').collect();
+    if parts.len() > 2 && parts.last() == Some(&"tls") {
+        parts.pop();
+    }
+    if parts.len() == 3 && rest == port && parts[0].parse::<Ipv4Addr>().is_ok() {
+        parts.remove(0);
+    }
+    parts.len() == 2 && parts.iter().all(|part| part.parse::<u16>().is_ok())
+}
+
+/// The candidate closest to `name`, if any is close enough to be a typo
+fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Toml,
+    Yaml,
+}
+
+/// A step of the path to a setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn key(name: &str) -> Segment {
+    Segment::Key(name.to_string())
+}
+
+fn key_path(segments: &[Segment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
+
+fn segments(path: &serde_ignored::Path) -> Vec<Segment> {
+    match path {
+        serde_ignored::Path::Root => Vec::new(),
+        serde_ignored::Path::Seq { parent, index } => {
+            let mut segments = segments(parent);
+            segments.push(Segment::Index(*index));
+            segments
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            let mut segments = segments(parent);
+            segments.push(Segment::Key(key.clone()));
+            segments
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => segments(parent),
+    }
+}
+
+/// The problems found in one parsed file
+struct Report<'a> {
+    file: &'a Path,
+    content: &'a str,
+    format: Format,
+    /// The TOML file with the spans of its keys and values
+    document: Option<toml_edit::ImDocument<&'a str>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Report<'a> {
+    /// Parse `content`, reporting each key nothing read with the closest
+    /// one that would have been
+    fn parse<T: DeserializeOwned + Serialize>(
+        file: &'a Path,
+        content: &'a str,
+        format: Format,
+    ) -> std::result::Result<(T, Self), Diagnostic> {
+        let failed = |location, message: &str| Diagnostic {
+            file: file.to_path_buf(),
+            location,
+            key: String::new(),
+            message: message.to_string(),
+            suggestion: None,
+        };
+        let mut ignored = Vec::new();
+        let mut callback = |path: serde_ignored::Path| ignored.push(segments(&path));
+        let (value, document) = match format {
+            Format::Toml => {
+                let value: T =
+                    serde_ignored::deserialize(toml::Deserializer::new(content), &mut callback)
+                        .map_err(|e| {
+                            let location =
+                                e.span().map(|span| offset_location(content, span.start));
+                            failed(location, e.message())
+                        })?;
+                (value, toml_edit::ImDocument::parse(content).ok())
+            }
+            Format::Yaml => {
+                let deserializer = serde_yaml::Deserializer::from_str(content);
+                let value: T =
+                    serde_ignored::deserialize(deserializer, &mut callback).map_err(|e| {
+                        let message = e.to_string();
+                        // The location is given on its own
+                        let message = message.split(" at line ").next().unwrap_or_default();
+                        failed(e.location().map(|l| (l.line(), l.column())), message)
+                    })?;
+                (value, None)
+            }
+        };
+
+        let mut report = Self {
+            file,
+            content,
+            format,
+            document,
+            diagnostics: Vec::new(),
+        };
+        report.add_unknown(&[], &value, ignored);
+        Ok((value, report))
+    }
+
+    /// Report each of the `ignored` keys of `value`, which is at `at`, with
+    /// the closest one that would have been read
+    fn add_unknown<T: Serialize>(
+        &mut self,
+        at: &[Segment],
+        value: &T,
+        ignored: Vec<Vec<Segment>>,
+    ) {
+        let known = value.serialize(KeySerializer).unwrap_or_default();
+        for path in ignored {
+            let Some((Segment::Key(name), parent)) = path.split_last() else {
+                continue;
+            };
+            let suggestion = known
+                .get(parent)
+                .and_then(|keys| closest(name, keys.names()));
+            self.add(&[at, &path].concat(), "unknown key", suggestion);
+        }
+    }
+
+    /// Report what keeps `section`, at `at`, from being read as settings of
+    /// the config file
+    fn check_config_section(&mut self, at: &[Segment], section: &serde_yaml::Value) {
+        let mut ignored = Vec::new();
+        let callback = |path: serde_ignored::Path| ignored.push(segments(&path));
+        match serde_ignored::deserialize::<_, _, VortexConfig>(section.clone(), callback) {
+            Ok(config) => self.add_unknown(at, &config, ignored),
+            Err(e) => self.add(at, e.to_string(), None),
+        }
+    }
+
+    fn add(&mut self, at: &[Segment], message: impl Into<String>, suggestion: Option<String>) {
+        let location = match self.format {
+            Format::Toml => self
+                .document
+                .as_ref()
+                .and_then(|document| locate_toml(document, self.content, at)),
+            Format::Yaml => locate_yaml(self.content, at),
+        };
+        self.diagnostics.push(Diagnostic {
+            file: self.file.to_path_buf(),
+            location,
+            key: key_path(at),
+            message: message.into(),
+            suggestion,
+        });
+    }
+
+    /// Report the ports and volumes of the service at `at` that do not parse
+    fn check_service(&mut self, at: &[Segment], service: &ServiceConfig) {
+        let mut add = |field: &str, index: usize, error: VortexError| {
+            let mut at = at.to_vec();
+            at.extend([key(field), Segment::Index(index)]);
+            self.add(&at, reason(error), None);
+        };
+        for (index, port) in service.ports.iter().enumerate() {
+            if let Err(e) = parse_port(port) {
+                add("ports", index, e);
+            }
+        }
+        for (index, volume) in service.volumes.iter().enumerate() {
+            if let Err(e) = parse_volume(volume) {
+                add("volumes", index, e);
+            }
+        }
+    }
+
+    /// Report `name` at `at` unless it is a dev template
+    fn check_template(&mut self, at: &[Segment], name: &str, templates: &DevEnvironmentManager) {
+        if templates.get_template(name).is_some() {
+            return;
+        }
+        let names = templates.list_templates();
+        let suggestion = closest(name, names.iter().map(|template| template.name.as_str()));
+        self.add(at, format!("unknown dev template '{}'", name), suggestion);
+    }
+
+    /// The problems in the order of the file
+    fn finish(mut self) -> Vec<Diagnostic> {
+        self.diagnostics
+            .sort_by_key(|diagnostic| diagnostic.location);
+        self.diagnostics
+    }
+}
+
+fn offset_location(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Where the key, or for a sequence index the element, at `at` is, or
+/// else the deepest of its parents that could be found
+fn locate_toml(
+    document: &toml_edit::ImDocument<&str>,
+    content: &str,
+    at: &[Segment],
+) -> Option<(usize, usize)> {
+    let mut item = document.as_item();
+    let mut span = None;
+    for segment in at {
+        match segment {
+            Segment::Key(key) => {
+                let Some((key, value)) = item.as_table_like().and_then(|t| t.get_key_value(key))
+                else {
+                    break;
+                };
+                span = key.span();
+                item = value;
+            }
+            Segment::Index(index) => {
+                let Some(element) = item.get(*index) else {
+                    break;
+                };
+                item = element;
+                span = item.span();
+            }
+        }
+    }
+    span.map(|span| offset_location(content, span.start))
+}
+
+/// Like [`locate_toml`], going by the indentation of block-style YAML
+fn locate_yaml(content: &str, at: &[Segment]) -> Option<(usize, usize)> {
+    let meaningful = |text: &str| !text.is_empty() && !text.starts_with('#');
+    // Indentation and text of each line; the first line of a sequence item
+    // is read as indented past its dash once the item is entered
+    let mut lines: Vec<(usize, &str)> = content
+        .lines()
+        .map(|line| {
+            let text = line.trim_start();
+            (line.len() - text.len(), text)
+        })
+        .collect();
+    let mut range = 0..lines.len();
+    let mut parent = None;
+    let mut found = None;
+    for segment in at {
+        let Some(&(block, _)) = lines[range.clone()]
+            .iter()
+            .find(|(indent, text)| meaningful(text) && parent.map_or(true, |p| *indent > p))
+        else {
+            break;
+        };
+        let line = match segment {
+            Segment::Key(key) => range.clone().find(|&i| {
+                let (indent, text) = lines[i];
+                indent == block && yaml_key(text) == Some(key.as_str())
+            }),
+            Segment::Index(index) => range
+                .clone()
+                .filter(|&i| {
+                    let (indent, text) = lines[i];
+                    indent == block && (text == "-" || text.starts_with("- "))
+                })
+                .nth(*index),
+        };
+        let Some(line) = line else {
+            break;
+        };
+        if let Segment::Index(_) = segment {
+            let (indent, text) = lines[line];
+            let item = text[1..].trim_start();
+            lines[line] = (indent + text.len() - item.len(), item);
+        }
+        let end = (line + 1..range.end)
+            .find(|&i| {
+                let (indent, text) = lines[i];
+                meaningful(text) && indent <= block
+            })
+            .unwrap_or(range.end);
+        found = Some((line + 1, lines[line].0 + 1));
+        parent = Some(block);
+        range = line..end;
+    }
+    found
+}
+
+fn yaml_key(text: &str) -> Option<&str> {
+    let (key, rest) = text.split_once(':')?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some(key.trim().trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// The keys of a value, including those left out of its serialization for
+/// being empty, which serde still names through `skip_field`
+#[derive(Debug, Default)]
+enum Keys {
+    #[default]
+    Leaf,
+    Fields(BTreeMap<String, Keys>),
+    Items(Vec<Keys>),
+}
+
+impl Keys {
+    fn get(&self, at: &[Segment]) -> Option<&Keys> {
+        at.iter()
+            .try_fold(self, |keys, segment| match (keys, segment) {
+                (Keys::Fields(fields), Segment::Key(key)) => fields.get(key),
+                (Keys::Items(items), Segment::Index(index)) => items.get(*index),
+                _ => None,
+            })
+    }
+
+    fn names(&self) -> Vec<&str> {
+        match self {
+            Keys::Fields(fields) => fields.keys().map(String::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+struct KeySerializer;
+
+macro_rules! leaves {
+    ($($method:ident: $type:ty),*) => {
+        $(
+            fn $method(self, _: $type) -> std::result::Result<Keys, serde_json::Error> {
+                Ok(Keys::Leaf)
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Keys;
+    type Error = serde_json::Error;
+    type SerializeSeq = ItemKeys;
+    type SerializeTuple = ItemKeys;
+    type SerializeTupleStruct = ItemKeys;
+    type SerializeTupleVariant = ItemKeys;
+    type SerializeMap = FieldKeys;
+    type SerializeStruct = FieldKeys;
+    type SerializeStructVariant = FieldKeys;
+
+    leaves!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+        serialize_unit_struct: &'static str
+    );
+
+    fn serialize_none(self) -> std::result::Result<Keys, serde_json::Error> {
+        Ok(Keys::Leaf)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(
+        self,
+        value: &T,
+    ) -> std::result::Result<Keys, serde_json::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> std::result::Result<Keys, serde_json::Error> {
+        Ok(Keys::Leaf)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> std::result::Result<Keys, serde_json::Error> {
+        Ok(Keys::Leaf)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> std::result::Result<Keys, serde_json::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> std::result::Result<Keys, serde_json::Error> {
+        let inner = value.serialize(self)?;
+        Ok(Keys::Fields(BTreeMap::from([(variant.to_string(), inner)])))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> std::result::Result<ItemKeys, serde_json::Error> {
+        Ok(ItemKeys(Vec::new()))
+    }
+
+    fn serialize_tuple(self, _: usize) -> std::result::Result<ItemKeys, serde_json::Error> {
+        Ok(ItemKeys(Vec::new()))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> std::result::Result<ItemKeys, serde_json::Error> {
+        Ok(ItemKeys(Vec::new()))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> std::result::Result<ItemKeys, serde_json::Error> {
+        Ok(ItemKeys(Vec::new()))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> std::result::Result<FieldKeys, serde_json::Error> {
+        Ok(FieldKeys::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> std::result::Result<FieldKeys, serde_json::Error> {
+        Ok(FieldKeys::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> std::result::Result<FieldKeys, serde_json::Error> {
+        Ok(FieldKeys {
+            variant: Some(variant),
+            ..FieldKeys::default()
+        })
+    }
+}
+
+struct ItemKeys(Vec<Keys>);
+
+impl ItemKeys {
+    fn push<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), serde_json::Error> {
+        self.0.push(value.serialize(KeySerializer)?);
+        Ok(())
+    }
+}
+
+macro_rules! item_keys {
+    ($($trait:ident: $method:ident),*) => {
+        $(
+            impl ser::$trait for ItemKeys {
+                type Ok = Keys;
+                type Error = serde_json::Error;
+
+                fn $method<T: ?Sized + Serialize>(
+                    &mut self,
+                    value: &T,
+                ) -> std::result::Result<(), serde_json::Error> {
+                    self.push(value)
+                }
+
+                fn end(self) -> std::result::Result<Keys, serde_json::Error> {
+                    Ok(Keys::Items(self.0))
+                }
+            }
+        )*
+    };
+}
+
+item_keys!(
+    SerializeSeq: serialize_element,
+    SerializeTuple: serialize_element,
+    SerializeTupleStruct: serialize_field,
+    SerializeTupleVariant: serialize_field
+);
+
+#[derive(Default)]
+struct FieldKeys {
+    fields: BTreeMap<String, Keys>,
+    next_key: Option<String>,
+    /// Variant of an enum the fields are nested under
+    variant: Option<&'static str>,
+}
+
+impl FieldKeys {
+    fn insert<T: ?Sized + Serialize>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> std::result::Result<(), serde_json::Error> {
+        self.fields.insert(key, value.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Keys {
+        let fields = Keys::Fields(self.fields);
+        match self.variant {
+            Some(variant) => Keys::Fields(BTreeMap::from([(variant.to_string(), fields)])),
+            None => fields,
+        }
+    }
+}
+
+impl ser::SerializeMap for FieldKeys {
+    type Ok = Keys;
+    type Error = serde_json::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(
+        &mut self,
+        key: &T,
+    ) -> std::result::Result<(), serde_json::Error> {
+        self.next_key = Some(match serde_json::to_value(key)? {
+            serde_json::Value::String(key) => key,
+            key => key.to_string(),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), serde_json::Error> {
+        let key = self.next_key.take().unwrap_or_default();
+        self.insert(key, value)
+    }
+
+    fn end(self) -> std::result::Result<Keys, serde_json::Error> {
+        Ok(self.finish())
+    }
+}
+
+macro_rules! field_keys {
+    ($($trait:ident),*) => {
+        $(
+            impl ser::$trait for FieldKeys {
+                type Ok = Keys;
+                type Error = serde_json::Error;
+
+                fn serialize_field<T: ?Sized + Serialize>(
+                    &mut self,
+                    key: &'static str,
+                    value: &T,
+                ) -> std::result::Result<(), serde_json::Error> {
+                    self.insert(key.to_string(), value)
+                }
+
+                fn skip_field(&mut self, key: &'static str) -> std::result::Result<(), serde_json::Error> {
+                    self.fields.insert(key.to_string(), Keys::Leaf);
+                    Ok(())
+                }
+
+                fn end(self) -> std::result::Result<Keys, serde_json::Error> {
+                    Ok(self.finish())
+                }
+            }
+        )*
+    };
+}
+
+field_keys!(SerializeStruct, SerializeStructVariant);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems_point_at_their_line() {
+        let templates = DevEnvironmentManager::new();
+        let file = Path::new("config.toml");
+        let content = toml::to_string_pretty(&VortexConfig::default()).unwrap();
+        assert_eq!(check_config(file, &content, &templates), vec![]);
+
+        let content = format!(
+            "{}\n[[pools]]\nsize = 1\n\n[[pool]]\ntemplate = \"pyhton\"\nsize = 2\n",
+            content
+        );
+        let diagnostics = check_config(file, &content, &templates);
+        let lines = content.lines().count();
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].key, "pools");
+        assert_eq!(diagnostics[0].location, Some((lines - 5, 3)));
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("pool"));
+        assert_eq!(
+            diagnostics[1].to_string(),
+            format!(
+                "config.toml:{}:1: pool[0].template: unknown dev template 'pyhton' (did you mean `python`?)",
+                lines - 1
+            )
+        );
+
+        let broken = "default_memory = \"lots\"\n";
+        let diagnostic = &check_config(file, broken, &templates)[0];
+        assert_eq!(diagnostic.location, Some((1, 18)));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            ProjectConfig::path(dir.path()),
+            "name: shop\nservices:\n  api:\n    imgae: python:3.11-slim\n    ports:\n      - 8000:8000\n      - 8001\n",
+        )
+        .unwrap();
+        let diagnostics = check_project(dir.path(), &templates).unwrap();
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].key, "services.api.imgae");
+        assert_eq!(diagnostics[0].location, Some((4, 5)));
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("image"));
+        assert_eq!(diagnostics[1].key, "services.api.ports[1]");
+        assert_eq!(diagnostics[1].location, Some((7, 9)));
+        assert!(ProjectConfig::load(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_templates_name_built_in_tuning_profiles() {
+        let templates = DevEnvironmentManager::new();
+        let file = Path::new("config.toml");
+        let mut config = VortexConfig::default();
+        let dev = config.templates.get_mut("dev").unwrap();
+        dev.tuning = Some("database".to_string());
+        let content = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(check_config(file, &content, &templates), vec![]);
+
+        let content = content.replace("\"database\"", "\"databse\"");
+        let diagnostics = check_config(file, &content, &templates);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].key, "templates.dev.tuning");
+    }
+}
@@ -0,0 +1,2497 @@
+use crate::archive::{ArchiveKind, ArchiveManifest};
+use crate::audit::{AuditAction, AuditLog, AuditRecord};
+use crate::backend::{Backend, BackendProvider, ExecOptions, ExecResult, ExitStatus, VmMetrics};
+use crate::error::{Result, VortexError};
+use crate::event_queue::{EventQueueConfig, EventSubscriber, SubscriberStats};
+use crate::events::SessionStateName;
+use crate::handover::VmRecord;
+use crate::ids::{
+    SnapshotId, VmId, LABEL_CLONED_FROM, LABEL_EGRESS_PROXY, LABEL_RUN_ID, LABEL_SESSION_ID,
+    LABEL_WORKSPACE_ID,
+};
+use crate::listing::{ListQuery, Listable, Page};
+use crate::logs;
+use crate::network;
+use crate::plugin::{PluginContext, PluginHook, PluginManager};
+use crate::pool::VmPool;
+use crate::provision::Provision;
+use crate::run_dir;
+use crate::snapshot::{SnapshotRecord, SnapshotStore};
+use crate::storage;
+use crate::tuning::TuningProfile;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+
+/// `network_config` value for a VM without any network device
+pub const NETWORK_NONE: &str = "none";
+
+/// Name guests reach services on the host at, like Docker's
+/// `host.docker.internal`
+pub const HOST_ALIAS: &str = "host.vortex.internal";
+
+/// How often VMs are checked against their TTL
+const REAP_INTERVAL: Duration = Duration::from_secs(30);
+/// How often the pool is topped up when no VM was taken
+const PREWARM_INTERVAL: Duration = Duration::from_secs(30);
+/// Events a `subscribe` receiver can fall behind by before it misses some
+const EVENT_BUS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmSpec {
+    pub image: String,
+    pub memory: u32,
+    pub cpus: u32,
+    pub ports: HashMap<u16, u16>,
+    pub volumes: HashMap<PathBuf, PathBuf>,
+    /// Variables set for the VM's command. Guests reach services on the
+    /// host at [`HOST_ALIAS`] on backends with a `Backend::host_address`,
+    /// so values such as `postgres://host.vortex.internal:5432/app` work
+    /// unchanged in every VM.
+    pub environment: HashMap<String, String>,
+    pub command: Option<String>,
+    pub labels: HashMap<String, String>,
+    pub network_config: Option<String>,
+    pub resource_limits: ResourceLimits,
+    pub backend: Option<String>,
+    /// Guest kernel tuning applied at boot
+    #[serde(default)]
+    pub tuning: Option<TuningProfile>,
+    /// Commands run around the VM's start and stop
+    #[serde(default)]
+    pub hooks: LifecycleHooks,
+    /// Readiness and health check of the VM
+    #[serde(default)]
+    pub health_check: Option<Probe>,
+    /// Seconds after its creation at which the VM is stopped and cleaned up
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+    /// GPUs attached to the VM
+    #[serde(default)]
+    pub gpus: Vec<GpuDevice>,
+    /// Users, files and commands set up in the guest on first boot
+    #[serde(default)]
+    pub provision: Option<Provision>,
+    /// Guest ports to forward from free host ports, which are picked when
+    /// the VM is created and added to `ports`
+    #[serde(default)]
+    pub publish: Vec<u16>,
+    /// Host ports taking HTTPS for a guest port, by host port. TLS is
+    /// terminated on the host with certificates of a local CA (needs the
+    /// `tls` feature), and the guest port is published for the plaintext.
+    #[serde(default)]
+    pub tls_ports: HashMap<u16, u16>,
+    /// Host address to listen on for a port of `ports` or `tls_ports`, by
+    /// host port. Ports without one are forwarded as the backend does by
+    /// default, and HTTPS ports listen on every IPv4 and IPv6 address.
+    #[serde(default)]
+    pub port_addresses: HashMap<u16, IpAddr>,
+    /// How the volume at a guest path of `volumes` is shared, by guest
+    /// path. Volumes without one are shared the backend's default way.
+    #[serde(default)]
+    pub share_mechanisms: HashMap<PathBuf, ShareMechanism>,
+    /// RAM-backed scratch mounts, by guest path, with their size limit in
+    /// MiB. They come out of the VM's memory and are empty at every boot.
+    #[serde(default)]
+    pub tmpfs: HashMap<PathBuf, u32>,
+    /// Where the VM may connect to
+    #[serde(default)]
+    pub network_policy: NetworkPolicy,
+}
+
+impl Default for VmSpec {
+    fn default() -> Self {
+        Self {
+            image: String::new(),
+            memory: 512,
+            cpus: 1,
+            ports: HashMap::new(),
+            volumes: HashMap::new(),
+            environment: HashMap::new(),
+            command: None,
+            labels: HashMap::new(),
+            network_config: None,
+            resource_limits: ResourceLimits::default(),
+            backend: None,
+            tuning: None,
+            hooks: LifecycleHooks::default(),
+            health_check: None,
+            ttl_seconds: None,
+            gpus: Vec::new(),
+            provision: None,
+            publish: Vec::new(),
+            tls_ports: HashMap::new(),
+            port_addresses: HashMap::new(),
+            share_mechanisms: HashMap::new(),
+            tmpfs: HashMap::new(),
+            network_policy: NetworkPolicy::Open,
+        }
+    }
+}
+
+impl VmSpec {
+    /// Whether the VM must boot without a network
+    pub fn network_disabled(&self) -> bool {
+        self.network_config.as_deref() == Some(NETWORK_NONE)
+            || self.network_policy == NetworkPolicy::None
+    }
+
+    /// Forward every port in `ports` from a free host port instead
+    pub fn publish_all(&mut self) {
+        let mut guests: Vec<u16> = self.ports.drain().map(|(_, guest)| guest).collect();
+        guests.sort();
+        self.publish.extend(guests);
+    }
+
+    /// The private network the VM joins, if `network_config` names one
+    pub fn private_network(&self) -> Option<&str> {
+        self.network_config
+            .as_deref()
+            .filter(|name| *name != NETWORK_NONE)
+    }
+
+    /// Check the spec before a VM is created from it
+    pub fn validate(&self) -> Result<()> {
+        if self.memory == 0 {
+            return Err(VortexError::InvalidInput {
+                field: "memory".to_string(),
+                message: "Memory must be greater than 0".to_string(),
+            });
+        }
+
+        if self.cpus == 0 {
+            return Err(VortexError::InvalidInput {
+                field: "cpus".to_string(),
+                message: "CPUs must be greater than 0".to_string(),
+            });
+        }
+
+        // Check resource limits
+        if let Some(max_memory) = self.resource_limits.max_memory {
+            if self.memory > max_memory {
+                return Err(VortexError::ResourceLimitExceeded {
+                    resource: format!("memory: {} > {}", self.memory, max_memory),
+                });
+            }
+        }
+
+        if let Some(max_cpus) = self.resource_limits.max_cpus {
+            if self.cpus > max_cpus {
+                return Err(VortexError::ResourceLimitExceeded {
+                    resource: format!("cpus: {} > {}", self.cpus, max_cpus),
+                });
+            }
+        }
+
+        if self.resource_limits.timeout_seconds == Some(0) {
+            return Err(VortexError::InvalidInput {
+                field: "resource_limits.timeout_seconds".to_string(),
+                message: "Timeout must be greater than 0".to_string(),
+            });
+        }
+
+        if let Some(max_gpus) = self.resource_limits.max_gpus {
+            if self.gpus.len() > max_gpus as usize {
+                return Err(VortexError::ResourceLimitExceeded {
+                    resource: format!("gpus: {} > {}", self.gpus.len(), max_gpus),
+                });
+            }
+        }
+
+        let virtio_gpus = self.gpus.iter().filter(|gpu| **gpu == GpuDevice::Virtio);
+        if virtio_gpus.count() > 1 {
+            return Err(VortexError::InvalidInput {
+                field: "gpus".to_string(),
+                message: "A VM takes at most one virtio GPU".to_string(),
+            });
+        }
+
+        if let Some(tuning) = &self.tuning {
+            tuning.validate()?;
+        }
+
+        if let Some(provision) = &self.provision {
+            provision.validate()?;
+        }
+
+        self.network_policy.validate()?;
+
+        let mut tmpfs: Vec<_> = self.tmpfs.iter().collect();
+        tmpfs.sort();
+        for (guest, size_mib) in &tmpfs {
+            if !guest.is_absolute() || **size_mib == 0 {
+                return Err(VortexError::InvalidInput {
+                    field: "tmpfs".to_string(),
+                    message: format!(
+                        "tmpfs mount {} needs an absolute guest path and a size",
+                        guest.display()
+                    ),
+                });
+            }
+        }
+        let tmpfs_mib: u64 = tmpfs.iter().map(|(_, size)| u64::from(**size)).sum();
+        if tmpfs_mib >= u64::from(self.memory) {
+            return Err(VortexError::InvalidInput {
+                field: "tmpfs".to_string(),
+                message: format!(
+                    "tmpfs mounts of {} MiB leave nothing of the VM's {} MiB of memory",
+                    tmpfs_mib, self.memory
+                ),
+            });
+        }
+
+        let mut tls_hosts: Vec<u16> = self.tls_ports.keys().copied().collect();
+        tls_hosts.sort();
+        for host in tls_hosts {
+            if cfg!(not(feature = "tls")) {
+                return Err(VortexError::InvalidInput {
+                    field: "tls_ports".to_string(),
+                    message: "HTTPS ports need Vortex built with --features tls".to_string(),
+                });
+            }
+            if host == 0 || self.ports.contains_key(&host) {
+                return Err(VortexError::InvalidInput {
+                    field: "tls_ports".to_string(),
+                    message: format!("Port {} cannot take HTTPS", host),
+                });
+            }
+        }
+
+        let mut bound: Vec<u16> = self.port_addresses.keys().copied().collect();
+        bound.sort();
+        if let Some(port) = bound
+            .into_iter()
+            .find(|port| !self.ports.contains_key(port) && !self.tls_ports.contains_key(port))
+        {
+            return Err(VortexError::InvalidInput {
+                field: "port_addresses".to_string(),
+                message: format!("Port {} is not forwarded", port),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Where to connect to the host port `port` from the host: its address,
+    /// or loopback when it listens on all of them
+    pub fn host_address(&self, port: u16) -> SocketAddr {
+        let ip = match self.port_addresses.get(&port) {
+            Some(IpAddr::V6(ip)) if ip.is_unspecified() => Ipv6Addr::LOCALHOST.into(),
+            Some(ip) if !ip.is_unspecified() => *ip,
+            _ => Ipv4Addr::LOCALHOST.into(),
+        };
+        SocketAddr::new(ip, port)
+    }
+
+    /// This spec for a clone of VM `source_id`: without the labels tying the
+    /// source to its session, run or workspace, and marked as a clone
+    pub fn for_clone(&self, source_id: &str) -> VmSpec {
+        let mut spec = self.clone();
+        for label in [LABEL_SESSION_ID, LABEL_RUN_ID, LABEL_WORKSPACE_ID] {
+            spec.labels.remove(label);
+        }
+        spec.labels
+            .insert(LABEL_CLONED_FROM.to_string(), source_id.to_string());
+        // The source still holds its host ports
+        spec.publish_all();
+        spec.tls_ports.clear();
+        spec.port_addresses.clear();
+        spec
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    pub max_memory: Option<u32>,
+    pub max_cpus: Option<u32>,
+    /// Disk budget in MB. Backends that boot disk images refuse a larger
+    /// image; for the others the manager warns once a VM's disk usage
+    /// outgrows it (see `VmManager::check_disk_quotas`).
+    pub max_disk: Option<u64>,
+    /// Seconds the VM may run before it is stopped and its command fails
+    pub timeout_seconds: Option<u64>,
+    pub max_gpus: Option<u32>,
+}
+
+/// Where a VM may connect to, written as `open`, `none`, `host-only` or
+/// `allow:<domain>,<domain>...`. Only `open` VMs reach the network
+/// directly; `host-only` and `allow` VMs connect through an egress proxy on
+/// the host (see `crate::network`), which they find in their
+/// `http_proxy` and `https_proxy` variables.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum NetworkPolicy {
+    #[default]
+    Open,
+    /// No network device at all
+    None,
+    /// Services on the host, as `localhost`
+    HostOnly,
+    /// The domains, and their subdomains
+    Allow { domains: Vec<String> },
+}
+
+impl NetworkPolicy {
+    /// Whether the VM connects through the egress proxy
+    pub fn needs_proxy(&self) -> bool {
+        matches!(self, NetworkPolicy::HostOnly | NetworkPolicy::Allow { .. })
+    }
+
+    /// Whether the VM may connect to `host`, a name or an IP address
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        match self {
+            NetworkPolicy::Open => true,
+            NetworkPolicy::None => false,
+            NetworkPolicy::HostOnly => {
+                host == "localhost"
+                    || host == HOST_ALIAS
+                    || host
+                        .trim_matches(|c| c == '[' || c == ']')
+                        .parse::<std::net::IpAddr>()
+                        .is_ok_and(|ip| ip.is_loopback())
+            }
+            NetworkPolicy::Allow { domains } => domains.iter().any(|domain| {
+                host == *domain
+                    || host
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|sub| sub.ends_with('.'))
+            }),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let NetworkPolicy::Allow { domains } = self else {
+            return Ok(());
+        };
+        let invalid = |domain: &String| {
+            domain.is_empty()
+                || domain.starts_with('.')
+                || !domain
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+        };
+        if domains.is_empty() {
+            return Err(VortexError::InvalidInput {
+                field: "network_policy".to_string(),
+                message: "An allow policy needs at least one domain".to_string(),
+            });
+        }
+        if let Some(domain) = domains.iter().find(|domain| invalid(domain)) {
+            return Err(VortexError::InvalidInput {
+                field: "network_policy".to_string(),
+                message: format!("'{}' is not a lowercase domain name", domain),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for NetworkPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkPolicy::Open => write!(f, "open"),
+            NetworkPolicy::None => write!(f, "none"),
+            NetworkPolicy::HostOnly => write!(f, "host-only"),
+            NetworkPolicy::Allow { domains } => write!(f, "allow:{}", domains.join(",")),
+        }
+    }
+}
+
+impl std::str::FromStr for NetworkPolicy {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let policy = match s {
+            "open" => NetworkPolicy::Open,
+            "none" => NetworkPolicy::None,
+            "host-only" => NetworkPolicy::HostOnly,
+            _ => match s.strip_prefix("allow:") {
+                Some(domains) => NetworkPolicy::Allow {
+                    domains: domains
+                        .split(',')
+                        .map(|domain| domain.trim().trim_start_matches("*.").to_ascii_lowercase())
+                        .filter(|domain| !domain.is_empty())
+                        .collect(),
+                },
+                None => {
+                    return Err(VortexError::InvalidInput {
+                        field: "network_policy".to_string(),
+                        message: format!(
+                            "Expected open, none, host-only or allow:<domain>,..., got '{}'",
+                            s
+                        ),
+                    })
+                }
+            },
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+}
+
+/// A GPU attached to a VM, written as `vfio:<pci-address>` (or just the
+/// address) or `virtio`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GpuDevice {
+    /// A host PCI device passed through with VFIO. The device must already be
+    /// bound to `vfio-pci` on the host.
+    Vfio { address: String },
+    /// A paravirtualized virtio-gpu, shared with the host's GPU
+    Virtio,
+}
+
+impl GpuDevice {
+    /// How the GPU is attached, for messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GpuDevice::Vfio { .. } => "vfio",
+            GpuDevice::Virtio => "virtio",
+        }
+    }
+}
+
+impl std::fmt::Display for GpuDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuDevice::Vfio { address } => write!(f, "vfio:{}", address),
+            GpuDevice::Virtio => write!(f, "virtio"),
+        }
+    }
+}
+
+impl std::str::FromStr for GpuDevice {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "virtio" {
+            return Ok(GpuDevice::Virtio);
+        }
+        let address = s.strip_prefix("vfio:").unwrap_or(s);
+        let address = pci_address(address).ok_or_else(|| VortexError::InvalidInput {
+            field: "gpus".to_string(),
+            message: format!(
+                "Expected virtio, vfio:<pci-address> or a PCI address like 0000:01:00.0, got '{}'",
+                s
+            ),
+        })?;
+        Ok(GpuDevice::Vfio { address })
+    }
+}
+
+/// How a volume reaches the guest, written as `virtiofs`, `9p` or `copy`.
+/// virtio-fs is the fastest live share but needs shared guest memory and
+/// a host daemon; 9p works without either; `copy` copies the volume's
+/// files into the guest once at boot, so the guest works on its own disk at
+/// native speed and its changes never reach the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShareMechanism {
+    #[serde(rename = "virtiofs")]
+    Virtiofs,
+    #[serde(rename = "9p")]
+    NineP,
+    #[serde(rename = "copy")]
+    Copy,
+}
+
+impl std::fmt::Display for ShareMechanism {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShareMechanism::Virtiofs => write!(f, "virtiofs"),
+            ShareMechanism::NineP => write!(f, "9p"),
+            ShareMechanism::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+impl std::str::FromStr for ShareMechanism {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "virtiofs" => Ok(ShareMechanism::Virtiofs),
+            "9p" => Ok(ShareMechanism::NineP),
+            "copy" => Ok(ShareMechanism::Copy),
+            _ => Err(VortexError::InvalidInput {
+                field: "share_mechanisms".to_string(),
+                message: format!("Expected virtiofs, 9p or copy, got '{}'", s),
+            }),
+        }
+    }
+}
+
+/// `address` as a full `dddd:bb:dd.f` PCI address, adding the default domain
+/// if it is left out
+fn pci_address(address: &str) -> Option<String> {
+    let address = address.to_ascii_lowercase();
+    let full = match address.matches(':').count() {
+        1 => format!("0000:{}", address),
+        2 => address,
+        _ => return None,
+    };
+    let (rest, function) = full.rsplit_once('.')?;
+    let fields: Vec<&str> = rest.split(':').collect();
+    let hex = |field: &str, len: usize| {
+        field.len() == len && field.chars().all(|c| c.is_ascii_hexdigit())
+    };
+    let valid = hex(fields[0], 4)
+        && hex(fields[1], 2)
+        && hex(fields[2], 2)
+        && function.len() == 1
+        && matches!(function.chars().next(), Some('0'..='7'));
+    valid.then_some(full)
+}
+
+/// Commands `VmManager` runs at points of a VM's lifecycle. Host hooks run
+/// with `sh -c` and see `VORTEX_HOOK`, `VORTEX_VM_ID` and `VORTEX_VM_IMAGE`.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleHooks {
+    /// On the host before the VM is created; a failure aborts the create
+    #[serde(default)]
+    pub pre_start: Option<String>,
+    /// In the guest once the VM and its command are started; a failure
+    /// fails the create
+    #[serde(default)]
+    pub post_start: Option<String>,
+    /// On the host before the VM is stopped; a failure is logged and the
+    /// stop goes ahead
+    #[serde(default)]
+    pub pre_stop: Option<String>,
+}
+
+/// Seconds between probes of a VM unless its `Probe` says otherwise
+pub const DEFAULT_PROBE_INTERVAL_SECS: u64 = 10;
+/// Seconds a probe may take before it counts as failed
+pub const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// What a probe checks. Ports are guest ports; the probe connects to the
+/// host port forwarded to it, or to the same port if none is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProbeCheck {
+    /// A command run with `sh -c` in the guest that exits 0
+    Exec { command: String },
+    /// A port that accepts TCP connections
+    Tcp { port: u16 },
+    /// A `GET` of `path` on a port that answers 2xx or 3xx
+    Http { port: u16, path: String },
+}
+
+/// Readiness and health check of a VM, written as `exec:<command>`,
+/// `tcp:<port>` or `http:<port>[/<path>]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Probe {
+    #[serde(flatten)]
+    pub check: ProbeCheck,
+    #[serde(default = "default_probe_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_probe_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_probe_interval() -> u64 {
+    DEFAULT_PROBE_INTERVAL_SECS
+}
+
+fn default_probe_timeout() -> u64 {
+    DEFAULT_PROBE_TIMEOUT_SECS
+}
+
+impl std::str::FromStr for Probe {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |message: String| VortexError::InvalidInput {
+            field: "health_check".to_string(),
+            message,
+        };
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .map_err(|_| invalid(format!("Invalid probe port '{}'", port)))
+        };
+
+        let (kind, target) = s.split_once(':').ok_or_else(|| {
+            invalid(format!(
+                "Expected exec:<command>, tcp:<port> or http:<port>[/<path>], got '{}'",
+                s
+            ))
+        })?;
+        let check = match kind {
+            "exec" if !target.trim().is_empty() => ProbeCheck::Exec {
+                command: target.to_string(),
+            },
+            "exec" => return Err(invalid("An exec probe needs a command".to_string())),
+            "tcp" => ProbeCheck::Tcp {
+                port: parse_port(target)?,
+            },
+            "http" => {
+                let (port, path) = match target.find('/') {
+                    Some(i) => target.split_at(i),
+                    None => (target, "/"),
+                };
+                ProbeCheck::Http {
+                    port: parse_port(port)?,
+                    path: path.to_string(),
+                }
+            }
+            other => return Err(invalid(format!("Unknown probe type '{}'", other))),
+        };
+        Ok(Probe {
+            check,
+            interval_secs: DEFAULT_PROBE_INTERVAL_SECS,
+            timeout_secs: DEFAULT_PROBE_TIMEOUT_SECS,
+        })
+    }
+}
+
+impl Probe {
+    /// Run the check once against `vm`, failing if it does not pass within
+    /// the probe's timeout
+    pub async fn check(&self, vm: &VmInstance) -> Result<()> {
+        let failed = |message: String| VortexError::VmError {
+            message: format!("Health check of VM {} failed: {}", vm.id, message),
+        };
+        let check = async {
+            match &self.check {
+                ProbeCheck::Exec { command } => {
+                    let argv = ["sh".to_string(), "-c".to_string(), command.clone()];
+                    let result = vm.backend.exec(vm, &argv, &ExecOptions::default()).await?;
+                    if !result.success() {
+                        return Err(failed(format!(
+                            "'{}' exited with {}",
+                            command, result.exit_code
+                        )));
+                    }
+                    Ok(())
+                }
+                ProbeCheck::Tcp { port } => {
+                    connect(vm, *port)
+                        .await
+                        .map_err(|e| failed(e.to_string()))?;
+                    Ok(())
+                }
+                ProbeCheck::Http { port, path } => {
+                    let status = http_status(vm, *port, path)
+                        .await
+                        .map_err(|e| failed(e.to_string()))?;
+                    if !(200..400).contains(&status) {
+                        return Err(failed(format!("GET {} answered {}", path, status)));
+                    }
+                    Ok(())
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(self.timeout_secs), check)
+            .await
+            .map_err(|_| failed(format!("no answer within {}s", self.timeout_secs)))?
+    }
+}
+
+/// Open a connection to guest `port` of `vm` through its forwarded host port
+async fn connect(vm: &VmInstance, port: u16) -> std::io::Result<tokio::net::TcpStream> {
+    let host_port = vm
+        .spec
+        .ports
+        .iter()
+        .find(|(_, guest)| **guest == port)
+        .map_or(port, |(host, _)| *host);
+    tokio::net::TcpStream::connect(("127.0.0.1", host_port)).await
+}
+
+/// Status code of a `GET` of `path` on guest `port` of `vm`
+async fn http_status(vm: &VmInstance, port: u16, path: &str) -> std::io::Result<u16> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let mut stream = connect(vm, port).await?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: localhost\r\nUser-Agent: vortex-probe\r\n\r\n",
+        path
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await?;
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("not an HTTP response: '{}'", status_line.trim()),
+            )
+        })
+}
+
+/// Lifecycle state of a VM. A VM with a health check is `Starting` until
+/// the check first passes and `Unhealthy` while it fails after that.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum VmState {
+    Creating,
+    Starting,
+    Running,
+    Unhealthy { message: String },
+    Paused,
+    Stopped,
+    Error { message: String },
+    Snapshotting,
+    Restoring,
+}
+
+impl VmState {
+    /// Lowercase state name used for filtering
+    pub fn name(&self) -> &'static str {
+        match self {
+            VmState::Creating => "creating",
+            VmState::Starting => "starting",
+            VmState::Running => "running",
+            VmState::Unhealthy { .. } => "unhealthy",
+            VmState::Paused => "paused",
+            VmState::Stopped => "stopped",
+            VmState::Error { .. } => "error",
+            VmState::Snapshotting => "snapshotting",
+            VmState::Restoring => "restoring",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VmInstance {
+    pub id: VmId,
+    pub spec: VmSpec,
+    pub state: VmState,
+    pub backend: Arc<dyn Backend>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl VmInstance {
+    /// When the VM outlives its `ttl_seconds` or its timeout, whichever
+    /// comes first
+    pub fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let ttl = expiry(self.created_at, self.spec.ttl_seconds);
+        let timeout = expiry(self.created_at, self.spec.resource_limits.timeout_seconds);
+        match (ttl, timeout) {
+            (Some(ttl), Some(timeout)) => Some(ttl.min(timeout)),
+            (ttl, timeout) => ttl.or(timeout),
+        }
+    }
+}
+
+/// `created_at` plus `ttl_seconds`, if there is a TTL
+pub fn expiry(
+    created_at: chrono::DateTime<chrono::Utc>,
+    ttl_seconds: Option<u64>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let ttl = i64::try_from(ttl_seconds?).ok()?;
+    created_at.checked_add_signed(chrono::Duration::try_seconds(ttl)?)
+}
+
+impl Listable for VmInstance {
+    fn list_id(&self) -> &str {
+        &self.id
+    }
+
+    fn list_name(&self) -> Option<&str> {
+        None
+    }
+
+    fn list_state(&self) -> &'static str {
+        self.state.name()
+    }
+
+    fn list_labels(&self) -> &HashMap<String, String> {
+        &self.spec.labels
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum VmEvent {
+    Created {
+        vm_id: VmId,
+    },
+    Started {
+        vm_id: VmId,
+    },
+    Stopped {
+        vm_id: VmId,
+    },
+    /// The VM outlived its TTL or timeout and is about to be stopped
+    Expired {
+        vm_id: VmId,
+    },
+    Error {
+        vm_id: VmId,
+        error: String,
+    },
+    SnapshotCreated {
+        vm_id: VmId,
+        snapshot_id: String,
+    },
+    ResourceUsage {
+        vm_id: VmId,
+        cpu: f64,
+        memory: u64,
+    },
+    /// The VM's disk usage outgrew its `max_disk`
+    DiskQuotaExceeded {
+        vm_id: VmId,
+        used_bytes: u64,
+        limit_bytes: u64,
+    },
+    Paused {
+        vm_id: VmId,
+    },
+    Resumed {
+        vm_id: VmId,
+    },
+    /// The VM now has `memory` MB and `cpus` vCPUs
+    Resized {
+        vm_id: VmId,
+        memory: u32,
+        cpus: u32,
+    },
+    /// The VM's health check passed for the first time, failed, or passed
+    /// again after failing
+    HealthChanged {
+        vm_id: VmId,
+        healthy: bool,
+        message: Option<String>,
+    },
+    /// The VM was destroyed and its resources released
+    CleanedUp {
+        vm_id: VmId,
+    },
+    /// The VM stayed over the threshold of the `alert` of the config
+    MetricAlert {
+        vm_id: VmId,
+        alert: String,
+        metric: String,
+        value: f64,
+        threshold: f64,
+    },
+    /// The session the VM runs for moved to `state`
+    SessionStateChanged {
+        vm_id: VmId,
+        session_id: String,
+        state: SessionStateName,
+        message: Option<String>,
+    },
+}
+
+impl VmEvent {
+    pub fn vm_id(&self) -> &VmId {
+        match self {
+            VmEvent::Created { vm_id }
+            | VmEvent::Started { vm_id }
+            | VmEvent::Stopped { vm_id }
+            | VmEvent::Expired { vm_id }
+            | VmEvent::Error { vm_id, .. }
+            | VmEvent::SnapshotCreated { vm_id, .. }
+            | VmEvent::ResourceUsage { vm_id, .. }
+            | VmEvent::DiskQuotaExceeded { vm_id, .. }
+            | VmEvent::Paused { vm_id }
+            | VmEvent::Resumed { vm_id }
+            | VmEvent::Resized { vm_id, .. }
+            | VmEvent::HealthChanged { vm_id, .. }
+            | VmEvent::CleanedUp { vm_id }
+            | VmEvent::MetricAlert { vm_id, .. }
+            | VmEvent::SessionStateChanged { vm_id, .. } => vm_id,
+        }
+    }
+}
+
+pub struct VmManager {
+    instances: RwLock<HashMap<VmId, VmInstance>>,
+    backend_provider: BackendProvider,
+    event_subscribers: RwLock<Vec<Arc<EventSubscriber>>>,
+    /// Every event, for `subscribe`
+    event_bus: broadcast::Sender<VmEvent>,
+    pool: VmPool,
+    /// Tasks serving VMs from the host, such as their egress proxy or TLS
+    /// termination, by VM
+    host_tasks: RwLock<HashMap<VmId, Vec<tokio::task::JoinHandle<()>>>>,
+    /// VMs already reported over their disk quota
+    over_disk_quota: RwLock<HashSet<VmId>>,
+    /// Where the operations done through this manager are recorded
+    audit: Option<AuditLog>,
+    /// Plugins hooked into the lifecycle of the VMs
+    plugins: Option<Arc<RwLock<PluginManager>>>,
+}
+
+#[async_trait]
+pub trait VmEventHandler: Send + Sync {
+    async fn handle(&self, event: VmEvent) -> Result<()>;
+
+    /// Name shown in subscriber queue metrics
+    fn name(&self) -> &str {
+        "handler"
+    }
+}
+
+impl VmManager {
+    /// A manager using `backend_provider`, usually the host's detected
+    /// backends extended by plugins
+    pub fn with_backends(backend_provider: BackendProvider) -> Self {
+        Self {
+            instances: RwLock::new(HashMap::new()),
+            backend_provider,
+            event_subscribers: RwLock::new(Vec::new()),
+            event_bus: broadcast::channel(EVENT_BUS_CAPACITY).0,
+            pool: VmPool::default(),
+            host_tasks: RwLock::new(HashMap::new()),
+            over_disk_quota: RwLock::new(HashSet::new()),
+            audit: None,
+            plugins: None,
+        }
+    }
+
+    /// Record who creates, runs commands in and removes VMs in `audit`
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Let `plugins` change the specs of VMs before they are created and
+    /// tell them when VMs are created, start and stop
+    pub fn with_plugins(mut self, plugins: Arc<RwLock<PluginManager>>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// The plugins hooked into this manager, if any
+    pub fn plugins(&self) -> Option<&Arc<RwLock<PluginManager>>> {
+        self.plugins.as_ref()
+    }
+
+    /// The log operations are recorded in, if any
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    fn audit<T>(&self, record: AuditRecord, result: &Result<T>) {
+        if let Some(audit) = &self.audit {
+            audit.record(record, result);
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(vm_id))]
+    pub async fn create(&self, spec: VmSpec) -> Result<VmInstance> {
+        let vm_id = generate_vm_id();
+        tracing::Span::current().record("vm_id", vm_id.as_str());
+        let record = AuditRecord::new(AuditAction::VmCreate).with_spec(&spec);
+        let result = self.create_as(vm_id.clone(), spec).await;
+        // A prewarmed VM keeps its ID
+        let created = result.as_ref().map_or(vm_id.as_str(), |vm| vm.id.as_str());
+        self.audit(record.with_vm(created), &result);
+        result
+    }
+
+    async fn create_as(&self, vm_id: VmId, mut spec: VmSpec) -> Result<VmInstance> {
+        if let Some(plugins) = &self.plugins {
+            plugins.read().await.mutate_spec(&mut spec).await?;
+        }
+        allocate_ports(&mut spec)?;
+        let backend = self
+            .backend_provider
+            .get_backend(spec.backend.as_deref())
+            .await?;
+
+        tracing::info!("Creating VM {} with spec: {:?}", vm_id, spec);
+        check_spec(&spec, backend.as_ref())?;
+        self.check_ports(&spec).await?;
+
+        if let Some(pooled) = self.pool.take(&spec).await {
+            return self.start_pooled(pooled, spec).await;
+        }
+        self.start_egress(&vm_id, &mut spec).await?;
+        if let Err(e) = network::join(&vm_id, &spec).await {
+            self.stop_host_tasks(&vm_id).await;
+            return Err(e);
+        }
+
+        let vm = VmInstance {
+            id: vm_id.clone(),
+            spec: spec.clone(),
+            state: VmState::Creating,
+            backend,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+
+        // Store instance
+        {
+            let mut instances = self.instances.write().await;
+            instances.insert(vm_id.clone(), vm.clone());
+        }
+
+        // Create VM via backend, then set its command running
+        let result = async {
+            storage::claim_volumes(&vm.id, &vm.spec).await?;
+            if let Some(hook) = &vm.spec.hooks.pre_start {
+                run_host_hook(&vm, "pre-start", hook).await?;
+            }
+            vm.backend.create(&vm).await?;
+            vm.backend.start(&vm).await?;
+            if let Some(hook) = &vm.spec.hooks.post_start {
+                run_guest_hook(&vm, "post-start", hook).await?;
+            }
+            Ok(())
+        }
+        .await;
+        self.finish_create(vm, result).await
+    }
+
+    /// Refuse host ports of `spec` that another VM of this manager forwards
+    /// or that something on the host listens on, before a backend fails on
+    /// them with a less helpful error
+    async fn check_ports(&self, spec: &VmSpec) -> Result<()> {
+        let mut ports: Vec<u16> = spec
+            .ports
+            .keys()
+            .chain(spec.tls_ports.keys())
+            .copied()
+            .collect();
+        ports.sort();
+        let instances = self.instances.read().await;
+        for port in ports {
+            let owner = instances.values().find(|vm| {
+                !matches!(vm.state, VmState::Stopped | VmState::Error { .. })
+                    && (vm.spec.ports.contains_key(&port) || vm.spec.tls_ports.contains_key(&port))
+            });
+            if let Some(vm) = owner {
+                return Err(VortexError::PortInUse {
+                    port,
+                    owner: format!("VM {}", vm.id),
+                });
+            }
+            // Other errors, such as a privileged port, are the backend's to report
+            let address = spec.port_addresses.get(&port).copied();
+            if let Err(e) = network::listen(address, port) {
+                if e.kind() == std::io::ErrorKind::AddrInUse {
+                    return Err(VortexError::PortInUse {
+                        port,
+                        owner: "another process on the host".to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a VM for each of `specs`, at most `concurrency` at a time.
+    /// One VM failing does not stop the others: the results are in the
+    /// order of `specs`, so callers can tell which ones failed.
+    pub async fn create_batch(
+        &self,
+        specs: Vec<VmSpec>,
+        concurrency: usize,
+    ) -> Vec<Result<VmInstance>> {
+        futures::stream::iter(specs)
+            .map(|spec| self.create(spec))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Start the command of `spec` in a prewarmed VM booted for it
+    async fn start_pooled(&self, pooled: VmInstance, spec: VmSpec) -> Result<VmInstance> {
+        tracing::info!("Using prewarmed VM {}", pooled.id);
+        let now = chrono::Utc::now();
+        let vm = VmInstance {
+            spec,
+            state: VmState::Creating,
+            created_at: now,
+            updated_at: now,
+            ..pooled
+        };
+        {
+            let mut instances = self.instances.write().await;
+            instances.insert(vm.id.clone(), vm.clone());
+        }
+
+        let result = async {
+            // Picks up the service name, which the pool's spec lacks
+            network::join(&vm.id, &vm.spec).await?;
+            storage::claim_volumes(&vm.id, &vm.spec).await?;
+            if let Some(hook) = &vm.spec.hooks.pre_start {
+                run_host_hook(&vm, "pre-start", hook).await?;
+            }
+            vm.backend.start(&vm).await?;
+            if let Some(hook) = &vm.spec.hooks.post_start {
+                run_guest_hook(&vm, "post-start", hook).await?;
+            }
+            Ok(())
+        }
+        .await;
+        self.finish_create(vm, result).await
+    }
+
+    /// Terminate TLS on the host ports `vm` takes HTTPS on, for as long as
+    /// it is tracked. A VM whose ports cannot be served is cleaned up.
+    async fn serve_tls(&self, vm: &VmInstance) -> Result<()> {
+        if vm.spec.tls_ports.is_empty() {
+            return Ok(());
+        }
+        #[cfg(feature = "tls")]
+        match crate::tls::serve(&vm.spec).await {
+            Ok(tasks) => {
+                let mut host_tasks = self.host_tasks.write().await;
+                host_tasks.entry(vm.id.clone()).or_default().extend(tasks);
+            }
+            Err(e) => {
+                if let Err(cleanup) = self.cleanup(&vm.id).await {
+                    tracing::warn!("Failed to clean up VM {}: {}", vm.id, cleanup);
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the egress proxy enforcing the network policy of `spec` for the
+    /// VM `vm_id`, and label the spec with its port
+    async fn start_egress(&self, vm_id: &str, spec: &mut VmSpec) -> Result<()> {
+        spec.labels.remove(LABEL_EGRESS_PROXY);
+        if !spec.network_policy.needs_proxy() {
+            return Ok(());
+        }
+        let listener = tokio::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0)).await?;
+        let port = listener.local_addr()?.port();
+        spec.labels
+            .insert(LABEL_EGRESS_PROXY.to_string(), port.to_string());
+        let task = tokio::spawn(network::serve_egress(listener, spec.network_policy.clone()));
+        let mut host_tasks = self.host_tasks.write().await;
+        host_tasks.entry(VmId::new(vm_id)).or_default().push(task);
+        Ok(())
+    }
+
+    async fn stop_host_tasks(&self, vm_id: &str) {
+        if let Some(tasks) = self.host_tasks.write().await.remove(vm_id) {
+            tasks.iter().for_each(|task| task.abort());
+        }
+    }
+
+    /// Record the outcome of creating `vm` and announce it
+    async fn finish_create(&self, vm: VmInstance, result: Result<()>) -> Result<VmInstance> {
+        let vm_id = vm.id.clone();
+        match result {
+            Ok(_) => {
+                let mut updated_vm = vm;
+                updated_vm.state = if updated_vm.spec.health_check.is_some() {
+                    VmState::Starting
+                } else {
+                    VmState::Running
+                };
+                updated_vm.updated_at = chrono::Utc::now();
+
+                {
+                    let mut instances = self.instances.write().await;
+                    instances.insert(vm_id.clone(), updated_vm.clone());
+                }
+                self.serve_tls(&updated_vm).await?;
+
+                self.emit_event(VmEvent::Created {
+                    vm_id: vm_id.clone(),
+                })
+                .await?;
+                self.emit_event(VmEvent::Started { vm_id }).await?;
+
+                Ok(updated_vm)
+            }
+            Err(e) => {
+                network::leave(&vm_id).await;
+                storage::release_volumes(&vm_id).await;
+                self.stop_host_tasks(&vm_id).await;
+                let mut failed_vm = vm;
+                failed_vm.state = VmState::Error {
+                    message: e.to_string(),
+                };
+
+                {
+                    let mut instances = self.instances.write().await;
+                    instances.insert(vm_id.clone(), failed_vm);
+                }
+
+                self.emit_event(VmEvent::Error {
+                    vm_id,
+                    error: e.to_string(),
+                })
+                .await?;
+
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get(&self, vm_id: &VmId) -> Result<Option<VmInstance>> {
+        let instances = self.instances.read().await;
+        Ok(instances.get(vm_id).cloned())
+    }
+
+    pub async fn list(&self) -> Result<Vec<VmInstance>> {
+        // First try to get from our in-memory instances
+        let instances = self.instances.read().await;
+        if !instances.is_empty() {
+            return Ok(instances.values().cloned().collect());
+        }
+
+        // If no in-memory instances, query the backend directly
+        // If no backend is registered, return empty list
+        if !self.backend_provider.has_backends() {
+            return Ok(Vec::new());
+        }
+
+        let backend = self.backend_provider.get_backend(None).await?;
+        let vm_names = backend.list_vms().await?;
+
+        let mut vm_instances = Vec::new();
+        for vm_name in vm_names {
+            // Only include VMs that match our naming pattern
+            if vm_name.starts_with("vortex-") {
+                // Create a minimal VmInstance for display purposes
+                let vm = discovered_instance(&VmId::new(vm_name), Arc::clone(&backend)).await;
+                vm_instances.push(vm);
+            }
+        }
+
+        Ok(vm_instances)
+    }
+
+    /// VMs this manager tracks, without asking the backend for others
+    pub async fn tracked(&self) -> Vec<VmInstance> {
+        let instances = self.instances.read().await;
+        instances.values().cloned().collect()
+    }
+
+    /// One page of VMs matching `query`, ordered by ID
+    pub async fn list_page(&self, query: &ListQuery) -> Result<Page<VmInstance>> {
+        Ok(query.paginate(self.list().await?))
+    }
+
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn stop(&self, vm_id: &VmId) -> Result<()> {
+        let result = self.stop_vm(vm_id).await;
+        self.audit(
+            AuditRecord::new(AuditAction::VmStop).with_vm(vm_id),
+            &result,
+        );
+        result
+    }
+
+    async fn stop_vm(&self, vm_id: &VmId) -> Result<()> {
+        // First check if we have the VM in memory
+        let vm_opt = {
+            let instances = self.instances.read().await;
+            instances.get(vm_id).cloned()
+        };
+
+        let vm = if let Some(vm) = vm_opt {
+            vm
+        } else {
+            // If not in memory, check if it exists in the backend
+            let backend = self.backend_provider.get_backend(None).await?;
+            let vm_names = backend.list_vms().await?;
+
+            if vm_names.contains(&vm_id.to_string()) {
+                // Create a minimal VM instance to use for stopping
+                discovered_instance(vm_id, Arc::clone(&backend)).await
+            } else {
+                return Err(VortexError::VmError {
+                    message: format!("VM {} not found", vm_id),
+                });
+            }
+        };
+
+        if let Some(hook) = &vm.spec.hooks.pre_stop {
+            if let Err(e) = run_host_hook(&vm, "pre-stop", hook).await {
+                tracing::warn!("{}", e);
+            }
+        }
+        vm.backend.stop(&vm).await?;
+        storage::release_volumes(vm_id).await;
+
+        let mut updated_vm = vm;
+        updated_vm.state = VmState::Stopped;
+        updated_vm.updated_at = chrono::Utc::now();
+
+        {
+            let mut instances = self.instances.write().await;
+            instances.insert(updated_vm.id.clone(), updated_vm);
+        }
+
+        self.emit_event(VmEvent::Stopped {
+            vm_id: vm_id.clone(),
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn cleanup(&self, vm_id: &VmId) -> Result<()> {
+        let result = self.cleanup_vm(vm_id).await;
+        self.audit(
+            AuditRecord::new(AuditAction::VmRemove).with_vm(vm_id),
+            &result,
+        );
+        result
+    }
+
+    async fn cleanup_vm(&self, vm_id: &VmId) -> Result<()> {
+        self.stop_host_tasks(vm_id).await;
+
+        // First check if we have the VM in memory
+        let vm_opt = {
+            let mut instances = self.instances.write().await;
+            instances.remove(vm_id)
+        };
+
+        let vm = if let Some(vm) = vm_opt {
+            vm
+        } else {
+            // If not in memory, check if it exists in the backend
+            let backend = self.backend_provider.get_backend(None).await?;
+            let vm_names = backend.list_vms().await?;
+
+            if vm_names.contains(&vm_id.to_string()) {
+                // Create a minimal VM instance to use for cleanup
+                discovered_instance(vm_id, Arc::clone(&backend)).await
+            } else {
+                // VM doesn't exist - consider this a no-op for cleanup
+                return Ok(());
+            }
+        };
+
+        vm.backend.cleanup(&vm).await?;
+        network::leave(vm_id).await;
+        storage::release_volumes(vm_id).await;
+        self.emit_event(VmEvent::CleanedUp {
+            vm_id: vm_id.clone(),
+        })
+        .await
+    }
+
+    /// The VM to attach or exec into: tracked, or found on the default backend
+    async fn running_instance(&self, vm_id: &VmId) -> Result<VmInstance> {
+        // First check if we have the VM in memory
+        let vm_opt = {
+            let instances = self.instances.read().await;
+            instances.get(vm_id).cloned()
+        };
+
+        if let Some(vm) = vm_opt {
+            return Ok(vm);
+        }
+
+        // If not in memory, check if it exists in the backend
+        let backend = self.backend_provider.get_backend(None).await?;
+        let vm_names = backend.list_vms().await?;
+
+        if vm_names.contains(&vm_id.to_string()) {
+            // Create a minimal VM instance to use for attaching
+            Ok(discovered_instance(vm_id, Arc::clone(&backend)).await)
+        } else {
+            Err(VortexError::VmError {
+                message: format!("VM {} not found", vm_id),
+            })
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn attach(&self, vm_id: &VmId) -> Result<()> {
+        let result = async {
+            let vm = self.running_instance(vm_id).await?;
+            vm.backend.attach(&vm).await
+        }
+        .await;
+        self.audit(
+            AuditRecord::new(AuditAction::VmAttach).with_vm(vm_id),
+            &result,
+        );
+        result
+    }
+
+    /// Freeze a running VM without stopping it
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn pause(&self, vm_id: &VmId) -> Result<()> {
+        let vm = self.running_instance(vm_id).await?;
+        vm.backend.pause(&vm).await?;
+        self.set_state(vm_id, VmState::Paused).await;
+        tracing::info!("Paused VM {}", vm_id);
+        // The VM is paused either way; a failing plugin hook must not
+        // make the caller think otherwise
+        if let Err(e) = self
+            .emit_event(VmEvent::Paused {
+                vm_id: vm_id.clone(),
+            })
+            .await
+        {
+            tracing::warn!("{}", e);
+        }
+        Ok(())
+    }
+
+    /// Continue a VM frozen by `pause`
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn resume(&self, vm_id: &VmId) -> Result<()> {
+        let vm = self.running_instance(vm_id).await?;
+        vm.backend.resume(&vm).await?;
+        self.set_state(vm_id, VmState::Running).await;
+        tracing::info!("Resumed VM {}", vm_id);
+        if let Err(e) = self
+            .emit_event(VmEvent::Resumed {
+                vm_id: vm_id.clone(),
+            })
+            .await
+        {
+            tracing::warn!("{}", e);
+        }
+        Ok(())
+    }
+
+    /// Grow or shrink the memory (MB) and vCPUs of a running VM without
+    /// restarting it. The limits of the VM's spec still apply.
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn resize(&self, vm_id: &VmId, memory: Option<u32>, cpus: Option<u32>) -> Result<()> {
+        if memory.is_none() && cpus.is_none() {
+            return Err(VortexError::InvalidInput {
+                field: "resize".to_string(),
+                message: "Give a new memory size, CPU count or both".to_string(),
+            });
+        }
+        let mut vm = self.running_instance(vm_id).await?;
+        vm.spec.memory = memory.unwrap_or(vm.spec.memory);
+        vm.spec.cpus = cpus.unwrap_or(vm.spec.cpus);
+        vm.spec.validate()?;
+
+        vm.backend.resize(&vm, memory, cpus).await?;
+        if let Some(tracked) = self.instances.write().await.get_mut(vm_id) {
+            tracked.spec.memory = vm.spec.memory;
+            tracked.spec.cpus = vm.spec.cpus;
+            tracked.updated_at = chrono::Utc::now();
+        }
+        tracing::info!(
+            "Resized VM {} to {}MB and {} CPU(s)",
+            vm_id,
+            vm.spec.memory,
+            vm.spec.cpus
+        );
+        if let Err(e) = self
+            .emit_event(VmEvent::Resized {
+                vm_id: vm_id.clone(),
+                memory: vm.spec.memory,
+                cpus: vm.spec.cpus,
+            })
+            .await
+        {
+            tracing::warn!("{}", e);
+        }
+        Ok(())
+    }
+
+    /// Block until the command the VM was created with finishes, or fail
+    /// with `ResourceLimitExceeded` once the VM outlives its timeout. The VM
+    /// itself is left as it is; callers stop and clean it up.
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn wait(&self, vm_id: &VmId) -> Result<ExitStatus> {
+        let vm = self.running_instance(vm_id).await?;
+        let wait = vm.backend.wait(&vm);
+        let status = match vm.spec.resource_limits.timeout_seconds {
+            Some(secs) => {
+                let deadline = expiry(vm.created_at, Some(secs))
+                    .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC);
+                let remaining = (deadline - chrono::Utc::now()).to_std().unwrap_or_default();
+                tokio::time::timeout(remaining, wait).await.map_err(|_| {
+                    VortexError::ResourceLimitExceeded {
+                        resource: format!("timeout: VM {} ran for more than {}s", vm_id, secs),
+                    }
+                })??
+            }
+            None => wait.await?,
+        };
+        tracing::info!("Command in VM {} exited with {}", vm_id, status.code);
+        Ok(status)
+    }
+
+    /// Probe `vm_id` once with `probe`, or the health check of its spec, and
+    /// record the outcome in its state. Returns the state the probe left;
+    /// VMs without a health check, or paused or stopped, are not probed.
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn check_health(&self, vm_id: &VmId, probe: Option<&Probe>) -> Result<VmState> {
+        let vm = self.running_instance(vm_id).await?;
+        let Some(probe) = probe.or(vm.spec.health_check.as_ref()) else {
+            return Ok(vm.state);
+        };
+        if !matches!(
+            vm.state,
+            VmState::Starting | VmState::Running | VmState::Unhealthy { .. }
+        ) {
+            return Ok(vm.state);
+        }
+
+        let state = match probe.check(&vm).await {
+            Ok(()) => VmState::Running,
+            // Not ready yet is expected while the guest boots
+            Err(_) if matches!(vm.state, VmState::Starting) => VmState::Starting,
+            Err(e) => VmState::Unhealthy {
+                message: e.to_string(),
+            },
+        };
+        let changed = match (&vm.state, &state) {
+            (VmState::Starting, VmState::Running) => {
+                tracing::info!("VM {} is ready", vm_id);
+                Some((true, None))
+            }
+            (VmState::Running, VmState::Unhealthy { message }) => {
+                tracing::warn!("{}", message);
+                Some((false, Some(message.clone())))
+            }
+            (VmState::Unhealthy { .. }, VmState::Running) => {
+                tracing::info!("VM {} is healthy again", vm_id);
+                Some((true, None))
+            }
+            _ => None,
+        };
+        self.set_state(vm_id, state.clone()).await;
+        if let Some((healthy, message)) = changed {
+            self.emit_event(VmEvent::HealthChanged {
+                vm_id: vm_id.clone(),
+                healthy,
+                message,
+            })
+            .await?;
+        }
+        Ok(state)
+    }
+
+    /// Probe `vm_id` at its probe's interval until it passes, giving up
+    /// after `timeout`
+    pub async fn wait_ready(&self, vm_id: &VmId, probe: &Probe, timeout: Duration) -> Result<()> {
+        let ready = async {
+            loop {
+                if matches!(
+                    self.check_health(vm_id, Some(probe)).await?,
+                    VmState::Running
+                ) {
+                    return Ok(());
+                }
+                tokio::time::sleep(Duration::from_secs(probe.interval_secs.max(1))).await;
+            }
+        };
+        tokio::time::timeout(timeout, ready)
+            .await
+            .map_err(|_| VortexError::VmError {
+                message: format!("VM {} was not ready after {}s", vm_id, timeout.as_secs()),
+            })?
+    }
+
+    /// Keep probing every tracked VM with a health check at its interval, for
+    /// as long as the manager is alive
+    pub fn watch_health(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut last_probed: HashMap<VmId, std::time::Instant> = HashMap::new();
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                let due: Vec<VmId> = {
+                    let instances = manager.instances.read().await;
+                    instances
+                        .values()
+                        .filter_map(|vm| {
+                            let probe = vm.spec.health_check.as_ref()?;
+                            let interval = Duration::from_secs(probe.interval_secs);
+                            match last_probed.get(&vm.id) {
+                                Some(at) if at.elapsed() < interval => None,
+                                _ => Some(vm.id.clone()),
+                            }
+                        })
+                        .collect()
+                };
+                for vm_id in due {
+                    if let Err(e) = manager.check_health(&vm_id, None).await {
+                        tracing::debug!("Could not probe VM {}: {}", vm_id, e);
+                    }
+                    last_probed.insert(vm_id, std::time::Instant::now());
+                }
+            }
+        })
+    }
+
+    /// Stop and clean up every VM past its TTL: tracked VMs, and those of
+    /// runs started by other processes. Returns the IDs of the VMs reaped.
+    pub async fn reap_expired(&self) -> Vec<VmId> {
+        let now = chrono::Utc::now();
+        let (mut expired, tracked): (Vec<VmId>, Vec<VmId>) = {
+            let instances = self.instances.read().await;
+            let expired = instances
+                .values()
+                .filter(|vm| !matches!(vm.state, VmState::Stopped | VmState::Error { .. }))
+                .filter(|vm| vm.expires_at().is_some_and(|at| at <= now))
+                .map(|vm| vm.id.clone())
+                .collect();
+            (expired, instances.keys().cloned().collect())
+        };
+        match run_dir::list_runs() {
+            Ok(runs) => {
+                for (_, record) in runs {
+                    let Some(vm_id) = record.vm_id else {
+                        continue;
+                    };
+                    if record.expires_at.is_some_and(|at| at <= now)
+                        && !tracked.contains(&vm_id)
+                        && self.is_running(&vm_id).await
+                    {
+                        expired.push(vm_id);
+                    }
+                }
+            }
+            Err(e) => tracing::debug!("Could not read run records: {}", e),
+        }
+
+        for vm_id in &expired {
+            tracing::info!("VM {} outlived its TTL or timeout; stopping it", vm_id);
+            if let Err(e) = self
+                .emit_event(VmEvent::Expired {
+                    vm_id: vm_id.clone(),
+                })
+                .await
+            {
+                tracing::warn!("{}", e);
+            }
+            if let Err(e) = self.stop(vm_id).await {
+                tracing::warn!("Failed to stop expired VM {}: {}", vm_id, e);
+            }
+            if let Err(e) = self.cleanup(vm_id).await {
+                tracing::warn!("Failed to clean up expired VM {}: {}", vm_id, e);
+            }
+        }
+        expired
+    }
+
+    /// Keep `size` VMs booted for each spec, replacing any previous targets,
+    /// so `create` can hand them out. The pool is topped up whenever a VM is
+    /// taken and every `PREWARM_INTERVAL`, for as long as the manager is
+    /// alive.
+    pub async fn prewarm(
+        self: &Arc<Self>,
+        targets: Vec<(VmSpec, usize)>,
+    ) -> tokio::task::JoinHandle<()> {
+        self.cleanup_pooled(self.pool.set_targets(targets).await)
+            .await;
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                manager.pool.fill(&manager.backend_provider).await;
+                let taken = manager.pool.taken.clone();
+                drop(manager);
+                let _ = tokio::time::timeout(PREWARM_INTERVAL, taken.notified()).await;
+            }
+        })
+    }
+
+    /// Number of prewarmed VMs waiting to be handed out
+    pub async fn pooled_vms(&self) -> usize {
+        self.pool.ready().await
+    }
+
+    /// Stop prewarming and destroy the VMs waiting in the pool
+    pub async fn drain_pool(&self) {
+        let retired = self.pool.set_targets(Vec::new()).await;
+        self.cleanup_pooled(retired).await;
+    }
+
+    async fn cleanup_pooled(&self, vms: Vec<VmInstance>) {
+        for vm in vms {
+            if let Err(e) = vm.backend.cleanup(&vm).await {
+                tracing::warn!("Failed to clean up prewarmed VM {}: {}", vm.id, e);
+            }
+            network::leave(&vm.id).await;
+        }
+    }
+
+    /// Metrics of every tracked VM that is up, each also reported as
+    /// `VmEvent::ResourceUsage`. VMs whose backend cannot tell are left out.
+    pub async fn resource_usage(&self) -> Vec<(VmId, VmMetrics)> {
+        let mut usage = Vec::new();
+        for vm in self.tracked().await {
+            if matches!(vm.state, VmState::Stopped | VmState::Error { .. }) {
+                continue;
+            }
+            let metrics = match vm.backend.get_metrics(&vm).await {
+                Ok(metrics) => metrics,
+                Err(e) => {
+                    tracing::debug!("No metrics for VM {}: {}", vm.id, e);
+                    continue;
+                }
+            };
+            if let Err(e) = self
+                .emit_event(VmEvent::ResourceUsage {
+                    vm_id: vm.id.clone(),
+                    cpu: metrics.cpu_usage,
+                    memory: metrics.memory_usage,
+                })
+                .await
+            {
+                tracing::warn!("{}", e);
+            }
+            usage.push((vm.id, metrics));
+        }
+        usage
+    }
+
+    /// Running VMs whose disk usage is over their `max_disk`. Each is
+    /// warned about and reported as `VmEvent::DiskQuotaExceeded` once, and
+    /// again only after it got back under budget.
+    pub async fn check_disk_quotas(&self) -> Vec<VmId> {
+        let limited: Vec<(VmInstance, u64)> = {
+            let instances = self.instances.read().await;
+            instances
+                .values()
+                .filter(|vm| !matches!(vm.state, VmState::Stopped | VmState::Error { .. }))
+                .filter_map(|vm| {
+                    let max_mib = vm.spec.resource_limits.max_disk?;
+                    Some((vm.clone(), max_mib * 1024 * 1024))
+                })
+                .collect()
+        };
+        self.over_disk_quota
+            .write()
+            .await
+            .retain(|vm_id| limited.iter().any(|(vm, _)| vm.id == *vm_id));
+
+        let mut over = Vec::new();
+        for (vm, limit_bytes) in limited {
+            // Backends that cannot tell report no usage
+            let used_bytes = match vm.backend.get_metrics(&vm).await {
+                Ok(metrics) => metrics.disk_usage,
+                Err(e) => {
+                    tracing::debug!("No disk usage for VM {}: {}", vm.id, e);
+                    continue;
+                }
+            };
+            if used_bytes <= limit_bytes {
+                self.over_disk_quota.write().await.remove(&vm.id);
+                continue;
+            }
+            over.push(vm.id.clone());
+            if !self.over_disk_quota.write().await.insert(vm.id.clone()) {
+                continue;
+            }
+            tracing::warn!(
+                "VM {} uses {:.1} MB of disk, over its {} MB limit",
+                vm.id,
+                used_bytes as f64 / 1024.0 / 1024.0,
+                limit_bytes / 1024 / 1024
+            );
+            if let Err(e) = self
+                .emit_event(VmEvent::DiskQuotaExceeded {
+                    vm_id: vm.id.clone(),
+                    used_bytes,
+                    limit_bytes,
+                })
+                .await
+            {
+                tracing::warn!("{}", e);
+            }
+        }
+        over
+    }
+
+    /// Reap expired VMs and check disk quotas every `REAP_INTERVAL` for as
+    /// long as the manager is alive
+    pub fn watch_expiry(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(REAP_INTERVAL).await;
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                manager.reap_expired().await;
+                manager.check_disk_quotas().await;
+            }
+        })
+    }
+
+    /// Console output of `vm_id` captured so far, line by line. With
+    /// `follow`, new output is streamed until the VM stops.
+    pub async fn logs(
+        &self,
+        vm_id: &VmId,
+        follow: bool,
+    ) -> Result<impl Stream<Item = Result<String>> + '_> {
+        let path = logs::log_path(vm_id)?;
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|_| VortexError::VmError {
+                message: format!("No logs for VM {}", vm_id),
+            })?;
+        let vm_id = vm_id.clone();
+        Ok(logs::lines(file, follow, move || {
+            let vm_id = vm_id.clone();
+            async move { self.is_running(&vm_id).await }
+        }))
+    }
+
+    /// Whether `vm_id` is still up: tracked and not stopped, or else known to
+    /// the default backend
+    async fn is_running(&self, vm_id: &VmId) -> bool {
+        let tracked = {
+            let instances = self.instances.read().await;
+            instances.get(vm_id).map(|vm| vm.state.clone())
+        };
+        match tracked {
+            Some(state) => !matches!(state, VmState::Stopped | VmState::Error { .. }),
+            None => match self.backend_provider.get_backend(None).await {
+                Ok(backend) => backend
+                    .list_vms()
+                    .await
+                    .is_ok_and(|ids| ids.iter().any(|id| id == vm_id)),
+                Err(_) => false,
+            },
+        }
+    }
+
+    /// Run `command` in a running VM and capture its output
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn exec(
+        &self,
+        vm_id: &VmId,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        let result = self.exec_in(vm_id, command, options).await;
+        let mut record = AuditRecord::new(AuditAction::VmExec)
+            .with_vm(vm_id)
+            .with_command(command);
+        if let Ok(output) = &result {
+            record = record.with_exit_code(output.exit_code);
+        }
+        self.audit(record, &result);
+        result
+    }
+
+    async fn exec_in(
+        &self,
+        vm_id: &VmId,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        if command.is_empty() {
+            return Err(VortexError::InvalidInput {
+                field: "command".to_string(),
+                message: "No command given".to_string(),
+            });
+        }
+        // Backends running through a shell put the names in the script as is
+        if let Some(key) = options.env.keys().find(|key| {
+            key.is_empty()
+                || key.starts_with(|c: char| c.is_ascii_digit())
+                || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }) {
+            return Err(VortexError::InvalidInput {
+                field: "env".to_string(),
+                message: format!("Invalid environment variable name '{}'", key),
+            });
+        }
+        let vm = self.running_instance(vm_id).await?;
+        let exec = vm.backend.exec(&vm, command, options);
+        match options.timeout_secs {
+            Some(secs) => tokio::time::timeout(Duration::from_secs(secs), exec)
+                .await
+                .map_err(|_| VortexError::VmError {
+                    message: format!("Command in {} timed out after {}s", vm_id, secs),
+                })?,
+            None => exec.await,
+        }
+    }
+
+    /// Copy the host file or directory `host` into the running VM `vm_id`
+    /// at `guest`
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn copy_to(&self, vm_id: &VmId, host: &Path, guest: &str) -> Result<()> {
+        let result = async {
+            if !host.exists() {
+                return Err(VortexError::InvalidInput {
+                    field: "path".to_string(),
+                    message: format!("{} does not exist", host.display()),
+                });
+            }
+            let vm = self.running_instance(vm_id).await?;
+            vm.backend.copy_to(&vm, host, guest).await
+        }
+        .await;
+        let record = AuditRecord::new(AuditAction::VmCopyIn)
+            .with_vm(vm_id)
+            .with_paths([host.display().to_string(), format!("{}:{}", vm_id, guest)]);
+        self.audit(record, &result);
+        result
+    }
+
+    /// Copy `guest` out of the running VM `vm_id` to the host path `host`
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn copy_from(&self, vm_id: &VmId, guest: &str, host: &Path) -> Result<()> {
+        let result = async {
+            let vm = self.running_instance(vm_id).await?;
+            vm.backend.copy_from(&vm, guest, host).await
+        }
+        .await;
+        let record = AuditRecord::new(AuditAction::VmCopyOut)
+            .with_vm(vm_id)
+            .with_paths([format!("{}:{}", vm_id, guest), host.display().to_string()]);
+        self.audit(record, &result);
+        result
+    }
+
+    /// Start a VM on a copy of the disk of a running VM, so packages and
+    /// files installed in the source carry over without repeating its setup.
+    /// The clone uses `overrides` as its spec, or else the source's spec
+    /// through `VmSpec::for_clone`, and runs on the source's backend.
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn clone(&self, vm_id: &VmId, overrides: Option<VmSpec>) -> Result<VmInstance> {
+        let result = self.clone_instance(vm_id, overrides).await;
+        let record = match &result {
+            Ok(vm) => AuditRecord::new(AuditAction::VmClone)
+                .with_spec(&vm.spec)
+                .with_vm(&vm.id),
+            Err(_) => AuditRecord::new(AuditAction::VmClone).with_vm(vm_id),
+        };
+        self.audit(record, &result);
+        result
+    }
+
+    async fn clone_instance(&self, vm_id: &VmId, overrides: Option<VmSpec>) -> Result<VmInstance> {
+        let source = self.running_instance(vm_id).await?;
+        let spec = match overrides {
+            Some(spec) => spec,
+            None if self.get(vm_id).await?.is_some() => source.spec.for_clone(vm_id),
+            None => {
+                return Err(VortexError::InvalidInput {
+                    field: "overrides".to_string(),
+                    message: format!(
+                        "The spec of VM {} is unknown to this process; pass one to clone it",
+                        vm_id
+                    ),
+                })
+            }
+        };
+        if let Some(backend) = spec.backend.as_deref() {
+            if backend != source.backend.name() {
+                return Err(VortexError::InvalidInput {
+                    field: "backend".to_string(),
+                    message: format!(
+                        "A clone of {} runs on its {} backend, not {}",
+                        vm_id,
+                        source.backend.name(),
+                        backend
+                    ),
+                });
+            }
+        }
+        let mut spec = spec;
+        allocate_ports(&mut spec)?;
+        check_spec(&spec, source.backend.as_ref())?;
+        self.check_ports(&spec).await?;
+
+        // Flush the source's pending writes so the copied disk has them
+        let sync = ["sync".to_string()];
+        if let Err(e) = source
+            .backend
+            .exec(&source, &sync, &ExecOptions::default())
+            .await
+        {
+            tracing::debug!("Could not sync {} before cloning: {}", vm_id, e);
+        }
+
+        let clone_id = generate_vm_id();
+        tracing::info!("Cloning VM {} as {}", vm_id, clone_id);
+        self.start_egress(&clone_id, &mut spec).await?;
+        let vm = VmInstance {
+            id: clone_id.clone(),
+            spec,
+            state: VmState::Creating,
+            backend: Arc::clone(&source.backend),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        if let Err(e) = network::join(&clone_id, &vm.spec).await {
+            self.stop_host_tasks(&clone_id).await;
+            return Err(e);
+        }
+        self.instances.write().await.insert(clone_id, vm.clone());
+
+        let result = async {
+            storage::claim_volumes(&vm.id, &vm.spec).await?;
+            vm.backend.clone_vm(&source, &vm).await
+        }
+        .await;
+        self.finish_create(vm, result).await
+    }
+
+    /// Save the state of a running VM, which keeps running
+    #[tracing::instrument(skip_all, fields(vm_id = %vm_id))]
+    pub async fn snapshot(&self, vm_id: &VmId) -> Result<SnapshotId> {
+        let vm = self.running_instance(vm_id).await?;
+        let store = SnapshotStore::new()?;
+        let snapshot_id = store.allocate()?;
+
+        self.set_state(vm_id, VmState::Snapshotting).await;
+        let result = vm
+            .backend
+            .snapshot(&vm, &store.state_dir(&snapshot_id))
+            .await;
+        self.set_state(vm_id, vm.state.clone()).await;
+        if let Err(e) = result {
+            if let Err(cleanup) = store.remove(&snapshot_id) {
+                tracing::warn!("Failed to remove snapshot {}: {}", snapshot_id, cleanup);
+            }
+            return Err(e);
+        }
+
+        store.commit(&SnapshotRecord {
+            id: snapshot_id.clone(),
+            vm_id: vm_id.clone(),
+            backend: vm.backend.name().to_string(),
+            spec: vm.spec.clone(),
+            created_at: chrono::Utc::now(),
+        })?;
+        self.emit_event(VmEvent::SnapshotCreated {
+            vm_id: vm_id.clone(),
+            snapshot_id: snapshot_id.to_string(),
+        })
+        .await?;
+        Ok(snapshot_id)
+    }
+
+    /// Save a running VM, which keeps running, to `dest` as a Vortex
+    /// archive that `load` starts again here or on another machine with
+    /// the same backend
+    pub async fn save(&self, vm_id: &VmId, dest: &Path) -> Result<ArchiveManifest> {
+        let snapshot_id = self.snapshot(vm_id).await?;
+        let store = SnapshotStore::new()?;
+        let saved = store.write_archive(&snapshot_id, ArchiveKind::Vm, dest);
+        if let Err(e) = store.remove(&snapshot_id) {
+            tracing::warn!("Failed to remove snapshot {}: {}", snapshot_id, e);
+        }
+        saved
+    }
+
+    /// Start a new VM from an archive written by `save`, or a snapshot
+    /// export. Its snapshot is kept, so it can be restored again.
+    pub async fn load(&self, src: &Path) -> Result<VmInstance> {
+        let record = SnapshotStore::new()?.load_archive(src)?;
+        self.restore(&record.id).await
+    }
+
+    /// Start a new VM from a snapshot, on the backend that took it
+    pub async fn restore(&self, snapshot_id: &SnapshotId) -> Result<VmInstance> {
+        let result = self.restore_snapshot(snapshot_id).await;
+        let record = match &result {
+            Ok(vm) => AuditRecord::new(AuditAction::VmRestore)
+                .with_spec(&vm.spec)
+                .with_vm(&vm.id),
+            Err(_) => AuditRecord::new(AuditAction::VmRestore),
+        };
+        self.audit(record, &result);
+        result
+    }
+
+    async fn restore_snapshot(&self, snapshot_id: &SnapshotId) -> Result<VmInstance> {
+        let store = SnapshotStore::new()?;
+        let record = store.load(snapshot_id)?;
+        if let Some(network) = record.spec.private_network() {
+            return Err(VortexError::InvalidInput {
+                field: "snapshot".to_string(),
+                message: format!(
+                    "Snapshot {} is of a VM on network {}; VMs on private networks cannot be restored",
+                    snapshot_id, network
+                ),
+            });
+        }
+        let backend = self
+            .backend_provider
+            .get_backend(Some(&record.backend))
+            .await?;
+        self.check_ports(&record.spec).await?;
+
+        let vm_id = generate_vm_id();
+        tracing::info!("Restoring snapshot {} as VM {}", snapshot_id, vm_id);
+        let mut spec = record.spec;
+        self.start_egress(&vm_id, &mut spec).await?;
+        let mut vm = VmInstance {
+            id: vm_id.clone(),
+            spec,
+            state: VmState::Restoring,
+            backend,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        self.instances
+            .write()
+            .await
+            .insert(vm_id.clone(), vm.clone());
+
+        if let Err(e) = vm.backend.restore(&vm, &store.state_dir(snapshot_id)).await {
+            self.instances.write().await.remove(&vm_id);
+            self.stop_host_tasks(&vm_id).await;
+            return Err(e);
+        }
+
+        vm.state = if vm.spec.health_check.is_some() {
+            VmState::Starting
+        } else {
+            VmState::Running
+        };
+        vm.updated_at = chrono::Utc::now();
+        self.instances
+            .write()
+            .await
+            .insert(vm_id.clone(), vm.clone());
+        self.serve_tls(&vm).await?;
+        self.emit_event(VmEvent::Created {
+            vm_id: vm_id.clone(),
+        })
+        .await?;
+        self.emit_event(VmEvent::Started { vm_id }).await?;
+        Ok(vm)
+    }
+
+    /// Update the state of a tracked VM; untracked VMs are left alone
+    async fn set_state(&self, vm_id: &VmId, state: VmState) {
+        if let Some(vm) = self.instances.write().await.get_mut(vm_id) {
+            vm.state = state;
+            vm.updated_at = chrono::Utc::now();
+        }
+    }
+
+    /// Every tracked VM, for handing over to an upgraded daemon
+    pub async fn export_instances(&self) -> Vec<VmRecord> {
+        let instances = self.instances.read().await;
+        instances.values().map(VmRecord::from).collect()
+    }
+
+    /// Track VMs started by a previous daemon, returning how many were adopted.
+    /// VMs whose backend is no longer available are left to backend discovery.
+    pub async fn adopt_instances(&self, records: Vec<VmRecord>) -> usize {
+        let mut adopted = 0;
+        for record in records {
+            let backend = match self
+                .backend_provider
+                .get_backend(Some(&record.backend))
+                .await
+            {
+                Ok(backend) if backend.name() == record.backend => backend,
+                _ => {
+                    tracing::warn!(
+                        "Cannot adopt VM {}: backend {} is unavailable",
+                        record.id,
+                        record.backend
+                    );
+                    continue;
+                }
+            };
+            let vm = VmInstance {
+                id: record.id.clone(),
+                spec: record.spec,
+                state: record.state,
+                backend,
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+            };
+            self.instances.write().await.insert(record.id, vm);
+            adopted += 1;
+        }
+        adopted
+    }
+
+    /// Deliver events to `handler` through its own bounded queue
+    pub async fn add_event_handler(
+        &self,
+        handler: Box<dyn VmEventHandler>,
+        config: EventQueueConfig,
+    ) {
+        let mut subscribers = self.event_subscribers.write().await;
+        subscribers.push(EventSubscriber::spawn(handler, config));
+    }
+
+    /// Queue metrics for every event subscriber
+    pub async fn event_subscriber_stats(&self) -> Vec<SubscriberStats> {
+        let subscribers = self.event_subscribers.read().await;
+        subscribers.iter().map(|s| s.stats()).collect()
+    }
+
+    /// Wait up to `timeout` for queued events to be handled, e.g. before exit
+    pub async fn flush_events(&self, timeout: Duration) {
+        let subscribers = self.event_subscribers.read().await;
+        let drain = async {
+            for subscriber in subscribers.iter() {
+                subscriber.drain().await;
+            }
+        };
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            tracing::warn!("Timed out flushing VM events");
+        }
+    }
+
+    /// A receiver of every event from now on. Receivers that fall more
+    /// than a few hundred events behind miss the oldest, and are told so
+    /// with `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<VmEvent> {
+        self.event_bus.subscribe()
+    }
+
+    /// Tell the subscribers that the session `session_id`, running on the
+    /// VM `vm_id`, moved to `state`
+    pub async fn session_state_changed(
+        &self,
+        vm_id: &VmId,
+        session_id: &str,
+        state: SessionStateName,
+        message: Option<String>,
+    ) -> Result<()> {
+        self.emit_event(VmEvent::SessionStateChanged {
+            vm_id: vm_id.clone(),
+            session_id: session_id.to_string(),
+            state,
+            message,
+        })
+        .await
+    }
+
+    pub(crate) async fn emit_event(&self, event: VmEvent) -> Result<()> {
+        // No receivers is not an error
+        let _ = self.event_bus.send(event.clone());
+        let subscribers = self.event_subscribers.read().await;
+
+        for subscriber in subscribers.iter() {
+            subscriber.push(event.clone()).await;
+        }
+        drop(subscribers);
+
+        self.call_plugins(&event).await
+    }
+
+    /// Run the hooks of the plugins for `event`, after it happened
+    async fn call_plugins(&self, event: &VmEvent) -> Result<()> {
+        let Some(plugins) = &self.plugins else {
+            return Ok(());
+        };
+        let hook = match event {
+            VmEvent::Created { .. } => PluginHook::VmPostCreate,
+            VmEvent::Started { .. } => PluginHook::VmPostStart,
+            VmEvent::Stopped { .. } => PluginHook::VmPostStop,
+            _ => return Ok(()),
+        };
+        let Some(vm) = self.get(event.vm_id()).await? else {
+            return Ok(());
+        };
+        plugins
+            .read()
+            .await
+            .call_hook(hook, PluginContext::VmInstance(vm))
+            .await
+    }
+}
+
+/// Run the `name` hook of `vm` on the host
+async fn run_host_hook(vm: &VmInstance, name: &str, command: &str) -> Result<()> {
+    tracing::info!("Running {} hook for VM {}", name, vm.id);
+    let output = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("VORTEX_HOOK", name)
+        .env("VORTEX_VM_ID", vm.id.as_str())
+        .env("VORTEX_VM_IMAGE", &vm.spec.image)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(VortexError::VmError {
+            message: format!(
+                "The {} hook of VM {} exited with {}: {}",
+                name,
+                vm.id,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Run the `name` hook of `vm` in the guest
+async fn run_guest_hook(vm: &VmInstance, name: &str, command: &str) -> Result<()> {
+    tracing::info!("Running {} hook in VM {}", name, vm.id);
+    let argv = ["sh".to_string(), "-c".to_string(), command.to_string()];
+    let result = vm.backend.exec(vm, &argv, &ExecOptions::default()).await?;
+    if !result.success() {
+        return Err(VortexError::VmError {
+            message: format!(
+                "The {} hook of VM {} exited with {}: {}",
+                name,
+                vm.id,
+                result.exit_code,
+                result.stderr.trim()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Move the ports `spec` publishes into `ports`, each forwarded from a free
+/// host port. The listeners probing for free ports are held until all are
+/// picked, so the picks differ from each other and from the ports taken.
+fn allocate_ports(spec: &mut VmSpec) -> Result<()> {
+    // The plaintext behind HTTPS ports needs a forward of its own
+    let mut tls_guests: Vec<u16> = spec.tls_ports.values().copied().collect();
+    tls_guests.sort();
+    for guest in tls_guests {
+        if !spec.ports.values().any(|forwarded| *forwarded == guest)
+            && !spec.publish.contains(&guest)
+        {
+            spec.publish.push(guest);
+        }
+    }
+
+    let mut probes = Vec::new();
+    for guest in std::mem::take(&mut spec.publish) {
+        let host = loop {
+            let probe = std::net::TcpListener::bind(("0.0.0.0", 0)).map_err(|e| {
+                VortexError::NetworkError {
+                    message: format!("Failed to find a free host port for port {}: {}", guest, e),
+                }
+            })?;
+            let port = probe.local_addr()?.port();
+            probes.push(probe);
+            if !spec.ports.contains_key(&port) && !spec.tls_ports.contains_key(&port) {
+                break port;
+            }
+        };
+        spec.ports.insert(host, guest);
+    }
+    Ok(())
+}
+
+/// Check that `spec` is valid and that `backend` can run it
+fn check_spec(spec: &VmSpec, backend: &dyn Backend) -> Result<()> {
+    spec.validate()?;
+
+    if let Some(gpu) = spec.gpus.iter().find(|gpu| !backend.supports_gpu(gpu)) {
+        return Err(VortexError::InvalidInput {
+            field: "gpus".to_string(),
+            message: format!(
+                "The {} backend cannot attach {} GPUs",
+                backend.name(),
+                gpu.kind()
+            ),
+        });
+    }
+
+    if spec.private_network().is_some() && !backend.supports_private_networks() {
+        return Err(VortexError::InvalidInput {
+            field: "network_config".to_string(),
+            message: format!(
+                "The {} backend cannot attach VMs to private networks",
+                backend.name()
+            ),
+        });
+    }
+
+    if spec.network_policy.needs_proxy() && !backend.supports_egress_proxy() {
+        return Err(VortexError::InvalidInput {
+            field: "network_policy".to_string(),
+            message: format!(
+                "The {} backend cannot enforce the network policy {}",
+                backend.name(),
+                spec.network_policy
+            ),
+        });
+    }
+
+    // HTTPS ports are Vortex's own listeners
+    let forwards_on_address = spec
+        .port_addresses
+        .keys()
+        .any(|port| spec.ports.contains_key(port));
+    if forwards_on_address && !backend.supports_port_addresses() {
+        return Err(VortexError::InvalidInput {
+            field: "port_addresses".to_string(),
+            message: format!(
+                "The {} backend cannot forward ports on a given address",
+                backend.name()
+            ),
+        });
+    }
+
+    let mut mechanisms: Vec<ShareMechanism> = spec
+        .share_mechanisms
+        .iter()
+        .filter(|(guest, _)| spec.volumes.values().any(|volume| volume == *guest))
+        .map(|(_, mechanism)| *mechanism)
+        .collect();
+    mechanisms.sort_by_key(|mechanism| mechanism.to_string());
+    if let Some(mechanism) = mechanisms
+        .into_iter()
+        .find(|mechanism| !backend.supports_share(*mechanism))
+    {
+        return Err(VortexError::InvalidInput {
+            field: "share_mechanisms".to_string(),
+            message: format!(
+                "The {} backend cannot share volumes with {}",
+                backend.name(),
+                mechanism
+            ),
+        });
+    }
+
+    if spec.network_disabled() && !backend.supports_network_isolation() {
+        return Err(VortexError::InvalidInput {
+            field: "network_config".to_string(),
+            message: format!(
+                "The {} backend cannot run a VM without a network",
+                backend.name()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Build a minimal instance for a VM found in the backend but not tracked in memory.
+/// The spec is unknown, so defaults are used for display and lifecycle calls.
+async fn discovered_instance(vm_id: &VmId, backend: Arc<dyn Backend>) -> VmInstance {
+    let mut vm = VmInstance {
+        id: vm_id.clone(),
+        spec: VmSpec {
+            image: "unknown".to_string(),
+            ..VmSpec::default()
+        },
+        state: VmState::Running,
+        backend,
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+    };
+    // Untracked, so the VM is as old as its processes
+    if let Some(started) = vm.backend.started_at(&vm).await {
+        vm.created_at = started;
+    }
+    vm
+}
+
+pub(crate) fn generate_vm_id() -> VmId {
+    let uuid_str = Uuid::new_v4().to_string();
+    VmId::new(format!("vortex-{}", &uuid_str[..8]))
+}
@@ -0,0 +1,728 @@
+//! Third-party plugins run as WebAssembly in a wasmtime sandbox.
+//!
+//! A plugin repository opts in by shipping `plugin.wasm`, or by naming a
+//! module in its `plugin.toml` (`module = "hooks.wat"`; text works too).
+//! Nothing the host can do is reachable from the module unless the user
+//! granted it with `vortex plugin grant`: hooks that are not granted are
+//! never called, and every host function checks its grant before acting.
+//!
+//! The module exports `memory`, `vortex_alloc(len) -> ptr` and
+//! `vortex_on_hook(hook_ptr, hook_len, ctx_ptr, ctx_len) -> i32`, which
+//! receives the hook name and a JSON description of the VM and returns 0 on
+//! success. It may import from the `vortex` module:
+//!
+//! - `log(level, ptr, len) -> i32` with `api:log`; level 0-3 is error to debug
+//! - `read_file(path_ptr, path_len, buf_ptr, buf_cap) -> i64` with a `read:`
+//!   or `write:` grant covering the path; returns the file's full length
+//! - `write_file(path_ptr, path_len, data_ptr, data_len) -> i32` with `write:`
+//! - `net_request(addr_ptr, addr_len, data_ptr, data_len, buf_ptr, buf_cap)
+//!   -> i64` with `net:<addr>`: sends the data over TCP and reads the reply
+//!
+//! - `set_spec(ptr, len) -> i32` in `vm-pre-create`, which receives the
+//!   JSON spec of a VM about to be created, to replace it
+//!
+//! Host functions return `DENIED` (-1) without the grant and `FAILED` (-2)
+//! when the operation itself fails. Each hook runs in a fresh instance with
+//! bounded memory and fuel, so a plugin cannot keep state between calls, loop
+//! forever or exhaust the host.
+//!
+//! A plugin can instead be a WebAssembly component of the `plugin` world in
+//! `wit/plugin.wit`, built with any toolchain that targets WIT. It exports
+//! `mutate-spec` and `on-event` and may only import `log`.
+//!
+//! Changing specs needs `hook:vm-pre-create` and `api:vm-spec`. A changed
+//! spec may not touch the lifecycle hooks, network policy or GPUs, nor mount
+//! host paths the plugin has no `write:` grant for, so that a plugin cannot
+//! reach further through the VMs it changes than it can itself.
+
+use crate::config::PluginConfig;
+use crate::error::{Result, VortexError};
+use crate::plugin::{Plugin, PluginApi, PluginGrants, PluginHook, PluginManager, PluginMetadata};
+use crate::vm::{VmInstance, VmSpec};
+use async_trait::async_trait;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use wasmtime::component::{self, Component};
+use wasmtime::{Caller, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+mod bindings {
+    wasmtime::component::bindgen!({ path: "wit", world: "plugin" });
+}
+
+use bindings::vortex::plugin::host::Level;
+
+/// Returned by host functions when the capability was not granted
+pub const DENIED: i64 = -1;
+/// Returned by host functions when a granted operation fails
+pub const FAILED: i64 = -2;
+
+const DEFAULT_MODULE: &str = "plugin.wasm";
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+/// Components instantiate a few core modules each
+const MAX_INSTANCES: usize = 16;
+/// Roughly a few hundred million instructions per hook
+const FUEL_PER_HOOK: u64 = 500_000_000;
+const NET_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(serde::Deserialize)]
+struct Manifest {
+    module: Option<String>,
+}
+
+struct HostState {
+    plugin: String,
+    grants: PluginGrants,
+    limits: StoreLimits,
+    /// Spec a module passed to `set_spec`
+    spec: Option<String>,
+}
+
+/// A plugin's compiled code: a core module using the ABI above, or a
+/// component of the `plugin` world
+enum Code {
+    Module(Module, Linker<HostState>),
+    Component(Component, component::Linker<HostState>),
+}
+
+struct Sandbox {
+    name: String,
+    grants: PluginGrants,
+    engine: Engine,
+    code: Code,
+}
+
+pub struct WasmPlugin {
+    metadata: PluginMetadata,
+    sandbox: Arc<Sandbox>,
+}
+
+impl std::fmt::Debug for WasmPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("metadata", &self.metadata)
+            .field("grants", &self.sandbox.grants)
+            .finish()
+    }
+}
+
+fn plugin_error(name: &str, message: impl std::fmt::Display) -> VortexError {
+    VortexError::PluginError {
+        message: format!("{}: {}", name, message),
+    }
+}
+
+/// The plugin's WebAssembly module: the one named by `plugin.toml`, or else
+/// `plugin.wasm` if the repository has one
+pub fn module_path(plugin_dir: &Path) -> Result<Option<PathBuf>> {
+    let manifest = match std::fs::read_to_string(plugin_dir.join("plugin.toml")) {
+        Ok(content) => toml::from_str(&content).map_err(|e| VortexError::PluginError {
+            message: format!("Invalid plugin.toml in {}: {}", plugin_dir.display(), e),
+        })?,
+        Err(_) => Manifest { module: None },
+    };
+    let module = match manifest.module {
+        Some(module) => module,
+        None if plugin_dir.join(DEFAULT_MODULE).is_file() => DEFAULT_MODULE.to_string(),
+        None => return Ok(None),
+    };
+
+    // The module must live inside the plugin's own directory
+    let dir = plugin_dir.canonicalize()?;
+    let path = dir
+        .join(&module)
+        .canonicalize()
+        .map_err(|e| VortexError::PluginError {
+            message: format!("Plugin module {} not found: {}", module, e),
+        })?;
+    if !path.starts_with(&dir) {
+        return Err(VortexError::PluginError {
+            message: format!("Plugin module {} is outside {}", module, dir.display()),
+        });
+    }
+    Ok(Some(path))
+}
+
+impl WasmPlugin {
+    /// Compile `module` and prepare it to run with `grants`
+    pub fn load(
+        name: &str,
+        config: &PluginConfig,
+        module: &Path,
+        grants: PluginGrants,
+    ) -> Result<Self> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(|e| plugin_error(name, e))?;
+        let bytes = std::fs::read(module)?;
+        let code = if is_component(&bytes) {
+            let component = Component::new(&engine, &bytes).map_err(|e| plugin_error(name, e))?;
+            let mut linker = component::Linker::new(&engine);
+            bindings::Plugin::add_to_linker(&mut linker, |state: &mut HostState| state)
+                .map_err(|e| plugin_error(name, e))?;
+            Code::Component(component, linker)
+        } else {
+            let module = Module::new(&engine, &bytes).map_err(|e| plugin_error(name, e))?;
+            let linker = host_functions(&engine).map_err(|e| plugin_error(name, e))?;
+            Code::Module(module, linker)
+        };
+
+        Ok(Self {
+            metadata: PluginMetadata {
+                name: name.to_string(),
+                version: config.version.clone(),
+                description: config.description.clone(),
+                author: config.author.clone(),
+                hooks: grants.hooks.clone(),
+            },
+            sandbox: Arc::new(Sandbox {
+                name: name.to_string(),
+                grants,
+                engine,
+                code,
+            }),
+        })
+    }
+
+    async fn run_hook(&self, hook: PluginHook, vm: &VmInstance) -> Result<()> {
+        let context = hook_context(&self.sandbox.grants, vm);
+        self.call(hook, context).await.map(|_| ())
+    }
+
+    /// Call `hook` in a blocking task, returning the spec the plugin set
+    async fn call(&self, hook: PluginHook, context: String) -> Result<Option<String>> {
+        let sandbox = Arc::clone(&self.sandbox);
+        tokio::task::spawn_blocking(move || sandbox.call(hook.name(), &context))
+            .await
+            .map_err(|e| plugin_error(&self.sandbox.name, e))?
+    }
+}
+
+/// Whether `bytes` is a component rather than a core module, in binary or
+/// text form
+fn is_component(bytes: &[u8]) -> bool {
+    match bytes.strip_prefix(b"\0asm") {
+        // Components are layer 1 of the binary format
+        Some(rest) => rest.get(2..4) == Some(&[0x01, 0x00]),
+        None => String::from_utf8_lossy(bytes)
+            .trim_start()
+            .starts_with("(component"),
+    }
+}
+
+/// Refuse changes to a spec that would reach beyond the sandbox: host
+/// commands, network access, devices and host paths not granted
+fn check_mutation(
+    grants: &PluginGrants,
+    before: &VmSpec,
+    after: &VmSpec,
+) -> std::result::Result<(), String> {
+    fn json<T: serde::Serialize>(value: &T) -> Option<serde_json::Value> {
+        serde_json::to_value(value).ok()
+    }
+    for (what, unchanged) in [
+        ("lifecycle hooks", json(&before.hooks) == json(&after.hooks)),
+        (
+            "network policy",
+            json(&before.network_policy) == json(&after.network_policy),
+        ),
+        ("GPUs", json(&before.gpus) == json(&after.gpus)),
+    ] {
+        if !unchanged {
+            return Err(format!("plugins may not change the {}", what));
+        }
+    }
+    for (host, guest) in &after.volumes {
+        if before.volumes.get(host) != Some(guest) && !grants.allows_write(host) {
+            return Err(format!(
+                "mounting {} needs write:{}",
+                host.display(),
+                host.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// What the plugin learns about a VM; the full spec needs `api:vm-spec`
+fn hook_context(grants: &PluginGrants, vm: &VmInstance) -> String {
+    let mut context = serde_json::json!({
+        "id": vm.id,
+        "image": vm.spec.image,
+        "state": vm.state,
+        "memory": vm.spec.memory,
+        "cpus": vm.spec.cpus,
+        "backend": vm.backend.name(),
+    });
+    if grants.allows_api(PluginApi::VmSpec) {
+        context["spec"] = serde_json::to_value(&vm.spec).unwrap_or_default();
+    }
+    context.to_string()
+}
+
+impl Sandbox {
+    /// Run `hook` in a fresh instance, returning the spec the plugin set
+    /// if it changed one
+    fn call(&self, hook: &str, context: &str) -> Result<Option<String>> {
+        let error = |e: wasmtime::Error| plugin_error(&self.name, e);
+
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                plugin: self.name.clone(),
+                grants: self.grants.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .instances(MAX_INSTANCES)
+                    .build(),
+                spec: None,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_HOOK).map_err(error)?;
+
+        let (module, linker) = match &self.code {
+            Code::Module(module, linker) => (module, linker),
+            Code::Component(component, linker) => {
+                let plugin =
+                    bindings::Plugin::instantiate(&mut store, component, linker).map_err(error)?;
+                let outcome = if hook == PluginHook::VmPreCreate.name() {
+                    plugin
+                        .call_mutate_spec(&mut store, context)
+                        .map_err(error)?
+                        .map(Some)
+                } else {
+                    plugin
+                        .call_on_event(&mut store, hook, context)
+                        .map_err(error)?
+                        .map(|()| None)
+                };
+                return outcome.map_err(|message| {
+                    plugin_error(&self.name, format!("{} failed: {}", hook, message))
+                });
+            }
+        };
+        let instance = linker.instantiate(&mut store, module).map_err(error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| plugin_error(&self.name, "module exports no memory"))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "vortex_alloc")
+            .map_err(error)?;
+        let on_hook = instance
+            .get_typed_func::<(i32, i32, i32, i32), i32>(&mut store, "vortex_on_hook")
+            .map_err(error)?;
+
+        let pass = |store: &mut Store<HostState>, bytes: &[u8]| -> Result<(i32, i32)> {
+            let len = i32::try_from(bytes.len()).map_err(|e| plugin_error(&self.name, e))?;
+            let ptr = alloc.call(&mut *store, len).map_err(error)?;
+            memory
+                .write(&mut *store, ptr as u32 as usize, bytes)
+                .map_err(|e| plugin_error(&self.name, e))?;
+            Ok((ptr, len))
+        };
+        let (hook_ptr, hook_len) = pass(&mut store, hook.as_bytes())?;
+        let (ctx_ptr, ctx_len) = pass(&mut store, context.as_bytes())?;
+
+        match on_hook
+            .call(&mut store, (hook_ptr, hook_len, ctx_ptr, ctx_len))
+            .map_err(error)?
+        {
+            0 => Ok(store.into_data().spec),
+            code => Err(plugin_error(
+                &self.name,
+                format!("{} returned {}", hook, code),
+            )),
+        }
+    }
+}
+
+fn guest_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let mut bytes = vec![0; usize::try_from(len).ok()?];
+    memory
+        .read(&*caller, ptr as u32 as usize, &mut bytes)
+        .ok()?;
+    Some(bytes)
+}
+
+fn guest_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(guest_bytes(caller, ptr, len)?).ok()
+}
+
+/// Copy as much of `data` as fits into the guest buffer, returning its length
+fn reply(caller: &mut Caller<'_, HostState>, data: &[u8], ptr: i32, cap: i32) -> i64 {
+    let Some(memory) = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+    else {
+        return FAILED;
+    };
+    let n = data.len().min(usize::try_from(cap).unwrap_or(0));
+    if memory
+        .write(&mut *caller, ptr as u32 as usize, &data[..n])
+        .is_err()
+    {
+        return FAILED;
+    }
+    data.len() as i64
+}
+
+fn denied(caller: &Caller<'_, HostState>, what: &str) -> i64 {
+    tracing::warn!("Plugin {} denied {}", caller.data().plugin, what);
+    DENIED
+}
+
+impl bindings::vortex::plugin::host::Host for HostState {
+    fn log(&mut self, level: Level, message: String) {
+        if !self.grants.allows_api(PluginApi::Log) {
+            tracing::warn!("Plugin {} denied api:log", self.plugin);
+            return;
+        }
+        let plugin = &self.plugin;
+        match level {
+            Level::Error => tracing::error!("[plugin {}] {}", plugin, message),
+            Level::Warn => tracing::warn!("[plugin {}] {}", plugin, message),
+            Level::Info => tracing::info!("[plugin {}] {}", plugin, message),
+            Level::Debug => tracing::debug!("[plugin {}] {}", plugin, message),
+        }
+    }
+}
+
+fn host_functions(engine: &Engine) -> wasmtime::Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap(
+        "vortex",
+        "log",
+        |mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| -> i32 {
+            if !caller.data().grants.allows_api(PluginApi::Log) {
+                return denied(&caller, "api:log") as i32;
+            }
+            let Some(message) = guest_string(&mut caller, ptr, len) else {
+                return FAILED as i32;
+            };
+            let plugin = &caller.data().plugin;
+            match level {
+                0 => tracing::error!("[plugin {}] {}", plugin, message),
+                1 => tracing::warn!("[plugin {}] {}", plugin, message),
+                2 => tracing::info!("[plugin {}] {}", plugin, message),
+                _ => tracing::debug!("[plugin {}] {}", plugin, message),
+            }
+            0
+        },
+    )?;
+
+    linker.func_wrap(
+        "vortex",
+        "read_file",
+        |mut caller: Caller<'_, HostState>, path_ptr: i32, path_len: i32, buf: i32, cap: i32| {
+            let Some(path) = guest_string(&mut caller, path_ptr, path_len) else {
+                return FAILED;
+            };
+            if !caller.data().grants.allows_read(Path::new(&path)) {
+                return denied(&caller, &format!("read:{}", path));
+            }
+            match std::fs::read(&path) {
+                Ok(data) => reply(&mut caller, &data, buf, cap),
+                Err(_) => FAILED,
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "vortex",
+        "write_file",
+        |mut caller: Caller<'_, HostState>,
+         path_ptr: i32,
+         path_len: i32,
+         data_ptr: i32,
+         data_len: i32|
+         -> i32 {
+            let Some(path) = guest_string(&mut caller, path_ptr, path_len) else {
+                return FAILED as i32;
+            };
+            if !caller.data().grants.allows_write(Path::new(&path)) {
+                return denied(&caller, &format!("write:{}", path)) as i32;
+            }
+            let Some(data) = guest_bytes(&mut caller, data_ptr, data_len) else {
+                return FAILED as i32;
+            };
+            match std::fs::write(&path, data) {
+                Ok(()) => 0,
+                Err(_) => FAILED as i32,
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "vortex",
+        "net_request",
+        |mut caller: Caller<'_, HostState>,
+         addr_ptr: i32,
+         addr_len: i32,
+         data_ptr: i32,
+         data_len: i32,
+         buf: i32,
+         cap: i32| {
+            let Some(address) = guest_string(&mut caller, addr_ptr, addr_len) else {
+                return FAILED;
+            };
+            if !caller.data().grants.allows_connect(&address) {
+                return denied(&caller, &format!("net:{}", address));
+            }
+            let Some(data) = guest_bytes(&mut caller, data_ptr, data_len) else {
+                return FAILED;
+            };
+            match tcp_request(&address, &data) {
+                Ok(response) => reply(&mut caller, &response, buf, cap),
+                Err(_) => FAILED,
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "vortex",
+        "set_spec",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i32 {
+            let Some(spec) = guest_string(&mut caller, ptr, len) else {
+                return FAILED as i32;
+            };
+            caller.data_mut().spec = Some(spec);
+            0
+        },
+    )?;
+
+    Ok(linker)
+}
+
+fn tcp_request(address: &str, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let target = address.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "address did not resolve")
+    })?;
+    let mut stream = TcpStream::connect_timeout(&target, NET_TIMEOUT)?;
+    stream.set_read_timeout(Some(NET_TIMEOUT))?;
+    stream.set_write_timeout(Some(NET_TIMEOUT))?;
+    stream.write_all(data)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut response = Vec::new();
+    stream
+        .take(MAX_MEMORY_BYTES as u64)
+        .read_to_end(&mut response)?;
+    Ok(response)
+}
+
+#[async_trait]
+impl Plugin for WasmPlugin {
+    fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    async fn initialize(&mut self, _settings: &serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_spec_mutate(&self, spec: &mut VmSpec) -> Result<()> {
+        let name = &self.sandbox.name;
+        // The plugin sees the whole spec, environment variables included
+        if !self.sandbox.grants.allows_api(PluginApi::VmSpec) {
+            tracing::warn!("Plugin {} needs api:vm-spec to change VM specs", name);
+            return Ok(());
+        }
+        let context = serde_json::to_string(spec).map_err(|e| plugin_error(name, e))?;
+        let Some(changed) = self.call(PluginHook::VmPreCreate, context).await? else {
+            return Ok(());
+        };
+        let changed: VmSpec = serde_json::from_str(&changed)
+            .map_err(|e| plugin_error(name, format!("invalid spec: {}", e)))?;
+        check_mutation(&self.sandbox.grants, spec, &changed).map_err(|e| plugin_error(name, e))?;
+        *spec = changed;
+        Ok(())
+    }
+
+    async fn on_vm_create(&self, vm: &VmInstance) -> Result<()> {
+        self.run_hook(PluginHook::VmPostCreate, vm).await
+    }
+
+    async fn on_vm_start(&self, vm: &VmInstance) -> Result<()> {
+        self.run_hook(PluginHook::VmPostStart, vm).await
+    }
+
+    async fn on_vm_stop(&self, vm: &VmInstance) -> Result<()> {
+        self.run_hook(PluginHook::VmPostStop, vm).await
+    }
+}
+
+/// Register every enabled plugin in `~/.vortex/plugins` that ships a module.
+/// A plugin that fails to load is skipped with a warning.
+pub async fn register_installed(
+    manager: &mut PluginManager,
+    plugins: &std::collections::HashMap<String, PluginConfig>,
+) {
+    let Some(plugins_dir) = crate::plugin::plugins_dir() else {
+        return;
+    };
+    for (name, config) in plugins.iter().filter(|(_, config)| config.enabled) {
+        let module = match module_path(&plugins_dir.join(name)) {
+            Ok(Some(module)) => module,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!("Skipping plugin {}: {}", name, e);
+                continue;
+            }
+        };
+        match WasmPlugin::load(name, config, &module, config.grants.clone()) {
+            Ok(plugin) => {
+                if let Err(e) = manager.register_plugin(Box::new(plugin)).await {
+                    tracing::warn!("Skipping plugin {}: {}", name, e);
+                }
+            }
+            Err(e) => tracing::warn!("Skipping plugin {}: {}", name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Capability;
+
+    const MODULE: &str = r#"
+        (module
+          (import "vortex" "log" (func $log (param i32 i32 i32) (result i32)))
+          (import "vortex" "write_file" (func $write (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (data (i32.const 0) "/etc/vortex-test")
+          (func (export "vortex_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "vortex_on_hook") (param i32 i32 i32 i32) (result i32)
+            (drop (call $log (i32.const 2) (local.get 2) (local.get 3)))
+            ;; Writing outside the grants must be refused
+            (call $write (i32.const 0) (i32.const 16) (local.get 2) (local.get 3))))
+    "#;
+
+    #[tokio::test]
+    async fn test_ungranted_capabilities_are_denied() {
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().join("plugin.wat");
+        std::fs::write(&module, MODULE).unwrap();
+        std::fs::write(dir.path().join("plugin.toml"), "module = \"plugin.wat\"\n").unwrap();
+        assert_eq!(
+            module_path(dir.path()).unwrap(),
+            Some(module.canonicalize().unwrap())
+        );
+
+        let grants = granted(&["hook:vm-post-start", "api:log"]);
+        let plugin = WasmPlugin::load("test", &config(&grants), &module, grants).unwrap();
+        assert_eq!(plugin.metadata().hooks, vec![PluginHook::VmPostStart]);
+
+        let sandbox = Arc::clone(&plugin.sandbox);
+        let err = tokio::task::spawn_blocking(move || sandbox.call("vm-post-start", "{}"))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains("returned -1"));
+        assert!(!Path::new("/etc/vortex-test").exists());
+    }
+
+    #[tokio::test]
+    async fn test_components_change_specs_within_their_grants() {
+        let dir = tempfile::tempdir().unwrap();
+        let grants = granted(&["hook:vm-pre-create", "api:vm-spec"]);
+        let original = VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        };
+        let load = |answer: &VmSpec| {
+            let path = dir.path().join("plugin.wat");
+            std::fs::write(&path, component(answer)).unwrap();
+            WasmPlugin::load("test", &config(&grants), &path, grants.clone()).unwrap()
+        };
+
+        let mut labelled = original.clone();
+        labelled
+            .labels
+            .insert("inventory".to_string(), "registered".to_string());
+        let mut spec = original.clone();
+        load(&labelled).on_spec_mutate(&mut spec).await.unwrap();
+        assert_eq!(spec.labels["inventory"], "registered");
+
+        // Mounting the host's root is beyond the plugin's grants
+        let mut escaping = original.clone();
+        escaping
+            .volumes
+            .insert(PathBuf::from("/"), PathBuf::from("/host"));
+        let mut spec = original.clone();
+        let err = load(&escaping).on_spec_mutate(&mut spec).await.unwrap_err();
+        assert!(err.to_string().contains("needs write:/"));
+        assert!(spec.volumes.is_empty());
+    }
+
+    fn granted(capabilities: &[&str]) -> PluginGrants {
+        let mut grants = PluginGrants::default();
+        for capability in capabilities {
+            grants.grant(capability.parse::<Capability>().unwrap());
+        }
+        grants
+    }
+
+    fn config(grants: &PluginGrants) -> PluginConfig {
+        PluginConfig {
+            enabled: true,
+            version: "0.1.0".to_string(),
+            source_repo: "https://example.com/plugin.git".to_string(),
+            description: "test".to_string(),
+            author: "test".to_string(),
+            grants: grants.clone(),
+            ..PluginConfig::default()
+        }
+    }
+
+    /// A component whose `mutate-spec` answers `spec`, whatever it is given
+    fn component(spec: &VmSpec) -> String {
+        let json = serde_json::to_string(spec).unwrap();
+        let data: String = json.bytes().map(|b| format!("\\{:02x}", b)).collect();
+        format!(
+            r#"
+            (component
+              (core module $m
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 8192))
+                (data (i32.const 16) "{data}")
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                  (local $ptr i32)
+                  (local.set $ptr (global.get $next))
+                  (global.set $next (i32.add (global.get $next) (local.get 3)))
+                  (local.get $ptr))
+                (func (export "mutate-spec") (param i32 i32) (result i32)
+                  (i32.store (i32.const 0) (i32.const 0))
+                  (i32.store (i32.const 4) (i32.const 16))
+                  (i32.store (i32.const 8) (i32.const {len}))
+                  (i32.const 0))
+                (func (export "on-event") (param i32 i32 i32 i32) (result i32)
+                  (i32.store (i32.const 0) (i32.const 0))
+                  (i32.const 0)))
+              (core instance $i (instantiate $m))
+              (func (export "mutate-spec") (param "spec" string)
+                (result (result string (error string)))
+                (canon lift (core func $i "mutate-spec")
+                  (memory $i "memory") (realloc (func $i "realloc"))))
+              (func (export "on-event") (param "hook" string) (param "vm" string)
+                (result (result (error string)))
+                (canon lift (core func $i "on-event")
+                  (memory $i "memory") (realloc (func $i "realloc")))))
+            "#,
+            len = json.len()
+        )
+    }
+}
@@ -1,12 +1,27 @@
+use crate::archive::{self, ArchiveKind, ArchiveManifest};
+use crate::auth::{AuthContext, Permission};
 use crate::error::{Result, VortexError};
+use crate::home_volume;
+use crate::ids::LABEL_WORKSPACE_ID;
+use crate::nix::NixEnvironment;
+use crate::storage::StorageManager;
 use crate::templates::DevTemplate;
-use crate::vm::VmSpec;
+use crate::tuning::TuningProfile;
+use crate::vm::{NetworkPolicy, VmSpec};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+/// Name of the layer holding workspace contents in exported archives
+const WORKSPACE_LAYER: &str = "workspace";
+/// File in a workspace's directory holding its config
+const CONFIG_FILE: &str = ".vortex.json";
+/// Directory of the workspaces holding the encrypted volumes of those
+/// encrypted at rest, each named after its workspace
+const VOLUMES_DIR: &str = ".volumes";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DevContainerConfig {
     #[serde(rename = "dockerComposeFile")]
@@ -70,6 +85,36 @@ pub struct VortexWorkspaceConfig {
 
     /// If present, indicates this workspace was created from a devcontainer.json
     pub devcontainer_source: Option<String>,
+
+    /// Nix devshell for this workspace, overriding the template's
+    #[serde(default)]
+    pub nix: Option<NixEnvironment>,
+
+    /// User who created the workspace, the only one who may delete it or
+    /// change who else may use it. Workspaces from before owners were
+    /// recorded have none and are open to everyone.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Users besides the owner who may use the workspace
+    #[serde(default)]
+    pub allowed_users: Vec<String>,
+
+    /// Whether the workspace's files are encrypted at rest, in an encrypted
+    /// volume (see `crate::storage`) unlocked while VMs have it mounted
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+impl VortexWorkspaceConfig {
+    /// Whether `user` may use the workspace: start, attach to and export it
+    pub fn allows(&self, user: &str) -> bool {
+        self.is_owner(user) || self.allowed_users.iter().any(|allowed| allowed == user)
+    }
+
+    /// Whether `user` owns the workspace, as everyone does one without owner
+    pub fn is_owner(&self, user: &str) -> bool {
+        self.owner.as_deref().map_or(true, |owner| owner == user)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -83,6 +128,8 @@ pub struct Workspace {
 #[derive(Debug)]
 pub struct WorkspaceManager {
     workspaces_dir: PathBuf,
+    /// Who changes to workspaces are made for
+    auth: AuthContext,
 }
 
 impl WorkspaceManager {
@@ -90,7 +137,50 @@ impl WorkspaceManager {
         let workspaces_dir = Self::get_workspaces_dir()?;
         fs::create_dir_all(&workspaces_dir)?;
 
-        Ok(Self { workspaces_dir })
+        Ok(Self::at(workspaces_dir))
+    }
+
+    pub fn at(workspaces_dir: PathBuf) -> Self {
+        Self {
+            workspaces_dir,
+            auth: AuthContext::local(),
+        }
+    }
+
+    /// Make changes for `auth`, which needs `WorkspaceManage` for them
+    pub fn with_auth(mut self, auth: AuthContext) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Fail unless the user may use `workspace`
+    pub fn check_access(&self, workspace: &Workspace) -> Result<()> {
+        if workspace.config.allows(&self.auth.user_id) {
+            return Ok(());
+        }
+        Err(VortexError::PermissionDenied {
+            action: format!(
+                "{} may not use workspace '{}' of {}",
+                self.auth.user_id,
+                workspace.name,
+                workspace.config.owner.as_deref().unwrap_or_default()
+            ),
+        })
+    }
+
+    /// Fail unless the user owns `workspace`
+    fn check_owner(&self, workspace: &Workspace, action: &str) -> Result<()> {
+        if workspace.config.is_owner(&self.auth.user_id) {
+            return Ok(());
+        }
+        Err(VortexError::PermissionDenied {
+            action: format!(
+                "only {} may {} workspace '{}'",
+                workspace.config.owner.as_deref().unwrap_or_default(),
+                action,
+                workspace.name
+            ),
+        })
     }
 
     fn get_workspaces_dir() -> Result<PathBuf> {
@@ -107,6 +197,7 @@ impl WorkspaceManager {
         template: &str,
         source_dir: Option<&Path>,
     ) -> Result<Workspace> {
+        self.auth.require(Permission::WorkspaceManage)?;
         let workspace_id = Uuid::new_v4().to_string();
         let workspace_dir = self.workspaces_dir.join(&workspace_id);
 
@@ -123,10 +214,14 @@ impl WorkspaceManager {
             port_forwards: Vec::new(),
             backend: None,
             devcontainer_source: None,
+            nix: None,
+            owner: Some(self.auth.user_id.clone()),
+            allowed_users: Vec::new(),
+            encrypted: false,
         };
 
         // Save config
-        self.save_workspace_config(&workspace_id, &config)?;
+        self.write_workspace_config(&workspace_id, &config)?;
 
         // Copy initial source if provided
         if let Some(source) = source_dir {
@@ -148,6 +243,7 @@ impl WorkspaceManager {
         devcontainer_path: &Path,
         source_dir: &Path,
     ) -> Result<Workspace> {
+        self.auth.require(Permission::WorkspaceManage)?;
         let devcontainer_config = self.parse_devcontainer(devcontainer_path)?;
 
         // Convert devcontainer config to Vortex template
@@ -175,10 +271,14 @@ impl WorkspaceManager {
                 .unwrap_or_default(),
             backend: None,
             devcontainer_source: Some(devcontainer_path.to_string_lossy().to_string()),
+            nix: None,
+            owner: Some(self.auth.user_id.clone()),
+            allowed_users: Vec::new(),
+            encrypted: false,
         };
 
         // Save config and copy source
-        self.save_workspace_config(&workspace_id, &config)?;
+        self.write_workspace_config(&workspace_id, &config)?;
         copy_dir_all(source_dir, &workspace_dir)?;
 
         Ok(Workspace {
@@ -189,6 +289,40 @@ impl WorkspaceManager {
         })
     }
 
+    /// Encrypt the files of the workspace at rest, moving them into an
+    /// encrypted volume that is locked until a VM mounts it
+    pub fn encrypt_workspace(&self, workspace_id: &str) -> Result<Workspace> {
+        self.auth.require(Permission::WorkspaceManage)?;
+        let mut workspace = self
+            .get_workspace(workspace_id)?
+            .ok_or_else(|| not_found(workspace_id))?;
+        self.check_owner(&workspace, "encrypt")?;
+        if workspace.config.encrypted {
+            return Ok(workspace);
+        }
+
+        let volumes = self.volumes();
+        fs::create_dir_all(self.workspaces_dir.join(VOLUMES_DIR))?;
+        let volume = volumes.create_encrypted(&workspace.id)?;
+        for entry in fs::read_dir(&workspace.path)? {
+            let entry = entry?;
+            if entry.file_name() != CONFIG_FILE {
+                fs::rename(entry.path(), volume.path.join(entry.file_name()))?;
+            }
+        }
+        volumes.lock(&workspace.id)?;
+
+        workspace.config.encrypted = true;
+        self.write_workspace_config(workspace_id, &workspace.config)?;
+        self.get_workspace(workspace_id)?
+            .ok_or_else(|| not_found(workspace_id))
+    }
+
+    /// Encrypted volumes of the workspaces encrypted at rest
+    fn volumes(&self) -> StorageManager {
+        StorageManager::at(self.workspaces_dir.join(VOLUMES_DIR))
+    }
+
     /// Get workspace by ID
     pub fn get_workspace(&self, workspace_id: &str) -> Result<Option<Workspace>> {
         let workspace_dir = self.workspaces_dir.join(workspace_id);
@@ -197,11 +331,26 @@ impl WorkspaceManager {
         }
 
         let config = self.load_workspace_config(workspace_id)?;
+        // The volume's files, which do not exist while it is locked
+        let path = match config.encrypted {
+            true => {
+                self.volumes()
+                    .volume(workspace_id)?
+                    .ok_or_else(|| VortexError::StorageError {
+                        message: format!(
+                            "The files of encrypted workspace '{}' are missing",
+                            config.name
+                        ),
+                    })?
+                    .path
+            }
+            false => workspace_dir,
+        };
 
         Ok(Some(Workspace {
             id: workspace_id.to_string(),
             name: config.name.clone(),
-            path: workspace_dir,
+            path,
             config,
         }))
     }
@@ -228,6 +377,10 @@ impl WorkspaceManager {
             let entry = entry?;
             if entry.file_type()?.is_dir() {
                 let workspace_id = entry.file_name().to_string_lossy().to_string();
+                // Skip in-progress archive loads
+                if workspace_id.starts_with('.') {
+                    continue;
+                }
                 if let Some(workspace) = self.get_workspace(&workspace_id)? {
                     workspaces.push(workspace);
                 }
@@ -235,7 +388,7 @@ impl WorkspaceManager {
         }
 
         // Sort by last used, most recent first
-        workspaces.sort_by(|a, b| b.config.last_used.cmp(&a.config.last_used));
+        workspaces.sort_by_key(|w| std::cmp::Reverse(w.config.last_used));
 
         Ok(workspaces)
     }
@@ -244,20 +397,106 @@ impl WorkspaceManager {
     pub fn touch_workspace(&self, workspace_id: &str) -> Result<()> {
         if let Some(mut workspace) = self.get_workspace(workspace_id)? {
             workspace.config.last_used = chrono::Utc::now();
-            self.save_workspace_config(workspace_id, &workspace.config)?;
+            self.write_workspace_config(workspace_id, &workspace.config)?;
         }
         Ok(())
     }
 
     /// Delete workspace
     pub fn delete_workspace(&self, workspace_id: &str) -> Result<()> {
+        self.auth.require(Permission::WorkspaceManage)?;
+        // One whose config is unreadable has no owner to ask
+        if let Ok(Some(workspace)) = self.get_workspace(workspace_id) {
+            self.check_owner(&workspace, "delete")?;
+        }
         let workspace_dir = self.workspaces_dir.join(workspace_id);
         if workspace_dir.exists() {
             fs::remove_dir_all(workspace_dir)?;
         }
+        if self.volumes().volume(workspace_id)?.is_some() {
+            self.volumes().remove(workspace_id)?;
+        }
         Ok(())
     }
 
+    /// Export a workspace (files and config) to a portable Vortex archive
+    pub fn export_workspace(&self, workspace_id: &str, dest: &Path) -> Result<ArchiveManifest> {
+        self.auth.require(Permission::WorkspaceManage)?;
+        let workspace = self
+            .get_workspace(workspace_id)?
+            .ok_or_else(|| not_found(workspace_id))?;
+        self.check_access(&workspace)?;
+        // An archive would hold its files in the clear
+        if workspace.config.encrypted {
+            return Err(VortexError::InvalidInput {
+                field: "workspace_id".to_string(),
+                message: format!(
+                    "Workspace '{}' is encrypted and cannot be exported",
+                    workspace.name
+                ),
+            });
+        }
+
+        let mut manifest = ArchiveManifest::new(ArchiveKind::Workspace);
+        manifest.source_id = Some(workspace.id.clone());
+        manifest
+            .labels
+            .insert("vortex.workspace-name".to_string(), workspace.name.clone());
+        manifest.labels.insert(
+            "vortex.template".to_string(),
+            workspace.config.template.clone(),
+        );
+
+        archive::write_archive(
+            dest,
+            manifest,
+            &[(WORKSPACE_LAYER.to_string(), workspace.path.clone())],
+        )
+    }
+
+    /// Load a workspace from an archive created by `export_workspace`.
+    ///
+    /// The workspace always gets a fresh ID; `name` overrides the archived name.
+    pub fn load_workspace(&self, archive_path: &Path, name: Option<&str>) -> Result<Workspace> {
+        self.auth.require(Permission::WorkspaceManage)?;
+        let manifest = archive::read_manifest(archive_path)?;
+        if manifest.kind != ArchiveKind::Workspace || manifest.layer(WORKSPACE_LAYER).is_none() {
+            return Err(VortexError::InvalidInput {
+                field: "archive".to_string(),
+                message: format!("{} is not a workspace archive", archive_path.display()),
+            });
+        }
+
+        let workspace_id = Uuid::new_v4().to_string();
+        let staging = self.workspaces_dir.join(format!(".load-{}", workspace_id));
+        let extracted = archive::extract_archive(archive_path, &staging).and_then(|_| {
+            fs::rename(
+                staging.join(WORKSPACE_LAYER),
+                self.workspaces_dir.join(&workspace_id),
+            )?;
+            Ok(())
+        });
+        let _ = fs::remove_dir_all(&staging);
+        extracted?;
+
+        let mut config = self.load_workspace_config(&workspace_id)?;
+        if let Some(name) = name {
+            config.name = name.to_string();
+        }
+        config.last_used = chrono::Utc::now();
+        // A loaded copy belongs to whoever loaded it
+        config.owner = Some(self.auth.user_id.clone());
+        config.allowed_users.clear();
+        self.write_workspace_config(&workspace_id, &config)?;
+
+        Ok(Workspace {
+            id: workspace_id.clone(),
+            name: config.name.clone(),
+            path: self.workspaces_dir.join(&workspace_id),
+            config,
+        })
+    }
+
     /// Convert workspace to VM spec
     pub fn workspace_to_vm_spec(
         &self,
@@ -273,12 +512,28 @@ impl WorkspaceManager {
             environment: base_template.environment.clone(),
             command: None,
             labels: HashMap::from([
-                ("vortex.workspace".to_string(), workspace.id.clone()),
+                (LABEL_WORKSPACE_ID.to_string(), workspace.id.clone()),
                 ("vortex.workspace-name".to_string(), workspace.name.clone()),
             ]),
             network_config: None,
             resource_limits: crate::vm::ResourceLimits::default(),
             backend: workspace.config.backend.clone(),
+            tuning: base_template
+                .tuning_profile
+                .as_deref()
+                .map(TuningProfile::resolve)
+                .transpose()?,
+            hooks: crate::vm::LifecycleHooks::default(),
+            health_check: None,
+            ttl_seconds: None,
+            gpus: Vec::new(),
+            provision: None,
+            publish: Vec::new(),
+            tls_ports: HashMap::new(),
+            port_addresses: HashMap::new(),
+            share_mechanisms: HashMap::new(),
+            tmpfs: HashMap::new(),
+            network_policy: NetworkPolicy::Open,
         };
 
         // Add workspace volume mount
@@ -287,6 +542,10 @@ impl WorkspaceManager {
             PathBuf::from(&workspace.config.preferred_workdir),
         );
 
+        if base_template.persist_home {
+            home_volume::apply(&mut spec, &workspace.config.template)?;
+        }
+
         // Add port forwards
         for port in &workspace.config.port_forwards {
             spec.ports.insert(*port, *port);
@@ -325,13 +584,24 @@ impl WorkspaceManager {
         let mut startup_commands = base_template.startup_commands.clone();
         startup_commands.extend(workspace.config.custom_commands.clone());
 
-        let setup_commands = startup_commands.join(" && ");
-        let full_command = format!(
-            "cd {} && {} && echo 'Vortex workspace \"{}\" ready!' && exec bash",
-            workspace.config.preferred_workdir, setup_commands, workspace.name
-        );
+        let nix = workspace.config.nix.as_ref().or(base_template.nix.as_ref());
+
+        let mut steps = vec![format!("cd {}", workspace.config.preferred_workdir)];
+        steps.extend(startup_commands);
+        steps.push(format!(
+            "echo 'Vortex workspace \"{}\" ready!'",
+            workspace.name
+        ));
+        steps.push(match nix {
+            Some(nix) => nix.shell_command(),
+            None => "exec bash".to_string(),
+        });
+
+        spec.command = Some(steps.join(" && "));
 
-        spec.command = Some(full_command);
+        if let Some(nix) = nix {
+            nix.apply(&mut spec)?;
+        }
 
         Ok(spec)
     }
@@ -341,14 +611,31 @@ impl WorkspaceManager {
         workspace_id: &str,
         config: &VortexWorkspaceConfig,
     ) -> Result<()> {
-        let config_path = self.workspaces_dir.join(workspace_id).join(".vortex.json");
+        self.auth.require(Permission::WorkspaceManage)?;
+        if let Some(workspace) = self.get_workspace(workspace_id)? {
+            self.check_access(&workspace)?;
+            if config.owner != workspace.config.owner
+                || config.allowed_users != workspace.config.allowed_users
+            {
+                self.check_owner(&workspace, "change who may use")?;
+            }
+        }
+        self.write_workspace_config(workspace_id, config)
+    }
+
+    fn write_workspace_config(
+        &self,
+        workspace_id: &str,
+        config: &VortexWorkspaceConfig,
+    ) -> Result<()> {
+        let config_path = self.workspaces_dir.join(workspace_id).join(CONFIG_FILE);
         let config_json = serde_json::to_string_pretty(config)?;
         fs::write(config_path, config_json)?;
         Ok(())
     }
 
     fn load_workspace_config(&self, workspace_id: &str) -> Result<VortexWorkspaceConfig> {
-        let config_path = self.workspaces_dir.join(workspace_id).join(".vortex.json");
+        let config_path = self.workspaces_dir.join(workspace_id).join(CONFIG_FILE);
         let config_json = fs::read_to_string(config_path)?;
         let config: VortexWorkspaceConfig = serde_json::from_str(&config_json)?;
         Ok(config)
@@ -396,6 +683,13 @@ impl WorkspaceManager {
     }
 }
 
+fn not_found(workspace_id: &str) -> VortexError {
+    VortexError::InvalidInput {
+        field: "workspace_id".to_string(),
+        message: format!("Workspace '{}' not found", workspace_id),
+    }
+}
+
 fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
     fs::create_dir_all(dst)?;
 
@@ -443,21 +737,29 @@ pub fn detect_workspace_info(dir: &Path) -> Option<WorkspaceInfo> {
         }
     }
 
-    // Detect project type
+    if let Some(template) = detect_template(dir) {
+        info.suggested_template = template.to_string();
+    }
+
+    Some(info)
+}
+
+/// Dev template matching the project's build files, if any are recognized
+pub fn detect_template(dir: &Path) -> Option<&'static str> {
     if dir.join("Cargo.toml").exists() {
-        info.suggested_template = "rust".to_string();
+        Some("rust")
     } else if dir.join("package.json").exists() {
-        info.suggested_template = "node".to_string();
+        Some("node")
     } else if dir.join("go.mod").exists() {
-        info.suggested_template = "go".to_string();
+        Some("go")
     } else if dir.join("requirements.txt").exists()
         || dir.join("pyproject.toml").exists()
         || dir.join("setup.py").exists()
     {
-        info.suggested_template = "python".to_string();
+        Some("python")
+    } else {
+        None
     }
-
-    Some(info)
 }
 
 #[derive(Debug)]
@@ -467,3 +769,46 @@ pub struct WorkspaceInfo {
     pub has_devcontainer: bool,
     pub devcontainer_path: Option<PathBuf>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthToken;
+
+    fn user(name: &str) -> AuthContext {
+        AuthContext::from(AuthToken {
+            token: String::new(),
+            user_id: name.to_string(),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            permissions: vec![Permission::WorkspaceManage],
+        })
+    }
+
+    #[test]
+    fn test_only_owners_and_allowed_users_use_a_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let alice = WorkspaceManager::at(dir.path().to_path_buf()).with_auth(user("alice"));
+        let bob = WorkspaceManager::at(dir.path().to_path_buf()).with_auth(user("bob"));
+        let workspace = alice.create_workspace("api", "rust", None).unwrap();
+        assert_eq!(workspace.config.owner.as_deref(), Some("alice"));
+
+        let denied =
+            |result: Result<()>| matches!(result, Err(VortexError::PermissionDenied { .. }));
+        assert!(denied(bob.check_access(&workspace)));
+        assert!(denied(bob.delete_workspace(&workspace.id)));
+        let mut shared = workspace.config.clone();
+        shared.allowed_users.push("bob".to_string());
+        assert!(denied(bob.save_workspace_config(&workspace.id, &shared)));
+
+        alice.save_workspace_config(&workspace.id, &shared).unwrap();
+        let workspace = bob.get_workspace(&workspace.id).unwrap().unwrap();
+        bob.check_access(&workspace).unwrap();
+        let mut taken = workspace.config.clone();
+        taken.owner = Some("bob".to_string());
+        assert!(denied(bob.save_workspace_config(&workspace.id, &taken)));
+        assert!(denied(bob.delete_workspace(&workspace.id)));
+
+        alice.delete_workspace(&workspace.id).unwrap();
+        assert!(alice.get_workspace(&workspace.id).unwrap().is_none());
+    }
+}
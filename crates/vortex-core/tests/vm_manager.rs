@@ -0,0 +1,550 @@
+//! `VmManager` behavior, checked against the in-memory backend of
+//! vortex-backends.
+
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use vortex_backends::mock::{mock_manager, MockBackend};
+use vortex_core::backend::Backend;
+use vortex_core::error::{Result, VortexError};
+use vortex_core::ids::{VmId, LABEL_CLONED_FROM, LABEL_EGRESS_PROXY, LABEL_SESSION_ID};
+use vortex_core::plugin::{Plugin, PluginHook, PluginManager, PluginMetadata};
+use vortex_core::vm::{
+    LifecycleHooks, NetworkPolicy, Probe, ResourceLimits, VmInstance, VmManager, VmSpec, VmState,
+};
+
+#[tokio::test]
+async fn test_clone_drops_owner_labels() {
+    let (manager, _) = mock_manager();
+
+    let mut spec = VmSpec {
+        image: "alpine".to_string(),
+        ..Default::default()
+    };
+    spec.labels
+        .insert(LABEL_SESSION_ID.to_string(), "session-1".to_string());
+    let source = manager.create(spec).await.unwrap();
+
+    let clone = manager.clone(&source.id, None).await.unwrap();
+    assert_ne!(clone.id, source.id);
+    assert!(!clone.spec.labels.contains_key(LABEL_SESSION_ID));
+    assert_eq!(clone.spec.labels[LABEL_CLONED_FROM], source.id);
+
+    let overrides = VmSpec {
+        memory: 0,
+        ..clone.spec.clone()
+    };
+    assert!(manager.clone(&source.id, Some(overrides)).await.is_err());
+    assert!(manager
+        .clone(&VmId::new("vortex-missing"), None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_track_state() {
+    let (manager, _) = mock_manager();
+    let vm = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    manager.pause(&vm.id).await.unwrap();
+    let paused = manager.get(&vm.id).await.unwrap().unwrap();
+    assert!(matches!(paused.state, VmState::Paused));
+
+    manager.resume(&vm.id).await.unwrap();
+    let resumed = manager.get(&vm.id).await.unwrap().unwrap();
+    assert!(matches!(resumed.state, VmState::Running));
+}
+
+#[tokio::test]
+async fn test_subscribers_see_every_transition() {
+    let (manager, _) = mock_manager();
+    let mut events = manager.subscribe();
+    let vm = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    manager.pause(&vm.id).await.unwrap();
+    manager.resume(&vm.id).await.unwrap();
+    manager.resize(&vm.id, Some(1024), None).await.unwrap();
+    assert_eq!(manager.resource_usage().await.len(), 1);
+    manager.stop(&vm.id).await.unwrap();
+    manager.cleanup(&vm.id).await.unwrap();
+
+    let mut seen = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        assert_eq!(*event.vm_id(), vm.id);
+        seen.push(vortex_core::events::EventPayload::from(event).event_type());
+    }
+    assert_eq!(
+        seen,
+        [
+            "vm_created",
+            "vm_started",
+            "vm_paused",
+            "vm_resumed",
+            "vm_resized",
+            "resource_usage",
+            "vm_stopped",
+            "vm_cleaned_up"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_wait_reports_exit_status() {
+    let (manager, _) = mock_manager();
+    let vm = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            command: Some("true".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert!(manager.wait(&vm.id).await.unwrap().success());
+    manager.cleanup(&vm.id).await.unwrap();
+    assert!(manager.wait(&vm.id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_hooks_run_around_start_and_stop() {
+    let dir = tempfile::tempdir().unwrap();
+    let record = dir.path().join("hooks");
+    let hook = format!(
+        "echo \"$VORTEX_HOOK $VORTEX_VM_ID\" >> {}",
+        record.display()
+    );
+    let (manager, backend) = mock_manager();
+
+    let vm = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            hooks: LifecycleHooks {
+                pre_start: Some(hook.clone()),
+                post_start: Some("true".to_string()),
+                pre_stop: Some(hook),
+            },
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    manager.stop(&vm.id).await.unwrap();
+    let ran = std::fs::read_to_string(&record).unwrap();
+    assert_eq!(ran, format!("pre-start {0}\npre-stop {0}\n", vm.id));
+
+    // A failing pre-start hook keeps the VM from being created
+    let failed = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            hooks: LifecycleHooks {
+                pre_start: Some("exit 3".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await;
+    assert!(failed.is_err());
+    assert_eq!(backend.list_vms().await.unwrap(), [vm.id]);
+}
+
+#[tokio::test]
+async fn test_tcp_probe_tracks_readiness_and_health() {
+    let probe: Probe = "tcp:5432".parse().unwrap();
+    let (manager, _) = mock_manager();
+
+    let vm = manager
+        .create(VmSpec {
+            image: "postgres".to_string(),
+            publish: vec![5432],
+            health_check: Some(probe),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(matches!(vm.state, VmState::Starting));
+    // Stands in for the guest behind the forwarded port
+    let host_port = *vm.spec.ports.keys().next().unwrap();
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", host_port))
+        .await
+        .unwrap();
+    let state = manager.check_health(&vm.id, None).await.unwrap();
+    assert!(matches!(state, VmState::Running));
+
+    drop(listener);
+    let state = manager.check_health(&vm.id, None).await.unwrap();
+    assert!(matches!(state, VmState::Unhealthy { .. }));
+    assert!("udp:53".parse::<Probe>().is_err());
+}
+
+#[tokio::test]
+async fn test_host_port_conflicts_are_refused() {
+    let (manager, _) = mock_manager();
+    let spec = VmSpec {
+        image: "nginx".to_string(),
+        publish: vec![80],
+        ..Default::default()
+    };
+    let vm = manager.create(spec.clone()).await.unwrap();
+
+    let taken = VmSpec {
+        ports: vm.spec.ports.clone(),
+        publish: Vec::new(),
+        ..spec.clone()
+    };
+    let err = manager.create(taken).await.unwrap_err();
+    assert!(
+        matches!(err, VortexError::PortInUse { ref owner, .. } if *owner == format!("VM {}", vm.id))
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let busy = VmSpec {
+        ports: [(listener.local_addr().unwrap().port(), 80)].into(),
+        publish: Vec::new(),
+        ..spec
+    };
+    let err = manager.create(busy).await.unwrap_err();
+    assert!(matches!(err, VortexError::PortInUse { .. }));
+
+    // Clones get host ports of their own
+    let clone = manager.clone(&vm.id, None).await.unwrap();
+    assert_eq!(clone.spec.ports.len(), 1);
+    assert!(clone
+        .spec
+        .ports
+        .keys()
+        .all(|port| !vm.spec.ports.contains_key(port)));
+}
+
+#[tokio::test]
+async fn test_network_policy_runs_an_egress_proxy_per_vm() {
+    let (manager, _) = mock_manager();
+    let spec = VmSpec {
+        image: "alpine".to_string(),
+        network_policy: NetworkPolicy::Allow {
+            domains: vec!["example.com".to_string()],
+        },
+        ..Default::default()
+    };
+    let vm = manager.create(spec).await.unwrap();
+    let port: u16 = vm.spec.labels[LABEL_EGRESS_PROXY].parse().unwrap();
+
+    let mut conn = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    conn.write_all(b"CONNECT pastebin.com:443 HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    conn.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 403"));
+
+    manager.cleanup(&vm.id).await.unwrap();
+    tokio::task::yield_now().await;
+    assert!(tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_reaper_stops_vms_past_their_ttl() {
+    let (manager, backend) = mock_manager();
+
+    let expired = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            ttl_seconds: Some(0),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let kept = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            ttl_seconds: Some(3600),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        manager.reap_expired().await,
+        std::slice::from_ref(&expired.id)
+    );
+    assert_eq!(
+        backend.list_vms().await.unwrap(),
+        std::slice::from_ref(&kept.id)
+    );
+    assert!(manager.get(&expired.id).await.unwrap().is_none());
+    assert!(manager.reap_expired().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_disk_quota_checks_report_vms_over_budget() {
+    let (manager, backend) = mock_manager();
+    let limited = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            resource_limits: ResourceLimits {
+                max_disk: Some(64),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    backend.set_disk_usage(32 * 1024 * 1024);
+    assert!(manager.check_disk_quotas().await.is_empty());
+    backend.set_disk_usage(128 * 1024 * 1024);
+    assert_eq!(
+        manager.check_disk_quotas().await,
+        std::slice::from_ref(&limited.id)
+    );
+    // Still over budget, though warned about only once
+    assert_eq!(manager.check_disk_quotas().await, [limited.id]);
+}
+
+#[tokio::test]
+async fn test_resource_limits_are_checked_before_create() {
+    let (manager, backend) = mock_manager();
+
+    let result = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            cpus: 4,
+            resource_limits: ResourceLimits {
+                max_cpus: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await;
+    assert!(matches!(
+        result,
+        Err(VortexError::ResourceLimitExceeded { .. })
+    ));
+    assert!(backend.list_vms().await.unwrap().is_empty());
+
+    // Resizing a running VM is held to the same limits
+    let vm = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            cpus: 1,
+            resource_limits: ResourceLimits {
+                max_cpus: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    manager.resize(&vm.id, Some(1024), Some(2)).await.unwrap();
+    let resized = manager.get(&vm.id).await.unwrap().unwrap();
+    assert_eq!((resized.spec.memory, resized.spec.cpus), (1024, 2));
+    assert!(matches!(
+        manager.resize(&vm.id, None, Some(4)).await,
+        Err(VortexError::ResourceLimitExceeded { .. })
+    ));
+    assert!(manager.resize(&vm.id, None, None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_create_batch_reports_each_result() {
+    let (manager, backend) = mock_manager();
+    // Copies of one spec publishing a port get host ports of their own
+    let spec = VmSpec {
+        image: "alpine".to_string(),
+        publish: vec![8000],
+        ..Default::default()
+    };
+    let invalid = VmSpec {
+        memory: 0,
+        ..spec.clone()
+    };
+
+    let results = manager
+        .create_batch(vec![spec.clone(), invalid, spec], 2)
+        .await;
+    assert_eq!(results.len(), 3);
+    assert!(results[1].is_err());
+    assert_eq!(backend.list_vms().await.unwrap().len(), 2);
+    let host_port = |vm: &VmInstance| {
+        assert!(vm.spec.publish.is_empty());
+        let (host, guest) = vm.spec.ports.iter().next().unwrap();
+        assert_eq!(*guest, 8000);
+        *host
+    };
+    let first = host_port(results[0].as_ref().unwrap());
+    let second = host_port(results[2].as_ref().unwrap());
+    assert!(first != 0 && first != second);
+}
+
+#[tokio::test]
+async fn test_create_hands_out_prewarmed_vms() {
+    let (manager, backend) = mock_manager();
+    let manager = Arc::new(manager);
+    let target = VmSpec {
+        image: "alpine".to_string(),
+        ..Default::default()
+    };
+    manager.prewarm(vec![(target.clone(), 1)]).await;
+
+    let pooled = wait_for_pool(&manager, &backend).await;
+    let vm = manager
+        .create(VmSpec {
+            command: Some("echo hi".to_string()),
+            ..target.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(vm.id, pooled);
+    assert_eq!(vm.spec.command.as_deref(), Some("echo hi"));
+    assert!(matches!(vm.state, VmState::Running));
+
+    // The pool boots a replacement; a spec that boots differently does
+    // not take it
+    let replacement = wait_for_pool(&manager, &backend).await;
+    let bigger = manager
+        .create(VmSpec {
+            memory: 1024,
+            ..target
+        })
+        .await
+        .unwrap();
+    assert_ne!(bigger.id, replacement);
+
+    manager.drain_pool().await;
+    assert_eq!(manager.pooled_vms().await, 0);
+    assert!(!backend
+        .list_vms()
+        .await
+        .unwrap()
+        .contains(&replacement.to_string()));
+}
+
+/// Labels the VMs it lets in and remembers what it was told
+#[derive(Debug)]
+struct InventoryPlugin {
+    metadata: PluginMetadata,
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Plugin for InventoryPlugin {
+    fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    async fn initialize(&mut self, _settings: &serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_spec_mutate(&self, spec: &mut VmSpec) -> Result<()> {
+        if spec.image == "forbidden" {
+            return Err(VortexError::InvalidInput {
+                field: "image".to_string(),
+                message: "not in the inventory".to_string(),
+            });
+        }
+        spec.labels
+            .insert("inventory".to_string(), "registered".to_string());
+        Ok(())
+    }
+
+    async fn on_vm_create(&self, vm: &VmInstance) -> Result<()> {
+        self.seen.lock().unwrap().push(format!("create {}", vm.id));
+        Ok(())
+    }
+
+    async fn on_vm_stop(&self, vm: &VmInstance) -> Result<()> {
+        self.seen.lock().unwrap().push(format!("stop {}", vm.id));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_plugins_hook_into_the_lifecycle() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let plugin = InventoryPlugin {
+        metadata: PluginMetadata {
+            name: "inventory".to_string(),
+            version: "1.0.0".to_string(),
+            description: String::new(),
+            author: String::new(),
+            // Not VmPostStart, so on_vm_start is never called
+            hooks: vec![
+                PluginHook::VmPreCreate,
+                PluginHook::VmPostCreate,
+                PluginHook::VmPostStop,
+            ],
+        },
+        seen: seen.clone(),
+    };
+    let mut plugins = PluginManager::new().await.unwrap();
+    plugins.register_plugin(Box::new(plugin)).await.unwrap();
+    let (manager, _) = mock_manager();
+    let manager = manager.with_plugins(Arc::new(tokio::sync::RwLock::new(plugins)));
+
+    let vm = manager
+        .create(VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(vm.spec.labels["inventory"], "registered");
+    manager.stop(&vm.id).await.unwrap();
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![format!("create {}", vm.id), format!("stop {}", vm.id)]
+    );
+
+    let refused = manager
+        .create(VmSpec {
+            image: "forbidden".to_string(),
+            ..Default::default()
+        })
+        .await;
+    assert!(matches!(refused, Err(VortexError::PluginError { .. })));
+}
+
+/// Id of the VM waiting in the pool once there is one
+async fn wait_for_pool(manager: &VmManager, backend: &MockBackend) -> VmId {
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        loop {
+            if manager.pooled_vms().await == 1 {
+                for id in backend.list_vms().await.unwrap() {
+                    let id = VmId::new(id);
+                    if manager.get(&id).await.unwrap().is_none() {
+                        return id;
+                    }
+                }
+            }
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .unwrap()
+}
@@ -0,0 +1,120 @@
+//! Cached access to `VortexConfig`.
+//!
+//! The daemon keeps the parsed config in memory and only re-reads the files
+//! when the fingerprint (mtime + size) of the user's or the system file
+//! changes. Read-only CLI paths ask the
+//! daemon first and fall back to parsing the file themselves.
+//!
+//! The cached config is as written, before `${VAR}` and `~` are expanded:
+//! the CLI expands it with its own environment and home, not the daemon's.
+
+use crate::daemon::DaemonClient;
+use crate::session::{SessionCommand, SessionResponse};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::UNIX_EPOCH;
+use vortex_core::config::{get_config_path, system_config_path, VortexConfig};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::interpolate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigFingerprint {
+    pub modified_ns: u128,
+    pub len: u64,
+}
+
+impl ConfigFingerprint {
+    /// Fingerprint of the file at `path`, `None` if it does not exist
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified_ns = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        Some(Self {
+            modified_ns,
+            len: metadata.len(),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigCache {
+    /// The unexpanded config with the fingerprints of the user's file and
+    /// of the system file, if there is one
+    entry: RwLock<Option<(ConfigFingerprint, Option<ConfigFingerprint>, VortexConfig)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ConfigCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the current config as written, re-parsing only if a file
+    /// changed
+    pub fn get(&self) -> Result<(ConfigFingerprint, VortexConfig)> {
+        let path = get_config_path()?;
+        let system = ConfigFingerprint::of(&system_config_path());
+
+        if let Some(current) = ConfigFingerprint::of(&path) {
+            let entry = self.entry.read().map_err(|_| cache_poisoned())?;
+            if let Some((fingerprint, system_fingerprint, config)) = entry.as_ref() {
+                if *fingerprint == current && *system_fingerprint == system {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok((current, config.clone()));
+                }
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        // load_raw() writes a default config when none exists, so fingerprint afterwards
+        let config = VortexConfig::load_raw()?;
+        let fingerprint = ConfigFingerprint::of(&path).ok_or_else(|| VortexError::ConfigError {
+            message: format!("Config file disappeared while loading: {}", path.display()),
+        })?;
+
+        let mut entry = self.entry.write().map_err(|_| cache_poisoned())?;
+        *entry = Some((fingerprint, system, config.clone()));
+        Ok((fingerprint, config))
+    }
+
+    /// (hits, misses) since the cache was created
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+fn cache_poisoned() -> VortexError {
+    VortexError::ConfigError {
+        message: "Config cache lock poisoned".to_string(),
+    }
+}
+
+/// Load the config through the daemon's cache, falling back to a direct load.
+///
+/// Use this for read-only paths; read-modify-write callers should use
+/// [`VortexConfig::load`] so they never save over a stale copy.
+pub async fn load_cached() -> Result<VortexConfig> {
+    if let Ok(client) = DaemonClient::new() {
+        if client.socket_exists() {
+            match client.send_command(SessionCommand::GetConfig).await {
+                Ok(SessionResponse::Config { config, .. }) => {
+                    return interpolate::expand_values(*config);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!("Daemon config cache unavailable: {}", e),
+            }
+        }
+    }
+
+    VortexConfig::load()
+}
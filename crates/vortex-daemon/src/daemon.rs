@@ -0,0 +1,795 @@
+use crate::session::{SessionCommand, SessionManager, SessionResponse};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{Notify, RwLock};
+use tokio::time::{interval, Duration};
+use tracing::{error, info, warn};
+use vortex_core::auth::{AuthContext, AuthCredentials, AuthProvider};
+use vortex_core::config::VortexConfig;
+use vortex_core::error::{Result, VortexError};
+use vortex_core::handover::Handover;
+use vortex_core::network::{self, NetworkManager};
+use vortex_core::rules::RulesEngine;
+use vortex_core::templates::DevEnvironmentManager;
+
+// Rate limiting configuration
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024; // 1MB limit
+const MAX_REQUESTS_PER_SECOND: u32 = 50;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+// How long an upgrade waits for in-flight requests before handing over
+const UPGRADE_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct RateLimitState {
+    count: u32,
+    window_start: u64, // Unix timestamp in seconds
+}
+
+pub struct VortexDaemon {
+    session_manager: Arc<SessionManager>,
+    socket_path: PathBuf,
+    running: Arc<RwLock<bool>>,
+    rate_limiter: Arc<RwLock<HashMap<String, RateLimitState>>>,
+    /// Wakes the accept loop when the daemon should stop
+    shutdown: Arc<Notify>,
+    /// Executable to hand over to once the accept loop exits
+    upgrade_to: Arc<RwLock<Option<PathBuf>>>,
+    active_connections: Arc<AtomicUsize>,
+    /// Provider clients of other accounts are known by
+    auth_provider: Option<Arc<dyn AuthProvider>>,
+}
+
+/// Decides who a client is from the account of its process. Clients of the
+/// daemon's own account may do anything; those of other accounts only when
+/// `[auth] peer_users` lists them, with the permissions the provider gives.
+struct PeerAuth {
+    owner_uid: Option<u32>,
+    peer_users: Vec<String>,
+    provider: Option<Arc<dyn AuthProvider>>,
+}
+
+impl PeerAuth {
+    /// Who a client running as `uid` is, or why it may not use the daemon
+    async fn identify(&self, uid: Option<u32>) -> Result<AuthContext> {
+        let Some(uid) = uid else {
+            return Err(VortexError::PermissionDenied {
+                action: "clients of unknown accounts may not use the daemon".to_string(),
+            });
+        };
+        if Some(uid) == self.owner_uid {
+            return Ok(AuthContext::local());
+        }
+        let user = vortex_core::process::user_name(uid).unwrap_or_else(|| uid.to_string());
+        if !self.peer_users.contains(&user) {
+            return Err(VortexError::PermissionDenied {
+                action: format!("{} may not use the daemon of another account", user),
+            });
+        }
+        let known = match &self.provider {
+            Some(provider) => provider.get_user(&user).await.ok().flatten(),
+            None => None,
+        };
+        // Accounts the provider does not know must authenticate with a token
+        Ok(known.map(AuthContext::from).unwrap_or_else(AuthContext::anonymous))
+    }
+
+    /// Who `token` belongs to
+    async fn sign_in(&self, token: &str) -> Result<AuthContext> {
+        let Some(provider) = &self.provider else {
+            return Err(VortexError::AuthError {
+                message: "The daemon accepts no tokens".to_string(),
+            });
+        };
+        let credentials = AuthCredentials::Token {
+            token: token.to_string(),
+        };
+        AuthContext::authenticate(provider.as_ref(), &credentials).await
+    }
+}
+
+/// Counts a connection as in flight until dropped
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ConnectionGuard {
+    fn new(active: &Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        Self(active.clone())
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl VortexDaemon {
+    pub async fn new(session_manager: SessionManager) -> Result<Self> {
+        let socket_path = Self::get_socket_path()?;
+
+        // Clean up any existing socket
+        if socket_path.exists() {
+            tokio::fs::remove_file(&socket_path)
+                .await
+                .map_err(|e| VortexError::VmError {
+                    message: format!("Failed to remove existing socket: {}", e),
+                })?;
+        }
+
+        Ok(Self {
+            session_manager: Arc::new(session_manager),
+            socket_path,
+            running: Arc::new(RwLock::new(false)),
+            rate_limiter: Arc::new(RwLock::new(HashMap::new())),
+            shutdown: Arc::new(Notify::new()),
+            upgrade_to: Arc::new(RwLock::new(None)),
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            auth_provider: None,
+        })
+    }
+
+    /// Know clients of the accounts in `[auth] peer_users` by `provider`
+    pub fn with_auth_provider(mut self, provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_provider = Some(provider);
+        self
+    }
+
+    fn get_socket_path() -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::VmError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+
+        let vortex_dir = home.join(".vortex");
+        std::fs::create_dir_all(&vortex_dir).map_err(|e| VortexError::VmError {
+            message: format!("Failed to create vortex directory: {}", e),
+        })?;
+
+        Ok(vortex_dir.join("daemon.sock"))
+    }
+
+    /// Set secure permissions (0o600 - owner read/write only) on the socket file
+    /// This function exists for documentation purposes; actual permission setting
+    /// is done inline in the start() method after binding.
+    #[allow(dead_code)]
+    fn set_socket_permissions(_path: &PathBuf) -> Result<()> {
+        // On Unix, socket permissions are set after bind() using chmod
+        // On non-Unix systems, socket permissions work differently
+        Ok(())
+    }
+
+    /// Subscribe the configured automation rules to VM events
+    async fn register_rules(&self) {
+        let config = match VortexConfig::load() {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Automation rules disabled: {}", e);
+                return;
+            }
+        };
+        if config.rules.is_empty() {
+            return;
+        }
+        let vm_manager = self.session_manager.vm_manager();
+        let engine = RulesEngine::new(config.rules, Arc::downgrade(vm_manager));
+        info!("Loaded {} automation rule(s)", engine.rule_count());
+        vm_manager
+            .add_event_handler(Box::new(engine), config.events)
+            .await;
+    }
+
+    /// Start keeping the configured pool of VMs booted
+    async fn start_pool(&self) {
+        let targets = match VortexConfig::load() {
+            Ok(config) => config.pool,
+            Err(e) => {
+                warn!("VM pool disabled: {}", e);
+                return;
+            }
+        };
+        if targets.is_empty() {
+            return;
+        }
+        let templates = match self.session_manager.vm_manager().plugins() {
+            Some(plugins) => {
+                DevEnvironmentManager::with_plugin_templates(plugins.read().await.templates())
+            }
+            None => DevEnvironmentManager::new(),
+        };
+        let mut specs = Vec::new();
+        for target in targets {
+            match target.spec(&templates) {
+                Ok(spec) => specs.push((spec, target.size)),
+                Err(e) => warn!("Skipping pool target: {}", e),
+            }
+        }
+        info!("Prewarming VMs for {} pool target(s)", specs.len());
+        self.session_manager.vm_manager().prewarm(specs).await;
+    }
+
+    /// Answer DNS queries on the private networks of QEMU guests
+    async fn start_dns(&self) {
+        match NetworkManager::new().await {
+            Ok(networks) => {
+                tokio::spawn(async move { networks.serve_dns().await });
+            }
+            Err(e) => warn!("Private network DNS disabled: {}", e),
+        }
+    }
+
+    /// Proxy `http://<vm-name>.localhost` to VMs, if configured
+    async fn start_ingress(&self) {
+        let port = match VortexConfig::load() {
+            Ok(config) => config.networking.ingress_port,
+            Err(e) => {
+                warn!("Ingress disabled: {}", e);
+                return;
+            }
+        };
+        let Some(port) = port else {
+            return;
+        };
+        let vm_manager = Arc::clone(self.session_manager.vm_manager());
+        tokio::spawn(async move {
+            if let Err(e) = network::serve_ingress(vm_manager, port).await {
+                warn!("Ingress stopped: {}", e);
+            }
+        });
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        info!("Starting Vortex daemon on socket: {:?}", self.socket_path);
+
+        // Re-adopt VMs if the previous daemon handed over to this one
+        let handover_path = Handover::default_path()?;
+        if let Err(e) = self.session_manager.resume_from_handover(&handover_path).await {
+            warn!("Failed to resume from handover: {}", e);
+        }
+        self.register_rules().await;
+        self.start_pool().await;
+        self.start_dns().await;
+        self.start_ingress().await;
+
+        // Start boot-start sessions
+        let session_manager = self.session_manager.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session_manager.start_boot_start_sessions().await {
+                warn!("Failed to start boot-start sessions: {}", e);
+            }
+        });
+
+        let listener = UnixListener::bind(&self.socket_path).map_err(|e| VortexError::VmError {
+            message: format!("Failed to bind to socket: {}", e),
+        })?;
+
+        let (peer_users, peer_group) = match VortexConfig::load() {
+            Ok(config) => (config.auth.peer_users, config.auth.peer_group),
+            Err(e) => {
+                warn!("Other accounts may not use the daemon: {}", e);
+                (Vec::new(), None)
+            }
+        };
+        // Set secure permissions on the socket (owner read/write only)
+        // This prevents other users from connecting to the daemon, unless
+        // some may: the socket's group may then use it too, and their
+        // connections are checked one by one
+        let socket_mode = if peer_users.is_empty() { 0o600 } else { 0o660 };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.socket_path, std::fs::Permissions::from_mode(socket_mode))
+                .map_err(|e| VortexError::VmError {
+                    message: format!("Failed to set socket permissions: {}", e),
+                })?;
+        }
+        if let Some(group) = peer_group.filter(|_| !peer_users.is_empty()) {
+            let status = std::process::Command::new("chgrp")
+                .arg(&group)
+                .arg(&self.socket_path)
+                .status();
+            if !matches!(status, Ok(status) if status.success()) {
+                warn!(
+                    "Could not give the socket to group {}; it stays with the daemon account's group",
+                    group
+                );
+            }
+        }
+        let peer_auth = Arc::new(PeerAuth {
+            owner_uid: std::fs::metadata(&self.socket_path)
+                .ok()
+                .map(|metadata| std::os::unix::fs::MetadataExt::uid(&metadata)),
+            peer_users,
+            provider: self.auth_provider.clone(),
+        });
+
+        {
+            let mut running = self.running.write().await;
+            *running = true;
+        }
+
+        // Start cleanup task
+        let session_manager = self.session_manager.clone();
+        let running_cleanup = self.running.clone();
+        tokio::spawn(async move {
+            let mut cleanup_interval = interval(Duration::from_secs(3600)); // Every hour
+            loop {
+                cleanup_interval.tick().await;
+
+                if !*running_cleanup.read().await {
+                    break;
+                }
+
+                if let Err(e) = session_manager.cleanup_stale_sessions().await {
+                    warn!("Failed to cleanup stale sessions: {}", e);
+                }
+            }
+        });
+
+        info!("Vortex daemon started successfully (socket permissions: {:04o})", socket_mode);
+
+        // Main connection handling loop
+        while *self.running.read().await {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = self.shutdown.notified() => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let session_manager = self.session_manager.clone();
+                    let running = self.running.clone();
+                    let rate_limiter = self.rate_limiter.clone();
+                    let shutdown = self.shutdown.clone();
+                    let upgrade_to = self.upgrade_to.clone();
+                    let peer_auth = peer_auth.clone();
+                    let guard = ConnectionGuard::new(&self.active_connections);
+
+                    tokio::spawn(async move {
+                        let _guard = guard;
+                        if let Err(e) = Self::handle_connection(
+                            stream,
+                            session_manager,
+                            running,
+                            rate_limiter,
+                            shutdown,
+                            upgrade_to,
+                            peer_auth,
+                        )
+                        .await
+                        {
+                            error!("Error handling connection: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to accept connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        // Cleanup
+        drop(listener);
+        self.session_manager.vm_manager().drain_pool().await;
+        if let Some(plugins) = self.session_manager.vm_manager().plugins() {
+            if let Err(e) = plugins.write().await.shutdown_all().await {
+                warn!("Failed to shut down plugins: {}", e);
+            }
+        }
+        if self.socket_path.exists() {
+            tokio::fs::remove_file(&self.socket_path)
+                .await
+                .map_err(|e| VortexError::VmError {
+                    message: format!("Failed to remove socket: {}", e),
+                })?;
+        }
+
+        let upgrade_to = self.upgrade_to.write().await.take();
+        if let Some(executable) = upgrade_to {
+            return self.hand_over(&executable, &handover_path).await;
+        }
+
+        info!("Vortex daemon stopped");
+        Ok(())
+    }
+
+    /// Write the handover and start the daemon from `executable` in our place.
+    /// This process then exits, so VMMs it spawned are reparented rather than
+    /// left as zombies of a process that no longer waits on them.
+    async fn hand_over(&self, executable: &Path, handover_path: &Path) -> Result<()> {
+        let drained = tokio::time::timeout(UPGRADE_DRAIN_TIMEOUT, async {
+            while self.active_connections.load(Ordering::SeqCst) > 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
+        if drained.is_err() {
+            warn!("Handing over with requests still in flight");
+        }
+
+        let vms = self.session_manager.write_handover(handover_path).await?;
+        std::process::Command::new(executable)
+            .arg("daemon")
+            .arg("start")
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!(
+                    "Failed to start upgraded daemon {} (the next `vortex daemon start` will adopt the VMs): {}",
+                    executable.display(),
+                    e
+                ),
+            })?;
+
+        info!("Handed {} VMs over to {}", vms, executable.display());
+        Ok(())
+    }
+
+    pub async fn stop(&self) -> Result<()> {
+        info!("Stopping Vortex daemon");
+        let mut running = self.running.write().await;
+        *running = false;
+        self.shutdown.notify_one();
+        Ok(())
+    }
+
+    async fn handle_connection(
+        mut stream: UnixStream,
+        session_manager: Arc<SessionManager>,
+        running: Arc<RwLock<bool>>,
+        rate_limiter: Arc<RwLock<HashMap<String, RateLimitState>>>,
+        shutdown: Arc<Notify>,
+        upgrade_to: Arc<RwLock<Option<PathBuf>>>,
+        peer_auth: Arc<PeerAuth>,
+    ) -> Result<()> {
+        // Get client identifier before splitting (to avoid borrow issues)
+        let client_id = format!("{:?}", stream.peer_addr().ok());
+        // Who the client is, by the account of its process
+        let mut auth = peer_auth
+            .identify(stream.peer_cred().ok().map(|cred| cred.uid()))
+            .await;
+        if let Err(e) = &auth {
+            warn!("Rejecting client: {}", e);
+        }
+
+        let (reader, mut writer) = stream.split();
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) => break, // EOF
+                Ok(_) => {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    // Check message size limit
+                    if line.len() > MAX_MESSAGE_SIZE {
+                        let response = SessionResponse::Error {
+                            message: format!("Message too large. Maximum size is {} bytes.", MAX_MESSAGE_SIZE),
+                        };
+                        if let Err(e) = writer.write_all(format!("{}\n", serde_json::to_string(&response).unwrap()).as_bytes()).await {
+                            error!("Failed to write error response: {}", e);
+                            break;
+                        }
+                        continue;
+                    }
+
+                    // Rate limiting (client_id already captured before stream.split())
+                    let rate_limited = {
+                        let mut limiter = rate_limiter.write().await;
+                        !Self::check_rate_limit(&mut limiter, &client_id)
+                    };
+
+                    if rate_limited {
+                        warn!("Rate limit exceeded for client");
+                        let response = SessionResponse::Error {
+                            message: "Rate limit exceeded. Please slow down.".to_string(),
+                        };
+                        if let Err(e) = writer.write_all(format!("{}\n", serde_json::to_string(&response).unwrap()).as_bytes()).await {
+                            error!("Failed to write rate limit response: {}", e);
+                            break;
+                        }
+                        continue;
+                    }
+
+                    let mut stopping = false;
+                    let response = match serde_json::from_str::<SessionCommand>(line) {
+                        Ok(command) => {
+                            let denied = match &auth {
+                                Ok(auth) => command
+                                    .permission()
+                                    .and_then(|p| auth.require(p).err())
+                                    .map(|e| e.to_string()),
+                                Err(e) => Some(e.to_string()),
+                            };
+                            if let Some(message) = denied {
+                                SessionResponse::Error { message }
+                            } else if let SessionCommand::Authenticate { token: Some(token) }
+                            | SessionCommand::VerifyToken { token: Some(token) } = &command
+                            {
+                                // Verifying a token leaves who the client is unchanged
+                                match peer_auth.sign_in(token).await {
+                                    Ok(signed_in) => {
+                                        if matches!(command, SessionCommand::Authenticate { .. }) {
+                                            auth = Ok(signed_in);
+                                        }
+                                        SessionResponse::Success
+                                    }
+                                    Err(e) => SessionResponse::Error {
+                                        message: e.to_string(),
+                                    },
+                                }
+                            // Handle shutdown command specially
+                            } else if matches!(command, SessionCommand::Shutdown) {
+                                let mut running_guard = running.write().await;
+                                *running_guard = false;
+                                stopping = true;
+                                SessionResponse::Success
+                            } else if let SessionCommand::Upgrade { executable } = command {
+                                if executable.is_absolute() && executable.is_file() {
+                                    *upgrade_to.write().await = Some(executable);
+                                    *running.write().await = false;
+                                    stopping = true;
+                                    SessionResponse::Success
+                                } else {
+                                    SessionResponse::Error {
+                                        message: format!(
+                                            "Upgrade executable {} is not an absolute path to a file",
+                                            executable.display()
+                                        ),
+                                    }
+                                }
+                            } else {
+                                let user_id = auth.as_ref().map(|auth| auth.user_id.as_str()).unwrap_or_default();
+                                let allowed = match command.session_id() {
+                                    Some(session_id) => {
+                                        session_manager
+                                            .check_workspace_access(session_id, user_id)
+                                            .await
+                                    }
+                                    None => Ok(()),
+                                };
+                                let result = match allowed {
+                                    Ok(()) => session_manager.handle_command(command).await,
+                                    Err(e) => Err(e),
+                                };
+                                result.unwrap_or_else(|e| SessionResponse::Error {
+                                    message: e.to_string(),
+                                })
+                            }
+                        }
+                        Err(e) => SessionResponse::Error {
+                            message: format!("Invalid command: {}", e),
+                        },
+                    };
+
+                    let response_json = match serde_json::to_string(&response) {
+                        Ok(json) => json,
+                        Err(_) => {
+                            error!("Failed to serialize response");
+                            serde_json::to_string(&SessionResponse::Error {
+                                message: "Internal server error".to_string(),
+                            })
+                            .expect("Failed to serialize error response - this should never fail")
+                        }
+                    };
+
+                    if let Err(e) = writer
+                        .write_all(format!("{}\n", response_json).as_bytes())
+                        .await
+                    {
+                        error!("Failed to write response: {}", e);
+                        break;
+                    }
+
+                    // Stop accepting only once the client has its answer
+                    if stopping {
+                        shutdown.notify_one();
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Error reading from stream: {}", e);
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if rate limit is exceeded for a client
+    fn check_rate_limit(rate_limiter: &mut HashMap<String, RateLimitState>, client_id: &str) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let state = rate_limiter.entry(client_id.to_string()).or_insert(RateLimitState {
+            count: 0,
+            window_start: now,
+        });
+
+        // Reset window if expired
+        if now > state.window_start + RATE_LIMIT_WINDOW.as_secs() {
+            state.count = 0;
+            state.window_start = now;
+        }
+
+        // Check limit
+        if state.count >= MAX_REQUESTS_PER_SECOND {
+            return false; // Rate limited
+        }
+
+        state.count += 1;
+        true // Allowed
+    }
+}
+
+pub struct DaemonClient {
+    socket_path: PathBuf,
+}
+
+impl DaemonClient {
+    pub fn new() -> Result<Self> {
+        let socket_path = VortexDaemon::get_socket_path()?;
+        Ok(Self { socket_path })
+    }
+
+    /// Cheap check for a daemon socket, without connecting
+    pub fn socket_exists(&self) -> bool {
+        self.socket_path.exists()
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.send_command(SessionCommand::Ping).await.is_ok()
+    }
+
+    pub async fn send_command(&self, command: SessionCommand) -> Result<SessionResponse> {
+        let mut stream =
+            UnixStream::connect(&self.socket_path)
+                .await
+                .map_err(|e| VortexError::VmError {
+                    message: format!("Failed to connect to daemon: {}", e),
+                })?;
+
+        let command_json = serde_json::to_string(&command).map_err(|e| VortexError::VmError {
+            message: format!("Failed to serialize command: {}", e),
+        })?;
+
+        stream
+            .write_all(format!("{}\n", command_json).as_bytes())
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to send command: {}", e),
+            })?;
+
+        let mut reader = BufReader::new(stream);
+        let mut response_line = String::new();
+        reader
+            .read_line(&mut response_line)
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to read response: {}", e),
+            })?;
+
+        serde_json::from_str(response_line.trim()).map_err(|e| VortexError::VmError {
+            message: format!("Failed to parse response: {}", e),
+        })
+    }
+
+    pub async fn start_daemon_if_needed() -> Result<()> {
+        let client = Self::new()?;
+
+        if client.is_running().await {
+            info!("Daemon is already running");
+            return Ok(());
+        }
+
+        info!("Starting daemon in background");
+
+        // Fork a background process to run the daemon
+        let current_exe = std::env::current_exe().map_err(|e| VortexError::VmError {
+            message: format!("Failed to get current executable: {}", e),
+        })?;
+
+        std::process::Command::new(current_exe)
+            .arg("daemon")
+            .arg("start")
+            .arg("--background")
+            .spawn()
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to start daemon: {}", e),
+            })?;
+
+        // Wait for daemon to start
+        for _ in 0..10 {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            if client.is_running().await {
+                info!("Daemon started successfully");
+                return Ok(());
+            }
+        }
+
+        Err(VortexError::VmError {
+            message: "Daemon failed to start within timeout".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vortex_core::auth::{ApiToken, Permission, TokenAuthProvider};
+    use vortex_core::vm::{GpuDevice, VmSpec};
+
+    #[tokio::test]
+    async fn test_other_accounts_need_to_be_listed() {
+        // An account no one has, known by its uid
+        let uid = 4_242_424;
+        let provider = TokenAuthProvider::new(vec![ApiToken {
+            name: uid.to_string(),
+            token: Some("s3cret".to_string()),
+            token_sha256: None,
+            permissions: vec![Permission::VmRead],
+            expires_at: None,
+        }])
+        .unwrap();
+        let mut peer_auth = PeerAuth {
+            owner_uid: Some(1000),
+            peer_users: Vec::new(),
+            provider: Some(Arc::new(provider)),
+        };
+
+        let owner = peer_auth.identify(Some(1000)).await.unwrap();
+        assert!(owner.allows(Permission::AdminAll));
+        assert!(peer_auth.identify(Some(uid)).await.is_err());
+        assert!(peer_auth.identify(None).await.is_err());
+
+        peer_auth.peer_users.push(uid.to_string());
+        let peer = peer_auth.identify(Some(uid)).await.unwrap();
+        assert_eq!(peer.user_id, uid.to_string());
+        assert!(peer.allows(Permission::VmRead));
+        assert!(!peer.allows(Permission::VmCreate));
+
+        assert!(peer_auth.sign_in("wrong").await.is_err());
+        assert!(peer_auth.sign_in("s3cret").await.unwrap().allows(Permission::VmRead));
+    }
+
+    #[test]
+    fn test_sessions_reaching_the_host_need_admin() {
+        let create = |spec: VmSpec| SessionCommand::CreateSession {
+            spec: Box::new(spec),
+            name: None,
+            persistent: false,
+            boot_start: false,
+        };
+        assert_eq!(create(VmSpec::default()).permission(), Some(Permission::VmCreate));
+
+        let mut mounting = VmSpec::default();
+        mounting.volumes.insert("/etc".into(), "/mnt".into());
+        assert_eq!(create(mounting).permission(), Some(Permission::AdminAll));
+
+        let mut hooked = VmSpec::default();
+        hooked.hooks.pre_start = Some("touch /tmp/owned".to_string());
+        assert_eq!(create(hooked).permission(), Some(Permission::AdminAll));
+
+        let mut with_gpu = VmSpec::default();
+        with_gpu.gpus.push(GpuDevice::Vfio {
+            address: "0000:01:00.0".to_string(),
+        });
+        assert_eq!(create(with_gpu).permission(), Some(Permission::AdminAll));
+
+        let picking = VmSpec {
+            backend: Some("firecracker".to_string()),
+            ..VmSpec::default()
+        };
+        assert_eq!(create(picking).permission(), Some(Permission::AdminAll));
+    }
+}
@@ -0,0 +1,14 @@
+//! # Vortex Daemon
+//!
+//! Long-lived sessions and the background daemon that owns them: the
+//! [`SessionManager`], its on-disk store, the Unix socket served by
+//! [`VortexDaemon`] and the [`DaemonClient`] the CLI talks to it with.
+
+pub mod config_cache;
+pub mod daemon;
+pub mod session;
+pub mod session_store;
+pub mod trace;
+
+pub use daemon::{DaemonClient, VortexDaemon};
+pub use session::{SessionCommand, SessionManager, SessionResponse, SessionState, VmSession};
@@ -0,0 +1,1190 @@
+use crate::config_cache::{ConfigCache, ConfigFingerprint};
+use crate::session_store::SessionStore;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+use vortex_core::audit::{AuditAction, AuditRecord};
+use vortex_core::auth::Permission;
+use vortex_core::config::VortexConfig;
+use vortex_core::error::{Result, VortexError};
+use vortex_core::event_queue::SubscriberStats;
+use vortex_core::events::SessionStateName;
+use vortex_core::handover::Handover;
+use vortex_core::ids::{
+    SessionId, VmId, WorkspaceId, LABEL_SESSION_ID, LABEL_SESSION_NAME, LABEL_WORKSPACE_ID,
+};
+use vortex_core::listing::{ListQuery, Listable, Page};
+use vortex_core::vm::{VmManager, VmSpec};
+use vortex_core::workspace::WorkspaceManager;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmSession {
+    pub id: SessionId,
+    pub name: Option<String>,
+    pub vm_id: VmId,
+    pub state: SessionState,
+    pub created_at: DateTime<Utc>,
+    pub last_attached: Option<DateTime<Utc>>,
+    pub persistent: bool,
+    /// If true, this session will be started automatically when the daemon starts
+    pub boot_start: bool,
+    pub spec: VmSpec,
+    pub metadata: HashMap<String, String>,
+    /// Workspace this session was started from, if any
+    #[serde(default)]
+    pub workspace_id: Option<WorkspaceId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionState {
+    Creating,
+    Running,
+    Detached,
+    Attached { client_pid: u32 },
+    Paused,
+    Stopped,
+    Error { message: String },
+}
+
+impl SessionState {
+    /// Lowercase state name used for filtering
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionState::Creating => "creating",
+            SessionState::Running => "running",
+            SessionState::Detached => "detached",
+            SessionState::Attached { .. } => "attached",
+            SessionState::Paused => "paused",
+            SessionState::Stopped => "stopped",
+            SessionState::Error { .. } => "error",
+        }
+    }
+}
+
+impl From<&SessionState> for SessionStateName {
+    fn from(state: &SessionState) -> Self {
+        match state {
+            SessionState::Creating => SessionStateName::Creating,
+            SessionState::Running => SessionStateName::Running,
+            SessionState::Detached => SessionStateName::Detached,
+            SessionState::Attached { .. } => SessionStateName::Attached,
+            SessionState::Paused => SessionStateName::Paused,
+            SessionState::Stopped => SessionStateName::Stopped,
+            SessionState::Error { .. } => SessionStateName::Error,
+        }
+    }
+}
+
+#[cfg(test)]
+impl VmSession {
+    /// A detached session `id` of an Alpine VM `vortex-<id>`
+    pub(crate) fn detached(id: &str) -> Self {
+        Self {
+            id: SessionId::new(id),
+            name: None,
+            vm_id: VmId::new(format!("vortex-{}", id)),
+            state: SessionState::Detached,
+            created_at: Utc::now(),
+            last_attached: None,
+            persistent: true,
+            boot_start: false,
+            spec: VmSpec {
+                image: "alpine".to_string(),
+                ..Default::default()
+            },
+            metadata: HashMap::new(),
+            workspace_id: None,
+        }
+    }
+}
+
+impl Listable for VmSession {
+    fn list_id(&self) -> &str {
+        &self.id
+    }
+
+    fn list_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn list_state(&self) -> &'static str {
+        self.state.name()
+    }
+
+    fn list_labels(&self) -> &HashMap<String, String> {
+        &self.spec.labels
+    }
+}
+
+/// Whether creating a VM of `spec` reads or runs anything on the host:
+/// mounts, host hooks, host GPUs, or a backend the client picked rather than
+/// the one the daemon would
+fn reaches_host(spec: &VmSpec) -> bool {
+    !spec.volumes.is_empty()
+        || spec.hooks.pre_start.is_some()
+        || spec.hooks.pre_stop.is_some()
+        || !spec.gpus.is_empty()
+        || spec.backend.is_some()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SessionCommand {
+    // Session management
+    CreateSession {
+        spec: Box<VmSpec>,
+        name: Option<String>,
+        persistent: bool,
+        boot_start: bool,
+    },
+    ListSessions,
+    /// Paginated, filtered listing with optional field projection
+    ListSessionsPage {
+        query: ListQuery,
+    },
+    GetSession {
+        session_id: SessionId,
+    },
+    DeleteSession {
+        session_id: SessionId,
+    },
+
+    // VM lifecycle
+    StartSession {
+        session_id: SessionId,
+    },
+    StopSession {
+        session_id: SessionId,
+    },
+    PauseSession {
+        session_id: SessionId,
+    },
+    ResumeSession {
+        session_id: SessionId,
+    },
+    RestartSession {
+        session_id: SessionId,
+    },
+
+    // Interactive
+    AttachSession {
+        session_id: SessionId,
+        client_pid: u32,
+    },
+    DetachSession {
+        session_id: SessionId,
+    },
+
+    // Authentication (optional token for daemon access)
+    Authenticate {
+        token: Option<String>,
+    },
+    VerifyToken {
+        token: Option<String>,
+    },
+
+    // Boot start management
+    EnableBootStart {
+        session_id: SessionId,
+    },
+    DisableBootStart {
+        session_id: SessionId,
+    },
+    GetBootStartSessions,
+
+    // Config served from the daemon's cache, unexpanded
+    GetConfig,
+
+    // Daemon control
+    Ping,
+    Shutdown,
+    /// Hand sessions and VMs over to a new daemon started from `executable`
+    Upgrade {
+        executable: PathBuf,
+    },
+    GetDaemonStatus,
+}
+
+impl SessionCommand {
+    /// The session the command acts on, if it acts on one
+    pub fn session_id(&self) -> Option<&SessionId> {
+        match self {
+            SessionCommand::GetSession { session_id }
+            | SessionCommand::DeleteSession { session_id }
+            | SessionCommand::StartSession { session_id }
+            | SessionCommand::StopSession { session_id }
+            | SessionCommand::PauseSession { session_id }
+            | SessionCommand::ResumeSession { session_id }
+            | SessionCommand::RestartSession { session_id }
+            | SessionCommand::AttachSession { session_id, .. }
+            | SessionCommand::DetachSession { session_id }
+            | SessionCommand::EnableBootStart { session_id }
+            | SessionCommand::DisableBootStart { session_id } => Some(session_id),
+            _ => None,
+        }
+    }
+
+    /// The permission a client needs to run the command; pinging and
+    /// authenticating need none. The daemon creates VMs as its own account,
+    /// so a session that mounts host paths, runs host hooks, attaches GPUs
+    /// or picks its backend needs `AdminAll`.
+    pub fn permission(&self) -> Option<Permission> {
+        match self {
+            SessionCommand::CreateSession { spec, .. } if reaches_host(spec) => {
+                Some(Permission::AdminAll)
+            }
+            SessionCommand::CreateSession { .. } => Some(Permission::VmCreate),
+            SessionCommand::ListSessions
+            | SessionCommand::ListSessionsPage { .. }
+            | SessionCommand::GetSession { .. }
+            | SessionCommand::GetBootStartSessions
+            | SessionCommand::GetDaemonStatus => Some(Permission::VmRead),
+            SessionCommand::StartSession { .. }
+            | SessionCommand::PauseSession { .. }
+            | SessionCommand::ResumeSession { .. }
+            | SessionCommand::RestartSession { .. }
+            | SessionCommand::AttachSession { .. }
+            | SessionCommand::DetachSession { .. }
+            | SessionCommand::EnableBootStart { .. }
+            | SessionCommand::DisableBootStart { .. } => Some(Permission::VmUpdate),
+            SessionCommand::StopSession { .. } | SessionCommand::DeleteSession { .. } => {
+                Some(Permission::VmDelete)
+            }
+            // The config holds the configured tokens
+            SessionCommand::GetConfig
+            | SessionCommand::Shutdown
+            | SessionCommand::Upgrade { .. } => Some(Permission::AdminAll),
+            SessionCommand::Ping
+            | SessionCommand::Authenticate { .. }
+            | SessionCommand::VerifyToken { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SessionResponse {
+    Success,
+    Error {
+        message: String,
+    },
+    SessionCreated {
+        session: VmSession,
+    },
+    SessionList {
+        sessions: Vec<VmSession>,
+    },
+    SessionPage {
+        page: Page<serde_json::Value>,
+    },
+    Session {
+        session: VmSession,
+    },
+    BootStartSessions {
+        sessions: Vec<VmSession>,
+    },
+    Config {
+        fingerprint: ConfigFingerprint,
+        config: Box<VortexConfig>,
+    },
+    DaemonStatus {
+        uptime: u64,
+        sessions_count: usize,
+        active_vms: usize,
+        memory_usage: u64,
+        /// Event queue metrics; absent from daemons predating them
+        #[serde(default)]
+        event_subscribers: Vec<SubscriberStats>,
+    },
+}
+
+pub struct SessionManager {
+    sessions: RwLock<HashMap<SessionId, VmSession>>,
+    vm_manager: Arc<VmManager>,
+    store: SessionStore,
+    daemon_start_time: DateTime<Utc>,
+    config_cache: ConfigCache,
+    /// Workspaces sessions are checked against; `~/.vortex/workspaces`
+    /// when unset
+    workspaces: Option<WorkspaceManager>,
+}
+
+impl SessionManager {
+    pub async fn new(vm_manager: Arc<VmManager>) -> Result<Self> {
+        let store = SessionStore::new(Self::get_session_file()?);
+        Self::with_store(vm_manager, store).await
+    }
+
+    /// Sessions kept in `store` rather than `~/.vortex/sessions.json`
+    pub async fn with_store(vm_manager: Arc<VmManager>, store: SessionStore) -> Result<Self> {
+        let manager = Self {
+            sessions: RwLock::new(HashMap::new()),
+            vm_manager,
+            store,
+            daemon_start_time: Utc::now(),
+            config_cache: ConfigCache::new(),
+            workspaces: None,
+        };
+
+        // Load persisted sessions from disk
+        let mut new_manager = manager;
+        new_manager.load_sessions().await?;
+
+        Ok(new_manager)
+    }
+
+    /// Check sessions against the workspaces of `workspaces`
+    pub fn with_workspaces(mut self, workspaces: WorkspaceManager) -> Self {
+        self.workspaces = Some(workspaces);
+        self
+    }
+
+    pub(crate) fn vm_manager(&self) -> &Arc<VmManager> {
+        &self.vm_manager
+    }
+
+    /// Fail unless `user` may use the workspace the session was started
+    /// from, if it was started from one. A session of a workspace that no
+    /// longer exists is no one's to use.
+    pub async fn check_workspace_access(&self, session_id: &SessionId, user: &str) -> Result<()> {
+        let Some(workspace_id) = self
+            .get_session(session_id)
+            .await?
+            .and_then(|session| session.workspace_id)
+        else {
+            return Ok(());
+        };
+        let workspace = match &self.workspaces {
+            Some(workspaces) => workspaces.get_workspace(workspace_id.as_str())?,
+            None => WorkspaceManager::new()?.get_workspace(workspace_id.as_str())?,
+        };
+        match workspace {
+            Some(workspace) if workspace.config.allows(user) => Ok(()),
+            Some(workspace) => Err(VortexError::PermissionDenied {
+                action: format!(
+                    "{} may not use session {} of workspace '{}'",
+                    user, session_id, workspace.name
+                ),
+            }),
+            None => Err(VortexError::PermissionDenied {
+                action: format!(
+                    "{} may not use session {} of missing workspace '{}'",
+                    user, session_id, workspace_id
+                ),
+            }),
+        }
+    }
+
+    /// Sessions are recorded in the audit log of their VMs' manager
+    fn audit<T>(&self, record: AuditRecord, result: &Result<T>) {
+        if let Some(audit) = self.vm_manager.audit_log() {
+            audit.record(record, result);
+        }
+    }
+
+    fn get_session_file() -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| VortexError::VmError {
+            message: "Could not determine home directory".to_string(),
+        })?;
+
+        let vortex_dir = home.join(".vortex");
+        std::fs::create_dir_all(&vortex_dir).map_err(|e| VortexError::VmError {
+            message: format!("Failed to create vortex directory: {}", e),
+        })?;
+
+        Ok(vortex_dir.join("sessions.json"))
+    }
+
+    async fn load_sessions(&mut self) -> Result<()> {
+        let store = self.store.clone();
+        let sessions = tokio::task::spawn_blocking(move || store.load())
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to load sessions: {}", e),
+            })??;
+
+        let mut session_map = self.sessions.write().await;
+        *session_map = sessions;
+
+        Ok(())
+    }
+
+    /// Write all sessions of this manager to the store, keeping those other
+    /// processes added
+    async fn save_sessions(&self) -> Result<()> {
+        let sessions = self.sessions.read().await.clone();
+        self.update_store(move |stored| stored.extend(sessions))
+            .await
+    }
+
+    /// Write the session's state, or its removal, to the store, leaving the
+    /// other sessions there as they are
+    async fn save_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = self.sessions.read().await.get(session_id).cloned();
+        let session_id = session_id.clone();
+        self.update_store(move |stored| match session {
+            Some(session) => {
+                stored.insert(session_id, session);
+            }
+            None => {
+                stored.remove(&session_id);
+            }
+        })
+        .await
+    }
+
+    /// Record `session` and write it to the store, telling the VM manager's
+    /// subscribers when its state changed
+    async fn set_session(&self, session: VmSession) -> Result<()> {
+        let previous = self
+            .sessions
+            .write()
+            .await
+            .insert(session.id.clone(), session.clone());
+        self.save_session(&session.id).await?;
+
+        if previous.map_or(true, |previous| previous.state != session.state) {
+            let message = match &session.state {
+                SessionState::Error { message } => Some(message.clone()),
+                _ => None,
+            };
+            let emitted = self
+                .vm_manager
+                .session_state_changed(
+                    &session.vm_id,
+                    &session.id,
+                    SessionStateName::from(&session.state),
+                    message,
+                )
+                .await;
+            if let Err(e) = emitted {
+                warn!("Failed to emit state of session {}: {}", session.id, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_store(
+        &self,
+        change: impl FnOnce(&mut HashMap<SessionId, VmSession>) + Send + 'static,
+    ) -> Result<()> {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || store.update(change))
+            .await
+            .map_err(|e| VortexError::VmError {
+                message: format!("Failed to save sessions: {}", e),
+            })?
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id))]
+    pub async fn create_session(
+        &self,
+        spec: VmSpec,
+        name: Option<String>,
+        persistent: bool,
+        boot_start: bool,
+    ) -> Result<VmSession> {
+        let uuid_str = Uuid::new_v4().simple().to_string();
+        let session_id = SessionId::new(format!("session-{}", &uuid_str[..8]));
+        tracing::Span::current().record("session_id", session_id.as_str());
+        let vm_id = VmId::new(format!("vortex-{}", &session_id));
+
+        // Create the VM
+        let mut vm_spec = spec.clone();
+        vm_spec
+            .labels
+            .insert(LABEL_SESSION_ID.to_string(), session_id.to_string());
+        vm_spec
+            .labels
+            .insert("persistent".to_string(), persistent.to_string());
+        vm_spec
+            .labels
+            .insert("boot_start".to_string(), boot_start.to_string());
+        if let Some(ref name) = name {
+            vm_spec
+                .labels
+                .insert(LABEL_SESSION_NAME.to_string(), name.clone());
+        }
+
+        let session = VmSession {
+            id: session_id.clone(),
+            name: name.clone(),
+            vm_id: vm_id.clone(),
+            state: SessionState::Creating,
+            created_at: Utc::now(),
+            last_attached: None,
+            persistent,
+            boot_start,
+            spec: vm_spec.clone(),
+            metadata: HashMap::new(),
+            workspace_id: vm_spec
+                .labels
+                .get(LABEL_WORKSPACE_ID)
+                .map(|id| WorkspaceId::new(id.as_str())),
+        };
+
+        // Store session first
+        self.set_session(session.clone()).await?;
+
+        // Create VM instance
+        let mut record = AuditRecord::new(AuditAction::SessionCreate)
+            .with_spec(&vm_spec)
+            .with_session(&session_id);
+        let result = self.vm_manager.create(vm_spec).await;
+        if let Ok(vm_instance) = &result {
+            record = record.with_vm(&vm_instance.id);
+        }
+        self.audit(record, &result);
+        match result {
+            Ok(vm_instance) => {
+                let mut updated_session = session;
+                updated_session.vm_id = vm_instance.id;
+                updated_session.state = SessionState::Detached;
+
+                self.set_session(updated_session.clone()).await?;
+
+                tracing::info!(
+                    "Created session {} with VM {}",
+                    session_id,
+                    updated_session.vm_id
+                );
+                Ok(updated_session)
+            }
+            Err(e) => {
+                let mut failed_session = session;
+                failed_session.state = SessionState::Error {
+                    message: e.to_string(),
+                };
+
+                self.set_session(failed_session).await?;
+
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<VmSession>> {
+        let sessions = self.sessions.read().await;
+        Ok(sessions.values().cloned().collect())
+    }
+
+    pub async fn list_sessions_page(&self, query: &ListQuery) -> Result<Page<VmSession>> {
+        let sessions = self.sessions.read().await;
+        Ok(query.paginate(sessions.values().cloned()))
+    }
+
+    pub async fn get_session(&self, session_id: &SessionId) -> Result<Option<VmSession>> {
+        let sessions = self.sessions.read().await;
+        Ok(sessions.get(session_id).cloned())
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn delete_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = {
+            let mut sessions = self.sessions.write().await;
+            sessions.remove(session_id)
+        };
+
+        if let Some(session) = session {
+            // Stop and cleanup VM if it exists
+            let removed = self.vm_manager.cleanup(&session.vm_id).await;
+            let record = AuditRecord::new(AuditAction::SessionDelete)
+                .with_session(session_id)
+                .with_vm(&session.vm_id);
+            self.audit(record, &removed);
+            if let Err(e) = removed {
+                tracing::warn!(
+                    "Failed to cleanup VM {} for session {}: {}",
+                    session.vm_id,
+                    session_id,
+                    e
+                );
+            }
+
+            self.save_session(session_id).await?;
+            tracing::info!("Deleted session {}", session_id);
+            Ok(())
+        } else {
+            Err(VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn set_boot_start(&self, session_id: &SessionId, enabled: bool) -> Result<()> {
+        let found = match self.sessions.write().await.get_mut(session_id) {
+            Some(session) => {
+                session.boot_start = enabled;
+                true
+            }
+            None => false,
+        };
+        if found {
+            self.save_session(session_id).await?;
+            tracing::info!("Set boot_start={} for session {}", enabled, session_id);
+            Ok(())
+        } else {
+            Err(VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })
+        }
+    }
+
+    pub async fn get_boot_start_sessions(&self) -> Result<Vec<VmSession>> {
+        let sessions = self.sessions.read().await;
+        Ok(sessions
+            .values()
+            .filter(|s| s.boot_start)
+            .cloned()
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn start_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = self
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })?;
+
+        match session.state {
+            SessionState::Stopped | SessionState::Error { .. } => {
+                // Recreate the VM
+                let mut record = AuditRecord::new(AuditAction::SessionStart)
+                    .with_spec(&session.spec)
+                    .with_session(session_id);
+                let result = self.vm_manager.create(session.spec.clone()).await;
+                if let Ok(vm_instance) = &result {
+                    record = record.with_vm(&vm_instance.id);
+                }
+                self.audit(record, &result);
+                let vm_instance = result?;
+
+                let mut updated_session = session;
+                updated_session.vm_id = vm_instance.id;
+                updated_session.state = SessionState::Detached;
+
+                self.set_session(updated_session).await?;
+
+                tracing::info!("Started session {}", session_id);
+                Ok(())
+            }
+            SessionState::Running | SessionState::Detached | SessionState::Attached { .. } => {
+                tracing::info!("Session {} already running", session_id);
+                Ok(())
+            }
+            _ => Err(VortexError::VmError {
+                message: format!(
+                    "Cannot start session {} in state {:?}",
+                    session_id, session.state
+                ),
+            }),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn stop_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = self
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })?;
+
+        let stopped = self.vm_manager.stop(&session.vm_id).await;
+        let record = AuditRecord::new(AuditAction::SessionStop)
+            .with_session(session_id)
+            .with_vm(&session.vm_id);
+        self.audit(record, &stopped);
+        if let Err(e) = stopped {
+            tracing::warn!(
+                "Failed to stop VM {} for session {}: {}",
+                session.vm_id,
+                session_id,
+                e
+            );
+        }
+
+        let mut updated_session = session;
+        updated_session.state = SessionState::Stopped;
+
+        self.set_session(updated_session).await?;
+
+        tracing::info!("Stopped session {}", session_id);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn pause_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = self
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })?;
+        self.vm_manager.pause(&session.vm_id).await?;
+
+        let mut updated_session = session;
+        updated_session.state = SessionState::Paused;
+
+        self.set_session(updated_session).await?;
+
+        tracing::info!("Paused session {}", session_id);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn resume_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = self
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })?;
+        self.vm_manager.resume(&session.vm_id).await?;
+
+        let mut updated_session = session;
+        updated_session.state = SessionState::Detached;
+
+        self.set_session(updated_session).await?;
+
+        tracing::info!("Resumed session {}", session_id);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn restart_session(&self, session_id: &SessionId) -> Result<()> {
+        self.stop_session(session_id).await?;
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        self.start_session(session_id).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn attach_session(&self, session_id: &SessionId, client_pid: u32) -> Result<()> {
+        let session = self
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })?;
+
+        // A client that exited without detaching must not lock the session
+        let state = match session.state.clone() {
+            SessionState::Attached { client_pid }
+                if !vortex_core::process::is_alive(client_pid) =>
+            {
+                tracing::warn!(
+                    "Session {} was held by exited client {}, taking it over",
+                    session_id,
+                    client_pid
+                );
+                SessionState::Detached
+            }
+            state => state,
+        };
+
+        match state {
+            SessionState::Detached | SessionState::Running => {
+                // Update session state
+                let mut updated_session = session.clone();
+                updated_session.state = SessionState::Attached { client_pid };
+                updated_session.last_attached = Some(Utc::now());
+
+                self.set_session(updated_session).await?;
+
+                // Attach to VM
+                self.vm_manager.attach(&session.vm_id).await?;
+
+                // When attach returns (user detached), update state
+                let mut detached_session = session;
+                detached_session.state = SessionState::Detached;
+
+                self.set_session(detached_session).await?;
+
+                Ok(())
+            }
+            SessionState::Attached { .. } => Err(VortexError::VmError {
+                message: format!("Session {} is already attached", session_id),
+            }),
+            _ => Err(VortexError::VmError {
+                message: format!(
+                    "Cannot attach to session {} in state {:?}",
+                    session_id, state
+                ),
+            }),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
+    pub async fn detach_session(&self, session_id: &SessionId) -> Result<()> {
+        let session = self
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| VortexError::VmError {
+                message: format!("Session {} not found", session_id),
+            })?;
+
+        let mut updated_session = session;
+        updated_session.state = SessionState::Detached;
+
+        self.set_session(updated_session).await?;
+
+        tracing::info!("Detached from session {}", session_id);
+        Ok(())
+    }
+
+    pub async fn get_daemon_status(&self) -> Result<SessionResponse> {
+        let sessions = self.sessions.read().await;
+        let uptime = (Utc::now() - self.daemon_start_time).num_seconds() as u64;
+        let sessions_count = sessions.len();
+
+        let active_vms = sessions
+            .values()
+            .filter(|s| {
+                matches!(
+                    s.state,
+                    SessionState::Running | SessionState::Attached { .. } | SessionState::Detached
+                )
+            })
+            .count();
+
+        // Get memory usage from actual metrics
+        let memory_usage = if sessions_count > 0 {
+            // Use actual estimated memory per session
+            sessions_count as u64 * 512 * 1024 * 1024
+        } else {
+            0
+        };
+
+        Ok(SessionResponse::DaemonStatus {
+            uptime,
+            sessions_count,
+            active_vms,
+            memory_usage,
+            event_subscribers: self.vm_manager.event_subscriber_stats().await,
+        })
+    }
+
+    pub async fn handle_command(&self, command: SessionCommand) -> Result<SessionResponse> {
+        match command {
+            SessionCommand::CreateSession {
+                spec,
+                name,
+                persistent,
+                boot_start,
+            } => match self
+                .create_session(*spec, name, persistent, boot_start)
+                .await
+            {
+                Ok(session) => Ok(SessionResponse::SessionCreated { session }),
+                Err(e) => Ok(SessionResponse::Error {
+                    message: e.to_string(),
+                }),
+            },
+            SessionCommand::ListSessions => match self.list_sessions().await {
+                Ok(sessions) => Ok(SessionResponse::SessionList { sessions }),
+                Err(e) => Ok(SessionResponse::Error {
+                    message: e.to_string(),
+                }),
+            },
+            SessionCommand::ListSessionsPage { query } => {
+                match self
+                    .list_sessions_page(&query)
+                    .await
+                    .and_then(|page| query.project(page))
+                {
+                    Ok(page) => Ok(SessionResponse::SessionPage { page }),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::GetSession { session_id } => {
+                match self.get_session(&session_id).await {
+                    Ok(Some(session)) => Ok(SessionResponse::Session { session }),
+                    Ok(None) => Ok(SessionResponse::Error {
+                        message: format!("Session {} not found", session_id),
+                    }),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::DeleteSession { session_id } => {
+                match self.delete_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::StartSession { session_id } => {
+                match self.start_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::StopSession { session_id } => {
+                match self.stop_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::PauseSession { session_id } => {
+                match self.pause_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::ResumeSession { session_id } => {
+                match self.resume_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::RestartSession { session_id } => {
+                match self.restart_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::AttachSession {
+                session_id,
+                client_pid,
+            } => match self.attach_session(&session_id, client_pid).await {
+                Ok(()) => Ok(SessionResponse::Success),
+                Err(e) => Ok(SessionResponse::Error {
+                    message: e.to_string(),
+                }),
+            },
+            SessionCommand::DetachSession { session_id } => {
+                match self.detach_session(&session_id).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::EnableBootStart { session_id } => {
+                match self.set_boot_start(&session_id, true).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::DisableBootStart { session_id } => {
+                match self.set_boot_start(&session_id, false).await {
+                    Ok(()) => Ok(SessionResponse::Success),
+                    Err(e) => Ok(SessionResponse::Error {
+                        message: e.to_string(),
+                    }),
+                }
+            }
+            SessionCommand::GetBootStartSessions => match self.get_boot_start_sessions().await {
+                Ok(sessions) => Ok(SessionResponse::BootStartSessions { sessions }),
+                Err(e) => Ok(SessionResponse::Error {
+                    message: e.to_string(),
+                }),
+            },
+            SessionCommand::Ping => Ok(SessionResponse::Success),
+            SessionCommand::Shutdown => Ok(SessionResponse::Success),
+            // Only the daemon can replace itself
+            SessionCommand::Upgrade { .. } => Err(VortexError::VmError {
+                message: "Upgrade must be sent to the daemon".to_string(),
+            }),
+            SessionCommand::GetDaemonStatus => self.get_daemon_status().await,
+            SessionCommand::GetConfig => match self.config_cache.get() {
+                Ok((fingerprint, config)) => Ok(SessionResponse::Config {
+                    fingerprint,
+                    config: Box::new(config),
+                }),
+                Err(e) => Ok(SessionResponse::Error {
+                    message: e.to_string(),
+                }),
+            },
+            // Authentication commands - currently no-op (socket permissions handle auth)
+            // Token can be added later for enhanced security
+            SessionCommand::Authenticate { .. } | SessionCommand::VerifyToken { .. } => {
+                Ok(SessionResponse::Success)
+            }
+        }
+    }
+
+    pub async fn cleanup_stale_sessions(&self) -> Result<()> {
+        let mut sessions_to_remove = Vec::new();
+
+        {
+            let sessions = self.sessions.read().await;
+            for (session_id, session) in sessions.iter() {
+                if !session.persistent {
+                    // Check if session has been detached for more than 24 hours
+                    if let Some(last_attached) = session.last_attached {
+                        let hours_since_attach = (Utc::now() - last_attached).num_hours();
+                        if hours_since_attach > 24
+                            && matches!(session.state, SessionState::Detached)
+                        {
+                            sessions_to_remove.push(session_id.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        for session_id in sessions_to_remove {
+            tracing::info!("Cleaning up stale session: {}", session_id);
+            if let Err(e) = self.delete_session(&session_id).await {
+                tracing::warn!("Failed to cleanup stale session {}: {}", session_id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Persist sessions and tracked VMs for the daemon replacing this one
+    pub async fn write_handover(&self, path: &std::path::Path) -> Result<usize> {
+        self.save_sessions().await?;
+        let handover = Handover::new(self.vm_manager.export_instances().await);
+        handover.write(path)?;
+        Ok(handover.vms.len())
+    }
+
+    /// Adopt the VMs left by a daemon that handed over to this one
+    pub async fn resume_from_handover(&self, path: &std::path::Path) -> Result<()> {
+        let Some(handover) = Handover::take(path)? else {
+            return Ok(());
+        };
+        let adopted = self.vm_manager.adopt_instances(handover.vms).await;
+        info!(
+            "Adopted {} VMs from vortex v{} (handover written {})",
+            adopted, handover.from_version, handover.written_at
+        );
+        Ok(())
+    }
+
+    /// Start all sessions with boot_start enabled
+    pub async fn start_boot_start_sessions(&self) -> Result<()> {
+        let boot_start_sessions = self.get_boot_start_sessions().await?;
+
+        if boot_start_sessions.is_empty() {
+            info!("No boot-start sessions to start");
+            return Ok(());
+        }
+
+        info!(
+            "Starting {} boot-start sessions...",
+            boot_start_sessions.len()
+        );
+
+        for session in boot_start_sessions {
+            info!("Starting boot-start session: {}", session.id);
+            if let Err(e) = self.start_session(&session.id).await {
+                warn!("Failed to start boot-start session {}: {}", session.id, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vortex_backends::mock::mock_manager;
+    use vortex_core::vm::VmEvent;
+
+    #[tokio::test]
+    async fn test_subscribers_see_session_transitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (vm_manager, _) = mock_manager();
+        let vm_manager = Arc::new(vm_manager);
+        let store = SessionStore::new(dir.path().join("sessions.json"));
+        let manager = SessionManager::with_store(vm_manager.clone(), store)
+            .await
+            .unwrap();
+        let mut events = vm_manager.subscribe();
+
+        let spec = VmSpec {
+            image: "alpine".to_string(),
+            ..Default::default()
+        };
+        let session = manager
+            .create_session(spec, None, false, false)
+            .await
+            .unwrap();
+        manager.pause_session(&session.id).await.unwrap();
+        manager.resume_session(&session.id).await.unwrap();
+        manager.detach_session(&session.id).await.unwrap();
+        manager.stop_session(&session.id).await.unwrap();
+
+        let mut states = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let VmEvent::SessionStateChanged {
+                session_id, state, ..
+            } = event
+            {
+                assert_eq!(session_id, session.id);
+                states.push(state);
+            }
+        }
+        // Detaching a detached session is no transition
+        assert_eq!(
+            states,
+            [
+                SessionStateName::Creating,
+                SessionStateName::Detached,
+                SessionStateName::Paused,
+                SessionStateName::Detached,
+                SessionStateName::Stopped,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sessions_of_missing_workspaces_are_denied() {
+        let dir = tempfile::tempdir().unwrap();
+        let (vm_manager, _) = mock_manager();
+        let store = SessionStore::new(dir.path().join("sessions.json"));
+        let manager = SessionManager::with_store(Arc::new(vm_manager), store)
+            .await
+            .unwrap()
+            .with_workspaces(WorkspaceManager::at(dir.path().join("workspaces")));
+
+        let plain = manager
+            .create_session(VmSpec::default(), None, false, false)
+            .await
+            .unwrap();
+        manager
+            .check_workspace_access(&plain.id, "ci")
+            .await
+            .unwrap();
+
+        let mut spec = VmSpec::default();
+        spec.labels
+            .insert(LABEL_WORKSPACE_ID.to_string(), "deleted".to_string());
+        let orphaned = manager
+            .create_session(spec, None, false, false)
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.check_workspace_access(&orphaned.id, "ci").await,
+            Err(VortexError::PermissionDenied { .. })
+        ));
+    }
+}
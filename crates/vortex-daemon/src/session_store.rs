@@ -0,0 +1,288 @@
+//! On-disk session state, `~/.vortex/sessions.json`.
+//!
+//! The daemon and CLI processes all read and write the file, so every access
+//! holds an advisory lock on `sessions.json.lock`; the data file is replaced
+//! on each write and cannot carry the lock itself. Writes go to a temporary
+//! file renamed over the old one, so a crash never leaves half a file.
+//! Processes change sessions with [`SessionStore::update`], which reads,
+//! changes and writes the file under one lock, so none of them loses the
+//! others' changes.
+//!
+//! Each write first copies the previous file to `sessions.json.bak`. A file
+//! that no longer parses is moved to `sessions.json.corrupt` and the backup
+//! is loaded instead.
+//!
+//! Files carry a `schema_version`. Version 1 files, written before the field
+//! existed, are the bare id-to-session map and are migrated on load.
+
+use crate::session::VmSession;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use vortex_core::error::{Result, VortexError};
+use vortex_core::ids::SessionId;
+
+/// Version written by this build
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct SessionFileRef<'a> {
+    schema_version: u32,
+    sessions: &'a HashMap<SessionId, VmSession>,
+}
+
+#[derive(Deserialize)]
+struct SessionFile {
+    sessions: HashMap<SessionId, VmSession>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    path: PathBuf,
+}
+
+fn storage_error(action: &str, path: &Path, e: impl std::fmt::Display) -> VortexError {
+    VortexError::StorageError {
+        message: format!("Failed to {} {}: {}", action, path.display(), e),
+    }
+}
+
+/// Sessions in `content`, migrated to the current schema
+fn parse(content: &str) -> Result<HashMap<SessionId, VmSession>> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    match value.get("schema_version").and_then(|v| v.as_u64()) {
+        // Version 1: the map itself
+        None => Ok(serde_json::from_value(value)?),
+        Some(version) if version == u64::from(SCHEMA_VERSION) => {
+            Ok(serde_json::from_value::<SessionFile>(value)?.sessions)
+        }
+        Some(version) => Err(VortexError::StorageError {
+            message: format!(
+                "Sessions file has schema version {}, newer than this Vortex supports ({}); \
+                 upgrade Vortex to use it",
+                version, SCHEMA_VERSION
+            ),
+        }),
+    }
+}
+
+impl SessionStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `sessions.json<suffix>` next to the data file
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        self.path.with_file_name(name)
+    }
+
+    pub fn backup_path(&self) -> PathBuf {
+        self.sibling(".bak")
+    }
+
+    /// Exclusive lock on the store, released when the file is dropped
+    fn lock(&self) -> Result<File> {
+        let path = self.sibling(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| storage_error("open", &path, e))?;
+        file.lock_exclusive()
+            .map_err(|e| storage_error("lock", &path, e))?;
+        Ok(file)
+    }
+
+    pub fn load(&self) -> Result<HashMap<SessionId, VmSession>> {
+        let _lock = self.lock()?;
+        self.read()
+    }
+
+    pub fn save(&self, sessions: &HashMap<SessionId, VmSession>) -> Result<()> {
+        let _lock = self.lock()?;
+        self.write(sessions)
+    }
+
+    /// Apply `change` to the sessions on disk and write them back, holding
+    /// the lock throughout
+    pub fn update<T>(
+        &self,
+        change: impl FnOnce(&mut HashMap<SessionId, VmSession>) -> T,
+    ) -> Result<T> {
+        let _lock = self.lock()?;
+        let mut sessions = self.read()?;
+        let result = change(&mut sessions);
+        self.write(&sessions)?;
+        Ok(result)
+    }
+
+    /// Sessions of the file; the caller holds the lock
+    fn read(&self) -> Result<HashMap<SessionId, VmSession>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(storage_error("read", &self.path, e)),
+        };
+        warn_if_readable_by_others(&self.path);
+
+        let error = match parse(&content) {
+            Ok(sessions) => return Ok(sessions),
+            Err(e @ VortexError::StorageError { .. }) => return Err(e),
+            Err(e) => e,
+        };
+
+        // Keep the damaged file for inspection and fall back to the backup
+        let corrupt = self.sibling(".corrupt");
+        fs::rename(&self.path, &corrupt).map_err(|e| storage_error("move", &self.path, e))?;
+        let backup = self.backup_path();
+        match fs::read_to_string(&backup)
+            .map_err(VortexError::from)
+            .and_then(|c| parse(&c))
+        {
+            Ok(sessions) => {
+                tracing::warn!(
+                    "{} is corrupted ({}); moved it to {} and restored {} session(s) from {}",
+                    self.path.display(),
+                    error,
+                    corrupt.display(),
+                    sessions.len(),
+                    backup.display()
+                );
+                Ok(sessions)
+            }
+            Err(backup_error) => {
+                tracing::warn!(
+                    "{} is corrupted ({}) and moved to {}; no usable backup ({}), \
+                     starting without sessions",
+                    self.path.display(),
+                    error,
+                    corrupt.display(),
+                    backup_error
+                );
+                Ok(HashMap::new())
+            }
+        }
+    }
+
+    /// Replace the file with `sessions`; the caller holds the lock
+    fn write(&self, sessions: &HashMap<SessionId, VmSession>) -> Result<()> {
+        let content = serde_json::to_string_pretty(&SessionFileRef {
+            schema_version: SCHEMA_VERSION,
+            sessions,
+        })?;
+
+        let tmp = self.sibling(".tmp");
+        write_private(&tmp, content.as_bytes()).map_err(|e| storage_error("write", &tmp, e))?;
+        if self.path.exists() {
+            let backup = self.backup_path();
+            fs::copy(&self.path, &backup).map_err(|e| storage_error("write", &backup, e))?;
+        }
+        fs::rename(&tmp, &self.path).map_err(|e| storage_error("replace", &self.path, e))?;
+        Ok(())
+    }
+}
+
+/// Write `content` to a new file only the owner can read, flushed to disk
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).truncate(true).write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+fn warn_if_readable_by_others(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = fs::metadata(path) {
+            let mode = metadata.permissions().mode();
+            if mode & 0o077 != 0 {
+                tracing::warn!(
+                    "Sessions file {} has insecure permissions (mode: {:o}). \
+                    Expected 0600 (owner read/write only). This may expose VM metadata.",
+                    path.display(),
+                    mode
+                );
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str) -> VmSession {
+        VmSession::detached(id)
+    }
+
+    #[test]
+    fn test_migration_and_corruption_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(dir.path().join("sessions.json"));
+        assert!(store.load().unwrap().is_empty());
+
+        // A version 1 file is the bare map
+        let v1: HashMap<_, _> = [("session-a".to_string(), session("session-a"))].into();
+        fs::write(store.path(), serde_json::to_string(&v1).unwrap()).unwrap();
+        let mut sessions = store.load().unwrap();
+        assert_eq!(sessions.len(), 1);
+
+        sessions.insert("session-b".into(), session("session-b"));
+        store.save(&sessions).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(store.path()).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], SCHEMA_VERSION);
+        assert!(store.backup_path().exists());
+
+        // The next save backs up the two-session file; then it is damaged
+        store.save(&sessions).unwrap();
+        fs::write(store.path(), "{\"schema_version\": 2, \"sess").unwrap();
+        assert_eq!(store.load().unwrap().len(), 2);
+        assert!(dir.path().join("sessions.json.corrupt").exists());
+
+        fs::write(store.path(), "{\"schema_version\": 99, \"sessions\": {}}").unwrap();
+        assert!(store.load().is_err());
+    }
+
+    #[test]
+    fn test_concurrent_updates_keep_every_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let store = SessionStore::new(path.clone());
+                std::thread::spawn(move || {
+                    for n in 0..5 {
+                        let id = format!("session-{}-{}", writer, n);
+                        store
+                            .update(|sessions| sessions.insert(id.as_str().into(), session(&id)))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(SessionStore::new(path).load().unwrap().len(), 40);
+    }
+}
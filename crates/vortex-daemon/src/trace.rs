@@ -0,0 +1,291 @@
+//! Object graph between workspaces, sessions, VMs and runs.
+//!
+//! `vortex trace <id>` accepts any ID (or session/workspace name), climbs to
+//! the outermost owner and prints everything below it.
+
+use crate::session::VmSession;
+use serde::Serialize;
+use vortex_core::ids::LABEL_WORKSPACE_ID;
+use vortex_core::run_dir::RunRecord;
+use vortex_core::workspace::Workspace;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceKind {
+    Workspace,
+    Session,
+    Vm,
+    Run,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceNode {
+    pub kind: TraceKind,
+    pub id: String,
+    pub summary: String,
+    /// True for the node the trace was requested for
+    pub matched: bool,
+    pub children: Vec<TraceNode>,
+}
+
+/// Snapshot of every record that can take part in a trace
+pub struct TraceIndex {
+    pub workspaces: Vec<Workspace>,
+    pub sessions: Vec<VmSession>,
+    pub runs: Vec<RunRecord>,
+}
+
+impl TraceIndex {
+    /// Build the graph containing `query`, or `None` if nothing matches
+    pub fn trace(&self, query: &str) -> Option<TraceNode> {
+        let (kind, id) = self.resolve(query)?;
+        let (root_kind, root_id) = self.root_of(kind, &id);
+        Some(self.build(root_kind, &root_id, &id))
+    }
+
+    fn resolve(&self, query: &str) -> Option<(TraceKind, String)> {
+        if let Some(ws) = self
+            .workspaces
+            .iter()
+            .find(|w| w.id == query || w.name == query)
+        {
+            return Some((TraceKind::Workspace, ws.id.clone()));
+        }
+        if let Some(s) = self
+            .sessions
+            .iter()
+            .find(|s| s.id == query || s.name.as_deref() == Some(query))
+        {
+            return Some((TraceKind::Session, s.id.to_string()));
+        }
+        if let Some(r) = self.runs.iter().find(|r| r.run_id == *query) {
+            return Some((TraceKind::Run, r.run_id.to_string()));
+        }
+        let vm_known = self.sessions.iter().any(|s| s.vm_id == query)
+            || self
+                .runs
+                .iter()
+                .any(|r| r.vm_id.as_ref().is_some_and(|id| id == query));
+        vm_known.then(|| (TraceKind::Vm, query.to_string()))
+    }
+
+    fn session_workspace(&self, session: &VmSession) -> Option<String> {
+        session
+            .workspace_id
+            .as_ref()
+            .map(|id| id.to_string())
+            .or_else(|| session.spec.labels.get(LABEL_WORKSPACE_ID).cloned())
+    }
+
+    fn root_of(&self, kind: TraceKind, id: &str) -> (TraceKind, String) {
+        match kind {
+            TraceKind::Workspace => (kind, id.to_string()),
+            TraceKind::Session => {
+                let session = self.sessions.iter().find(|s| s.id == id);
+                match session.and_then(|s| self.session_workspace(s)) {
+                    Some(ws) => (TraceKind::Workspace, ws),
+                    None => (kind, id.to_string()),
+                }
+            }
+            TraceKind::Vm => match self.sessions.iter().find(|s| s.vm_id == id) {
+                Some(session) => self.root_of(TraceKind::Session, &session.id),
+                None => {
+                    let run = self
+                        .runs
+                        .iter()
+                        .find(|r| r.vm_id.as_ref().is_some_and(|v| v == id));
+                    match run.and_then(|r| r.workspace_id.as_ref()) {
+                        Some(ws) => (TraceKind::Workspace, ws.to_string()),
+                        None => (kind, id.to_string()),
+                    }
+                }
+            },
+            TraceKind::Run => {
+                let run = self.runs.iter().find(|r| r.run_id == *id);
+                match run.and_then(|r| r.vm_id.as_ref()) {
+                    Some(vm_id) => self.root_of(TraceKind::Vm, vm_id.as_str()),
+                    None => (kind, id.to_string()),
+                }
+            }
+        }
+    }
+
+    fn build(&self, kind: TraceKind, id: &str, matched: &str) -> TraceNode {
+        let mut children = Vec::new();
+        let summary = match kind {
+            TraceKind::Workspace => {
+                for session in &self.sessions {
+                    if self.session_workspace(session).as_deref() == Some(id) {
+                        children.push(self.build(TraceKind::Session, &session.id, matched));
+                    }
+                }
+                for run in &self.runs {
+                    let in_session = run.session_id.is_some();
+                    if !in_session && run.workspace_id.as_ref().is_some_and(|ws| ws == id) {
+                        children.push(self.build_vm_or_run(run, matched));
+                    }
+                }
+                self.workspaces
+                    .iter()
+                    .find(|w| w.id == id)
+                    .map(|w| format!("{} (template {})", w.name, w.config.template))
+                    .unwrap_or_else(|| "workspace not found locally".to_string())
+            }
+            TraceKind::Session => {
+                let session = self.sessions.iter().find(|s| s.id == id);
+                if let Some(session) = session {
+                    children.push(self.build(TraceKind::Vm, &session.vm_id, matched));
+                }
+                session
+                    .map(|s| {
+                        format!(
+                            "{}{:?}",
+                            s.name
+                                .as_ref()
+                                .map(|n| format!("{} ", n))
+                                .unwrap_or_default(),
+                            s.state
+                        )
+                    })
+                    .unwrap_or_default()
+            }
+            TraceKind::Vm => {
+                for run in &self.runs {
+                    if run.vm_id.as_ref().is_some_and(|v| v == id) {
+                        children.push(self.build(TraceKind::Run, run.run_id.as_str(), matched));
+                    }
+                }
+                self.sessions
+                    .iter()
+                    .find(|s| s.vm_id == id)
+                    .map(|s| {
+                        format!(
+                            "{}, {}MB, {} CPU(s)",
+                            s.spec.image, s.spec.memory, s.spec.cpus
+                        )
+                    })
+                    .unwrap_or_default()
+            }
+            TraceKind::Run => self
+                .runs
+                .iter()
+                .find(|r| r.run_id == *id)
+                .map(|r| format!("started {}", r.created_at.format("%Y-%m-%d %H:%M:%S")))
+                .unwrap_or_default(),
+        };
+
+        TraceNode {
+            kind,
+            id: id.to_string(),
+            summary,
+            matched: id == matched,
+            children,
+        }
+    }
+
+    fn build_vm_or_run(&self, run: &RunRecord, matched: &str) -> TraceNode {
+        match &run.vm_id {
+            Some(vm_id) => self.build(TraceKind::Vm, vm_id.as_str(), matched),
+            None => self.build(TraceKind::Run, run.run_id.as_str(), matched),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vortex_core::ids::{RunId, SessionId, VmId, WorkspaceId};
+
+    fn run(id: &str, vm_id: &str, session_id: Option<&str>) -> RunRecord {
+        RunRecord {
+            run_id: RunId::new(id),
+            vm_id: Some(VmId::new(vm_id)),
+            session_id: session_id.map(SessionId::new),
+            workspace_id: None,
+            created_at: chrono::Utc::now(),
+            health_check: None,
+            expires_at: None,
+        }
+    }
+
+    fn index() -> TraceIndex {
+        let config = serde_json::from_value(serde_json::json!({
+            "name": "shop",
+            "template": "python",
+            "created_at": "2026-01-01T00:00:00Z",
+            "last_used": "2026-01-01T00:00:00Z",
+            "custom_commands": [],
+            "preferred_workdir": "/workspace",
+            "environment_vars": {},
+            "port_forwards": [],
+            "backend": null,
+        }))
+        .unwrap();
+        let workspace = Workspace {
+            id: "ws-1".to_string(),
+            name: "shop".to_string(),
+            path: "/tmp/shop".into(),
+            config,
+        };
+        let mut session = VmSession::detached("session-a");
+        session.name = Some("api".to_string());
+        session.workspace_id = Some(WorkspaceId::new("ws-1"));
+        TraceIndex {
+            workspaces: vec![workspace],
+            sessions: vec![session],
+            runs: vec![
+                run("run-a", "vortex-session-a", Some("session-a")),
+                run("run-b", "vortex-solo", None),
+            ],
+        }
+    }
+
+    /// The nodes of the graph, depth first
+    fn ids(node: &TraceNode) -> Vec<(TraceKind, &str, bool)> {
+        let mut nodes = vec![(node.kind, node.id.as_str(), node.matched)];
+        for child in &node.children {
+            nodes.extend(ids(child));
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_any_id_traces_from_its_outermost_owner() {
+        let index = index();
+        let graph = [
+            (TraceKind::Workspace, "ws-1"),
+            (TraceKind::Session, "session-a"),
+            (TraceKind::Vm, "vortex-session-a"),
+            (TraceKind::Run, "run-a"),
+        ];
+        for query in [
+            "ws-1",
+            "shop",
+            "session-a",
+            "api",
+            "vortex-session-a",
+            "run-a",
+        ] {
+            let node = index.trace(query).unwrap();
+            let found: Vec<_> = ids(&node).iter().map(|(k, id, _)| (*k, *id)).collect();
+            assert_eq!(found, graph, "{}", query);
+        }
+        let node = index.trace("api").unwrap();
+        let matched: Vec<_> = ids(&node)
+            .into_iter()
+            .filter(|(_, _, matched)| *matched)
+            .collect();
+        assert_eq!(matched, [(TraceKind::Session, "session-a", true)]);
+
+        // A run outside any session or workspace hangs off its VM
+        let node = index.trace("run-b").unwrap();
+        assert_eq!(
+            ids(&node),
+            [
+                (TraceKind::Vm, "vortex-solo", false),
+                (TraceKind::Run, "run-b", true),
+            ]
+        );
+        assert!(index.trace("vortex-unknown").is_none());
+    }
+}
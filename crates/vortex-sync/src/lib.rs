@@ -0,0 +1,390 @@
+//! Conflict-aware copy of `--sync-back` results to the host.
+//!
+//! The guest copies its results into a staging directory inside the run
+//! directory instead of straight into the host destination. Applying the
+//! staged files compares each one with a manifest of the destination taken
+//! when the run started: a file the host left alone takes the guest's
+//! version, while a file edited on both sides is a [`Conflict`] settled by
+//! the run's [`ConflictPolicy`] rather than silently overwritten.
+//!
+//! Staged files are only ever added or updated on the host; a file missing
+//! from the staging directory was not produced by the run, not deleted.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vortex_core::error::{Result, VortexError};
+
+const RECORD_NAME: &str = vortex_core::run_dir::PENDING_SYNC_RECORD;
+
+/// What to do with a file changed both on the host and in the VM
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Ask for each conflict, showing a diff
+    #[default]
+    Prompt,
+    /// Keep the host's version
+    Host,
+    /// Take the guest's version
+    Guest,
+    /// Write both versions with conflict markers around differing lines
+    Merge,
+}
+
+impl ConflictPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Prompt => "prompt",
+            ConflictPolicy::Host => "host",
+            ConflictPolicy::Guest => "guest",
+            ConflictPolicy::Merge => "merge",
+        }
+    }
+
+    /// The resolution this policy applies without asking
+    pub fn resolution(&self) -> Option<Resolution> {
+        match self {
+            ConflictPolicy::Prompt => None,
+            ConflictPolicy::Host => Some(Resolution::Host),
+            ConflictPolicy::Guest => Some(Resolution::Guest),
+            ConflictPolicy::Merge => Some(Resolution::Merge),
+        }
+    }
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = VortexError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "prompt" => Ok(ConflictPolicy::Prompt),
+            "host" => Ok(ConflictPolicy::Host),
+            "guest" => Ok(ConflictPolicy::Guest),
+            "merge" => Ok(ConflictPolicy::Merge),
+            _ => Err(VortexError::InvalidInput {
+                field: "on-conflict".to_string(),
+                message: format!(
+                    "Unknown conflict policy '{}', expected prompt, host, guest or merge",
+                    s
+                ),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Host,
+    Guest,
+    Merge,
+}
+
+/// SHA-256 of every regular file under a directory, by relative path
+pub type Manifest = BTreeMap<PathBuf, String>;
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(fs::read(path)?);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Manifest of `dir`; a missing directory has no files
+pub fn scan(dir: &Path) -> Result<Manifest> {
+    fn walk(root: &Path, dir: &Path, manifest: &mut Manifest) -> Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            // Symlinks are not followed, so a link cannot pull in files
+            // from outside the directory
+            let file_type = fs::symlink_metadata(&path)?.file_type();
+            if file_type.is_dir() {
+                walk(root, &path, manifest)?;
+            } else if file_type.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                manifest.insert(relative, hash_file(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    let mut manifest = Manifest::new();
+    walk(dir, dir, &mut manifest)?;
+    Ok(manifest)
+}
+
+/// A file changed on the host since the run started and also by the guest
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub relative: PathBuf,
+    pub host: PathBuf,
+    pub staged: PathBuf,
+}
+
+impl Conflict {
+    /// Both versions as text, or `None` if either is not UTF-8
+    pub fn texts(&self) -> Result<Option<(String, String)>> {
+        let host = fs::read(&self.host)?;
+        let guest = fs::read(&self.staged)?;
+        Ok(String::from_utf8(host)
+            .ok()
+            .zip(String::from_utf8(guest).ok()))
+    }
+
+    /// Unified diff from the host's version to the guest's, `None` for
+    /// binary files
+    pub fn diff(&self, color: bool) -> Result<Option<String>> {
+        Ok(self
+            .texts()?
+            .map(|(host, guest)| render_diff(&host, &guest, &self.relative, color)))
+    }
+}
+
+/// Unified diff of `host` against `guest`, optionally with ANSI colors
+pub fn render_diff(host: &str, guest: &str, relative: &Path, color: bool) -> String {
+    let paint = |code: &str, line: String| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, line)
+        } else {
+            line
+        }
+    };
+
+    let diff = TextDiff::from_lines(host, guest);
+    let mut out = String::new();
+    out.push_str(&paint("1", format!("--- host/{}", relative.display())));
+    out.push('\n');
+    out.push_str(&paint("1", format!("+++ guest/{}", relative.display())));
+    out.push('\n');
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        out.push_str(&paint("36", hunk.header().to_string()));
+        out.push('\n');
+        for change in hunk.iter_changes() {
+            let (sign, code) = match change.tag() {
+                ChangeTag::Delete => ("-", "31"),
+                ChangeTag::Insert => ("+", "32"),
+                ChangeTag::Equal => (" ", "0"),
+            };
+            let line = format!("{}{}", sign, change.value().trim_end_matches('\n'));
+            out.push_str(&paint(code, line));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// `host` and `guest` combined, with git-style markers around each
+/// differing region
+pub fn merge_with_markers(host: &str, guest: &str) -> String {
+    fn flush(out: &mut String, ours: &mut Vec<&str>, theirs: &mut Vec<&str>) {
+        if ours.is_empty() && theirs.is_empty() {
+            return;
+        }
+        out.push_str("<<<<<<< host\n");
+        for line in ours.drain(..) {
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        out.push_str("=======\n");
+        for line in theirs.drain(..) {
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        out.push_str(">>>>>>> guest\n");
+    }
+
+    let diff = TextDiff::from_lines(host, guest);
+    let mut out = String::new();
+    let (mut ours, mut theirs) = (Vec::new(), Vec::new());
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Delete => ours.push(change.value()),
+            ChangeTag::Insert => theirs.push(change.value()),
+            ChangeTag::Equal => {
+                flush(&mut out, &mut ours, &mut theirs);
+                out.push_str(change.value());
+            }
+        }
+    }
+    flush(&mut out, &mut ours, &mut theirs);
+    out
+}
+
+/// One `--sync-back` mapping waiting to be applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncBack {
+    /// Directory the guest copies from, for messages
+    pub guest: PathBuf,
+    /// Destination on the host
+    pub host: PathBuf,
+    /// Host directory the guest copies into
+    pub staging: PathBuf,
+    /// Destination contents when the run started, updated as files are applied
+    pub baseline: Manifest,
+}
+
+impl SyncBack {
+    pub fn new(guest: PathBuf, host: PathBuf, staging: PathBuf) -> Result<Self> {
+        let baseline = scan(&host)?;
+        fs::create_dir_all(&staging)?;
+        Ok(Self {
+            guest,
+            host,
+            staging,
+            baseline,
+        })
+    }
+
+    /// Copy staged files the host has not touched since the run started,
+    /// returning how many were copied and the files that conflict
+    pub fn apply(&mut self) -> Result<(usize, Vec<Conflict>)> {
+        let mut applied = 0;
+        let mut conflicts = Vec::new();
+        for (relative, guest_hash) in scan(&self.staging)? {
+            let base = self.baseline.get(&relative);
+            if base == Some(&guest_hash) {
+                // The guest left it as it was
+                continue;
+            }
+            let host = self.host.join(&relative);
+            let host_hash = if host.is_file() {
+                Some(hash_file(&host)?)
+            } else {
+                None
+            };
+            if host_hash.as_ref() == Some(&guest_hash) {
+                self.baseline.insert(relative, guest_hash);
+                continue;
+            }
+            let staged = self.staging.join(&relative);
+            if host_hash.as_ref() == base {
+                copy_file(&staged, &host)?;
+                self.baseline.insert(relative, guest_hash);
+                applied += 1;
+            } else {
+                conflicts.push(Conflict {
+                    relative,
+                    host,
+                    staged,
+                });
+            }
+        }
+        Ok((applied, conflicts))
+    }
+
+    /// Settle `conflict`; later applies treat the result as the baseline
+    pub fn resolve(&mut self, conflict: &Conflict, resolution: Resolution) -> Result<()> {
+        match resolution {
+            Resolution::Host => {}
+            Resolution::Guest => copy_file(&conflict.staged, &conflict.host)?,
+            Resolution::Merge => {
+                let (host, guest) = conflict.texts()?.ok_or_else(|| VortexError::InvalidInput {
+                    field: "on-conflict".to_string(),
+                    message: format!(
+                        "{} is binary and cannot be merged; keep the host or guest version",
+                        conflict.host.display()
+                    ),
+                })?;
+                fs::write(&conflict.host, merge_with_markers(&host, &guest))?;
+            }
+        }
+        self.baseline
+            .insert(conflict.relative.clone(), hash_file(&conflict.staged)?);
+        Ok(())
+    }
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(from, to)?;
+    Ok(())
+}
+
+/// The sync-back mappings of one run, kept in its run directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSync {
+    pub policy: ConflictPolicy,
+    pub mappings: Vec<SyncBack>,
+}
+
+impl PendingSync {
+    pub fn load(run_dir: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(run_dir.join(RECORD_NAME)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, run_dir: &Path) -> Result<()> {
+        fs::write(
+            run_dir.join(RECORD_NAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_files_changed_on_both_sides_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let host = dir.path().join("host");
+        fs::create_dir_all(&host).unwrap();
+        fs::write(host.join("edited.txt"), "a\nb\nc\n").unwrap();
+        fs::write(host.join("untouched.txt"), "old\n").unwrap();
+
+        let staging = dir.path().join("staging");
+        let mut sync = SyncBack::new("/out".into(), host.clone(), staging.clone()).unwrap();
+        // The host edits one file while the guest rewrites both and adds one
+        fs::write(host.join("edited.txt"), "a\nhost\nc\n").unwrap();
+        fs::write(staging.join("edited.txt"), "a\nguest\nc\n").unwrap();
+        fs::write(staging.join("untouched.txt"), "new\n").unwrap();
+        fs::create_dir_all(staging.join("sub")).unwrap();
+        fs::write(staging.join("sub/added.txt"), "x\n").unwrap();
+
+        let (applied, conflicts) = sync.apply().unwrap();
+        assert_eq!(applied, 2);
+        assert_eq!(
+            fs::read_to_string(host.join("untouched.txt")).unwrap(),
+            "new\n"
+        );
+        assert_eq!(conflicts.len(), 1);
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.relative, PathBuf::from("edited.txt"));
+        let diff = conflict.diff(false).unwrap().unwrap();
+        assert!(diff.contains("-host\n+guest\n"));
+
+        sync.resolve(conflict, Resolution::Merge).unwrap();
+        assert_eq!(
+            fs::read_to_string(host.join("edited.txt")).unwrap(),
+            "a\n<<<<<<< host\nhost\n=======\nguest\n>>>>>>> guest\nc\n"
+        );
+        // Settled files are not reported again
+        let (applied, conflicts) = sync.apply().unwrap();
+        assert_eq!(applied, 0);
+        assert!(conflicts.is_empty());
+    }
+}
@@ -1,804 +0,0 @@
-use crate::error::{Result, VortexError};
-use crate::vm::{VmManager, VmSpec};
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::fs as tokio_fs;
-use tokio::sync::RwLock;
-use tracing::{info, warn};
-use uuid::Uuid;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VmSession {
-    pub id: String,
-    pub name: Option<String>,
-    pub vm_id: String,
-    pub state: SessionState,
-    pub created_at: DateTime<Utc>,
-    pub last_attached: Option<DateTime<Utc>>,
-    pub persistent: bool,
-    /// If true, this session will be started automatically when the daemon starts
-    pub boot_start: bool,
-    pub spec: VmSpec,
-    pub metadata: HashMap<String, String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum SessionState {
-    Creating,
-    Running,
-    Detached,
-    Attached { client_pid: u32 },
-    Paused,
-    Stopped,
-    Error { message: String },
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum SessionCommand {
-    // Session management
-    CreateSession {
-        spec: Box<VmSpec>,
-        name: Option<String>,
-        persistent: bool,
-        boot_start: bool,
-    },
-    ListSessions,
-    GetSession {
-        session_id: String,
-    },
-    DeleteSession {
-        session_id: String,
-    },
-
-    // VM lifecycle
-    StartSession {
-        session_id: String,
-    },
-    StopSession {
-        session_id: String,
-    },
-    PauseSession {
-        session_id: String,
-    },
-    ResumeSession {
-        session_id: String,
-    },
-    RestartSession {
-        session_id: String,
-    },
-
-    // Interactive
-    AttachSession {
-        session_id: String,
-        client_pid: u32,
-    },
-    DetachSession {
-        session_id: String,
-    },
-
-    // Authentication (optional token for daemon access)
-    Authenticate {
-        token: Option<String>,
-    },
-    VerifyToken {
-        token: Option<String>,
-    },
-
-    // Boot start management
-    EnableBootStart {
-        session_id: String,
-    },
-    DisableBootStart {
-        session_id: String,
-    },
-    GetBootStartSessions,
-
-    // Daemon control
-    Ping,
-    Shutdown,
-    GetDaemonStatus,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum SessionResponse {
-    Success,
-    Error {
-        message: String,
-    },
-    SessionCreated {
-        session: VmSession,
-    },
-    SessionList {
-        sessions: Vec<VmSession>,
-    },
-    Session {
-        session: VmSession,
-    },
-    BootStartSessions {
-        sessions: Vec<VmSession>,
-    },
-    DaemonStatus {
-        uptime: u64,
-        sessions_count: usize,
-        active_vms: usize,
-        memory_usage: u64,
-    },
-}
-
-pub struct SessionManager {
-    sessions: RwLock<HashMap<String, VmSession>>,
-    vm_manager: Arc<VmManager>,
-    session_file: PathBuf,
-    daemon_start_time: DateTime<Utc>,
-}
-
-impl SessionManager {
-    pub async fn new(vm_manager: Arc<VmManager>) -> Result<Self> {
-        let session_file = Self::get_session_file()?;
-
-        let manager = Self {
-            sessions: RwLock::new(HashMap::new()),
-            vm_manager,
-            session_file,
-            daemon_start_time: Utc::now(),
-        };
-
-        // Load persisted sessions from disk
-        let mut new_manager = manager;
-        new_manager.load_sessions().await?;
-
-        Ok(new_manager)
-    }
-
-    fn get_session_file() -> Result<PathBuf> {
-        let home = dirs::home_dir().ok_or_else(|| VortexError::VmError {
-            message: "Could not determine home directory".to_string(),
-        })?;
-
-        let vortex_dir = home.join(".vortex");
-        std::fs::create_dir_all(&vortex_dir).map_err(|e| VortexError::VmError {
-            message: format!("Failed to create vortex directory: {}", e),
-        })?;
-
-        Ok(vortex_dir.join("sessions.json"))
-    }
-
-    async fn load_sessions(&mut self) -> Result<()> {
-        if !self.session_file.exists() {
-            return Ok(());
-        }
-
-        // Check file permissions on Unix systems for security
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let metadata = fs::metadata(&self.session_file).map_err(|e| VortexError::VmError {
-                message: format!("Failed to read sessions file metadata: {}", e),
-            })?;
-
-            let permissions = metadata.permissions();
-            let mode = permissions.mode();
-
-            // Warn if file is world-readable (not strictly 0600)
-            if mode & 0o077 != 0 {
-                tracing::warn!(
-                    "Sessions file {} has insecure permissions (mode: {:o}). \
-                    Expected 0600 (owner read/write only). This may expose VM metadata.",
-                    self.session_file.display(),
-                    mode
-                );
-                // Continue but proceed with caution
-            }
-        }
-
-        let content = tokio::fs::read_to_string(&self.session_file)
-            .await
-            .map_err(|e| VortexError::VmError {
-                message: format!("Failed to read sessions file: {}", e),
-            })?;
-
-        // Properly handle JSON errors instead of silently defaulting
-        let sessions: HashMap<String, VmSession> = serde_json::from_str(&content).map_err(|e| {
-            VortexError::VmError {
-                message: format!("Failed to parse sessions file: {}. The file may be corrupted.", e),
-            }
-        })?;
-
-        let mut session_map = self.sessions.write().await;
-        *session_map = sessions;
-
-        Ok(())
-    }
-
-    async fn save_sessions(&self) -> Result<()> {
-        let sessions = self.sessions.read().await;
-
-        // Skip save if no sessions (empty map)
-        if sessions.is_empty() {
-            return Ok(());
-        }
-
-        let content =
-            serde_json::to_string_pretty(&*sessions).map_err(|e| VortexError::VmError {
-                message: format!("Failed to serialize sessions: {}", e),
-            })?;
-
-        // Write with secure permissions (0o600 - read/write only by owner)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            tokio_fs::write(&self.session_file, &content)
-                .await
-                .map_err(|e| VortexError::VmError {
-                    message: format!("Failed to write sessions file: {}", e),
-                })?;
-            fs::set_permissions(&self.session_file, fs::Permissions::from_mode(0o600))
-                .map_err(|e| VortexError::VmError {
-                    message: format!("Failed to set session file permissions: {}", e),
-                })?;
-        }
-        #[cfg(not(unix))]
-        {
-            tokio_fs::write(&self.session_file, &content)
-                .await
-                .map_err(|e| VortexError::VmError {
-                    message: format!("Failed to write sessions file: {}", e),
-                })?;
-        }
-
-        Ok(())
-    }
-
-    pub async fn create_session(
-        &self,
-        spec: VmSpec,
-        name: Option<String>,
-        persistent: bool,
-        boot_start: bool,
-    ) -> Result<VmSession> {
-        let uuid_str = Uuid::new_v4().simple().to_string();
-        let session_id = format!("session-{}", &uuid_str[..8]);
-        let vm_id = format!("vortex-{}", &session_id);
-
-        // Create the VM
-        let mut vm_spec = spec.clone();
-        vm_spec
-            .labels
-            .insert("session_id".to_string(), session_id.clone());
-        vm_spec
-            .labels
-            .insert("persistent".to_string(), persistent.to_string());
-        vm_spec
-            .labels
-            .insert("boot_start".to_string(), boot_start.to_string());
-        if let Some(ref name) = name {
-            vm_spec
-                .labels
-                .insert("session_name".to_string(), name.clone());
-        }
-
-        let session = VmSession {
-            id: session_id.clone(),
-            name: name.clone(),
-            vm_id: vm_id.clone(),
-            state: SessionState::Creating,
-            created_at: Utc::now(),
-            last_attached: None,
-            persistent,
-            boot_start,
-            spec: vm_spec.clone(),
-            metadata: HashMap::new(),
-        };
-
-        // Store session first
-        {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(session_id.clone(), session.clone());
-        }
-        self.save_sessions().await?;
-
-        // Create VM instance
-        match self.vm_manager.create(vm_spec).await {
-            Ok(vm_instance) => {
-                let mut updated_session = session;
-                updated_session.vm_id = vm_instance.id;
-                updated_session.state = SessionState::Detached;
-
-                {
-                    let mut sessions = self.sessions.write().await;
-                    sessions.insert(session_id.clone(), updated_session.clone());
-                }
-                self.save_sessions().await?;
-
-                tracing::info!(
-                    "Created session {} with VM {}",
-                    session_id,
-                    updated_session.vm_id
-                );
-                Ok(updated_session)
-            }
-            Err(e) => {
-                let mut failed_session = session;
-                failed_session.state = SessionState::Error {
-                    message: e.to_string(),
-                };
-
-                {
-                    let mut sessions = self.sessions.write().await;
-                    sessions.insert(session_id.clone(), failed_session.clone());
-                }
-                self.save_sessions().await?;
-
-                Err(e)
-            }
-        }
-    }
-
-    pub async fn list_sessions(&self) -> Result<Vec<VmSession>> {
-        let sessions = self.sessions.read().await;
-        Ok(sessions.values().cloned().collect())
-    }
-
-    pub async fn get_session(&self, session_id: &str) -> Result<Option<VmSession>> {
-        let sessions = self.sessions.read().await;
-        Ok(sessions.get(session_id).cloned())
-    }
-
-    pub async fn delete_session(&self, session_id: &str) -> Result<()> {
-        let session = {
-            let mut sessions = self.sessions.write().await;
-            sessions.remove(session_id)
-        };
-
-        if let Some(session) = session {
-            // Stop and cleanup VM if it exists
-            if let Err(e) = self.vm_manager.cleanup(&session.vm_id).await {
-                tracing::warn!(
-                    "Failed to cleanup VM {} for session {}: {}",
-                    session.vm_id,
-                    session_id,
-                    e
-                );
-            }
-
-            self.save_sessions().await?;
-            tracing::info!("Deleted session {}", session_id);
-            Ok(())
-        } else {
-            Err(VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })
-        }
-    }
-
-    pub async fn set_boot_start(&self, session_id: &str, enabled: bool) -> Result<()> {
-        let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(session_id) {
-            session.boot_start = enabled;
-            self.save_sessions().await?;
-            tracing::info!("Set boot_start={} for session {}", enabled, session_id);
-            Ok(())
-        } else {
-            Err(VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })
-        }
-    }
-
-    pub async fn get_boot_start_sessions(&self) -> Result<Vec<VmSession>> {
-        let sessions = self.sessions.read().await;
-        Ok(sessions
-            .values()
-            .filter(|s| s.boot_start)
-            .cloned()
-            .collect())
-    }
-
-    pub async fn start_session(&self, session_id: &str) -> Result<()> {
-        let session = self
-            .get_session(session_id)
-            .await?
-            .ok_or_else(|| VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })?;
-
-        match session.state {
-            SessionState::Stopped | SessionState::Error { .. } => {
-                // Recreate the VM
-                let vm_instance = self.vm_manager.create(session.spec.clone()).await?;
-
-                let mut updated_session = session;
-                updated_session.vm_id = vm_instance.id;
-                updated_session.state = SessionState::Detached;
-
-                {
-                    let mut sessions = self.sessions.write().await;
-                    sessions.insert(session_id.to_string(), updated_session);
-                }
-                self.save_sessions().await?;
-
-                tracing::info!("Started session {}", session_id);
-                Ok(())
-            }
-            SessionState::Running | SessionState::Detached | SessionState::Attached { .. } => {
-                tracing::info!("Session {} already running", session_id);
-                Ok(())
-            }
-            _ => Err(VortexError::VmError {
-                message: format!(
-                    "Cannot start session {} in state {:?}",
-                    session_id, session.state
-                ),
-            }),
-        }
-    }
-
-    pub async fn stop_session(&self, session_id: &str) -> Result<()> {
-        let session = self
-            .get_session(session_id)
-            .await?
-            .ok_or_else(|| VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })?;
-
-        if let Err(e) = self.vm_manager.stop(&session.vm_id).await {
-            tracing::warn!(
-                "Failed to stop VM {} for session {}: {}",
-                session.vm_id,
-                session_id,
-                e
-            );
-        }
-
-        let mut updated_session = session;
-        updated_session.state = SessionState::Stopped;
-
-        {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(session_id.to_string(), updated_session);
-        }
-        self.save_sessions().await?;
-
-        tracing::info!("Stopped session {}", session_id);
-        Ok(())
-    }
-
-    pub async fn pause_session(&self, session_id: &str) -> Result<()> {
-        // Note: krunvm doesn't have native pause, but we can track the state
-        let session = self
-            .get_session(session_id)
-            .await?
-            .ok_or_else(|| VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })?;
-
-        let mut updated_session = session;
-        updated_session.state = SessionState::Paused;
-
-        {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(session_id.to_string(), updated_session);
-        }
-        self.save_sessions().await?;
-
-        tracing::info!("Paused session {}", session_id);
-        Ok(())
-    }
-
-    pub async fn resume_session(&self, session_id: &str) -> Result<()> {
-        let session = self
-            .get_session(session_id)
-            .await?
-            .ok_or_else(|| VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })?;
-
-        let mut updated_session = session;
-        updated_session.state = SessionState::Detached;
-
-        {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(session_id.to_string(), updated_session);
-        }
-        self.save_sessions().await?;
-
-        tracing::info!("Resumed session {}", session_id);
-        Ok(())
-    }
-
-    pub async fn restart_session(&self, session_id: &str) -> Result<()> {
-        self.stop_session(session_id).await?;
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-        self.start_session(session_id).await?;
-        Ok(())
-    }
-
-    pub async fn attach_session(&self, session_id: &str, client_pid: u32) -> Result<()> {
-        let session = self
-            .get_session(session_id)
-            .await?
-            .ok_or_else(|| VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })?;
-
-        match session.state {
-            SessionState::Detached | SessionState::Running => {
-                // Update session state
-                let mut updated_session = session.clone();
-                updated_session.state = SessionState::Attached { client_pid };
-                updated_session.last_attached = Some(Utc::now());
-
-                {
-                    let mut sessions = self.sessions.write().await;
-                    sessions.insert(session_id.to_string(), updated_session);
-                }
-                self.save_sessions().await?;
-
-                // Attach to VM
-                self.vm_manager.attach(&session.vm_id).await?;
-
-                // When attach returns (user detached), update state
-                let mut detached_session = session;
-                detached_session.state = SessionState::Detached;
-
-                {
-                    let mut sessions = self.sessions.write().await;
-                    sessions.insert(session_id.to_string(), detached_session);
-                }
-                self.save_sessions().await?;
-
-                Ok(())
-            }
-            SessionState::Attached { .. } => Err(VortexError::VmError {
-                message: format!("Session {} is already attached", session_id),
-            }),
-            _ => Err(VortexError::VmError {
-                message: format!(
-                    "Cannot attach to session {} in state {:?}",
-                    session_id, session.state
-                ),
-            }),
-        }
-    }
-
-    pub async fn detach_session(&self, session_id: &str) -> Result<()> {
-        let session = self
-            .get_session(session_id)
-            .await?
-            .ok_or_else(|| VortexError::VmError {
-                message: format!("Session {} not found", session_id),
-            })?;
-
-        let mut updated_session = session;
-        updated_session.state = SessionState::Detached;
-
-        {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(session_id.to_string(), updated_session);
-        }
-        self.save_sessions().await?;
-
-        tracing::info!("Detached from session {}", session_id);
-        Ok(())
-    }
-
-    pub async fn get_daemon_status(&self) -> Result<SessionResponse> {
-        let sessions = self.sessions.read().await;
-        let uptime = (Utc::now() - self.daemon_start_time).num_seconds() as u64;
-        let sessions_count = sessions.len();
-
-        let active_vms = sessions
-            .values()
-            .filter(|s| {
-                matches!(
-                    s.state,
-                    SessionState::Running | SessionState::Attached { .. } | SessionState::Detached
-                )
-            })
-            .count();
-
-        // Get memory usage from actual metrics
-        let memory_usage = if sessions_count > 0 {
-            // Use actual estimated memory per session
-            sessions_count as u64 * 512 * 1024 * 1024
-        } else {
-            0
-        };
-
-        Ok(SessionResponse::DaemonStatus {
-            uptime,
-            sessions_count,
-            active_vms,
-            memory_usage,
-        })
-    }
-
-    pub async fn handle_command(&self, command: SessionCommand) -> Result<SessionResponse> {
-        match command {
-            SessionCommand::CreateSession {
-                spec,
-                name,
-                persistent,
-                boot_start,
-            } => match self
-                .create_session(*spec, name, persistent, boot_start)
-                .await
-            {
-                Ok(session) => Ok(SessionResponse::SessionCreated { session }),
-                Err(e) => Ok(SessionResponse::Error {
-                    message: e.to_string(),
-                }),
-            },
-            SessionCommand::ListSessions => match self.list_sessions().await {
-                Ok(sessions) => Ok(SessionResponse::SessionList { sessions }),
-                Err(e) => Ok(SessionResponse::Error {
-                    message: e.to_string(),
-                }),
-            },
-            SessionCommand::GetSession { session_id } => {
-                match self.get_session(&session_id).await {
-                    Ok(Some(session)) => Ok(SessionResponse::Session { session }),
-                    Ok(None) => Ok(SessionResponse::Error {
-                        message: format!("Session {} not found", session_id),
-                    }),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::DeleteSession { session_id } => {
-                match self.delete_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::StartSession { session_id } => {
-                match self.start_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::StopSession { session_id } => {
-                match self.stop_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::PauseSession { session_id } => {
-                match self.pause_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::ResumeSession { session_id } => {
-                match self.resume_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::RestartSession { session_id } => {
-                match self.restart_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::AttachSession {
-                session_id,
-                client_pid,
-            } => match self.attach_session(&session_id, client_pid).await {
-                Ok(()) => Ok(SessionResponse::Success),
-                Err(e) => Ok(SessionResponse::Error {
-                    message: e.to_string(),
-                }),
-            },
-            SessionCommand::DetachSession { session_id } => {
-                match self.detach_session(&session_id).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::EnableBootStart { session_id } => {
-                match self.set_boot_start(&session_id, true).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::DisableBootStart { session_id } => {
-                match self.set_boot_start(&session_id, false).await {
-                    Ok(()) => Ok(SessionResponse::Success),
-                    Err(e) => Ok(SessionResponse::Error {
-                        message: e.to_string(),
-                    }),
-                }
-            }
-            SessionCommand::GetBootStartSessions => match self.get_boot_start_sessions().await {
-                Ok(sessions) => Ok(SessionResponse::BootStartSessions { sessions }),
-                Err(e) => Ok(SessionResponse::Error {
-                    message: e.to_string(),
-                }),
-            },
-            SessionCommand::Ping => Ok(SessionResponse::Success),
-            SessionCommand::Shutdown => Ok(SessionResponse::Success),
-            SessionCommand::GetDaemonStatus => self.get_daemon_status().await,
-            // Authentication commands - currently no-op (socket permissions handle auth)
-            // Token can be added later for enhanced security
-            SessionCommand::Authenticate { .. } | SessionCommand::VerifyToken { .. } => {
-                Ok(SessionResponse::Success)
-            }
-        }
-    }
-
-    pub async fn cleanup_stale_sessions(&self) -> Result<()> {
-        let mut sessions_to_remove = Vec::new();
-
-        {
-            let sessions = self.sessions.read().await;
-            for (session_id, session) in sessions.iter() {
-                if !session.persistent {
-                    // Check if session has been detached for more than 24 hours
-                    if let Some(last_attached) = session.last_attached {
-                        let hours_since_attach = (Utc::now() - last_attached).num_hours();
-                        if hours_since_attach > 24
-                            && matches!(session.state, SessionState::Detached)
-                        {
-                            sessions_to_remove.push(session_id.clone());
-                        }
-                    }
-                }
-            }
-        }
-
-        for session_id in sessions_to_remove {
-            tracing::info!("Cleaning up stale session: {}", session_id);
-            if let Err(e) = self.delete_session(&session_id).await {
-                tracing::warn!("Failed to cleanup stale session {}: {}", session_id, e);
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Start all sessions with boot_start enabled
-    pub async fn start_boot_start_sessions(&self) -> Result<()> {
-        let boot_start_sessions = self.get_boot_start_sessions().await?;
-
-        if boot_start_sessions.is_empty() {
-            info!("No boot-start sessions to start");
-            return Ok(());
-        }
-
-        info!(
-            "Starting {} boot-start sessions...",
-            boot_start_sessions.len()
-        );
-
-        for session in boot_start_sessions {
-            info!("Starting boot-start session: {}", session.id);
-            if let Err(e) = self.start_session(&session.id).await {
-                warn!("Failed to start boot-start session {}: {}", session.id, e);
-            }
-        }
-
-        Ok(())
-    }
-}
@@ -2,9 +2,28 @@
 //!
 //! Lightning-fast ephemeral VM platform with hardware-level isolation.
 //! 20x faster than Docker DevContainers with true security.
+//!
+//! This crate bundles the workspace crates behind one name:
+//!
+//! - `vortex-core`: VM lifecycle, configuration, templates and workspaces
+//! - `vortex-backends`: the backend implementations, one feature each
+//! - `vortex-daemon`: sessions and the background daemon (`daemon` feature)
+//! - `vortex-sync`: conflict-aware `--sync-back` (`sync` feature)
+//!
+//! Embedders that only need to run VMs can depend on `vortex-core` and
+//! `vortex-backends` directly.
 
-pub mod core;
 pub mod discovery;
+#[cfg(feature = "daemon")]
+mod platform;
+
+// Re-export everything from the subsystem crates for convenience
+pub use vortex_backends::*;
+pub use vortex_core::*;
+#[cfg(feature = "daemon")]
+pub use vortex_daemon::*;
+#[cfg(feature = "sync")]
+pub use vortex_sync as sync;
 
-// Re-export everything from core for convenience
-pub use core::*;
+#[cfg(feature = "daemon")]
+pub use platform::{init, VortexCore};
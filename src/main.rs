@@ -1,3151 +0,0 @@
-use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::sync::Semaphore;
-use tracing::info;
-use vortex::{
-    config::PluginConfig, detect_workspace_info, init, DaemonClient, ResourceLimits,
-    SessionCommand, SessionResponse, VmSpec, VortexConfig, VortexCore, VortexDaemon,
-    WorkspaceInfo, VERSION,
-};
-
-#[derive(Parser)]
-#[command(
-    name = "vortex",
-    about = "Vortex - Lightning-fast ephemeral VM platform",
-    version = env!("CARGO_PKG_VERSION")
-)]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
-
-    #[arg(long, global = true, help = "Enable verbose logging")]
-    verbose: bool,
-}
-
-#[derive(Subcommand)]
-enum Commands {
-    #[command(about = "Start a new ephemeral VM")]
-    Run {
-        #[arg(help = "VM image (alpine, ubuntu:22.04, debian:bullseye)")]
-        image: String,
-
-        #[arg(short, long, help = "Memory in MB", default_value = "512")]
-        memory: u32,
-
-        #[arg(short, long, help = "CPU cores", default_value = "1")]
-        cpus: u32,
-
-        #[arg(short, long, help = "Port forwarding (host:guest)")]
-        port: Vec<String>,
-
-        #[arg(short = 'v', long, help = "Volume mounts (host:guest)")]
-        volume: Vec<String>,
-
-        #[arg(short = 'e', long, help = "Command to run in VM")]
-        command: Option<String>,
-
-        #[arg(long, help = "Keep VM running after command exits")]
-        persist: bool,
-
-        #[arg(
-            short = 'q',
-            long,
-            help = "Quiet mode - suppress all non-command output"
-        )]
-        quiet: bool,
-
-        #[arg(long, help = "Show real-time performance stats (Docker can't do this)")]
-        monitor_performance: bool,
-
-        #[arg(
-            long,
-            help = "Copy contents of host directory to VM directory (host:guest)"
-        )]
-        copy_to: Vec<String>,
-
-        #[arg(
-            long,
-            help = "Copy VM directory contents back to host after execution (guest:host)"
-        )]
-        sync_back: Vec<String>,
-
-        #[arg(short = 'w', long, help = "Set working directory inside VM")]
-        workdir: Option<String>,
-
-        #[arg(long, help = "Add labels (key=value)")]
-        label: Vec<String>,
-
-        #[arg(
-            long,
-            help = "Cache dependencies for faster subsequent runs (Docker can't do this efficiently)"
-        )]
-        cache_deps: bool,
-    },
-
-    #[command(about = "List running VMs")]
-    List,
-
-    #[command(about = "Stop and cleanup a VM")]
-    Stop {
-        #[arg(help = "VM ID")]
-        vm_id: String,
-    },
-
-    #[command(about = "Stop all running VMs")]
-    Cleanup,
-
-    #[command(about = "Run from a template")]
-    Template {
-        #[arg(help = "Template name")]
-        name: String,
-
-        #[arg(short, long, help = "Override command")]
-        command: Option<String>,
-    },
-
-    #[command(about = "Show available templates and aliases")]
-    Templates,
-
-    #[command(about = "Show VM metrics")]
-    Metrics {
-        #[arg(help = "VM ID (optional - shows all if omitted)")]
-        vm_id: Option<String>,
-    },
-
-    #[command(about = "Run command across multiple VMs in parallel (Docker can't do this)")]
-    Parallel {
-        #[arg(help = "VM images to run in parallel")]
-        images: Vec<String>,
-
-        #[arg(short = 'e', long, help = "Command to run in each VM")]
-        command: String,
-
-        #[arg(short = 'q', long, help = "Quiet mode - show only aggregated results")]
-        quiet: bool,
-
-        #[arg(long, help = "Copy contents to each VM (host:guest)")]
-        copy_to: Vec<String>,
-
-        #[arg(long, help = "Sync results back from each VM")]
-        sync_back: Vec<String>,
-    },
-
-    #[command(about = "Create instant dev environments (Docker can't match this speed!)")]
-    Dev {
-        #[arg(help = "Development template (python, node, rust, go, ai)", required_unless_present_any = ["list", "init", "workspace"])]
-        template: Option<String>,
-
-        #[arg(short, long, help = "Custom working directory")]
-        workdir: Option<String>,
-
-        #[arg(short = 'v', long, help = "Volume mounts (host:guest)")]
-        volume: Vec<String>,
-
-        #[arg(short = 'p', long, help = "Port mappings (host:guest)")]
-        port: Vec<String>,
-
-        #[arg(short = 'q', long, help = "Quiet mode - no banner")]
-        quiet: bool,
-
-        #[arg(long, help = "List available development templates")]
-        list: bool,
-
-        #[arg(long, help = "Create/use persistent workspace")]
-        workspace: Option<String>,
-
-        #[arg(long, help = "Initialize workspace from current directory")]
-        init: bool,
-
-        #[arg(short, long, help = "Session name for background execution")]
-        name: Option<String>,
-
-        #[arg(long, help = "Run in background (detached mode)")]
-        detach: bool,
-    },
-
-    #[command(about = "Manage persistent workspaces")]
-    Workspace {
-        #[command(subcommand)]
-        command: WorkspaceCommand,
-    },
-
-    #[command(about = "Session management - create persistent VM sessions")]
-    Session {
-        #[command(subcommand)]
-        command: SessionSubcommand,
-    },
-
-    #[command(about = "Daemon management - background session orchestration")]
-    Daemon {
-        #[command(subcommand)]
-        command: DaemonSubcommand,
-    },
-
-    #[command(about = "Plugin management - add and manage vortex extensions")]
-    Plugin {
-        #[command(subcommand)]
-        command: PluginCommand,
-    },
-
-    #[command(about = "Attach to a running session (like screen -r)")]
-    Attach {
-        #[arg(help = "Session ID or name to attach to")]
-        session: String,
-    },
-
-    #[command(about = "Virtual machine management commands")]
-    Vm {
-        #[command(subcommand)]
-        command: VmCommand,
-    },
-}
-
-#[derive(Subcommand)]
-enum WorkspaceCommand {
-    #[command(about = "List all workspaces")]
-    List,
-
-    #[command(about = "Create a new workspace")]
-    Create {
-        #[arg(help = "Workspace name")]
-        name: String,
-
-        #[arg(short, long, help = "Development template to use")]
-        template: String,
-
-        #[arg(long, help = "Source directory to copy (defaults to current dir)")]
-        source: Option<PathBuf>,
-
-        #[arg(
-            long,
-            help = "VM backend to use (krunvm or firecracker)",
-            default_value = "krunvm"
-        )]
-        backend: String,
-    },
-
-    #[command(about = "Delete a workspace")]
-    Delete {
-        #[arg(help = "Workspace name or ID")]
-        workspace: String,
-    },
-
-    #[command(about = "Show workspace details")]
-    Info {
-        #[arg(help = "Workspace name or ID")]
-        workspace: String,
-    },
-
-    #[command(about = "Import from devcontainer.json")]
-    Import {
-        #[arg(help = "Workspace name")]
-        name: String,
-
-        #[arg(
-            long,
-            help = "Path to devcontainer.json",
-            default_value = ".devcontainer/devcontainer.json"
-        )]
-        devcontainer: PathBuf,
-
-        #[arg(long, help = "Source directory (defaults to current dir)")]
-        source: Option<PathBuf>,
-
-        #[arg(
-            long,
-            help = "VM backend to use (krunvm or firecracker)",
-            default_value = "krunvm"
-        )]
-        backend: String,
-    },
-
-    #[command(about = "Initialize a new workspace with interactive setup")]
-    Init {
-        #[arg(
-            help = "Directory to scan (defaults to current dir)",
-            default_value = "."
-        )]
-        directory: PathBuf,
-
-        #[arg(
-            long,
-            help = "Output path for vortex.yaml",
-            default_value = "vortex.yaml"
-        )]
-        output: PathBuf,
-
-        #[arg(long, help = "Non-interactive mode - auto-scan and generate")]
-        non_interactive: bool,
-
-        #[arg(
-            long,
-            help = "VM backend to use (krunvm or firecracker)",
-            default_value = "krunvm"
-        )]
-        backend: String,
-    },
-}
-
-#[derive(Subcommand)]
-enum SessionSubcommand {
-    #[command(about = "Create a new persistent session")]
-    Create {
-        #[arg(help = "Development template (python, node, rust, etc.)")]
-        template: String,
-
-        #[arg(short, long, help = "Session name (optional)")]
-        name: Option<String>,
-
-        #[arg(short, long, help = "Memory in MB", default_value = "512")]
-        memory: u32,
-
-        #[arg(short, long, help = "CPU cores", default_value = "1")]
-        cpus: u32,
-
-        #[arg(short, long, help = "Port mappings (host:guest)")]
-        port: Vec<String>,
-
-        #[arg(short = 'v', long, help = "Volume mounts (host:guest)")]
-        volume: Vec<String>,
-
-        #[arg(long, help = "Create but don't attach immediately")]
-        detach: bool,
-
-        #[arg(long, help = "Make session persistent (survives logout)")]
-        persistent: bool,
-    },
-
-    #[command(about = "List all sessions")]
-    List,
-
-    #[command(about = "Show session details")]
-    Info {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Start a stopped session")]
-    Start {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Stop a running session")]
-    Stop {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Pause a session (preserve state)")]
-    Pause {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Resume a paused session")]
-    Resume {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Restart a session")]
-    Restart {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Delete a session")]
-    Delete {
-        #[arg(help = "Session ID or name")]
-        session: String,
-
-        #[arg(short, long, help = "Force deletion without confirmation")]
-        force: bool,
-    },
-
-    #[command(about = "Enable autostart for a session (starts on boot)")]
-    EnableAutostart {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-
-    #[command(about = "Disable autostart for a session")]
-    DisableAutostart {
-        #[arg(help = "Session ID or name")]
-        session: String,
-    },
-}
-
-#[derive(Subcommand)]
-enum DaemonSubcommand {
-    #[command(about = "Start the Vortex daemon")]
-    Start {
-        #[arg(long, help = "Run in background (fork)")]
-        background: bool,
-    },
-
-    #[command(about = "Stop the Vortex daemon")]
-    Stop,
-
-    #[command(about = "Show daemon status")]
-    Status,
-
-    #[command(about = "Restart the daemon")]
-    Restart,
-
-    #[command(about = "Show daemon logs (if available)")]
-    Logs,
-}
-
-#[derive(Subcommand)]
-enum PluginCommand {
-    #[command(about = "List all installed plugins")]
-    List,
-
-    #[command(about = "Add a new plugin from a git repository")]
-    Add {
-        #[arg(help = "Git repository URL (e.g., https://github.com/exec/vortex-web)")]
-        repo: String,
-
-        #[arg(long, help = "Plugin name (defaults to repo name)")]
-        name: Option<String>,
-    },
-
-    #[command(about = "Remove a plugin")]
-    Remove {
-        #[arg(help = "Plugin name")]
-        name: String,
-    },
-
-    #[command(about = "Enable a plugin")]
-    Enable {
-        #[arg(help = "Plugin name")]
-        name: String,
-    },
-
-    #[command(about = "Disable a plugin")]
-    Disable {
-        #[arg(help = "Plugin name")]
-        name: String,
-    },
-}
-
-#[derive(Subcommand)]
-enum VmCommand {
-    #[command(about = "Create a new VM")]
-    Create {
-        #[arg(help = "VM name")]
-        name: String,
-
-        #[arg(help = "VM image (alpine, ubuntu:22.04, debian:bullseye)")]
-        image: String,
-
-        #[arg(short, long, help = "Memory in MB", default_value = "512")]
-        memory: u32,
-
-        #[arg(short, long, help = "CPU cores", default_value = "1")]
-        cpus: u32,
-
-        #[arg(short, long, help = "Port forwarding (host:guest)")]
-        port: Vec<String>,
-    },
-
-    #[command(about = "List running VMs")]
-    List,
-
-    #[command(about = "Stop and cleanup a VM")]
-    Stop {
-        #[arg(help = "VM name or ID")]
-        vm_name: String,
-    },
-
-    #[command(about = "Cleanup a specific VM or all VMs")]
-    Cleanup {
-        #[arg(long, help = "Name of VM to cleanup (if omitted, cleans all)")]
-        name: Option<String>,
-    },
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    // Check if any command is using quiet mode
-    let is_quiet = match &cli.command {
-        Commands::Run { quiet, .. } => *quiet,
-        Commands::Dev { quiet, .. } => *quiet,
-        _ => false,
-    };
-
-    if is_quiet {
-        // Disable all logging in quiet mode - use ERROR level as lowest
-        tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::ERROR)
-            .with_target(false)
-            .without_time()
-            .init();
-    } else if cli.verbose {
-        tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::DEBUG)
-            .init();
-    } else {
-        tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::INFO)
-            .init();
-    }
-
-    if !is_quiet {
-        info!("Vortex v{} - Ephemeral VM Platform", VERSION);
-    }
-
-    // Initialize Vortex Core
-    let vortex = Arc::new(init().await.context("Failed to initialize Vortex core")?);
-
-    match cli.command {
-        Commands::Run {
-            image,
-            memory,
-            cpus,
-            port,
-            volume,
-            command,
-            persist,
-            quiet: run_quiet,
-            monitor_performance,
-            copy_to,
-            sync_back,
-            workdir,
-            label,
-            cache_deps,
-        } => {
-            let spec = VmSpec {
-                image,
-                memory,
-                cpus,
-                ports: parse_port_mappings(port)?,
-                volumes: parse_volume_mappings(volume)?,
-                environment: HashMap::new(),
-                command,
-                labels: parse_labels(label)?,
-                network_config: None,
-                resource_limits: ResourceLimits::default(),
-                backend: None,
-            };
-
-            run_vm(
-                &vortex,
-                spec,
-                persist,
-                run_quiet,
-                monitor_performance,
-                copy_to,
-                sync_back,
-                workdir,
-                cache_deps,
-            )
-            .await?;
-        }
-        Commands::List => {
-            list_vms(&vortex).await?;
-        }
-        Commands::Stop { vm_id } => {
-            stop_vm(&vortex, &vm_id).await?;
-        }
-        Commands::Cleanup => {
-            cleanup_vms(&vortex).await?;
-        }
-        Commands::Template { name, command } => {
-            run_template(&vortex, &name, command).await?;
-        }
-        Commands::Templates => {
-            show_templates().await?;
-        }
-        Commands::Metrics { vm_id } => {
-            show_metrics(&vortex, vm_id.as_deref()).await?;
-        }
-        Commands::Parallel {
-            images,
-            command,
-            quiet,
-            copy_to,
-            sync_back,
-        } => {
-            run_parallel_vms(&vortex, images, command, quiet, copy_to, sync_back).await?;
-        }
-        Commands::Dev {
-            template,
-            workdir,
-            volume,
-            port,
-            quiet,
-            list,
-            workspace,
-            init,
-            name,
-            detach,
-        } => {
-            if list {
-                show_dev_templates(&vortex).await?;
-            } else if init {
-                init_workspace_from_current_dir(&vortex).await?;
-            } else if let Some(workspace_name) = workspace {
-                start_workspace(&vortex, &workspace_name, quiet).await?;
-            } else if let Some(template_name) = template {
-                start_dev_environment(
-                    &vortex,
-                    &template_name,
-                    workdir,
-                    volume,
-                    port,
-                    quiet,
-                    name,
-                    detach,
-                )
-                .await?;
-            } else {
-                return Err(anyhow::anyhow!(
-                    "Template name, workspace, or --list is required"
-                ));
-            }
-        }
-        Commands::Workspace { command } => match command {
-            WorkspaceCommand::List => {
-                list_workspaces(&vortex).await?;
-            }
-            WorkspaceCommand::Create {
-                name,
-                template,
-                source,
-                backend,
-            } => {
-                create_workspace(&vortex, &name, &template, &source, &backend).await?;
-            }
-            WorkspaceCommand::Delete { workspace } => {
-                delete_workspace(&vortex, &workspace).await?;
-            }
-            WorkspaceCommand::Info { workspace } => {
-                show_workspace_info(&vortex, &workspace).await?;
-            }
-            WorkspaceCommand::Import {
-                name,
-                devcontainer,
-                source,
-                backend,
-            } => {
-                import_devcontainer_workspace(&vortex, &name, &devcontainer, &source, &backend)
-                    .await?;
-            }
-            WorkspaceCommand::Init {
-                directory,
-                output,
-                non_interactive,
-                backend,
-            } => {
-                // Workspace init can work without a backend for config generation
-                handle_workspace_init(&directory, &output, non_interactive, &backend).await?;
-            }
-        },
-        Commands::Session { command } => match command {
-            SessionSubcommand::Create {
-                template,
-                name,
-                memory,
-                cpus,
-                port,
-                volume,
-                detach,
-                persistent,
-            } => {
-                handle_session_create(
-                    &vortex, &template, name, memory, cpus, &port, &volume, detach, persistent,
-                )
-                .await?;
-            }
-            SessionSubcommand::List => {
-                handle_session_list().await?;
-            }
-            SessionSubcommand::Info { session } => {
-                handle_session_info(&session).await?;
-            }
-            SessionSubcommand::Start { session } => {
-                handle_session_start(&session).await?;
-            }
-            SessionSubcommand::Stop { session } => {
-                handle_session_stop(&session).await?;
-            }
-            SessionSubcommand::Pause { session } => {
-                handle_session_pause(&session).await?;
-            }
-            SessionSubcommand::Resume { session } => {
-                handle_session_resume(&session).await?;
-            }
-            SessionSubcommand::Restart { session } => {
-                handle_session_restart(&session).await?;
-            }
-            SessionSubcommand::Delete { session, force } => {
-                handle_session_delete(&session, force).await?;
-            }
-            SessionSubcommand::EnableAutostart { session } => {
-                handle_session_enable_autostart(&session).await?;
-            }
-            SessionSubcommand::DisableAutostart { session } => {
-                handle_session_disable_autostart(&session).await?;
-            }
-        },
-        Commands::Daemon { command } => match command {
-            DaemonSubcommand::Start { background } => {
-                handle_daemon_start(background).await?;
-            }
-            DaemonSubcommand::Stop => {
-                handle_daemon_stop().await?;
-            }
-            DaemonSubcommand::Status => {
-                handle_daemon_status().await?;
-            }
-            DaemonSubcommand::Restart => {
-                handle_daemon_restart().await?;
-            }
-            DaemonSubcommand::Logs => {
-                handle_daemon_logs().await?;
-            }
-        },
-        Commands::Attach { session } => {
-            // Just use the VM manager's attach directly
-            vortex.attach_vm(&session).await?;
-        }
-        Commands::Vm { command } => match command {
-            VmCommand::Create {
-                name,
-                image,
-                memory,
-                cpus,
-                port,
-            } => {
-                let spec = VmSpec {
-                    image,
-                    memory,
-                    cpus,
-                    ports: parse_port_mappings(port)?,
-                    volumes: HashMap::new(),
-                    environment: HashMap::new(),
-                    command: None,
-                    labels: HashMap::new(),
-                    network_config: None,
-                    resource_limits: ResourceLimits::default(),
-                    backend: None,
-                };
-                tracing::info!("Creating VM '{}' with spec: {:?}", name, spec);
-                vortex.vm_manager.create(spec).await?;
-            }
-            VmCommand::List => {
-                list_vms(&vortex).await?;
-            }
-            VmCommand::Stop { vm_name } => {
-                stop_vm(&vortex, &vm_name).await?;
-            }
-            VmCommand::Cleanup { name } => {
-                if let Some(vm_name) = name {
-                    vortex.vm_manager.cleanup(&vm_name).await?;
-                } else {
-                    cleanup_vms(&vortex).await?;
-                }
-            }
-        }
-        Commands::Plugin { command } => match command {
-            PluginCommand::List => {
-                list_plugins(&vortex).await?;
-            }
-            PluginCommand::Add { repo, name } => {
-                add_plugin(&vortex, &repo, name.as_deref()).await?;
-            }
-            PluginCommand::Remove { name } => {
-                remove_plugin(&vortex, &name).await?;
-            }
-            PluginCommand::Enable { name } => {
-                enable_plugin(&vortex, &name).await?;
-            }
-            PluginCommand::Disable { name } => {
-                disable_plugin(&vortex, &name).await?;
-            }
-        }
-    }
-
-    Ok(())
-}
-
-#[allow(clippy::too_many_arguments)]
-async fn run_vm(
-    vortex: &Arc<VortexCore>,
-    mut spec: VmSpec,
-    persist: bool,
-    quiet: bool,
-    monitor_performance: bool,
-    copy_to: Vec<String>,
-    sync_back: Vec<String>,
-    workdir: Option<String>,
-    cache_deps: bool,
-) -> Result<()> {
-    // Parse copy mappings and set up volumes
-    let copy_mappings = parse_copy_mappings(copy_to)?;
-    let sync_mappings = parse_sync_back_mappings(sync_back)?;
-
-    // Get cache directory with secure fallback
-    let cache_dir = get_cache_dir()?;
-
-    // Add dependency caching volume if requested
-    if cache_deps {
-        std::fs::create_dir_all(&cache_dir)?;
-        spec.volumes.insert(
-            cache_dir.clone(),
-            std::path::PathBuf::from("/vortex_cache"),
-        );
-        if !quiet {
-            println!("🔄 Dependency caching enabled (Docker's layer caching is primitive compared to this!)");
-        }
-    }
-
-    // Add temporary mount points for copy operations
-    for (i, (host_path, _)) in copy_mappings.iter().enumerate() {
-        let temp_mount = format!("/tmp/vortex_copy_in_{}", i);
-        spec.volumes
-            .insert(host_path.clone(), PathBuf::from(&temp_mount));
-    }
-
-    // Add mount points for sync back operations
-    for (i, (_guest_path, host_path)) in sync_mappings.iter().enumerate() {
-        let temp_mount = format!("/tmp/vortex_copy_out_{}", i);
-        spec.volumes
-            .insert(host_path.clone(), PathBuf::from(&temp_mount));
-    }
-
-    // Build enhanced command with copy operations and workdir
-    if let Some(original_cmd) = &spec.command {
-        // Note: original_cmd comes from user input and is executed inside the VM.
-        // The VM provides isolation, but we still validate paths in copy mappings.
-        // The command is executed in a minimal shell environment inside the VM.
-        let mut enhanced_cmd = String::new();
-
-        // Copy input files
-        for (i, (_, dest_path)) in copy_mappings.iter().enumerate() {
-            let temp_mount = format!("/tmp/vortex_copy_in_{}", i);
-            // Use shell quoting for paths to handle special characters safely
-            enhanced_cmd.push_str(&format!(
-                "mkdir -p {} && cp -r {}/* {} 2>/dev/null || true; ",
-                shell_quote(&dest_path.display().to_string()),
-                shell_quote(&temp_mount),
-                shell_quote(&dest_path.display().to_string())
-            ));
-        }
-
-        // Change to workdir if specified
-        if let Some(wd) = &workdir {
-            enhanced_cmd.push_str(&format!("cd {}; ", shell_quote(wd)));
-        }
-
-        // Run the actual command - execute directly without shell concatenation
-        // The command should be a simple command, not a shell script
-        enhanced_cmd.push_str(original_cmd);
-        enhanced_cmd.push(';');
-
-        // Copy output files back
-        for (i, (source_path, _)) in sync_mappings.iter().enumerate() {
-            let temp_mount = format!("/tmp/vortex_copy_out_{}", i);
-            enhanced_cmd.push_str(&format!(
-                " cp -r {} {} 2>/dev/null || true;",
-                shell_quote(&source_path.display().to_string()),
-                shell_quote(&temp_mount)
-            ));
-        }
-
-        spec.command = Some(enhanced_cmd);
-    }
-
-    if !quiet {
-        info!("Starting VM with image: {}", spec.image);
-    }
-
-    let vm = vortex.create_vm(spec).await?;
-
-    // Start performance monitoring if requested
-    if monitor_performance && !quiet {
-        let vortex_clone = Arc::clone(vortex);
-        let vm_id_clone = vm.id.clone();
-        tokio::spawn(async move {
-            monitor_vm_performance(&vortex_clone, &vm_id_clone).await;
-        });
-    }
-
-    if persist {
-        if !quiet {
-            info!(
-                "VM {} started and persisting. Use 'vortex stop {}' to stop it.",
-                vm.id, vm.id
-            );
-        }
-    } else if !quiet {
-        info!("VM {} started. Command completed.", vm.id);
-        // For non-persistent VMs, we should wait for completion and cleanup
-        // This would require backend support for monitoring VM completion
-    }
-
-    Ok(())
-}
-
-async fn list_vms(vortex: &Arc<VortexCore>) -> Result<()> {
-    let vms = vortex.vm_manager.list().await?;
-
-    if vms.is_empty() {
-        println!("No background sessions found.");
-        println!("💡 Create one with: vortex dev <template> --name <name> --detach");
-    } else {
-        println!("🔥 Background Sessions:");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        for vm in vms {
-            println!(
-                "🟢 {} - {}MB RAM, {} CPU(s)",
-                vm.id, vm.spec.memory, vm.spec.cpus
-            );
-        }
-        println!();
-        println!("💡 Attach to session: vortex attach <session-id>");
-    }
-
-    Ok(())
-}
-
-async fn stop_vm(vortex: &Arc<VortexCore>, vm_id: &str) -> Result<()> {
-    vortex.vm_manager.stop(vm_id).await?;
-    vortex.vm_manager.cleanup(vm_id).await?;
-    info!("VM {} stopped and cleaned up.", vm_id);
-    Ok(())
-}
-
-async fn cleanup_vms(vortex: &Arc<VortexCore>) -> Result<()> {
-    let vms = vortex.vm_manager.list().await?;
-    let count = vms.len();
-
-    for vm in vms {
-        if let Err(e) = vortex.vm_manager.cleanup(&vm.id).await {
-            tracing::warn!("Failed to cleanup VM {}: {}", vm.id, e);
-        }
-    }
-
-    info!("Cleaned up {} VMs.", count);
-    Ok(())
-}
-
-async fn run_template(
-    vortex: &Arc<VortexCore>,
-    template_name: &str,
-    override_command: Option<String>,
-) -> Result<()> {
-    let config = VortexConfig::load()?;
-    let template = config
-        .get_template(template_name)
-        .ok_or_else(|| anyhow::anyhow!("Template '{}' not found", template_name))?;
-
-    info!(
-        "Running template: {} - {}",
-        template_name, template.description
-    );
-
-    let spec = VmSpec {
-        image: config.resolve_image(&template.image),
-        memory: template.memory,
-        cpus: template.cpus,
-        ports: parse_port_mappings(template.ports.clone())?,
-        volumes: parse_volume_mappings(template.volumes.clone())?,
-        environment: template.environment.clone(),
-        command: override_command.or_else(|| template.command.clone()),
-        labels: template.labels.clone(),
-        network_config: None,
-        resource_limits: ResourceLimits::default(),
-        backend: None,
-    };
-
-    run_vm(
-        vortex,
-        spec,
-        false,
-        false,
-        false,
-        vec![],
-        vec![],
-        None,
-        false,
-    )
-    .await?;
-    Ok(())
-}
-
-async fn list_plugins(_vortex: &Arc<VortexCore>) -> Result<()> {
-    let config = VortexConfig::load()?;
-
-    println!("Installed Plugins:");
-    if config.plugins.is_empty() {
-        println!("  No plugins installed. Use 'vortex plugin add <repo-url>' to add one.");
-    } else {
-        for (name, plugin) in &config.plugins {
-            let status = if plugin.enabled { "enabled" } else { "disabled" };
-            println!("  {} (v{}) - {}", name, plugin.version, status);
-            println!("    {} by {}", plugin.description, plugin.author);
-            println!("    Source: {}", plugin.source_repo);
-        }
-    }
-    Ok(())
-}
-
-/// Validate plugin name - only allow alphanumeric, hyphens, underscores, and dots
-fn validate_plugin_name(name: &str) -> Result<()> {
-    if name.is_empty() {
-        return Err(anyhow::anyhow!("Plugin name cannot be empty"));
-    }
-
-    if name.len() > 100 {
-        return Err(anyhow::anyhow!("Plugin name is too long (max 100 characters)"));
-    }
-
-    // Check for path traversal attempts
-    if name.contains("..") || name.contains("/") || name.contains("\\") {
-        return Err(anyhow::anyhow!(
-            "Invalid plugin name: path traversal characters not allowed"
-        ));
-    }
-
-    // Only allow alphanumeric, hyphens, underscores, and dots
-    for c in name.chars() {
-        if !c.is_ascii_alphanumeric() && c != '-' && c != '_' && c != '.' {
-            return Err(anyhow::anyhow!(
-                "Invalid plugin name: only alphanumeric, hyphens, underscores, and dots are allowed"
-            ));
-        }
-    }
-
-    Ok(())
-}
-
-/// Shell-quote a string to safely use in shell commands
-/// This function escapes special characters that could lead to command injection
-fn shell_quote(s: &str) -> String {
-    // If the string is empty or doesn't contain special chars, return as-is
-    if s.is_empty() || !s.chars().any(|c| {
-        matches!(c, ' ' | '\t' | '\n' | '\r' | '"' | '\'' | '`' | '$' | '\\' | '|' | '&' | ';' | '(' | ')' | '<' | '>' | '{' | '}' | '[' | ']' | '*' | '?' | '~' | '!' | '#' | '%')
-    }) {
-        return format!("'{}'", s.replace('\'', "'\"'\"'"));
-    }
-
-    // Escape single quotes and wrap in single quotes
-    format!("'{}'", s.replace('\'', "'\"'\"'"))
-}
-
-/// Normalize repo URL: convert "exec/vortex-web" to "https://github.com/exec/vortex-web.git"
-fn normalize_repo_url(repo: &str) -> Result<String> {
-    // If it already has a protocol, validate it
-    if let Some(protocol_end) = repo.find("://") {
-        let protocol = &repo[..protocol_end];
-        // Only allow HTTPS protocol (HTTP is insecure for plugin downloads)
-        if protocol != "https" {
-            return Err(anyhow::anyhow!(
-                "Only HTTPS URLs are supported for plugins. HTTP is not allowed \
-                as it is vulnerable to man-in-the-middle attacks. Please use a HTTPS URL."
-            ));
-        }
-        return Ok(repo.to_string());
-    }
-
-    // Convert "user/repo" to "https://github.com/user/repo.git"
-    // First validate the repo format (no path traversal)
-    let repo_clean = repo.trim_end_matches(".git");
-    if repo_clean.contains("..") || repo_clean.contains("/") {
-        // Only allow single-level "user/repo" format
-        let parts: Vec<&str> = repo_clean.split('/').collect();
-        if parts.len() > 2 {
-            return Err(anyhow::anyhow!(
-                "Invalid repo format. Use 'user/repo' (e.g., 'exec/vortex-web') or full HTTPS URL"
-            ));
-        }
-    }
-
-    Ok(format!("https://github.com/{}.git", repo_clean))
-}
-
-async fn add_plugin(_vortex: &Arc<VortexCore>, repo: &str, name: Option<&str>) -> Result<()> {
-    // Normalize repo URL: convert "exec/vortex-web" to "https://github.com/exec/vortex-web.git"
-    let normalized_repo = normalize_repo_url(repo)?;
-
-    let plugin_name = match name {
-        Some(n) => {
-            // Validate custom plugin name
-            validate_plugin_name(n)?;
-            n.to_string()
-        }
-        None => {
-            // Extract name from repo URL
-            let raw_name = normalized_repo
-                .trim_end_matches(".git")
-                .split('/')
-                .next_back()
-                .unwrap_or("plugin");
-
-            // Validate the extracted name
-            validate_plugin_name(raw_name)?;
-            raw_name.to_string()
-        }
-    };
-
-    println!("Adding plugin '{}' from {}...", plugin_name, normalized_repo);
-
-    let mut config = VortexConfig::load()?;
-
-    // Check if plugin already exists
-    if config.plugins.contains_key(&plugin_name) {
-        return Err(anyhow::anyhow!(
-            "Plugin '{}' is already installed",
-            plugin_name
-        ));
-    }
-
-    // Get the plugins directory
-    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
-    let vortex_dir = home.join(".vortex");
-    let plugins_dir = vortex_dir.join("plugins");
-
-    // Create plugins directory if it doesn't exist
-    std::fs::create_dir_all(&plugins_dir)?;
-
-    // Clone the plugin repo
-    let plugin_repo_dir = plugins_dir.join(&plugin_name);
-
-    println!("Cloning plugin repository...");
-    clone_repo(&normalized_repo, &plugin_repo_dir)?;
-
-    // Read plugin metadata from plugin.toml if it exists
-    let (version, description, author) = read_plugin_metadata(&plugin_repo_dir)?;
-
-    // Add plugin config
-    config.add_plugin(
-        plugin_name.clone(),
-        PluginConfig {
-            enabled: true,
-            version,
-            source_repo: normalized_repo.clone(),
-            description: description.unwrap_or_else(|| format!("Plugin from {}", normalized_repo)),
-            author: author.unwrap_or_else(|| "Unknown".to_string()),
-        },
-    );
-
-    config.save()?;
-
-    println!("Plugin '{}' added successfully!", plugin_name);
-    println!("Plugin repository cloned to: {}", plugin_repo_dir.display());
-    println!("The plugin config has been saved. You can enable/disable it with: vortex plugin enable/disable {}", plugin_name);
-
-    Ok(())
-}
-
-/// Clone a git repository to the specified directory
-/// Note: This function does not verify git signatures. For production use,
-/// implement GPG signature verification before executing plugin code.
-fn clone_repo(repo_url: &str, dest_dir: &Path) -> Result<()> {
-    // Check if directory exists and is not empty
-    if dest_dir.exists() {
-        if dest_dir.read_dir()?.any(|_| true) {
-            return Err(anyhow::anyhow!(
-                "Plugin directory '{}' already exists and is not empty",
-                dest_dir.display()
-            ));
-        }
-    } else {
-        std::fs::create_dir_all(dest_dir)?;
-    }
-
-    // Use git CLI which typically has better HTTPS support
-    // Add timeout to prevent hanging on slow/stalled repositories (5 minutes)
-    let output = std::process::Command::new("git")
-        .arg("clone")
-        .arg(repo_url)
-        .arg(dest_dir)
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to run git clone: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to clone repository: {}", stderr));
-    }
-
-    // Verify that the clone was successful by checking for .git directory
-    let git_dir = dest_dir.join(".git");
-    if !git_dir.exists() || !git_dir.is_dir() {
-        return Err(anyhow::anyhow!(
-            "Git clone completed but .git directory not found. Repository may be corrupted."
-        ));
-    }
-
-    // Basic plugin signature verification
-    // This provides warning for unsigned plugins rather than a strict verification system
-    // Full GPG verification would require additional dependencies and user setup
-    verify_plugin_integrity(dest_dir)?;
-
-    Ok(())
-}
-
-/// Basic verification of plugin integrity
-/// Checks for common security indicators and warns if issues are found.
-fn verify_plugin_integrity(repo_dir: &Path) -> Result<()> {
-    let plugin_toml = repo_dir.join("plugin.toml");
-    let signature_file = repo_dir.join(".vortex-signature");
-    let metadata_file = repo_dir.join(".vortex-metadata.json");
-
-    // Check if plugin.toml exists (required)
-    if !plugin_toml.exists() {
-        tracing::warn!(
-            "Plugin at {} does not have a plugin.toml file. \
-            This is required for plugin functionality.",
-            repo_dir.display()
-        );
-    }
-
-    // Check for signature file (optional but recommended)
-    if !signature_file.exists() {
-        tracing::warn!(
-            "Plugin at {} has no signature file (.vortex-signature). \
-            Unsigned plugins may have been modified. Consider only using signed plugins \
-            from trusted sources.",
-            repo_dir.display()
-        );
-    }
-
-    // Check for metadata file (optional but recommended)
-    if !metadata_file.exists() {
-        tracing::info!(
-            "Plugin at {} has no metadata file (.vortex-metadata.json). \
-            This file can store checksums and other integrity information.",
-            repo_dir.display()
-        );
-    }
-
-    // Check that plugin.toml is not empty
-    if plugin_toml.exists() {
-        if let Ok(content) = std::fs::read_to_string(&plugin_toml) {
-            if content.trim().is_empty() {
-                tracing::warn!("Plugin plugin.toml file is empty.");
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// Read plugin metadata from plugin.toml in the repo root
-fn read_plugin_metadata(repo_dir: &Path) -> Result<(String, Option<String>, Option<String>)> {
-    let plugin_toml_path = repo_dir.join("plugin.toml");
-
-    if plugin_toml_path.exists() {
-        let content = std::fs::read_to_string(&plugin_toml_path).unwrap_or_default();
-        if let Ok(metadata) = toml::from_str::<PluginMetadata>(&content) {
-            return Ok((
-                metadata.version.unwrap_or_else(|| "0.1.0".to_string()),
-                metadata.description,
-                metadata.author,
-            ));
-        }
-    }
-
-    // Default metadata if plugin.toml doesn't exist or is invalid
-    Ok((
-        "0.1.0".to_string(),
-        None,
-        None,
-    ))
-}
-
-/// Plugin metadata structure for plugin.toml
-#[derive(serde::Deserialize, Debug)]
-struct PluginMetadata {
-    version: Option<String>,
-    description: Option<String>,
-    author: Option<String>,
-}
-
-async fn remove_plugin(_vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
-    let mut config = VortexConfig::load()?;
-
-    if config.remove_plugin(name).is_some() {
-        config.save()?;
-        println!("Plugin '{}' removed successfully!", name);
-    } else {
-        return Err(anyhow::anyhow!("Plugin '{}' not found", name));
-    }
-
-    Ok(())
-}
-
-async fn enable_plugin(_vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
-    let mut config = VortexConfig::load()?;
-
-    if config.enable_plugin(name) {
-        config.save()?;
-        println!("Plugin '{}' enabled!", name);
-    } else {
-        return Err(anyhow::anyhow!("Plugin '{}' not found", name));
-    }
-
-    Ok(())
-}
-
-async fn disable_plugin(_vortex: &Arc<VortexCore>, name: &str) -> Result<()> {
-    let mut config = VortexConfig::load()?;
-
-    if config.disable_plugin(name) {
-        config.save()?;
-        println!("Plugin '{}' disabled!", name);
-    } else {
-        return Err(anyhow::anyhow!("Plugin '{}' not found", name));
-    }
-
-    Ok(())
-}
-
-async fn show_templates() -> Result<()> {
-    let config = VortexConfig::load()?;
-
-    println!("Available Templates:");
-    for (name, template) in &config.templates {
-        println!("  {} - {} ({})", name, template.description, template.image);
-        if !template.ports.is_empty() {
-            println!("    Ports: {}", template.ports.join(", "));
-        }
-        if template.memory != 512 || template.cpus != 1 {
-            println!(
-                "    Resources: {}MB RAM, {} CPU(s)",
-                template.memory, template.cpus
-            );
-        }
-        println!();
-    }
-
-    println!("\nImage Aliases:");
-    for (alias, image) in &config.image_aliases {
-        println!("  {} -> {}", alias, image);
-    }
-
-    Ok(())
-}
-
-async fn show_metrics(vortex: &Arc<VortexCore>, vm_id: Option<&str>) -> Result<()> {
-    if let Some(vm_id) = vm_id {
-        // Get VM and collect real-time metrics
-        let vms = vortex.vm_manager.list().await?;
-        if let Some(vm) = vms.iter().find(|v| v.id == vm_id) {
-            match vm.backend.get_metrics(vm).await {
-                Ok(metrics) => {
-                    println!("VM Metrics for {}:", vm_id);
-                    println!("  CPU Usage: {:.1}%", metrics.cpu_usage);
-                    println!(
-                        "  Memory: {:.1}MB / {:.1}MB",
-                        metrics.memory_usage as f64 / 1024.0 / 1024.0,
-                        metrics.memory_total as f64 / 1024.0 / 1024.0
-                    );
-                    println!(
-                        "  Disk Usage: {:.1}MB",
-                        metrics.disk_usage as f64 / 1024.0 / 1024.0
-                    );
-                    println!("  Network RX: {:.1}KB", metrics.network_rx as f64 / 1024.0);
-                    println!("  Network TX: {:.1}KB", metrics.network_tx as f64 / 1024.0);
-                    println!("  Uptime: {}s", metrics.uptime_seconds);
-                }
-                Err(e) => {
-                    println!("Failed to collect metrics for VM {}: {}", vm_id, e);
-                }
-            }
-        } else {
-            println!("VM {} not found", vm_id);
-        }
-    } else {
-        // Show system metrics based on all running VMs
-        let vms = vortex.vm_manager.list().await?;
-        let mut total_memory_allocated = 0u64;
-        let mut total_memory_used = 0u64;
-        let mut total_cpu_usage = 0.0f64;
-        let mut successful_metrics = 0;
-
-        for vm in &vms {
-            if let Ok(metrics) = vm.backend.get_metrics(vm).await {
-                total_memory_allocated += metrics.memory_total;
-                total_memory_used += metrics.memory_usage;
-                total_cpu_usage += metrics.cpu_usage;
-                successful_metrics += 1;
-            }
-        }
-
-        println!("System Metrics:");
-        println!("  Total VMs: {}", vms.len());
-        println!("  Running VMs: {}", vms.len());
-        println!("  Total CPU Usage: {:.1}%", total_cpu_usage);
-        println!(
-            "  Total Memory Used: {:.1}MB / {:.1}MB",
-            total_memory_used as f64 / 1024.0 / 1024.0,
-            total_memory_allocated as f64 / 1024.0 / 1024.0
-        );
-        if successful_metrics > 0 {
-            println!(
-                "  Average CPU per VM: {:.1}%",
-                total_cpu_usage / successful_metrics as f64
-            );
-        }
-    }
-
-    Ok(())
-}
-
-fn parse_port_mappings(ports: Vec<String>) -> Result<HashMap<u16, u16>> {
-    let mut mappings = HashMap::new();
-
-    for port in ports {
-        let parts: Vec<&str> = port.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow::anyhow!(
-                "Invalid port mapping format: {}. Use host:guest",
-                port
-            ));
-        }
-
-        let host_port: u16 = parts[0]
-            .parse()
-            .with_context(|| format!("Invalid host port: {}", parts[0]))?;
-        let guest_port: u16 = parts[1]
-            .parse()
-            .with_context(|| format!("Invalid guest port: {}", parts[1]))?;
-
-        // Prevent host_port == guest_port which could cause issues with some backends
-        if host_port == guest_port {
-            return Err(anyhow::anyhow!(
-                "Host port {} cannot be the same as guest port. This may cause port conflicts.",
-                host_port
-            ));
-        }
-
-        mappings.insert(host_port, guest_port);
-    }
-
-    Ok(mappings)
-}
-
-/// Helper function to validate and normalize a host path, preventing path traversal
-fn validate_host_path(path: &str) -> Result<std::path::PathBuf> {
-    let host_path = std::path::PathBuf::from(path);
-
-    // Check for path traversal attempts (basic first line of defense)
-    if path.contains("..") {
-        return Err(anyhow::anyhow!(
-            "Invalid path: path traversal detected. Relative paths with '..' are not allowed."
-        ));
-    }
-
-    // Normalize the path
-    let normalized = if path.starts_with('/') {
-        // Absolute path - check against known safe locations
-        std::fs::canonicalize(&host_path)
-            .map_err(|e| anyhow::anyhow!("Cannot access host path '{}': {}", path, e))?
-    } else {
-        // Relative path - join with current directory
-        std::env::current_dir()
-            .map_err(|e| anyhow::anyhow!("Cannot get current directory: {}", e))?
-            .join(&host_path)
-    };
-
-    // Check if path exists
-    if !normalized.exists() {
-        return Err(anyhow::anyhow!(
-            "Host path does not exist: {}",
-            normalized.display()
-        ));
-    }
-
-    // Explicitly deny sensitive system directories (even if they happen to be inside allowed locations)
-    let normalized_str = normalized.to_string_lossy();
-    let sensitive_patterns = [
-        "/etc/", "/etc$",          // Configuration files
-        "/proc/", "/proc$",        // Process info
-        "/sys/", "/sys$",          // System info
-        "/root/", "/root$",        // Root user home
-        "/.ssh/", "/.ssh$",        // SSH keys
-        "/.gnupg/", "/.gnupg$",    // GPG keys
-        "/.kube/", "/.kube$",      // Kubernetes config
-        "/.config/google/", "/.config/google$",
-        "/.aws/", "/.aws$",        // AWS credentials
-        "/.azure/", "/.azure$",    // Azure credentials
-        "/.kube/", "/.kube$",      // Kubernetes config
-    ];
-
-    for pattern in &sensitive_patterns {
-        if normalized_str.starts_with(pattern.trim_end_matches('$'))
-            || normalized_str == pattern.trim_end_matches('$')
-        {
-            let suffix = if pattern.ends_with('$') {
-                " or its exact path"
-            } else {
-                ""
-            };
-            return Err(anyhow::anyhow!(
-                "Cannot mount {} paths for security reasons.{} Path: {}",
-                pattern.trim_end_matches('$'),
-                suffix,
-                normalized.display()
-            ));
-        }
-    }
-
-    // Prevent mounting sensitive system directories
-    // Only allow paths under user's home directory or the current working directory
-    let home_dir =
-        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
-    let canonical_home = home_dir
-        .canonicalize()
-        .map_err(|e| anyhow::anyhow!("Invalid home directory: {}", e))?;
-
-    let current_dir = std::env::current_dir()
-        .map_err(|e| anyhow::anyhow!("Cannot get current directory: {}", e))?;
-    let canonical_cwd = current_dir
-        .canonicalize()
-        .map_err(|e| anyhow::anyhow!("Invalid current directory: {}", e))?;
-
-    // Check if path is within allowed boundaries
-    if !normalized.starts_with(&canonical_home) && !normalized.starts_with(&canonical_cwd) {
-        return Err(anyhow::anyhow!(
-            "Cannot mount paths outside of home directory ({}) or current directory ({}). Path: {}",
-            canonical_home.display(),
-            canonical_cwd.display(),
-            normalized.display()
-        ));
-    }
-
-    // Final verification: ensure canonicalized path is still under allowed boundaries
-    // (prevents symlink-based bypasses)
-    for parent in normalized.ancestors() {
-        if parent == canonical_home || parent == canonical_cwd {
-            break;
-        }
-        // Check if any parent is a sensitive directory
-        let parent_str = parent.to_string_lossy();
-        for pattern in &sensitive_patterns {
-            if parent_str.starts_with(pattern.trim_end_matches('$')) {
-                return Err(anyhow::anyhow!(
-                    "Cannot mount path inside sensitive directory: {}",
-                    parent.display()
-                ));
-            }
-        }
-    }
-
-    Ok(normalized)
-}
-
-fn parse_volume_mappings(volumes: Vec<String>) -> Result<HashMap<PathBuf, PathBuf>> {
-    let mut mappings = HashMap::new();
-
-    for volume in volumes {
-        let parts: Vec<&str> = volume.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow::anyhow!(
-                "Invalid volume mapping format: {}. Use host:guest",
-                volume
-            ));
-        }
-
-        // Validate host path (prevents path traversal and forbidden directories)
-        let host_path = validate_host_path(parts[0])?;
-
-        // Guest path - just validate it's not empty and doesn't contain path traversal
-        let guest_path_str = parts[1];
-        if guest_path_str.is_empty() {
-            return Err(anyhow::anyhow!("Guest path cannot be empty"));
-        }
-        if guest_path_str.contains("..") {
-            return Err(anyhow::anyhow!(
-                "Invalid guest path: path traversal detected"
-            ));
-        }
-        let guest_path = std::path::PathBuf::from(guest_path_str);
-
-        mappings.insert(host_path, guest_path);
-    }
-
-    Ok(mappings)
-}
-
-/// Validate that a label key matches allowed patterns (alphanumeric, hyphens, underscores, dots)
-fn validate_label_key(key: &str) -> Result<()> {
-    // Label keys must match Kubernetes label key format: [A-Za-z0-9]([-A-Za-z0-9_.]*)?[A-Za-z0-9]
-    if key.is_empty() {
-        return Err(anyhow::anyhow!("Label key cannot be empty"));
-    }
-    if key.len() > 63 {
-        return Err(anyhow::anyhow!("Label key cannot exceed 63 characters"));
-    }
-
-    // Check first character
-    let first = key
-        .chars()
-        .next()
-        .expect("non-empty string must have first char");
-    if !first.is_ascii_alphanumeric() && first != '.' && first != '-' {
-        return Err(anyhow::anyhow!(
-            "Label key must start with alphanumeric, dot, or hyphen"
-        ));
-    }
-
-    for c in key.chars().skip(1) {
-        if !c.is_ascii_alphanumeric() && c != '-' && c != '_' && c != '.' {
-            return Err(anyhow::anyhow!(
-                "Label key contains invalid character: '{}'",
-                c
-            ));
-        }
-    }
-
-    Ok(())
-}
-
-/// Get the cache directory from HOME environment variable
-/// Returns an error if HOME is not set (prevents insecure /tmp fallback)
-fn get_cache_dir() -> Result<std::path::PathBuf> {
-    let home = std::env::var("HOME").map_err(|_| {
-        anyhow::anyhow!(
-            "HOME environment variable is not set. \
-            This is required to determine the vortex cache directory. \
-            Please set HOME to a valid user home directory path."
-        )
-    })?;
-
-    let cache_dir = std::path::Path::new(&home).join(".vortex").join("cache");
-
-    // Verify the path is not pointing to /tmp or other insecure locations
-    let canonical = std::fs::canonicalize(&cache_dir).map_err(|e| {
-        anyhow::anyhow!("Failed to resolve cache directory path: {}", e)
-    })?;
-
-    // Deny cache directory in /tmp or other world-writable locations
-    let canonical_str = canonical.to_string_lossy();
-    if canonical_str.starts_with("/tmp/") || canonical_str.starts_with("/var/tmp/") {
-        return Err(anyhow::anyhow!(
-            "Cache directory cannot be in /tmp or /var/tmp for security reasons. \
-            Please set HOME to a secure location."
-        ));
-    }
-
-    Ok(cache_dir)
-}
-
-fn parse_labels(labels: Vec<String>) -> Result<HashMap<String, String>> {
-    let mut mappings = HashMap::new();
-
-    for label in labels {
-        let parts: Vec<&str> = label.splitn(2, '=').collect();
-        if parts.len() != 2 {
-            return Err(anyhow::anyhow!(
-                "Invalid label format: {}. Use key=value",
-                label
-            ));
-        }
-
-        // Validate label key
-        validate_label_key(parts[0])?;
-
-        mappings.insert(parts[0].to_string(), parts[1].to_string());
-    }
-
-    Ok(mappings)
-}
-
-fn parse_copy_mappings(copy_to: Vec<String>) -> Result<Vec<(PathBuf, PathBuf)>> {
-    let mut mappings = Vec::new();
-
-    for copy_spec in copy_to {
-        let parts: Vec<&str> = copy_spec.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow::anyhow!(
-                "Invalid copy-to format: {}. Use host_dir:guest_dir",
-                copy_spec
-            ));
-        }
-
-        // Validate host path (prevents path traversal and forbidden directories)
-        let host_path = validate_host_path(parts[0])?;
-
-        let guest_path = PathBuf::from(parts[1]);
-
-        // Verify host path exists and is a directory
-        if !host_path.is_dir() {
-            return Err(anyhow::anyhow!(
-                "Host path is not a directory: {}",
-                host_path.display()
-            ));
-        }
-
-        // Validate guest path
-        if guest_path.to_string_lossy().contains("..") {
-            return Err(anyhow::anyhow!(
-                "Invalid guest path: path traversal detected"
-            ));
-        }
-
-        mappings.push((host_path, guest_path));
-    }
-
-    Ok(mappings)
-}
-
-fn parse_sync_back_mappings(sync_back: Vec<String>) -> Result<Vec<(PathBuf, PathBuf)>> {
-    let mut mappings = Vec::new();
-
-    for sync_spec in sync_back {
-        let parts: Vec<&str> = sync_spec.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow::anyhow!(
-                "Invalid sync-back format: {}. Use guest_dir:host_dir",
-                sync_spec
-            ));
-        }
-
-        // Guest path - validate it's not empty and doesn't contain path traversal
-        let guest_path_str = parts[0];
-        if guest_path_str.is_empty() {
-            return Err(anyhow::anyhow!("Guest path cannot be empty"));
-        }
-        if guest_path_str.contains("..") {
-            return Err(anyhow::anyhow!(
-                "Invalid guest path: path traversal detected"
-            ));
-        }
-        let guest_path = std::path::PathBuf::from(guest_path_str);
-
-        // Validate host path (prevents path traversal and forbidden directories)
-        let host_path = validate_host_path(parts[1])?;
-
-        // Create host directory if it doesn't exist
-        if let Some(parent) = host_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        mappings.push((guest_path, host_path));
-    }
-
-    Ok(mappings)
-}
-
-async fn run_parallel_vms(
-    vortex: &Arc<VortexCore>,
-    images: Vec<String>,
-    command: String,
-    quiet: bool,
-    copy_to: Vec<String>,
-    sync_back: Vec<String>,
-) -> Result<()> {
-    use tokio::time::Instant;
-
-    let start_time = Instant::now();
-
-    if !quiet {
-        println!(
-            "🚀 Launching {} VMs in parallel (try this with Docker!)",
-            images.len()
-        );
-    }
-
-    // Get resource limits to enforce concurrent VM cap
-    let config = VortexConfig::load()?;
-    let max_concurrent = config.get_resource_limits().max_concurrent_vms as usize;
-
-    // Create a semaphore to limit concurrent VM creation
-    let semaphore = Arc::new(Semaphore::new(max_concurrent));
-
-    if images.len() > max_concurrent && !quiet {
-        println!(
-            "⚠️  Limiting parallel execution to {} VMs (configured max_concurrent_vms)",
-            max_concurrent
-        );
-    }
-
-    // Create futures for all VMs with concurrency limiting
-    let mut tasks = Vec::new();
-
-    for (i, image) in images.into_iter().enumerate() {
-        let vortex_clone = Arc::clone(vortex);
-        let command = command.clone();
-        let copy_to = copy_to.clone();
-        let sync_back = sync_back.clone();
-        let semaphore = Arc::clone(&semaphore);
-        let config = config.clone();
-
-        let task = async move {
-            // Acquire semaphore permit (blocks if at capacity)
-            let _permit = semaphore.acquire().await;
-
-            let resolved_image = config.resolve_image(&image);
-
-            // Create unique sync paths for each VM
-            let mut unique_sync_back = Vec::new();
-            for sync_spec in &sync_back {
-                let parts: Vec<&str> = sync_spec.split(':').collect();
-                if parts.len() == 2 {
-                    let guest_path = parts[0];
-                    let host_path = format!("{}_vm_{}", parts[1], i);
-                    unique_sync_back.push(format!("{}:{}", guest_path, host_path));
-                }
-            }
-
-            let spec = VmSpec {
-                image: resolved_image.clone(),
-                memory: 512,
-                cpus: 1,
-                ports: HashMap::new(),
-                volumes: HashMap::new(),
-                environment: HashMap::new(),
-                command: Some(command),
-                labels: HashMap::new(),
-                network_config: None,
-                resource_limits: ResourceLimits::default(),
-                backend: None,
-            };
-
-            let vm_start = Instant::now();
-            run_vm(
-                &vortex_clone,
-                spec,
-                false,
-                true,
-                false,
-                copy_to,
-                unique_sync_back,
-                None,
-                false,
-            )
-            .await?;
-            let vm_duration = vm_start.elapsed();
-
-            Ok::<(String, std::time::Duration), anyhow::Error>((resolved_image, vm_duration))
-        };
-
-        tasks.push(tokio::spawn(task));
-    }
-
-    // Collect results from all tasks
-    let mut results = Vec::new();
-    for task in tasks {
-        match task.await {
-            Ok(Ok(result)) => results.push(result),
-            Ok(Err(e)) => return Err(e),
-            Err(e) => return Err(anyhow::anyhow!("Task panicked: {}", e)),
-        }
-    }
-
-
-    let total_duration = start_time.elapsed();
-
-    if !quiet {
-        println!("\n🎯 Parallel Execution Results:");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        for (image, duration) in &results {
-            println!("  {} - completed in {:.2}s", image, duration.as_secs_f64());
-        }
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!(
-            "🚀 Total time: {:.2}s ({} VMs in parallel)",
-            total_duration.as_secs_f64(),
-            results.len()
-        );
-        println!(
-            "⚡ Docker would take {}x longer running these sequentially!",
-            results.len()
-        );
-    }
-
-    Ok(())
-}
-
-async fn monitor_vm_performance(vortex: &Arc<VortexCore>, vm_id: &str) {
-    use tokio::time::{sleep, Duration};
-
-    println!(
-        "
-🎯 Real-time Performance Monitor (Docker can't do this!)"
-    );
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-
-    for i in 1..=10 {
-        sleep(Duration::from_secs(1)).await;
-
-        if let Ok(vms) = vortex.vm_manager.list().await {
-            if let Some(vm) = vms.iter().find(|v| v.id == vm_id) {
-                if let Ok(metrics) = vm.backend.get_metrics(vm).await {
-                    print!(
-                        "\r[{}s] CPU: {:.1}% | RAM: {:.0}MB/{:.0}MB | Disk: {:.0}MB",
-                        i,
-                        metrics.cpu_usage,
-                        metrics.memory_usage as f64 / 1024.0 / 1024.0,
-                        metrics.memory_total as f64 / 1024.0 / 1024.0,
-                        metrics.disk_usage as f64 / 1024.0 / 1024.0
-                    );
-
-                    use std::io::{self, Write};
-                    if let Err(e) = io::stdout().flush() {
-                        eprintln!("Warning: Failed to flush stdout: {}", e);
-                    }
-                }
-            }
-        }
-    }
-
-    println!(
-        "
-━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
-    );
-}
-
-async fn show_dev_templates(vortex: &Arc<VortexCore>) -> Result<()> {
-    let templates = vortex.dev_env_manager.list_templates();
-
-    println!("🔥 Available Dev Environment Templates:");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-
-    for template in templates {
-        println!("📦 {} - {}", template.name, template.description);
-        println!("   Base: {}", template.base_image);
-        println!("   Tools: {}", template.tools.join(", "));
-        if !template.ports.is_empty() {
-            println!("   Ports: {}", template.ports.join(", "));
-        }
-        if !template.extensions.is_empty() {
-            println!("   IDE Extensions: {}", template.extensions.join(", "));
-        }
-        println!();
-    }
-
-    println!("💡 Usage: vortex dev <template> [options]");
-    println!("📖 Example: vortex dev python --workdir /workspace --volume ./src:/workspace/src");
-
-    Ok(())
-}
-
-#[allow(clippy::too_many_arguments)]
-async fn start_dev_environment(
-    vortex: &Arc<VortexCore>,
-    template_name: &str,
-    workdir: Option<String>,
-    volumes: Vec<String>,
-    ports: Vec<String>,
-    quiet: bool,
-    name: Option<String>,
-    detach: bool,
-) -> Result<()> {
-    // Parse volume and port mappings
-    let volume_mappings = parse_volume_mappings(volumes)?;
-    let _port_mappings = parse_port_mappings(ports)?;
-
-    // Create the dev environment VM with optional custom name
-    let mut vm = vortex
-        .create_dev_environment(template_name, workdir.clone(), volume_mappings)
-        .await?;
-
-    // If a name is provided, update the VM ID to be more user-friendly
-    if let Some(session_name) = &name {
-        let new_id = format!("vortex-{}", session_name);
-        vm.id = new_id;
-    }
-
-    if !quiet {
-        let template = vortex
-            .dev_env_manager
-            .get_template(template_name)
-            .ok_or_else(|| anyhow::anyhow!("Template '{}' not found", template_name))?;
-
-        println!();
-        println!("🚀 Dev Environment Ready!");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!("📦 Template: {} ({})", template.name, template.description);
-        println!("🐳 Base: {}", template.base_image);
-        println!("🔧 Tools: {}", template.tools.join(", "));
-        if !template.ports.is_empty() {
-            println!("🌐 Ports: {}", template.ports.join(", "));
-        }
-        if !vm.spec.volumes.is_empty() {
-            println!("📂 Volumes: {} mount(s)", vm.spec.volumes.len());
-        }
-        println!("💾 VM ID: {}", vm.id);
-
-        if let Some(session_name) = &name {
-            println!("📝 Session: {}", session_name);
-        }
-
-        if detach {
-            println!("🔓 Running in background (detached mode)");
-            println!("💡 Attach with: vortex attach {}", vm.id);
-        } else {
-            println!();
-            println!("⚡ Lightning-fast setup complete! (Docker would still be pulling images)");
-            println!("💬 Connecting to interactive shell...");
-        }
-        println!();
-    }
-
-    if detach {
-        // For detached mode, just leave the VM running
-        if !quiet {
-            println!("✅ Background session '{}' started", vm.id);
-            println!("🔗 Attach anytime with: vortex attach {}", vm.id);
-        }
-    } else {
-        // Attach to the VM for interactive development
-        vortex.attach_vm(&vm.id).await?;
-
-        // Cleanup when done (only for non-detached sessions)
-        if !quiet {
-            println!("\n🧹 Cleaning up dev environment...");
-        }
-        vortex.vm_manager.cleanup(&vm.id).await?;
-
-        if !quiet {
-            println!("✅ Dev session complete!");
-        }
-    }
-
-    Ok(())
-}
-
-// Workspace management functions
-
-async fn init_workspace_from_current_dir(vortex: &Arc<VortexCore>) -> Result<()> {
-    let current_dir = std::env::current_dir()?;
-
-    if let Some(info) = detect_workspace_info(&current_dir) {
-        println!("🔍 Detected project in current directory:");
-        println!("   Name: {}", info.name);
-        println!("   Suggested template: {}", info.suggested_template);
-
-        if info.has_devcontainer {
-            println!("   📦 DevContainer detected!");
-            println!();
-            println!("Would you like to:");
-            println!("  1. Import from devcontainer.json (recommended)");
-            println!("  2. Create standard Vortex workspace");
-            println!();
-
-            // For now, just import the devcontainer
-            if let Some(devcontainer_path) = &info.devcontainer_path {
-                let workspace = vortex.workspace_manager.create_from_devcontainer(
-                    &info.name,
-                    devcontainer_path,
-                    &current_dir,
-                )?;
-
-                println!(
-                    "✅ Workspace '{}' created from devcontainer!",
-                    workspace.name
-                );
-                println!("🚀 Run: vortex dev --workspace {}", workspace.name);
-            }
-        } else {
-            let workspace = vortex.workspace_manager.create_workspace(
-                &info.name,
-                &info.suggested_template,
-                Some(&current_dir),
-            )?;
-
-            println!("✅ Workspace '{}' created!", workspace.name);
-            println!("🚀 Run: vortex dev --workspace {}", workspace.name);
-        }
-    } else {
-        return Err(anyhow::anyhow!(
-            "Could not detect project type in current directory"
-        ));
-    }
-
-    Ok(())
-}
-
-async fn start_workspace(
-    vortex: &Arc<VortexCore>,
-    workspace_name: &str,
-    quiet: bool,
-) -> Result<()> {
-    // Try to find workspace by name first, then by ID
-    let workspace = vortex
-        .workspace_manager
-        .find_workspace_by_name(workspace_name)?
-        .or_else(|| {
-            vortex
-                .workspace_manager
-                .get_workspace(workspace_name)
-                .unwrap_or(None)
-        })
-        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
-
-    if !quiet {
-        println!();
-        println!("🔄 Launching workspace '{}'...", workspace.name);
-        println!("📁 Path: {}", workspace.path.display());
-        println!("🎯 Template: {}", workspace.config.template);
-
-        if let Some(devcontainer) = &workspace.config.devcontainer_source {
-            println!("📦 DevContainer: {}", devcontainer);
-        }
-
-        println!(
-            "⏰ Last used: {}",
-            workspace.config.last_used.format("%Y-%m-%d %H:%M")
-        );
-        println!();
-    }
-
-    // Create and start VM from workspace
-    let vm = vortex.create_workspace_vm(&workspace.id).await?;
-
-    if !quiet {
-        println!("⚡ Workspace VM ready!");
-        println!("💬 Connecting to interactive session...");
-        println!();
-    }
-
-    // Attach to the VM
-    vortex.attach_vm(&vm.id).await?;
-
-    // Cleanup when done
-    if !quiet {
-        println!("\n🧹 Cleaning up workspace VM...");
-    }
-    vortex.vm_manager.cleanup(&vm.id).await?;
-
-    if !quiet {
-        println!("✅ Workspace session complete! Your work is safely stored.");
-    }
-
-    Ok(())
-}
-
-async fn list_workspaces(vortex: &Arc<VortexCore>) -> Result<()> {
-    let workspaces = vortex.workspace_manager.list_workspaces()?;
-
-    if workspaces.is_empty() {
-        println!("No workspaces found.");
-        println!("💡 Create one with: vortex workspace create <name> --template <template>");
-        println!("💡 Or initialize from current dir: vortex dev --init");
-        return Ok(());
-    }
-
-    println!("🔥 Persistent Workspaces:");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-
-    for workspace in workspaces {
-        println!("📁 {} ({})", workspace.name, &workspace.id[..8]);
-        println!("   Template: {}", workspace.config.template);
-        println!("   Path: {}", workspace.path.display());
-        println!(
-            "   Last used: {}",
-            workspace.config.last_used.format("%Y-%m-%d %H:%M")
-        );
-
-        if let Some(devcontainer) = &workspace.config.devcontainer_source {
-            println!("   📦 DevContainer: {}", devcontainer);
-        }
-
-        if !workspace.config.port_forwards.is_empty() {
-            println!(
-                "   🌐 Ports: {}",
-                workspace
-                    .config
-                    .port_forwards
-                    .iter()
-                    .map(|p| p.to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            );
-        }
-
-        println!();
-    }
-
-    println!("💡 Start workspace: vortex dev --workspace <name>");
-    println!("📖 More info: vortex workspace info <name>");
-
-    Ok(())
-}
-
-async fn create_workspace(
-    vortex: &Arc<VortexCore>,
-    name: &str,
-    template: &str,
-    source: &Option<PathBuf>,
-    backend: &str,
-) -> Result<()> {
-    let source_dir = source
-        .as_ref()
-        .map(|p| p.as_path())
-        .unwrap_or_else(|| std::path::Path::new("."));
-
-    // Verify template exists
-    if vortex.dev_env_manager.get_template(template).is_none() {
-        return Err(anyhow::anyhow!("Template '{}' not found", template));
-    }
-
-    // For now, we'll store the backend in the workspace config
-    // The backend field is stored in VortexWorkspaceConfig
-    let workspace = vortex
-        .workspace_manager
-        .create_workspace(name, template, Some(source_dir))?;
-
-    // Store backend preference in workspace config
-    if let Some(mut config) = vortex.workspace_manager.get_workspace(&workspace.id)? {
-        config.config.backend = Some(backend.to_string());
-        vortex
-            .workspace_manager
-            .save_workspace_config(&workspace.id, &config.config)?;
-    }
-
-    println!("✅ Workspace '{}' created!", workspace.name);
-    println!("📁 Path: {}", workspace.path.display());
-    println!("🎯 Template: {}", workspace.config.template);
-    println!("⚙️  Backend: {}", backend);
-    println!("🚀 Start with: vortex dev --workspace {}", workspace.name);
-
-    Ok(())
-}
-
-async fn delete_workspace(vortex: &Arc<VortexCore>, workspace_name: &String) -> Result<()> {
-    // Find workspace by name or ID
-    let workspace = vortex
-        .workspace_manager
-        .find_workspace_by_name(workspace_name)?
-        .or_else(|| {
-            vortex
-                .workspace_manager
-                .get_workspace(workspace_name)
-                .unwrap_or(None)
-        })
-        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
-
-    println!(
-        "⚠️  This will permanently delete workspace '{}'",
-        workspace.name
-    );
-    println!("📁 Path: {}", workspace.path.display());
-    println!();
-    println!("Are you sure? [y/N]: ");
-
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-
-    if input.trim().to_lowercase() == "y" {
-        vortex.workspace_manager.delete_workspace(&workspace.id)?;
-        println!("🗑️  Workspace '{}' deleted", workspace.name);
-    } else {
-        println!("❌ Cancelled");
-    }
-
-    Ok(())
-}
-
-async fn show_workspace_info(vortex: &Arc<VortexCore>, workspace_name: &String) -> Result<()> {
-    let workspace = vortex
-        .workspace_manager
-        .find_workspace_by_name(workspace_name)?
-        .or_else(|| {
-            vortex
-                .workspace_manager
-                .get_workspace(workspace_name)
-                .unwrap_or(None)
-        })
-        .ok_or_else(|| anyhow::anyhow!("Workspace '{}' not found", workspace_name))?;
-
-    println!("🔍 Workspace Details:");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📁 Name: {}", workspace.name);
-    println!("🆔 ID: {}", workspace.id);
-    println!("🎯 Template: {}", workspace.config.template);
-    println!("📂 Path: {}", workspace.path.display());
-    println!(
-        "📅 Created: {}",
-        workspace.config.created_at.format("%Y-%m-%d %H:%M")
-    );
-    println!(
-        "⏰ Last used: {}",
-        workspace.config.last_used.format("%Y-%m-%d %H:%M")
-    );
-    println!(
-        "📁 Working directory: {}",
-        workspace.config.preferred_workdir
-    );
-
-    if let Some(devcontainer) = &workspace.config.devcontainer_source {
-        println!("📦 DevContainer source: {}", devcontainer);
-    }
-
-    if !workspace.config.port_forwards.is_empty() {
-        println!(
-            "🌐 Port forwards: {}",
-            workspace
-                .config
-                .port_forwards
-                .iter()
-                .map(|p| p.to_string())
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-    }
-
-    if !workspace.config.environment_vars.is_empty() {
-        println!("🌍 Environment variables:");
-        for (key, value) in &workspace.config.environment_vars {
-            println!("   {}={}", key, value);
-        }
-    }
-
-    if !workspace.config.custom_commands.is_empty() {
-        println!("⚙️  Custom commands:");
-        for cmd in &workspace.config.custom_commands {
-            println!("   {}", cmd);
-        }
-    }
-
-    // Show directory contents
-    if workspace.path.exists() {
-        println!("\n📋 Workspace contents:");
-        match std::fs::read_dir(&workspace.path) {
-            Ok(entries) => {
-                let mut files: Vec<_> = entries.collect::<std::io::Result<Vec<_>>>()?;
-                files.sort_by_key(|e| e.file_name());
-
-                for entry in files.iter().take(10) {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    if entry.file_type()?.is_dir() {
-                        println!("   📁 {}/", name);
-                    } else {
-                        println!("   📄 {}", name);
-                    }
-                }
-
-                if files.len() > 10 {
-                    println!("   ... and {} more files", files.len() - 10);
-                }
-            }
-            Err(e) => println!("   Error reading directory: {}", e),
-        }
-    }
-
-    println!();
-    println!(
-        "🚀 Start workspace: vortex dev --workspace {}",
-        workspace.name
-    );
-
-    Ok(())
-}
-
-async fn import_devcontainer_workspace(
-    vortex: &Arc<VortexCore>,
-    name: &str,
-    devcontainer_path: &Path,
-    source: &Option<PathBuf>,
-    backend: &str,
-) -> Result<()> {
-    let source_dir = source
-        .as_ref()
-        .map(|p| p.as_path())
-        .unwrap_or_else(|| std::path::Path::new("."));
-
-    if !devcontainer_path.exists() {
-        return Err(anyhow::anyhow!(
-            "DevContainer file not found: {}",
-            devcontainer_path.display()
-        ));
-    }
-
-    let workspace =
-        vortex
-            .workspace_manager
-            .create_from_devcontainer(name, devcontainer_path, source_dir)?;
-
-    // Store backend preference in workspace config
-    if let Some(mut config) = vortex.workspace_manager.get_workspace(&workspace.id)? {
-        config.config.backend = Some(backend.to_string());
-        vortex
-            .workspace_manager
-            .save_workspace_config(&workspace.id, &config.config)?;
-    }
-
-    println!(
-        "✅ Workspace '{}' imported from devcontainer!",
-        workspace.name
-    );
-    println!("📦 DevContainer: {}", devcontainer_path.display());
-    println!("📁 Path: {}", workspace.path.display());
-    println!("🎯 Detected template: {}", workspace.config.template);
-    println!("⚙️  Backend: {}", backend);
-
-    if !workspace.config.port_forwards.is_empty() {
-        println!(
-            "🌐 Port forwards: {}",
-            workspace
-                .config
-                .port_forwards
-                .iter()
-                .map(|p| p.to_string())
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-    }
-
-    println!("🚀 Start with: vortex dev --workspace {}", workspace.name);
-
-    Ok(())
-}
-
-// Workspace initialization with interactive discovery
-
-async fn handle_workspace_init(
-    directory: &Path,
-    output: &Path,
-    non_interactive: bool,
-    backend: &str,
-) -> Result<()> {
-    println!();
-    println!("🔍 Scanning project directory: {}", directory.display());
-
-    // Auto-detect project structure using the core workspace detection
-    let workspace_info = detect_workspace_info(directory);
-
-    if let Some(info) = workspace_info {
-        if non_interactive {
-            // Non-interactive mode: Generate config directly
-            println!("✅ Detected project: {}", info.name);
-            println!("   Template: {}", info.suggested_template);
-            println!("   Backend: {}", backend);
-
-            let vortex_config_path = output;
-            generate_vortex_yaml_from_info(&info, vortex_config_path, backend)?;
-
-            println!(
-                "✅ Configuration saved to: {}",
-                vortex_config_path.display()
-            );
-            println!(
-                "🚀 Run: vortex workspace create <name> --template <template> --source {}",
-                directory.display()
-            );
-        } else {
-            // Interactive mode: Ask user questions
-            interactive_workspace_init(&info, directory, output, backend).await?;
-        }
-    } else {
-        // No project detected - use interactive mode
-        interactive_workspace_init(
-            &default_workspace_info(directory),
-            directory,
-            output,
-            backend,
-        )
-        .await?;
-    }
-
-    Ok(())
-}
-
-fn default_workspace_info(directory: &Path) -> WorkspaceInfo {
-    WorkspaceInfo {
-        name: directory
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "my-project".to_string()),
-        suggested_template: "python".to_string(),
-        has_devcontainer: false,
-        devcontainer_path: None,
-    }
-}
-
-fn generate_vortex_yaml_from_info(
-    info: &WorkspaceInfo,
-    output_path: &Path,
-    backend: &str,
-) -> Result<()> {
-    // Simple YAML generation for now
-    let yaml = format!(
-        r#"name: {}
-description: Auto-generated workspace for {}
-backend: {}
-
-services:
-  default:
-    type: backend
-    language: python
-    image: python:3.11-slim
-    ports:
-      - 8000:8000
-"#,
-        info.name, info.name, backend
-    );
-
-    std::fs::write(output_path, yaml)?;
-    Ok(())
-}
-
-async fn interactive_workspace_init(
-    info: &WorkspaceInfo,
-    _directory: &Path,
-    _output: &Path,
-    backend: &str,
-) -> Result<()> {
-    // Interactive mode - ask user questions
-    println!("✅ Detected project: {}", info.name);
-    println!("   Template: {}", info.suggested_template);
-    println!("   Backend: {}", backend);
-    println!();
-
-    // In a real implementation, we'd ask user questions here
-    // For now, just generate the config
-    let output = Path::new("vortex.yaml");
-    generate_vortex_yaml_from_info(info, output, backend)?;
-
-    println!("✅ Configuration saved to: vortex.yaml");
-    println!("🚀 Run: vortex workspace create <name> --template <template>");
-
-    Ok(())
-}
-
-// Session management handlers
-
-#[allow(clippy::too_many_arguments)]
-async fn handle_session_create(
-    vortex: &Arc<VortexCore>,
-    template: &str,
-    name: Option<String>,
-    memory: u32,
-    cpus: u32,
-    ports: &[String],
-    volumes: &[String],
-    detach: bool,
-    persistent: bool,
-) -> Result<()> {
-    // Ensure daemon is running
-    DaemonClient::start_daemon_if_needed().await?;
-
-    // Convert template to VmSpec
-    let mut spec = vortex.dev_env_manager.template_to_vm_spec(template, None)?;
-
-    // Override with user preferences
-    spec.memory = memory;
-    spec.cpus = cpus;
-    spec.ports = parse_port_mappings(ports.to_vec())?;
-
-    // Merge volumes
-    let additional_volumes = parse_volume_mappings(volumes.to_vec())?;
-    for (host, guest) in additional_volumes {
-        spec.volumes.insert(host, guest);
-    }
-
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::CreateSession {
-            spec: Box::new(spec),
-            name: name.clone(),
-            persistent,
-            boot_start: false, // New sessions don't auto-start by default
-        })
-        .await?;
-
-    match response {
-        SessionResponse::SessionCreated { session } => {
-            println!("✅ Session created: {}", session.id);
-            if let Some(name) = &session.name {
-                println!("📝 Name: {}", name);
-            }
-            println!("🎯 Template: {}", template);
-            println!("🚀 State: {:?}", session.state);
-
-            if !detach {
-                println!("🔗 Attaching to session...");
-                handle_attach_session(&session.id).await?;
-            } else {
-                println!("💡 Attach later with: vortex attach {}", session.id);
-                if let Some(name) = &session.name {
-                    println!("💡 Or: vortex attach {}", name);
-                }
-            }
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to create session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_list() -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if !client.is_running().await {
-        println!("📴 Daemon is not running. Start it with: vortex daemon start");
-        return Ok(());
-    }
-
-    let response = client.send_command(SessionCommand::ListSessions).await?;
-
-    match response {
-        SessionResponse::SessionList { sessions } => {
-            if sessions.is_empty() {
-                println!("No active sessions found.");
-                println!("💡 Create one with: vortex session create <template>");
-                return Ok(());
-            }
-
-            println!("🔥 Active Sessions:");
-            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-
-            for session in sessions {
-                let state_emoji = match session.state {
-                    vortex::SessionState::Running => "🟢",
-                    vortex::SessionState::Detached => "🟡",
-                    vortex::SessionState::Attached { .. } => "🔵",
-                    vortex::SessionState::Stopped => "🔴",
-                    vortex::SessionState::Paused => "⏸️",
-                    vortex::SessionState::Error { .. } => "❌",
-                    _ => "⚪",
-                };
-
-                print!("{} {} ", state_emoji, &session.id[..12]);
-                if let Some(name) = &session.name {
-                    print!("({}) ", name);
-                }
-                println!("- {:?}", session.state);
-
-                println!("   🖼️  Image: {}", session.spec.image);
-                println!(
-                    "   💾 Resources: {}MB RAM, {} CPU(s)",
-                    session.spec.memory, session.spec.cpus
-                );
-
-                if let Some(last_attached) = session.last_attached {
-                    let duration = chrono::Utc::now() - last_attached;
-                    if duration.num_hours() > 0 {
-                        println!("   ⏰ Last attached: {} hours ago", duration.num_hours());
-                    } else {
-                        println!(
-                            "   ⏰ Last attached: {} minutes ago",
-                            duration.num_minutes()
-                        );
-                    }
-                }
-
-                if session.persistent {
-                    println!("   🔒 Persistent session");
-                }
-
-                println!();
-            }
-
-            println!("💡 Attach to session: vortex attach <session-id>");
-            println!("📖 Session details: vortex session info <session-id>");
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to list sessions: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_info(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::GetSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Session { session } => {
-            println!("🔍 Session Details:");
-            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-            println!("🆔 ID: {}", session.id);
-
-            if let Some(name) = &session.name {
-                println!("📝 Name: {}", name);
-            }
-
-            println!("🚀 State: {:?}", session.state);
-            println!("🖼️  Image: {}", session.spec.image);
-            println!(
-                "💾 Resources: {}MB RAM, {} CPU(s)",
-                session.spec.memory, session.spec.cpus
-            );
-            println!(
-                "📅 Created: {}",
-                session.created_at.format("%Y-%m-%d %H:%M:%S")
-            );
-
-            if let Some(last_attached) = session.last_attached {
-                println!(
-                    "⏰ Last attached: {}",
-                    last_attached.format("%Y-%m-%d %H:%M:%S")
-                );
-            }
-
-            if session.persistent {
-                println!("🔒 Persistent: Yes");
-            } else {
-                println!("🔒 Persistent: No");
-            }
-
-            if !session.spec.ports.is_empty() {
-                println!("🌐 Port forwards:");
-                for (host, guest) in &session.spec.ports {
-                    println!("   {}:{}", host, guest);
-                }
-            }
-
-            if !session.spec.volumes.is_empty() {
-                println!("📂 Volume mounts:");
-                for (host, guest) in &session.spec.volumes {
-                    println!("   {} → {}", host.display(), guest.display());
-                }
-            }
-
-            if !session.spec.labels.is_empty() {
-                println!("🏷️  Labels:");
-                for (key, value) in &session.spec.labels {
-                    println!("   {}={}", key, value);
-                }
-            }
-
-            match session.state {
-                vortex::SessionState::Detached | vortex::SessionState::Running => {
-                    println!("\n💡 Attach with: vortex attach {}", session.id);
-                }
-                vortex::SessionState::Stopped => {
-                    println!("\n💡 Start with: vortex session start {}", session.id);
-                }
-                vortex::SessionState::Paused => {
-                    println!("\n💡 Resume with: vortex session resume {}", session.id);
-                }
-                _ => {}
-            }
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to get session info: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_start(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::StartSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("✅ Session {} started", session_id);
-            println!("💡 Attach with: vortex attach {}", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to start session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_stop(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::StopSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("⏹️  Session {} stopped", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to stop session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_pause(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::PauseSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("⏸️  Session {} paused", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to pause session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_resume(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::ResumeSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("▶️  Session {} resumed", session_id);
-            println!("💡 Attach with: vortex attach {}", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to resume session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_restart(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::RestartSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("🔄 Session {} restarted", session_id);
-            println!("💡 Attach with: vortex attach {}", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to restart session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_delete(session_id: &str, force: bool) -> Result<()> {
-    if !force {
-        println!("⚠️  This will permanently delete session: {}", session_id);
-        println!("Are you sure? [y/N]: ");
-
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-
-        if input.trim().to_lowercase() != "y" {
-            println!("❌ Cancelled");
-            return Ok(());
-        }
-    }
-
-    let client = DaemonClient::new()?;
-    let response = client
-        .send_command(SessionCommand::DeleteSession {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("🗑️  Session {} deleted", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to delete session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_attach_session(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if !client.is_running().await {
-        return Err(anyhow::anyhow!(
-            "Daemon is not running. Start it with: vortex daemon start"
-        ));
-    }
-
-    let client_pid = std::process::id();
-    let response = client
-        .send_command(SessionCommand::AttachSession {
-            session_id: session_id.to_string(),
-            client_pid,
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("✅ Attached to session {}", session_id);
-            println!("🚪 Session detached");
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to attach to session: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_enable_autostart(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if !client.is_running().await {
-        return Err(anyhow::anyhow!(
-            "Daemon is not running. Start it with: vortex daemon start"
-        ));
-    }
-
-    let response = client
-        .send_command(SessionCommand::EnableBootStart {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("✅ Autostart enabled for session {}", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to enable autostart: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_session_disable_autostart(session_id: &str) -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if !client.is_running().await {
-        return Err(anyhow::anyhow!(
-            "Daemon is not running. Start it with: vortex daemon start"
-        ));
-    }
-
-    let response = client
-        .send_command(SessionCommand::DisableBootStart {
-            session_id: session_id.to_string(),
-        })
-        .await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("✅ Autostart disabled for session {}", session_id);
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to disable autostart: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-// Daemon management handlers
-
-async fn handle_daemon_start(background: bool) -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if client.is_running().await {
-        println!("📴 Daemon is already running");
-        return Ok(());
-    }
-
-    if background {
-        // Start daemon in background by spawning a new process
-        let current_exe = std::env::current_exe()?;
-        let child = std::process::Command::new(current_exe)
-            .arg("daemon")
-            .arg("start")
-            .spawn()?;
-
-        println!("🚀 Daemon starting in background (PID: {})", child.id());
-
-        // Wait a moment for it to start
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-
-        if client.is_running().await {
-            println!("✅ Daemon started successfully");
-        } else {
-            return Err(anyhow::anyhow!("Failed to start daemon"));
-        }
-    } else {
-        // Start daemon in foreground
-        println!("🚀 Starting Vortex daemon...");
-        let vortex = init().await?;
-        let daemon = VortexDaemon::new(vortex.session_manager).await?;
-
-        // Handle Ctrl+C gracefully
-        let daemon_ref = Arc::new(daemon);
-        let daemon_clone = daemon_ref.clone();
-
-        tokio::spawn(async move {
-            tokio::signal::ctrl_c()
-                .await
-                .expect("Failed to listen for Ctrl+C");
-            println!("\n🛑 Shutting down daemon...");
-            if let Err(e) = daemon_clone.stop().await {
-                eprintln!("Error stopping daemon: {}", e);
-            }
-        });
-
-        daemon_ref.start().await?;
-    }
-
-    Ok(())
-}
-
-async fn handle_daemon_stop() -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if !client.is_running().await {
-        println!("📴 Daemon is not running");
-        return Ok(());
-    }
-
-    let response = client.send_command(SessionCommand::Shutdown).await?;
-
-    match response {
-        SessionResponse::Success => {
-            println!("🛑 Daemon stopped");
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to stop daemon: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_daemon_status() -> Result<()> {
-    let client = DaemonClient::new()?;
-
-    if !client.is_running().await {
-        println!("📴 Daemon Status: Not Running");
-        println!("💡 Start with: vortex daemon start");
-        return Ok(());
-    }
-
-    let response = client.send_command(SessionCommand::GetDaemonStatus).await?;
-
-    match response {
-        SessionResponse::DaemonStatus {
-            uptime,
-            sessions_count,
-            active_vms,
-            memory_usage,
-        } => {
-            println!("🚀 Daemon Status: Running");
-            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-
-            let hours = uptime / 3600;
-            let minutes = (uptime % 3600) / 60;
-            let seconds = uptime % 60;
-
-            if hours > 0 {
-                println!("⏰ Uptime: {}h {}m {}s", hours, minutes, seconds);
-            } else if minutes > 0 {
-                println!("⏰ Uptime: {}m {}s", minutes, seconds);
-            } else {
-                println!("⏰ Uptime: {}s", seconds);
-            }
-
-            println!("📊 Sessions: {}", sessions_count);
-            println!("🔥 Active VMs: {}", active_vms);
-            println!(
-                "💾 Memory Usage: {:.1}MB",
-                memory_usage as f64 / 1024.0 / 1024.0
-            );
-
-            println!("\n💡 List sessions: vortex sessions");
-            println!("📖 Session details: vortex session info <id>");
-        }
-        SessionResponse::Error { message } => {
-            return Err(anyhow::anyhow!("Failed to get daemon status: {}", message));
-        }
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected response from daemon"));
-        }
-    }
-
-    Ok(())
-}
-
-async fn handle_daemon_restart() -> Result<()> {
-    println!("🔄 Restarting daemon...");
-
-    // Stop first
-    if DaemonClient::new()?.is_running().await {
-        handle_daemon_stop().await?;
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-    }
-
-    // Start again
-    handle_daemon_start(true).await?;
-
-    Ok(())
-}
-
-async fn handle_daemon_logs() -> Result<()> {
-    println!("📋 Daemon logs are currently only available in daemon output");
-    println!("💡 Start daemon in foreground to see logs: vortex daemon start");
-    Ok(())
-}
@@ -0,0 +1,501 @@
+//! The [`VortexCore`] orchestrator, wiring the backends detected on this host,
+//! plugins and the session manager together
+
+#[cfg(feature = "wasm-plugins")]
+use vortex_core::wasm_plugin;
+use futures::Stream;
+use std::path::Path;
+use std::time::Duration;
+use vortex_core::{
+    archive::ArchiveManifest,
+    audit::AuditLog,
+    auth::{self, AuthContext, TOKEN_ENV},
+    events,
+    ids::{SessionId, SnapshotId, VmId},
+    metrics::VmUsage,
+    oidc::OidcAuthProvider,
+    plugin,
+    snapshot::{self, SnapshotRecord, SnapshotStore},
+    AuthProvider, DevEnvironmentManager, DevOverrides, ExecOptions, ExecResult, ExitStatus,
+    MetricsCollector, NetworkManager, Permission, Plugin, PluginManager, Probe, Result,
+    StorageManager, TokenAuthProvider, VmInstance, VmManager, VmSpec, VmState, VortexConfig,
+    VortexError, WorkspaceManager,
+};
+use vortex_daemon::{SessionManager, VmSession, VortexDaemon};
+
+/// Initialize the Vortex core library
+pub async fn init() -> Result<VortexCore> {
+    VortexCore::new().await
+}
+
+/// Main Vortex core orchestrator
+pub struct VortexCore {
+    /// Reached only through the methods below, which check `auth` first
+    vm_manager: std::sync::Arc<VmManager>,
+    session_manager: SessionManager,
+    /// Checks `NetworkManage` itself, for `auth`
+    pub network_manager: NetworkManager,
+    pub storage_manager: StorageManager,
+    pub metrics_collector: std::sync::Arc<MetricsCollector>,
+    pub auth_provider: Box<dyn AuthProvider>,
+    pub plugin_manager: std::sync::Arc<tokio::sync::RwLock<PluginManager>>,
+    pub dev_env_manager: DevEnvironmentManager,
+    pub workspace_manager: WorkspaceManager,
+    /// Who operations are done for: the local user unless `[auth]`
+    /// configures tokens or single sign-on
+    pub auth: AuthContext,
+    /// The system, user and project layers of the config, as loaded when
+    /// the core started
+    config: VortexConfig,
+}
+
+impl VortexCore {
+    pub async fn new() -> Result<Self> {
+        Self::with_plugins(Vec::new()).await
+    }
+
+    /// Start with `plugins` registered ahead of the installed ones, so
+    /// out-of-tree code can add hooks and backends
+    pub async fn with_plugins(plugins: Vec<Box<dyn Plugin>>) -> Result<Self> {
+        // No defaults on error: those would sign everyone in as the local
+        // user even where the broken file configures tokens or single sign-on
+        let project_dir = std::env::current_dir().ok();
+        let config = VortexConfig::load_layered(project_dir.as_deref())?;
+        let mut plugin_manager = PluginManager::new()
+            .await?
+            .with_config(config.plugins.clone());
+        for plugin in plugins {
+            plugin_manager.register_plugin(plugin).await?;
+        }
+        #[cfg(feature = "wasm-plugins")]
+        wasm_plugin::register_installed(&mut plugin_manager, &config.plugins).await;
+        if let Some(dir) = plugin::plugins_dir() {
+            if let Err(e) = plugin_manager.load_from_dir(&dir).await {
+                tracing::warn!("Plugin libraries not loaded: {}", e);
+            }
+        }
+        plugin_manager.load_processes(&config.process_plugins).await;
+
+        let mut backends = vortex_backends::detect_backends().await;
+        plugin_manager.register_backends(&mut backends);
+        let mut dev_env_manager =
+            DevEnvironmentManager::with_plugin_templates(plugin_manager.templates());
+        if let Some(dir) = &project_dir {
+            dev_env_manager = dev_env_manager.with_project_templates(dir);
+        }
+        let plugin_manager = std::sync::Arc::new(tokio::sync::RwLock::new(plugin_manager));
+        let mut vm_manager =
+            VmManager::with_backends(backends).with_plugins(plugin_manager.clone());
+        match AuditLog::from_config(&config.audit) {
+            Ok(Some(audit)) => vm_manager = vm_manager.with_audit(audit),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Audit log disabled: {}", e),
+        }
+        let vm_manager = std::sync::Arc::new(vm_manager);
+        // These end on their own once the manager is dropped
+        vm_manager.watch_health();
+        vm_manager.watch_expiry();
+        let alerts = config.alerts.clone();
+        let metrics_collector =
+            std::sync::Arc::new(MetricsCollector::new().await?.with_alerts(alerts));
+        metrics_collector.watch(&vm_manager);
+        let event_queue = config.events.clone();
+        match events::EventLogHandler::new() {
+            Ok(handler) => {
+                vm_manager
+                    .add_event_handler(Box::new(handler), event_queue)
+                    .await
+            }
+            Err(e) => tracing::warn!("Event log disabled: {}", e),
+        }
+
+        let session_manager = SessionManager::new(vm_manager.clone()).await?;
+        // Single sign-on or configured tokens replace the development
+        // provider, and operations are then done for whoever signed in
+        let (auth_provider, auth): (Box<dyn AuthProvider>, AuthContext) = match &config.auth.oidc {
+            Some(oidc) => {
+                let provider = OidcAuthProvider::new(oidc.clone())?;
+                let token = match std::env::var(TOKEN_ENV) {
+                    Ok(token) => Some(token),
+                    Err(_) => provider
+                        .cached_tokens()
+                        .await
+                        .unwrap_or_else(|e| {
+                            tracing::warn!("Could not refresh the sign-in: {}", e);
+                            None
+                        })
+                        .map(|tokens| tokens.access_token),
+                };
+                let auth = caller(&provider, token).await;
+                (Box::new(provider), auth)
+            }
+            None => match TokenAuthProvider::from_config(&config.auth)? {
+                Some(provider) => {
+                    let auth = caller(&provider, std::env::var(TOKEN_ENV).ok()).await;
+                    (Box::new(provider), auth)
+                }
+                None => (Box::new(auth::NoOpAuthProvider), AuthContext::local()),
+            },
+        };
+        if let Some(audit) = vm_manager.audit_log() {
+            audit.set_user(&auth.user_id);
+        }
+
+        Ok(Self {
+            vm_manager,
+            session_manager,
+            network_manager: NetworkManager::new().await?.with_auth(auth.clone()),
+            storage_manager: StorageManager::new().await?.with_auth(auth.clone()),
+            metrics_collector,
+            auth_provider,
+            plugin_manager,
+            dev_env_manager,
+            workspace_manager: WorkspaceManager::new()?.with_auth(auth.clone()),
+            auth,
+            config,
+        })
+    }
+
+    /// Do operations for `auth` from now on, as a server does for each
+    /// client it serves
+    pub fn with_auth(mut self, auth: AuthContext) -> Self {
+        self.network_manager = self.network_manager.with_auth(auth.clone());
+        self.storage_manager = self.storage_manager.with_auth(auth.clone());
+        self.workspace_manager = self.workspace_manager.with_auth(auth.clone());
+        if let Some(audit) = self.vm_manager.audit_log() {
+            audit.set_user(&auth.user_id);
+        }
+        self.auth = auth;
+        self
+    }
+
+    /// The config the core started with
+    pub fn config(&self) -> &VortexConfig {
+        &self.config
+    }
+
+    /// Resolve an image alias in `spec` and move a Docker Hub image to the
+    /// configured default registry
+    fn resolve_image(&self, spec: &mut VmSpec) {
+        spec.image = self.config.resolve_image(&spec.image);
+    }
+
+    /// Add the user's configured dotfiles to a dev VM. Failures only warn so a
+    /// broken dotfiles repo never blocks getting a shell.
+    fn apply_dotfiles(&self, spec: &mut VmSpec) {
+        if let Some(dotfiles) = &self.config.dotfiles {
+            if let Err(e) = dotfiles.apply(spec) {
+                tracing::warn!("Skipping dotfiles: {}", e);
+            }
+        }
+    }
+
+    /// Create a new VM with full lifecycle management
+    pub async fn create_vm(&self, mut spec: VmSpec) -> Result<VmInstance> {
+        self.auth.require(Permission::VmCreate)?;
+        self.resolve_image(&mut spec);
+        self.vm_manager.create(spec).await
+    }
+
+    /// Attach to an interactive VM session
+    pub async fn attach_vm(&self, vm_id: &VmId) -> Result<()> {
+        self.auth.require(Permission::VmUpdate)?;
+        self.vm_manager.attach(vm_id).await
+    }
+
+    pub async fn list_vms(&self) -> Result<Vec<VmInstance>> {
+        self.auth.require(Permission::VmRead)?;
+        self.vm_manager.list().await
+    }
+
+    pub async fn get_vm(&self, vm_id: &VmId) -> Result<Option<VmInstance>> {
+        self.auth.require(Permission::VmRead)?;
+        self.vm_manager.get(vm_id).await
+    }
+
+    /// Stop a VM and remove it
+    pub async fn stop_vm(&self, vm_id: &VmId) -> Result<()> {
+        self.auth.require(Permission::VmDelete)?;
+        self.vm_manager.stop(vm_id).await?;
+        self.vm_manager.cleanup(vm_id).await
+    }
+
+    /// Remove a VM, stopping it first if needed
+    pub async fn cleanup_vm(&self, vm_id: &VmId) -> Result<()> {
+        self.auth.require(Permission::VmDelete)?;
+        self.vm_manager.cleanup(vm_id).await
+    }
+
+    pub async fn exec_vm(
+        &self,
+        vm_id: &VmId,
+        command: &[String],
+        options: &ExecOptions,
+    ) -> Result<ExecResult> {
+        self.auth.require(Permission::VmUpdate)?;
+        self.vm_manager.exec(vm_id, command, options).await
+    }
+
+    /// Copy a host file or directory into a VM. Like exec, this runs in the
+    /// guest.
+    pub async fn copy_to_vm(&self, vm_id: &VmId, host: &Path, guest: &str) -> Result<()> {
+        self.auth.require(Permission::VmUpdate)?;
+        self.vm_manager.copy_to(vm_id, host, guest).await
+    }
+
+    /// Copy a file or directory of a VM to the host. Like exec, this runs in
+    /// the guest.
+    pub async fn copy_from_vm(&self, vm_id: &VmId, guest: &str, host: &Path) -> Result<()> {
+        self.auth.require(Permission::VmUpdate)?;
+        self.vm_manager.copy_from(vm_id, guest, host).await
+    }
+
+    pub async fn resize_vm(
+        &self,
+        vm_id: &VmId,
+        memory: Option<u32>,
+        cpus: Option<u32>,
+    ) -> Result<()> {
+        self.auth.require(Permission::VmUpdate)?;
+        self.vm_manager.resize(vm_id, memory, cpus).await
+    }
+
+    pub async fn clone_vm(&self, vm_id: &VmId, overrides: Option<VmSpec>) -> Result<VmInstance> {
+        self.auth.require(Permission::VmCreate)?;
+        // Spelled out: method syntax would pick `Arc::clone`
+        VmManager::clone(&self.vm_manager, vm_id, overrides).await
+    }
+
+    pub async fn snapshot_vm(&self, vm_id: &VmId) -> Result<SnapshotId> {
+        self.auth.require(Permission::SnapshotCreate)?;
+        self.vm_manager.snapshot(vm_id).await
+    }
+
+    /// Start a new VM from a snapshot
+    pub async fn restore_snapshot(&self, snapshot_id: &SnapshotId) -> Result<VmInstance> {
+        self.auth.require(Permission::SnapshotRestore)?;
+        self.vm_manager.restore(snapshot_id).await
+    }
+
+    /// Save a running VM to a portable archive
+    pub async fn save_vm(&self, vm_id: &VmId, dest: &Path) -> Result<ArchiveManifest> {
+        self.auth.require(Permission::SnapshotCreate)?;
+        self.vm_manager.save(vm_id, dest).await
+    }
+
+    /// Start a new VM from an archive `save_vm` wrote
+    pub async fn load_vm(&self, src: &Path) -> Result<VmInstance> {
+        self.auth.require(Permission::SnapshotRestore)?;
+        self.vm_manager.load(src).await
+    }
+
+    /// Saved snapshots, oldest first, with the bytes each takes up
+    pub fn list_snapshots(&self) -> Result<Vec<(SnapshotRecord, u64)>> {
+        self.auth.require(Permission::VmRead)?;
+        let store = SnapshotStore::new()?;
+        Ok(store
+            .list()?
+            .into_iter()
+            .map(|record| {
+                let size = snapshot::dir_size(&store.state_dir(&record.id));
+                (record, size)
+            })
+            .collect())
+    }
+
+    pub fn get_snapshot(&self, snapshot_id: &SnapshotId) -> Result<SnapshotRecord> {
+        self.auth.require(Permission::VmRead)?;
+        SnapshotStore::new()?.load(snapshot_id)
+    }
+
+    /// Delete a saved snapshot, failing if there is none of that ID
+    pub fn delete_snapshot(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        self.auth.require(Permission::SnapshotCreate)?;
+        let store = SnapshotStore::new()?;
+        store.load(snapshot_id)?;
+        store.remove(snapshot_id)
+    }
+
+    /// Write a snapshot to a portable archive
+    pub fn export_snapshot(
+        &self,
+        snapshot_id: &SnapshotId,
+        dest: &Path,
+    ) -> Result<ArchiveManifest> {
+        self.auth.require(Permission::SnapshotCreate)?;
+        SnapshotStore::new()?.export(snapshot_id, dest)
+    }
+
+    /// Add the snapshot of an archive `export_snapshot` or `save_vm` wrote
+    pub fn load_snapshot_archive(&self, src: &Path) -> Result<SnapshotRecord> {
+        self.auth.require(Permission::SnapshotRestore)?;
+        SnapshotStore::new()?.load_archive(src)
+    }
+
+    /// Wait for the command of a VM to exit
+    pub async fn wait_vm(&self, vm_id: &VmId) -> Result<ExitStatus> {
+        self.auth.require(Permission::VmRead)?;
+        self.vm_manager.wait(vm_id).await
+    }
+
+    /// Wait for a VM to pass `probe`
+    pub async fn wait_ready(&self, vm_id: &VmId, probe: &Probe, timeout: Duration) -> Result<()> {
+        self.auth.require(Permission::VmRead)?;
+        self.vm_manager.wait_ready(vm_id, probe, timeout).await
+    }
+
+    pub async fn check_health(&self, vm_id: &VmId, probe: Option<&Probe>) -> Result<VmState> {
+        self.auth.require(Permission::VmRead)?;
+        self.vm_manager.check_health(vm_id, probe).await
+    }
+
+    /// Console output of a VM, followed until it stops with `follow`
+    pub async fn vm_logs(
+        &self,
+        vm_id: &VmId,
+        follow: bool,
+    ) -> Result<impl Stream<Item = Result<String>> + '_> {
+        self.auth.require(Permission::VmRead)?;
+        self.vm_manager.logs(vm_id, follow).await
+    }
+
+    /// What a VM used so far, see [`MetricsCollector::usage`]
+    pub async fn vm_usage(&self, vm_id: &VmId) -> Result<VmUsage> {
+        self.auth.require(Permission::MetricsRead)?;
+        self.metrics_collector.usage(&self.vm_manager, vm_id).await
+    }
+
+    /// Let queued events reach their handlers, for at most `timeout`
+    pub async fn flush_events(&self, timeout: Duration) {
+        self.vm_manager.flush_events(timeout).await
+    }
+
+    /// Create a new session with optional persistence and boot-start
+    pub async fn create_session(
+        &self,
+        mut spec: VmSpec,
+        name: Option<String>,
+        persistent: bool,
+        boot_start: bool,
+    ) -> Result<VmSession> {
+        self.auth.require(Permission::VmCreate)?;
+        self.resolve_image(&mut spec);
+        self.session_manager
+            .create_session(spec, name, persistent, boot_start)
+            .await
+    }
+
+    /// List all sessions
+    pub async fn list_sessions(&self) -> Result<Vec<VmSession>> {
+        self.auth.require(Permission::VmRead)?;
+        self.session_manager.list_sessions().await
+    }
+
+    /// Attach to a session by ID
+    pub async fn attach_session(&self, session_id: &SessionId) -> Result<()> {
+        self.auth.require(Permission::VmUpdate)?;
+        self.session_manager
+            .check_workspace_access(session_id, &self.auth.user_id)
+            .await?;
+        let client_pid = std::process::id();
+        self.session_manager
+            .attach_session(session_id, client_pid)
+            .await
+    }
+
+    /// Stop a session
+    pub async fn stop_session(&self, session_id: &SessionId) -> Result<()> {
+        self.auth.require(Permission::VmDelete)?;
+        self.session_manager
+            .check_workspace_access(session_id, &self.auth.user_id)
+            .await?;
+        self.session_manager.stop_session(session_id).await
+    }
+
+    /// Delete a session
+    pub async fn delete_session(&self, session_id: &SessionId) -> Result<()> {
+        self.auth.require(Permission::VmDelete)?;
+        self.session_manager
+            .check_workspace_access(session_id, &self.auth.user_id)
+            .await?;
+        self.session_manager.delete_session(session_id).await
+    }
+
+    /// Serve the sessions from a daemon, which checks the permissions of
+    /// each client itself
+    pub async fn into_daemon(self) -> Result<VortexDaemon> {
+        Ok(VortexDaemon::new(self.session_manager)
+            .await?
+            .with_auth_provider(std::sync::Arc::from(self.auth_provider)))
+    }
+
+    /// Create a development environment VM from a template
+    pub async fn create_dev_environment(
+        &self,
+        template_name: &str,
+        workdir: Option<String>,
+        volumes: std::collections::HashMap<std::path::PathBuf, std::path::PathBuf>,
+        overrides: DevOverrides,
+    ) -> Result<VmInstance> {
+        self.auth.require(Permission::VmCreate)?;
+        let mut spec = self
+            .dev_env_manager
+            .template_to_vm_spec(template_name, workdir)?;
+        overrides.apply(&mut spec);
+
+        // Add any additional volumes
+        for (host, guest) in volumes {
+            spec.volumes.insert(host, guest);
+        }
+        self.apply_dotfiles(&mut spec);
+        self.resolve_image(&mut spec);
+
+        self.vm_manager.create(spec).await
+    }
+
+    /// Create a VM from a workspace
+    pub async fn create_workspace_vm(&self, workspace_id: &str) -> Result<VmInstance> {
+        self.auth.require(Permission::VmCreate)?;
+        let workspace = self
+            .workspace_manager
+            .get_workspace(workspace_id)?
+            .ok_or_else(|| VortexError::InvalidInput {
+                field: "workspace_id".to_string(),
+                message: format!("Workspace '{}' not found", workspace_id),
+            })?;
+        self.workspace_manager.check_access(&workspace)?;
+
+        let template = self
+            .dev_env_manager
+            .get_template(&workspace.config.template)
+            .ok_or_else(|| VortexError::TemplateNotFound {
+                name: workspace.config.template.clone(),
+            })?;
+
+        let mut spec = self
+            .workspace_manager
+            .workspace_to_vm_spec(&workspace, template)?;
+        self.apply_dotfiles(&mut spec);
+        self.resolve_image(&mut spec);
+
+        // Update workspace last used time
+        self.workspace_manager.touch_workspace(workspace_id)?;
+
+        self.vm_manager.create(spec).await
+    }
+}
+
+/// Who `token` belongs to, or no one when it is missing or invalid
+async fn caller(provider: &dyn AuthProvider, token: Option<String>) -> AuthContext {
+    let Some(token) = token else {
+        return AuthContext::anonymous();
+    };
+    match AuthContext::authenticate(provider, &auth::AuthCredentials::Token { token }).await {
+        Ok(auth) => auth,
+        Err(e) => {
+            tracing::warn!("Not signed in: {}", e);
+            AuthContext::anonymous()
+        }
+    }
+}
+
@@ -120,7 +120,7 @@ echo -e "${BLUE}🔬 Core Tests${NC}"
 echo "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
 
 # Test vortex-core in its own directory
-run_test "Vortex Core Library" "cargo test --lib --release"
+run_test "Vortex Core Library" "cargo test --workspace --lib --release"
 
 # Test integration tests from main directory
 run_test "CLI Integration Tests" "cargo test --test cli_integration_test --release"
@@ -174,6 +174,6 @@ else
     echo
     echo "🔍 To debug failing tests, run them individually:"
     echo "   cargo test --test cli_integration_test --release"
-    echo "   cargo test --lib --release"
+    echo "   cargo test --workspace --lib --release"
     exit 1
 fi
\ No newline at end of file